@@ -0,0 +1,47 @@
+use anyhow::{bail, Context, Result};
+use bytes::BytesMut;
+use futures_util::SinkExt;
+use scheduler_core::{ClientRequest, ServerResponse};
+use std::net::SocketAddr;
+use tokio::net::TcpStream;
+use tokio_stream::StreamExt;
+use tokio_util::codec::{Framed, LengthDelimitedCodec};
+
+/// 與 scheduler-cli 相同的協定：長度前綴 + JSON
+pub struct Conn {
+    framed: Framed<TcpStream, LengthDelimitedCodec>,
+}
+
+impl Conn {
+    pub async fn connect(addr: SocketAddr) -> Result<Self> {
+        let stream = TcpStream::connect(addr)
+            .await
+            .with_context(|| format!("connect {addr}"))?;
+        stream.set_nodelay(true)?;
+        Ok(Self {
+            framed: Framed::new(stream, LengthDelimitedCodec::new()),
+        })
+    }
+
+    pub async fn send(&mut self, req: &ClientRequest) -> Result<()> {
+        let bytes = serde_json::to_vec(req)?;
+        self.framed.send(bytes.into()).await?;
+        Ok(())
+    }
+
+    pub async fn recv(&mut self) -> Result<ServerResponse> {
+        let frame: BytesMut = match self.framed.next().await {
+            Some(frame) => frame?,
+            None => bail!("server closed the connection"),
+        };
+        Ok(serde_json::from_slice(&frame)?)
+    }
+
+    pub async fn request(&mut self, req: &ClientRequest) -> Result<ServerResponse> {
+        self.send(req).await?;
+        match self.recv().await? {
+            ServerResponse::Error(e) => bail!("server error: {e}"),
+            resp => Ok(resp),
+        }
+    }
+}
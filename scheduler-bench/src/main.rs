@@ -0,0 +1,247 @@
+mod client;
+mod server;
+mod stats;
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, FixedOffset, Local, Timelike};
+use clap::Parser;
+use client::Conn;
+use scheduler_core::{
+    ClientRequest, EventKind, OutputEncoding, Priority, Schedule, ServerResponse, TaskSort,
+    TaskSpec, DEFAULT_NAMESPACE,
+};
+use serde::Serialize;
+use server::Server;
+use stats::{Latency, Throughput};
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+/// 以 mock 執行器啟動 scheduler-server，量測吞吐量、計時精準度與持久化延遲
+#[derive(Parser, Debug)]
+#[command(name = "scheduler-bench")]
+struct Opts {
+    /// scheduler-server 執行檔（預設與本程式位於同一目錄）
+    #[arg(long)]
+    server: Option<PathBuf>,
+
+    /// 在空伺服器上連續新增幾個任務（量測 add 吞吐量）
+    #[arg(long, default_value_t = 1000)]
+    adds: usize,
+
+    /// 新增完後連續列出幾次（量測 list 吞吐量）
+    #[arg(long, default_value_t = 100)]
+    lists: usize,
+
+    /// 預先載入的任務數（量測啟動、持久化與計時精準度）
+    #[arg(long, default_value_t = 100_000)]
+    tasks: usize,
+
+    /// 計時精準度的取樣數：在載入大量任務的伺服器上新增的一次性任務
+    #[arg(long, default_value_t = 100)]
+    timers: usize,
+
+    /// 取樣任務在新增後幾秒觸發
+    #[arg(long, default_value_t = 5)]
+    timer_delay: u64,
+
+    /// 以 JSON 輸出結果（供 CI 比較）
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Debug, Default, Serialize)]
+struct Report {
+    /// 空伺服器逐筆新增任務
+    add: Throughput,
+    /// 列出 `adds` 個任務
+    list: Throughput,
+    /// 載入 `tasks` 個任務到可連線的時間
+    startup_ms: f64,
+    /// 已有 `tasks` 個任務時新增一筆（每次新增都會改寫持久化檔）
+    persist: Latency,
+    /// 實際開始時間與預定時間的差
+    timer_lateness: Latency,
+    /// 逾時仍未觸發的取樣任務
+    timers_missed: usize,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let opts = Opts::parse();
+    let bin = match &opts.server {
+        Some(p) => p.clone(),
+        None => std::env::current_exe()?
+            .with_file_name(format!("scheduler-server{}", std::env::consts::EXE_SUFFIX)),
+    };
+    if !bin.exists() {
+        bail!(
+            "找不到 {}；請先 cargo build -p scheduler-server，或以 --server 指定",
+            bin.display()
+        );
+    }
+
+    let mut report = Report::default();
+    bench_add_list(&bin, &opts, &mut report).await?;
+    bench_loaded(&bin, &opts, &mut report).await?;
+
+    if opts.json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        println!("add（{} 筆）：{}", opts.adds, report.add);
+        println!("list（{} 個任務）：{}", opts.adds, report.list);
+        println!(
+            "啟動（載入 {} 個任務）：{:.0} ms",
+            opts.tasks, report.startup_ms
+        );
+        println!("持久化（{} 個任務時新增）：{}", opts.tasks, report.persist);
+        println!("計時誤差：{}", report.timer_lateness);
+        if report.timers_missed > 0 {
+            println!("⚠️ {} 個取樣任務逾時未觸發", report.timers_missed);
+        }
+    }
+    Ok(())
+}
+
+/// 空伺服器：逐筆新增，再重複列出
+async fn bench_add_list(bin: &std::path::Path, opts: &Opts, report: &mut Report) -> Result<()> {
+    let (server, _) = Server::start(bin, "add", &[]).await?;
+    let mut conn = Conn::connect(server.addr).await?;
+
+    let schedule = far_daily();
+    let mut samples = Vec::with_capacity(opts.adds);
+    let started = Instant::now();
+    for _ in 0..opts.adds {
+        let t = Instant::now();
+        expect_added(
+            conn.request(&ClientRequest::AddTask(spec(schedule.clone())))
+                .await?,
+        )?;
+        samples.push(t.elapsed());
+    }
+    report.add = Throughput::new(samples, started.elapsed());
+
+    let mut samples = Vec::with_capacity(opts.lists);
+    let started = Instant::now();
+    for _ in 0..opts.lists {
+        let t = Instant::now();
+        let req = ClientRequest::ListTasksSorted {
+            sort: TaskSort::NextRun,
+            descending: false,
+        };
+        match conn.request(&req).await? {
+            ServerResponse::Tasks(list) if list.len() >= opts.adds => {}
+            other => bail!("unexpected response to ListTasksSorted: {other:?}"),
+        }
+        samples.push(t.elapsed());
+    }
+    report.list = Throughput::new(samples, started.elapsed());
+
+    server.stop().await
+}
+
+/// 預先載入大量任務：量測啟動、持久化延遲與計時誤差
+async fn bench_loaded(bin: &std::path::Path, opts: &Opts, report: &mut Report) -> Result<()> {
+    let seed: Vec<TaskSpec> = (0..opts.tasks).map(|_| spec(far_daily())).collect();
+    let (server, startup) = Server::start(bin, "loaded", &seed).await?;
+    report.startup_ms = stats::ms(startup);
+
+    // 先訂閱，才不會漏掉 RunStarted
+    let mut events = Conn::connect(server.addr).await?;
+    events
+        .send(&ClientRequest::Subscribe { since: None })
+        .await?;
+
+    let mut conn = Conn::connect(server.addr).await?;
+    let mut due: HashMap<u64, DateTime<FixedOffset>> = HashMap::new();
+    let mut samples = Vec::with_capacity(opts.timers);
+    for _ in 0..opts.timers {
+        let at = Local::now().fixed_offset() + chrono::Duration::seconds(opts.timer_delay as i64);
+        let t = Instant::now();
+        let id = expect_added(
+            conn.request(&ClientRequest::AddTask(spec(Schedule::Once(at))))
+                .await?,
+        )?;
+        samples.push(t.elapsed());
+        due.insert(id, at);
+    }
+    report.persist = Latency::from_samples(samples);
+
+    let deadline = Instant::now() + Duration::from_secs(opts.timer_delay + 60);
+    let mut lateness = Vec::with_capacity(opts.timers);
+    while !due.is_empty() {
+        let left = deadline.saturating_duration_since(Instant::now());
+        let resp = match tokio::time::timeout(left, events.recv()).await {
+            Ok(resp) => resp.context("event stream")?,
+            Err(_) => break,
+        };
+        if let ServerResponse::Event(ev) = resp {
+            if let EventKind::RunStarted { task_id, .. } = ev.kind {
+                if let Some(at) = due.remove(&task_id) {
+                    lateness.push((ev.at - at).to_std().unwrap_or_default());
+                }
+            }
+        }
+    }
+    report.timers_missed = due.len();
+    report.timer_lateness = Latency::from_samples(lateness);
+
+    server.stop().await
+}
+
+fn expect_added(resp: ServerResponse) -> Result<u64> {
+    match resp {
+        ServerResponse::Added { id, .. } => Ok(id),
+        other => bail!("unexpected response to AddTask: {other:?}"),
+    }
+}
+
+/// 十二小時後的每日任務：量測期間不會觸發
+fn far_daily() -> Schedule {
+    let t = Local::now() + chrono::Duration::hours(12);
+    Schedule::Daily {
+        hour: t.hour(),
+        minute: t.minute(),
+    }
+}
+
+fn spec(schedule: Schedule) -> TaskSpec {
+    TaskSpec {
+        name: None,
+        tags: Vec::new(),
+        namespace: DEFAULT_NAMESPACE.to_string(),
+        owner: None,
+        metric_labels: Default::default(),
+        cmd: "true".to_string(),
+        args: Vec::new(),
+        env: Default::default(),
+        clean_env: false,
+        skip_cmd_check: false,
+        outputs: None,
+        output_path: PathBuf::from("out.log"),
+        append: true,
+        output_perms: None,
+        schedule,
+        timeout_secs: None,
+        stop_signal: None,
+        stop_grace_secs: None,
+        sched: Default::default(),
+        sandbox: None,
+        locks: Vec::new(),
+        throttle: None,
+        triggers: Vec::new(),
+        healthcheck: None,
+        allowed_window: None,
+        approval: None,
+        breaker: None,
+        priority: Priority::Normal,
+        success: None,
+        output_encoding: OutputEncoding::Lossy,
+        cleanup: Vec::new(),
+        exact_start: false,
+        protected: false,
+        source: None,
+    }
+}
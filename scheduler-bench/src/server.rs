@@ -0,0 +1,109 @@
+use crate::client::Conn;
+use anyhow::{bail, Context, Result};
+use scheduler_core::TaskSpec;
+use std::{
+    net::{SocketAddr, TcpListener},
+    path::{Path, PathBuf},
+    process::Stdio,
+    time::{Duration, Instant},
+};
+use tokio::process::{Child, Command};
+
+/// 等待伺服器開始接受連線的上限（載入大量任務時可能較久）
+const READY_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// 在暫存目錄中以模擬執行器啟動的 scheduler-server
+pub struct Server {
+    child: Child,
+    pub addr: SocketAddr,
+    dir: PathBuf,
+}
+
+impl Server {
+    /// 建立暫存目錄（可預先寫入任務），啟動伺服器並等到可連線；
+    /// 回傳從啟動到可連線所花的時間
+    pub async fn start(bin: &Path, name: &str, seed: &[TaskSpec]) -> Result<(Self, Duration)> {
+        let dir =
+            std::env::temp_dir().join(format!("scheduler-bench-{}-{name}", std::process::id()));
+        if dir.exists() {
+            std::fs::remove_dir_all(&dir)?;
+        }
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(
+            dir.join("config.toml"),
+            concat!(
+                "data_path = \"tasks.json\"\n",
+                "[mock]\nenabled = true\n",
+                "[history]\npath = \"history.jsonl\"\n",
+                "[events]\npersist = false\n",
+                "[notify]\noutbox_path = \"outbox.json\"\n",
+            ),
+        )?;
+        if !seed.is_empty() {
+            write_seed(&dir.join("tasks.json"), seed)?;
+        }
+
+        let addr = free_addr()?;
+        let started = Instant::now();
+        let child = Command::new(bin)
+            .arg("--config")
+            .arg("config.toml")
+            .arg("--bind")
+            .arg(addr.to_string())
+            .current_dir(&dir)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(std::fs::File::create(dir.join("server.log"))?)
+            .kill_on_drop(true)
+            .spawn()
+            .with_context(|| format!("spawn {}", bin.display()))?;
+        let mut server = Self { child, addr, dir };
+
+        loop {
+            if Conn::connect(addr).await.is_ok() {
+                return Ok((server, started.elapsed()));
+            }
+            if let Some(status) = server.child.try_wait()? {
+                bail!(
+                    "server exited early ({status}); see {}",
+                    server.dir.join("server.log").display()
+                );
+            }
+            if started.elapsed() > READY_TIMEOUT {
+                bail!("server did not accept connections within {READY_TIMEOUT:?}");
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    }
+
+    pub async fn stop(mut self) -> Result<()> {
+        let _ = self.child.kill().await;
+        std::fs::remove_dir_all(&self.dir)?;
+        Ok(())
+    }
+}
+
+/// 與伺服器持久化檔相同的格式
+fn write_seed(path: &Path, specs: &[TaskSpec]) -> Result<()> {
+    #[derive(serde::Serialize)]
+    struct Rec<'a> {
+        id: u64,
+        spec: &'a TaskSpec,
+    }
+    let list: Vec<Rec> = specs
+        .iter()
+        .enumerate()
+        .map(|(i, spec)| Rec {
+            id: i as u64 + 1,
+            spec,
+        })
+        .collect();
+    std::fs::write(path, serde_json::to_vec(&list)?)?;
+    Ok(())
+}
+
+/// 由系統配發一個目前沒人用的埠
+fn free_addr() -> Result<SocketAddr> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    Ok(listener.local_addr()?)
+}
@@ -0,0 +1,68 @@
+use serde::Serialize;
+use std::time::Duration;
+
+/// 延遲分布（毫秒）
+#[derive(Debug, Default, Serialize)]
+pub struct Latency {
+    pub samples: usize,
+    pub p50_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+}
+
+impl Latency {
+    pub fn from_samples(mut samples: Vec<Duration>) -> Self {
+        if samples.is_empty() {
+            return Self::default();
+        }
+        samples.sort();
+        let pick = |q: f64| {
+            let i = ((samples.len() - 1) as f64 * q).round() as usize;
+            ms(samples[i])
+        };
+        Self {
+            samples: samples.len(),
+            p50_ms: pick(0.50),
+            p99_ms: pick(0.99),
+            max_ms: ms(*samples.last().unwrap()),
+        }
+    }
+}
+
+impl std::fmt::Display for Latency {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "p50 {:.2} ms / p99 {:.2} ms / max {:.2} ms（{} 筆）",
+            self.p50_ms, self.p99_ms, self.max_ms, self.samples
+        )
+    }
+}
+
+/// 吞吐量與單次延遲
+#[derive(Debug, Default, Serialize)]
+pub struct Throughput {
+    pub ops: usize,
+    pub per_sec: f64,
+    pub latency: Latency,
+}
+
+impl Throughput {
+    pub fn new(samples: Vec<Duration>, total: Duration) -> Self {
+        Self {
+            ops: samples.len(),
+            per_sec: samples.len() as f64 / total.as_secs_f64().max(f64::EPSILON),
+            latency: Latency::from_samples(samples),
+        }
+    }
+}
+
+impl std::fmt::Display for Throughput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:.0} ops/s，{}", self.per_sec, self.latency)
+    }
+}
+
+pub fn ms(d: Duration) -> f64 {
+    d.as_secs_f64() * 1000.0
+}
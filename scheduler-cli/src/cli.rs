@@ -0,0 +1,690 @@
+use crate::{schema::SchemaType, tz::DisplayTz};
+use clap::{Parser, Subcommand};
+use clap_complete::Shell;
+use scheduler_core::{OutputEncoding, Priority, RunStatus, StopSignal, TaskSort, WindowPolicy};
+use std::path::PathBuf;
+
+#[derive(Parser, Debug)]
+#[command(
+    name = "scheduler-cli",
+    after_help = "結束碼：0 成功、1 其他錯誤、2 找不到、3 伺服器錯誤、4 連線錯誤、64 用法錯誤"
+)]
+pub struct Opts {
+    /// 連線到 scheduler-server 的位址（預設取自 profile，否則 127.0.0.1:7878）
+    #[arg(long)]
+    pub connect: Option<String>,
+
+    /// 以 mDNS 在區網尋找伺服器並連線；找到多台時請改用 --connect 指定
+    #[arg(long, conflicts_with = "connect")]
+    pub discover: bool,
+
+    /// 使用 ~/.config/scheduler/config.toml 中的哪個 profile
+    #[arg(long, env = "SCHEDULER_PROFILE")]
+    pub profile: Option<String>,
+
+    /// 建立連線的逾時秒數（預設 5）
+    #[arg(long)]
+    pub connect_timeout: Option<u64>,
+
+    /// 等待伺服器回應的逾時秒數（預設 30）
+    #[arg(long)]
+    pub request_timeout: Option<u64>,
+
+    /// 連線失敗時的重試次數，間隔以指數退避（預設 3）
+    #[arg(long)]
+    pub retries: Option<u32>,
+
+    /// 操作伺服器上的哪個環境（預設取自 profile，否則伺服器的預設環境）
+    #[arg(long = "env", env = "SCHEDULER_ENV")]
+    pub environment: Option<String>,
+
+    /// 環境的 token（預設取自 profile）；未指定 --env 時用於預設環境
+    #[arg(long, env = "SCHEDULER_ENV_TOKEN", hide_env_values = true)]
+    pub env_token: Option<String>,
+
+    /// 顯示時間用的時區：local、utc 或 IANA 名稱如 Asia/Taipei（預設取自 profile，否則本機時區）
+    #[arg(long, global = true, env = "SCHEDULER_TZ")]
+    pub tz: Option<DisplayTz>,
+
+    /// 印出完整時間，而不是「3 minutes ago」「in 2h 13m」這類相對說法
+    #[arg(long, global = true)]
+    pub raw_times: bool,
+
+    /// stdout 只輸出伺服器回應的 JSON，其餘訊息走 stderr
+    #[arg(long, global = true)]
+    pub json: bool,
+
+    /// 伺服器連不上時，把 add / remove 請求暫存起來，之後用 flush 重送
+    #[arg(long)]
+    pub spool: bool,
+
+    /// 暫存目錄（預設 ~/.local/state/scheduler/spool）
+    #[arg(long, env = "SCHEDULER_SPOOL_DIR")]
+    pub spool_dir: Option<PathBuf>,
+
+    /// 子命令
+    #[command(subcommand)]
+    pub cmd: Cmd,
+}
+
+#[derive(Subcommand, Debug)]
+#[allow(clippy::large_enum_variant)] // 只在啟動時解析一次，大小無所謂
+pub enum Cmd {
+    /// 新增任務
+    Add {
+        /// 顯示用名稱
+        #[arg(long)]
+        name: Option<String>,
+        /// 標籤（可重複）
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+        /// 命名空間（配額依此計算）
+        #[arg(long, default_value = scheduler_core::DEFAULT_NAMESPACE)]
+        namespace: String,
+        /// 負責人或值班信箱（顯示在列表與失敗通知中）
+        #[arg(long)]
+        owner_email: Option<String>,
+        /// 負責的團隊
+        #[arg(long)]
+        owner_team: Option<String>,
+        /// 指標標籤 KEY=VALUE（可重複），例如 service=billing；附加在此任務的 Prometheus 指標上
+        #[arg(long = "label")]
+        labels: Vec<String>,
+        /// 從檔案讀取完整的任務規格（.json 或 .yaml；`-` 為標準輸入），
+        /// 可使用還沒有對應旗標的欄位；此時其餘旗標不適用
+        #[arg(short = 'f', long = "file", conflicts_with_all = ["cmd", "output"])]
+        file: Option<PathBuf>,
+        #[arg(long, required_unless_present = "file")]
+        cmd: Option<String>,
+        /// 參數；依賴任務可用 {{upstream.<key>}} 引用前置任務的輸出變數
+        #[arg(long, num_args = 0.., value_delimiter = ' ')]
+        args: Vec<String>,
+        /// 環境變數 KEY=VALUE（可重複）；值可用 {{upstream.<key>}}
+        #[arg(long = "env")]
+        env: Vec<String>,
+        /// 不繼承伺服器的環境變數，只給 PATH、HOME、TZ 與 --env 宣告的
+        #[arg(long)]
+        clean_env: bool,
+        /// 不檢查 --cmd 是否在伺服器的 PATH 中（命令之後才會安裝等情況）
+        #[arg(long)]
+        skip_cmd_check: bool,
+        /// 以 stdout 最後一行的 key=value 作為輸出變數
+        #[arg(long, conflicts_with = "outputs_file")]
+        outputs_last_line: bool,
+        /// 從這個檔案讀取輸出變數（每行 key=value；路徑也會放在 $SCHEDULER_OUTPUTS）
+        #[arg(long)]
+        outputs_file: Option<PathBuf>,
+        #[arg(long, required_unless_present = "file")]
+        output: Option<PathBuf>,
+        #[arg(long, default_value_t = true)]
+        append: bool,
+        /// 輸出檔的權限（八進位，例如 0640）；自動建立的目錄另加上對應的 x
+        #[arg(long)]
+        output_mode: Option<String>,
+        /// 輸出檔的擁有者（使用者名稱或 uid）
+        #[arg(long)]
+        output_user: Option<String>,
+        /// 輸出檔的群組（群組名稱或 gid）
+        #[arg(long)]
+        output_group: Option<String>,
+        #[arg(long)]
+        once: Option<String>, // RFC3339
+        #[arg(long)]
+        daily: Option<String>, // "HH:MM"
+        #[arg(long)]
+        after: Option<u64>,
+        #[arg(long, default_value_t = 0)]
+        delay: u64,
+        /// 前置任務開始後超過這麼多秒仍未完成，本輪略過（需搭配 --after）
+        #[arg(long, requires = "after")]
+        expires: Option<u64>,
+        /// 不自動排程，只由 triggers（--mqtt、--bridge）啟動
+        #[arg(long)]
+        manual: bool,
+        /// 以文字寫排程：@reboot、@hourly、@daily、daily HH:MM、hourly :MM、
+        /// once <RFC3339>、after <id> [+<秒>s]、manual、named <名稱>
+        #[arg(long)]
+        schedule: Option<String>,
+        /// 單次執行逾時秒數
+        #[arg(long)]
+        timeout: Option<u64>,
+        /// 逾時或取消時先送的訊號：term（預設）、int、hup、quit、usr1、usr2、kill
+        #[arg(long)]
+        stop_signal: Option<StopSignal>,
+        /// 送出停止訊號後等幾秒仍未結束才 SIGKILL（預設 5）
+        #[arg(long)]
+        stop_grace: Option<u64>,
+        /// nice 值（-20 ~ 19）
+        #[arg(long, allow_hyphen_values = true)]
+        nice: Option<i32>,
+        /// ionice 類別："idle"、"be:<0-7>"、"rt:<0-7>"
+        #[arg(long)]
+        ionice: Option<String>,
+        /// CPU 親和性，例如 "0,2-3"
+        #[arg(long)]
+        cpus: Option<String>,
+        /// 在沙箱內執行（檔案系統唯讀、無網路）
+        #[arg(long)]
+        sandbox: bool,
+        /// 沙箱內可寫的路徑（可重複）
+        #[arg(long = "sandbox-rw", requires = "sandbox")]
+        sandbox_rw: Vec<PathBuf>,
+        /// 沙箱內允許網路
+        #[arg(long = "sandbox-net", requires = "sandbox")]
+        sandbox_net: bool,
+        /// 具名互斥鎖（可重複）：共用同一把鎖的任務不會同時執行
+        #[arg(long = "lock")]
+        locks: Vec<String>,
+        /// 節流：兩次啟動至少間隔幾秒（多餘的觸發會被略過）
+        #[arg(long)]
+        min_interval: Option<u64>,
+        /// 節流：任意一小時內最多啟動幾次
+        #[arg(long)]
+        max_per_hour: Option<u32>,
+        /// 收到符合此 MQTT topic（可用 + / #）的訊息時執行（可重複）
+        #[arg(long = "mqtt")]
+        mqtt: Vec<String>,
+        /// 伺服器 [bridge] 上的 Redis channel / NATS subject 收到訊息時執行（可重複）
+        #[arg(long = "bridge")]
+        bridge: Vec<String>,
+        /// healthchecks.io 式 ping 網址：開始打 <url>/start，成功打 <url>，失敗打 <url>/fail
+        #[arg(long)]
+        ping: Option<String>,
+        /// 開始時的 ping 網址（覆寫 --ping 的推導）
+        #[arg(long)]
+        ping_start: Option<String>,
+        /// 成功時的 ping 網址
+        #[arg(long)]
+        ping_success: Option<String>,
+        /// 失敗時的 ping 網址
+        #[arg(long)]
+        ping_failure: Option<String>,
+        /// 只在每日此時段內開始執行，例如 22:00-06:00（可跨午夜）
+        #[arg(long)]
+        window: Option<String>,
+        /// 時段外的觸發：defer 延到時段開始、skip 略過
+        #[arg(long, requires = "window", default_value = "defer")]
+        outside_window: WindowPolicy,
+        /// 每次執行前先等人工核准（scheduler-cli approve --run <id>）
+        #[arg(long)]
+        approval: bool,
+        /// 幾秒內未核准就略過這一輪（未指定則一直等）
+        #[arg(long, requires = "approval")]
+        approval_timeout: Option<u64>,
+        /// 連續失敗幾次後跳脫斷路器，暫停自動執行
+        #[arg(long)]
+        breaker: Option<u32>,
+        /// 斷路器跳脫後每隔幾秒放行一次試跑（未指定則等 reset-breaker）
+        #[arg(long, requires = "breaker")]
+        breaker_cooldown: Option<u64>,
+        /// 0 以外也算成功的結束碼（可重複或以逗號分隔），例如 rsync 的 24
+        #[arg(long, value_delimiter = ',')]
+        success_exit: Vec<i32>,
+        /// stdout 須符合此正規表示式才算成功
+        #[arg(long)]
+        success_match: Option<String>,
+        /// stdout 符合此正規表示式即算失敗（即使結束碼為 0）
+        #[arg(long)]
+        failure_match: Option<String>,
+        /// 輸出不是 UTF-8 時：lossy（取代）、utf8（視為不符合條件）、raw（以位元組比對）
+        #[arg(long, default_value = "lossy")]
+        output_encoding: OutputEncoding,
+        /// 每次執行後刪除這個目錄（絕對路徑，可重複）；
+        /// 須在伺服器 [cleanup] roots 或任務沙箱可寫的目錄底下
+        #[arg(long = "cleanup-dir")]
+        cleanup_dirs: Vec<PathBuf>,
+        /// 每次執行後刪除符合的檔案，例如 "/var/tmp/export/*.part"（可重複）
+        #[arg(long = "cleanup-files")]
+        cleanup_files: Vec<String>,
+        /// --cleanup-files 只刪最後修改超過幾天的
+        #[arg(long, requires = "cleanup_files")]
+        cleanup_older_than: Option<u64>,
+        /// 優先順序：low、normal、high；伺服器負載過高時可能略過低優先的 Daily 任務
+        #[arg(long, default_value = "normal")]
+        priority: Priority,
+        /// 不參與伺服器的錯開啟動，準時執行
+        #[arg(long)]
+        exact_start: bool,
+        /// 受保護：之後移除、永久刪除或回溯版本都要加 --force
+        #[arg(long)]
+        protected: bool,
+    },
+
+    /// 移除任務（伺服器有設定保留期時先進回收桶，可用 restore 還原）。
+    /// 重複排程、被依賴、一次多個或永久刪除時會先列出影響並要求確認
+    Remove {
+        /// 可重複或以逗號分隔一次移除多個
+        #[arg(long = "id", required = true, value_delimiter = ',')]
+        ids: Vec<u64>,
+        /// 永久刪除，不經過回收桶（也可刪除回收桶中的任務）
+        #[arg(long)]
+        purge: bool,
+        /// 不詢問直接執行（非互動執行時必須）
+        #[arg(long, short = 'y')]
+        yes: bool,
+        /// 連受保護的任務也移除；同時不再詢問
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// 列出回收桶中的任務
+    Trash,
+
+    /// 從回收桶還原任務（沿用原本的 id）
+    Restore {
+        #[arg(long)]
+        id: u64,
+    },
+
+    /// 列出所有任務
+    List {
+        /// 排序欄位：id、name、next-run、last-run、last-status
+        #[arg(long, default_value = "id")]
+        sort: TaskSort,
+        /// 反向排序
+        #[arg(long)]
+        desc: bool,
+        /// 顯示完整欄位，不依終端機寬度截斷
+        #[arg(long)]
+        wide: bool,
+        /// 只列出這個版本之後有變更的任務（版本為上次輸出的 version）
+        #[arg(long, conflicts_with_all = ["sort", "desc"])]
+        since_version: Option<u64>,
+    },
+
+    /// 依名稱、命令、參數、標籤、輸出路徑搜尋任務
+    Search {
+        /// 搜尋字串（預設不分大小寫的子字串）
+        pattern: String,
+        /// 把 pattern 當成正規表示式
+        #[arg(long)]
+        regex: bool,
+        /// 顯示完整欄位，不依終端機寬度截斷
+        #[arg(long)]
+        wide: bool,
+    },
+
+    /// 顯示任務最近一次保存的輸出
+    Output {
+        #[arg(long)]
+        id: u64,
+    },
+
+    /// 顯示任務的執行歷史（新到舊）
+    History {
+        #[arg(long)]
+        id: u64,
+        /// 最多顯示幾筆
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+    },
+
+    /// 跨任務查詢某段時間內的執行，例如 runs --since yesterday --status failed
+    Runs {
+        /// 起點（含）：today、yesterday、12h／2d 前、2024-06-01、"2024-06-01 22:00" 或 RFC 3339
+        #[arg(long)]
+        since: Option<String>,
+        /// 終點（不含），格式同 --since
+        #[arg(long)]
+        until: Option<String>,
+        /// 只看這個狀態：succeeded、failed、skipped
+        #[arg(long)]
+        status: Option<RunStatus>,
+        /// 明細最多幾筆（彙總不受影響）
+        #[arg(long, default_value_t = 50)]
+        limit: usize,
+    },
+
+    /// 顯示任務規格的修訂紀錄（誰、何時、改了什麼）
+    Revisions {
+        #[arg(long)]
+        id: u64,
+    },
+
+    /// 把任務規格回滾到指定修訂（會產生一個新修訂）
+    Rollback {
+        #[arg(long)]
+        id: u64,
+        /// 要回到的修訂編號（見 revisions）
+        #[arg(long)]
+        revision: u32,
+        /// 任務受保護時仍然回溯
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// 立即依伺服器的保留政策清理歷史
+    Prune,
+
+    /// 以歷史中記錄的 SHA-256 驗證 artifacts 與輸出檔（偵測竄改或截斷）
+    Verify {
+        /// 只驗證此任務；未指定則驗證全部
+        #[arg(long)]
+        id: Option<u64>,
+    },
+
+    /// 立即執行一次任務（不影響原本的排程）
+    RunNow {
+        #[arg(long)]
+        id: u64,
+        /// 即時顯示命令的 stdout / stderr，直到執行結束；結束碼非 0 時以 7 結束
+        #[arg(long)]
+        follow: bool,
+        /// 記在這次 run 上的追查資訊 KEY=VALUE（可重複），例如 ticket=OPS-12 commit=3f2c1d0；
+        /// 會出現在 history 與事件中
+        #[arg(long = "meta")]
+        meta: Vec<String>,
+    },
+
+    /// 重設任務的斷路器，恢復自動執行
+    ResetBreaker {
+        #[arg(long)]
+        id: u64,
+    },
+
+    /// 依序重送以 --spool 暫存的請求
+    Flush,
+
+    /// 持續顯示伺服器事件；斷線會自動重連並補回錯過的事件
+    Watch {
+        /// 從這個事件編號之後開始（預設只看新事件）
+        #[arg(long)]
+        since: Option<u64>,
+    },
+
+    /// 檢視或重送 webhook 通知
+    Outbox {
+        #[command(subcommand)]
+        action: OutboxCmd,
+    },
+
+    /// 顯示已觸發、排隊等待開始的 run 數量與丟棄統計
+    Queue,
+
+    /// 顯示伺服器版本、啟動時間，以及啟動時的自我檢查（載入的任務、無效的任務、接下來的執行）
+    Info,
+
+    /// 顯示伺服器內部的依賴表與不一致之處（依賴鏈沒有觸發時排查用）
+    Deps,
+
+    /// 檢視或刪除載入時被隔離的持久化紀錄（修正後以 add -f 重新新增）
+    Quarantine {
+        #[command(subcommand)]
+        action: Option<QuarantineCmd>,
+    },
+
+    /// 預約維護：暫停任務一段時間或在指定時間移除，到時由伺服器執行
+    Maintenance {
+        #[command(subcommand)]
+        action: Option<MaintenanceCmd>,
+    },
+
+    /// 全域凍結一段時間（例如部署期間）：定時觸發一律略過，時間到自動恢復，
+    /// 例如 freeze --duration 30m；狀態見 info
+    Freeze {
+        /// 凍結多久：30s、30m、2h、1d
+        #[arg(long)]
+        duration: String,
+        /// 外部觸發、依賴觸發與手動執行也一併略過
+        #[arg(long)]
+        all: bool,
+        /// 凍結的原因（記在略過的紀錄中）
+        #[arg(long)]
+        reason: Option<String>,
+    },
+
+    /// 提前解除凍結
+    Unfreeze,
+
+    /// 觸發網址：讓 CI 等外部系統以一個 HTTP POST 觸發指定任務（伺服器要開啟 [trigger_hooks]）
+    Hook {
+        #[command(subcommand)]
+        action: Option<HookCmd>,
+    },
+
+    /// 故障注入（伺服器要開啟 [chaos]）：讓任務的執行延遲或失敗，演練重試、通知與依賴鏈
+    Chaos {
+        #[command(subcommand)]
+        action: Option<ChaosCmd>,
+    },
+
+    /// 取消任務在依賴鏈中排定、尚未開始的延遲執行
+    CancelChained {
+        #[arg(long)]
+        id: u64,
+    },
+
+    /// 重跑某一輪依賴鏈中失敗或被略過的任務（沿用當時前置任務的輸出），
+    /// 成功的分支不會再執行
+    RetryChain {
+        /// 這一輪的任一筆 run（通常是鏈頭）
+        #[arg(long = "run")]
+        run_id: u64,
+    },
+
+    /// 列出等待人工核准的 run
+    Approvals,
+
+    /// 核准等待中的 run，讓流程繼續
+    Approve {
+        #[arg(long = "run")]
+        run_id: u64,
+        /// 駁回：本輪略過，後續依賴不執行
+        #[arg(long)]
+        reject: bool,
+    },
+
+    /// 檢視或修改伺服器層級的模板變數（{{server.*}}、{{vars.*}}）
+    Vars {
+        #[command(subcommand)]
+        action: Option<VarsCmd>,
+    },
+
+    /// 檢視或修改伺服器上的具名排程（任務以 --schedule "named <名稱>" 引用）
+    Schedules {
+        #[command(subcommand)]
+        action: Option<SchedulesCmd>,
+    },
+
+    /// 預覽排程接下來的觸發時間（不需連線）；依本機時區計算，以 --tz 或 profile 的時區顯示
+    Preview {
+        /// 排程運算式，例如 "daily 08:00"、"hourly :30"、"@daily"
+        schedule: String,
+        /// 列出幾次
+        #[arg(long, short = 'n', default_value_t = 5)]
+        count: usize,
+    },
+
+    /// 靜態檢查任務清單（不需連線）：共用鎖的排程重疊、After 的前置任務、
+    /// 過長的延遲、輸出檔衝突；--json 輸出供 CI 使用
+    Lint {
+        /// 任務清單：任務陣列、含 tasks 陣列的 git-sync 清單或單一任務規格（YAML/JSON/TOML）
+        #[arg(long, short = 'f')]
+        file: PathBuf,
+        /// 有警告也以失敗結束
+        #[arg(long)]
+        deny_warnings: bool,
+    },
+
+    /// 逐欄列出伺服器上由 git-sync 管理的任務與清單的差異（套用時會新增、更新、移除什麼），
+    /// 不做任何變更
+    Diff {
+        /// git-sync 清單：tasks 陣列中每個任務帶 key（YAML/JSON/TOML）
+        #[arg(long, short = 'f')]
+        file: PathBuf,
+        /// 有差異時以結束碼 9 結束（同 git diff --exit-code）
+        #[arg(long)]
+        exit_code: bool,
+    },
+
+    /// 以 mDNS 列出區網上的伺服器（不需連線）
+    Discover {
+        /// 等待回應的秒數
+        #[arg(long, default_value_t = 3)]
+        timeout: u64,
+    },
+
+    /// 輸出 shell 補全腳本（不需連線）
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+
+    /// 輸出任務規格與協定訊息的 JSON Schema（不需連線）
+    Schema {
+        /// 只輸出這個型別；未指定則輸出以型別為鍵、包含全部型別的物件
+        #[arg(long = "type", value_enum)]
+        ty: Option<SchemaType>,
+    },
+
+    /// 輸出 man page（不需連線）；指定 --dir 時為每個子命令各產生一頁
+    Man {
+        #[arg(long)]
+        dir: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum OutboxCmd {
+    /// 列出尚未送達的通知
+    List {
+        /// 只列出已放棄重試的通知
+        #[arg(long)]
+        dead: bool,
+    },
+    /// 重設重試次數並立即重送；未指定 --id 時重送所有已放棄的通知
+    Requeue {
+        #[arg(long = "id")]
+        ids: Vec<u64>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum VarsCmd {
+    /// 顯示目前的變數（預設動作）
+    Show,
+    /// 設定變數；伺服器須設定權杖（--env-token），修改會存檔，重啟後仍有效
+    Set {
+        /// KEY=VALUE（可多個）
+        pairs: Vec<String>,
+        /// 同時修改 {{server.environment}}
+        #[arg(long)]
+        environment: Option<String>,
+    },
+    /// 刪除變數
+    Unset {
+        #[arg(required = true)]
+        keys: Vec<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum QuarantineCmd {
+    /// 列出被隔離的紀錄與原因，含原始 JSON（預設動作）
+    List,
+    /// 修復後刪除一筆紀錄
+    Discard {
+        /// 隔離編號（list 中的 seq）
+        #[arg(long)]
+        seq: u64,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum MaintenanceCmd {
+    /// 列出尚未結束的預約（預設動作）
+    List,
+    /// 預約暫停，例如 pause --id 7 --from "2024-07-05 18:00" --until "2024-07-08 08:00"
+    Pause {
+        #[arg(long)]
+        id: u64,
+        /// 開始時間："2024-07-05 18:00"、2024-07-05 或 RFC 3339；預設為現在
+        #[arg(long)]
+        from: Option<String>,
+        /// 恢復時間，格式同 --from
+        #[arg(long)]
+        until: String,
+    },
+    /// 預約移除，例如 remove --id 12 --at 2024-07-01
+    Remove {
+        #[arg(long)]
+        id: u64,
+        /// 移除時間，格式同 pause --from
+        #[arg(long)]
+        at: String,
+        /// 受保護的任務也移除
+        #[arg(long)]
+        force: bool,
+    },
+    /// 取消一筆預約；取消進行中的暫停即提前恢復
+    Cancel {
+        /// 預約編號（list 中的 seq）
+        #[arg(long)]
+        seq: u64,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum HookCmd {
+    /// 列出觸發網址與最後使用時間（預設動作）
+    List,
+    /// 建立觸發網址，例如 create --id 7 --param GIT_SHA；token 只顯示這一次
+    Create {
+        #[arg(long)]
+        id: u64,
+        /// 說明用途，例如 "github actions"
+        #[arg(long)]
+        label: Option<String>,
+        /// 呼叫時可帶的參數（以環境變數交給該次執行；可重複）
+        #[arg(long = "param")]
+        params: Vec<String>,
+    },
+    /// 撤銷觸發網址
+    Revoke {
+        /// 觸發網址編號（list 中的 hook）
+        #[arg(long)]
+        id: u64,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ChaosCmd {
+    /// 列出目前的規則與已注入次數（預設動作）
+    List,
+    /// 設定任務的規則（取代原有的），例如 set --id 7 --exit-code 1 --count 2
+    Set {
+        #[arg(long)]
+        id: u64,
+        /// 每次執行被注入的機率（0–1）
+        #[arg(long, default_value_t = 1.0)]
+        probability: f64,
+        /// 最多注入幾次，用完自動清除；不指定則不限
+        #[arg(long)]
+        count: Option<u32>,
+        /// 開始執行前先等待這麼久（毫秒）
+        #[arg(long, default_value_t = 0)]
+        delay_ms: u64,
+        /// 不實際執行命令，直接以這個結束碼失敗
+        #[arg(long)]
+        exit_code: Option<i32>,
+    },
+    /// 清除任務的規則
+    Clear {
+        #[arg(long)]
+        id: u64,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum SchedulesCmd {
+    /// 列出具名排程與引用它的任務（預設動作）
+    List,
+    /// 新增或修改具名排程；引用它的任務立即依新排程重新排定
+    Set {
+        name: String,
+        /// 排程運算式：daily HH:MM、hourly :MM、once <RFC3339> 等
+        schedule: String,
+    },
+    /// 刪除具名排程（仍有任務引用時會被拒絕）
+    Remove { name: String },
+}
@@ -0,0 +1,136 @@
+use crate::exit::{self, fail};
+use anyhow::{Context, Result};
+use bytes::BytesMut;
+use futures_util::SinkExt;
+use scheduler_core::{ClientRequest, ServerResponse, DEFAULT_ENVIRONMENT};
+use std::{net::SocketAddr, time::Duration};
+use tokio::{net::TcpStream, time::timeout};
+use tokio_stream::StreamExt;
+use tokio_util::codec::{Framed, LengthDelimitedCodec};
+
+/// 第一次重試前的等待；之後每次加倍
+const BACKOFF_BASE: Duration = Duration::from_millis(200);
+/// 單次等待的上限
+const BACKOFF_MAX: Duration = Duration::from_secs(5);
+
+/// 重試前等待的時間：200ms 起每次加倍，最多 5 秒
+pub fn backoff(attempt: u32) -> Duration {
+    BACKOFF_BASE
+        .saturating_mul(1 << attempt.min(16))
+        .min(BACKOFF_MAX)
+}
+
+/// 連線參數
+#[derive(Debug, Clone)]
+pub struct NetOptions {
+    pub connect_timeout: Duration,
+    pub request_timeout: Duration,
+    /// 連線失敗時額外重試的次數
+    pub retries: u32,
+    /// 請求送往伺服器上的哪個環境（None 為預設環境）
+    pub environment: Option<String>,
+    /// 該環境的 token
+    pub env_token: Option<String>,
+}
+
+/// 與 scheduler-server 的一條連線
+pub struct Client {
+    framed: Framed<TcpStream, LengthDelimitedCodec>,
+    request_timeout: Duration,
+    environment: Option<String>,
+    env_token: Option<String>,
+}
+
+impl Client {
+    /// 連線到伺服器；連不上（含逾時）時依指數退避重試。
+    /// 只重試「建立連線」，請求送出後不重送，避免 add 被執行兩次
+    pub async fn connect(connect: &str, net: NetOptions) -> Result<Self> {
+        let addr: SocketAddr = connect.parse().context("parse address")?;
+        let mut attempt = 0;
+        loop {
+            let err = match timeout(net.connect_timeout, TcpStream::connect(addr)).await {
+                Ok(Ok(stream)) => {
+                    return Ok(Self {
+                        framed: Framed::new(stream, LengthDelimitedCodec::new()),
+                        request_timeout: net.request_timeout,
+                        environment: net.environment,
+                        env_token: net.env_token,
+                    })
+                }
+                Ok(Err(e)) => e.to_string(),
+                Err(_) => format!("{} 秒內未連上", net.connect_timeout.as_secs_f32()),
+            };
+            if attempt >= net.retries {
+                return Err(fail(exit::CONNECTION, format!("無法連線到 {addr}：{err}")));
+            }
+            let wait = backoff(attempt);
+            eprintln!(
+                "⏳ 無法連線到 {addr}（{err}），{}ms 後重試",
+                wait.as_millis()
+            );
+            tokio::time::sleep(wait).await;
+            attempt += 1;
+        }
+    }
+
+    /// 送出請求但不等回應（訂閱用）
+    pub async fn send(&mut self, req: ClientRequest) -> Result<()> {
+        // 指定了環境或權杖時，每個請求都包成 InEnvironment（只有權杖時為預設環境）
+        let req = match (&self.environment, &self.env_token) {
+            (None, None) => req,
+            (environment, token) => ClientRequest::InEnvironment {
+                environment: environment
+                    .clone()
+                    .unwrap_or_else(|| DEFAULT_ENVIRONMENT.to_string()),
+                token: token.clone(),
+                request: Box::new(req),
+            },
+        };
+        let bytes = serde_json::to_vec(&req)?;
+        self.framed
+            .send(bytes.into())
+            .await
+            .map_err(|e| fail(exit::CONNECTION, format!("送出請求失敗：{e}")))
+    }
+
+    /// 等待下一個訊息；超過 wait 視為連線已死
+    pub async fn recv(&mut self, wait: Duration) -> Result<ServerResponse> {
+        match timeout(wait, self.read()).await {
+            Ok(resp) => resp,
+            Err(_) => Err(fail(
+                exit::CONNECTION,
+                format!("{} 秒內沒有收到任何訊息", wait.as_secs()),
+            )),
+        }
+    }
+
+    /// 等待下一個訊息，不設逾時（follow 的命令可能很久沒有輸出）
+    pub async fn next(&mut self) -> Result<ServerResponse> {
+        self.read().await
+    }
+
+    /// 送出一個請求並等待回應
+    pub async fn request(&mut self, req: ClientRequest) -> Result<ServerResponse> {
+        match timeout(self.request_timeout, self.exchange(req)).await {
+            Ok(resp) => resp,
+            Err(_) => Err(fail(
+                exit::CONNECTION,
+                format!("{} 秒內未收到回應", self.request_timeout.as_secs_f32()),
+            )),
+        }
+    }
+
+    async fn exchange(&mut self, req: ClientRequest) -> Result<ServerResponse> {
+        self.send(req).await?;
+        self.read().await
+    }
+
+    async fn read(&mut self) -> Result<ServerResponse> {
+        let bytes: BytesMut = match self.framed.next().await {
+            Some(Ok(bytes)) => bytes,
+            Some(Err(e)) => return Err(fail(exit::CONNECTION, format!("讀取回應失敗：{e}"))),
+            None => return Err(fail(exit::CONNECTION, "伺服器未回應就關閉連線")),
+        };
+        Ok(serde_json::from_slice(&bytes[..])?)
+    }
+}
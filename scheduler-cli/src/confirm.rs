@@ -0,0 +1,103 @@
+//! 破壞性操作前的確認：影響由伺服器試算（PreviewRemoval），這裡只負責呈現與詢問
+
+use crate::{
+    client::Client,
+    exit::{self, fail},
+};
+use anyhow::Result;
+use scheduler_core::{ClientRequest, RemovalImpact, ServerResponse};
+use std::io::{BufRead, IsTerminal, Write};
+
+/// 試算移除的影響；有重複排程、被依賴、執行中、一次多個或永久刪除時印出摘要並詢問。
+/// 回傳是否繼續
+pub async fn removal(client: &mut Client, ids: Vec<u64>, purge: bool) -> Result<bool> {
+    let impacts = match client
+        .request(ClientRequest::PreviewRemoval { ids })
+        .await?
+    {
+        ServerResponse::RemovalPreview(list) => list,
+        ServerResponse::Error(e) => {
+            return Err(fail(
+                exit::SERVER,
+                format!("❌ 無法試算移除的影響：{e}（可加 --yes 略過確認）"),
+            ));
+        }
+        other => {
+            return Err(fail(
+                exit::SERVER,
+                format!("❌ 伺服器不支援移除試算：{other:?}（可加 --yes 略過確認）"),
+            ));
+        }
+    };
+    let risky = purge
+        || impacts.len() > 1
+        || impacts
+            .iter()
+            .any(|i| i.recurring || i.protected || !i.dependents.is_empty() || i.running > 0);
+    if !risky {
+        return Ok(true);
+    }
+
+    // 摘要與提示走 stderr，--json 時 stdout 仍只有回應
+    print_summary(&impacts, purge);
+    if !std::io::stdin().is_terminal() {
+        return Err(fail(
+            exit::USAGE,
+            "需要確認才能繼續；非互動執行時請加 --yes",
+        ));
+    }
+    ask("確定要繼續嗎？[y/N] ")
+}
+
+fn print_summary(impacts: &[RemovalImpact], purge: bool) {
+    let action = if purge { "永久刪除" } else { "移除" };
+    eprintln!("即將{action} {} 個任務：", impacts.len());
+    for i in impacts {
+        let label = i.label.as_deref().unwrap_or("-");
+        if !i.found && !i.in_trash {
+            eprintln!("- 任務 {}：不存在", i.id);
+            continue;
+        }
+        if !i.found {
+            eprintln!("- 任務 {} {label}：在回收桶中", i.id);
+            continue;
+        }
+        let mut notes = Vec::new();
+        if i.system {
+            notes.push("內建任務，無法移除".to_string());
+        }
+        if i.protected {
+            notes.push("受保護，要加 --force 才會移除".to_string());
+        }
+        if i.recurring {
+            notes.push("重複排程".to_string());
+        }
+        if !i.dependents.is_empty() {
+            let ids: Vec<String> = i.dependents.iter().map(|d| d.to_string()).collect();
+            notes.push(format!("依賴它的任務 {} 將不再觸發", ids.join(", ")));
+        }
+        if i.running > 0 {
+            notes.push(format!("{} 個執行中的 run 會被終止", i.running));
+        }
+        if notes.is_empty() {
+            eprintln!("- 任務 {} {label}", i.id);
+        } else {
+            eprintln!("- 任務 {} {label}（{}）", i.id, notes.join("；"));
+        }
+    }
+    if !purge {
+        eprintln!("（伺服器有設定保留期時，可用 restore 還原）");
+    }
+}
+
+/// 只有 y / yes 算同意；讀不到輸入視為拒絕
+fn ask(prompt: &str) -> Result<bool> {
+    eprint!("{prompt}");
+    std::io::stderr().flush()?;
+    let mut line = String::new();
+    std::io::stdin().lock().read_line(&mut line)?;
+    Ok(matches!(
+        line.trim().to_ascii_lowercase().as_str(),
+        "y" | "yes"
+    ))
+}
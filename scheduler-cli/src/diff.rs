@@ -0,0 +1,97 @@
+use crate::{
+    client::{Client, NetOptions},
+    exit::{self, fail},
+    specfile, table,
+};
+use anyhow::Result;
+use scheduler_core::{ChangeKind, ClientRequest, ManifestChange, SchedulerError, ServerResponse};
+use std::path::Path;
+
+/// `diff -f`：請伺服器以 git-sync 的比對規則試算清單，逐欄列出套用時會做的變更（不套用）。
+/// exit_code 時有差異就以 DIFFERENT 結束，供變更審核流程使用
+pub async fn run(
+    connect: &str,
+    net: NetOptions,
+    path: &Path,
+    exit_code: bool,
+    json: bool,
+) -> Result<()> {
+    let tasks = specfile::read_manifest(path)?;
+    let mut client = Client::connect(connect, net).await?;
+    let resp = client
+        .request(ClientRequest::DiffManifest { tasks })
+        .await?;
+    if json {
+        println!("{}", serde_json::to_string(&resp)?);
+    }
+    let changes = match resp {
+        ServerResponse::ManifestDiff(changes) => changes,
+        ServerResponse::Error(SchedulerError::InvalidRequest { msg }) => {
+            return Err(fail(exit::USAGE, format!("❌ 清單無法套用：{msg}")));
+        }
+        ServerResponse::Error(e) => {
+            return Err(fail(exit::SERVER, format!("❌ 伺服器錯誤：{e}")));
+        }
+        other => return Err(fail(exit::SERVER, format!("❌ 非預期的回覆：{other:?}"))),
+    };
+    if !json {
+        print_changes(&changes, table::use_color());
+    }
+    if exit_code && !changes.is_empty() {
+        return Err(fail(
+            exit::DIFFERENT,
+            format!("{} 個任務與清單不同", changes.len()),
+        ));
+    }
+    Ok(())
+}
+
+fn print_changes(changes: &[ManifestChange], color: bool) {
+    if changes.is_empty() {
+        println!("（伺服器上的任務與清單一致）");
+        return;
+    }
+    let paint = |code: &str, text: String| {
+        if color {
+            format!("\x1b[{code}m{text}\x1b[0m")
+        } else {
+            text
+        }
+    };
+    let (red, green, yellow) = ("31", "32", "33");
+    for c in changes {
+        let task = c
+            .task_id
+            .map(|id| format!("（任務 {id}）"))
+            .unwrap_or_default();
+        let (mark, code) = match c.change {
+            ChangeKind::Add => ("+", green),
+            ChangeKind::Update => ("~", yellow),
+            ChangeKind::Remove => ("-", red),
+        };
+        println!("{}", paint(code, format!("{mark} {}{task}", c.key)));
+        for f in &c.fields {
+            let line = match (&f.old, &f.new) {
+                (Some(old), Some(new)) => {
+                    format!(
+                        "{}: {} → {}",
+                        f.field,
+                        paint(red, old.clone()),
+                        paint(green, new.clone())
+                    )
+                }
+                (Some(old), None) => format!("{}: {}", f.field, paint(red, old.clone())),
+                (None, Some(new)) => format!("{}: {}", f.field, paint(green, new.clone())),
+                (None, None) => continue,
+            };
+            println!("    {line}");
+        }
+    }
+    let count = |kind| changes.iter().filter(|c| c.change == kind).count();
+    println!(
+        "新增 {}、更新 {}、移除 {}",
+        count(ChangeKind::Add),
+        count(ChangeKind::Update),
+        count(ChangeKind::Remove)
+    );
+}
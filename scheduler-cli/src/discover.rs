@@ -0,0 +1,71 @@
+//! 以 mDNS 在區網尋找 scheduler-server（伺服器需設定 [mdns]）
+
+use anyhow::{Context, Result};
+use mdns_sd::{ServiceDaemon, ServiceEvent};
+use scheduler_core::MDNS_SERVICE_TYPE;
+use std::{
+    collections::BTreeMap,
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+/// 找到的一台伺服器
+#[derive(Debug, Clone)]
+pub struct Found {
+    pub instance: String,
+    pub addr: SocketAddr,
+    pub version: String,
+    pub environment: String,
+}
+
+/// 瀏覽 `window` 這麼久，回傳期間解析到的伺服器（依實例名稱排序）
+pub async fn browse(window: Duration) -> Result<Vec<Found>> {
+    tokio::task::spawn_blocking(move || browse_blocking(window)).await?
+}
+
+fn browse_blocking(window: Duration) -> Result<Vec<Found>> {
+    let daemon = ServiceDaemon::new().context("無法啟動 mDNS")?;
+    let events = daemon
+        .browse(MDNS_SERVICE_TYPE)
+        .context("無法啟動 mDNS 瀏覽")?;
+    let deadline = Instant::now() + window;
+    let mut found = BTreeMap::new();
+    while let Some(left) = deadline.checked_duration_since(Instant::now()) {
+        match events.recv_timeout(left) {
+            Ok(ServiceEvent::ServiceResolved(info)) => {
+                // 同時有 IPv4 與 IPv6 時優先用 IPv4
+                let Some(ip) = info
+                    .get_addresses()
+                    .iter()
+                    .min_by_key(|ip| ip.is_ipv6())
+                    .copied()
+                else {
+                    continue;
+                };
+                let instance = info
+                    .get_fullname()
+                    .strip_suffix(MDNS_SERVICE_TYPE)
+                    .map(|s| s.trim_end_matches('.'))
+                    .unwrap_or(info.get_fullname())
+                    .to_string();
+                let prop = |key| info.get_property_val_str(key).unwrap_or("?").to_string();
+                found.insert(
+                    info.get_fullname().to_string(),
+                    Found {
+                        instance,
+                        addr: SocketAddr::new(ip, info.get_port()),
+                        version: prop("version"),
+                        environment: prop("environment"),
+                    },
+                );
+            }
+            Ok(ServiceEvent::ServiceRemoved(_, fullname)) => {
+                found.remove(&fullname);
+            }
+            Ok(_) => {}
+            Err(_) => break,
+        }
+    }
+    let _ = daemon.shutdown();
+    Ok(found.into_values().collect())
+}
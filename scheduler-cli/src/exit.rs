@@ -0,0 +1,52 @@
+//! 結束碼約定：腳本可依此分辨失敗原因，已發布的數值不再變動
+
+use std::fmt;
+
+pub const OK: u8 = 0;
+/// 其他錯誤（參數內容不合法、本機檔案錯誤等）
+pub const GENERIC: u8 = 1;
+/// 找不到指定的任務或資料
+pub const NOT_FOUND: u8 = 2;
+/// 伺服器回報錯誤
+pub const SERVER: u8 = 3;
+/// 無法連線或連線中斷
+pub const CONNECTION: u8 = 4;
+/// verify 發現輸出與紀錄的 checksum 不符
+pub const INTEGRITY: u8 = 5;
+/// 在確認提示中拒絕，沒有做任何變更
+pub const DECLINED: u8 = 6;
+/// run-now --follow 的執行失敗（結束碼非 0、逾時、被終止）
+pub const RUN_FAILED: u8 = 7;
+/// lint 發現錯誤（或 --deny-warnings 時發現警告）
+pub const LINT: u8 = 8;
+/// diff --exit-code 發現伺服器與清單有差異
+pub const DIFFERENT: u8 = 9;
+/// 命令列用法錯誤（同 sysexits.h 的 EX_USAGE）
+pub const USAGE: u8 = 64;
+
+/// 帶有結束碼的錯誤；其餘錯誤一律視為 GENERIC
+#[derive(Debug)]
+pub struct Failure {
+    pub code: u8,
+    pub message: String,
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Failure {}
+
+/// 建立帶結束碼的錯誤
+pub fn fail(code: u8, message: impl Into<String>) -> anyhow::Error {
+    anyhow::Error::new(Failure {
+        code,
+        message: message.into(),
+    })
+}
+
+pub fn code_of(err: &anyhow::Error) -> u8 {
+    err.downcast_ref::<Failure>().map_or(GENERIC, |f| f.code)
+}
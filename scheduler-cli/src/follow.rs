@@ -0,0 +1,73 @@
+use crate::{
+    client::{Client, NetOptions},
+    exit::{self, fail},
+};
+use anyhow::Result;
+use scheduler_core::{ClientRequest, OutputStream, RunOutcome, SchedulerError, ServerResponse};
+use std::{collections::BTreeMap, io::Write};
+
+/// run-now --follow：立即執行並即時印出命令的輸出（stdout 對 stdout、stderr 對 stderr），
+/// 直到伺服器回報結果
+pub async fn run_now(
+    connect: &str,
+    net: NetOptions,
+    id: u64,
+    metadata: BTreeMap<String, String>,
+    json: bool,
+) -> Result<()> {
+    let mut client = Client::connect(connect, net).await?;
+    client
+        .send(ClientRequest::RunNow {
+            id,
+            follow: true,
+            metadata,
+        })
+        .await?;
+    loop {
+        let resp = client.next().await?;
+        if json {
+            println!("{}", serde_json::to_string(&resp)?);
+        }
+        match resp {
+            ServerResponse::RunOutput { .. } if json => {}
+            ServerResponse::RunOutput { stream, data, .. } => {
+                if stream == OutputStream::Stderr {
+                    eprint!("{data}");
+                } else {
+                    print!("{data}");
+                    std::io::stdout().flush()?;
+                }
+            }
+            ServerResponse::RunDone {
+                run_id,
+                status_code,
+                outcome,
+                ..
+            } => {
+                return match outcome {
+                    RunOutcome::Exited if status_code == 0 => {
+                        if !json {
+                            eprintln!("✅ run {run_id} 完成");
+                        }
+                        Ok(())
+                    }
+                    RunOutcome::Exited => Err(fail(
+                        exit::RUN_FAILED,
+                        format!("❌ run {run_id} 結束碼 {status_code}"),
+                    )),
+                    other => Err(fail(
+                        exit::RUN_FAILED,
+                        format!("❌ run {run_id} 未正常結束（{other:?}）"),
+                    )),
+                };
+            }
+            ServerResponse::Error(SchedulerError::NotFound { msg }) => {
+                return Err(fail(exit::NOT_FOUND, format!("⚠️ {msg}")));
+            }
+            ServerResponse::Error(e) => {
+                return Err(fail(exit::SERVER, format!("❌ 伺服器錯誤：{e}")));
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,360 @@
+use crate::{
+    exit::{self, fail},
+    specfile,
+};
+use anyhow::Result;
+use chrono::Local;
+use scheduler_core::{Schedule, TaskSpec};
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    path::{Component, Path, PathBuf},
+};
+
+/// After 的延遲超過這麼久多半是單位寫錯（分鐘、小時當成秒）
+const SUSPICIOUS_DELAY_SECS: u64 = 24 * 3600;
+const MINUTES_PER_DAY: u32 = 24 * 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// 一個問題；tasks 為相關任務的標示（key、name 或 #序號）
+#[derive(Debug, Serialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: &'static str,
+    pub tasks: Vec<String>,
+    pub message: String,
+}
+
+/// 清單中的一個任務；id 只用來對應 After 的 task_id
+struct Entry {
+    label: String,
+    id: Option<u64>,
+    spec: TaskSpec,
+}
+
+/// `lint -f`：檢查清單後列出問題。有錯誤（或 deny_warnings 時有警告）就以 LINT 結束碼失敗
+pub fn run(path: &Path, deny_warnings: bool, json: bool) -> Result<()> {
+    let (label, value) = specfile::load(path)?;
+    let (entries, mut diags) = entries(value)?;
+    diags.extend(check(&entries));
+    diags.sort_by(|a, b| (a.severity, a.code).cmp(&(b.severity, b.code)));
+
+    let errors = diags
+        .iter()
+        .filter(|d| d.severity == Severity::Error)
+        .count();
+    let warnings = diags.len() - errors;
+    if json {
+        println!(
+            "{}",
+            serde_json::json!({
+                "file": label,
+                "tasks": entries.len(),
+                "errors": errors,
+                "warnings": warnings,
+                "diagnostics": diags,
+            })
+        );
+    } else {
+        for d in &diags {
+            let icon = match d.severity {
+                Severity::Error => "❌ error",
+                Severity::Warning => "⚠️ warning",
+            };
+            println!("{icon}[{}] {}：{}", d.code, d.tasks.join(", "), d.message);
+        }
+        println!(
+            "{label}：{} 個任務，{errors} 個錯誤、{warnings} 個警告",
+            entries.len()
+        );
+    }
+    if errors > 0 || (deny_warnings && warnings > 0) {
+        return Err(fail(exit::LINT, "lint 未通過"));
+    }
+    Ok(())
+}
+
+/// 接受任務陣列、`tasks` 陣列（同 git-sync 清單，可帶 key）或單一任務規格；
+/// 各任務另可帶 id，供其他任務的 After 引用
+fn entries(value: serde_json::Value) -> Result<(Vec<Entry>, Vec<Diagnostic>)> {
+    let items = match value {
+        serde_json::Value::Array(items) => items,
+        serde_json::Value::Object(mut map) if map.contains_key("tasks") => {
+            match map.remove("tasks") {
+                Some(serde_json::Value::Array(items)) => items,
+                _ => return Err(fail(exit::USAGE, "tasks 必須是陣列")),
+            }
+        }
+        single => vec![single],
+    };
+    let mut entries = Vec::new();
+    let mut diags = Vec::new();
+    for (i, mut item) in items.into_iter().enumerate() {
+        let mut take = |field: &str| match &mut item {
+            serde_json::Value::Object(map) => map.remove(field),
+            _ => None,
+        };
+        let key = take("key").and_then(|v| v.as_str().map(str::to_string));
+        let id = take("id").and_then(|v| v.as_u64());
+        let name = item
+            .get("name")
+            .and_then(|v| v.as_str())
+            .map(str::to_string);
+        let label = key.or(name).unwrap_or_else(|| format!("#{}", i + 1));
+        match specfile::parse_spec(item) {
+            Ok((spec, unknown)) => {
+                if !unknown.is_empty() {
+                    diags.push(Diagnostic {
+                        severity: Severity::Error,
+                        code: "unknown-field",
+                        tasks: vec![label.clone()],
+                        message: format!("不認得的欄位：{}", unknown.join(", ")),
+                    });
+                }
+                entries.push(Entry { label, id, spec });
+            }
+            Err(e) => diags.push(Diagnostic {
+                severity: Severity::Error,
+                code: "invalid-spec",
+                tasks: vec![label],
+                message: e,
+            }),
+        }
+    }
+    Ok((entries, diags))
+}
+
+fn check(entries: &[Entry]) -> Vec<Diagnostic> {
+    let mut diags = Vec::new();
+    lock_overlaps(entries, &mut diags);
+    upstreams(entries, &mut diags);
+    long_delays(entries, &mut diags);
+    output_collisions(entries, &mut diags);
+    diags
+}
+
+/// 共用同一把鎖的任務在同一時間到期：其中一個只能等，或被略過
+fn lock_overlaps(entries: &[Entry], diags: &mut Vec<Diagnostic>) {
+    let mut by_lock: BTreeMap<&str, Vec<&Entry>> = BTreeMap::new();
+    for e in entries {
+        for lock in &e.spec.locks {
+            by_lock.entry(lock.as_str()).or_default().push(e);
+        }
+    }
+    for (lock, tasks) in by_lock {
+        for (i, a) in tasks.iter().enumerate() {
+            for b in &tasks[i + 1..] {
+                if let Some(when) = overlap(&a.spec, &b.spec) {
+                    diags.push(Diagnostic {
+                        severity: Severity::Warning,
+                        code: "lock-overlap",
+                        tasks: vec![a.label.clone(), b.label.clone()],
+                        message: format!("共用鎖 {lock:?}，{when} 可能同時執行"),
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// 兩個排程會不會重疊：一方開始時另一方可能還在執行（以 timeout_secs 估計執行時間，
+/// 未設定則只看同一分鐘）；回傳重疊的時間描述
+fn overlap(a: &TaskSpec, b: &TaskSpec) -> Option<String> {
+    match (&a.schedule, &b.schedule) {
+        (Schedule::Reboot, Schedule::Reboot) => return Some("伺服器啟動時".to_string()),
+        (Schedule::Once(x), Schedule::Once(y)) => {
+            let (first, second, running) = if x <= y {
+                (x, y, window_secs(a))
+            } else {
+                (y, x, window_secs(b))
+            };
+            return ((*second - *first).num_seconds() < running as i64)
+                .then(|| second.to_rfc3339());
+        }
+        _ => {}
+    }
+    let (wa, wb) = (
+        window_secs(a).div_ceil(60) as u32,
+        window_secs(b).div_ceil(60) as u32,
+    );
+    for x in day_minutes(&a.schedule) {
+        for y in day_minutes(&b.schedule) {
+            let d = (y + MINUTES_PER_DAY - x) % MINUTES_PER_DAY;
+            if d < wa {
+                return Some(format!("每天 {:02}:{:02}", y / 60, y % 60));
+            }
+            if (MINUTES_PER_DAY - d) % MINUTES_PER_DAY < wb {
+                return Some(format!("每天 {:02}:{:02}", x / 60, x % 60));
+            }
+        }
+    }
+    None
+}
+
+/// 估計的執行時間（秒），至少涵蓋開始的那一分鐘
+fn window_secs(spec: &TaskSpec) -> u64 {
+    spec.timeout_secs.unwrap_or(0).max(60)
+}
+
+/// 一天中觸發的分鐘（0..1440）；只有 Daily 與 Hourly 有固定時刻
+fn day_minutes(s: &Schedule) -> Vec<u32> {
+    match *s {
+        Schedule::Daily { hour, minute } => vec![hour * 60 + minute],
+        Schedule::Hourly { minute } => (0..24).map(|h| h * 60 + minute).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// After 的前置任務：清單中有 id 時，引用不存在的 id、不會執行的前置任務與循環依賴
+fn upstreams(entries: &[Entry], diags: &mut Vec<Diagnostic>) {
+    let by_id: HashMap<u64, &Entry> = entries.iter().filter_map(|e| Some((e.id?, e))).collect();
+    if by_id.is_empty() {
+        // 沒有宣告 id 時，After 引用的都是伺服器上既有的任務，無從檢查
+        return;
+    }
+    let now = Local::now().fixed_offset();
+    let mut cycles = BTreeSet::new();
+    for e in entries {
+        let Schedule::After { task_id, .. } = e.spec.schedule else {
+            continue;
+        };
+        match by_id.get(&task_id) {
+            None => diags.push(Diagnostic {
+                severity: Severity::Warning,
+                code: "unknown-upstream",
+                tasks: vec![e.label.clone()],
+                message: format!("前置任務 #{task_id} 不在清單中，須已存在於伺服器上"),
+            }),
+            Some(up) => {
+                let never = match &up.spec.schedule {
+                    Schedule::Once(at) if *at < now => Some(format!("一次性排程 {at} 已過")),
+                    Schedule::Manual if up.spec.triggers.is_empty() => {
+                        Some("手動排程且沒有觸發來源".to_string())
+                    }
+                    _ => None,
+                };
+                if let Some(why) = never {
+                    diags.push(Diagnostic {
+                        severity: Severity::Warning,
+                        code: "unreachable-upstream",
+                        tasks: vec![e.label.clone(), up.label.clone()],
+                        message: format!("前置任務不會執行（{why}），此任務永遠不會觸發"),
+                    });
+                }
+            }
+        }
+        if let Some(cycle) = cycle_from(e, &by_id) {
+            cycles.insert(cycle);
+        }
+    }
+    for cycle in cycles {
+        diags.push(Diagnostic {
+            severity: Severity::Error,
+            code: "dependency-cycle",
+            tasks: cycle
+                .iter()
+                .filter_map(|id| by_id.get(id).map(|e| e.label.clone()))
+                .collect(),
+            message: format!(
+                "循環依賴：{}",
+                cycle
+                    .iter()
+                    .chain(cycle.first())
+                    .map(|id| format!("#{id}"))
+                    .collect::<Vec<_>>()
+                    .join(" → ")
+            ),
+        });
+    }
+}
+
+/// 從 e 沿 After 往上游走回到 e 時的循環（由最小的 id 開始排列，方便去重）
+fn cycle_from(e: &Entry, by_id: &HashMap<u64, &Entry>) -> Option<Vec<u64>> {
+    let start = e.id?;
+    let mut path = vec![start];
+    let mut cur = e;
+    while let Schedule::After { task_id, .. } = cur.spec.schedule {
+        if task_id == start {
+            let min = path.iter().enumerate().min_by_key(|(_, id)| **id)?.0;
+            path.rotate_left(min);
+            return Some(path);
+        }
+        if path.contains(&task_id) {
+            return None; // 循環不經過 e，由循環上的任務回報
+        }
+        cur = by_id.get(&task_id)?;
+        path.push(task_id);
+    }
+    None
+}
+
+/// After 的延遲長達一天以上
+fn long_delays(entries: &[Entry], diags: &mut Vec<Diagnostic>) {
+    for e in entries {
+        if let Schedule::After { delay_secs, .. } = e.spec.schedule {
+            if delay_secs >= SUSPICIOUS_DELAY_SECS {
+                diags.push(Diagnostic {
+                    severity: Severity::Warning,
+                    code: "long-delay",
+                    tasks: vec![e.label.clone()],
+                    message: format!(
+                        "After 延遲 {delay_secs} 秒（約 {:.1} 天），單位是否寫錯？",
+                        delay_secs as f64 / 86400.0
+                    ),
+                });
+            }
+        }
+    }
+}
+
+/// 多個任務寫到同一個輸出檔：有任一個覆寫（append = false）就會蓋掉其他的輸出
+fn output_collisions(entries: &[Entry], diags: &mut Vec<Diagnostic>) {
+    let mut by_path: BTreeMap<PathBuf, Vec<&Entry>> = BTreeMap::new();
+    for e in entries {
+        // 含模板的路徑要代入後才知道
+        if !e.spec.output_path.to_string_lossy().contains("{{") {
+            by_path
+                .entry(normalize(&e.spec.output_path))
+                .or_default()
+                .push(e);
+        }
+    }
+    for (path, tasks) in by_path.into_iter().filter(|(_, t)| t.len() > 1) {
+        let overwrites = tasks.iter().any(|e| !e.spec.append);
+        diags.push(Diagnostic {
+            severity: if overwrites {
+                Severity::Error
+            } else {
+                Severity::Warning
+            },
+            code: "output-collision",
+            tasks: tasks.iter().map(|e| e.label.clone()).collect(),
+            message: if overwrites {
+                format!("共用輸出檔 {}，且有任務會覆寫它", path.display())
+            } else {
+                format!("共用輸出檔 {}，輸出會交錯在一起", path.display())
+            },
+        });
+    }
+}
+
+/// 只做字面上的正規化（去掉 `.`、處理 `..`），不碰檔案系統
+fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for c in path.components() {
+        match c {
+            Component::CurDir => {}
+            Component::ParentDir if out.file_name().is_some() => {
+                out.pop();
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
@@ -1,163 +1,1459 @@
+mod cli;
+mod client;
+mod confirm;
+mod diff;
+mod discover;
+mod exit;
+mod follow;
+mod lint;
+mod profile;
+mod schema;
+mod specfile;
+mod spool;
+mod table;
+mod tz;
+mod watch;
+
 use anyhow::{bail, Context, Result};
-use bytes::BytesMut;
-use clap::{Parser, Subcommand};
-use scheduler_core::{ClientRequest, Schedule, ServerResponse, TaskInfo, TaskSpec};
-use std::{net::SocketAddr, path::PathBuf};
-use tokio::net::TcpStream;
-use tokio_stream::StreamExt;
-use tokio_util::codec::{Framed, LengthDelimitedCodec};
-use futures_util::SinkExt; // for framed.send()
-
-#[derive(Parser, Debug)]
-#[command(name = "scheduler-cli")]
-struct Opts {
-    /// 連線到 scheduler-server 的位址
-    #[arg(long, default_value = "127.0.0.1:7878")]
-    connect: String,
-
-    /// 子命令
-    #[command(subcommand)]
-    cmd: Cmd,
-}
-
-#[derive(Subcommand, Debug)]
-enum Cmd {
-    /// 新增任務
-    Add {
-        #[arg(long)]
-        cmd: String,
-        #[arg(long, num_args = 0.., value_delimiter = ' ')]
-        args: Vec<String>,
-        #[arg(long)]
-        output: PathBuf,
-        #[arg(long, default_value_t = true)]
-        append: bool,
-        #[arg(long)]
-        once: Option<String>, // RFC3339
-        #[arg(long)]
-        daily: Option<String>, // "HH:MM"
-        #[arg(long)]
-        after: Option<u64>,
-        #[arg(long, default_value_t = 0)]
-        delay: u64,
-    },
-
-    /// 移除任務
-    Remove {
-        #[arg(long)]
-        id: u64,
-    },
-
-    /// 列出所有任務
-    List,
-}
+use chrono::{DateTime, FixedOffset, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone};
+use clap::{CommandFactory, Parser};
+use cli::{
+    ChaosCmd, Cmd, HookCmd, MaintenanceCmd, Opts, OutboxCmd, QuarantineCmd, SchedulesCmd, VarsCmd,
+};
+use client::{Client, NetOptions};
+use exit::fail;
+use profile::OutputFormat;
+use scheduler_core::{
+    Approval, ChaosEntry, ChaosRule, CheckStatus, CircuitBreaker, CleanupAction, ClientRequest,
+    DependencyGraph, DependencyIssueKind, FreezeStatus, Healthcheck, IoNice, MaintenanceAction,
+    MaintenanceKind, NamedSchedule, Notification, OutputCheck, OutputPerms, OutputsFrom, Owner,
+    PendingApproval, QuarantinedTask, Revision, RunRecord, RunReport, SandboxProfile, SchedClass,
+    Schedule, SchedulerError, ServerInfo, ServerResponse, SuccessCriteria, TaskSort, TaskSpec,
+    Throttle, TimeWindow, TrashedTask, Trigger, TriggerHookInfo, WindowPolicy,
+};
+use std::{path::Path, process::ExitCode, time::Duration};
+use tz::{DisplayTz, TimeStyle};
 
 #[tokio::main]
-async fn main() -> Result<()> {
-    let opts = Opts::parse();
-    let addr: SocketAddr = opts.connect.parse().context("parse address")?;
-    let stream = TcpStream::connect(addr).await?;
-    let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+async fn main() -> ExitCode {
+    let opts = match Opts::try_parse() {
+        Ok(opts) => opts,
+        Err(e) => {
+            // --help / --version 不算錯誤
+            let code = if e.use_stderr() {
+                exit::USAGE
+            } else {
+                exit::OK
+            };
+            let _ = e.print();
+            return ExitCode::from(code);
+        }
+    };
+    match run(opts).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {e:#}");
+            ExitCode::from(exit::code_of(&e))
+        }
+    }
+}
+
+async fn run(opts: Opts) -> Result<()> {
+    // 產生文件類的子命令不需要伺服器
+    match &opts.cmd {
+        Cmd::Completions { shell } => {
+            let mut cmd = Opts::command();
+            clap_complete::generate(*shell, &mut cmd, "scheduler-cli", &mut std::io::stdout());
+            return Ok(());
+        }
+        Cmd::Man { dir } => return write_man(dir.as_deref()),
+        Cmd::Schema { ty } => return schema::print(*ty),
+        Cmd::Lint {
+            file,
+            deny_warnings,
+        } => return lint::run(file, *deny_warnings, opts.json),
+        Cmd::Discover { timeout } => return list_discovered(*timeout, opts.json).await,
+        _ => {}
+    }
+
+    let profile = profile::resolve(opts.profile.as_deref())?;
+    let tz = match (opts.tz, &profile.tz) {
+        (Some(tz), _) => tz,
+        (None, Some(name)) => name
+            .parse()
+            .map_err(|e| fail(exit::USAGE, format!("profile 的 tz：{e}")))?,
+        (None, None) => DisplayTz::Local,
+    };
+    let view = View {
+        times: TimeStyle {
+            tz,
+            raw: opts.raw_times,
+        },
+        json: opts.json || profile.output == Some(OutputFormat::Json),
+        wide: profile.output == Some(OutputFormat::Wide)
+            || matches!(
+                opts.cmd,
+                Cmd::List { wide: true, .. } | Cmd::Search { wide: true, .. }
+            ),
+    };
+    // 不需要伺服器，但跟其他子命令一樣套用 profile 的時區與輸出格式
+    if let Cmd::Preview { schedule, count } = &opts.cmd {
+        return preview(schedule, *count, view.times.tz, view.json);
+    }
+    let connect = match opts.connect {
+        Some(connect) => connect,
+        None if opts.discover => discover_one().await?,
+        None => profile
+            .connect
+            .unwrap_or_else(|| profile::DEFAULT_CONNECT.to_string()),
+    };
+    let net = NetOptions {
+        connect_timeout: Duration::from_secs(
+            opts.connect_timeout
+                .or(profile.connect_timeout_secs)
+                .unwrap_or(5),
+        ),
+        request_timeout: Duration::from_secs(
+            opts.request_timeout
+                .or(profile.request_timeout_secs)
+                .unwrap_or(30),
+        ),
+        retries: opts.retries.or(profile.retries).unwrap_or(3),
+        environment: opts.environment.or(profile.environment),
+        env_token: opts.env_token.or(profile.env_token),
+    };
 
+    let spool_dir = || {
+        opts.spool_dir
+            .clone()
+            .or_else(spool::default_dir)
+            .context("無法決定暫存目錄，請指定 --spool-dir")
+    };
     match opts.cmd {
+        Cmd::Flush => return flush(&connect, net, &spool_dir()?, view).await,
+        Cmd::Watch { since } => {
+            return watch::watch(&connect, net, since, view.json, view.times.tz).await
+        }
+        Cmd::RunNow {
+            id,
+            follow: true,
+            meta,
+        } => {
+            let metadata = meta.iter().map(|m| parse_pair(m)).collect::<Result<_>>()?;
+            return follow::run_now(&connect, net, id, metadata, view.json).await;
+        }
+        Cmd::Diff { file, exit_code } => {
+            return diff::run(&connect, net, &file, exit_code, view.json).await;
+        }
+        _ => {}
+    }
+
+    // remove 可一次指定多個 id，逐一送出；未加 --yes / --force 時先請伺服器試算影響再確認
+    let (reqs, confirm) = match opts.cmd {
+        Cmd::Remove {
+            ids,
+            purge,
+            yes,
+            force,
+        } => {
+            let reqs = ids
+                .iter()
+                .map(|&id| match purge {
+                    true => ClientRequest::PurgeTask { id, force },
+                    false => ClientRequest::RemoveTask { id, force },
+                })
+                .collect();
+            (reqs, (!yes && !force).then_some((ids, purge)))
+        }
+        cmd => (vec![build_request(cmd)?], None),
+    };
+    let spoolable = |req: &ClientRequest| {
+        matches!(
+            req,
+            ClientRequest::AddTask(_)
+                | ClientRequest::RemoveTask { .. }
+                | ClientRequest::PurgeTask { .. }
+        )
+    };
+    if opts.spool && !reqs.iter().all(spoolable) {
+        return Err(fail(exit::USAGE, "--spool 只適用於 add / remove"));
+    }
+    let mut client = match Client::connect(&connect, net).await {
+        Ok(client) => client,
+        // 連不上就無法試算，不能在未確認的情況下暫存移除
+        Err(e) if opts.spool && confirm.is_some() && exit::code_of(&e) == exit::CONNECTION => {
+            return Err(fail(
+                exit::CONNECTION,
+                format!("伺服器無法連線（{e:#}），無法確認移除的影響；確定要暫存請加 --yes"),
+            ));
+        }
+        // 只有「根本連不上」才暫存；已送出的請求不重送，以免重複
+        Err(e) if opts.spool && exit::code_of(&e) == exit::CONNECTION => {
+            for req in &reqs {
+                let path = spool::save(&spool_dir()?, req)?;
+                eprintln!("📥 伺服器無法連線（{e:#}），請求已暫存：{}", path.display());
+                if view.json {
+                    println!("{}", serde_json::json!({ "Spooled": { "path": path } }));
+                }
+            }
+            return Ok(());
+        }
+        Err(e) => return Err(e),
+    };
+    if let Some((ids, purge)) = confirm {
+        if !confirm::removal(&mut client, ids, purge).await? {
+            return Err(fail(exit::DECLINED, "已取消，沒有移除任何任務"));
+        }
+    }
+    for req in reqs {
+        let resp = client.request(req).await?;
+        handle_response(resp, view)?;
+    }
+    Ok(())
+}
+
+/// 依序重送暫存的請求；連不上就停，伺服器拒絕的移到 failed/ 後繼續
+async fn flush(connect: &str, net: NetOptions, dir: &Path, view: View) -> Result<()> {
+    let files = spool::pending(dir)?;
+    if files.is_empty() {
+        eprintln!("（沒有暫存的請求）");
+        return Ok(());
+    }
+    let mut client = Client::connect(connect, net).await?;
+    let (mut sent, mut failed) = (0, 0);
+    for path in files {
+        let req = spool::load(&path)?;
+        let resp = client.request(req).await?;
+        match handle_response(resp, view) {
+            Ok(()) => {
+                std::fs::remove_file(&path)?;
+                sent += 1;
+            }
+            Err(e) if exit::code_of(&e) == exit::NOT_FOUND => {
+                // 要移除的任務已不存在，目的已達成
+                eprintln!("{e:#}（{}）", path.display());
+                std::fs::remove_file(&path)?;
+                sent += 1;
+            }
+            Err(e) => {
+                let dest = spool::mark_failed(dir, &path)?;
+                eprintln!("{e:#}（已移到 {}）", dest.display());
+                failed += 1;
+            }
+        }
+    }
+    eprintln!("📤 已重送 {sent} 筆，失敗 {failed} 筆");
+    if failed > 0 {
+        return Err(fail(
+            exit::SERVER,
+            format!("{failed} 筆暫存請求被伺服器拒絕"),
+        ));
+    }
+    Ok(())
+}
+
+/// 把子命令轉成送給伺服器的請求（先在本機檢查參數，不必等連線）
+fn build_request(cmd: Cmd) -> Result<ClientRequest> {
+    let req = match cmd {
         Cmd::Add {
+            name,
+            tags,
+            namespace,
+            owner_email,
+            owner_team,
+            labels,
+            file,
             cmd,
             args,
+            env,
+            clean_env,
+            skip_cmd_check,
+            outputs_last_line,
+            outputs_file,
             output,
             append,
+            output_mode,
+            output_user,
+            output_group,
             once,
             daily,
             after,
             delay,
+            expires,
+            manual,
+            schedule,
+            timeout,
+            stop_signal,
+            stop_grace,
+            nice,
+            ionice,
+            cpus,
+            sandbox,
+            sandbox_rw,
+            sandbox_net,
+            locks,
+            min_interval,
+            max_per_hour,
+            mqtt,
+            bridge,
+            ping,
+            ping_start,
+            ping_success,
+            ping_failure,
+            window,
+            outside_window,
+            approval,
+            approval_timeout,
+            breaker,
+            breaker_cooldown,
+            success_exit,
+            success_match,
+            failure_match,
+            output_encoding,
+            cleanup_dirs,
+            cleanup_files,
+            cleanup_older_than,
+            priority,
+            exact_start,
+            protected,
         } => {
-            let schedule = build_schedule(once, daily, after, delay)?;
-            let spec = TaskSpec {
+            if let Some(path) = file {
+                return Ok(ClientRequest::AddTask(specfile::read(&path)?));
+            }
+            // 沒有 --file 時 clap 已要求兩者
+            let (Some(cmd), Some(output)) = (cmd, output) else {
+                return Err(fail(
+                    exit::USAGE,
+                    "需要 --cmd 與 --output（或以 -f 指定規格檔）",
+                ));
+            };
+            let schedule = build_schedule(once, daily, after, delay, expires, manual, schedule)?;
+            let sched = SchedClass {
+                nice,
+                ionice: ionice.as_deref().map(parse_ionice).transpose()?,
+                cpu_affinity: cpus
+                    .as_deref()
+                    .map(parse_cpu_list)
+                    .transpose()?
+                    .unwrap_or_default(),
+            };
+            ClientRequest::AddTask(TaskSpec {
+                name,
+                tags,
+                namespace,
+                owner: (owner_email.is_some() || owner_team.is_some()).then_some(Owner {
+                    email: owner_email,
+                    team: owner_team,
+                }),
+                metric_labels: labels
+                    .iter()
+                    .map(|l| parse_pair(l))
+                    .collect::<Result<_>>()?,
                 cmd,
                 args,
+                env: env.iter().map(|e| parse_pair(e)).collect::<Result<_>>()?,
+                clean_env,
+                skip_cmd_check,
+                outputs: match outputs_file {
+                    Some(path) => Some(OutputsFrom::File(path)),
+                    None => outputs_last_line.then_some(OutputsFrom::LastLine),
+                },
                 output_path: output,
                 append,
+                output_perms: (output_mode.is_some()
+                    || output_user.is_some()
+                    || output_group.is_some())
+                .then_some(OutputPerms {
+                    mode: output_mode,
+                    user: output_user,
+                    group: output_group,
+                }),
                 schedule,
-            };
-            send_request(&mut framed, ClientRequest::AddTask(spec)).await?;
-            if let Some(resp) = framed.next().await {
-                let bytes: BytesMut = resp?;
-                handle_response(&bytes).await?;
-            }
+                timeout_secs: timeout,
+                stop_signal,
+                stop_grace_secs: stop_grace,
+                sched,
+                sandbox: sandbox.then_some(SandboxProfile {
+                    writable_paths: sandbox_rw,
+                    network: sandbox_net,
+                }),
+                locks,
+                throttle: (min_interval.is_some() || max_per_hour.is_some()).then_some(Throttle {
+                    min_interval_secs: min_interval,
+                    max_per_hour,
+                }),
+                priority,
+                triggers: mqtt
+                    .into_iter()
+                    .map(|topic| Trigger::Mqtt { topic })
+                    .chain(
+                        bridge
+                            .into_iter()
+                            .map(|subject| Trigger::Bridge { subject }),
+                    )
+                    .collect(),
+                healthcheck: (ping.is_some()
+                    || ping_start.is_some()
+                    || ping_success.is_some()
+                    || ping_failure.is_some())
+                .then_some(Healthcheck {
+                    url: ping,
+                    start: ping_start,
+                    success: ping_success,
+                    failure: ping_failure,
+                }),
+                allowed_window: window
+                    .as_deref()
+                    .map(|w| parse_window(w, outside_window))
+                    .transpose()?,
+                approval: approval.then_some(Approval {
+                    timeout_secs: approval_timeout,
+                }),
+                breaker: breaker.map(|failures| CircuitBreaker {
+                    failures,
+                    cooldown_secs: breaker_cooldown,
+                }),
+                success: (!success_exit.is_empty()
+                    || success_match.is_some()
+                    || failure_match.is_some())
+                .then_some(SuccessCriteria {
+                    exit_codes: success_exit,
+                    output_matches: success_match,
+                    output_rejects: failure_match,
+                }),
+                output_encoding,
+                cleanup: cleanup_dirs
+                    .into_iter()
+                    .map(|path| CleanupAction::RemoveDir { path })
+                    .chain(
+                        cleanup_files
+                            .into_iter()
+                            .map(|glob| CleanupAction::RemoveFiles {
+                                glob,
+                                older_than_days: cleanup_older_than,
+                            }),
+                    )
+                    .collect(),
+                exact_start,
+                protected,
+                source: None,
+            })
+        }
+        Cmd::Trash => ClientRequest::ListTrash,
+        Cmd::Restore { id } => ClientRequest::RestoreTask { id },
+        Cmd::List {
+            since_version: Some(version),
+            ..
+        } => ClientRequest::ListTasksSince { version },
+        // 預設排序送舊版的 ListTasks，較舊的伺服器也看得懂
+        Cmd::List {
+            sort: TaskSort::Id,
+            desc: false,
+            ..
+        } => ClientRequest::ListTasks,
+        Cmd::List { sort, desc, .. } => ClientRequest::ListTasksSorted {
+            sort,
+            descending: desc,
         },
-
-        Cmd::Remove { id } => {
-            send_request(&mut framed, ClientRequest::RemoveTask { id }).await?;
-            if let Some(resp) = framed.next().await {
-                let bytes: BytesMut = resp?;
-                handle_response(&bytes).await?;
-            }
+        Cmd::Search { pattern, regex, .. } => ClientRequest::Search { pattern, regex },
+        Cmd::Output { id } => ClientRequest::GetOutput { id },
+        Cmd::History { id, limit } => ClientRequest::GetHistory { id, limit },
+        Cmd::Runs {
+            since,
+            until,
+            status,
+            limit,
+        } => ClientRequest::QueryRuns {
+            since: since.as_deref().map(parse_time).transpose()?,
+            until: until.as_deref().map(parse_time).transpose()?,
+            status,
+            limit,
         },
-
-        Cmd::List => {
-            send_request(&mut framed, ClientRequest::ListTasks).await?;
-            if let Some(resp) = framed.next().await {
-                let bytes: BytesMut = resp?;
-                handle_response(&bytes).await?;
-            }
+        Cmd::Revisions { id } => ClientRequest::ListRevisions { id },
+        Cmd::Rollback {
+            id,
+            revision,
+            force,
+        } => ClientRequest::RollbackTask {
+            id,
+            revision,
+            force,
+        },
+        Cmd::Prune => ClientRequest::PruneHistory,
+        Cmd::Verify { id } => ClientRequest::VerifyOutputs { id },
+        Cmd::Outbox { action } => match action {
+            OutboxCmd::List { dead } => ClientRequest::ListOutbox { dead_only: dead },
+            OutboxCmd::Requeue { ids } => ClientRequest::RequeueNotifications { ids },
+        },
+        Cmd::Queue => ClientRequest::GetQueueStats,
+        Cmd::Info => ClientRequest::GetServerInfo,
+        Cmd::Deps => ClientRequest::GetDependencyGraph,
+        Cmd::Quarantine { action } => match action.unwrap_or(QuarantineCmd::List) {
+            QuarantineCmd::List => ClientRequest::ListQuarantine,
+            QuarantineCmd::Discard { seq } => ClientRequest::DiscardQuarantined { seq },
         },
+        Cmd::Maintenance { action } => match action.unwrap_or(MaintenanceCmd::List) {
+            MaintenanceCmd::List => ClientRequest::ListMaintenance,
+            MaintenanceCmd::Pause { id, from, until } => ClientRequest::ScheduleMaintenance {
+                task_id: id,
+                action: MaintenanceKind::Pause {
+                    until: parse_time(&until)?,
+                },
+                at: match from {
+                    Some(from) => parse_time(&from)?,
+                    None => Local::now().fixed_offset(),
+                },
+                force: false,
+            },
+            MaintenanceCmd::Remove { id, at, force } => ClientRequest::ScheduleMaintenance {
+                task_id: id,
+                action: MaintenanceKind::Remove,
+                at: parse_time(&at)?,
+                force,
+            },
+            MaintenanceCmd::Cancel { seq } => ClientRequest::CancelMaintenance { seq },
+        },
+        Cmd::Freeze {
+            duration,
+            all,
+            reason,
+        } => ClientRequest::Freeze {
+            duration_secs: parse_duration(&duration)?.as_secs(),
+            block_triggered: all,
+            reason,
+        },
+        Cmd::Unfreeze => ClientRequest::Unfreeze,
+        Cmd::Hook { action } => match action.unwrap_or(HookCmd::List) {
+            HookCmd::List => ClientRequest::ListTriggerHooks,
+            HookCmd::Create { id, label, params } => ClientRequest::CreateTriggerHook {
+                task_id: id,
+                label,
+                params,
+            },
+            HookCmd::Revoke { id } => ClientRequest::RevokeTriggerHook { id },
+        },
+        Cmd::Chaos { action } => match action.unwrap_or(ChaosCmd::List) {
+            ChaosCmd::List => ClientRequest::ListChaos,
+            ChaosCmd::Set {
+                id,
+                probability,
+                count,
+                delay_ms,
+                exit_code,
+            } => ClientRequest::SetChaos {
+                task_id: id,
+                rule: Some(ChaosRule {
+                    probability,
+                    count,
+                    delay_ms,
+                    exit_code,
+                }),
+            },
+            ChaosCmd::Clear { id } => ClientRequest::SetChaos {
+                task_id: id,
+                rule: None,
+            },
+        },
+        Cmd::RunNow { id, meta, .. } => ClientRequest::RunNow {
+            id,
+            follow: false,
+            metadata: meta.iter().map(|m| parse_pair(m)).collect::<Result<_>>()?,
+        },
+        Cmd::ResetBreaker { id } => ClientRequest::ResetBreaker { id },
+        Cmd::CancelChained { id } => ClientRequest::CancelChained { id },
+        Cmd::RetryChain { run_id } => ClientRequest::RetryChain { run_id },
+        Cmd::Approvals => ClientRequest::ListApprovals,
+        Cmd::Approve { run_id, reject } => ClientRequest::Approve {
+            run_id,
+            approve: !reject,
+        },
+        Cmd::Vars { action } => match action.unwrap_or(VarsCmd::Show) {
+            VarsCmd::Show => ClientRequest::GetContext,
+            VarsCmd::Set { pairs, environment } => ClientRequest::UpdateContext {
+                environment,
+                set: pairs.iter().map(|p| parse_pair(p)).collect::<Result<_>>()?,
+                unset: Vec::new(),
+            },
+            VarsCmd::Unset { keys } => ClientRequest::UpdateContext {
+                environment: None,
+                set: Default::default(),
+                unset: keys,
+            },
+        },
+        Cmd::Schedules { action } => match action.unwrap_or(SchedulesCmd::List) {
+            SchedulesCmd::List => ClientRequest::ListSchedules,
+            SchedulesCmd::Set { name, schedule } => ClientRequest::SetSchedule {
+                name,
+                schedule: schedule
+                    .parse()
+                    .map_err(|e| fail(exit::USAGE, format!("排程格式錯誤：{e}")))?,
+            },
+            SchedulesCmd::Remove { name } => ClientRequest::RemoveSchedule { name },
+        },
+        Cmd::Completions { .. }
+        | Cmd::Man { .. }
+        | Cmd::Schema { .. }
+        | Cmd::Preview { .. }
+        | Cmd::Lint { .. }
+        | Cmd::Diff { .. }
+        | Cmd::Discover { .. }
+        | Cmd::Flush
+        | Cmd::Watch { .. }
+        | Cmd::Remove { .. } => {
+            unreachable!("handled before building a request")
+        }
+    };
+    Ok(req)
+}
+
+/// 用與伺服器相同的 Schedule::next_occurrence 計算，結果才不會兩邊不一致；
+/// --json 時印出換算到 tz 的 RFC 3339 時間陣列
+fn preview(expr: &str, count: usize, tz: DisplayTz, json: bool) -> Result<()> {
+    let schedule: Schedule = expr
+        .parse()
+        .map_err(|e| fail(exit::USAGE, format!("排程格式錯誤：{e}")))?;
+    let times: Vec<_> = std::iter::successors(
+        schedule.next_occurrence(chrono::Local::now().fixed_offset(), &chrono::Local),
+        |t| schedule.next_occurrence(*t, &chrono::Local),
+    )
+    .take(count)
+    .collect();
+    if json {
+        let list: Vec<_> = times
+            .iter()
+            .map(|t| tz.format(t, "%Y-%m-%dT%H:%M:%S%:z"))
+            .collect();
+        println!("{}", serde_json::to_string(&list)?);
+        return Ok(());
+    }
+    if times.is_empty() {
+        println!("（此排程接下來沒有固定的觸發時間）");
+    }
+    for t in times {
+        println!("{}", tz.full(&t));
     }
+    Ok(())
+}
 
+/// mDNS 瀏覽的等待時間（--discover）
+const DISCOVER_WINDOW: Duration = Duration::from_secs(2);
+
+async fn list_discovered(timeout: u64, json: bool) -> Result<()> {
+    let found = discover::browse(Duration::from_secs(timeout)).await?;
+    if json {
+        let list: Vec<_> = found
+            .iter()
+            .map(|f| {
+                serde_json::json!({
+                    "instance": f.instance,
+                    "addr": f.addr.to_string(),
+                    "version": f.version,
+                    "environment": f.environment,
+                })
+            })
+            .collect();
+        println!("{}", serde_json::Value::Array(list));
+        return Ok(());
+    }
+    if found.is_empty() {
+        println!("（區網上沒有找到 scheduler-server；伺服器需設定 [mdns] 且不能只綁 127.0.0.1）");
+    }
+    for f in found {
+        println!(
+            "📡 {}  {}  env={}  v{}",
+            f.instance, f.addr, f.environment, f.version
+        );
+    }
     Ok(())
 }
 
-async fn send_request(
-    framed: &mut Framed<TcpStream, LengthDelimitedCodec>,
-    req: ClientRequest,
-) -> Result<()> {
-    let bytes = serde_json::to_vec(&req)?;
-    framed.send(bytes.into()).await?;
+/// --discover：區網上恰好一台伺服器時回傳其位址
+async fn discover_one() -> Result<String> {
+    let found = discover::browse(DISCOVER_WINDOW).await?;
+    match found.as_slice() {
+        [] => Err(fail(exit::CONNECTION, "區網上沒有找到 scheduler-server")),
+        [one] => {
+            eprintln!("📡 使用 {}（{}）", one.instance, one.addr);
+            Ok(one.addr.to_string())
+        }
+        many => {
+            let names: Vec<_> = many
+                .iter()
+                .map(|f| format!("{}（{}）", f.instance, f.addr))
+                .collect();
+            Err(fail(
+                exit::USAGE,
+                format!(
+                    "找到 {} 台伺服器，請用 --connect 指定：{}",
+                    many.len(),
+                    names.join("、")
+                ),
+            ))
+        }
+    }
+}
+
+fn write_man(dir: Option<&Path>) -> Result<()> {
+    let cmd = Opts::command();
+    match dir {
+        Some(dir) => {
+            std::fs::create_dir_all(dir)?;
+            clap_mangen::generate_to(cmd, dir)?;
+            eprintln!("📖 man page 已寫入 {}", dir.display());
+        }
+        None => clap_mangen::Man::new(cmd).render(&mut std::io::stdout())?,
+    }
     Ok(())
 }
 
-async fn handle_response(bytes: &BytesMut) -> Result<()> {
-    let resp: ServerResponse = serde_json::from_slice(&bytes[..])?;
+/// 輸出方式
+#[derive(Clone, Copy)]
+struct View {
+    /// stdout 只印回應的 JSON；說明文字一律走 stderr
+    json: bool,
+    wide: bool,
+    /// 時間的時區與相對／完整顯示
+    times: TimeStyle,
+}
+
+fn handle_response(resp: ServerResponse, view: View) -> Result<()> {
+    if view.json {
+        println!("{}", serde_json::to_string(&resp)?);
+    }
     match resp {
-        ServerResponse::Added { id } => {
+        ServerResponse::Removed { ok: false } => {
+            return Err(fail(exit::NOT_FOUND, "⚠️ 找不到該任務 id，或移除失敗"));
+        }
+        ServerResponse::Error(SchedulerError::NotFound { msg }) => {
+            return Err(fail(exit::NOT_FOUND, format!("⚠️ {msg}")));
+        }
+        ServerResponse::Error(SchedulerError::CycleDetected { cycle }) => {
+            let ids: Vec<String> = cycle
+                .iter()
+                .chain(cycle.first())
+                .map(|id| format!("#{id}"))
+                .collect();
+            return Err(fail(
+                exit::SERVER,
+                format!("❌ 依賴會形成循環：{}", ids.join(" → ")),
+            ));
+        }
+        ServerResponse::Error(e) => {
+            return Err(fail(exit::SERVER, format!("❌ 伺服器錯誤：{e}")));
+        }
+        ServerResponse::Verified(checks) => {
+            if !view.json {
+                print_checks(&checks);
+            }
+            let bad = checks
+                .iter()
+                .filter(|c| c.status != CheckStatus::Ok)
+                .count();
+            if bad > 0 {
+                return Err(fail(exit::INTEGRITY, format!("❌ {bad} 筆輸出與紀錄不符")));
+            }
+        }
+        _ if view.json => {}
+        ServerResponse::Added { id, warnings } => {
             println!("✅ 任務已新增：id={}", id);
+            for w in warnings {
+                eprintln!("⚠️ {w}");
+            }
+        }
+        ServerResponse::Removed { .. } => {
+            println!("🗑️ 任務已移除");
         }
-        ServerResponse::Removed { ok } => {
-            if ok {
-                println!("🗑️ 任務已移除");
+        ServerResponse::Trash(list) => {
+            if list.is_empty() {
+                println!("（回收桶是空的）");
             } else {
-                println!("⚠️ 找不到該任務 id，或移除失敗");
+                print_trash(list, view.times);
             }
         }
+        ServerResponse::Restored { id } => {
+            println!("♻️ 任務 {id} 已還原");
+        }
         ServerResponse::Tasks(list) => {
             if list.is_empty() {
                 println!("（目前沒有任務）");
             } else {
-                print_tasks(list);
+                table::print_tasks(&list, view.wide, view.times);
+            }
+        }
+        ServerResponse::Output { content, .. } => {
+            print!("{content}");
+        }
+        ServerResponse::History(list) => {
+            if list.is_empty() {
+                println!("（沒有執行紀錄）");
+            } else {
+                print_history(list, view.times);
             }
         }
-        ServerResponse::Error(msg) => {
-            bail!("❌ 伺服器錯誤：{msg}");
+        ServerResponse::Runs(report) => {
+            if report.total == 0 {
+                println!("（沒有符合的執行）");
+            } else {
+                print_runs(report, view.times);
+            }
+        }
+        ServerResponse::Revisions(list) => {
+            if list.is_empty() {
+                println!("（沒有修訂紀錄）");
+            } else {
+                print_revisions(list, view.times);
+            }
+        }
+        ServerResponse::RolledBack { id, revision } => {
+            println!("⏪ 任務 {id} 已回滾（新修訂 r{revision}）");
+        }
+        ServerResponse::Pruned { removed } => {
+            println!("🧹 已清理 {} 筆歷史紀錄", removed);
         }
+        ServerResponse::Outbox(list) => {
+            if list.is_empty() {
+                println!("（沒有待送的通知）");
+            } else {
+                print_outbox(list, view.times);
+            }
+        }
+        ServerResponse::BreakerReset { id, was_open: true } => {
+            println!("🔌 任務 {id} 的斷路器已重設，恢復自動執行");
+        }
+        ServerResponse::BreakerReset {
+            id,
+            was_open: false,
+        } => {
+            println!("🔌 任務 {id} 的斷路器未跳脫，已清除失敗計數");
+        }
+        ServerResponse::CancelledChained { id, count: 0 } => {
+            println!("（任務 {id} 沒有排定中的延遲執行）");
+        }
+        ServerResponse::CancelledChained { id, count } => {
+            println!("⛓️ 已取消任務 {id} 的 {count} 筆延遲執行");
+        }
+        ServerResponse::Approvals(list) => {
+            if list.is_empty() {
+                println!("（沒有等待核准的 run）");
+            } else {
+                print_approvals(list, view.times);
+            }
+        }
+        ServerResponse::Decided {
+            run_id,
+            approved: true,
+        } => {
+            println!("👍 run {run_id} 已核准，流程繼續");
+        }
+        ServerResponse::Decided {
+            run_id,
+            approved: false,
+        } => {
+            println!("👎 run {run_id} 已駁回，本輪略過");
+        }
+        ServerResponse::Requeued { count } => {
+            println!("🔁 已重新排入 {} 則通知", count);
+        }
+        ServerResponse::QueueStats(stats) => {
+            println!(
+                "排隊中：{}　已丟棄：{}　已拒絕：{}",
+                stats.pending, stats.dropped, stats.rejected
+            );
+            for (id, n) in &stats.per_task {
+                println!("- task {id}: {n}");
+            }
+        }
+        ServerResponse::ServerInfo(info) => print_server_info(info, view.times),
+        ServerResponse::Quarantine(list) => {
+            if list.is_empty() {
+                println!("（沒有被隔離的紀錄）");
+            } else {
+                print_quarantine(list, view.times);
+            }
+        }
+        ServerResponse::DependencyGraph(graph) => print_dependency_graph(graph),
+        ServerResponse::QuarantineDiscarded { seq } => {
+            println!("🧹 隔離紀錄 {seq} 已刪除");
+        }
+        ServerResponse::MaintenanceScheduled { seq } => {
+            println!("🛠️ 維護已預約（seq={seq}）");
+        }
+        ServerResponse::Maintenance(list) => {
+            if list.is_empty() {
+                println!("（沒有預約的維護）");
+            } else {
+                print_maintenance(list, view.times);
+            }
+        }
+        ServerResponse::MaintenanceCancelled { seq } => {
+            println!("🛠️ 維護預約 {seq} 已取消");
+        }
+        ServerResponse::Frozen(f) => {
+            println!(
+                "🧊 已凍結到 {}（{}）",
+                view.times.at(&f.until),
+                freeze_scope(&f)
+            );
+        }
+        ServerResponse::Unfrozen { was_frozen: true } => println!("🌤️ 已解除凍結"),
+        ServerResponse::Unfrozen { was_frozen: false } => println!("（目前沒有凍結）"),
+        ServerResponse::TriggerHookCreated { hook, token } => {
+            println!(
+                "🪝 任務 {} 的觸發網址已建立（hook={}）",
+                hook.task_id, hook.id
+            );
+            println!("token（只顯示這一次）：{token}");
+            let body = if hook.params.is_empty() {
+                String::new()
+            } else {
+                let fields: Vec<String> = hook
+                    .params
+                    .iter()
+                    .map(|p| format!("\"{p}\": \"...\""))
+                    .collect();
+                format!(" -d '{{{}}}'", fields.join(", "))
+            };
+            println!(
+                "呼叫方式：curl -X POST -H 'Authorization: Bearer {token}'{body} http://<trigger_hooks 位址>/hooks/{}",
+                hook.id
+            );
+        }
+        ServerResponse::TriggerHooks(list) => {
+            if list.is_empty() {
+                println!("（沒有觸發網址）");
+            } else {
+                print_trigger_hooks(list, view.times);
+            }
+        }
+        ServerResponse::TriggerHookRevoked { id } => {
+            println!("🪝 觸發網址 {id} 已撤銷");
+        }
+        ServerResponse::ChaosSet { task_id } => {
+            println!("🐒 任務 {task_id} 的故障注入規則已更新");
+        }
+        ServerResponse::Chaos(list) => {
+            if list.is_empty() {
+                println!("（沒有故障注入規則）");
+            } else {
+                print_chaos(list);
+            }
+        }
+        ServerResponse::TaskChanges {
+            version,
+            full,
+            changed,
+            removed,
+        } => {
+            if full {
+                println!("📋 版本 {version}（完整清單）");
+            } else {
+                println!("📋 版本 {version}");
+            }
+            if changed.is_empty() && removed.is_empty() {
+                println!("（沒有變更）");
+            }
+            if !changed.is_empty() {
+                table::print_tasks(&changed, view.wide, view.times);
+            }
+            if !removed.is_empty() {
+                println!("已移除：{removed:?}");
+            }
+        }
+        ServerResponse::Schedules(list) => {
+            if list.is_empty() {
+                println!("（沒有具名排程）");
+            } else {
+                print_schedules(list);
+            }
+        }
+        ServerResponse::ScheduleSet { name, tasks } => {
+            println!("📅 具名排程 {name} 已設定，{tasks} 個任務重新排定");
+        }
+        ServerResponse::Triggered { id } => {
+            println!("▶️ 任務 {id} 已開始執行");
+        }
+        ServerResponse::ChainRetried { run_id, tasks } => {
+            println!("🔁 run {run_id} 的依賴鏈已開始重跑任務 {tasks:?}");
+        }
+        ServerResponse::ScheduleRemoved { name } => {
+            println!("📅 具名排程 {name} 已刪除");
+        }
+        ServerResponse::Context(ctx) => {
+            println!("server.hostname    = {}", ctx.hostname);
+            println!("server.environment = {}", ctx.environment);
+            for (k, v) in &ctx.vars {
+                println!("vars.{k} = {v}");
+            }
+        }
+        ServerResponse::Event(_) | ServerResponse::Heartbeat { .. } => {}
+        // 較新的伺服器；不算失敗
+        _ => eprintln!("⚠️ 伺服器的回應無法辨識，請更新 scheduler-cli"),
     }
     Ok(())
 }
 
-fn print_tasks(list: Vec<TaskInfo>) {
-    println!("=== 任務清單（共 {} 筆） ===", list.len());
-    for t in list {
-        println!("- id={} {:?}", t.id, t.spec);
-        if let Some(rr) = t.last_result {
+fn print_history(list: Vec<RunRecord>, times: TimeStyle) {
+    println!("=== 執行歷史（共 {} 筆） ===", list.len());
+    for rec in list {
+        let rr = rec.result;
+        let started = rr
+            .started_at
+            .map(|t| times.at(&t))
+            .unwrap_or_else(|| "-".to_string());
+        let took = rr
+            .started_at
+            .map(|t| tz::took(&t, &rr.finished_at))
+            .unwrap_or_else(|| "-".to_string());
+        println!(
+            "- run={} status={}{} ({:?})  start={}  end={}  took={}  stdout={}B  stderr={}B",
+            rr.run_id,
+            rr.status_code,
+            rr.exit_code
+                .map(|c| format!(" exit={c}"))
+                .unwrap_or_default(),
+            rr.outcome,
+            started,
+            times.at(&rr.finished_at),
+            took,
+            rr.stdout_len,
+            rr.stderr_len
+        );
+        if !rr.outputs.is_empty() {
+            let pairs: Vec<String> = rr.outputs.iter().map(|(k, v)| format!("{k}={v}")).collect();
+            println!("    outputs: {}", pairs.join(" "));
+        }
+        if let (Some(size), Some(sha)) = (rr.output_size, &rr.output_sha256) {
+            let kind = if rr.binary { "，二進位" } else { "" };
+            println!("    sha256: {sha}（{size}B{kind}）");
+        }
+        if let Some(u) = rr.usage {
             println!(
-                "  └─ 上次：status={}  at={}  stdout={}B  stderr={}B  -> {}",
-                rr.status_code,
-                rr.finished_at,
-                rr.stdout_len,
-                rr.stderr_len,
-                rr.wrote_to.display()
+                "    usage: cpu={}ms（user {}ms / sys {}ms）  max_rss={}KiB",
+                u.cpu_user_ms + u.cpu_system_ms,
+                u.cpu_user_ms,
+                u.cpu_system_ms,
+                u.max_rss_kb
             );
         }
+        if !rr.metadata.is_empty() {
+            let pairs: Vec<String> = rr
+                .metadata
+                .iter()
+                .map(|(k, v)| format!("{k}={v}"))
+                .collect();
+            println!("    metadata: {}", pairs.join(" "));
+        }
+        if let Some(c) = &rr.cleanup {
+            println!(
+                "    cleanup: 刪除 {} 項，釋出 {}B",
+                c.removed, c.freed_bytes
+            );
+            for e in &c.errors {
+                println!("    ⚠️ cleanup 失敗：{e}");
+            }
+        }
+    }
+}
+
+fn print_checks(checks: &[OutputCheck]) {
+    if checks.is_empty() {
+        println!("（沒有可驗證的輸出）");
+        return;
+    }
+    for c in checks {
+        let status = match &c.status {
+            CheckStatus::Ok => "✅ 相符".to_string(),
+            CheckStatus::Missing(e) => format!("❌ 無法讀取：{e}"),
+            CheckStatus::SizeMismatch { expected, actual } => {
+                format!("❌ 大小不符：應為 {expected}B，實際 {actual}B")
+            }
+            CheckStatus::HashMismatch => "❌ 內容不符（SHA-256）".to_string(),
+            _ => "❔ 無法辨識的狀態".to_string(),
+        };
+        println!(
+            "task {} run {}  {}  {status}",
+            c.task_id,
+            c.run_id,
+            c.path.display()
+        );
+    }
+}
+
+fn print_outbox(list: Vec<Notification>, times: TimeStyle) {
+    println!("=== 待送通知（共 {} 則） ===", list.len());
+    for n in list {
+        let state = if n.dead { "dead" } else { "pending" };
+        println!(
+            "- id={} [{}] event={} attempts={} next={}  url={}",
+            n.id,
+            state,
+            n.event.seq,
+            n.attempts,
+            times.at(&n.next_attempt),
+            n.url
+        );
+        if let Some(owner) = n.owner {
+            println!("    負責人：{owner}");
+        }
+        if let Some(err) = n.last_error {
+            println!("    最後錯誤：{err}");
+        }
+    }
+}
+
+fn print_runs(report: RunReport, times: TimeStyle) {
+    println!("=== 符合的執行共 {} 筆 ===", report.total);
+    for t in &report.tasks {
+        println!(
+            "- 任務 {} {}：成功 {}、失敗 {}、略過 {}（最後結束 {}）",
+            t.task_id,
+            t.label.as_deref().unwrap_or("-"),
+            t.succeeded,
+            t.failed,
+            t.skipped,
+            times.at(&t.last_finished_at)
+        );
+    }
+    if report.runs.len() < report.total {
+        println!("--- 最近 {} 筆明細 ---", report.runs.len());
+    } else {
+        println!("--- 明細 ---");
+    }
+    for rec in report.runs {
+        let rr = rec.result;
+        println!(
+            "- run={} task={} status={} ({:?})  end={}",
+            rr.run_id,
+            rec.task_id,
+            rr.status_code,
+            rr.outcome,
+            times.at(&rr.finished_at)
+        );
+    }
+}
+
+fn print_revisions(list: Vec<Revision>, times: TimeStyle) {
+    println!(
+        "=== 任務 {} 的修訂（共 {} 版） ===",
+        list[0].task_id,
+        list.len()
+    );
+    for r in list {
+        let note = match r.rollback_of {
+            Some(from) => format!("（回滾至 r{from}）"),
+            None if r.revision == 1 => "（建立）".to_string(),
+            None => String::new(),
+        };
+        println!("- r{} {} by {}{note}", r.revision, times.at(&r.at), r.actor);
+        for c in r.changes {
+            println!(
+                "    {}: {} → {}",
+                c.field,
+                c.old.as_deref().unwrap_or("-"),
+                c.new.as_deref().unwrap_or("-")
+            );
+        }
+    }
+}
+
+fn print_trash(list: Vec<TrashedTask>, times: TimeStyle) {
+    println!("=== 回收桶（共 {} 個任務） ===", list.len());
+    for t in list {
+        let name = t.spec.name.as_deref().unwrap_or(&t.spec.cmd);
+        println!(
+            "- id={} {} removed={} by {} expires={}",
+            t.id,
+            name,
+            times.at(&t.removed_at),
+            t.removed_by,
+            times.at(&t.expires_at)
+        );
+    }
+}
+
+fn print_schedules(list: Vec<NamedSchedule>) {
+    println!("=== 具名排程（共 {} 個） ===", list.len());
+    for s in list {
+        let tasks: Vec<String> = s.tasks.iter().map(|id| format!("#{id}")).collect();
+        let tasks = if tasks.is_empty() {
+            "-".to_string()
+        } else {
+            tasks.join(" ")
+        };
+        println!("- {}  {}  任務：{}", s.name, s.schedule, tasks);
     }
 }
 
+fn print_approvals(list: Vec<PendingApproval>, times: TimeStyle) {
+    println!("=== 等待核准（共 {} 筆） ===", list.len());
+    for a in list {
+        let expires = a
+            .expires_at
+            .map(|t| times.at(&t))
+            .unwrap_or_else(|| "-".to_string());
+        println!(
+            "- run={} task={} requested={} expires={}",
+            a.run_id,
+            a.task_id,
+            times.at(&a.requested_at),
+            expires
+        );
+    }
+}
+
+fn print_quarantine(list: Vec<QuarantinedTask>, times: TimeStyle) {
+    println!("=== 隔離的紀錄（共 {} 筆） ===", list.len());
+    for q in list {
+        let id = q.id.map_or_else(|| "-".to_string(), |id| id.to_string());
+        println!(
+            "- seq={} id={} at={} {}",
+            q.seq,
+            id,
+            times.at(&q.quarantined_at),
+            q.reason
+        );
+        println!("  {}", q.record);
+    }
+}
+
+fn print_maintenance(list: Vec<MaintenanceAction>, times: TimeStyle) {
+    println!("=== 預約的維護（共 {} 筆） ===", list.len());
+    for a in list {
+        let what = match &a.action {
+            MaintenanceKind::Pause { until } => format!("暫停到 {}", times.at(until)),
+            MaintenanceKind::Remove if a.force => "移除（force）".to_string(),
+            MaintenanceKind::Remove => "移除".to_string(),
+            _ => "（未知的動作）".to_string(),
+        };
+        println!(
+            "- seq={} task={} at={} {} by={}",
+            a.seq,
+            a.task_id,
+            times.at(&a.at),
+            what,
+            a.actor
+        );
+    }
+}
+
+fn print_trigger_hooks(list: Vec<TriggerHookInfo>, times: TimeStyle) {
+    println!("=== 觸發網址（共 {} 個） ===", list.len());
+    for h in list {
+        let params = if h.params.is_empty() {
+            "-".to_string()
+        } else {
+            h.params.join(",")
+        };
+        let last_used = h
+            .last_used
+            .map(|t| times.at(&t))
+            .unwrap_or_else(|| "從未".to_string());
+        println!(
+            "- hook={} task={} params={} last_used={} by={}{}",
+            h.id,
+            h.task_id,
+            params,
+            last_used,
+            h.actor,
+            h.label.map(|l| format!(" （{l}）")).unwrap_or_default()
+        );
+    }
+}
+
+fn print_chaos(list: Vec<ChaosEntry>) {
+    println!("=== 故障注入（共 {} 個任務） ===", list.len());
+    for e in list {
+        let r = &e.rule;
+        let mut what = Vec::new();
+        if r.delay_ms > 0 {
+            what.push(format!("延遲 {}ms", r.delay_ms));
+        }
+        if let Some(code) = r.exit_code {
+            what.push(format!("以結束碼 {code} 失敗"));
+        }
+        let left = r
+            .count
+            .map_or_else(|| "不限".to_string(), |n| n.to_string());
+        println!(
+            "- task={} 機率={} {} 剩餘={} 已注入={}",
+            e.task_id,
+            r.probability,
+            what.join("、"),
+            left,
+            e.injected
+        );
+    }
+}
+
+fn print_dependency_graph(graph: DependencyGraph) {
+    println!("=== 依賴表（前置 → 依賴它的任務） ===");
+    for w in &graph.dependents {
+        let deps: Vec<String> = w.dependents.iter().map(|id| format!("#{id}")).collect();
+        println!("#{} → {}", w.upstream, deps.join(", "));
+    }
+    if graph.issues.is_empty() {
+        println!("✅ 依賴表與任務規格一致");
+        return;
+    }
+    println!("=== 不一致（共 {} 筆） ===", graph.issues.len());
+    for i in graph.issues {
+        let what = match i.kind {
+            DependencyIssueKind::MissingUpstream => "前置任務不存在，永遠不會觸發",
+            DependencyIssueKind::NotRegistered => "沒有登記在依賴表中，不會觸發",
+            DependencyIssueKind::MissingDependent => "任務已不存在，卻仍登記在依賴表中",
+            DependencyIssueKind::StaleEntry => "已不再依賴此任務，卻仍登記在依賴表中",
+            DependencyIssueKind::Duplicate => "重複登記",
+            _ => "未知的問題",
+        };
+        println!("- #{} ← #{}：{}", i.task_id, i.upstream, what);
+    }
+}
+
+fn print_server_info(info: ServerInfo, times: TimeStyle) {
+    let startup = info.startup;
+    println!(
+        "scheduler-server {}，啟動於 {}",
+        info.version,
+        times.at(&info.started_at)
+    );
+    println!(
+        "載入任務：{}　無效：{}　儲存檢查：{}",
+        startup.tasks_loaded,
+        startup.invalid.len(),
+        if startup.storage_ok {
+            "正常"
+        } else {
+            "有問題"
+        }
+    );
+    for t in &startup.invalid {
+        println!("⚠️ 任務 {} 未排程：{}", t.id, t.reason);
+    }
+    if let Some(f) = &info.freeze {
+        let reason = f
+            .reason
+            .as_deref()
+            .map(|r| format!("：{r}"))
+            .unwrap_or_default();
+        println!(
+            "🧊 凍結中，到 {} 自動恢復（{}，{} 發起{reason}）",
+            times.at(&f.until),
+            freeze_scope(f),
+            f.actor
+        );
+    }
+    if startup.quarantined > 0 {
+        println!(
+            "⚠️ {} 筆紀錄無法解析，已隔離（scheduler-cli quarantine 查看）",
+            startup.quarantined
+        );
+    }
+    for o in &startup.orphans {
+        println!(
+            "♻️ 任務 {} 在上次停機時仍在執行（自 {} 起）{}",
+            o.task_id,
+            times.at(&o.running_since),
+            if o.rerun { "，已重新執行" } else { "" }
+        );
+    }
+    if !startup.storage_ok {
+        print!("{}", startup.storage_report);
+    }
+    if !startup.next_fires.is_empty() {
+        println!("=== 啟動時接下來的執行 ===");
+        for f in &startup.next_fires {
+            let name = f
+                .name
+                .as_deref()
+                .map(|n| format!(" ({n})"))
+                .unwrap_or_default();
+            println!("- {} task {}{}", times.at(&f.at), f.task_id, name);
+        }
+    }
+}
+
+/// 查詢用的時間點（本機時區）：today／yesterday 為當天零時，
+/// 12h、30m、2d、1w 為多久以前，其餘為日期、日期加時間或 RFC 3339
+fn parse_time(s: &str) -> Result<DateTime<FixedOffset>> {
+    let now = Local::now();
+    let midnight = |date: NaiveDate| local_time(date.and_time(NaiveTime::MIN));
+    match s {
+        "today" => return midnight(now.date_naive()),
+        "yesterday" => return midnight(now.date_naive() - chrono::Duration::days(1)),
+        _ => {}
+    }
+    if let Some(unit) = s.chars().last().filter(|c| "mhdw".contains(*c)) {
+        if let Ok(n) = s[..s.len() - 1].parse::<i64>() {
+            let secs = match unit {
+                'm' => 60,
+                'h' => 3600,
+                'd' => 86400,
+                _ => 7 * 86400,
+            };
+            return Ok((now - chrono::Duration::seconds(n.saturating_mul(secs))).fixed_offset());
+        }
+    }
+    if let Ok(t) = DateTime::parse_from_rfc3339(s) {
+        return Ok(t);
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        return midnight(date);
+    }
+    for fmt in ["%Y-%m-%d %H:%M", "%Y-%m-%d %H:%M:%S"] {
+        if let Ok(t) = NaiveDateTime::parse_from_str(s, fmt) {
+            return local_time(t);
+        }
+    }
+    bail!("時間格式錯誤：{s}（例如 yesterday、12h、2024-06-01、\"2024-06-01 22:00\"）")
+}
+
+/// 30s、30m、2h、1d 這類時間長度
+fn parse_duration(s: &str) -> Result<Duration> {
+    let unit = s.chars().last().unwrap_or_default();
+    let secs = match unit {
+        's' => 1,
+        'm' => 60,
+        'h' => 3600,
+        'd' => 86400,
+        _ => bail!("時間長度格式錯誤：{s}（例如 30m、2h、1d）"),
+    };
+    match s[..s.len() - 1].parse::<u64>() {
+        Ok(n) if n > 0 => Ok(Duration::from_secs(n.saturating_mul(secs))),
+        _ => bail!("時間長度格式錯誤：{s}（例如 30m、2h、1d）"),
+    }
+}
+
+fn freeze_scope(f: &FreezeStatus) -> &'static str {
+    if f.block_triggered {
+        "所有執行都略過"
+    } else {
+        "只略過定時觸發"
+    }
+}
+
+/// 本機時間轉成帶時區的時間；夏令時間重疊時取較早者
+fn local_time(t: NaiveDateTime) -> Result<DateTime<FixedOffset>> {
+    Local
+        .from_local_datetime(&t)
+        .earliest()
+        .map(|t| t.fixed_offset())
+        .with_context(|| format!("本機時區中不存在的時間：{t}"))
+}
+
+/// HH:MM-HH:MM
+fn parse_window(s: &str, outside: WindowPolicy) -> Result<TimeWindow> {
+    let Some((start, end)) = s.split_once('-') else {
+        bail!("時段請用 HH:MM-HH:MM，例如 22:00-06:00");
+    };
+    parse_daily_hhmm(start)?;
+    parse_daily_hhmm(end)?;
+    Ok(TimeWindow {
+        start: start.to_string(),
+        end: end.to_string(),
+        outside,
+    })
+}
+
 fn parse_daily_hhmm(s: &str) -> Result<(u32, u32)> {
     let parts: Vec<_> = s.split(':').collect();
     if parts.len() != 2 {
@@ -171,35 +1467,103 @@ fn parse_daily_hhmm(s: &str) -> Result<(u32, u32)> {
     Ok((hour, minute))
 }
 
+fn parse_pair(s: &str) -> Result<(String, String)> {
+    match s.split_once('=') {
+        Some((k, v)) if !k.is_empty() => Ok((k.to_string(), v.to_string())),
+        _ => bail!("請用 KEY=VALUE，收到 {s:?}"),
+    }
+}
+
+fn parse_ionice(s: &str) -> Result<IoNice> {
+    let (class, level) = match s.split_once(':') {
+        Some((c, l)) => (c, Some(l.parse::<u8>().context("ionice level")?)),
+        None => (s, None),
+    };
+    match (class, level) {
+        ("idle", None) => Ok(IoNice::Idle),
+        ("be", l) => Ok(IoNice::BestEffort {
+            level: l.unwrap_or(4),
+        }),
+        ("rt", l) => Ok(IoNice::RealTime {
+            level: l.unwrap_or(4),
+        }),
+        _ => bail!("ionice 請用 idle、be:<0-7> 或 rt:<0-7>"),
+    }
+}
+
+fn parse_cpu_list(s: &str) -> Result<Vec<usize>> {
+    let mut cpus = Vec::new();
+    for part in s.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        match part.split_once('-') {
+            Some((a, b)) => {
+                let (a, b): (usize, usize) = (a.parse().context("cpu")?, b.parse().context("cpu")?);
+                if a > b {
+                    bail!("CPU 範圍錯誤：{part}");
+                }
+                cpus.extend(a..=b);
+            }
+            None => cpus.push(part.parse().context("cpu")?),
+        }
+    }
+    Ok(cpus)
+}
+
 fn build_schedule(
     once: Option<String>,
     daily: Option<String>,
     after: Option<u64>,
     delay: u64,
+    expires: Option<u64>,
+    manual: bool,
+    schedule: Option<String>,
 ) -> Result<Schedule> {
     let mut cnt = 0;
-    if once.is_some() { cnt += 1; }
-    if daily.is_some() { cnt += 1; }
-    if after.is_some() { cnt += 1; }
+    if once.is_some() {
+        cnt += 1;
+    }
+    if daily.is_some() {
+        cnt += 1;
+    }
+    if after.is_some() {
+        cnt += 1;
+    }
+    if manual {
+        cnt += 1;
+    }
+    if schedule.is_some() {
+        cnt += 1;
+    }
 
     if cnt == 0 {
-        bail!("請至少指定一種排程：--once 或 --daily 或 --after");
+        bail!("請至少指定一種排程：--once 或 --daily 或 --after 或 --manual 或 --schedule");
     }
     if cnt > 1 {
-        bail!("--once / --daily / --after 只能擇一使用");
+        bail!("--once / --daily / --after / --manual / --schedule 只能擇一使用");
     }
 
+    // 文字排程一律交給 scheduler-core 解析，與其他前端一致
+    let parse = |text: String| {
+        text.parse::<Schedule>()
+            .map_err(|e| anyhow::anyhow!("排程格式錯誤：{e}"))
+    };
+    if let Some(s) = schedule {
+        return parse(s);
+    }
     if let Some(s) = once {
-        let dt = chrono::DateTime::parse_from_rfc3339(&s)
-            .with_context(|| format!("解析 RFC3339 失敗：{s}"))?;
-        return Ok(Schedule::Once(dt));
+        return parse(format!("once {s}"));
     }
     if let Some(s) = daily {
-        let (h, m) = parse_daily_hhmm(&s)?;
-        return Ok(Schedule::Daily { hour: h, minute: m });
+        return parse(format!("daily {s}"));
     }
     if let Some(id) = after {
-        return Ok(Schedule::After { task_id: id, delay_secs: delay });
+        return Ok(Schedule::After {
+            task_id: id,
+            delay_secs: delay,
+            expires_after_secs: expires,
+        });
+    }
+    if manual {
+        return Ok(Schedule::Manual);
     }
     unreachable!()
 }
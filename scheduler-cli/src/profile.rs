@@ -0,0 +1,77 @@
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::{collections::HashMap, path::PathBuf};
+
+/// 沒有任何設定時的伺服器位址
+pub const DEFAULT_CONNECT: &str = "127.0.0.1:7878";
+
+/// ~/.config/scheduler/config.toml
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CliConfig {
+    /// 未指定 --profile 時使用的 profile
+    pub default_profile: Option<String>,
+    pub profiles: HashMap<String, Profile>,
+}
+
+/// 一組連線環境的預設值；命令列參數優先
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Profile {
+    /// 伺服器位址
+    pub connect: Option<String>,
+    /// 預設輸出格式
+    pub output: Option<OutputFormat>,
+    /// 顯示時間用的時區（local、utc 或 IANA 名稱）
+    pub tz: Option<String>,
+    pub connect_timeout_secs: Option<u64>,
+    pub request_timeout_secs: Option<u64>,
+    /// 連線失敗時的重試次數
+    pub retries: Option<u32>,
+    /// 伺服器上的環境名稱
+    pub environment: Option<String>,
+    /// 該環境的 token
+    pub env_token: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    Table,
+    Wide,
+    Json,
+}
+
+/// 設定檔位置：$XDG_CONFIG_HOME/scheduler/config.toml，否則 ~/.config/scheduler/config.toml
+pub fn config_path() -> Option<PathBuf> {
+    let base = match std::env::var_os("XDG_CONFIG_HOME").filter(|v| !v.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+    };
+    Some(base.join("scheduler").join("config.toml"))
+}
+
+/// 讀設定檔並挑出要用的 profile；沒有設定檔時回傳空 profile
+pub fn resolve(name: Option<&str>) -> Result<Profile> {
+    let path = config_path();
+    let cfg = match &path {
+        Some(p) if p.exists() => {
+            let text =
+                std::fs::read_to_string(p).with_context(|| format!("讀取 {}", p.display()))?;
+            toml::from_str::<CliConfig>(&text).with_context(|| format!("解析 {}", p.display()))?
+        }
+        _ => CliConfig::default(),
+    };
+
+    let Some(name) = name.or(cfg.default_profile.as_deref()) else {
+        return Ok(Profile::default());
+    };
+    match cfg.profiles.get(name) {
+        Some(p) => Ok(p.clone()),
+        None => bail!(
+            "找不到 profile {name:?}（設定檔：{}）",
+            path.map(|p| p.display().to_string())
+                .unwrap_or_else(|| "無".to_string())
+        ),
+    }
+}
@@ -0,0 +1,49 @@
+use anyhow::Result;
+use clap::ValueEnum;
+use scheduler_core::{
+    ClientRequest, Event, RequestFrame, ResponseFrame, Schedule, ServerResponse, TaskSpec,
+};
+use schemars::{schema::RootSchema, schema_for};
+
+/// 可匯出 schema 的型別
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SchemaType {
+    TaskSpec,
+    Schedule,
+    ClientRequest,
+    ServerResponse,
+    RequestFrame,
+    ResponseFrame,
+    Event,
+}
+
+impl SchemaType {
+    fn root(self) -> RootSchema {
+        match self {
+            Self::TaskSpec => schema_for!(TaskSpec),
+            Self::Schedule => schema_for!(Schedule),
+            Self::ClientRequest => schema_for!(ClientRequest),
+            Self::ServerResponse => schema_for!(ServerResponse),
+            Self::RequestFrame => schema_for!(RequestFrame),
+            Self::ResponseFrame => schema_for!(ResponseFrame),
+            Self::Event => schema_for!(Event),
+        }
+    }
+}
+
+/// 印出單一型別的 JSON Schema；未指定時印出以型別名稱為鍵、包含全部型別的物件
+pub fn print(ty: Option<SchemaType>) -> Result<()> {
+    let doc = match ty {
+        Some(ty) => serde_json::to_value(ty.root())?,
+        None => {
+            let mut all = serde_json::Map::new();
+            for ty in SchemaType::value_variants() {
+                let name = ty.to_possible_value().map(|v| v.get_name().to_string());
+                all.insert(name.unwrap_or_default(), serde_json::to_value(ty.root())?);
+            }
+            serde_json::Value::Object(all)
+        }
+    };
+    println!("{}", serde_json::to_string_pretty(&doc)?);
+    Ok(())
+}
@@ -0,0 +1,117 @@
+use crate::exit::{self, fail};
+use anyhow::{Context, Result};
+use scheduler_core::{ManifestTask, TaskSpec};
+use std::{io::Read, path::Path};
+
+/// 讀取 `add -f` 的任務規格：.json 以 JSON 解析，其餘（.yaml、.yml、`-` 標準輸入）
+/// 以 YAML 解析，YAML 也接受 JSON 內容；有不認得的欄位時不送出
+pub fn read(path: &Path) -> Result<TaskSpec> {
+    let (label, value) = load(path)?;
+    let (spec, unknown) = parse_spec(value)
+        .map_err(|e| fail(exit::USAGE, format!("任務規格 {label} 格式錯誤：{e}")))?;
+    if !unknown.is_empty() {
+        return Err(fail(
+            exit::USAGE,
+            format!("任務規格 {label} 有不認得的欄位：{}", unknown.join(", ")),
+        ));
+    }
+    Ok(spec)
+}
+
+/// 讀取 git-sync 清單（含 tasks 陣列，或直接是任務陣列），每個任務都要有 key；
+/// 有不認得的欄位時不送出
+pub fn read_manifest(path: &Path) -> Result<Vec<ManifestTask>> {
+    let (label, value) = load(path)?;
+    let items = match value {
+        serde_json::Value::Array(items) => items,
+        serde_json::Value::Object(mut map) => match map.remove("tasks") {
+            Some(serde_json::Value::Array(items)) => items,
+            Some(_) => {
+                return Err(fail(
+                    exit::USAGE,
+                    format!("清單 {label} 的 tasks 必須是陣列"),
+                ))
+            }
+            None => Vec::new(),
+        },
+        _ => return Err(fail(exit::USAGE, format!("清單 {label} 必須是 tasks 陣列"))),
+    };
+    let mut tasks = Vec::new();
+    for (i, mut item) in items.into_iter().enumerate() {
+        let key = match &mut item {
+            serde_json::Value::Object(map) => map.remove("key"),
+            _ => None,
+        };
+        let Some(serde_json::Value::String(key)) = key else {
+            return Err(fail(
+                exit::USAGE,
+                format!("清單 {label} 的第 {} 個任務缺少 key", i + 1),
+            ));
+        };
+        let (spec, unknown) = parse_spec(item).map_err(|e| {
+            fail(
+                exit::USAGE,
+                format!("清單 {label} 的任務 {key} 格式錯誤：{e}"),
+            )
+        })?;
+        if !unknown.is_empty() {
+            return Err(fail(
+                exit::USAGE,
+                format!(
+                    "清單 {label} 的任務 {key} 有不認得的欄位：{}",
+                    unknown.join(", ")
+                ),
+            ));
+        }
+        tasks.push(ManifestTask { key, spec });
+    }
+    Ok(tasks)
+}
+
+/// 讀取規格檔為 JSON 值（副檔名規則同 read，另接受 git-sync 清單的 .toml）；
+/// 回傳訊息中用的檔名
+pub fn load(path: &Path) -> Result<(String, serde_json::Value)> {
+    let (label, text) = if path.as_os_str() == "-" {
+        let mut text = String::new();
+        std::io::stdin()
+            .read_to_string(&mut text)
+            .context("讀取標準輸入")?;
+        ("標準輸入".to_string(), text)
+    } else {
+        let text =
+            std::fs::read_to_string(path).with_context(|| format!("讀取 {}", path.display()))?;
+        (path.display().to_string(), text)
+    };
+    let value = match path.extension().and_then(|e| e.to_str()) {
+        Some("json") => serde_json::from_str::<serde_json::Value>(&text).map_err(|e| e.to_string()),
+        Some("toml") => toml::from_str::<serde_json::Value>(&text).map_err(|e| e.to_string()),
+        _ => serde_yaml::from_str::<serde_json::Value>(&text).map_err(|e| e.to_string()),
+    };
+    let value = value.map_err(|e| fail(exit::USAGE, format!("規格檔 {label} 格式錯誤：{e}")))?;
+    Ok((label, value))
+}
+
+/// 解析一個任務規格，一併回傳不認得的欄位（拼錯的欄位會被直接略過，送出前先擋下）
+pub fn parse_spec(value: serde_json::Value) -> Result<(TaskSpec, Vec<String>), String> {
+    let mut unknown = Vec::new();
+    let spec = serde_ignored::deserialize(&value, |p| unknown.push(field_path(&p)))
+        .map_err(|e| e.to_string())?;
+    Ok((spec, unknown))
+}
+
+/// 欄位路徑，例如 `healthcheck.sucess`
+fn field_path(path: &serde_ignored::Path) -> String {
+    use serde_ignored::Path;
+    let (parent, seg) = match path {
+        Path::Root => return String::new(),
+        Path::Map { parent, key } => (*parent, key.clone()),
+        Path::Seq { parent, index } => (*parent, index.to_string()),
+        Path::Some { parent }
+        | Path::NewtypeStruct { parent }
+        | Path::NewtypeVariant { parent } => return field_path(parent),
+    };
+    match field_path(parent) {
+        p if p.is_empty() => seg,
+        p => format!("{p}.{seg}"),
+    }
+}
@@ -0,0 +1,57 @@
+use anyhow::{Context, Result};
+use scheduler_core::ClientRequest;
+use std::path::{Path, PathBuf};
+
+/// 預設暫存目錄：$XDG_STATE_HOME/scheduler/spool，否則 ~/.local/state/scheduler/spool
+pub fn default_dir() -> Option<PathBuf> {
+    let base = match std::env::var_os("XDG_STATE_HOME").filter(|v| !v.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(std::env::var_os("HOME")?)
+            .join(".local")
+            .join("state"),
+    };
+    Some(base.join("scheduler").join("spool"))
+}
+
+/// 伺服器連不上時先存起來；檔名依時間排序，flush 時照原順序重送
+pub fn save(dir: &Path, req: &ClientRequest) -> Result<PathBuf> {
+    std::fs::create_dir_all(dir).with_context(|| format!("建立 {}", dir.display()))?;
+    let stamp = chrono::Local::now().format("%Y%m%dT%H%M%S%.6f");
+    let path = dir.join(format!("{stamp}-{}.json", std::process::id()));
+    // 先寫暫存檔再改名，flush 不會讀到寫一半的檔案
+    let tmp = path.with_extension("tmp");
+    std::fs::write(&tmp, serde_json::to_vec_pretty(req)?)
+        .with_context(|| format!("寫入 {}", tmp.display()))?;
+    std::fs::rename(&tmp, &path)?;
+    Ok(path)
+}
+
+/// 待重送的請求（舊到新）
+pub fn pending(dir: &Path) -> Result<Vec<PathBuf>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(dir).with_context(|| format!("讀取 {}", dir.display()))? {
+        let path = entry?.path();
+        if path.is_file() && path.extension().is_some_and(|e| e == "json") {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+pub fn load(path: &Path) -> Result<ClientRequest> {
+    let bytes = std::fs::read(path).with_context(|| format!("讀取 {}", path.display()))?;
+    serde_json::from_slice(&bytes).with_context(|| format!("解析 {}", path.display()))
+}
+
+/// 伺服器拒絕的請求移到 failed/，不再擋住後面的請求
+pub fn mark_failed(dir: &Path, path: &Path) -> Result<PathBuf> {
+    let failed = dir.join("failed");
+    std::fs::create_dir_all(&failed)?;
+    let dest = failed.join(path.file_name().unwrap_or_default());
+    std::fs::rename(path, &dest)?;
+    Ok(dest)
+}
@@ -0,0 +1,252 @@
+use crate::tz::{self, DisplayTz, TimeStyle};
+use scheduler_core::{Progress, RunOutcome, RunResult, Schedule, TaskInfo};
+use std::io::IsTerminal;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// 無法取得終端機寬度（例如輸出導向檔案）時不截斷
+const FALLBACK_WIDTH: usize = usize::MAX;
+
+/// 被壓縮欄位的最小寬度
+const MIN_SHRINK: usize = 8;
+
+#[derive(Clone, Copy)]
+enum Color {
+    Green,
+    Red,
+    Yellow,
+}
+
+struct Cell {
+    text: String,
+    color: Option<Color>,
+}
+
+impl Cell {
+    fn plain(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            color: None,
+        }
+    }
+}
+
+/// 以對齊的表格列出任務；wide 顯示完整欄位且不截斷
+pub fn print_tasks(list: &[TaskInfo], wide: bool, times: TimeStyle) {
+    let mut header = vec!["ID", "NAME", "SCHEDULE", "NEXT RUN", "LAST", "DURATION"];
+    if wide {
+        header.extend(["NAMESPACE", "OWNER", "TAGS", "LOCKS", "COMMAND", "OUTPUT"]);
+    }
+
+    let rows: Vec<Vec<Cell>> = list.iter().map(|t| row(t, wide, times)).collect();
+    let mut widths: Vec<usize> = header.iter().map(|h| h.width()).collect();
+    for r in &rows {
+        for (w, c) in widths.iter_mut().zip(r) {
+            *w = (*w).max(c.text.width());
+        }
+    }
+
+    // 非 wide 時，超出終端機寬度就壓縮 NAME 欄
+    if !wide {
+        let total: usize = widths.iter().sum::<usize>() + 2 * (widths.len() - 1);
+        let term = terminal_width();
+        if total > term {
+            let over = total - term;
+            widths[1] = widths[1]
+                .saturating_sub(over)
+                .max(MIN_SHRINK.min(widths[1]));
+        }
+    }
+
+    let color = use_color();
+    let header: Vec<Cell> = header.into_iter().map(Cell::plain).collect();
+    print_row(&header, &widths, false);
+    for r in &rows {
+        print_row(r, &widths, color);
+    }
+}
+
+fn row(t: &TaskInfo, wide: bool, times: TimeStyle) -> Vec<Cell> {
+    let time_fmt = if wide {
+        "%Y-%m-%d %H:%M:%S"
+    } else {
+        "%m-%d %H:%M"
+    };
+    let last = t.last_result.as_ref();
+    let mut cells = vec![
+        Cell::plain(t.id.to_string()),
+        Cell::plain(t.spec.name.clone().unwrap_or_else(|| "-".to_string())),
+        Cell::plain(schedule_summary(&t.spec.schedule, times.tz)),
+        next_run_cell(t, time_fmt, times),
+        // 執行中且有回報進度時，LAST 欄改顯示進度
+        match &t.progress {
+            Some(p) => Cell {
+                text: progress_text(p),
+                color: Some(Color::Yellow),
+            },
+            None => status_cell(last),
+        },
+        Cell::plain(
+            last.and_then(|r| r.started_at.map(|s| tz::took(&s, &r.finished_at)))
+                .unwrap_or_else(|| "-".to_string()),
+        ),
+    ];
+    if wide {
+        let mut command = t.spec.cmd.clone();
+        for a in &t.spec.args {
+            command.push(' ');
+            command.push_str(a);
+        }
+        cells.extend([
+            Cell::plain(t.spec.namespace.clone()),
+            Cell::plain(t.spec.owner.clone().unwrap_or_default().to_string()),
+            Cell::plain(t.spec.tags.join(",")),
+            Cell::plain(t.spec.locks.join(",")),
+            Cell::plain(command),
+            Cell::plain(t.spec.output_path.display().to_string()),
+        ]);
+    }
+    cells
+}
+
+fn print_row(cells: &[Cell], widths: &[usize], color: bool) {
+    let mut line = String::new();
+    for (i, (c, &w)) in cells.iter().zip(widths).enumerate() {
+        let text = truncate(&c.text, w);
+        let pad = w - text.width();
+        match c.color.filter(|_| color) {
+            Some(col) => {
+                let code = match col {
+                    Color::Green => "32",
+                    Color::Red => "31",
+                    Color::Yellow => "33",
+                };
+                line.push_str(&format!("\x1b[{code}m{text}\x1b[0m"));
+            }
+            None => line.push_str(&text),
+        }
+        // 最後一欄不補空白
+        if i + 1 < cells.len() {
+            line.push_str(&" ".repeat(pad + 2));
+        }
+    }
+    println!("{line}");
+}
+
+/// 斷路器跳脫時標示暫停；排隊等鎖時顯示在等哪些鎖；依賴鏈排定的時間加註 (chain)
+fn next_run_cell(t: &TaskInfo, time_fmt: &str, times: TimeStyle) -> Cell {
+    if t.breaker_open_since.is_some() {
+        return Cell {
+            text: "breaker open".to_string(),
+            color: Some(Color::Red),
+        };
+    }
+    if !t.waiting_for.is_empty() {
+        return Cell {
+            text: format!("waiting {}", t.waiting_for.join(",")),
+            color: Some(Color::Yellow),
+        };
+    }
+    let Some(next) = t.next_run else {
+        return Cell::plain("-");
+    };
+    let text = times.cell(&next, time_fmt);
+    // 依賴鏈排定的延遲執行
+    if t.chained.first() == Some(&next) {
+        return Cell {
+            text: format!("{text} (chain)"),
+            color: Some(Color::Yellow),
+        };
+    }
+    Cell::plain(text)
+}
+
+fn schedule_summary(s: &Schedule, tz: DisplayTz) -> String {
+    match s {
+        Schedule::Once(t) => format!("once {}", tz.format(t, "%Y-%m-%d %H:%M")),
+        Schedule::Daily { hour, minute } => format!("daily {hour:02}:{minute:02}"),
+        Schedule::After {
+            task_id,
+            delay_secs: 0,
+            ..
+        } => format!("after #{task_id}"),
+        Schedule::After {
+            task_id,
+            delay_secs,
+            ..
+        } => format!("after #{task_id} +{delay_secs}s"),
+        Schedule::Manual => "manual".to_string(),
+        Schedule::Hourly { minute } => format!("hourly :{minute:02}"),
+        Schedule::Reboot => "@reboot".to_string(),
+        Schedule::Named { name } => format!("named {name}"),
+        _ => "unknown".to_string(),
+    }
+}
+
+/// 執行中回報的進度，例如「42% uploading」
+pub fn progress_text(p: &Progress) -> String {
+    match (p.percent, &p.phase) {
+        (Some(pct), Some(phase)) => format!("{pct}% {phase}"),
+        (Some(pct), None) => format!("{pct}%"),
+        (None, Some(phase)) => phase.clone(),
+        (None, None) => "running".to_string(),
+    }
+}
+
+fn status_cell(last: Option<&RunResult>) -> Cell {
+    let Some(r) = last else {
+        return Cell::plain("-");
+    };
+    let (text, color) = match r.outcome {
+        // exit_code 有值表示被任務的 success 條件改判
+        RunOutcome::Exited if r.status_code == 0 => match r.exit_code {
+            Some(code) => (format!("ok (exit {code})"), Color::Green),
+            None => ("ok".to_string(), Color::Green),
+        },
+        RunOutcome::Exited if r.exit_code.is_some() => ("check failed".to_string(), Color::Red),
+        RunOutcome::Exited => (format!("exit {}", r.status_code), Color::Red),
+        RunOutcome::TimedOut => ("timed out".to_string(), Color::Red),
+        RunOutcome::Lost => ("lost".to_string(), Color::Yellow),
+        RunOutcome::Cancelled => ("cancelled".to_string(), Color::Yellow),
+        RunOutcome::Orphaned => ("orphaned".to_string(), Color::Yellow),
+        RunOutcome::Skipped => ("skipped".to_string(), Color::Yellow),
+        _ => ("unknown".to_string(), Color::Yellow),
+    };
+    Cell {
+        text,
+        color: Some(color),
+    }
+}
+
+/// 依顯示寬度截斷（CJK 字元佔兩格），超出時以 … 結尾
+fn truncate(s: &str, width: usize) -> String {
+    if s.width() <= width {
+        return s.to_string();
+    }
+    let mut out = String::new();
+    let mut used = 0;
+    for ch in s.chars() {
+        let w = ch.width().unwrap_or(0);
+        if used + w + 1 > width {
+            break;
+        }
+        out.push(ch);
+        used += w;
+    }
+    out.push('…');
+    out
+}
+
+fn terminal_width() -> usize {
+    if let Some((terminal_size::Width(w), _)) = terminal_size::terminal_size() {
+        return usize::from(w);
+    }
+    std::env::var("COLUMNS")
+        .ok()
+        .and_then(|c| c.parse().ok())
+        .unwrap_or(FALLBACK_WIDTH)
+}
+
+/// 只在輸出到終端機且未設定 NO_COLOR 時上色
+pub fn use_color() -> bool {
+    std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none()
+}
@@ -0,0 +1,108 @@
+use chrono::{DateTime, FixedOffset, Local, Utc};
+use chrono_tz::Tz;
+use std::str::FromStr;
+
+/// 顯示時間用的時區；伺服器送來的時間帶的是伺服器的固定偏移，
+/// 在客戶端換算成操作者習慣的時區
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum DisplayTz {
+    /// 本機時區
+    #[default]
+    Local,
+    Utc,
+    /// IANA 時區名稱，例如 Asia/Taipei
+    Zone(Tz),
+}
+
+impl FromStr for DisplayTz {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "local" => Ok(Self::Local),
+            "utc" | "z" => Ok(Self::Utc),
+            _ => s.parse::<Tz>().map(Self::Zone).map_err(|_| {
+                format!("未知的時區 {s:?}（可用 local、utc 或 IANA 名稱如 Asia/Taipei）")
+            }),
+        }
+    }
+}
+
+impl DisplayTz {
+    /// 依 fmt 格式化 t（先換算到這個時區）
+    pub fn format(self, t: &DateTime<FixedOffset>, fmt: &str) -> String {
+        match self {
+            Self::Local => t.with_timezone(&Local).format(fmt).to_string(),
+            Self::Utc => t.with_timezone(&Utc).format(fmt).to_string(),
+            Self::Zone(tz) => t.with_timezone(&tz).format(fmt).to_string(),
+        }
+    }
+
+    /// 完整時間，附上偏移量以免混淆
+    pub fn full(self, t: &DateTime<FixedOffset>) -> String {
+        self.format(t, "%Y-%m-%d %H:%M:%S %:z")
+    }
+}
+
+/// 時間的顯示方式：預設以相對現在的說法（3 minutes ago、in 2h 13m）方便一眼看懂，
+/// raw 時印出換算時區後的完整時間
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TimeStyle {
+    pub tz: DisplayTz,
+    pub raw: bool,
+}
+
+impl TimeStyle {
+    /// 單獨列出的時間點
+    pub fn at(self, t: &DateTime<FixedOffset>) -> String {
+        if self.raw {
+            self.tz.full(t)
+        } else {
+            relative(t.signed_duration_since(Utc::now()).num_seconds())
+        }
+    }
+
+    /// 表格欄位；raw 時依 fmt 格式化
+    pub fn cell(self, t: &DateTime<FixedOffset>, fmt: &str) -> String {
+        if self.raw {
+            self.tz.format(t, fmt)
+        } else {
+            relative(t.signed_duration_since(Utc::now()).num_seconds())
+        }
+    }
+}
+
+/// 相對現在的說法；secs 為正表示未來
+fn relative(secs: i64) -> String {
+    match secs {
+        -4..=4 => "now".to_string(),
+        s if s > 0 => format!("in {}", span(s)),
+        s => format!("{} ago", span(-s)),
+    }
+}
+
+/// 一分鐘內以秒、一小時內以分鐘計，再長就取兩個最大的單位
+fn span(secs: i64) -> String {
+    let plural = |n: i64, unit: &str| match n {
+        1 => format!("1 {unit}"),
+        n => format!("{n} {unit}s"),
+    };
+    match secs {
+        0..=59 => plural(secs, "second"),
+        60..=3599 => plural(secs / 60, "minute"),
+        3600..=86399 => format!("{}h {}m", secs / 3600, secs % 3600 / 60),
+        _ => format!("{}d {}h", secs / 86400, secs % 86400 / 3600),
+    }
+}
+
+/// 執行花費的時間，例如 4.2s、2m 05s、1h 13m
+pub fn took(started: &DateTime<FixedOffset>, finished: &DateTime<FixedOffset>) -> String {
+    let secs = (*finished - *started).num_milliseconds().max(0) as f64 / 1000.0;
+    if secs < 60.0 {
+        format!("{secs:.1}s")
+    } else if secs < 3600.0 {
+        format!("{}m {:02}s", secs as u64 / 60, secs as u64 % 60)
+    } else {
+        format!("{}h {:02}m", secs as u64 / 3600, secs as u64 % 3600 / 60)
+    }
+}
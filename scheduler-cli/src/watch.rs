@@ -0,0 +1,189 @@
+use crate::{
+    client::{backoff, Client, NetOptions},
+    exit::{self, fail},
+    table,
+    tz::DisplayTz,
+};
+use anyhow::Result;
+use scheduler_core::{ClientRequest, Event, EventKind, RunOutcome, ServerResponse};
+use std::{collections::BTreeMap, time::Duration};
+
+/// 尚未收到心跳前假設的間隔（與伺服器預設相同）
+const DEFAULT_HEARTBEAT: Duration = Duration::from_secs(15);
+/// 連續漏掉幾次心跳就視為斷線
+const MISSED_HEARTBEATS: u32 = 3;
+
+/// 持續印出伺服器事件；斷線或心跳逾時就帶著 cursor 重新訂閱，補回中間的事件
+pub async fn watch(
+    connect: &str,
+    net: NetOptions,
+    since: Option<u64>,
+    json: bool,
+    tz: DisplayTz,
+) -> Result<()> {
+    let mut cursor = since;
+    let mut failures = 0;
+    loop {
+        let err = match Client::connect(connect, net.clone()).await {
+            Ok(mut client) => follow(&mut client, &mut cursor, &mut failures, json, tz).await,
+            Err(e) => e,
+        };
+        if exit::code_of(&err) != exit::CONNECTION {
+            return Err(err);
+        }
+        let wait = backoff(failures);
+        failures += 1;
+        match cursor {
+            Some(seq) => eprintln!(
+                "🔌 事件串流中斷（{err:#}），{}ms 後從 #{seq} 之後重新訂閱",
+                wait.as_millis()
+            ),
+            None => eprintln!(
+                "🔌 事件串流中斷（{err:#}），{}ms 後重新訂閱",
+                wait.as_millis()
+            ),
+        }
+        tokio::time::sleep(wait).await;
+    }
+}
+
+/// 跟隨一條訂閱連線，直到出錯
+async fn follow(
+    client: &mut Client,
+    cursor: &mut Option<u64>,
+    failures: &mut u32,
+    json: bool,
+    tz: DisplayTz,
+) -> anyhow::Error {
+    if let Err(e) = client
+        .send(ClientRequest::Subscribe { since: *cursor })
+        .await
+    {
+        return e;
+    }
+    let mut wait = DEFAULT_HEARTBEAT * MISSED_HEARTBEATS;
+    loop {
+        let resp = match client.recv(wait).await {
+            Ok(resp) => resp,
+            Err(e) => return e,
+        };
+        *failures = 0;
+        match resp {
+            ServerResponse::Heartbeat {
+                last_seq,
+                interval_secs,
+            } => {
+                wait = Duration::from_secs(interval_secs.max(1)) * MISSED_HEARTBEATS;
+                match *cursor {
+                    // 伺服器的編號比我們的 cursor 還小：伺服器已重啟，從頭接續
+                    Some(c) if last_seq < c => {
+                        eprintln!("⚠️ 伺服器事件編號已重置（#{c} → #{last_seq}），可能已重新啟動");
+                        *cursor = Some(last_seq);
+                    }
+                    Some(_) => {}
+                    None => *cursor = Some(last_seq),
+                }
+            }
+            ServerResponse::Event(ev) => {
+                if let Some(last) = *cursor {
+                    if ev.seq <= last {
+                        continue;
+                    }
+                    if ev.seq > last + 1 {
+                        eprintln!(
+                            "⚠️ 事件 #{}..#{} 已不在伺服器保留範圍內",
+                            last + 1,
+                            ev.seq - 1
+                        );
+                    }
+                }
+                *cursor = Some(ev.seq);
+                print_event(&ev, json, tz);
+            }
+            ServerResponse::Error(e) => return fail(exit::SERVER, format!("❌ 伺服器錯誤：{e}")),
+            _ => {}
+        }
+    }
+}
+
+fn print_event(ev: &Event, json: bool, tz: DisplayTz) {
+    if json {
+        if let Ok(line) = serde_json::to_string(ev) {
+            println!("{line}");
+        }
+        return;
+    }
+    let at = tz.format(&ev.at, "%m-%d %H:%M:%S");
+    let text = match &ev.kind {
+        EventKind::TaskAdded { task_id } => format!("➕ 任務 {task_id} 已新增"),
+        EventKind::TaskRemoved { task_id } => format!("🗑️ 任務 {task_id} 已移除"),
+        EventKind::TaskUpdated { task_id, revision } => {
+            format!("✏️ 任務 {task_id} 已更新（r{revision}）")
+        }
+        EventKind::RunStarted {
+            task_id,
+            run_id,
+            metadata,
+        } => {
+            format!(
+                "▶️ 任務 {task_id} 開始執行（run {run_id}）{}",
+                describe_metadata(metadata)
+            )
+        }
+        EventKind::RunFinished {
+            task_id,
+            run_id,
+            status_code,
+            outcome,
+            ..
+        } => match outcome {
+            RunOutcome::Exited if *status_code == 0 => {
+                format!("✅ 任務 {task_id} 執行成功（run {run_id}）")
+            }
+            RunOutcome::Exited => {
+                format!("❌ 任務 {task_id} 結束碼 {status_code}（run {run_id}）")
+            }
+            other => format!("⚠️ 任務 {task_id} {other:?}（run {run_id}）"),
+        },
+        EventKind::RunSkipped { task_id, reason } => {
+            format!("⏭️ 任務 {task_id} 本次略過：{reason}")
+        }
+        EventKind::RunKilled {
+            task_id,
+            run_id,
+            reason,
+        } => format!("🛑 watchdog 終止任務 {task_id}（run {run_id}，{reason:?}）"),
+        EventKind::BreakerOpened { task_id, failures } => {
+            format!("🔌 任務 {task_id} 連續失敗 {failures} 次，斷路器跳脫，暫停自動執行")
+        }
+        EventKind::ApprovalRequested {
+            task_id, run_id, ..
+        } => format!("✋ 任務 {task_id} 等待核准（approve --run {run_id}）"),
+        EventKind::RunProgress {
+            task_id, progress, ..
+        } => format!("⏳ 任務 {task_id} 進度：{}", table::progress_text(progress)),
+        EventKind::DigestReady { digest } => format!(
+            "📰 彙總：失敗 {} 個任務、逾時 {} 個、略過 {} 個",
+            digest.failures.len(),
+            digest.sla_breaches.len(),
+            digest.skipped.len()
+        ),
+        EventKind::ServerStarted { report } => format!(
+            "🚀 伺服器啟動：載入 {} 個任務，無效 {} 個，中斷的 run {} 個",
+            report.tasks_loaded,
+            report.invalid.len(),
+            report.orphans.len()
+        ),
+        _ => "❔ 無法辨識的事件（請更新 scheduler-cli）".to_string(),
+    };
+    println!("[{at}] #{} {text}", ev.seq);
+}
+
+/// 觸發者附上的追查資訊，例如 " [commit=3f2c1d0 ticket=OPS-12]"；沒有時為空字串
+fn describe_metadata(metadata: &BTreeMap<String, String>) -> String {
+    if metadata.is_empty() {
+        return String::new();
+    }
+    let pairs: Vec<String> = metadata.iter().map(|(k, v)| format!("{k}={v}")).collect();
+    format!(" [{}]", pairs.join(" "))
+}
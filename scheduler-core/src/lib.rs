@@ -1,60 +1,1985 @@
-use chrono::{DateTime, FixedOffset};
-use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+//! 伺服器與客戶端共用的協定型別
+//!
+//! 相容性約定：較舊的客戶端必須能連上較新的伺服器。
+//!
+//! - 新欄位一律加 `#[serde(default)]`，舊資料與舊客戶端省略時仍可解析；
+//!   不認得的欄位直接忽略；唯一的例外是 AddTask 的任務規格，伺服器預設拒絕
+//!   不認得的欄位以抓出拼錯的設定（伺服器設定 `spec_mode = "lenient"` 時忽略）。
+//! - 會出現在回應中、之後可能再新增變體的列舉（[`ServerResponse`]、
+//!   [`SchedulerError`]、[`EventKind`]、[`Schedule`]、[`Trigger`]、
+//!   [`RunOutcome`]、[`CheckStatus`]）標為 `#[non_exhaustive]`，
+//!   並有 `Unknown` 變體承接看不懂的內容，而不是整個回應解析失敗。
+//! - [`ClientRequest`] 也是 `#[non_exhaustive]`，但沒有 `Unknown`：
+//!   伺服器收到看不懂的請求時回 [`SchedulerError::InvalidRequest`]。
+//! - 伺服器不接受任何 `Unknown`（新增任務時回 [`SchedulerError::InvalidSchedule`]
+//!   或 [`SchedulerError::InvalidRequest`]）。
+//! - 既有變體與欄位不改名、不刪除；真的要改時新增一個。
+//! - 所有型別都產生 JSON Schema（`scheduler-cli schema`）；`Unknown` 變體不列入，
+//!   schema 只描述目前認得的內容。
+
+use chrono::{DateTime, Duration, FixedOffset, LocalResult, NaiveDateTime, TimeZone, Timelike};
+use schemars::JsonSchema;
+use serde::{de::IgnoredAny, Deserialize, Deserializer, Serialize, Serializer};
+use std::{collections::BTreeMap, path::PathBuf};
+
+/// 較新版本才有、這個版本看不懂的內容；解析時整個略過，序列化為 null
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Unrecognized;
+
+impl Serialize for Unrecognized {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_unit()
+    }
+}
+
+impl<'de> Deserialize<'de> for Unrecognized {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        IgnoredAny::deserialize(d)?;
+        Ok(Unrecognized)
+    }
+}
 
 /// 任務排程
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[non_exhaustive]
 pub enum Schedule {
     /// 一次性（RFC3339 帶時區）
     Once(DateTime<FixedOffset>),
     /// 每日固定時間（本地時間）
     Daily { hour: u32, minute: u32 },
-    /// 任務依賴：當 task_id 完成後觸發；可選延遲秒數
-    After { task_id: u64, delay_secs: u64 },
+    /// 任務依賴：當 task_id 完成後觸發；可選延遲秒數。
+    /// expires_after_secs：前置任務開始後這麼久仍未完成，本輪就略過
+    After {
+        task_id: u64,
+        delay_secs: u64,
+        #[serde(default)]
+        expires_after_secs: Option<u64>,
+    },
+    /// 不自動排程，只由 triggers（MQTT、Redis/NATS）啟動
+    Manual,
+    /// 每小時的第 minute 分（本地時間）
+    Hourly { minute: u32 },
+    /// 伺服器啟動時執行一次（cron 的 @reboot）；新增當下不執行
+    Reboot,
+    /// 引用伺服器上的具名排程（SetSchedule）；改動具名排程即套用到所有引用它的任務
+    Named { name: String },
+    /// 較新伺服器的排程種類；不會觸發
+    #[serde(untagged)]
+    #[schemars(skip)]
+    Unknown(Unrecognized),
+}
+
+/// 排程的文字寫法，所有前端共用：
+///
+/// - `@reboot`、`@hourly`、`@daily`、`@midnight`
+/// - `once <RFC3339>`，或直接寫 RFC3339 時間
+/// - `daily HH:MM`、`hourly :MM`
+/// - `after <id>`，可接 `+<秒>s` 延遲與 `expires <秒>s`（id 前可加 #）
+/// - `manual`
+/// - `named <名稱>`
+impl std::str::FromStr for Schedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (head, rest) = match s.split_once(char::is_whitespace) {
+            Some((h, r)) => (h, r.trim()),
+            None => (s, ""),
+        };
+        match (head.to_ascii_lowercase().as_str(), rest) {
+            ("@reboot", "") => Ok(Schedule::Reboot),
+            ("@hourly", "") => Ok(Schedule::Hourly { minute: 0 }),
+            ("@daily" | "@midnight", "") => Ok(Schedule::Daily { hour: 0, minute: 0 }),
+            ("@weekly" | "@monthly" | "@yearly" | "@annually", "") => Err(format!(
+                "{head} is not supported (use @hourly, @daily or daily HH:MM)"
+            )),
+            ("manual", "") => Ok(Schedule::Manual),
+            ("once", t) => DateTime::parse_from_rfc3339(t)
+                .map(Schedule::Once)
+                .map_err(|e| format!("invalid RFC3339 time {t:?}: {e}")),
+            ("daily", t) => {
+                let (hour, minute) = parse_hhmm(t)?;
+                Ok(Schedule::Daily { hour, minute })
+            }
+            ("hourly", t) => {
+                let minute = t
+                    .strip_prefix(':')
+                    .and_then(|m| m.parse::<u32>().ok())
+                    .filter(|m| *m <= 59)
+                    .ok_or_else(|| format!("invalid minute {t:?} (expected :MM)"))?;
+                Ok(Schedule::Hourly { minute })
+            }
+            ("after", t) if !t.is_empty() => parse_after(t),
+            ("named", t) if !t.is_empty() && !t.contains(char::is_whitespace) => {
+                Ok(Schedule::Named {
+                    name: t.to_string(),
+                })
+            }
+            _ => DateTime::parse_from_rfc3339(s)
+                .map(Schedule::Once)
+                .map_err(|_| format!("unrecognized schedule {s:?}")),
+        }
+    }
+}
+
+impl std::fmt::Display for Schedule {
+    /// 與 from_str 互通的寫法
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Schedule::Once(t) => write!(f, "once {}", t.to_rfc3339()),
+            Schedule::Daily { hour, minute } => write!(f, "daily {hour:02}:{minute:02}"),
+            Schedule::After {
+                task_id,
+                delay_secs,
+                expires_after_secs,
+            } => {
+                write!(f, "after #{task_id}")?;
+                if *delay_secs > 0 {
+                    write!(f, " +{delay_secs}s")?;
+                }
+                if let Some(secs) = expires_after_secs {
+                    write!(f, " expires {secs}s")?;
+                }
+                Ok(())
+            }
+            Schedule::Manual => f.write_str("manual"),
+            Schedule::Hourly { minute } => write!(f, "hourly :{minute:02}"),
+            Schedule::Reboot => f.write_str("@reboot"),
+            Schedule::Named { name } => write!(f, "named {name}"),
+            Schedule::Unknown(_) => f.write_str("unknown"),
+        }
+    }
+}
+
+impl Schedule {
+    /// after 之後（不含）的下一次觸發時間；牆上時間依 tz 解讀（伺服器用 chrono::Local）
+    ///
+    /// 依賴、手動、@reboot 與看不懂的排程沒有固定時間，回傳 None；
+    /// 具名排程要先由伺服器換成實際的排程。
+    /// 夏令時間：Daily 落在跳過的時段時順延到跳過後的第一分鐘，Hourly 則略過那一小時；
+    /// 重複的時刻都只取較早的一次。
+    pub fn next_occurrence<Tz: TimeZone>(
+        &self,
+        after: DateTime<FixedOffset>,
+        tz: &Tz,
+    ) -> Option<DateTime<FixedOffset>> {
+        let local = after.with_timezone(tz).naive_local();
+        match *self {
+            Schedule::Once(t) => (t > after).then_some(t),
+            Schedule::Daily { hour, minute } => {
+                let first = local.date().and_hms_opt(hour, minute, 0)?;
+                (0..=2)
+                    .map(|d| first + Duration::days(d))
+                    .filter_map(|n| resolve(tz, n))
+                    .find(|t| *t > after)
+            }
+            Schedule::Hourly { minute } => {
+                let first = local.date().and_hms_opt(local.hour(), minute, 0)?;
+                (0..=3)
+                    .map(|h| first + Duration::hours(h))
+                    .filter_map(|n| Some(tz.from_local_datetime(&n).earliest()?.fixed_offset()))
+                    .find(|t| *t > after)
+            }
+            Schedule::After { .. }
+            | Schedule::Manual
+            | Schedule::Reboot
+            | Schedule::Named { .. }
+            | Schedule::Unknown(_) => None,
+        }
+    }
+}
+
+/// 牆上時間轉成實際時刻；落在夏令時間的空隙時往後找第一個存在的分鐘
+fn resolve<Tz: TimeZone>(tz: &Tz, naive: NaiveDateTime) -> Option<DateTime<FixedOffset>> {
+    (0..=180).find_map(
+        |m| match tz.from_local_datetime(&(naive + Duration::minutes(m))) {
+            LocalResult::Single(t) | LocalResult::Ambiguous(t, _) => Some(t.fixed_offset()),
+            LocalResult::None => None,
+        },
+    )
+}
+
+fn parse_hhmm(s: &str) -> Result<(u32, u32), String> {
+    let err = || format!("invalid time {s:?} (expected HH:MM)");
+    let (h, m) = s.split_once(':').ok_or_else(err)?;
+    let hour: u32 = h.parse().map_err(|_| err())?;
+    let minute: u32 = m.parse().map_err(|_| err())?;
+    if hour > 23 || minute > 59 {
+        return Err(format!("time out of range: {s:?}"));
+    }
+    Ok((hour, minute))
+}
+
+/// `<id> [+<秒>s] [expires <秒>s]`
+fn parse_after(s: &str) -> Result<Schedule, String> {
+    let secs = |t: &str| {
+        t.strip_suffix('s')
+            .unwrap_or(t)
+            .parse::<u64>()
+            .map_err(|_| format!("invalid seconds {t:?}"))
+    };
+    let mut words = s.split_whitespace();
+    let id = words.next().unwrap_or_default();
+    let task_id = id
+        .strip_prefix('#')
+        .unwrap_or(id)
+        .parse::<u64>()
+        .map_err(|_| format!("invalid task id {id:?}"))?;
+    let (mut delay_secs, mut expires_after_secs) = (0, None);
+    while let Some(w) = words.next() {
+        if let Some(d) = w.strip_prefix('+') {
+            delay_secs = secs(d)?;
+        } else if w == "expires" {
+            let t = words.next().ok_or("expires needs a duration")?;
+            expires_after_secs = Some(secs(t)?);
+        } else {
+            return Err(format!("unexpected {w:?} in after schedule"));
+        }
+    }
+    Ok(Schedule::After {
+        task_id,
+        delay_secs,
+        expires_after_secs,
+    })
+}
+
+/// 外部觸發來源；收到訊息即執行一次（節流設定照常生效）
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[non_exhaustive]
+pub enum Trigger {
+    /// MQTT topic filter，可用 + 與 # 萬用字元
+    Mqtt { topic: String },
+    /// [bridge] 設定的 Redis channel 或 NATS subject（NATS 可用 * 與 >）
+    Bridge { subject: String },
+    #[serde(untagged)]
+    #[schemars(skip)]
+    Unknown(Unrecognized),
+}
+
+/// 未指定命名空間時使用
+pub const DEFAULT_NAMESPACE: &str = "default";
+
+/// 保留給伺服器內建任務的命名空間
+pub const SYSTEM_NAMESPACE: &str = "system";
+
+/// InEnvironment 以此名稱指定伺服器的預設環境（頂層設定設了權杖時用來帶上權杖）
+pub const DEFAULT_ENVIRONMENT: &str = "default";
+
+/// 伺服器在區網以 mDNS 宣告的服務類型
+pub const MDNS_SERVICE_TYPE: &str = "_scheduler._tcp.local.";
+
+fn default_namespace() -> String {
+    DEFAULT_NAMESPACE.to_string()
 }
 
-/// 任務規格
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// 任務規格。cmd、args、env 的值、output_path、輸出變數檔與沙箱可寫路徑
+/// 可使用模板變數：`{{server.hostname}}`、`{{server.environment}}`、
+/// `{{vars.<key>}}`，依賴任務另有 `{{upstream.<key>}}`；
+/// 日期以執行時計算，例如 `{{today-1d:%Y-%m-%d}}`、`{{now+2h:%H:%M}}`
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct TaskSpec {
+    /// 顯示用名稱（可不填）
+    #[serde(default)]
+    pub name: Option<String>,
+    /// 標籤（搜尋、分類用）
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// 所屬命名空間（配額依此計算）
+    #[serde(default = "default_namespace")]
+    pub namespace: String,
+    /// 負責人；列表與失敗通知中會帶上，出事時知道找誰
+    #[serde(default)]
+    pub owner: Option<Owner>,
+    /// 附加在此任務 Prometheus 指標上的標籤（例如 service、team），儀表板依此切分。
+    /// 只用於指標匯出（textfile 與 pushgateway）；伺服器沒有 OTLP 追蹤匯出，不會出現在 span 上
+    #[serde(default)]
+    pub metric_labels: BTreeMap<String, String>,
     pub cmd: String,
+    /// 參數；可用 `{{upstream.<key>}}` 引用前置任務的輸出變數
     pub args: Vec<String>,
+    /// 額外的環境變數；值同樣可用模板變數
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// 不繼承伺服器的環境變數：子程序只拿到 PATH、HOME、TZ 與 env 中宣告的
+    #[serde(default)]
+    pub clean_env: bool,
+    /// 新增時不檢查 cmd 是否在伺服器的 PATH 中（命令之後才會安裝等情況）
+    #[serde(default)]
+    pub skip_cmd_check: bool,
+    /// 從哪裡讀取本任務發布的輸出變數；未設定則不發布
+    #[serde(default)]
+    pub outputs: Option<OutputsFrom>,
     pub output_path: PathBuf,
     pub append: bool,
+    /// 輸出檔與自動建立的目錄的權限與擁有者；未設定則依伺服器的 umask
+    #[serde(default)]
+    pub output_perms: Option<OutputPerms>,
     pub schedule: Schedule,
+    /// 單次執行逾時秒數；超過即終止
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+    /// 逾時或取消時先送給行程群組的訊號；未設定為 SIGTERM
+    #[serde(default)]
+    pub stop_signal: Option<StopSignal>,
+    /// 送出 stop_signal 後等多久仍未結束才 SIGKILL；未設定為 5 秒
+    #[serde(default)]
+    pub stop_grace_secs: Option<u64>,
+    /// 子程序的 CPU/IO 排程類別
+    #[serde(default)]
+    pub sched: SchedClass,
+    /// 沙箱限制；未設定則不受限
+    #[serde(default)]
+    pub sandbox: Option<SandboxProfile>,
+    /// 具名互斥鎖：共用任一把鎖的任務不會同時執行，依到達順序排隊
+    #[serde(default)]
+    pub locks: Vec<String>,
+    /// 觸發頻率限制；被擋下的觸發記為 RunSkipped，不會補跑
+    #[serde(default)]
+    pub throttle: Option<Throttle>,
+    /// 伺服器負載過高時，低於 [load_shed] 門檻的 Daily/Hourly 任務會被略過
+    #[serde(default)]
+    pub priority: Priority,
+    /// 外部觸發來源；可與任何排程並用
+    #[serde(default)]
+    pub triggers: Vec<Trigger>,
+    /// 每次執行前後呼叫的監控網址（cron 監控服務用）
+    #[serde(default)]
+    pub healthcheck: Option<Healthcheck>,
+    /// 只在每日的這段時間內開始執行；窗外的觸發延到窗口開始或略過
+    #[serde(default)]
+    pub allowed_window: Option<TimeWindow>,
+    /// 每次執行前先等人工核准（部署流程的關卡）
+    #[serde(default)]
+    pub approval: Option<Approval>,
+    /// 連續失敗斷路器；跳脫後暫停自動執行
+    #[serde(default)]
+    pub breaker: Option<CircuitBreaker>,
+    /// 成敗的判定方式；未設定則結束碼 0 為成功
+    #[serde(default)]
+    pub success: Option<SuccessCriteria>,
+    /// 輸出不是 UTF-8 時怎麼看待（輸出快照、success 條件、outputs）
+    #[serde(default)]
+    pub output_encoding: OutputEncoding,
+    /// 每次執行後由伺服器依序做的清理（刪暫存目錄、清舊檔），結果另記在 RunResult.cleanup
+    #[serde(default)]
+    pub cleanup: Vec<CleanupAction>,
+    /// 不參與伺服器的錯開啟動（[stagger]），準時執行
+    #[serde(default)]
+    pub exact_start: bool,
+    /// 受保護的任務：移除、永久刪除、回溯版本都要加 force（CLI 的 --force）；
+    /// 避免手誤或批次操作波及重要的正式任務。git-sync 依清單更新不受此限
+    #[serde(default)]
+    pub protected: bool,
+    /// 由伺服器設定：管理此任務的宣告來源（例如 `git-sync:<key>`）；手動新增為 None
+    #[serde(default)]
+    pub source: Option<String>,
+}
+
+/// healthchecks.io 式的 ping：開始時打 `<url>/start`，成功打 `<url>`，
+/// 失敗打 `<url>/fail`；個別指定的網址優先
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct Healthcheck {
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub start: Option<String>,
+    #[serde(default)]
+    pub success: Option<String>,
+    #[serde(default)]
+    pub failure: Option<String>,
+}
+
+impl Healthcheck {
+    pub fn start_url(&self) -> Option<String> {
+        self.start.clone().or_else(|| self.with_suffix("/start"))
+    }
+
+    pub fn success_url(&self) -> Option<String> {
+        self.success.clone().or_else(|| self.with_suffix(""))
+    }
+
+    pub fn failure_url(&self) -> Option<String> {
+        self.failure.clone().or_else(|| self.with_suffix("/fail"))
+    }
+
+    fn with_suffix(&self, suffix: &str) -> Option<String> {
+        let base = self.url.as_deref()?.trim_end_matches('/');
+        Some(format!("{base}{suffix}"))
+    }
+}
+
+/// 輸出檔的權限（僅 Unix）
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct OutputPerms {
+    /// 八進位權限，例如 "0640"；自動建立的目錄另加上可讀位元對應的 x
+    #[serde(default)]
+    pub mode: Option<String>,
+    /// 擁有者，使用者名稱或 uid；伺服器須有 chown 的權限
+    #[serde(default)]
+    pub user: Option<String>,
+    /// 群組名稱或 gid
+    #[serde(default)]
+    pub group: Option<String>,
+}
+
+/// 觸發節流：避免一連串觸發（依賴、事件、trigger hook）在短時間內啟動大量執行；
+/// 定時執行與手動的 RunNow、RetryChain 不受限，也不計入次數
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct Throttle {
+    /// 兩次啟動之間至少間隔幾秒
+    #[serde(default)]
+    pub min_interval_secs: Option<u64>,
+    /// 任意一小時內最多啟動幾次
+    #[serde(default)]
+    pub max_per_hour: Option<u32>,
+}
+
+/// 每日允許執行的時段（本地時間 HH:MM，end 早於 start 表示跨午夜）
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct TimeWindow {
+    pub start: String,
+    pub end: String,
+    #[serde(default)]
+    pub outside: WindowPolicy,
+}
+
+/// 時段外的觸發如何處理
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum WindowPolicy {
+    /// 等到下一次時段開始再執行
+    #[default]
+    Defer,
+    /// 本次略過（記為 RunSkipped）
+    Skip,
+}
+
+impl std::str::FromStr for WindowPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "defer" => Ok(WindowPolicy::Defer),
+            "skip" => Ok(WindowPolicy::Skip),
+            _ => Err(format!("unknown window policy {s:?} (defer, skip)")),
+        }
+    }
+}
+
+/// 任務的負責人與聯絡方式
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Owner {
+    /// 負責人或值班信箱
+    #[serde(default)]
+    pub email: Option<String>,
+    /// 負責的團隊
+    #[serde(default)]
+    pub team: Option<String>,
+}
+
+impl std::fmt::Display for Owner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (&self.team, &self.email) {
+            (Some(team), Some(email)) => write!(f, "{team} <{email}>"),
+            (Some(team), None) => f.write_str(team),
+            (None, Some(email)) => f.write_str(email),
+            (None, None) => f.write_str("-"),
+        }
+    }
+}
+
+/// 執行前的人工核准關卡；timeout_secs 內未核准則本輪略過（未設定則一直等）
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct Approval {
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+}
+
+/// 等待核准中的 run（伺服器重啟後不保留）
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct PendingApproval {
+    pub run_id: u64,
+    pub task_id: u64,
+    pub requested_at: DateTime<FixedOffset>,
+    pub expires_at: Option<DateTime<FixedOffset>>,
+}
+
+/// 連續失敗 failures 次後跳脫，不再自動執行；
+/// 有 cooldown_secs 時每隔這麼久放行一次試跑，成功即恢復，否則等人工重設
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct CircuitBreaker {
+    pub failures: u32,
+    #[serde(default)]
+    pub cooldown_secs: Option<u64>,
+}
+
+/// 任務輸出的解讀方式。輸出檔與 artifacts 一律保存原始位元組，
+/// 這裡影響的是轉成文字的地方：GetOutput、follow 的即時輸出、success 條件與 outputs
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum OutputEncoding {
+    /// 無法解碼的位元組以 U+FFFD 取代
+    #[default]
+    Lossy,
+    /// 必須是 UTF-8：無法解碼時 success 條件不成立、outputs 不採用，
+    /// 不拿取代過的文字比對
+    Utf8,
+    /// 原始位元組：success 條件以位元組比對（可用 `(?-u)\xff` 這類樣式），
+    /// 轉成文字時無法解碼的位元組寫成 `\xNN`
+    Raw,
+}
+
+impl std::str::FromStr for OutputEncoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lossy" => Ok(OutputEncoding::Lossy),
+            "utf8" | "utf-8" => Ok(OutputEncoding::Utf8),
+            "raw" => Ok(OutputEncoding::Raw),
+            _ => Err(format!("unknown output encoding {s:?} (lossy, utf8, raw)")),
+        }
+    }
+}
+
+/// 終止任務時先送的訊號，讓程式有機會清掉暫存檔與寫到一半的輸出；
+/// 寬限期過後仍未結束才 SIGKILL。Kill 為不給寬限直接 SIGKILL（Windows 一律直接終止）
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum StopSignal {
+    #[default]
+    Term,
+    Int,
+    Hup,
+    Quit,
+    Usr1,
+    Usr2,
+    Kill,
+}
+
+impl std::str::FromStr for StopSignal {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.to_ascii_lowercase();
+        match name.strip_prefix("sig").unwrap_or(&name) {
+            "term" => Ok(StopSignal::Term),
+            "int" => Ok(StopSignal::Int),
+            "hup" => Ok(StopSignal::Hup),
+            "quit" => Ok(StopSignal::Quit),
+            "usr1" => Ok(StopSignal::Usr1),
+            "usr2" => Ok(StopSignal::Usr2),
+            "kill" => Ok(StopSignal::Kill),
+            _ => Err(format!(
+                "unknown stop signal {s:?} (term, int, hup, quit, usr1, usr2, kill)"
+            )),
+        }
+    }
+}
+
+/// 自訂的成功條件：結束碼在 exit_codes（0 永遠算成功）之內，
+/// 且 stdout 符合 output_matches、不符合 output_rejects（正規表示式）才算成功。
+/// 改判後的結果記在 status_code，失敗通知、斷路器、chain 都依改判後的結果
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct SuccessCriteria {
+    /// 0 以外也算成功的結束碼，例如 rsync 的 24（來源檔案在傳輸中消失）
+    #[serde(default)]
+    pub exit_codes: Vec<i32>,
+    #[serde(default)]
+    pub output_matches: Option<String>,
+    #[serde(default)]
+    pub output_rejects: Option<String>,
+}
+
+/// 斷路器目前的狀態（伺服器持久化）
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct BreakerState {
+    /// 目前連續失敗的次數
+    pub consecutive_failures: u32,
+    /// 跳脫時間（試跑失敗時更新）；None 表示未跳脫
+    pub open_since: Option<DateTime<FixedOffset>>,
+}
+
+/// 輸出變數的來源，格式皆為 key=value
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub enum OutputsFrom {
+    /// stdout 最後一個非空白行，以空白分隔多組 key=value
+    LastLine,
+    /// 指定的檔案，每行一組 key=value（# 開頭為註解）；執行前會先刪除
+    File(PathBuf),
+}
+
+/// 執行後的清理動作；路徑必須是絕對路徑。不論成敗都會做（模擬執行除外），
+/// 失敗只記在紀錄中，不影響 run 的結果
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[non_exhaustive]
+pub enum CleanupAction {
+    /// 刪除整個目錄（含內容）；不存在時略過
+    RemoveDir { path: PathBuf },
+    /// 刪除符合 glob 的檔案，例如 `/var/tmp/export/*.part`；萬用字元（`*`、`?`）只能用在檔名。
+    /// 設定 older_than_days 時只刪最後修改超過這麼多天的
+    RemoveFiles {
+        glob: String,
+        #[serde(default)]
+        older_than_days: Option<u64>,
+    },
+    #[serde(untagged)]
+    #[schemars(skip)]
+    Unknown(Unrecognized),
+}
+
+/// 一次執行後清理的結果
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct CleanupReport {
+    /// 刪掉的檔案與目錄數
+    pub removed: u64,
+    /// 釋出的空間（位元組）
+    pub freed_bytes: u64,
+    /// 失敗的動作與原因
+    #[serde(default)]
+    pub errors: Vec<String>,
+}
+
+/// 沙箱設定（Linux，透過 bubblewrap 執行）：整個檔案系統唯讀，
+/// 只有 writable_paths 可寫；預設無網路
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct SandboxProfile {
+    /// 可寫入的路徑（絕對路徑）
+    #[serde(default)]
+    pub writable_paths: Vec<PathBuf>,
+    /// 是否允許網路
+    #[serde(default)]
+    pub network: bool,
+}
+
+/// 子程序的 CPU/IO 排程類別（nice 適用所有 Unix；ionice 與 CPU 親和性僅 Linux）
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct SchedClass {
+    /// nice 值（-20 ~ 19，越大越禮讓）
+    #[serde(default)]
+    pub nice: Option<i32>,
+    #[serde(default)]
+    pub ionice: Option<IoNice>,
+    /// 只允許在這些 CPU 上執行；空表示不限制
+    #[serde(default)]
+    pub cpu_affinity: Vec<usize>,
+}
+
+/// ionice 類別；level 0（最高）~ 7（最低）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum IoNice {
+    RealTime { level: u8 },
+    BestEffort { level: u8 },
+    Idle,
+}
+
+/// 子程序的輸出管線
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+    /// 較新伺服器的管線種類
+    #[serde(other)]
+    #[schemars(skip)]
+    Unknown,
+}
+
+/// 一次執行的結局
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, JsonSchema)]
+#[non_exhaustive]
+pub enum RunOutcome {
+    /// 程式自行結束（成敗看 status_code）
+    #[default]
+    Exited,
+    /// 超過任務逾時或全域執行上限，被終止
+    TimedOut,
+    /// 子程序消失，結果不明
+    Lost,
+    /// 任務被移除或伺服器關閉時中止
+    Cancelled,
+    /// 伺服器在執行途中停止（重啟時對帳發現），結果不明
+    Orphaned,
+    /// 負載過高，未執行就略過（[load_shed]）
+    Skipped,
+    /// 較新伺服器的結局種類
+    #[serde(other)]
+    #[schemars(skip)]
+    Unknown,
 }
 
 /// 執行結果
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct RunResult {
+    /// 伺服器配發的 run 編號（0 表示舊資料，無編號）
+    #[serde(default)]
+    pub run_id: u64,
+    #[serde(default)]
+    pub started_at: Option<DateTime<FixedOffset>>,
     pub finished_at: DateTime<FixedOffset>,
+    /// 依任務的 success 條件判定後的結束碼：成功為 0，失敗時非 0
     pub status_code: i32,
+    /// 程式實際的結束碼；只在與 status_code 不同（被 success 條件改判）時記錄
+    #[serde(default)]
+    pub exit_code: Option<i32>,
+    /// 依賴鏈中，本次執行所接續的前置 run（After 排程才有）
+    #[serde(default)]
+    pub upstream_run: Option<u64>,
+    #[serde(default)]
+    pub outcome: RunOutcome,
     pub stdout_len: usize,
     pub stderr_len: usize,
     pub wrote_to: PathBuf,
+    /// 本次執行發布的輸出變數
+    #[serde(default)]
+    pub outputs: BTreeMap<String, String>,
+    /// 本次寫出的輸出（未壓縮）大小與 SHA-256（hex）；沒有寫輸出時為 None
+    #[serde(default)]
+    pub output_size: Option<u64>,
+    #[serde(default)]
+    pub output_sha256: Option<String>,
+    /// stdout 被判定為二進位：輸出檔與 artifact 只有原始的 stdout 位元組（無標頭，
+    /// stderr 另寫到 `<輸出檔>.stderr`），即時輸出與 GetOutput 只給大小與摘要
+    #[serde(default)]
+    pub binary: bool,
+    /// 本次輸出另存的 artifact 檔
+    #[serde(default)]
+    pub artifact: Option<PathBuf>,
+    /// 子程序（含其已結束的子孫）用掉的資源；內建任務、模擬執行與無法取得的平台為 None
+    #[serde(default)]
+    pub usage: Option<ResourceUsage>,
+    /// 執行後清理的結果；任務沒有設定 cleanup 時為 None
+    #[serde(default)]
+    pub cleanup: Option<CleanupReport>,
+    /// 觸發者附上的追查資訊（工單編號、commit SHA 等）；依賴任務不繼承，改以 upstream_run 串回
+    #[serde(default)]
+    pub metadata: BTreeMap<String, String>,
+}
+
+/// 一次執行的資源用量（取自結束時的 rusage）
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ResourceUsage {
+    /// 使用者態 CPU 時間（毫秒）
+    pub cpu_user_ms: u64,
+    /// 核心態 CPU 時間（毫秒）
+    pub cpu_system_ms: u64,
+    /// 最大常駐記憶體（KiB）
+    pub max_rss_kb: u64,
+}
+
+/// 執行結果的粗分類（QueryRuns 篩選與彙總用）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum RunStatus {
+    /// 程式自行結束且結束碼為 0
+    Succeeded,
+    /// 結束碼非 0、逾時、被中止或結果不明
+    Failed,
+    /// 負載過高而略過，沒有執行
+    Skipped,
+}
+
+impl RunStatus {
+    pub fn of(r: &RunResult) -> Self {
+        match r.outcome {
+            RunOutcome::Exited if r.status_code == 0 => RunStatus::Succeeded,
+            RunOutcome::Skipped => RunStatus::Skipped,
+            _ => RunStatus::Failed,
+        }
+    }
+}
+
+impl std::str::FromStr for RunStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "succeeded" | "ok" => Ok(RunStatus::Succeeded),
+            "failed" => Ok(RunStatus::Failed),
+            "skipped" => Ok(RunStatus::Skipped),
+            _ => Err(format!("unknown status {s:?} (succeeded, failed, skipped)")),
+        }
+    }
+}
+
+/// QueryRuns 的結果：符合條件的執行（新到舊，最多 limit 筆）與逐任務彙總（不受 limit 影響）
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct RunReport {
+    pub runs: Vec<RunRecord>,
+    /// 依任務 id 排序
+    pub tasks: Vec<TaskRunTally>,
+    /// 符合條件的總筆數
+    pub total: usize,
+}
+
+/// 一個任務在查詢範圍內的執行統計
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct TaskRunTally {
+    pub task_id: u64,
+    /// 任務名稱，沒有名稱時為命令；任務已移除且查不到規格時為 None
+    pub label: Option<String>,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub skipped: usize,
+    pub last_finished_at: DateTime<FixedOffset>,
+}
+
+/// 一筆輸出與紀錄中 checksum 的比對結果
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct OutputCheck {
+    pub task_id: u64,
+    pub run_id: u64,
+    pub path: PathBuf,
+    pub status: CheckStatus,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[non_exhaustive]
+pub enum CheckStatus {
+    Ok,
+    /// 檔案不存在或無法讀取
+    Missing(String),
+    /// 大小不同（多半是被截斷或附加）
+    SizeMismatch {
+        expected: u64,
+        actual: u64,
+    },
+    /// 大小相同但內容不同
+    HashMismatch,
+    #[serde(untagged)]
+    #[schemars(skip)]
+    Unknown(Unrecognized),
+}
+
+/// 歷史紀錄中的一筆執行
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct RunRecord {
+    pub task_id: u64,
+    #[serde(flatten)]
+    pub result: RunResult,
+}
+
+/// 任務規格的一個版本；新增為第 1 版，之後每次更新或回滾加一版
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Revision {
+    pub task_id: u64,
+    pub revision: u32,
+    pub at: DateTime<FixedOffset>,
+    /// 變更者：連線的身分或位址，git-sync 為 `git-sync:<commit>`
+    pub actor: String,
+    /// 回滾時為回到的版本
+    #[serde(default)]
+    pub rollback_of: Option<u32>,
+    /// 與上一版不同的欄位；第 1 版為空
+    #[serde(default)]
+    pub changes: Vec<SpecChange>,
+    pub spec: TaskSpec,
+}
+
+/// 一個欄位的變更；值為 JSON 文字，None 表示該欄位不存在
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct SpecChange {
+    pub field: String,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+/// 回收桶中的任務；過了 expires_at 會被永久刪除
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct TrashedTask {
+    pub id: u64,
+    pub spec: TaskSpec,
+    pub removed_at: DateTime<FixedOffset>,
+    /// 移除者，同 [`Revision::actor`]
+    pub removed_by: String,
+    pub expires_at: DateTime<FixedOffset>,
+}
+
+/// 載入時無法解析、移到隔離檔的持久化紀錄；修正後以 AddTask 重新新增
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct QuarantinedTask {
+    /// 隔離檔中的編號（DiscardQuarantined 用）
+    pub seq: u64,
+    /// 紀錄中的任務 id；讀不到時為 None
+    #[serde(default)]
+    pub id: Option<u64>,
+    pub reason: String,
+    pub quarantined_at: DateTime<FixedOffset>,
+    /// 原始 JSON；整個持久化檔都無法解析時為檔案全文
+    pub record: String,
+}
+
+/// 預約的維護動作（ScheduleMaintenance）：到了 at 由伺服器執行，完成或結束後自動刪除
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct MaintenanceAction {
+    /// 預約編號（CancelMaintenance 用）
+    pub seq: u64,
+    pub task_id: u64,
+    pub action: MaintenanceKind,
+    /// 生效時間
+    pub at: DateTime<FixedOffset>,
+    /// 受保護的任務也照樣移除
+    #[serde(default)]
+    pub force: bool,
+    /// 預約者，同 [`Revision::actor`]
+    pub actor: String,
+    pub created_at: DateTime<FixedOffset>,
+}
+
+/// 預先授權的觸發網址（CreateTriggerHook）：持有 token 的外部系統（CI 等）
+/// 只能以 `POST /hooks/<id>` 觸發這一個任務，沒有其他協定權限
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct TriggerHookInfo {
+    pub id: u64,
+    pub task_id: u64,
+    #[serde(default)]
+    pub label: Option<String>,
+    /// 呼叫時可帶的參數名稱；以同名的環境變數交給該次執行，其他名稱一律拒絕。
+    /// 值由外部系統決定，不經命令政策檢查：PATH、LD_* 等會改變執行內容的名稱
+    /// 與任務 env 已設定的名稱不能當參數
+    #[serde(default)]
+    pub params: Vec<String>,
+    /// 建立者，同 [`Revision::actor`]
+    pub actor: String,
+    pub created_at: DateTime<FixedOffset>,
+    #[serde(default)]
+    pub last_used: Option<DateTime<FixedOffset>>,
+}
+
+/// 全域凍結（Freeze）：期間內所有定時觸發都略過，到 until 自動恢復
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct FreezeStatus {
+    pub since: DateTime<FixedOffset>,
+    pub until: DateTime<FixedOffset>,
+    /// 外部觸發、依賴觸發與手動執行（RunNow）也一併略過
+    #[serde(default)]
+    pub block_triggered: bool,
+    /// 凍結的原因，例如部署編號
+    #[serde(default)]
+    pub reason: Option<String>,
+    /// 發出凍結的連線，同 [`Revision::actor`]
+    pub actor: String,
+}
+
+/// 維護動作的種類
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[non_exhaustive]
+pub enum MaintenanceKind {
+    /// 從 at 暫停到 until：期間的執行都略過（記一筆 Skipped），之後自動恢復
+    Pause { until: DateTime<FixedOffset> },
+    /// 在 at 移除任務（同 RemoveTask，有保留期時先放進回收桶）
+    Remove,
+    #[serde(untagged)]
+    #[schemars(skip)]
+    Unknown(Unrecognized),
+}
+
+/// 故障注入規則（SetChaos，伺服器要開啟 [chaos]）：用來演練重試、通知與依賴鏈的失敗處理。
+/// 規則只存在伺服器記憶體中，重啟後清空
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ChaosRule {
+    /// 每次執行被注入的機率（0–1）
+    #[serde(default = "always")]
+    pub probability: f64,
+    /// 最多注入幾次，用完自動清除；None 不限
+    #[serde(default)]
+    pub count: Option<u32>,
+    /// 開始執行前先等待這麼久（毫秒）；等待期間可被終止
+    #[serde(default)]
+    pub delay_ms: u64,
+    /// 不實際執行命令，直接以這個結束碼失敗；None 只延遲
+    #[serde(default)]
+    pub exit_code: Option<i32>,
+}
+
+fn always() -> f64 {
+    1.0
+}
+
+/// 任務目前的故障注入規則（ListChaos）
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ChaosEntry {
+    pub task_id: u64,
+    /// count 為剩餘次數
+    pub rule: ChaosRule,
+    /// 已注入的次數
+    pub injected: u64,
+}
+
+/// git-sync 清單中的一個任務：以 key 識別，其餘欄位同 TaskSpec
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ManifestTask {
+    pub key: String,
+    #[serde(flatten)]
+    pub spec: TaskSpec,
+}
+
+/// 套用清單會對一個任務做的變更（DiffManifest）
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ManifestChange {
+    pub key: String,
+    pub change: ChangeKind,
+    /// 目前對應的任務；新增時為 None
+    #[serde(default)]
+    pub task_id: Option<u64>,
+    /// 有差異的欄位；新增與移除時列出有值的欄位
+    #[serde(default)]
+    pub fields: Vec<FieldChange>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum ChangeKind {
+    Add,
+    Update,
+    Remove,
+}
+
+/// 一個欄位的差異；值為 JSON 文字，沒有該欄位時為 None
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct FieldChange {
+    /// 以 `.` 連接的欄位路徑，例如 `schedule.Daily.hour`
+    pub field: String,
+    #[serde(default)]
+    pub old: Option<String>,
+    #[serde(default)]
+    pub new: Option<String>,
+}
+
+/// 伺服器內部的依賴表（GetDependencyGraph），排查依賴鏈沒有觸發時用
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct DependencyGraph {
+    /// 前置任務 → 它完成後要觸發的任務（伺服器實際依這張表觸發），依前置任務排序
+    pub dependents: Vec<WatchEntry>,
+    /// 規格中的 After：任務 → 前置任務（反向），依任務排序
+    pub upstream: Vec<DependencyEdge>,
+    /// 兩張表對不起來的地方；正常時為空
+    pub issues: Vec<DependencyIssue>,
+}
+
+/// 依賴表中的一列
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct WatchEntry {
+    pub upstream: u64,
+    pub dependents: Vec<u64>,
+}
+
+/// 任務規格中的一條依賴
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct DependencyEdge {
+    pub task_id: u64,
+    pub upstream: u64,
+}
+
+/// 依賴表中的不一致
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct DependencyIssue {
+    pub task_id: u64,
+    pub upstream: u64,
+    pub kind: DependencyIssueKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+#[non_exhaustive]
+pub enum DependencyIssueKind {
+    /// 前置任務已不存在，永遠不會被觸發
+    MissingUpstream,
+    /// 規格依賴前置任務，但沒有登記在依賴表中，不會被觸發
+    NotRegistered,
+    /// 依賴表中登記的任務已不存在
+    MissingDependent,
+    /// 依賴表中登記的任務已改為依賴別的任務或不再依賴
+    StaleEntry,
+    /// 同一個依賴登記了不只一次
+    Duplicate,
+    /// 較新伺服器的種類
+    #[serde(other)]
+    #[schemars(skip)]
+    Unknown,
+}
+
+/// 移除一個任務會影響到什麼（PreviewRemoval 的結果）
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct RemovalImpact {
+    pub id: u64,
+    /// 任務名稱，沒有名稱時為命令
+    pub label: Option<String>,
+    /// 任務存在於任務表
+    pub found: bool,
+    /// 任務在回收桶中（只能永久刪除）
+    pub in_trash: bool,
+    /// 內建任務，不能移除
+    pub system: bool,
+    /// 受保護的任務，需要 force 才能移除
+    #[serde(default)]
+    pub protected: bool,
+    /// 每日或每小時重複執行
+    pub recurring: bool,
+    /// 以 After 依賴此任務的任務；移除後它們不會再被觸發
+    pub dependents: Vec<u64>,
+    /// 執行中的 run 數，移除時會被終止
+    pub running: usize,
+}
+
+/// 伺服器上的具名排程與目前引用它的任務
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct NamedSchedule {
+    pub name: String,
+    pub schedule: Schedule,
+    /// 依 id 排序
+    #[serde(default)]
+    pub tasks: Vec<u64>,
 }
 
 /// 任務資訊（給 list 用）
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct TaskInfo {
     pub id: u64,
     pub spec: TaskSpec,
     pub last_result: Option<RunResult>,
+    /// 下一次預定執行時間（已執行過的一次性任務、沒有排定延遲執行的依賴任務為 None）
+    #[serde(default)]
+    pub next_run: Option<DateTime<FixedOffset>>,
+    /// 正在排隊等待的鎖（空表示沒有在等）
+    #[serde(default)]
+    pub waiting_for: Vec<String>,
+    /// 斷路器跳脫的時間；None 表示未跳脫
+    #[serde(default)]
+    pub breaker_open_since: Option<DateTime<FixedOffset>>,
+    /// 依賴鏈中排定的延遲執行時間（早到晚）
+    #[serde(default)]
+    pub chained: Vec<DateTime<FixedOffset>>,
+    /// 執行中的 run 最近一次回報的進度；沒有在執行或未回報時為 None
+    #[serde(default)]
+    pub progress: Option<Progress>,
+}
+
+/// 執行中的命令透過 $SCHEDULER_PROGRESS 回報的進度
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Progress {
+    /// 0..=100
+    pub percent: Option<u8>,
+    /// 目前階段，例如 "uploading"
+    pub phase: Option<String>,
+    pub at: DateTime<FixedOffset>,
+}
+
+/// 任務優先順序
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, JsonSchema,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+impl std::str::FromStr for Priority {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "low" => Ok(Priority::Low),
+            "normal" => Ok(Priority::Normal),
+            "high" => Ok(Priority::High),
+            _ => Err(format!("unknown priority {s:?} (low, normal, high)")),
+        }
+    }
+}
+
+/// ListTasksSorted 的排序欄位
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum TaskSort {
+    #[default]
+    Id,
+    Name,
+    NextRun,
+    LastRun,
+    LastStatus,
+}
+
+impl std::str::FromStr for TaskSort {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "id" => Ok(TaskSort::Id),
+            "name" => Ok(TaskSort::Name),
+            "next-run" => Ok(TaskSort::NextRun),
+            "last-run" => Ok(TaskSort::LastRun),
+            "last-status" => Ok(TaskSort::LastStatus),
+            _ => Err(format!(
+                "unknown sort key {s:?} (id, name, next-run, last-run, last-status)"
+            )),
+        }
+    }
+}
+
+/// 伺服器事件；seq 全域遞增，斷線重連時用來接續
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Event {
+    pub seq: u64,
+    pub at: DateTime<FixedOffset>,
+    pub kind: EventKind,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[non_exhaustive]
+pub enum EventKind {
+    TaskAdded {
+        task_id: u64,
+    },
+    TaskRemoved {
+        task_id: u64,
+    },
+    /// 任務規格就地更新（git-sync、回滾）
+    TaskUpdated {
+        task_id: u64,
+        revision: u32,
+    },
+    RunStarted {
+        task_id: u64,
+        run_id: u64,
+        /// 同 [`RunResult::metadata`]
+        #[serde(default)]
+        metadata: BTreeMap<String, String>,
+    },
+    RunFinished {
+        task_id: u64,
+        run_id: u64,
+        status_code: i32,
+        outcome: RunOutcome,
+        #[serde(default)]
+        metadata: BTreeMap<String, String>,
+    },
+    /// 執行前檢查未通過（磁碟空間、命令政策、配額），本次不執行
+    RunSkipped {
+        task_id: u64,
+        reason: String,
+    },
+    /// watchdog 要求終止執行中的 run
+    RunKilled {
+        task_id: u64,
+        run_id: u64,
+        reason: RunOutcome,
+    },
+    /// 連續失敗達門檻，斷路器跳脫，任務暫停自動執行
+    BreakerOpened {
+        task_id: u64,
+        failures: u32,
+    },
+    /// run 在核准關卡等待人工核准
+    ApprovalRequested {
+        task_id: u64,
+        run_id: u64,
+        expires_at: Option<DateTime<FixedOffset>>,
+    },
+    /// 定期彙總（[digest]）：一段期間內的失敗、逾時與略過
+    DigestReady {
+        digest: Digest,
+    },
+    /// 執行中的 run 回報了新的進度
+    RunProgress {
+        task_id: u64,
+        run_id: u64,
+        progress: Progress,
+    },
+    /// 伺服器啟動完成，附啟動時的自我檢查（含孤兒 run）
+    ServerStarted {
+        report: StartupReport,
+    },
+    /// 較新伺服器的事件種類
+    #[serde(untagged)]
+    #[schemars(skip)]
+    Unknown(Unrecognized),
+}
+
+impl EventKind {
+    /// 事件所屬的任務
+    pub fn task_id(&self) -> Option<u64> {
+        match self {
+            EventKind::TaskAdded { task_id }
+            | EventKind::TaskRemoved { task_id }
+            | EventKind::TaskUpdated { task_id, .. }
+            | EventKind::RunStarted { task_id, .. }
+            | EventKind::RunFinished { task_id, .. }
+            | EventKind::RunSkipped { task_id, .. }
+            | EventKind::RunKilled { task_id, .. }
+            | EventKind::BreakerOpened { task_id, .. }
+            | EventKind::ApprovalRequested { task_id, .. }
+            | EventKind::RunProgress { task_id, .. } => Some(*task_id),
+            EventKind::DigestReady { .. }
+            | EventKind::ServerStarted { .. }
+            | EventKind::Unknown(_) => None,
+        }
+    }
+}
+
+/// [period_start, period_end) 內的問題，依任務合併；都沒有時仍可能送出（send_empty）
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Digest {
+    pub period_start: DateTime<FixedOffset>,
+    pub period_end: DateTime<FixedOffset>,
+    /// 非零結束碼、遺失、孤兒等
+    pub failures: Vec<DigestEntry>,
+    /// 超過逾時被終止
+    pub sla_breaches: Vec<DigestEntry>,
+    /// 執行前檢查未通過或負載過高而略過
+    pub skipped: Vec<DigestEntry>,
+}
+
+impl Digest {
+    pub fn is_empty(&self) -> bool {
+        self.failures.is_empty() && self.sla_breaches.is_empty() && self.skipped.is_empty()
+    }
+}
+
+/// 一個任務在彙總期間的某類問題
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct DigestEntry {
+    pub task_id: u64,
+    /// 任務名稱，沒有名稱時為命令
+    pub label: Option<String>,
+    pub count: u32,
+    pub last_at: DateTime<FixedOffset>,
+    /// 最後一次的結束碼、結局或略過原因
+    pub last_detail: String,
+}
+
+/// 通知 outbox 中的一筆待送（或已放棄）的 webhook
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Notification {
+    pub id: u64,
+    pub url: String,
+    pub event: Event,
+    /// 已嘗試送出的次數
+    pub attempts: u32,
+    /// 下次重試時間
+    pub next_attempt: DateTime<FixedOffset>,
+    pub last_error: Option<String>,
+    /// 超過重試上限，不再自動重送（dead letter）
+    pub dead: bool,
+    /// 事件所屬任務在排入時的負責人；送出時附在 JSON 的 owner 欄位
+    #[serde(default)]
+    pub owner: Option<Owner>,
+}
+
+/// 等待開始的 run 佇列統計
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct QueueStats {
+    /// 目前排隊中的 run 數
+    pub pending: usize,
+    /// 各任務排隊中的 run 數
+    #[serde(deserialize_with = "id_keyed")]
+    pub per_task: BTreeMap<u64, usize>,
+    /// 啟動以來因佇列已滿而丟棄的觸發數
+    pub dropped: u64,
+    /// 啟動以來因佇列已滿而拒絕的觸發數
+    pub rejected: u64,
+}
+
+/// 以任務 id 為鍵的表。JSON 的鍵是字串；ServerResponse 有 untagged 的後備變體，
+/// 內容會先緩衝再解析，這時 serde 不會把字串鍵轉回數字，要自己轉
+fn id_keyed<'de, D: Deserializer<'de>, V: Deserialize<'de>>(
+    d: D,
+) -> Result<BTreeMap<u64, V>, D::Error> {
+    BTreeMap::<String, V>::deserialize(d)?
+        .into_iter()
+        .map(|(k, v)| {
+            k.parse()
+                .map(|k| (k, v))
+                .map_err(|_| serde::de::Error::custom(format!("invalid task id {k:?}")))
+        })
+        .collect()
+}
+
+/// 伺服器資訊
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ServerInfo {
+    pub version: String,
+    pub started_at: DateTime<FixedOffset>,
+    /// 啟動時的自我檢查；重啟後確認是否一切正常
+    pub startup: StartupReport,
+    /// 目前的全域凍結（沒有凍結或已結束時為 None）
+    #[serde(default)]
+    pub freeze: Option<FreezeStatus>,
+}
+
+/// 啟動時的自我檢查結果
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct StartupReport {
+    /// 從持久化檔案載入的任務數（含未排程的）
+    pub tasks_loaded: usize,
+    /// 規格已不再有效的任務：保留在任務表中但不排程，修正後以 update 恢復
+    pub invalid: Vec<InvalidTask>,
+    /// 無法解析、移到隔離檔的紀錄數（ListQuarantine 查看）
+    #[serde(default)]
+    pub quarantined: usize,
+    /// 上次停機時仍在執行的 run（已記為 Orphaned）
+    #[serde(default)]
+    pub orphans: Vec<OrphanedRun>,
+    /// 接下來最早的幾次預定執行（早到晚）
+    pub next_fires: Vec<UpcomingFire>,
+    /// 持久化檔案與記憶體中的任務表一致
+    pub storage_ok: bool,
+    /// 一致性檢查的明細
+    pub storage_report: String,
+}
+
+/// 重啟時對帳到的孤兒 run
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct OrphanedRun {
+    pub task_id: u64,
+    pub running_since: DateTime<FixedOffset>,
+    /// 依 orphan_policy 重新執行
+    pub rerun: bool,
+}
+
+/// 載入時未通過檢查的任務
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct InvalidTask {
+    pub id: u64,
+    pub reason: String,
+}
+
+/// 一次預定執行
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct UpcomingFire {
+    pub task_id: u64,
+    #[serde(default)]
+    pub name: Option<String>,
+    pub at: DateTime<FixedOffset>,
+}
+
+/// 伺服器層級的模板變數
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct ServerContext {
+    /// `{{server.hostname}}`
+    pub hostname: String,
+    /// `{{server.environment}}`，例如 prod、staging
+    pub environment: String,
+    /// `{{vars.<key>}}`
+    pub vars: BTreeMap<String, String>,
 }
 
 /// 客戶端 → 服務端
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[allow(clippy::large_enum_variant)] // 每個 frame 只解一次，不值得 Box
+#[non_exhaustive]
 pub enum ClientRequest {
     AddTask(TaskSpec),
-    RemoveTask { id: u64 },
+    /// 移除任務；伺服器有設定保留期時先放進回收桶，可再還原。
+    /// 受保護的任務要加 force
+    RemoveTask {
+        id: u64,
+        #[serde(default)]
+        force: bool,
+    },
+    /// 列出任務（依 id 排序）
     ListTasks,
+    /// 列出任務；依 sort 排序，沒有值的項目一律排在最後。
+    /// 另立變體而不是在 ListTasks 加欄位：舊客戶端送的 `"ListTasks"` 才能照常解析
+    ListTasksSorted {
+        #[serde(default)]
+        sort: TaskSort,
+        #[serde(default)]
+        descending: bool,
+    },
+    /// 只列出 version 之後有變更的任務（version 為事件的 seq）；
+    /// 給無法維持訂閱連線的儀表板定期輪詢。version 為 0 時回傳完整清單
+    ListTasksSince {
+        #[serde(default)]
+        version: u64,
+    },
+    /// 依名稱、命令、參數、標籤、輸出路徑搜尋任務；
+    /// regex=false 時為不分大小寫的子字串比對
+    Search {
+        pattern: String,
+        regex: bool,
+    },
+    /// 取回任務最近一次保存於 artifacts 目錄的輸出
+    GetOutput {
+        id: u64,
+    },
+    /// 查詢任務的執行歷史（新到舊）
+    GetHistory {
+        id: u64,
+        limit: usize,
+    },
+    /// 跨任務查詢 [since, until) 內結束的執行，可依狀態篩選；伺服器端彙總
+    QueryRuns {
+        #[serde(default)]
+        since: Option<DateTime<FixedOffset>>,
+        #[serde(default)]
+        until: Option<DateTime<FixedOffset>>,
+        #[serde(default)]
+        status: Option<RunStatus>,
+        limit: usize,
+    },
+    /// 立即依保留政策清理歷史
+    PruneHistory,
+    /// 訂閱事件：此連線改為只送 Event 與 Heartbeat。
+    /// since 為已收到的最後一個 seq，伺服器先補送之後仍保留著的事件
+    Subscribe {
+        #[serde(default)]
+        since: Option<u64>,
+    },
+    /// 列出通知 outbox；dead_only 只列已放棄的
+    ListOutbox {
+        dead_only: bool,
+    },
+    /// 重新排入指定的通知並重設重試次數；ids 為空時重排所有 dead letter
+    RequeueNotifications {
+        ids: Vec<u64>,
+    },
+    /// 查詢伺服器層級的模板變數
+    GetContext,
+    /// 執行期間修改模板變數；修改存檔，重啟後仍套用在設定檔的值之上。
+    /// 伺服器（或該環境）須設定權杖，未設定時一律拒絕
+    UpdateContext {
+        #[serde(default)]
+        environment: Option<String>,
+        #[serde(default)]
+        set: BTreeMap<String, String>,
+        #[serde(default)]
+        unset: Vec<String>,
+    },
+    /// 查詢等待開始的 run 佇列
+    GetQueueStats,
+    /// 以歷史中記錄的 checksum 驗證 artifacts 與輸出檔；id 未指定則驗證全部
+    VerifyOutputs {
+        #[serde(default)]
+        id: Option<u64>,
+    },
+    /// 重設任務的斷路器，恢復自動執行
+    ResetBreaker {
+        id: u64,
+    },
+    /// 取消任務在依賴鏈中排定、尚未開始的延遲執行
+    CancelChained {
+        id: u64,
+    },
+    /// 列出等待核准的 run
+    ListApprovals,
+    /// 核准（approve=true）或駁回等待中的 run
+    Approve {
+        run_id: u64,
+        approve: bool,
+    },
+    /// 任務規格的歷次版本，舊到新
+    ListRevisions {
+        id: u64,
+    },
+    /// 把任務規格改回某一版（會新增一版，不會刪除之後的版本）；受保護的任務要加 force
+    RollbackTask {
+        id: u64,
+        revision: u32,
+        #[serde(default)]
+        force: bool,
+    },
+    /// 列出回收桶中的任務
+    ListTrash,
+    /// 把回收桶中的任務還原（沿用原本的 id）
+    RestoreTask {
+        id: u64,
+    },
+    /// 永久刪除任務：回收桶中或仍在排程中的都可以；受保護的任務要加 force
+    PurgeTask {
+        id: u64,
+        #[serde(default)]
+        force: bool,
+    },
+    /// 試算移除這些任務的影響，不做任何變更（CLI 用來確認）
+    PreviewRemoval {
+        ids: Vec<u64>,
+    },
+    /// 列出具名排程
+    ListSchedules,
+    /// 新增或修改具名排程；引用它的任務立即依新排程重新排定
+    SetSchedule {
+        name: String,
+        schedule: Schedule,
+    },
+    /// 刪除具名排程；仍有任務引用時拒絕
+    RemoveSchedule {
+        name: String,
+    },
+    /// 立即執行一次（不影響原本的排程）；follow 時在同一條連線上即時送回
+    /// 子程序的輸出（RunOutput），最後以 RunDone 結束
+    RunNow {
+        id: u64,
+        #[serde(default)]
+        follow: bool,
+        /// 記在這次 run 上的追查資訊，例如 {"ticket": "OPS-12", "commit": "3f2c1d0"}
+        #[serde(default)]
+        metadata: BTreeMap<String, String>,
+    },
+    /// 重跑 run_id 那一輪依賴鏈中失敗或未執行的節點（沿用當時前置 run 的輸出），
+    /// 重跑的節點之後照常展開它的依賴；成功的分支不再執行
+    RetryChain {
+        run_id: u64,
+    },
+    /// 伺服器版本、啟動時間與啟動時的自我檢查結果
+    GetServerInfo,
+    /// 列出載入時被隔離的持久化紀錄
+    ListQuarantine,
+    /// 修復（或確定不要）後，從隔離檔刪除這筆紀錄
+    DiscardQuarantined {
+        seq: u64,
+    },
+    /// 依賴表的原始內容與不一致之處（除錯用）
+    GetDependencyGraph,
+    /// 預約在 at 對任務執行維護動作（暫停一段時間或移除）；
+    /// 移除受保護的任務要 force
+    ScheduleMaintenance {
+        task_id: u64,
+        action: MaintenanceKind,
+        at: DateTime<FixedOffset>,
+        #[serde(default)]
+        force: bool,
+    },
+    /// 列出尚未結束的維護預約（依生效時間）
+    ListMaintenance,
+    /// 取消一筆維護預約；取消進行中的暫停即提前恢復
+    CancelMaintenance {
+        seq: u64,
+    },
+    /// 設定（rule 為 None 時清除）任務的故障注入規則；伺服器未開啟 [chaos] 時拒絕
+    SetChaos {
+        task_id: u64,
+        #[serde(default)]
+        rule: Option<ChaosRule>,
+    },
+    /// 列出目前的故障注入規則
+    ListChaos,
+    /// 比對清單與伺服器上由 git-sync 管理的任務，回報套用時會做的變更，不實際套用
+    DiffManifest {
+        tasks: Vec<ManifestTask>,
+    },
+    /// 在伺服器設定的另一個環境（[environments.<名稱>]）中處理 request；
+    /// 該環境設定了權杖時須帶上。預設環境的名稱為 [`DEFAULT_ENVIRONMENT`]。不可巢狀
+    InEnvironment {
+        environment: String,
+        #[serde(default)]
+        token: Option<String>,
+        request: Box<ClientRequest>,
+    },
+    /// 全域凍結 duration_secs 秒（例如部署期間）：定時觸發一律略過，時間到自動恢復；
+    /// 已在凍結中時以這次的設定取代
+    Freeze {
+        duration_secs: u64,
+        /// 外部觸發、依賴觸發與手動執行也一併略過
+        #[serde(default)]
+        block_triggered: bool,
+        #[serde(default)]
+        reason: Option<String>,
+    },
+    /// 提前解除凍結
+    Unfreeze,
+    /// 為任務建立觸發網址；token 只在回覆中出現這一次，伺服器只存雜湊
+    CreateTriggerHook {
+        task_id: u64,
+        #[serde(default)]
+        label: Option<String>,
+        /// 允許呼叫者帶的參數名稱（環境變數名稱，例如 GIT_SHA）
+        #[serde(default)]
+        params: Vec<String>,
+    },
+    /// 列出觸發網址（不含 token）
+    ListTriggerHooks,
+    /// 撤銷觸發網址，之後以它的 token 呼叫一律拒絕
+    RevokeTriggerHook {
+        id: u64,
+    },
+}
+
+impl ClientRequest {
+    /// 請求種類（與 JSON 中的標籤相同），供日誌與統計使用
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ClientRequest::AddTask(_) => "AddTask",
+            ClientRequest::RemoveTask { .. } => "RemoveTask",
+            ClientRequest::ListTasks => "ListTasks",
+            ClientRequest::ListTasksSorted { .. } => "ListTasksSorted",
+            ClientRequest::ListTasksSince { .. } => "ListTasksSince",
+            ClientRequest::Search { .. } => "Search",
+            ClientRequest::GetOutput { .. } => "GetOutput",
+            ClientRequest::GetHistory { .. } => "GetHistory",
+            ClientRequest::QueryRuns { .. } => "QueryRuns",
+            ClientRequest::PruneHistory => "PruneHistory",
+            ClientRequest::Subscribe { .. } => "Subscribe",
+            ClientRequest::ListOutbox { .. } => "ListOutbox",
+            ClientRequest::RequeueNotifications { .. } => "RequeueNotifications",
+            ClientRequest::GetContext => "GetContext",
+            ClientRequest::UpdateContext { .. } => "UpdateContext",
+            ClientRequest::GetQueueStats => "GetQueueStats",
+            ClientRequest::VerifyOutputs { .. } => "VerifyOutputs",
+            ClientRequest::ResetBreaker { .. } => "ResetBreaker",
+            ClientRequest::CancelChained { .. } => "CancelChained",
+            ClientRequest::ListApprovals => "ListApprovals",
+            ClientRequest::Approve { .. } => "Approve",
+            ClientRequest::ListRevisions { .. } => "ListRevisions",
+            ClientRequest::RollbackTask { .. } => "RollbackTask",
+            ClientRequest::ListTrash => "ListTrash",
+            ClientRequest::RestoreTask { .. } => "RestoreTask",
+            ClientRequest::PurgeTask { .. } => "PurgeTask",
+            ClientRequest::PreviewRemoval { .. } => "PreviewRemoval",
+            ClientRequest::ListSchedules => "ListSchedules",
+            ClientRequest::SetSchedule { .. } => "SetSchedule",
+            ClientRequest::RemoveSchedule { .. } => "RemoveSchedule",
+            ClientRequest::RunNow { .. } => "RunNow",
+            ClientRequest::RetryChain { .. } => "RetryChain",
+            ClientRequest::GetServerInfo => "GetServerInfo",
+            ClientRequest::ListQuarantine => "ListQuarantine",
+            ClientRequest::DiscardQuarantined { .. } => "DiscardQuarantined",
+            ClientRequest::GetDependencyGraph => "GetDependencyGraph",
+            ClientRequest::ScheduleMaintenance { .. } => "ScheduleMaintenance",
+            ClientRequest::ListMaintenance => "ListMaintenance",
+            ClientRequest::CancelMaintenance { .. } => "CancelMaintenance",
+            ClientRequest::SetChaos { .. } => "SetChaos",
+            ClientRequest::ListChaos => "ListChaos",
+            ClientRequest::DiffManifest { .. } => "DiffManifest",
+            ClientRequest::InEnvironment { request, .. } => request.kind(),
+            ClientRequest::Freeze { .. } => "Freeze",
+            ClientRequest::Unfreeze => "Unfreeze",
+            ClientRequest::CreateTriggerHook { .. } => "CreateTriggerHook",
+            ClientRequest::ListTriggerHooks => "ListTriggerHooks",
+            ClientRequest::RevokeTriggerHook { .. } => "RevokeTriggerHook",
+        }
+    }
+}
+
+/// 帶關聯編號的請求：同一條連線上可連續送出多個，伺服器並行處理，
+/// 回覆為帶同一個 id 的 [`ResponseFrame`]，順序不保證。
+/// 直接送 [`ClientRequest`]（不包 frame）則依序處理、依序回覆
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct RequestFrame {
+    pub id: u64,
+    pub request: ClientRequest,
+}
+
+/// [`RequestFrame`] 的回覆；Subscribe 的每個事件與心跳都帶訂閱請求的 id
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ResponseFrame {
+    pub id: u64,
+    pub response: ServerResponse,
+}
+
+/// 伺服器回報的錯誤；客戶端依種類分支，不必比對訊息字串
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[non_exhaustive]
+pub enum SchedulerError {
+    /// 指定的任務或資料不存在
+    NotFound {
+        msg: String,
+    },
+    /// 排程設定不合法
+    InvalidSchedule {
+        msg: String,
+    },
+    /// 新增後依賴會形成循環；cycle 為循環上的任務 id（依上游方向）
+    CycleDetected {
+        cycle: Vec<u64>,
+    },
+    /// 伺服器不允許的操作（系統任務、保留命名空間、命令政策、配額）
+    Unauthorized {
+        msg: String,
+    },
+    /// 請求本身不合法（無法解析、超出限制、其他規格錯誤）
+    InvalidRequest {
+        msg: String,
+    },
+    Internal {
+        msg: String,
+    },
+    /// 較新伺服器的錯誤種類
+    #[serde(untagged)]
+    #[schemars(skip)]
+    Unknown(Unrecognized),
+}
+
+impl std::fmt::Display for SchedulerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SchedulerError::NotFound { msg }
+            | SchedulerError::Unauthorized { msg }
+            | SchedulerError::InvalidRequest { msg }
+            | SchedulerError::Internal { msg } => f.write_str(msg),
+            SchedulerError::Unknown(_) => f.write_str("unrecognized error"),
+            SchedulerError::InvalidSchedule { msg } => write!(f, "invalid schedule: {msg}"),
+            SchedulerError::CycleDetected { cycle } => {
+                let ids: Vec<String> = cycle
+                    .iter()
+                    .chain(cycle.first())
+                    .map(|id| format!("#{id}"))
+                    .collect();
+                write!(f, "dependency cycle: {}", ids.join(" -> "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for SchedulerError {}
+
+impl SchedulerError {
+    /// 錯誤種類（與 JSON 中的標籤相同）
+    pub fn kind(&self) -> &'static str {
+        match self {
+            SchedulerError::NotFound { .. } => "NotFound",
+            SchedulerError::InvalidSchedule { .. } => "InvalidSchedule",
+            SchedulerError::CycleDetected { .. } => "CycleDetected",
+            SchedulerError::Unauthorized { .. } => "Unauthorized",
+            SchedulerError::InvalidRequest { .. } => "InvalidRequest",
+            SchedulerError::Internal { .. } => "Internal",
+            SchedulerError::Unknown(_) => "Unknown",
+        }
+    }
 }
 
 /// 服務端 → 客戶端
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[non_exhaustive]
 pub enum ServerResponse {
-    Added { id: u64 },
-    Removed { ok: bool },
+    Added {
+        id: u64,
+        /// 已新增但值得注意的問題，例如找不到命令（[cmd_check] 為 warn 時）
+        #[serde(default)]
+        warnings: Vec<String>,
+    },
+    Removed {
+        ok: bool,
+    },
     Tasks(Vec<TaskInfo>),
-    Error(String),
+    /// 依任務的 output_encoding 轉成文字
+    Output {
+        id: u64,
+        content: String,
+    },
+    History(Vec<RunRecord>),
+    Pruned {
+        removed: usize,
+    },
+    Event(Event),
+    /// 訂閱連線的心跳；last_seq 為目前最新的事件編號
+    Heartbeat {
+        last_seq: u64,
+        interval_secs: u64,
+    },
+    Outbox(Vec<Notification>),
+    Requeued {
+        count: usize,
+    },
+    Context(ServerContext),
+    QueueStats(QueueStats),
+    Verified(Vec<OutputCheck>),
+    /// was_open：重設前是否處於跳脫狀態
+    BreakerReset {
+        id: u64,
+        was_open: bool,
+    },
+    CancelledChained {
+        id: u64,
+        count: usize,
+    },
+    Approvals(Vec<PendingApproval>),
+    Decided {
+        run_id: u64,
+        approved: bool,
+    },
+    Revisions(Vec<Revision>),
+    /// revision 為回滾後新增的版本
+    RolledBack {
+        id: u64,
+        revision: u32,
+    },
+    Trash(Vec<TrashedTask>),
+    Restored {
+        id: u64,
+    },
+    RemovalPreview(Vec<RemovalImpact>),
+    Runs(RunReport),
+    Schedules(Vec<NamedSchedule>),
+    /// tasks 為重新排定的任務數
+    ScheduleSet {
+        name: String,
+        tasks: usize,
+    },
+    ScheduleRemoved {
+        name: String,
+    },
+    /// RunNow 已開始執行（未 follow）
+    Triggered {
+        id: u64,
+    },
+    /// follow 中的即時輸出片段；無法解碼的位元組依任務的 output_encoding
+    /// 以 U+FFFD 取代或寫成 `\xNN`
+    RunOutput {
+        task_id: u64,
+        stream: OutputStream,
+        data: String,
+    },
+    /// follow 的 run 已結束
+    RunDone {
+        task_id: u64,
+        run_id: u64,
+        status_code: i32,
+        outcome: RunOutcome,
+    },
+    /// RetryChain 已開始重跑的任務
+    ChainRetried {
+        run_id: u64,
+        tasks: Vec<u64>,
+    },
+    ServerInfo(ServerInfo),
+    Quarantine(Vec<QuarantinedTask>),
+    QuarantineDiscarded {
+        seq: u64,
+    },
+    DependencyGraph(DependencyGraph),
+    MaintenanceScheduled {
+        seq: u64,
+    },
+    Maintenance(Vec<MaintenanceAction>),
+    MaintenanceCancelled {
+        seq: u64,
+    },
+    ChaosSet {
+        task_id: u64,
+    },
+    Chaos(Vec<ChaosEntry>),
+    /// ListTasksSince 的回覆；下次以 version 查詢。full 為 true 時 changed 是完整清單，
+    /// 客戶端應整份取代（查詢的 version 太舊或伺服器已重啟）
+    TaskChanges {
+        version: u64,
+        full: bool,
+        /// 有變更的任務（目前狀態），依 id 排序
+        changed: Vec<TaskInfo>,
+        /// 已移除的任務 id
+        removed: Vec<u64>,
+    },
+    /// DiffManifest 的回覆：依移除、更新、新增的順序（同 git-sync 套用的順序）
+    ManifestDiff(Vec<ManifestChange>),
+    Frozen(FreezeStatus),
+    /// was_frozen 為 false 表示本來就沒有凍結
+    Unfrozen {
+        was_frozen: bool,
+    },
+    TriggerHookCreated {
+        hook: TriggerHookInfo,
+        token: String,
+    },
+    TriggerHooks(Vec<TriggerHookInfo>),
+    TriggerHookRevoked {
+        id: u64,
+    },
+    Error(SchedulerError),
+    /// 較新伺服器的回應種類
+    #[serde(untagged)]
+    #[schemars(skip)]
+    Unknown(Unrecognized),
 }
-
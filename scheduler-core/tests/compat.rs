@@ -0,0 +1,59 @@
+use scheduler_core::{
+    ClientRequest, Event, EventKind, RunOutcome, Schedule, SchedulerError, ServerResponse,
+};
+
+#[test]
+fn unknown_response_variants_are_tolerated() {
+    let resp: ServerResponse = serde_json::from_str(r#"{"Paused":{"id":3,"until":null}}"#).unwrap();
+    assert!(matches!(resp, ServerResponse::Unknown(_)));
+
+    let resp: ServerResponse = serde_json::from_str(r#"{"Added":{"id":3}}"#).unwrap();
+    assert!(matches!(resp, ServerResponse::Added { id: 3, .. }));
+
+    let resp: ServerResponse =
+        serde_json::from_str(r#"{"Error":{"RateLimited":{"retry_after":5}}}"#).unwrap();
+    assert!(matches!(
+        resp,
+        ServerResponse::Error(SchedulerError::Unknown(_))
+    ));
+}
+
+#[test]
+fn unknown_nested_variants_are_tolerated() {
+    let ev: Event = serde_json::from_str(
+        r#"{"seq":7,"at":"2030-01-01T00:00:00+08:00","kind":{"TaskPaused":{"task_id":1}}}"#,
+    )
+    .unwrap();
+    assert!(matches!(ev.kind, EventKind::Unknown(_)));
+
+    let outcome: RunOutcome = serde_json::from_str(r#""Preempted""#).unwrap();
+    assert_eq!(outcome, RunOutcome::Unknown);
+
+    let schedule: Schedule = serde_json::from_str(r#"{"Weekly":{"weekday":1}}"#).unwrap();
+    assert!(matches!(schedule, Schedule::Unknown(_)));
+    assert_eq!(schedule.to_string(), "unknown");
+}
+
+#[test]
+fn unknown_requests_are_errors() {
+    assert!(serde_json::from_str::<ClientRequest>(r#"{"PauseTask":{"id":1}}"#).is_err());
+    assert!(serde_json::from_str::<ClientRequest>(r#"{"RemoveTask":{"id":1}}"#).is_ok());
+    // 舊客戶端的 ListTasks 沒有參數
+    assert!(matches!(
+        serde_json::from_str::<ClientRequest>(r#""ListTasks""#).unwrap(),
+        ClientRequest::ListTasks
+    ));
+}
+
+#[test]
+fn schema_lists_known_variants_only() {
+    let schema = serde_json::to_value(schemars::schema_for!(ServerResponse)).unwrap();
+    let text = schema.to_string();
+    assert!(text.contains("\"Added\""));
+    assert!(!text.contains("Unknown"));
+
+    let spec = serde_json::to_value(schemars::schema_for!(scheduler_core::TaskSpec)).unwrap();
+    let required = spec["required"].as_array().unwrap();
+    assert!(required.contains(&"cmd".into()));
+    assert!(!required.contains(&"success".into()));
+}
@@ -0,0 +1,163 @@
+use chrono::{DateTime, FixedOffset};
+use chrono_tz::America::New_York;
+use scheduler_core::Schedule;
+
+fn parse(s: &str) -> Schedule {
+    s.parse().unwrap_or_else(|e| panic!("{s:?}: {e}"))
+}
+
+#[test]
+fn cron_shortcuts() {
+    assert!(matches!(parse("@reboot"), Schedule::Reboot));
+    assert!(matches!(parse("@hourly"), Schedule::Hourly { minute: 0 }));
+    assert!(matches!(
+        parse("@daily"),
+        Schedule::Daily { hour: 0, minute: 0 }
+    ));
+    assert!(matches!(
+        parse("@midnight"),
+        Schedule::Daily { hour: 0, minute: 0 }
+    ));
+    assert!("@weekly".parse::<Schedule>().is_err());
+}
+
+#[test]
+fn own_syntaxes() {
+    assert!(matches!(
+        parse("daily 08:05"),
+        Schedule::Daily { hour: 8, minute: 5 }
+    ));
+    assert!(matches!(
+        parse("hourly :30"),
+        Schedule::Hourly { minute: 30 }
+    ));
+    assert!(matches!(parse("manual"), Schedule::Manual));
+    assert!(matches!(
+        parse("after #3 +60s expires 300s"),
+        Schedule::After {
+            task_id: 3,
+            delay_secs: 60,
+            expires_after_secs: Some(300),
+        }
+    ));
+    assert!(matches!(
+        parse("after 7"),
+        Schedule::After {
+            task_id: 7,
+            delay_secs: 0,
+            expires_after_secs: None,
+        }
+    ));
+    assert!(matches!(
+        parse("2030-01-02T03:04:05+08:00"),
+        Schedule::Once(_)
+    ));
+}
+
+#[test]
+fn invalid_input_is_rejected() {
+    for s in [
+        "",
+        "daily 24:00",
+        "daily 8",
+        "hourly 60",
+        "hourly :60",
+        "after",
+        "after x",
+        "after 1 +abc",
+        "once tomorrow",
+        "@reboot now",
+        "named",
+        "named two words",
+        "weekly",
+    ] {
+        assert!(s.parse::<Schedule>().is_err(), "{s:?} should not parse");
+    }
+}
+
+#[test]
+fn display_round_trips() {
+    for s in [
+        "@reboot",
+        "daily 23:59",
+        "hourly :07",
+        "after #2",
+        "after #2 +10s expires 60s",
+        "manual",
+        "named business-hours",
+        "once 2030-01-02T03:04:05+08:00",
+    ] {
+        assert_eq!(parse(s).to_string(), s);
+    }
+}
+
+fn at(s: &str) -> DateTime<FixedOffset> {
+    DateTime::parse_from_rfc3339(s).unwrap()
+}
+
+fn next(schedule: &str, after: &str) -> Option<String> {
+    let tz = FixedOffset::east_opt(8 * 3600).unwrap();
+    parse(schedule)
+        .next_occurrence(at(after), &tz)
+        .map(|t| t.to_rfc3339())
+}
+
+fn next_ny(schedule: &str, after: &str) -> Option<String> {
+    parse(schedule)
+        .next_occurrence(at(after), &New_York)
+        .map(|t| t.to_rfc3339())
+}
+
+#[test]
+fn daily_next_occurrence() {
+    let later_today = next("daily 09:30", "2030-01-01T08:00:00+08:00");
+    assert_eq!(later_today.as_deref(), Some("2030-01-01T09:30:00+08:00"));
+    // 剛好等於觸發時間時排到隔天
+    let exact = next("daily 09:30", "2030-01-01T09:30:00+08:00");
+    assert_eq!(exact.as_deref(), Some("2030-01-02T09:30:00+08:00"));
+    let year_end = next("daily 00:00", "2030-12-31T23:59:59+08:00");
+    assert_eq!(year_end.as_deref(), Some("2031-01-01T00:00:00+08:00"));
+    // after 的時區與 tz 不同時依 tz 的牆上時間
+    let utc = next("daily 09:30", "2030-01-01T02:00:00+00:00");
+    assert_eq!(utc.as_deref(), Some("2030-01-02T09:30:00+08:00"));
+}
+
+#[test]
+fn hourly_next_occurrence() {
+    let this_hour = next("hourly :45", "2030-01-01T10:15:00+08:00");
+    assert_eq!(this_hour.as_deref(), Some("2030-01-01T10:45:00+08:00"));
+    let next_hour = next("hourly :15", "2030-01-01T10:15:00+08:00");
+    assert_eq!(next_hour.as_deref(), Some("2030-01-01T11:15:00+08:00"));
+    let next_day = next("@hourly", "2030-01-01T23:30:00+08:00");
+    assert_eq!(next_day.as_deref(), Some("2030-01-02T00:00:00+08:00"));
+}
+
+#[test]
+fn once_and_untimed_next_occurrence() {
+    let once = "once 2030-01-01T12:00:00+08:00";
+    let before = next(once, "2030-01-01T11:00:00+08:00");
+    assert_eq!(before.as_deref(), Some("2030-01-01T12:00:00+08:00"));
+    assert_eq!(next(once, "2030-01-01T12:00:00+08:00"), None);
+    for s in ["after #1 +5s", "manual", "@reboot"] {
+        assert_eq!(next(s, "2030-01-01T00:00:00+08:00"), None, "{s}");
+    }
+}
+
+#[test]
+fn next_occurrence_across_dst() {
+    // 2030-03-10 02:00 EST 跳到 03:00 EDT
+    let gap = next_ny("daily 02:30", "2030-03-10T00:00:00-05:00");
+    assert_eq!(gap.as_deref(), Some("2030-03-10T03:00:00-04:00"));
+    let after_gap = next_ny("daily 02:30", "2030-03-10T03:00:00-04:00");
+    assert_eq!(after_gap.as_deref(), Some("2030-03-11T02:30:00-04:00"));
+    let skipped_hour = next_ny("hourly :30", "2030-03-10T01:45:00-05:00");
+    assert_eq!(skipped_hour.as_deref(), Some("2030-03-10T03:30:00-04:00"));
+
+    // 2030-11-03 02:00 EDT 退回 01:00 EST：01:30 出現兩次，只取第一次
+    let first = next_ny("daily 01:30", "2030-11-03T00:00:00-04:00");
+    assert_eq!(first.as_deref(), Some("2030-11-03T01:30:00-04:00"));
+    let repeat = next_ny("daily 01:30", "2030-11-03T01:30:00-04:00");
+    assert_eq!(repeat.as_deref(), Some("2030-11-04T01:30:00-05:00"));
+    let hourly = next_ny("hourly :30", "2030-11-03T01:30:00-04:00");
+    assert_eq!(hourly.as_deref(), Some("2030-11-03T02:30:00-05:00"));
+}
@@ -0,0 +1,249 @@
+use crate::{config::AccessLogConfig, local_now_fixed, rotate::RotatingFile};
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, FixedOffset};
+use scheduler_core::ServerResponse;
+use serde::Serialize;
+use std::{
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Instant,
+};
+
+/// 存取紀錄（JSON Lines），與 stdout/stderr 的應用程式日誌分開；未設定 [access_log] 時不做事
+pub struct AccessLog {
+    inner: Option<Inner>,
+    next_conn: AtomicU64,
+}
+
+struct Inner {
+    file: Mutex<RotatingFile>,
+    sample_every: u64,
+    connections: bool,
+    /// 成功請求的計數（抽樣用）
+    seen: AtomicU64,
+}
+
+/// 連線的來源
+pub struct Peer {
+    /// "tcp"、"unix"、"ws" 或 "hook"（觸發網址）
+    pub transport: &'static str,
+    pub addr: String,
+    /// 可辨識的呼叫者（Unix socket 的 uid、瀏覽器的 Origin）；TCP 沒有
+    pub identity: Option<String>,
+}
+
+impl Peer {
+    /// 變更紀錄中的「誰」：有身分用身分，否則用來源（不含埠號）
+    pub fn actor(&self) -> String {
+        if let Some(identity) = &self.identity {
+            return identity.clone();
+        }
+        let host = match self.addr.parse::<SocketAddr>() {
+            Ok(addr) => addr.ip().to_string(),
+            Err(_) => self.addr.clone(),
+        };
+        format!("{}:{host}", self.transport)
+    }
+}
+
+/// 一條連線；最後一個參照釋放時（含處理中的 tagged 請求）記錄關閉
+pub struct Conn {
+    log: Arc<AccessLog>,
+    id: u64,
+    peer: Peer,
+    opened: Instant,
+    requests: AtomicU64,
+}
+
+/// 進行中的訂閱
+pub struct Subscription {
+    conn: Arc<Conn>,
+    id: Option<u64>,
+    started: Instant,
+    failed: bool,
+}
+
+#[derive(Serialize)]
+struct Record<'a> {
+    at: DateTime<FixedOffset>,
+    event: &'static str,
+    conn: u64,
+    transport: &'static str,
+    peer: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    identity: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    latency_ms: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    outcome: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    duration_ms: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    requests: Option<u64>,
+}
+
+impl AccessLog {
+    pub fn open(cfg: Option<&AccessLogConfig>) -> Result<Self> {
+        let inner = match cfg {
+            None => None,
+            Some(cfg) => {
+                if cfg.sample_every == 0 {
+                    bail!("access_log.sample_every must be at least 1");
+                }
+                let file = RotatingFile::open(&cfg.path, cfg.rotate.clone())
+                    .with_context(|| format!("open access log {}", cfg.path.display()))?;
+                Some(Inner {
+                    file: Mutex::new(file),
+                    sample_every: cfg.sample_every,
+                    connections: cfg.connections,
+                    seen: AtomicU64::new(0),
+                })
+            }
+        };
+        Ok(Self {
+            inner,
+            next_conn: AtomicU64::new(1),
+        })
+    }
+
+    /// 新連線；記錄 connect 並回傳供後續請求使用的 Conn
+    pub fn connect(self: &Arc<Self>, peer: Peer) -> Arc<Conn> {
+        let conn = Arc::new(Conn {
+            log: self.clone(),
+            id: self.next_conn.fetch_add(1, Ordering::Relaxed),
+            peer,
+            opened: Instant::now(),
+            requests: AtomicU64::new(0),
+        });
+        if self.inner.as_ref().is_some_and(|i| i.connections) {
+            self.write(&conn.record("connect"));
+        }
+        conn
+    }
+
+    fn write(&self, record: &Record) {
+        let Some(inner) = &self.inner else {
+            return;
+        };
+        let mut line = match serde_json::to_vec(record) {
+            Ok(line) => line,
+            Err(e) => return eprintln!("access log encode error: {e}"),
+        };
+        line.push(b'\n');
+        // 整行一次寫入，並行的連線不會交錯
+        if let Err(e) = inner.file.lock().unwrap().write_line(&line) {
+            eprintln!("access log write error: {e}");
+        }
+    }
+}
+
+impl Conn {
+    pub fn actor(&self) -> String {
+        self.peer.actor()
+    }
+
+    fn record(&self, event: &'static str) -> Record<'_> {
+        Record {
+            at: local_now_fixed(),
+            event,
+            conn: self.id,
+            transport: self.peer.transport,
+            peer: &self.peer.addr,
+            identity: self.peer.identity.as_deref(),
+            request: None,
+            id: None,
+            latency_ms: None,
+            outcome: None,
+            duration_ms: None,
+            requests: None,
+        }
+    }
+
+    /// 記錄一個已回覆的請求；kind 為 ClientRequest::kind，無法解析時為 "invalid"
+    pub fn request(
+        &self,
+        kind: &'static str,
+        id: Option<u64>,
+        started: Instant,
+        resp: &ServerResponse,
+    ) {
+        let error = match resp {
+            ServerResponse::Error(e) => Some(e.kind()),
+            _ => None,
+        };
+        self.log_request(kind, id, started, error);
+    }
+
+    /// 開始一個訂閱；回傳的 guard 在訂閱結束（含連線中斷）時記錄，latency 即訂閱時間
+    pub fn subscription(self: &Arc<Self>, id: Option<u64>) -> Subscription {
+        Subscription {
+            conn: self.clone(),
+            id,
+            started: Instant::now(),
+            failed: false,
+        }
+    }
+
+    /// 成功的依 sample_every 抽樣，錯誤一律記錄
+    fn log_request(
+        &self,
+        kind: &'static str,
+        id: Option<u64>,
+        started: Instant,
+        error: Option<&'static str>,
+    ) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        let Some(inner) = &self.log.inner else {
+            return;
+        };
+        if error.is_none() && inner.seen.fetch_add(1, Ordering::Relaxed) % inner.sample_every != 0 {
+            return;
+        }
+        self.log.write(&Record {
+            request: Some(kind),
+            id,
+            latency_ms: Some(millis(started)),
+            outcome: Some(error.unwrap_or("ok")),
+            ..self.record("request")
+        });
+    }
+}
+
+impl Drop for Conn {
+    fn drop(&mut self) {
+        if !self.log.inner.as_ref().is_some_and(|i| i.connections) {
+            return;
+        }
+        self.log.write(&Record {
+            duration_ms: Some(millis(self.opened)),
+            requests: Some(self.requests.load(Ordering::Relaxed)),
+            ..self.record("close")
+        });
+    }
+}
+
+impl Subscription {
+    /// 因落後太多等錯誤而中斷
+    pub fn fail(&mut self) {
+        self.failed = true;
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        let error = self.failed.then_some("Internal");
+        self.conn
+            .log_request("Subscribe", self.id, self.started, error);
+    }
+}
+
+fn millis(since: Instant) -> f64 {
+    since.elapsed().as_secs_f64() * 1000.0
+}
@@ -0,0 +1,114 @@
+use crate::{local_now_fixed, record_skip, State};
+use anyhow::{bail, Result};
+use scheduler_core::{Approval, EventKind, PendingApproval, TaskSpec};
+use std::{fmt, sync::Arc, time::Duration};
+use tokio::sync::oneshot;
+
+/// 等待中的核准與通知等待者的通道
+pub struct Waiting {
+    pub info: PendingApproval,
+    decide: oneshot::Sender<bool>,
+}
+
+/// 核准關卡未通過（駁回、逾時、任務被移除）；依賴鏈到此為止
+#[derive(Debug)]
+pub struct NotApproved(String);
+
+impl fmt::Display for NotApproved {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for NotApproved {}
+
+/// 以 run_id 登記一筆待核准的 run 並等待決定；核准時回傳 run_id
+pub async fn wait(
+    state: &Arc<State>,
+    task_id: u64,
+    run_id: u64,
+    spec: &TaskSpec,
+    gate: &Approval,
+) -> Result<u64> {
+    let now = local_now_fixed();
+    let expires_at = gate
+        .timeout_secs
+        .map(|s| now + chrono::Duration::seconds(s as i64));
+    let (tx, rx) = oneshot::channel();
+    state.approvals.insert(
+        run_id,
+        Waiting {
+            info: PendingApproval {
+                run_id,
+                task_id,
+                requested_at: now,
+                expires_at,
+            },
+            decide: tx,
+        },
+    );
+    // 等待被放棄（例如被擠出 run 佇列）時也要撤掉登記
+    let registered = Registered { state, run_id };
+    println!("✋ task {} run {} waiting for approval", task_id, run_id);
+    state.events.emit(EventKind::ApprovalRequested {
+        task_id,
+        run_id,
+        expires_at,
+    });
+
+    let decision = match gate.timeout_secs {
+        Some(secs) => tokio::time::timeout(Duration::from_secs(secs), rx).await,
+        None => Ok(rx.await),
+    };
+    drop(registered);
+    let reason = match decision {
+        Ok(Ok(true)) => {
+            println!("👍 task {} run {} approved", task_id, run_id);
+            return Ok(run_id);
+        }
+        Ok(Ok(false)) => "approval rejected".to_string(),
+        Ok(Err(_)) => "approval cancelled".to_string(),
+        Err(_) => format!(
+            "approval not granted within {}s",
+            gate.timeout_secs.unwrap_or_default()
+        ),
+    };
+    println!("👎 task {} run {} {}", task_id, run_id, reason);
+    record_skip(state, task_id, spec, Some(run_id), reason.clone()).await;
+    bail!(NotApproved(reason))
+}
+
+struct Registered<'a> {
+    state: &'a State,
+    run_id: u64,
+}
+
+impl Drop for Registered<'_> {
+    fn drop(&mut self) {
+        self.state.approvals.remove(&self.run_id);
+    }
+}
+
+/// 送出決定；找不到等待中的 run 時回傳 false
+pub fn decide(state: &State, run_id: u64, approve: bool) -> bool {
+    match state.approvals.remove(&run_id) {
+        Some((_, w)) => w.decide.send(approve).is_ok(),
+        None => false,
+    }
+}
+
+/// 目前等待核准的 run，依 run 編號排序
+pub fn pending(state: &State) -> Vec<PendingApproval> {
+    let mut list: Vec<PendingApproval> = state
+        .approvals
+        .iter()
+        .map(|kv| kv.value().info.clone())
+        .collect();
+    list.sort_by_key(|a| a.run_id);
+    list
+}
+
+/// 任務移除時取消它等待中的核准
+pub fn cancel_task(state: &State, task_id: u64) {
+    state.approvals.retain(|_, w| w.info.task_id != task_id);
+}
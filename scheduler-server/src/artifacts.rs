@@ -0,0 +1,85 @@
+use crate::config::{ArtifactsConfig, Compression};
+use anyhow::{Context, Result};
+use chrono::{DateTime, FixedOffset};
+use std::{
+    io::{Read, Write},
+    path::{Path, PathBuf},
+};
+
+/// 保存一次執行的輸出：<dir>/<task_id>/<時間>.log[.gz|.zst]
+/// 未設定 artifacts 目錄時回傳 None
+pub fn store(
+    cfg: &ArtifactsConfig,
+    id: u64,
+    at: DateTime<FixedOffset>,
+    content: &[u8],
+) -> Result<Option<PathBuf>> {
+    let Some(dir) = &cfg.dir else {
+        return Ok(None);
+    };
+    let task_dir = dir.join(id.to_string());
+    std::fs::create_dir_all(&task_dir)
+        .with_context(|| format!("create artifacts dir {}", task_dir.display()))?;
+
+    let stem = at.format("%Y%m%dT%H%M%S%.3f").to_string();
+    let (ext, bytes) = encode(cfg.compression, content)?;
+    let path = task_dir.join(format!("{stem}.{ext}"));
+
+    std::fs::write(&path, bytes).with_context(|| format!("write {}", path.display()))?;
+    Ok(Some(path))
+}
+
+/// 依壓縮方式編碼輸出；回傳副檔名與內容
+pub fn encode(compression: Compression, content: &[u8]) -> Result<(&'static str, Vec<u8>)> {
+    Ok(match compression {
+        Compression::None => ("log", content.to_vec()),
+        Compression::Gzip => {
+            let mut enc = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            enc.write_all(content)?;
+            ("log.gz", enc.finish()?)
+        }
+        Compression::Zstd => ("log.zst", zstd::encode_all(content, 0)?),
+    })
+}
+
+/// 讀回某任務最近一次的輸出（依副檔名自動解壓縮）
+pub fn read_latest(cfg: &ArtifactsConfig, id: u64) -> Result<Option<Vec<u8>>> {
+    let Some(dir) = &cfg.dir else {
+        return Ok(None);
+    };
+    let task_dir = dir.join(id.to_string());
+    if !task_dir.exists() {
+        return Ok(None);
+    }
+
+    // 檔名以時間開頭，字典序最大者即最新
+    let mut latest: Option<PathBuf> = None;
+    for ent in std::fs::read_dir(&task_dir)? {
+        let p = ent?.path();
+        if latest
+            .as_ref()
+            .is_none_or(|cur| p.file_name() > cur.file_name())
+        {
+            latest = Some(p);
+        }
+    }
+
+    match latest {
+        Some(p) => Ok(Some(read_artifact(&p)?)),
+        None => Ok(None),
+    }
+}
+
+pub fn read_artifact(path: &Path) -> Result<Vec<u8>> {
+    let raw = std::fs::read(path).with_context(|| format!("read {}", path.display()))?;
+    let name = path.to_string_lossy();
+    if name.ends_with(".gz") {
+        let mut out = Vec::new();
+        flate2::read::GzDecoder::new(&raw[..]).read_to_end(&mut out)?;
+        Ok(out)
+    } else if name.ends_with(".zst") {
+        Ok(zstd::decode_all(&raw[..])?)
+    } else {
+        Ok(raw)
+    }
+}
@@ -0,0 +1,78 @@
+use crate::{local_now_fixed, State};
+use anyhow::{bail, Result};
+use scheduler_core::{CircuitBreaker, EventKind, RunOutcome};
+
+/// 斷路器跳脫時擋下這次執行；冷卻時間已過則放行一次試跑
+pub fn check(state: &State, task_id: u64, cb: &CircuitBreaker) -> Result<()> {
+    let mut st = state.breakers.entry(task_id).or_default();
+    let Some(since) = st.open_since else {
+        return Ok(());
+    };
+    let now = local_now_fixed();
+    match cb.cooldown_secs {
+        None => bail!(
+            "circuit breaker open since {since} after {} consecutive failures (reset required)",
+            st.consecutive_failures
+        ),
+        Some(secs) if (now - since).num_seconds() < secs as i64 => {
+            bail!("circuit breaker open since {since}: next trial after {secs}s cooldown")
+        }
+        Some(_) => {
+            // 試跑佔用這一輪冷卻，期間的其他觸發仍被擋下
+            st.open_since = Some(now);
+            println!("🔌 task {} circuit breaker half-open: trial run", task_id);
+            Ok(())
+        }
+    }
+}
+
+/// 依執行結果更新連續失敗次數；達到門檻時跳脫並發出事件
+pub fn record(
+    state: &State,
+    task_id: u64,
+    cb: &CircuitBreaker,
+    outcome: RunOutcome,
+    status_code: i32,
+) {
+    // 被取消的 run 不算成敗
+    if outcome == RunOutcome::Cancelled {
+        return;
+    }
+    let mut st = state.breakers.entry(task_id).or_default();
+    if outcome == RunOutcome::Exited && status_code == 0 {
+        if st.open_since.is_some() {
+            println!(
+                "🔌 task {} circuit breaker closed after successful run",
+                task_id
+            );
+        }
+        *st = Default::default();
+        return;
+    }
+
+    st.consecutive_failures += 1;
+    if st.consecutive_failures < cb.failures {
+        return;
+    }
+    let tripped = st.open_since.is_none();
+    st.open_since = Some(local_now_fixed());
+    let failures = st.consecutive_failures;
+    drop(st);
+    if tripped {
+        eprintln!(
+            "🔌 task {} circuit breaker opened after {} consecutive failures",
+            task_id, failures
+        );
+        state
+            .events
+            .emit(EventKind::BreakerOpened { task_id, failures });
+    }
+}
+
+/// 人工重設；回傳重設前是否處於跳脫狀態
+pub fn reset(state: &State, task_id: u64) -> bool {
+    state
+        .breakers
+        .remove(&task_id)
+        .is_some_and(|(_, st)| st.open_since.is_some())
+}
@@ -0,0 +1,248 @@
+use crate::{
+    config::{BridgeConfig, BridgeKind},
+    fire_triggers, metadata, trigger_subjects, State,
+};
+use anyhow::{bail, Result};
+use futures_util::{stream, StreamExt};
+use redis::AsyncCommands;
+use scheduler_core::{Event, EventKind, Trigger};
+use std::{collections::BTreeSet, sync::Arc, time::Duration};
+use tokio::sync::broadcast;
+
+/// 連線失敗後重試前的等待
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// 啟動 Redis/NATS 橋接：發布生命週期事件、依任務 triggers 訂閱
+pub fn spawn(state: Arc<State>) {
+    let Some(cfg) = state.config.bridge.clone() else {
+        return;
+    };
+    match cfg.kind {
+        BridgeKind::Redis => {
+            tokio::spawn(redis_loop(state, cfg));
+        }
+        BridgeKind::Nats => {
+            tokio::spawn(nats_loop(state, cfg));
+        }
+    }
+}
+
+/// 新增任務時檢查 trigger 設定
+pub fn check_triggers(cfg: Option<&BridgeConfig>, triggers: &[Trigger]) -> Result<()> {
+    for t in triggers {
+        if let Trigger::Bridge { subject } = t {
+            if cfg.is_none() {
+                bail!("bridge trigger requires the [bridge] server configuration");
+            }
+            if subject.is_empty() || subject.chars().any(char::is_whitespace) {
+                bail!("invalid bridge subject {subject:?}");
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 事件發布用的名稱（subject 最後一段）
+fn event_name(kind: &EventKind) -> &'static str {
+    match kind {
+        EventKind::TaskAdded { .. } => "task_added",
+        EventKind::TaskRemoved { .. } => "task_removed",
+        EventKind::TaskUpdated { .. } => "task_updated",
+        EventKind::RunStarted { .. } => "run_started",
+        EventKind::RunFinished { .. } => "run_finished",
+        EventKind::RunSkipped { .. } => "run_skipped",
+        EventKind::RunKilled { .. } => "run_killed",
+        EventKind::BreakerOpened { .. } => "breaker_opened",
+        EventKind::ApprovalRequested { .. } => "approval_requested",
+        EventKind::DigestReady { .. } => "digest_ready",
+        EventKind::RunProgress { .. } => "run_progress",
+        EventKind::ServerStarted { .. } => "server_started",
+        _ => "unknown",
+    }
+}
+
+fn wanted(state: &State) -> BTreeSet<String> {
+    trigger_subjects(state, |t| match t {
+        Trigger::Bridge { subject } => Some(subject),
+        _ => None,
+    })
+}
+
+/// 訊息內容帶的追查資訊（見 metadata::from_payload）記在觸發的 run 上
+fn fire(state: &Arc<State>, kind: &str, subject: &str, payload: &[u8]) {
+    fire_triggers(
+        state,
+        &format!("{kind} {subject}"),
+        metadata::from_payload(payload),
+        |t| matches!(t, Trigger::Bridge { subject: s } if s == subject),
+    );
+}
+
+/// 事件匯流排的下一筆；落後時略過並提示
+async fn next_event(rx: &mut broadcast::Receiver<Event>) -> Option<Event> {
+    loop {
+        match rx.recv().await {
+            Ok(ev) => return Some(ev),
+            Err(broadcast::error::RecvError::Lagged(n)) => {
+                eprintln!("bridge event publisher skipped {n} event(s)");
+            }
+            Err(broadcast::error::RecvError::Closed) => return None,
+        }
+    }
+}
+
+async fn redis_loop(state: Arc<State>, cfg: BridgeConfig) {
+    let client = match redis::Client::open(cfg.url.as_str()) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("❌ bridge: invalid redis url {}: {e}", cfg.url);
+            return;
+        }
+    };
+
+    if cfg.publish_events {
+        let client = client.clone();
+        let st = state.clone();
+        let prefix = cfg.event_prefix.clone();
+        tokio::spawn(async move {
+            let (_, _, mut rx) = st.events.subscribe(None);
+            let mut conn = None;
+            while let Some(ev) = next_event(&mut rx).await {
+                if conn.is_none() {
+                    match client.get_multiplexed_async_connection().await {
+                        Ok(c) => conn = Some(c),
+                        Err(e) => {
+                            eprintln!("bridge: redis connect error: {e}");
+                            continue;
+                        }
+                    }
+                }
+                let Some(c) = conn.as_mut() else { continue };
+                let channel = format!("{prefix}.{}", event_name(&ev.kind));
+                let payload = serde_json::to_vec(&ev).unwrap_or_default();
+                if let Err(e) = c.publish::<_, _, ()>(channel, payload).await {
+                    eprintln!("bridge: redis publish error: {e}");
+                    conn = None; // 下一筆重新連線
+                }
+            }
+        });
+    }
+
+    // 訂閱：任務 triggers 變動時重開 pubsub 連線，以最新的 channel 集合重新訂閱
+    let mut changed = state.triggers_changed.subscribe();
+    loop {
+        let channels = wanted(&state);
+        if channels.is_empty() {
+            if changed.changed().await.is_err() {
+                return;
+            }
+            continue;
+        }
+        let mut pubsub = match client.get_async_pubsub().await {
+            Ok(p) => p,
+            Err(e) => {
+                eprintln!("bridge: redis connect error: {e}");
+                tokio::time::sleep(RECONNECT_DELAY).await;
+                continue;
+            }
+        };
+        let mut ok = true;
+        for ch in &channels {
+            if let Err(e) = pubsub.subscribe(ch).await {
+                eprintln!("bridge: redis subscribe {ch} error: {e}");
+                ok = false;
+                break;
+            }
+        }
+        if !ok {
+            tokio::time::sleep(RECONNECT_DELAY).await;
+            continue;
+        }
+        println!(
+            "🌉 bridge subscribed to {} redis channel(s)",
+            channels.len()
+        );
+
+        let mut messages = pubsub.on_message();
+        loop {
+            tokio::select! {
+                msg = messages.next() => match msg {
+                    Some(m) => fire(&state, "redis channel", m.get_channel_name(), m.get_payload_bytes()),
+                    None => {
+                        eprintln!("bridge: redis connection lost");
+                        tokio::time::sleep(RECONNECT_DELAY).await;
+                        break;
+                    }
+                },
+                res = changed.changed() => {
+                    if res.is_err() {
+                        return;
+                    }
+                    if wanted(&state) != channels {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
+
+async fn nats_loop(state: Arc<State>, cfg: BridgeConfig) {
+    // 第一次連線失敗時重試；連上後由 client 自行重連並恢復訂閱
+    let client = loop {
+        match async_nats::connect(cfg.url.as_str()).await {
+            Ok(c) => break c,
+            Err(e) => {
+                eprintln!("bridge: nats connect error: {e}");
+                tokio::time::sleep(RECONNECT_DELAY).await;
+            }
+        }
+    };
+    println!("🌉 bridge connected to {}", cfg.url);
+
+    if cfg.publish_events {
+        let client = client.clone();
+        let st = state.clone();
+        let prefix = cfg.event_prefix.clone();
+        tokio::spawn(async move {
+            let (_, _, mut rx) = st.events.subscribe(None);
+            while let Some(ev) = next_event(&mut rx).await {
+                let subject = format!("{prefix}.{}", event_name(&ev.kind));
+                let payload = serde_json::to_vec(&ev).unwrap_or_default();
+                if let Err(e) = client.publish(subject, payload.into()).await {
+                    eprintln!("bridge: nats publish error: {e}");
+                }
+            }
+        });
+    }
+
+    // 每個 subscription 的訊息都標上它的 subject，萬用字元才對得回任務
+    let mut changed = state.triggers_changed.subscribe();
+    loop {
+        let subjects = wanted(&state);
+        let mut subs = Vec::new();
+        for s in &subjects {
+            match client.subscribe(s.clone()).await {
+                Ok(sub) => {
+                    let s = s.clone();
+                    subs.push(sub.map(move |m| (s.clone(), m.payload)));
+                }
+                Err(e) => eprintln!("bridge: nats subscribe {s} error: {e}"),
+            }
+        }
+        let mut messages = stream::select_all(subs);
+        loop {
+            tokio::select! {
+                Some((subject, payload)) = messages.next() => fire(&state, "nats subject", &subject, &payload),
+                res = changed.changed() => {
+                    if res.is_err() {
+                        return;
+                    }
+                    if wanted(&state) != subjects {
+                        break; // 丟掉舊的 subscription 即取消訂閱
+                    }
+                }
+            }
+        }
+    }
+}
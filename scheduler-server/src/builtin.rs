@@ -0,0 +1,299 @@
+use crate::{
+    disk, exec::ExecOutput, history, local_now_fixed, spawn_scheduler_loop, verify, State,
+    TaskEntry,
+};
+use anyhow::{bail, Context, Result};
+use scheduler_core::{
+    CheckStatus, OutputEncoding, Priority, RunOutcome, SchedClass, Schedule, TaskSpec,
+    SYSTEM_NAMESPACE,
+};
+use std::{
+    collections::HashSet,
+    fmt::Write,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+use tokio_util::sync::CancellationToken;
+
+/// 內建任務的 cmd 前綴；客戶端不可使用
+pub const CMD_PREFIX: &str = "builtin:";
+
+/// 伺服器為自己排程的內建任務，列在 system 命名空間
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Builtin {
+    DiskUsage,
+    IntegrityCheck,
+    HistoryPrune,
+}
+
+impl Builtin {
+    const ALL: [Builtin; 3] = [
+        Builtin::DiskUsage,
+        Builtin::IntegrityCheck,
+        Builtin::HistoryPrune,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Builtin::DiskUsage => "disk-usage",
+            Builtin::IntegrityCheck => "integrity-check",
+            Builtin::HistoryPrune => "history-prune",
+        }
+    }
+
+    /// 內建任務使用保留的最大 ID，避免與一般任務衝突
+    fn reserved_id(self) -> u64 {
+        match self {
+            Builtin::DiskUsage => u64::MAX,
+            Builtin::IntegrityCheck => u64::MAX - 1,
+            Builtin::HistoryPrune => u64::MAX - 2,
+        }
+    }
+
+    pub fn from_spec(spec: &TaskSpec) -> Option<Self> {
+        if spec.namespace != SYSTEM_NAMESPACE {
+            return None;
+        }
+        let name = spec.cmd.strip_prefix(CMD_PREFIX)?;
+        Self::ALL.into_iter().find(|b| b.name() == name)
+    }
+
+    pub async fn run(self, state: &Arc<State>) -> ExecOutput {
+        let (ok, report) = match self {
+            Builtin::DiskUsage => disk_usage(state),
+            Builtin::IntegrityCheck => integrity_check(state),
+            Builtin::HistoryPrune => history::blocking(state, history_prune)
+                .await
+                .unwrap_or_else(|e| (false, format!("ERR {e:#}\n"))),
+        };
+        ExecOutput {
+            status_code: if ok { 0 } else { 1 },
+            outcome: RunOutcome::Exited,
+            stdout: report.into_bytes(),
+            stderr: Vec::new(),
+            usage: None,
+        }
+    }
+}
+
+/// 依設定登記內建任務（不持久化，每次啟動重新建立）
+pub fn register(state: &Arc<State>) -> Result<()> {
+    let cfg = &state.config.builtin;
+    for b in Builtin::ALL {
+        let at = match b {
+            Builtin::DiskUsage => &cfg.disk_usage_at,
+            Builtin::IntegrityCheck => &cfg.integrity_check_at,
+            Builtin::HistoryPrune => &cfg.history_prune_at,
+        };
+        let Some(at) = at else { continue };
+        let (hour, minute) =
+            parse_hhmm(at).with_context(|| format!("builtin {} time {at:?}", b.name()))?;
+
+        let id = b.reserved_id();
+        let spec = TaskSpec {
+            name: Some(b.name().to_string()),
+            tags: Vec::new(),
+            namespace: SYSTEM_NAMESPACE.to_string(),
+            owner: None,
+            metric_labels: Default::default(),
+            cmd: format!("{CMD_PREFIX}{}", b.name()),
+            args: Vec::new(),
+            env: Default::default(),
+            clean_env: false,
+            skip_cmd_check: false,
+            outputs: None,
+            locks: Vec::new(),
+            throttle: None,
+            triggers: Vec::new(),
+            healthcheck: None,
+            allowed_window: None,
+            approval: None,
+            breaker: None,
+            priority: Priority::Normal,
+            success: None,
+            output_encoding: OutputEncoding::Lossy,
+            cleanup: Vec::new(),
+            exact_start: false,
+            protected: false,
+            source: None,
+            output_path: cfg.output_path.clone(),
+            append: true,
+            output_perms: None,
+            schedule: Schedule::Daily { hour, minute },
+            timeout_secs: None,
+            stop_signal: None,
+            stop_grace_secs: None,
+            sched: SchedClass::default(),
+            sandbox: None,
+        };
+        let tok = CancellationToken::new();
+        spawn_scheduler_loop(id, spec.clone(), tok.clone(), state.clone());
+        state.tasks.insert(
+            id,
+            TaskEntry {
+                spec,
+                cancel: Some(tok),
+                last_result: Arc::new(Mutex::new(None)),
+            },
+        );
+    }
+    Ok(())
+}
+
+fn parse_hhmm(s: &str) -> Result<(u32, u32)> {
+    let Some((h, m)) = s.split_once(':') else {
+        bail!("expected HH:MM");
+    };
+    let (hour, minute): (u32, u32) = (h.parse()?, m.parse()?);
+    if hour > 23 || minute > 59 {
+        bail!("time out of range");
+    }
+    Ok((hour, minute))
+}
+
+/// 檢查資料檔、artifacts、內建報告所在檔案系統的剩餘空間
+fn disk_usage(state: &State) -> (bool, String) {
+    let cfg = &state.config;
+    let mut paths = vec![cfg.data_path.as_path(), cfg.builtin.output_path.as_path()];
+    if let Some(dir) = &cfg.artifacts.dir {
+        paths.push(dir.as_path());
+    }
+
+    let mut ok = true;
+    let mut report = String::new();
+    for p in paths {
+        match disk::usage(p) {
+            Ok((free, total)) => {
+                let pct = free.saturating_mul(100).checked_div(total).unwrap_or(100);
+                let low = pct < u64::from(cfg.builtin.min_free_percent);
+                ok &= !low;
+                let _ = writeln!(
+                    report,
+                    "{} {}: {} MiB free of {} MiB ({}%)",
+                    if low { "LOW" } else { "ok " },
+                    p.display(),
+                    free / (1024 * 1024),
+                    total / (1024 * 1024),
+                    pct
+                );
+            }
+            Err(e) => {
+                ok = false;
+                let _ = writeln!(report, "ERR {}: {e:#}", p.display());
+            }
+        }
+    }
+    (ok, report)
+}
+
+/// 依 [history] 保留政策清理執行歷史
+fn history_prune(state: &State) -> (bool, String) {
+    match state
+        .history
+        .prune(&state.config.history, local_now_fixed())
+    {
+        Ok(removed) => (true, format!("removed {removed} history record(s)\n")),
+        Err(e) => (false, format!("ERR {e:#}\n")),
+    }
+}
+
+/// 比對持久化檔案與記憶體中的任務表，並驗證輸出的 checksum
+fn integrity_check(state: &State) -> (bool, String) {
+    let mut report = String::new();
+    let mut problems = match check_data_file(state, &state.config.data_path, &mut report) {
+        Ok(n) => n,
+        Err(e) => {
+            let _ = writeln!(report, "ERR {e:#}");
+            1
+        }
+    };
+    for c in verify::run(state, None) {
+        if c.status != CheckStatus::Ok {
+            let _ = writeln!(
+                report,
+                "task {} run {}: {} {:?}",
+                c.task_id,
+                c.run_id,
+                c.path.display(),
+                c.status
+            );
+            problems += 1;
+        }
+    }
+    let _ = writeln!(report, "{problems} problem(s) found");
+    (problems == 0, report)
+}
+
+/// 只比對持久化檔案與記憶體中的任務表（啟動時用；輸出的 checksum 留給定期檢查）
+pub fn check_storage(state: &State) -> (bool, String) {
+    let path = &state.config.data_path;
+    let persisted = state
+        .tasks
+        .iter()
+        .any(|kv| kv.value().spec.namespace != SYSTEM_NAMESPACE);
+    if !path.exists() && !persisted {
+        return (true, format!("{} does not exist yet\n", path.display()));
+    }
+    let mut report = String::new();
+    let problems = match check_data_file(state, path, &mut report) {
+        Ok(n) => n,
+        Err(e) => {
+            let _ = writeln!(report, "ERR {e:#}");
+            1
+        }
+    };
+    let _ = writeln!(report, "{problems} problem(s) found");
+    (problems == 0, report)
+}
+
+fn check_data_file(state: &State, path: &Path, report: &mut String) -> Result<usize> {
+    let bytes = std::fs::read(path).with_context(|| format!("read {}", path.display()))?;
+    let records: Vec<serde_json::Value> =
+        serde_json::from_slice(&bytes).with_context(|| format!("parse {}", path.display()))?;
+
+    let mut problems = 0;
+    let mut on_disk = HashSet::new();
+    for (i, rec) in records.iter().enumerate() {
+        let Some(id) = rec.get("id").and_then(|v| v.as_u64()) else {
+            let _ = writeln!(report, "record #{i}: missing id");
+            problems += 1;
+            continue;
+        };
+        if !on_disk.insert(id) {
+            let _ = writeln!(report, "task {id}: duplicated record");
+            problems += 1;
+        }
+        let spec = rec.get("spec").cloned().unwrap_or_default();
+        if let Err(e) = serde_json::from_value::<TaskSpec>(spec) {
+            let _ = writeln!(report, "task {id}: malformed spec: {e}");
+            problems += 1;
+        }
+    }
+
+    // 先複製出來，避免持有 DashMap guard 時再查同一張表
+    let in_memory: Vec<(u64, TaskSpec)> = state
+        .tasks
+        .iter()
+        .filter(|kv| kv.value().spec.namespace != SYSTEM_NAMESPACE)
+        .map(|kv| (*kv.key(), kv.value().spec.clone()))
+        .collect();
+    for (id, spec) in in_memory {
+        if !on_disk.contains(&id) {
+            let _ = writeln!(report, "task {id}: in memory but not persisted");
+            problems += 1;
+        }
+        if let Schedule::After { task_id, .. } = spec.schedule {
+            if !state.tasks.contains_key(&task_id) {
+                let _ = writeln!(report, "task {id}: depends on missing task {task_id}");
+                problems += 1;
+            }
+        }
+    }
+    for id in on_disk {
+        if !state.tasks.contains_key(&id) {
+            let _ = writeln!(report, "task {id}: persisted but not loaded");
+            problems += 1;
+        }
+    }
+    Ok(problems)
+}
@@ -0,0 +1,214 @@
+use crate::{dependents_of, duration_to, enter_queue, evicted, persist, run_chained, State};
+use anyhow::{bail, Result};
+use chrono::{DateTime, FixedOffset};
+use scheduler_core::{
+    DependencyEdge, DependencyGraph, DependencyIssue, DependencyIssueKind, RunResult, RunStatus,
+    Schedule, SchedulerError, TaskSpec, WatchEntry,
+};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+use tokio_util::sync::CancellationToken;
+
+/// 排定序號（只用來區分同一任務的多筆排定）
+static NEXT_SEQ: AtomicU64 = AtomicU64::new(1);
+
+/// 依賴鏈中等待延遲的 run；隨任務持久化，重啟後重新排定
+pub struct Pending {
+    pub task_id: u64,
+    pub due: DateTime<FixedOffset>,
+    cancel: CancellationToken,
+}
+
+/// 排定 task_id 在 due 執行（之後照常展開它的依賴）；已過時則立即執行。
+/// 等待期間佔著 run 佇列的位置；佇列不收時不排定
+pub fn schedule(state: &Arc<State>, task_id: u64, due: DateTime<FixedOffset>) {
+    let Ok(slot) = enter_queue(state, task_id) else {
+        return;
+    };
+    let seq = NEXT_SEQ.fetch_add(1, Ordering::SeqCst);
+    let cancel = CancellationToken::new();
+    state.chained.insert(
+        seq,
+        Pending {
+            task_id,
+            due,
+            cancel: cancel.clone(),
+        },
+    );
+    println!(
+        "⛓️ task {} scheduled at {} (waiting on chain)",
+        task_id, due
+    );
+
+    let state = state.clone();
+    tokio::spawn(async move {
+        let dropped = tokio::select! {
+            _ = tokio::time::sleep(duration_to(due)) => false,
+            _ = slot.evicted() => true,
+            _ = cancel.cancelled() => return,
+        };
+        state.chained.remove(&seq);
+        if let Err(e) = persist(&state).await {
+            eprintln!("task {} persist chained run error: {e:?}", task_id);
+        }
+        if dropped {
+            eprintln!("⚠️ {:#}", evicted(&state, task_id));
+            return;
+        }
+        let Some(spec) = state.tasks.get(&task_id).map(|e| e.value().spec.clone()) else {
+            return;
+        };
+        run_chained(task_id, spec, state, slot).await;
+    });
+}
+
+/// 任務排定中的時間，早到晚
+pub fn due_times(state: &State, task_id: u64) -> Vec<DateTime<FixedOffset>> {
+    let mut list: Vec<_> = state
+        .chained
+        .iter()
+        .filter(|kv| kv.value().task_id == task_id)
+        .map(|kv| kv.value().due)
+        .collect();
+    list.sort();
+    list
+}
+
+/// 取消任務所有排定中的 run；回傳取消筆數
+pub fn cancel_task(state: &State, task_id: u64) -> usize {
+    let mut count = 0;
+    state.chained.retain(|_, p| {
+        if p.task_id != task_id {
+            return true;
+        }
+        p.cancel.cancel();
+        count += 1;
+        false
+    });
+    count
+}
+
+/// RetryChain 要重跑的節點；upstream 為當時前置 run 的結果（代入 {{upstream.*}}），
+/// 紀錄已被清掉時為 None，改用前置任務最近一次的結果
+pub struct Retry {
+    pub task_id: u64,
+    pub spec: TaskSpec,
+    pub upstream: Option<RunResult>,
+}
+
+/// 找出 run_id 那一輪依賴鏈中要重跑的節點：run_id 本身失敗就只重跑它；
+/// 否則往下游找，沒有接續這一輪的紀錄（被略過、未執行）或失敗的依賴都要重跑，
+/// 成功的依賴再往下找。重跑節點之後的依賴由重跑時照常展開，不另外列出
+pub fn retry_plan(state: &State, run_id: u64) -> Result<Vec<Retry>> {
+    let Some(root) = state.history.find_run(run_id) else {
+        bail!(SchedulerError::NotFound {
+            msg: format!("run {run_id} not found in history"),
+        });
+    };
+    if RunStatus::of(&root.result) != RunStatus::Succeeded {
+        let Some(spec) = state
+            .tasks
+            .get(&root.task_id)
+            .map(|e| e.value().spec.clone())
+        else {
+            bail!(SchedulerError::NotFound {
+                msg: format!("task {} not found", root.task_id),
+            });
+        };
+        let upstream = root
+            .result
+            .upstream_run
+            .and_then(|up| state.history.find_run(up))
+            .map(|rec| rec.result);
+        return Ok(vec![Retry {
+            task_id: root.task_id,
+            spec,
+            upstream,
+        }]);
+    }
+
+    let mut plan = Vec::new();
+    let mut q = VecDeque::from([root]);
+    while let Some(done) = q.pop_front() {
+        for (dep_id, dep_spec) in dependents_of(state, done.task_id) {
+            match state.history.linked(dep_id, done.result.run_id) {
+                Some(rec) if RunStatus::of(&rec.result) == RunStatus::Succeeded => q.push_back(rec),
+                _ => plan.push(Retry {
+                    task_id: dep_id,
+                    spec: dep_spec,
+                    upstream: Some(done.result.clone()),
+                }),
+            }
+        }
+    }
+    if plan.is_empty() {
+        bail!(SchedulerError::InvalidRequest {
+            msg: format!("nothing to retry: every task in the chain of run {run_id} succeeded"),
+        });
+    }
+    Ok(plan)
+}
+
+/// 依賴表（watchers）與各任務規格中的 After 對照，列出對不起來的地方
+pub fn graph(state: &State) -> DependencyGraph {
+    let dependents: BTreeMap<u64, Vec<u64>> = state
+        .watchers
+        .iter()
+        .map(|kv| (*kv.key(), kv.value().clone()))
+        .collect();
+    let upstream: BTreeMap<u64, u64> = state
+        .tasks
+        .iter()
+        .filter_map(|kv| match kv.value().spec.schedule {
+            Schedule::After { task_id, .. } => Some((*kv.key(), task_id)),
+            _ => None,
+        })
+        .collect();
+
+    let mut issues = Vec::new();
+    let mut issue = |task_id, upstream, kind| {
+        issues.push(DependencyIssue {
+            task_id,
+            upstream,
+            kind,
+        })
+    };
+    for (&id, &up) in &upstream {
+        if !state.tasks.contains_key(&up) {
+            issue(id, up, DependencyIssueKind::MissingUpstream);
+        }
+        if !dependents.get(&up).is_some_and(|deps| deps.contains(&id)) {
+            issue(id, up, DependencyIssueKind::NotRegistered);
+        }
+    }
+    for (&up, deps) in &dependents {
+        for (i, &id) in deps.iter().enumerate() {
+            if deps[..i].contains(&id) {
+                issue(id, up, DependencyIssueKind::Duplicate);
+            } else if !state.tasks.contains_key(&id) {
+                issue(id, up, DependencyIssueKind::MissingDependent);
+            } else if upstream.get(&id) != Some(&up) {
+                issue(id, up, DependencyIssueKind::StaleEntry);
+            }
+        }
+    }
+    DependencyGraph {
+        dependents: dependents
+            .into_iter()
+            .map(|(upstream, dependents)| WatchEntry {
+                upstream,
+                dependents,
+            })
+            .collect(),
+        upstream: upstream
+            .into_iter()
+            .map(|(task_id, upstream)| DependencyEdge { task_id, upstream })
+            .collect(),
+        issues,
+    }
+}
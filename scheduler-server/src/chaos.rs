@@ -0,0 +1,124 @@
+use crate::{
+    exec::{self, ExecOutput, RunHandle},
+    State,
+};
+use anyhow::{bail, Result};
+use scheduler_core::{ChaosEntry, ChaosRule, RunOutcome, SchedulerError, TaskSpec};
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+/// SetChaos：檢查後設定或清除任務的規則
+pub fn set(state: &State, task_id: u64, rule: Option<ChaosRule>) -> Result<()> {
+    if !state.config.chaos.enabled {
+        bail!(SchedulerError::Unauthorized {
+            msg: "chaos mode is disabled; set [chaos] enabled = true".to_string(),
+        });
+    }
+    let Some(rule) = rule else {
+        if state.chaos.remove(&task_id).is_some() {
+            println!("🐒 task {task_id} chaos rule cleared");
+        }
+        return Ok(());
+    };
+    if !state.tasks.contains_key(&task_id) {
+        bail!(SchedulerError::NotFound {
+            msg: format!("task {task_id} not found"),
+        });
+    }
+    if !(0.0..=1.0).contains(&rule.probability) {
+        bail!(SchedulerError::InvalidRequest {
+            msg: format!(
+                "chaos probability {} is not between 0 and 1",
+                rule.probability
+            ),
+        });
+    }
+    if rule.count == Some(0) {
+        bail!(SchedulerError::InvalidRequest {
+            msg: "chaos count must be at least 1".to_string(),
+        });
+    }
+    if rule.delay_ms == 0 && rule.exit_code.is_none() {
+        bail!(SchedulerError::InvalidRequest {
+            msg: "chaos rule needs delay_ms or exit_code".to_string(),
+        });
+    }
+    println!("🐒 task {task_id} chaos rule set: {rule:?}");
+    state.chaos.insert(
+        task_id,
+        ChaosEntry {
+            task_id,
+            rule,
+            injected: 0,
+        },
+    );
+    Ok(())
+}
+
+/// 依任務排序
+pub fn list(state: &State) -> Vec<ChaosEntry> {
+    let mut list: Vec<ChaosEntry> = state.chaos.iter().map(|kv| kv.value().clone()).collect();
+    list.sort_by_key(|e| e.task_id);
+    list
+}
+
+/// 這次執行是否注入；注入時扣掉次數（用完即清除）並回傳規則
+pub fn roll(state: &State, task_id: u64) -> Option<ChaosRule> {
+    if !state.config.chaos.enabled {
+        return None;
+    }
+    let mut entry = state.chaos.get_mut(&task_id)?;
+    if random() >= entry.rule.probability {
+        return None;
+    }
+    entry.injected += 1;
+    let rule = entry.rule.clone();
+    if let Some(left) = entry.rule.count.as_mut() {
+        *left -= 1;
+        if *left == 0 {
+            drop(entry);
+            state.chaos.remove(&task_id);
+        }
+    }
+    Some(rule)
+}
+
+/// 套用注入的規則：先延遲（逾時與終止照常生效），要失敗時回傳取代實際執行的結果；
+/// 回傳 None 時照常執行命令
+pub async fn inject(
+    rule: &ChaosRule,
+    task_id: u64,
+    spec: &TaskSpec,
+    run: &RunHandle,
+) -> Option<ExecOutput> {
+    let output = |status_code, outcome, stderr: String| ExecOutput {
+        status_code,
+        outcome,
+        stdout: Vec::new(),
+        stderr: stderr.into_bytes(),
+        usage: None,
+    };
+    if rule.delay_ms > 0 {
+        println!("🐒 task {task_id} chaos: delaying {}ms", rule.delay_ms);
+        let delay = Duration::from_millis(rule.delay_ms);
+        if let Some(outcome) = exec::idle(spec, run, delay).await {
+            return Some(output(-1, outcome, String::new()));
+        }
+    }
+    let code = rule.exit_code?;
+    println!("🐒 task {task_id} chaos: injected failure (exit {code})");
+    let why = format!("chaos: injected failure (exit {code})\n");
+    Some(output(code, RunOutcome::Exited, why))
+}
+
+/// [0, 1) 的亂數；不值得為此引入亂數套件，用 RandomState 的隨機鍵雜湊計數器
+fn random() -> f64 {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let mut h = RandomState::new().build_hasher();
+    h.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+    (h.finish() >> 11) as f64 / (1u64 << 53) as f64
+}
@@ -0,0 +1,210 @@
+use crate::{config::CleanupConfig, template};
+use anyhow::{bail, Context, Result};
+use regex::Regex;
+use scheduler_core::{CleanupAction, CleanupReport, SchedulerError, TaskSpec};
+use std::{
+    path::{Component, Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+/// 新增任務時檢查：只接受絕對路徑，不可含 `..`，且要在允許的目錄（roots）底下；
+/// 要刪的目錄不可是允許的目錄本身，避免一個打錯的路徑清掉整台機器
+pub fn check(cfg: &CleanupConfig, spec: &TaskSpec) -> Result<()> {
+    let roots = roots(cfg, spec);
+    for action in &spec.cleanup {
+        match action {
+            CleanupAction::RemoveDir { path } => {
+                check_path(path)?;
+                check_within(path, &roots, true)?;
+            }
+            CleanupAction::RemoveFiles { glob, .. } => {
+                let (dir, name) = split_glob(glob)?;
+                check_path(dir)?;
+                check_within(dir, &roots, false)?;
+                if name.is_empty() {
+                    bail!("cleanup glob {glob:?} has no file name pattern");
+                }
+            }
+            _ => bail!("unrecognized cleanup action"),
+        }
+    }
+    Ok(())
+}
+
+fn check_path(path: &Path) -> Result<()> {
+    if !path.is_absolute() {
+        bail!("cleanup path must be absolute: {}", path.display());
+    }
+    if path.parent().is_none() || path.components().any(|c| c == Component::ParentDir) {
+        bail!("cleanup path is not allowed: {}", path.display());
+    }
+    Ok(())
+}
+
+/// 可清理的目錄：設定的 [cleanup] roots 加上任務沙箱的 writable_paths（含模板的要代入後才算）
+pub fn roots(cfg: &CleanupConfig, spec: &TaskSpec) -> Vec<PathBuf> {
+    let writable = spec.sandbox.iter().flat_map(|sb| &sb.writable_paths);
+    cfg.roots
+        .iter()
+        .chain(writable.filter(|p| !p.to_str().is_some_and(template::is_templated)))
+        .filter_map(|p| std::path::absolute(p).ok())
+        .collect()
+}
+
+/// strict 時不可是允許的目錄本身（RemoveDir），否則可以（RemoveFiles 清目錄內的檔案）
+fn check_within(path: &Path, roots: &[PathBuf], strict: bool) -> Result<()> {
+    if roots
+        .iter()
+        .any(|root| path.starts_with(root) && !(strict && path == root))
+    {
+        return Ok(());
+    }
+    bail!(SchedulerError::Unauthorized {
+        msg: format!(
+            "cleanup path {} is outside the allowed cleanup roots",
+            path.display()
+        ),
+    })
+}
+
+/// 執行時以實際路徑（解開符號連結）再比一次，目錄中途被換成連結也清不到外面
+fn real_within(path: &Path, roots: &[PathBuf], strict: bool) -> Result<Option<PathBuf>> {
+    let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+        bail!("cleanup path is not allowed: {}", path.display());
+    };
+    let real = match std::fs::canonicalize(parent) {
+        Ok(parent) => parent.join(name),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).with_context(|| format!("resolve {}", path.display())),
+    };
+    let roots: Vec<_> = roots
+        .iter()
+        .filter_map(|r| std::fs::canonicalize(r).ok())
+        .collect();
+    check_within(&real, &roots, strict)?;
+    Ok(Some(real))
+}
+
+/// 拆成目錄與檔名樣式；萬用字元只能出現在檔名
+fn split_glob(glob: &str) -> Result<(&Path, &str)> {
+    let (dir, name) = glob.rsplit_once('/').unwrap_or(("", glob));
+    if dir.contains(['*', '?']) {
+        bail!("cleanup glob {glob:?} may only use wildcards in the file name");
+    }
+    Ok((Path::new(if dir.is_empty() { "/" } else { dir }), name))
+}
+
+/// 檔名樣式轉成正規表示式：`*` 任意字元、`?` 單一字元，其餘照字面比對
+fn name_pattern(name: &str) -> Result<Regex> {
+    let mut re = String::from("^");
+    for c in name.chars() {
+        match c {
+            '*' => re.push_str(".*"),
+            '?' => re.push('.'),
+            c => re.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    re.push('$');
+    Regex::new(&re).with_context(|| format!("invalid cleanup pattern {name:?}"))
+}
+
+/// 依序執行清理動作；個別失敗記下後繼續做下一個。會遞迴走訪目錄，請在 blocking 執行緒呼叫
+pub fn run(actions: &[CleanupAction], roots: &[PathBuf]) -> CleanupReport {
+    let mut report = CleanupReport::default();
+    let now = SystemTime::now();
+    for action in actions {
+        let done = match action {
+            CleanupAction::RemoveDir { path } => remove_dir(path, roots, &mut report),
+            CleanupAction::RemoveFiles {
+                glob,
+                older_than_days,
+            } => remove_files(glob, *older_than_days, now, roots, &mut report),
+            _ => Ok(()),
+        };
+        if let Err(e) = done {
+            report.errors.push(format!("{e:#}"));
+        }
+    }
+    report
+}
+
+fn remove_dir(path: &Path, roots: &[PathBuf], report: &mut CleanupReport) -> Result<()> {
+    let Some(path) = real_within(path, roots, true)? else {
+        return Ok(());
+    };
+    let path = path.as_path();
+    let (count, bytes) = match tree_size(path) {
+        Ok(size) => size,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e).with_context(|| format!("read {}", path.display())),
+    };
+    std::fs::remove_dir_all(path).with_context(|| format!("remove {}", path.display()))?;
+    report.removed += count;
+    report.freed_bytes += bytes;
+    Ok(())
+}
+
+/// 目錄底下（含自己）的項目數與檔案大小；不跟隨符號連結
+fn tree_size(path: &Path) -> std::io::Result<(u64, u64)> {
+    let meta = std::fs::symlink_metadata(path)?;
+    if !meta.is_dir() {
+        return Ok((1, meta.len()));
+    }
+    let (mut count, mut bytes) = (1, 0);
+    for entry in std::fs::read_dir(path)? {
+        let (c, b) = tree_size(&entry?.path())?;
+        count += c;
+        bytes += b;
+    }
+    Ok((count, bytes))
+}
+
+fn remove_files(
+    glob: &str,
+    older_than_days: Option<u64>,
+    now: SystemTime,
+    roots: &[PathBuf],
+    report: &mut CleanupReport,
+) -> Result<()> {
+    let (dir, name) = split_glob(glob)?;
+    let pattern = name_pattern(name)?;
+    // 目錄本身解開連結後也要在允許的目錄內
+    let Some(dir) = real_within(dir, roots, false)? else {
+        return Ok(());
+    };
+    let dir = dir.as_path();
+    let cutoff = older_than_days.map(|days| {
+        now.checked_sub(Duration::from_secs(days.saturating_mul(86400)))
+            .unwrap_or(SystemTime::UNIX_EPOCH)
+    });
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e).with_context(|| format!("list {}", dir.display())),
+    };
+    for entry in entries {
+        let entry = entry?;
+        if !entry
+            .file_name()
+            .to_str()
+            .is_some_and(|n| pattern.is_match(n))
+        {
+            continue;
+        }
+        // 只刪一般檔案與符號連結本身，目錄請用 RemoveDir
+        let meta = entry.metadata()?;
+        if meta.is_dir() {
+            continue;
+        }
+        if let Some(cutoff) = cutoff {
+            if meta.modified().is_ok_and(|m| m > cutoff) {
+                continue;
+            }
+        }
+        let path = entry.path();
+        std::fs::remove_file(&path).with_context(|| format!("remove {}", path.display()))?;
+        report.removed += 1;
+        report.freed_bytes += meta.len();
+    }
+    Ok(())
+}
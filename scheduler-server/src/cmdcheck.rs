@@ -0,0 +1,85 @@
+use crate::{
+    config::{CmdCheck, ServerConfig},
+    defaults, template,
+};
+use scheduler_core::TaskSpec;
+use std::path::{Path, PathBuf};
+
+/// 伺服器沒有 PATH 時子程序拿到的預設值（與 clean_env 相同）
+const FALLBACK_PATH: &str = "/usr/local/bin:/usr/bin:/bin";
+
+/// AddTask 時確認命令找得到、可以執行；有問題時回傳說明。
+/// 依任務實際會拿到的 PATH（任務或 [defaults] 的 env 優先，否則伺服器的）尋找；
+/// 含模板的命令要代入後才知道，不檢查
+pub fn check(cfg: &ServerConfig, spec: &TaskSpec) -> Option<String> {
+    if cfg.cmd_check == CmdCheck::Off || spec.skip_cmd_check || template::is_templated(&spec.cmd) {
+        return None;
+    }
+    let env = defaults::apply(&cfg.defaults, spec).env;
+    let search = match env.get("PATH") {
+        Some(p) if !template::is_templated(p) => p.into(),
+        _ => std::env::var_os("PATH").unwrap_or_else(|| FALLBACK_PATH.into()),
+    };
+
+    let cmd = Path::new(&spec.cmd);
+    if cmd.components().count() > 1 {
+        return problem(cmd).map(|why| format!("command {:?} {why}", spec.cmd));
+    }
+    let mut found = None;
+    for dir in std::env::split_paths(&search) {
+        for candidate in candidates(&dir.join(cmd)) {
+            match problem(&candidate) {
+                None => return None,
+                Some(why) if candidate.exists() => found = found.or(Some((candidate, why))),
+                Some(_) => {}
+            }
+        }
+    }
+    Some(match found {
+        Some((path, why)) => format!(
+            "command {:?} resolves to {} which {why}",
+            spec.cmd,
+            path.display()
+        ),
+        None => format!("command {:?} not found in PATH", spec.cmd),
+    })
+}
+
+/// 路徑不能當命令執行的原因
+fn problem(path: &Path) -> Option<&'static str> {
+    match std::fs::metadata(path) {
+        Err(_) => Some("does not exist"),
+        Ok(m) if !m.is_file() => Some("is not a file"),
+        Ok(m) if !is_executable(&m) => Some("is not executable"),
+        Ok(_) => None,
+    }
+}
+
+#[cfg(unix)]
+fn is_executable(m: &std::fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    m.permissions().mode() & 0o111 != 0
+}
+
+#[cfg(not(unix))]
+fn is_executable(_m: &std::fs::Metadata) -> bool {
+    true
+}
+
+/// Windows 的命令可以省略副檔名
+#[cfg(windows)]
+fn candidates(path: &Path) -> Vec<PathBuf> {
+    let mut out = vec![path.to_path_buf()];
+    let exts = std::env::var("PATHEXT").unwrap_or_else(|_| ".EXE;.CMD;.BAT".to_string());
+    out.extend(exts.split(';').filter(|e| !e.is_empty()).map(|e| {
+        let mut p = path.as_os_str().to_os_string();
+        p.push(e);
+        PathBuf::from(p)
+    }));
+    out
+}
+
+#[cfg(not(windows))]
+fn candidates(path: &Path) -> Vec<PathBuf> {
+    vec![path.to_path_buf()]
+}
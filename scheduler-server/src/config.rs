@@ -0,0 +1,1088 @@
+use anyhow::{Context, Result};
+use scheduler_core::{CircuitBreaker, ManifestTask, Owner, Priority, StopSignal};
+use serde::{Deserialize, Deserializer};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+};
+
+/// 伺服器設定（TOML）；所有欄位皆有預設值，未提供設定檔時行為與舊版一致
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// 監聽位址；可為單一字串或陣列，例如 ["[::1]:7878", "unix:/run/scheduler.sock"]
+    #[serde(deserialize_with = "one_or_many")]
+    pub bind: Vec<String>,
+    /// 持久化檔案
+    pub data_path: PathBuf,
+    /// 任務 id 與 run 編號的高水位；刪掉會從既有資料推算，已清除的 id 可能被重用
+    pub ids_path: PathBuf,
+    /// 載入時無法解析的持久化紀錄移到這裡，修復前不會遺失
+    pub quarantine_path: PathBuf,
+    /// 預約的維護動作（暫停、移除）
+    pub maintenance_path: PathBuf,
+    /// 全域凍結的狀態（Freeze）
+    pub freeze_path: PathBuf,
+    /// 外部觸發網址（CreateTriggerHook）與其 token 雜湊
+    pub trigger_hooks_path: PathBuf,
+    /// 配額的今日輸出量，重啟後不歸零
+    pub quota_usage_path: PathBuf,
+    /// 受管理的執行輸出（artifacts）
+    pub artifacts: ArtifactsConfig,
+    /// 磁碟剩餘空間保護
+    pub disk_guard: DiskGuardConfig,
+    /// 全域執行上限與遺失子程序偵測
+    pub watchdog: WatchdogConfig,
+    /// 重啟時對帳（上次停機時仍在執行的 run）
+    pub recovery: RecoveryConfig,
+    /// 任務沙箱
+    pub sandbox: SandboxConfig,
+    /// 可執行命令的白名單/黑名單
+    pub policy: CommandPolicy,
+    /// 任務執行後清理（cleanup）可動的目錄
+    pub cleanup: CleanupConfig,
+    /// 各命名空間的配額；鍵 "*" 為未個別設定的命名空間合用的一份配額
+    pub quotas: HashMap<String, Quota>,
+    /// 伺服器自我監控的內建任務
+    pub builtin: BuiltinConfig,
+    /// 執行歷史與保留政策
+    pub history: HistoryConfig,
+    /// 任務規格的版本紀錄
+    pub revisions: RevisionsConfig,
+    /// 移除的任務先進回收桶
+    pub trash: TrashConfig,
+    /// 多個任務共用的具名排程
+    pub schedules: SchedulesConfig,
+    /// 任務未指定時沿用的設定
+    pub defaults: TaskDefaults,
+    /// 執行中的命令回報進度用的 named pipe
+    pub progress: ProgressConfig,
+    /// 事件訂閱
+    pub events: EventsConfig,
+    /// webhook 通知
+    pub notify: NotifyConfig,
+    /// 伺服器層級的模板變數
+    pub context: ContextConfig,
+    /// 等待開始的 run 佇列上限
+    pub queue: QueueConfig,
+    /// 同一分鐘觸發的 Daily 任務錯開啟動
+    pub stagger: StaggerConfig,
+    /// 負載過高時略過低優先的定時（Daily/Hourly）任務
+    pub load_shed: LoadShedConfig,
+    /// 單一請求 frame 的大小上限（位元組）；超過時回覆錯誤並關閉連線
+    pub max_frame_bytes: usize,
+    /// 新增任務時規格中有不認得的欄位（例如拼錯的 "apend"）如何處理
+    pub spec_mode: SpecMode,
+    /// 新增任務時在 PATH 中找不到命令或不可執行時如何處理（任務可設 skip_cmd_check 略過）
+    pub cmd_check: CmdCheck,
+    /// 模擬執行器：不實際執行命令（開發規格、效能測試用）
+    pub mock: MockConfig,
+    /// 故障注入（SetChaos）：演練失敗處理用，正式環境不要開啟
+    pub chaos: ChaosConfig,
+    /// MQTT 觸發與結果發布；未設定則不連線
+    pub mqtt: Option<MqttConfig>,
+    /// Redis pub/sub 或 NATS 事件橋接；未設定則不連線
+    pub bridge: Option<BridgeConfig>,
+    /// 每次執行後推送/寫出指標
+    pub metrics_export: MetricsExportConfig,
+    /// 從 git repo 中的任務清單同步任務；未設定則不啟用
+    pub git_sync: Option<GitSyncConfig>,
+    /// 設定檔中宣告的任務（`[[tasks]]`，以 key 識別，其餘欄位同 TaskSpec）；
+    /// 啟動時與任務表對齊，只能暫停，不能經由 API 移除或修改
+    pub tasks: Vec<ManifestTask>,
+    /// 每次執行的輸出另外上傳到 S3 相容儲存；未設定則不上傳
+    pub s3: Option<S3Config>,
+    /// 給瀏覽器用的 WebSocket 端點；未設定則不開
+    pub websocket: Option<WebSocketConfig>,
+    /// 外部系統（CI 等）以 HTTP POST 觸發任務的端點；未設定則不開
+    pub trigger_hooks: Option<TriggerHooksConfig>,
+    /// 在區網以 mDNS 宣告此伺服器（供 scheduler-cli --discover 尋找）；未設定則不宣告
+    pub mdns: Option<MdnsConfig>,
+    /// 每條連線與每個請求的存取紀錄；未設定則不記錄
+    pub access_log: Option<AccessLogConfig>,
+    /// 定期把失敗、逾時與略過彙總成一則通知；未設定則不彙總
+    pub digest: Option<DigestConfig>,
+    /// 以唯讀副本執行（分擔查詢與訂閱）；未設定則為一般的伺服器
+    pub replica: Option<ReplicaConfig>,
+    /// 同一個伺服器上彼此隔離的其他環境（例如 dev、staging），各有任務表、持久化檔與權杖；
+    /// 客戶端以 InEnvironment 指定，未指定的請求由頂層設定的預設環境處理
+    pub environments: BTreeMap<String, EnvironmentConfig>,
+    /// 預設環境的權杖；設定後，預設環境的請求都要包成 InEnvironment（環境名稱 "default"）帶上，
+    /// 未設定則不檢查
+    pub token: Option<String>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            bind: vec!["127.0.0.1:7878".to_string()],
+            data_path: PathBuf::from("tasks.json"),
+            ids_path: PathBuf::from("ids.json"),
+            quarantine_path: PathBuf::from("quarantine.json"),
+            maintenance_path: PathBuf::from("maintenance.json"),
+            freeze_path: PathBuf::from("freeze.json"),
+            trigger_hooks_path: PathBuf::from("trigger_hooks.json"),
+            quota_usage_path: PathBuf::from("quota_usage.json"),
+            artifacts: ArtifactsConfig::default(),
+            disk_guard: DiskGuardConfig::default(),
+            watchdog: WatchdogConfig::default(),
+            recovery: RecoveryConfig::default(),
+            sandbox: SandboxConfig::default(),
+            policy: CommandPolicy::default(),
+            cleanup: CleanupConfig::default(),
+            quotas: HashMap::new(),
+            builtin: BuiltinConfig::default(),
+            history: HistoryConfig::default(),
+            revisions: RevisionsConfig::default(),
+            trash: TrashConfig::default(),
+            schedules: SchedulesConfig::default(),
+            defaults: TaskDefaults::default(),
+            progress: ProgressConfig::default(),
+            events: EventsConfig::default(),
+            notify: NotifyConfig::default(),
+            context: ContextConfig::default(),
+            queue: QueueConfig::default(),
+            stagger: StaggerConfig::default(),
+            load_shed: LoadShedConfig::default(),
+            max_frame_bytes: 1024 * 1024,
+            spec_mode: SpecMode::default(),
+            cmd_check: CmdCheck::default(),
+            mock: MockConfig::default(),
+            chaos: ChaosConfig::default(),
+            mqtt: None,
+            bridge: None,
+            metrics_export: MetricsExportConfig::default(),
+            git_sync: None,
+            tasks: Vec::new(),
+            s3: None,
+            websocket: None,
+            trigger_hooks: None,
+            mdns: None,
+            access_log: None,
+            digest: None,
+            replica: None,
+            environments: BTreeMap::new(),
+            token: None,
+        }
+    }
+}
+
+/// 接受 `"a"` 或 `["a", "b"]`，讓舊的單一位址設定照常可用
+fn one_or_many<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+    Ok(match OneOrMany::deserialize(d)? {
+        OneOrMany::One(s) => vec![s],
+        OneOrMany::Many(v) => v,
+    })
+}
+
+/// 每次執行完成後，另存一份輸出到 artifacts 目錄（供 GetOutput 取回）
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ArtifactsConfig {
+    /// 未設定則不保存 artifacts
+    pub dir: Option<PathBuf>,
+    /// 保存時的壓縮方式
+    pub compression: Compression,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    #[default]
+    None,
+    Gzip,
+    Zstd,
+}
+
+/// 輸出所在檔案系統剩餘空間低於門檻時的處理
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DiskGuardConfig {
+    /// 最低剩餘空間（MiB）；未設定則不檢查
+    pub min_free_mb: Option<u64>,
+    pub action: DiskGuardAction,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiskGuardAction {
+    /// 不啟動這次執行
+    #[default]
+    SkipRun,
+    /// 照常執行，但不寫輸出檔與 artifact
+    SkipOutput,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WatchdogConfig {
+    /// 任何一次執行的伺服器上限（秒）；未設定則不限
+    pub max_runtime_secs: Option<u64>,
+    /// 偵測子程序已消失的 run
+    pub detect_lost: bool,
+    /// 巡檢間隔（秒）
+    pub interval_secs: u64,
+}
+
+impl Default for WatchdogConfig {
+    fn default() -> Self {
+        Self {
+            max_runtime_secs: None,
+            detect_lost: true,
+            interval_secs: 30,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RecoveryConfig {
+    pub orphan_policy: OrphanPolicy,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrphanPolicy {
+    /// 僅標記為 Orphaned
+    #[default]
+    Mark,
+    /// 標記後立即重跑（Once 任務本就會在載入時補跑，不重複觸發）
+    Rerun,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpecMode {
+    /// 拒絕新增，回覆不認得的欄位
+    #[default]
+    Strict,
+    /// 忽略不認得的欄位（接受較新客戶端送來、本伺服器還不支援的欄位）
+    Lenient,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CmdCheck {
+    /// 不檢查
+    Off,
+    /// 照常新增，在回覆中附上警告
+    #[default]
+    Warn,
+    /// 拒絕新增
+    Reject,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SandboxConfig {
+    /// bubblewrap 執行檔
+    pub bwrap_path: PathBuf,
+}
+
+impl Default for SandboxConfig {
+    fn default() -> Self {
+        Self {
+            bwrap_path: PathBuf::from("bwrap"),
+        }
+    }
+}
+
+/// 規則：`/usr/bin/`（路徑前綴）、`/usr/bin/rsync`（完整路徑）、`rm`（執行檔名稱）
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CommandPolicy {
+    /// 非空時，只允許符合的命令
+    pub allow: Vec<String>,
+    /// 一律拒絕（優先於 allow）
+    pub deny: Vec<String>,
+}
+
+/// 清理由伺服器本身執行、不受沙箱限制，所以只能清理管理者允許的目錄底下，
+/// 或任務自己沙箱的 writable_paths 底下；其餘路徑在新增任務時拒絕
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CleanupConfig {
+    /// 允許清理的目錄；相對路徑以伺服器的工作目錄為準
+    pub roots: Vec<PathBuf>,
+}
+
+/// 單一命名空間的配額；未設定的項目不限制
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Quota {
+    /// 任務數上限（AddTask 時檢查）
+    pub max_tasks: Option<usize>,
+    /// 同時執行的 run 上限
+    pub max_concurrent_runs: Option<usize>,
+    /// 每日（伺服器本地日期）輸出位元組上限
+    pub max_output_bytes_per_day: Option<u64>,
+}
+
+/// 內建任務：每日於指定時間（"HH:MM"）執行；未設定時間者不啟用
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BuiltinConfig {
+    /// 內建任務報告寫入的檔案
+    pub output_path: PathBuf,
+    /// 檢查資料目錄、artifacts 目錄剩餘空間
+    pub disk_usage_at: Option<String>,
+    /// 剩餘空間低於此百分比視為失敗
+    pub min_free_percent: u8,
+    /// 檢查持久化檔案與記憶體中的任務表是否一致
+    pub integrity_check_at: Option<String>,
+    /// 依 [history] 保留政策清理歷史
+    pub history_prune_at: Option<String>,
+}
+
+impl Default for BuiltinConfig {
+    fn default() -> Self {
+        Self {
+            output_path: PathBuf::from("scheduler-system.log"),
+            disk_usage_at: None,
+            min_free_percent: 10,
+            integrity_check_at: None,
+            history_prune_at: None,
+        }
+    }
+}
+
+/// 歷史保留政策；未設定的項目不限制
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HistoryConfig {
+    /// 歷史檔（JSON Lines，只附加）；封存的分段與索引放在同一個目錄
+    pub path: PathBuf,
+    /// 歷史檔超過這個大小就封存成一個分段並建立索引
+    pub segment_bytes: u64,
+    /// 每個任務最多保留幾筆
+    pub max_per_task: Option<usize>,
+    /// 超過幾天的紀錄刪除
+    pub max_age_days: Option<u64>,
+    /// 全部任務合計最多保留幾筆
+    pub max_total: Option<usize>,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::from("history.jsonl"),
+            segment_bytes: 8 * 1024 * 1024,
+            max_per_task: None,
+            max_age_days: None,
+            max_total: None,
+        }
+    }
+}
+
+/// 每個任務的規格版本（誰、何時、改了什麼），可回滾
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RevisionsConfig {
+    /// 版本檔（JSON Lines，只附加）
+    pub path: PathBuf,
+}
+
+impl Default for RevisionsConfig {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::from("revisions.jsonl"),
+        }
+    }
+}
+
+/// 移除的任務保留一段時間，期間可以還原
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TrashConfig {
+    /// 回收桶檔案（JSON）
+    pub path: PathBuf,
+    /// 保留天數；0 表示移除即永久刪除
+    pub retention_days: u64,
+}
+
+impl Default for TrashConfig {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::from("trash.json"),
+            retention_days: 7,
+        }
+    }
+}
+
+/// 具名排程（以 SetSchedule 維護）
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SchedulesConfig {
+    /// 具名排程檔（JSON）
+    pub path: PathBuf,
+}
+
+impl Default for SchedulesConfig {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::from("schedules.json"),
+        }
+    }
+}
+
+/// 任務層級設定的伺服器預設值：每次執行時才套用，任務有自己的值就以任務為準，
+/// 修改後重啟伺服器即對所有任務生效（列表中顯示的仍是任務自己的規格）
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TaskDefaults {
+    /// 相對的 output_path 放在這個目錄下；未設定則相對於伺服器的工作目錄
+    pub output_dir: Option<PathBuf>,
+    /// 輸出檔與自動建立的目錄的 umask（八進位，例如 "027"）；任務的 output_perms 優先。
+    /// 未設定則沿用伺服器程序的 umask
+    pub umask: Option<String>,
+    pub timeout_secs: Option<u64>,
+    /// 任務沒有設定 stop_signal / stop_grace_secs 時使用
+    pub stop_signal: Option<StopSignal>,
+    pub stop_grace_secs: Option<u64>,
+    /// 與任務的 env 合併，同名時以任務為準
+    pub env: BTreeMap<String, String>,
+    /// 所有任務都不繼承伺服器的環境變數（等同每個任務都設 clean_env）
+    pub clean_env: bool,
+    /// 任務沒有負責人時，通知中帶上的對象
+    pub owner: Option<Owner>,
+    /// 任務沒有設定斷路器時使用的失敗處理
+    pub breaker: Option<CircuitBreaker>,
+}
+
+/// 每次 run 建立一個 FIFO，路徑經 $SCHEDULER_PROGRESS 交給子程序（沙箱中的任務除外）
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProgressConfig {
+    pub enabled: bool,
+    /// FIFO 所在目錄
+    pub dir: PathBuf,
+    /// RunProgress 事件的最小間隔；階段改變或到 100% 時不受限
+    pub min_interval_ms: u64,
+}
+
+impl Default for ProgressConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            dir: PathBuf::from("progress"),
+            min_interval_ms: 1000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EventsConfig {
+    /// 訂閱連線的心跳間隔
+    pub heartbeat_secs: u64,
+    /// 保留最近幾筆事件，供重連的訂閱者補送
+    pub buffer: usize,
+    /// 是否把事件寫入檔案，讓重啟後 seq 延續、訂閱者仍能補送
+    pub persist: bool,
+    /// 事件檔（JSON Lines）；大小約在 buffer 的 1～2 倍筆數之間
+    pub path: PathBuf,
+    /// seq 的預留高水位；不寫事件檔時也使用，重啟後 seq 不會倒退或重用
+    pub seq_path: PathBuf,
+}
+
+impl Default for EventsConfig {
+    fn default() -> Self {
+        Self {
+            heartbeat_secs: 15,
+            buffer: 10_000,
+            persist: true,
+            path: PathBuf::from("events.jsonl"),
+            seq_path: PathBuf::from("events.seq.json"),
+        }
+    }
+}
+
+/// webhook 通知：先寫入 outbox 再送出，失敗依指數退避重試，跨重啟保留
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotifyConfig {
+    pub webhooks: Vec<Webhook>,
+    /// outbox 日誌（JSON Lines，含尚未送達與已放棄的通知；定期改寫成快照）
+    pub outbox_path: PathBuf,
+    /// 超過這個次數就放入 dead letter，等人工 requeue
+    pub max_attempts: u32,
+    /// 第一次重試的等待秒數，之後每次加倍
+    pub retry_base_secs: u64,
+    /// 重試等待的上限
+    pub retry_max_secs: u64,
+    /// 單次 HTTP 請求逾時
+    pub timeout_secs: u64,
+    /// `mailto:` 通知使用的 SMTP 伺服器
+    pub smtp: Option<SmtpConfig>,
+}
+
+impl Default for NotifyConfig {
+    fn default() -> Self {
+        Self {
+            webhooks: Vec::new(),
+            outbox_path: PathBuf::from("outbox.json"),
+            max_attempts: 8,
+            retry_base_secs: 5,
+            retry_max_secs: 3600,
+            timeout_secs: 10,
+            smtp: None,
+        }
+    }
+}
+
+/// 不加密、不驗證的 SMTP（通常是本機或內網的 relay）
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SmtpConfig {
+    pub host: String,
+    #[serde(default = "default_smtp_port")]
+    pub port: u16,
+    /// 寄件者，例如 "scheduler <scheduler@example.com>"
+    pub from: String,
+}
+
+fn default_smtp_port() -> u16 {
+    25
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Webhook {
+    /// http(s) 以 POST 送出 JSON；`mailto:<地址>` 經 [notify.smtp] 寄信
+    pub url: String,
+    /// 哪些情況要通知；預設通知失敗、斷路器跳脫與等待核准
+    #[serde(default = "default_notify_on")]
+    pub on: Vec<NotifyOn>,
+    /// 只即時通知優先順序不低於此的任務；較低的留給彙總（digest）
+    #[serde(default)]
+    pub min_priority: Option<Priority>,
+}
+
+fn default_notify_on() -> Vec<NotifyOn> {
+    vec![NotifyOn::Failure, NotifyOn::Breaker, NotifyOn::Approval]
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotifyOn {
+    Success,
+    /// 非零結束碼、逾時、遺失、孤兒
+    Failure,
+    /// 執行前檢查未通過而略過
+    Skipped,
+    /// 被 watchdog 終止
+    Killed,
+    /// 連續失敗使斷路器跳脫
+    Breaker,
+    /// run 等待人工核准
+    Approval,
+    /// 定期彙總（[digest]）
+    Digest,
+}
+
+/// 唯讀副本：與主伺服器共用持久化檔但不取得鎖、不排程也不執行；
+/// 任務清單、歷史與事件在本機回覆，其他請求轉送給主伺服器
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ReplicaConfig {
+    /// 主伺服器的 TCP 位址（host:port），用來轉送請求與接收事件
+    pub primary: String,
+    /// 沒有事件時也每隔多久重新讀取持久化檔
+    #[serde(default = "default_replica_sync_secs")]
+    pub sync_interval_secs: u64,
+}
+
+fn default_replica_sync_secs() -> u64 {
+    30
+}
+
+/// 失敗彙總的週期與送出時間（本機時區）
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DigestConfig {
+    pub every: DigestPeriod,
+    /// 送出時間 HH:MM
+    #[serde(default = "default_digest_at")]
+    pub at: String,
+    /// weekly 時在星期幾送出（mon、tue…）
+    #[serde(default = "default_digest_weekday")]
+    pub weekday: chrono::Weekday,
+    /// 期間內沒有任何問題時也送出
+    #[serde(default)]
+    pub send_empty: bool,
+    /// 累積中的彙總（跨重啟保留）
+    #[serde(default = "default_digest_path")]
+    pub path: PathBuf,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DigestPeriod {
+    Daily,
+    Weekly,
+}
+
+fn default_digest_at() -> String {
+    "08:00".to_string()
+}
+
+fn default_digest_weekday() -> chrono::Weekday {
+    chrono::Weekday::Mon
+}
+
+fn default_digest_path() -> PathBuf {
+    PathBuf::from("digest.json")
+}
+
+/// 已觸發但尚未開始（等允許時段、核准、鎖或依賴延遲）的 run 數量上限；未設定則不限制
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct QueueConfig {
+    pub max_pending_per_task: Option<usize>,
+    pub max_pending_total: Option<usize>,
+    /// 超過上限時的處理
+    pub policy: QueuePolicy,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum QueuePolicy {
+    /// 擠掉佇列中最舊的一筆（超過單一任務上限時只擠該任務的）
+    DropOldest,
+    /// 丟棄這次新的觸發
+    #[default]
+    DropNewest,
+    /// 拒絕這次觸發，記為錯誤
+    Reject,
+}
+
+/// 多個 Daily 任務排在同一分鐘時，依 id 順序把實際啟動時間平均分散到 window 內；
+/// window_secs 為 0 則不錯開
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StaggerConfig {
+    pub window_secs: u64,
+    /// 同一分鐘至少有幾個任務才錯開
+    pub min_tasks: usize,
+}
+
+impl Default for StaggerConfig {
+    fn default() -> Self {
+        Self {
+            window_secs: 0,
+            min_tasks: 2,
+        }
+    }
+}
+
+/// 執行中加上排隊中的 run 達到 max_active 時，
+/// 優先順序低於 min_priority 的 Daily/Hourly 任務這一輪直接略過（記為 Skipped），不再排隊
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoadShedConfig {
+    /// 未設定則不略過
+    pub max_active: Option<usize>,
+    /// 低於此優先順序者會被略過（預設 normal，即只略過 low）
+    pub min_priority: Priority,
+}
+
+/// MQTT broker 連線；訂閱任務 triggers 中的 topic，並可把每次執行結果發布出去
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MqttConfig {
+    pub host: String,
+    pub port: u16,
+    pub client_id: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub keep_alive_secs: u64,
+    /// 執行結束後發布 RunRecord（JSON）的 topic；`{task_id}` 會代入任務 id
+    pub result_topic: Option<String>,
+    /// 訂閱與發布的 QoS（0、1、2）
+    pub qos: u8,
+}
+
+impl Default for MqttConfig {
+    fn default() -> Self {
+        Self {
+            host: "localhost".to_string(),
+            port: 1883,
+            client_id: "scheduler-server".to_string(),
+            username: None,
+            password: None,
+            keep_alive_secs: 30,
+            result_topic: None,
+            qos: 1,
+        }
+    }
+}
+
+/// 沒有 scrape 路徑時的指標匯出：推到 Pushgateway，或寫成 node_exporter
+/// textfile collector 讀取的 .prom 檔；兩者都未設定則不匯出
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MetricsExportConfig {
+    /// 例如 http://pushgateway:9091；以 job 與 task_id 為 grouping key，
+    /// run 佇列的指標只以 job 為 grouping key
+    pub pushgateway_url: Option<String>,
+    pub job: String,
+    /// 每個任務一個 scheduler_task_<id>.prom，run 佇列另寫 scheduler_queue.prom
+    pub textfile_dir: Option<PathBuf>,
+    pub timeout_secs: u64,
+}
+
+impl Default for MetricsExportConfig {
+    fn default() -> Self {
+        Self {
+            pushgateway_url: None,
+            job: "scheduler".to_string(),
+            textfile_dir: None,
+            timeout_secs: 10,
+        }
+    }
+}
+
+/// WebSocket 端點：每則文字（或二進位）訊息是一個 JSON 請求，協定與 TCP 相同
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WebSocketConfig {
+    /// 監聽位址，例如 "127.0.0.1:7879"
+    pub bind: String,
+    /// 允許的瀏覽器 Origin（例如 "http://localhost:3000"）；"*" 允許任何來源。
+    /// 沒有帶 Origin 的非瀏覽器客戶端一律允許
+    #[serde(default)]
+    pub allowed_origins: Vec<String>,
+}
+
+/// 觸發網址的 HTTP 端點：`POST /hooks/<id>`，以 `Authorization: Bearer <token>` 驗證
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TriggerHooksConfig {
+    /// 監聽位址，例如 "0.0.0.0:7880"
+    pub bind: String,
+    /// 請求本文（參數）的大小上限（位元組）
+    #[serde(default = "default_hook_body_bytes")]
+    pub max_body_bytes: usize,
+}
+
+fn default_hook_body_bytes() -> usize {
+    64 * 1024
+}
+
+/// mDNS 宣告；TXT 記錄帶版本、環境與 WebSocket 埠號
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MdnsConfig {
+    /// 服務實例名稱（預設為主機名稱）
+    pub instance: Option<String>,
+}
+
+/// 存取紀錄：JSON Lines，每行一筆 connect / request / close，
+/// 含來源、身分、請求種類、耗時與結果，供流量分析與濫用調查
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AccessLogConfig {
+    pub path: PathBuf,
+    /// 成功的請求每 N 筆記一筆（1 為全記）；錯誤一律記錄
+    #[serde(default = "default_sample_every")]
+    pub sample_every: u64,
+    /// 是否記錄連線建立與關閉
+    #[serde(default = "default_true")]
+    pub connections: bool,
+    /// 伺服器自行輪替；未設定則一直附加到同一個檔案
+    #[serde(default)]
+    pub rotate: Option<RotateConfig>,
+}
+
+fn default_sample_every() -> u64 {
+    1
+}
+
+/// 日誌輪替：超過 max_bytes 或跨過 every 的時段就改名保存並開新檔，
+/// 不需要外部 logrotate 與重新開檔的訊號
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RotateConfig {
+    pub max_bytes: Option<u64>,
+    pub every: Option<RotateEvery>,
+    /// 保留幾份輪替下來的檔案
+    #[serde(default = "default_rotate_keep")]
+    pub keep: usize,
+    /// 輪替下來的檔案在背景壓縮
+    #[serde(default)]
+    pub compression: Compression,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RotateEvery {
+    Hourly,
+    Daily,
+}
+
+fn default_rotate_keep() -> usize {
+    7
+}
+
+/// S3 相容儲存（AWS、MinIO 等）；以 SigV4 簽署 PUT 上傳
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct S3Config {
+    pub bucket: String,
+    #[serde(default = "default_s3_region")]
+    pub region: String,
+    /// 未設定則使用 https://s3.<region>.amazonaws.com
+    pub endpoint: Option<String>,
+    /// 路徑式位址（<endpoint>/<bucket>/<key>）；關掉則用 <bucket>.<endpoint host>
+    #[serde(default = "default_true")]
+    pub path_style: bool,
+    /// 物件 key 模板；可用 {task_id}、{run_id}、{namespace}、{name}、{date}、{time}，
+    /// 副檔名依壓縮方式自動加上
+    #[serde(default = "default_s3_key")]
+    pub key: String,
+    /// 未設定則讀 AWS_ACCESS_KEY_ID / AWS_SECRET_ACCESS_KEY（及 AWS_SESSION_TOKEN）
+    pub access_key: Option<String>,
+    pub secret_key: Option<String>,
+    #[serde(default)]
+    pub compression: Compression,
+    /// 上傳失敗時最多嘗試幾次（指數退避）
+    #[serde(default = "default_s3_attempts")]
+    pub max_attempts: u32,
+    #[serde(default = "default_s3_timeout")]
+    pub timeout_secs: u64,
+}
+
+fn default_s3_region() -> String {
+    "us-east-1".to_string()
+}
+
+fn default_s3_key() -> String {
+    "scheduler/{namespace}/{task_id}/{date}/{run_id}".to_string()
+}
+
+fn default_s3_attempts() -> u32 {
+    5
+}
+
+fn default_s3_timeout() -> u64 {
+    30
+}
+
+/// 定期拉取 git repo，把其中的任務清單套用到伺服器（新增、更新、移除）
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GitSyncConfig {
+    /// git clone 用的位址
+    pub repo: String,
+    #[serde(default = "default_git_branch")]
+    pub branch: String,
+    /// 本機工作目錄
+    #[serde(default = "default_git_dir")]
+    pub dir: PathBuf,
+    /// 清單檔在 repo 中的路徑（TOML）
+    #[serde(default = "default_git_manifest")]
+    pub manifest: PathBuf,
+    #[serde(default = "default_git_interval")]
+    pub interval_secs: u64,
+    /// 安全模式：只印出差異，不實際套用
+    #[serde(default)]
+    pub dry_run: bool,
+    /// 一次同步最多移除幾個任務；超過則整次不套用（避免清單被誤刪時清空任務）
+    #[serde(default = "default_git_max_removals")]
+    pub max_removals: usize,
+}
+
+fn default_git_branch() -> String {
+    "main".to_string()
+}
+
+fn default_git_dir() -> PathBuf {
+    PathBuf::from("git-sync")
+}
+
+fn default_git_manifest() -> PathBuf {
+    PathBuf::from("tasks.toml")
+}
+
+fn default_git_interval() -> u64 {
+    300
+}
+
+fn default_git_max_removals() -> usize {
+    5
+}
+
+/// 把任務生命週期事件發布到 Redis/NATS，並訂閱任務 triggers 中的 subject
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BridgeConfig {
+    pub kind: BridgeKind,
+    /// 例如 redis://127.0.0.1:6379/ 或 nats://127.0.0.1:4222
+    pub url: String,
+    /// 事件發布到 `<prefix>.<事件名稱>`，例如 scheduler.events.run_finished
+    #[serde(default = "default_event_prefix")]
+    pub event_prefix: String,
+    /// 是否發布事件；關掉時只接受觸發
+    #[serde(default = "default_true")]
+    pub publish_events: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BridgeKind {
+    Redis,
+    Nats,
+}
+
+fn default_event_prefix() -> String {
+    "scheduler.events".to_string()
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// 模擬執行器：依命令列（cmd 與 args 以空白串接）比對規則，
+/// 等待指定時間後以指定結束碼與輸出完成；沒有規則符合時使用預設值
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MockConfig {
+    pub enabled: bool,
+    /// 預設的模擬執行時間
+    pub duration_ms: u64,
+    /// 預設的結束碼
+    pub exit_code: i32,
+    /// 依序比對，第一個符合的生效
+    pub rules: Vec<MockRule>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MockRule {
+    /// 正規表示式
+    pub pattern: String,
+    #[serde(default)]
+    pub duration_ms: Option<u64>,
+    #[serde(default)]
+    pub exit_code: Option<i32>,
+    /// 模擬的 stdout（可用來測試輸出變數）
+    #[serde(default)]
+    pub stdout: Option<String>,
+}
+
+/// 故障注入：開啟後才接受 SetChaos，規則由客戶端在執行期間設定
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChaosConfig {
+    pub enabled: bool,
+}
+
+/// 一個額外的環境（[environments.<名稱>]）；其餘設定沿用頂層
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EnvironmentConfig {
+    /// 資料目錄，持久化檔都放在其下；預設為 environments/<名稱>。
+    /// 相對路徑以預設環境 data_path 所在的目錄為準，與伺服器從哪裡啟動無關
+    pub dir: Option<PathBuf>,
+    /// 請求須帶上的權杖；未設定則不檢查
+    pub token: Option<String>,
+    /// 與頂層 [context] 的 vars 合併，同名時以此為準
+    pub vars: BTreeMap<String, String>,
+}
+
+/// 任務模板可用的 `{{server.*}}` 與 `{{vars.*}}`；可在執行期間以 UpdateContext 修改
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ContextConfig {
+    /// 未設定則使用系統主機名稱
+    pub hostname: Option<String>,
+    pub environment: String,
+    pub vars: BTreeMap<String, String>,
+    /// UpdateContext 的修改；重啟後套用在上面的值之上
+    pub path: PathBuf,
+}
+
+impl Default for ContextConfig {
+    fn default() -> Self {
+        Self {
+            hostname: None,
+            environment: "default".to_string(),
+            vars: BTreeMap::new(),
+            path: PathBuf::from("context.json"),
+        }
+    }
+}
+
+impl ServerConfig {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("read config {}", path.display()))?;
+        toml::from_str(&text).with_context(|| format!("parse config {}", path.display()))
+    }
+
+    /// 環境 name 的設定：持久化檔移到環境的資料目錄（絕對路徑只取檔名），
+    /// S3 上傳的 key 前面加上 environments/<名稱>/，各環境的物件不會互相覆蓋；
+    /// {{server.environment}} 為環境名稱；監聽、存取紀錄、副本、設定檔宣告的任務與對外的整合
+    /// （MQTT、橋接、git-sync、彙總、指標匯出）只屬於預設環境
+    pub fn for_environment(&self, name: &str, env: &EnvironmentConfig) -> ServerConfig {
+        let base = self.data_path.parent().unwrap_or(Path::new(""));
+        let dir = base.join(
+            env.dir
+                .clone()
+                .unwrap_or_else(|| Path::new("environments").join(name)),
+        );
+        let at = |p: &Path| {
+            if p.is_absolute() {
+                dir.join(p.file_name().unwrap_or_default())
+            } else {
+                dir.join(p)
+            }
+        };
+        let mut c = self.clone();
+        c.environments.clear();
+        c.token = env.token.clone();
+        c.data_path = at(&self.data_path);
+        c.ids_path = at(&self.ids_path);
+        c.quarantine_path = at(&self.quarantine_path);
+        c.maintenance_path = at(&self.maintenance_path);
+        c.freeze_path = at(&self.freeze_path);
+        c.trigger_hooks_path = at(&self.trigger_hooks_path);
+        c.quota_usage_path = at(&self.quota_usage_path);
+        c.artifacts.dir = self.artifacts.dir.as_deref().map(at);
+        c.builtin.output_path = at(&self.builtin.output_path);
+        c.history.path = at(&self.history.path);
+        c.revisions.path = at(&self.revisions.path);
+        c.trash.path = at(&self.trash.path);
+        c.schedules.path = at(&self.schedules.path);
+        c.progress.dir = at(&self.progress.dir);
+        c.events.path = at(&self.events.path);
+        c.events.seq_path = at(&self.events.seq_path);
+        c.notify.outbox_path = at(&self.notify.outbox_path);
+        if let Some(s3) = &mut c.s3 {
+            s3.key = format!("environments/{name}/{}", s3.key.trim_start_matches('/'));
+        }
+        c.context.environment = name.to_string();
+        c.context.path = at(&self.context.path);
+        c.context
+            .vars
+            .extend(env.vars.iter().map(|(k, v)| (k.clone(), v.clone())));
+        c.mqtt = None;
+        c.bridge = None;
+        c.git_sync = None;
+        c.tasks.clear();
+        c.digest = None;
+        c.metrics_export = MetricsExportConfig::default();
+        c.websocket = None;
+        c.trigger_hooks = None;
+        c.mdns = None;
+        c.access_log = None;
+        c.replica = None;
+        c
+    }
+}
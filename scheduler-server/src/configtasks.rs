@@ -0,0 +1,43 @@
+use crate::{add_task, gitsync, remove_task, update_task, State};
+use anyhow::{Context, Result};
+use std::sync::Arc;
+
+/// 設定檔宣告的任務在 TaskSpec.source 中的前綴
+pub const SOURCE_PREFIX: &str = "config:";
+
+/// 變更紀錄中的 actor
+const ACTOR: &str = "config";
+
+/// 由設定檔宣告（只能暫停，不能經由 API 移除或修改）
+pub fn is_config_task(state: &State, id: u64) -> bool {
+    state.tasks.get(&id).is_some_and(|ent| {
+        ent.spec
+            .source
+            .as_deref()
+            .is_some_and(|s| s.starts_with(SOURCE_PREFIX))
+    })
+}
+
+/// 啟動時把任務表對齊設定檔的 [[tasks]]：新增缺少的、原地更新有變的（保留編號、歷史與版本紀錄），
+/// 移除設定檔中已刪掉的。任何一個任務無效就不啟動，內建的任務不該靜靜地消失
+pub async fn reconcile(state: &Arc<State>) -> Result<()> {
+    let plan = gitsync::diff(state, SOURCE_PREFIX, state.config.tasks.clone())
+        .context("config [[tasks]]")?;
+    if plan.is_empty() {
+        return Ok(());
+    }
+    for (key, id) in plan.remove {
+        remove_task(state, id).await?;
+        println!("⚙️ config task {key} removed (task {id})");
+    }
+    for (key, id, spec) in plan.update {
+        if update_task(state, id, spec, ACTOR, None).await?.is_some() {
+            println!("⚙️ config task {key} updated (task {id})");
+        }
+    }
+    for (key, spec) in plan.add {
+        let id = add_task(state, spec, ACTOR).await?;
+        println!("⚙️ config task {key} added (task {id})");
+    }
+    Ok(())
+}
@@ -0,0 +1,75 @@
+use crate::{config::ContextConfig, template};
+use anyhow::{Context, Result};
+use scheduler_core::ServerContext;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// UpdateContext 改過的部分；存檔，重啟後套用在設定檔的值之上
+#[derive(Default, Serialize, Deserialize)]
+struct Overrides {
+    #[serde(default)]
+    environment: Option<String>,
+    /// None 表示移除設定檔中的變數
+    #[serde(default)]
+    vars: BTreeMap<String, Option<String>>,
+}
+
+/// 設定檔的模板變數
+fn base(cfg: &ContextConfig) -> ServerContext {
+    ServerContext {
+        hostname: cfg
+            .hostname
+            .clone()
+            .unwrap_or_else(template::system_hostname),
+        environment: cfg.environment.clone(),
+        vars: cfg.vars.clone(),
+    }
+}
+
+/// 設定檔的值加上存檔中的修改
+pub fn load(cfg: &ContextConfig) -> Result<ServerContext> {
+    let mut ctx = base(cfg);
+    let path = &cfg.path;
+    if !path.exists() {
+        return Ok(ctx);
+    }
+    let text = std::fs::read_to_string(path).with_context(|| format!("read {}", path.display()))?;
+    let saved: Overrides =
+        serde_json::from_str(&text).with_context(|| format!("parse {}", path.display()))?;
+    if let Some(env) = saved.environment {
+        ctx.environment = env;
+    }
+    for (key, value) in saved.vars {
+        match value {
+            Some(v) => ctx.vars.insert(key, v),
+            None => ctx.vars.remove(&key),
+        };
+    }
+    Ok(ctx)
+}
+
+/// 存下目前與設定檔不同的部分；先寫暫存檔再改名
+pub fn save(cfg: &ContextConfig, ctx: &ServerContext) -> Result<()> {
+    let base = base(cfg);
+    let mut saved = Overrides {
+        environment: (ctx.environment != base.environment).then(|| ctx.environment.clone()),
+        vars: BTreeMap::new(),
+    };
+    for (key, value) in &ctx.vars {
+        if base.vars.get(key) != Some(value) {
+            saved.vars.insert(key.clone(), Some(value.clone()));
+        }
+    }
+    for key in base.vars.keys().filter(|k| !ctx.vars.contains_key(*k)) {
+        saved.vars.insert(key.clone(), None);
+    }
+
+    let path = &cfg.path;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, serde_json::to_vec_pretty(&saved)?)
+        .with_context(|| format!("write {}", tmp.display()))?;
+    std::fs::rename(&tmp, path).with_context(|| format!("replace {}", path.display()))
+}
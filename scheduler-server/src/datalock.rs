@@ -0,0 +1,92 @@
+use anyhow::{bail, Context, Result};
+use std::{
+    fs::File,
+    io::{Read, Seek, Write},
+    path::{Path, PathBuf},
+};
+
+/// 持久化檔的排他鎖：同一份資料只允許一個伺服器使用，
+/// 否則兩邊交錯改寫持久化檔，每個任務也會被執行兩次。
+/// 鎖檔在資料檔旁（`tasks.json.lock`），內容為持有者的 PID；伺服器結束前一直持有
+pub struct DataLock {
+    file: File,
+}
+
+/// 資料檔對應的鎖檔
+pub fn lock_path(data: &Path) -> PathBuf {
+    let mut name = data.file_name().unwrap_or_default().to_os_string();
+    name.push(".lock");
+    data.with_file_name(name)
+}
+
+impl DataLock {
+    /// 取得鎖；已被另一個仍在執行的伺服器持有時失敗。
+    /// 上一個持有者沒有正常結束留下的鎖檔直接接手
+    pub fn acquire(data: &Path) -> Result<Self> {
+        let path = lock_path(data);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)
+            .with_context(|| format!("open {}", path.display()))?;
+        let mut holder = String::new();
+        let _ = file.read_to_string(&mut holder);
+        let holder = holder.trim().parse::<u32>().ok();
+
+        if !try_lock(&file, holder)? {
+            let by = holder.map_or_else(|| "unknown pid".to_string(), |pid| format!("pid {pid}"));
+            bail!(
+                "data file {} is in use by another scheduler-server ({by}); \
+                 stop it first or use a different data_path (lock: {})",
+                data.display(),
+                path.display()
+            );
+        }
+        if let Some(pid) = holder.filter(|pid| *pid != std::process::id()) {
+            println!(
+                "🔓 taking over stale lock on {} left by pid {pid}",
+                data.display()
+            );
+        }
+
+        file.set_len(0)?;
+        file.rewind()?;
+        writeln!(file, "{}", std::process::id())?;
+        file.sync_all()?;
+        Ok(Self { file })
+    }
+}
+
+/// 正常結束時清掉 PID（此時仍持有鎖），下次啟動才分得出上次是否異常結束
+impl Drop for DataLock {
+    fn drop(&mut self) {
+        let _ = self.file.set_len(0);
+    }
+}
+
+/// flock：持有者結束（含異常結束）時由系統自動釋放，所以取得成功就表示沒有人在用
+#[cfg(unix)]
+fn try_lock(file: &File, _holder: Option<u32>) -> Result<bool> {
+    use std::os::unix::io::AsRawFd;
+
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } == 0 {
+        return Ok(true);
+    }
+    let err = std::io::Error::last_os_error();
+    if err.raw_os_error() == Some(libc::EWOULDBLOCK) {
+        Ok(false)
+    } else {
+        Err(err).context("lock data file")
+    }
+}
+
+/// 其他平台沒有 flock：鎖檔中記錄的 PID 仍存在就視為使用中
+#[cfg(not(unix))]
+fn try_lock(_file: &File, holder: Option<u32>) -> Result<bool> {
+    Ok(!holder.is_some_and(|pid| pid != std::process::id() && crate::exec::process_alive(pid)))
+}
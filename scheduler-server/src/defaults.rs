@@ -0,0 +1,47 @@
+use crate::{config::TaskDefaults, perms};
+use anyhow::{bail, Context, Result};
+use scheduler_core::TaskSpec;
+use std::path::{Path, PathBuf};
+
+/// 啟動前檢查
+pub fn check(cfg: &TaskDefaults) -> Result<()> {
+    if cfg.breaker.as_ref().is_some_and(|b| b.failures == 0) {
+        bail!("defaults.breaker.failures must be at least 1");
+    }
+    if cfg.timeout_secs == Some(0) {
+        bail!("defaults.timeout_secs must be at least 1");
+    }
+    if let Some(umask) = &cfg.umask {
+        if perms::parse_mode(umask).context("defaults.umask")? > 0o777 {
+            bail!("defaults.umask must be within 0..=0777");
+        }
+    }
+    Ok(())
+}
+
+/// 本次執行實際使用的規格：任務沒有指定的欄位沿用 [defaults]
+pub fn apply(cfg: &TaskDefaults, spec: &TaskSpec) -> TaskSpec {
+    let mut out = spec.clone();
+    out.output_path = output_path(cfg, &spec.output_path);
+    out.timeout_secs = spec.timeout_secs.or(cfg.timeout_secs);
+    out.stop_signal = spec.stop_signal.or(cfg.stop_signal);
+    out.stop_grace_secs = spec.stop_grace_secs.or(cfg.stop_grace_secs);
+    out.env = cfg.env.clone();
+    out.env.extend(spec.env.clone());
+    out.clean_env |= cfg.clean_env;
+    if out.owner.is_none() {
+        out.owner = cfg.owner.clone();
+    }
+    if out.breaker.is_none() {
+        out.breaker = cfg.breaker.clone();
+    }
+    out
+}
+
+/// 相對的輸出路徑放到 output_dir 下
+pub fn output_path(cfg: &TaskDefaults, path: &Path) -> PathBuf {
+    match &cfg.output_dir {
+        Some(dir) if path.is_relative() => dir.join(path),
+        _ => path.to_path_buf(),
+    }
+}
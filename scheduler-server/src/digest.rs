@@ -0,0 +1,246 @@
+use crate::{
+    config::{DigestConfig, DigestPeriod},
+    local_now_fixed, State,
+};
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Datelike, FixedOffset, Local};
+use scheduler_core::{Digest, DigestEntry, Event, EventKind, RunOutcome, Schedule};
+use serde::{Deserialize, Serialize};
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::sync::broadcast;
+
+/// 累積中的彙總；和 outbox 一樣記下 cursor，重啟後從事件檔接續
+struct DigestBook {
+    path: PathBuf,
+    inner: Mutex<DigestFile>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct DigestFile {
+    /// 已計入的最後一個事件
+    cursor: u64,
+    /// 本期開始的時間；第一次啟用時為啟動當下
+    period_start: Option<DateTime<FixedOffset>>,
+    failures: Vec<DigestEntry>,
+    sla_breaches: Vec<DigestEntry>,
+    skipped: Vec<DigestEntry>,
+}
+
+impl DigestBook {
+    fn load(path: &Path) -> Result<Self> {
+        let mut inner: DigestFile = if path.exists() {
+            let bytes = std::fs::read(path).with_context(|| format!("read {}", path.display()))?;
+            serde_json::from_slice(&bytes).with_context(|| format!("parse {}", path.display()))?
+        } else {
+            DigestFile::default()
+        };
+        inner.period_start.get_or_insert_with(local_now_fixed);
+        Ok(Self {
+            path: path.to_path_buf(),
+            inner: Mutex::new(inner),
+        })
+    }
+
+    fn cursor(&self) -> u64 {
+        self.inner.lock().unwrap().cursor
+    }
+
+    /// 把事件計入本期（失敗、逾時或略過之外的事件只推進 cursor）
+    fn record(&self, state: &State, ev: &Event) -> Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        if ev.seq <= inner.cursor {
+            return Ok(());
+        }
+        inner.cursor = ev.seq;
+        let (task_id, detail, list) = match &ev.kind {
+            EventKind::RunFinished {
+                outcome: RunOutcome::Exited,
+                status_code: 0,
+                ..
+            }
+            | EventKind::RunFinished {
+                outcome: RunOutcome::Cancelled,
+                ..
+            } => return Ok(()),
+            EventKind::RunFinished {
+                task_id,
+                outcome: RunOutcome::TimedOut,
+                ..
+            } => (*task_id, "timed out".to_string(), &mut inner.sla_breaches),
+            EventKind::RunFinished {
+                task_id,
+                outcome: RunOutcome::Exited,
+                status_code,
+                ..
+            } => (*task_id, format!("exit {status_code}"), &mut inner.failures),
+            EventKind::RunFinished {
+                task_id, outcome, ..
+            } => (*task_id, format!("{outcome:?}"), &mut inner.failures),
+            EventKind::RunSkipped { task_id, reason } => {
+                (*task_id, reason.clone(), &mut inner.skipped)
+            }
+            _ => return Ok(()),
+        };
+        match list.iter_mut().find(|e| e.task_id == task_id) {
+            Some(entry) => {
+                entry.count += 1;
+                entry.last_at = ev.at;
+                entry.last_detail = detail;
+            }
+            None => list.push(DigestEntry {
+                task_id,
+                label: state
+                    .tasks
+                    .get(&task_id)
+                    .map(|t| t.spec.name.clone().unwrap_or_else(|| t.spec.cmd.clone())),
+                count: 1,
+                last_at: ev.at,
+                last_detail: detail,
+            }),
+        }
+        save(&self.path, &inner)
+    }
+
+    /// 結束本期：取出到 now 為止的彙總並開始下一期
+    fn close(&self, now: DateTime<FixedOffset>) -> Result<Digest> {
+        let mut inner = self.inner.lock().unwrap();
+        let digest = Digest {
+            period_start: inner.period_start.unwrap_or(now),
+            period_end: now,
+            failures: std::mem::take(&mut inner.failures),
+            sla_breaches: std::mem::take(&mut inner.sla_breaches),
+            skipped: std::mem::take(&mut inner.skipped),
+        };
+        inner.period_start = Some(now);
+        save(&self.path, &inner)?;
+        Ok(digest)
+    }
+}
+
+fn save(path: &Path, inner: &DigestFile) -> Result<()> {
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, serde_json::to_vec_pretty(inner)?)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// 啟動前檢查送出時間
+pub fn check(cfg: &DigestConfig) -> Result<()> {
+    daily_at(cfg)?;
+    Ok(())
+}
+
+fn daily_at(cfg: &DigestConfig) -> Result<Schedule> {
+    match format!("daily {}", cfg.at).parse::<Schedule>() {
+        Ok(s) => Ok(s),
+        Err(e) => bail!("digest.at: {e}"),
+    }
+}
+
+/// after 之後下一次送出的時間（本機時區）
+fn next_send(cfg: &DigestConfig, after: DateTime<FixedOffset>) -> Option<DateTime<FixedOffset>> {
+    let daily = daily_at(cfg).ok()?;
+    let mut t = after;
+    for _ in 0..8 {
+        t = daily.next_occurrence(t, &Local)?;
+        if cfg.every == DigestPeriod::Daily || t.with_timezone(&Local).weekday() == cfg.weekday {
+            return Some(t);
+        }
+    }
+    None
+}
+
+/// 啟動彙總：持續把事件計入本期，到了送出時間發出 DigestReady（由 [notify] 轉送）
+pub fn spawn(state: Arc<State>) -> Result<()> {
+    let Some(cfg) = state.config.digest.clone() else {
+        return Ok(());
+    };
+    let book = DigestBook::load(&cfg.path)?;
+
+    tokio::spawn(async move {
+        let mut cursor = book.cursor();
+        let mut due = next_send(&cfg, local_now_fixed());
+        loop {
+            let (_, backlog, mut rx) = state.events.subscribe(Some(cursor));
+            for ev in backlog {
+                cursor = ev.seq;
+                if let Err(e) = book.record(&state, &ev) {
+                    eprintln!("digest record error: {e:?}");
+                }
+            }
+            loop {
+                let wait = due
+                    .and_then(|at| (at - local_now_fixed()).to_std().ok())
+                    .unwrap_or(Duration::ZERO);
+                tokio::select! {
+                    ev = rx.recv() => match ev {
+                        Ok(ev) => {
+                            cursor = ev.seq;
+                            if let Err(e) = book.record(&state, &ev) {
+                                eprintln!("digest record error: {e:?}");
+                            }
+                        }
+                        // 跟不上就用 cursor 重新訂閱補回
+                        Err(broadcast::error::RecvError::Lagged(_)) => break,
+                        Err(broadcast::error::RecvError::Closed) => return,
+                    },
+                    _ = tokio::time::sleep(wait), if due.is_some() => {
+                        let now = local_now_fixed();
+                        due = next_send(&cfg, now);
+                        match book.close(now) {
+                            Ok(digest) if digest.is_empty() && !cfg.send_empty => {}
+                            Ok(digest) => {
+                                println!(
+                                    "📰 digest: {} failure(s), {} SLA breach(es), {} skipped",
+                                    digest.failures.len(),
+                                    digest.sla_breaches.len(),
+                                    digest.skipped.len()
+                                );
+                                state.events.emit(EventKind::DigestReady { digest });
+                            }
+                            Err(e) => eprintln!("digest save error: {e:?}"),
+                        }
+                    }
+                }
+            }
+        }
+    });
+    Ok(())
+}
+
+/// 寄信用的純文字內容
+pub fn render(digest: &Digest) -> String {
+    let mut text = format!(
+        "{} ~ {}\n",
+        digest.period_start.format("%Y-%m-%d %H:%M"),
+        digest.period_end.format("%Y-%m-%d %H:%M")
+    );
+    if digest.is_empty() {
+        text.push_str("\nNo failures, SLA breaches or skipped runs.\n");
+    }
+    for (title, entries) in [
+        ("Failures", &digest.failures),
+        ("SLA breaches", &digest.sla_breaches),
+        ("Skipped", &digest.skipped),
+    ] {
+        if entries.is_empty() {
+            continue;
+        }
+        text.push_str(&format!("\n{title}:\n"));
+        for e in entries {
+            text.push_str(&format!(
+                "  #{} {} x{} (last {} at {})\n",
+                e.task_id,
+                e.label.as_deref().unwrap_or("-"),
+                e.count,
+                e.last_detail,
+                e.last_at.format("%m-%d %H:%M")
+            ));
+        }
+    }
+    text
+}
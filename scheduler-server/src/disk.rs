@@ -0,0 +1,49 @@
+use crate::config::DiskGuardConfig;
+use anyhow::Result;
+use std::path::Path;
+
+/// 檢查 `path` 所在檔案系統是否低於門檻；回傳 Some(剩餘 bytes) 表示已觸發
+pub fn check_low_space(cfg: &DiskGuardConfig, path: &Path) -> Result<Option<u64>> {
+    let Some(min_mb) = cfg.min_free_mb else {
+        return Ok(None);
+    };
+    let free = free_bytes(path)?;
+    if free < min_mb.saturating_mul(1024 * 1024) {
+        Ok(Some(free))
+    } else {
+        Ok(None)
+    }
+}
+
+/// 取得路徑所在檔案系統的可用空間
+pub fn free_bytes(path: &Path) -> Result<u64> {
+    Ok(usage(path)?.0)
+}
+
+/// 取得路徑所在檔案系統的 (可用, 總容量)；路徑尚不存在時往上找最近的既有目錄
+#[cfg(unix)]
+pub fn usage(path: &Path) -> Result<(u64, u64)> {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt};
+
+    let mut probe = path;
+    while !probe.exists() {
+        probe = match probe.parent() {
+            Some(p) if !p.as_os_str().is_empty() => p,
+            _ => Path::new("."),
+        };
+    }
+
+    let c = CString::new(probe.as_os_str().as_bytes())?;
+    let mut st: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(c.as_ptr(), &mut st) } != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    let frsize = st.f_frsize as u64;
+    Ok((st.f_bavail as u64 * frsize, st.f_blocks as u64 * frsize))
+}
+
+#[cfg(not(unix))]
+pub fn usage(_path: &Path) -> Result<(u64, u64)> {
+    // 非 Unix 平台暫不支援，視為空間充足
+    Ok((u64::MAX, u64::MAX))
+}
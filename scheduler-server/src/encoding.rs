@@ -0,0 +1,60 @@
+use anyhow::{bail, Result};
+use scheduler_core::OutputEncoding;
+use std::{borrow::Cow, fmt::Write};
+
+/// 給人看的文字：lossy 以 U+FFFD 取代，其餘把無法解碼的位元組寫成 `\xNN`，不丟失內容
+pub fn display(encoding: OutputEncoding, bytes: &[u8]) -> Cow<'_, str> {
+    match encoding {
+        OutputEncoding::Lossy => String::from_utf8_lossy(bytes),
+        _ => escape(bytes),
+    }
+}
+
+/// success 條件與 outputs 用的文字；utf8 時無法解碼是錯誤
+pub fn text(encoding: OutputEncoding, bytes: &[u8]) -> Result<Cow<'_, str>> {
+    match encoding {
+        OutputEncoding::Utf8 => match std::str::from_utf8(bytes) {
+            Ok(text) => Ok(Cow::Borrowed(text)),
+            Err(e) => bail!("output is not valid UTF-8 (at byte {})", e.valid_up_to()),
+        },
+        _ => Ok(display(encoding, bytes)),
+    }
+}
+
+/// 判斷二進位只看開頭這麼多位元組
+const SNIFF_BYTES: usize = 8192;
+
+/// 開頭有 NUL，或控制字元（換行、tab、ESC 等常見於文字的除外）超過一成時視為二進位
+pub fn is_binary(bytes: &[u8]) -> bool {
+    let head = &bytes[..bytes.len().min(SNIFF_BYTES)];
+    if head.contains(&0) {
+        return true;
+    }
+    let control = head
+        .iter()
+        .filter(|&&b| b < 0x20 && !matches!(b, b'\t' | b'\n' | b'\r' | 0x0c | 0x1b | 0x08))
+        .count();
+    control * 10 > head.len()
+}
+
+/// 二進位輸出在即時輸出與 GetOutput 中的摘要
+pub fn binary_notice(len: usize, sha256: Option<&str>) -> String {
+    match sha256 {
+        Some(sha) => format!("[binary output: {len} bytes, sha256 {sha}]\n"),
+        None => format!("[binary output: {len} bytes, not shown]\n"),
+    }
+}
+
+fn escape(bytes: &[u8]) -> Cow<'_, str> {
+    if let Ok(text) = std::str::from_utf8(bytes) {
+        return Cow::Borrowed(text);
+    }
+    let mut out = String::with_capacity(bytes.len() + 16);
+    for chunk in bytes.utf8_chunks() {
+        out.push_str(chunk.valid());
+        for b in chunk.invalid() {
+            let _ = write!(out, "\\x{b:02x}");
+        }
+    }
+    Cow::Owned(out)
+}
@@ -0,0 +1,89 @@
+use crate::{config::ServerConfig, verify, State};
+use anyhow::{bail, Result};
+use scheduler_core::{ClientRequest, SchedulerError, DEFAULT_ENVIRONMENT};
+use std::sync::Arc;
+
+/// 啟動時檢查：環境名稱會成為目錄名稱，只接受英數字、`-` 與 `_`；"default" 保留給預設環境
+pub fn check(config: &ServerConfig) -> Result<()> {
+    if config.environments.is_empty() {
+        return Ok(());
+    }
+    if config.replica.is_some() {
+        bail!("environments cannot be used on a read-only replica");
+    }
+    for name in config.environments.keys() {
+        let valid = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            bail!("environment name {name:?} may only contain letters, digits, '-' and '_'");
+        }
+        if name == DEFAULT_ENVIRONMENT {
+            bail!("environment name {name:?} is reserved; set the top-level token instead");
+        }
+    }
+    Ok(())
+}
+
+/// 拆開 InEnvironment，回傳處理請求的環境與實際的請求；其他請求由預設環境處理。
+/// 各環境（含預設環境）設定了權杖時都要帶上
+pub fn select(
+    state: &Arc<State>,
+    req: ClientRequest,
+) -> Result<(Arc<State>, ClientRequest), SchedulerError> {
+    let ClientRequest::InEnvironment {
+        environment,
+        token,
+        request,
+    } = req
+    else {
+        if state.config.token.is_some() {
+            return Err(SchedulerError::Unauthorized {
+                msg: format!(
+                    "this server requires a token; send requests as InEnvironment {DEFAULT_ENVIRONMENT:?}"
+                ),
+            });
+        }
+        return Ok((state.clone(), req));
+    };
+    let env = if environment == DEFAULT_ENVIRONMENT {
+        state
+    } else {
+        state
+            .environments
+            .get(&environment)
+            .ok_or_else(|| SchedulerError::NotFound {
+                msg: format!("environment {environment:?} not found"),
+            })?
+    };
+    if !token_matches(env.config.token.as_deref(), token.as_deref()) {
+        return Err(SchedulerError::Unauthorized {
+            msg: format!("invalid or missing token for environment {environment:?}"),
+        });
+    }
+    Ok((env.clone(), *request))
+}
+
+/// 比對雜湊而不是權杖本身，比對所花的時間不會透露權杖內容
+fn token_matches(expected: Option<&str>, given: Option<&str>) -> bool {
+    match (expected, given) {
+        (None, _) => true,
+        (Some(expected), Some(given)) => {
+            verify::sha256_hex(expected.as_bytes()) == verify::sha256_hex(given.as_bytes())
+        }
+        (Some(_), None) => false,
+    }
+}
+
+/// 副本轉送給主伺服器的請求：預設環境設了權杖時以同一個權杖包裝
+pub fn authorize(config: &ServerConfig, req: ClientRequest) -> ClientRequest {
+    match &config.token {
+        Some(token) => ClientRequest::InEnvironment {
+            environment: DEFAULT_ENVIRONMENT.to_string(),
+            token: Some(token.clone()),
+            request: Box::new(req),
+        },
+        None => req,
+    }
+}
@@ -0,0 +1,415 @@
+use crate::{config::EventsConfig, local_now_fixed, State};
+use anyhow::{anyhow, bail, Context, Result};
+use bytes::Bytes;
+use scheduler_core::{Event, EventKind, ServerResponse};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    thread::JoinHandle,
+    time::Duration,
+};
+use tokio::sync::{broadcast, mpsc};
+
+/// 每預留多少個 seq 才寫一次高水位檔；異常停機後最多跳過一個區塊，不會重用
+const SEQ_BLOCK: u64 = 256;
+
+/// 事件匯流排：保留最近 capacity 筆供重連補送（可寫入檔案），並廣播給訂閱者。
+/// seq 從高水位接續，重啟後不會倒退或重用（不論事件是否寫入檔案）
+pub struct EventBus {
+    last_seq: AtomicU64,
+    /// 啟動時接續的 seq；這之後的事件都是這次啟動發出的
+    opened_at: u64,
+    recent: Mutex<Recent>,
+    capacity: usize,
+    /// 事件檔的寫入執行緒；close 時等它寫完
+    writer_thread: Mutex<Option<JoinHandle<()>>>,
+    tx: broadcast::Sender<Event>,
+}
+
+struct Recent {
+    events: VecDeque<Event>,
+    /// 各任務最後一筆事件的 seq（ListTasksSince 用）
+    touched: HashMap<u64, u64>,
+    /// 移除任務的 seq → id，最多保留 capacity 筆
+    removed: BTreeMap<u64, u64>,
+    /// 更早的 version 無法判斷變更（啟動前或已捨棄的移除紀錄），只能回傳完整清單
+    floor: u64,
+    /// 交給事件檔寫入執行緒；沒有持久化或已關閉時為 None
+    writer: Option<mpsc::UnboundedSender<Event>>,
+    /// seq 的預留高水位；唯讀副本沿用主伺服器的 seq，不預留也不寫檔
+    mark: Option<SeqMark>,
+}
+
+/// 已預留到哪個 seq（含）；配發超過時先寫檔再往後預留一個區塊
+struct SeqMark {
+    path: PathBuf,
+    reserved: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedMark {
+    reserved: u64,
+}
+
+impl SeqMark {
+    fn load(path: &Path) -> Result<Self> {
+        let reserved = if path.exists() {
+            let text = std::fs::read_to_string(path)
+                .with_context(|| format!("read {}", path.display()))?;
+            let saved: SavedMark =
+                serde_json::from_str(&text).with_context(|| format!("parse {}", path.display()))?;
+            saved.reserved
+        } else {
+            0
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            reserved,
+        })
+    }
+
+    /// 確保 seq 已在預留範圍內。寫檔失敗不擋事件，只提示；下一筆事件會再試著寫
+    fn reserve(&mut self, seq: u64) {
+        if seq <= self.reserved {
+            return;
+        }
+        let reserved = seq + SEQ_BLOCK - 1;
+        match self.save(reserved) {
+            Ok(()) => self.reserved = reserved,
+            Err(e) => eprintln!("⚠️ event seq reservation not saved: {e:#}"),
+        }
+    }
+
+    fn save(&self, reserved: u64) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let tmp = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec(&SavedMark { reserved })?)
+            .with_context(|| format!("write {}", tmp.display()))?;
+        std::fs::rename(&tmp, &self.path)
+            .with_context(|| format!("replace {}", self.path.display()))
+    }
+}
+
+/// 事件檔：在自己的執行緒上保持開啟的檔案逐行附加，不在事件的鎖內或 async 執行緒上做 I/O
+/// （只有每 SEQ_BLOCK 筆一次的高水位在鎖內寫）。
+/// 行數超過 capacity 兩倍時改寫成只剩最近 capacity 筆
+struct EventLog {
+    path: PathBuf,
+    capacity: usize,
+    /// 最近 capacity 筆（改寫用）
+    events: VecDeque<Event>,
+    /// 事件檔目前的行數
+    lines: usize,
+    file: Option<File>,
+}
+
+impl EventLog {
+    /// 送出端全部關閉後，寫完佇列中剩下的事件才結束
+    fn spawn(mut self) -> Result<(mpsc::UnboundedSender<Event>, JoinHandle<()>)> {
+        let (tx, mut rx) = mpsc::unbounded_channel::<Event>();
+        let handle = std::thread::Builder::new()
+            .name("event-log".to_string())
+            .spawn(move || {
+                while let Some(ev) = rx.blocking_recv() {
+                    if let Err(e) = self.append(ev) {
+                        eprintln!("event persist error: {e:?}");
+                    }
+                }
+            })
+            .context("spawn event log writer")?;
+        Ok((tx, handle))
+    }
+
+    fn append(&mut self, ev: Event) -> Result<()> {
+        let mut line = serde_json::to_vec(&ev)?;
+        line.push(b'\n');
+        self.events.push_back(ev);
+        while self.events.len() > self.capacity {
+            self.events.pop_front();
+        }
+        if self.lines >= self.capacity * 2 {
+            return self.compact();
+        }
+        let file = match &mut self.file {
+            Some(f) => f,
+            None => self.file.insert(
+                std::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&self.path)
+                    .with_context(|| format!("open {}", self.path.display()))?,
+            ),
+        };
+        if let Err(e) = file.write_all(&line) {
+            self.file = None; // 下一筆重新開啟
+            return Err(e).with_context(|| format!("append {}", self.path.display()));
+        }
+        self.lines += 1;
+        Ok(())
+    }
+
+    /// 先寫暫存檔再改名；之後的事件附加到新檔
+    fn compact(&mut self) -> Result<()> {
+        let mut buf = Vec::new();
+        for e in &self.events {
+            serde_json::to_writer(&mut buf, e)?;
+            buf.push(b'\n');
+        }
+        let tmp = self.path.with_extension("jsonl.tmp");
+        std::fs::write(&tmp, &buf).with_context(|| format!("write {}", tmp.display()))?;
+        std::fs::rename(&tmp, &self.path)
+            .with_context(|| format!("replace {}", self.path.display()))?;
+        self.file = None;
+        self.lines = self.events.len();
+        Ok(())
+    }
+}
+
+/// ListTasksSince 要回傳的任務
+pub enum TaskChangeSet {
+    /// version 太舊，回傳完整清單
+    Full,
+    Since {
+        touched: Vec<u64>,
+        removed: Vec<u64>,
+    },
+}
+
+impl EventBus {
+    /// relay：唯讀副本只轉播主伺服器的事件，沿用它的 seq，不寫任何檔案
+    pub fn open(cfg: &EventsConfig, relay: bool) -> Result<Self> {
+        let capacity = cfg.buffer.max(1);
+        let path = cfg.persist.then(|| cfg.path.clone());
+        let mut events = VecDeque::new();
+        let mut file_lines = 0;
+        if let Some(path) = path.as_ref().filter(|p| p.exists()) {
+            let text = std::fs::read_to_string(path)
+                .with_context(|| format!("read {}", path.display()))?;
+            for (i, line) in text.lines().enumerate() {
+                file_lines += 1;
+                match serde_json::from_str::<Event>(line) {
+                    Ok(ev) => events.push_back(ev),
+                    Err(e) => eprintln!("events {}:{}: skipped: {e}", path.display(), i + 1),
+                }
+            }
+            while events.len() > capacity {
+                events.pop_front();
+            }
+        }
+        let mark = match relay {
+            true => None,
+            false => Some(SeqMark::load(&cfg.seq_path)?),
+        };
+        // 已預留的 seq 可能已經廣播出去，只是沒寫進事件檔：一律從預留上限之後接續
+        let last_seq = events
+            .back()
+            .map_or(0, |e: &Event| e.seq)
+            .max(mark.as_ref().map_or(0, |m| m.reserved));
+        let (writer, writer_thread) = match path {
+            Some(path) => {
+                let (tx, handle) = EventLog::spawn(EventLog {
+                    path,
+                    capacity,
+                    events: events.clone(),
+                    lines: file_lines,
+                    file: None,
+                })?;
+                (Some(tx), Some(handle))
+            }
+            None => (None, None),
+        };
+
+        let (tx, _) = broadcast::channel(capacity);
+        Ok(Self {
+            last_seq: AtomicU64::new(last_seq),
+            opened_at: last_seq,
+            recent: Mutex::new(Recent {
+                events,
+                touched: HashMap::new(),
+                removed: BTreeMap::new(),
+                floor: last_seq,
+                writer,
+                mark,
+            }),
+            capacity,
+            writer_thread: Mutex::new(writer_thread),
+            tx,
+        })
+    }
+
+    /// 關閉時呼叫：停止接收新的事件檔寫入，並等寫入執行緒把已送出的事件寫完。
+    /// 之後的事件仍會配號（不重用）與廣播，只是不再寫入檔案
+    pub fn close(&self) {
+        self.recent.lock().unwrap().writer = None;
+        let handle = self.writer_thread.lock().unwrap().take();
+        if let Some(handle) = handle {
+            if handle.join().is_err() {
+                eprintln!("event log writer panicked");
+            }
+        }
+    }
+
+    pub fn emit(&self, kind: EventKind) {
+        // 在鎖內配號、交給寫入執行緒與廣播，訂閱者與事件檔看到的順序都與 seq 一致
+        let mut recent = self.recent.lock().unwrap();
+        let seq = self.last_seq.fetch_add(1, Ordering::SeqCst) + 1;
+        if let Some(mark) = &mut recent.mark {
+            mark.reserve(seq);
+        }
+        let ev = Event {
+            seq,
+            at: local_now_fixed(),
+            kind,
+        };
+        recent.events.push_back(ev.clone());
+        while recent.events.len() > self.capacity {
+            recent.events.pop_front();
+        }
+        self.touch(&mut recent, &ev);
+        if let Some(writer) = &recent.writer {
+            let _ = writer.send(ev.clone()); // 寫入執行緒只在 close 後停止
+        }
+        let _ = self.tx.send(ev); // 沒有訂閱者時會失敗，忽略
+    }
+
+    /// 唯讀副本轉播主伺服器的事件：沿用原本的 seq，客戶端換到主伺服器也能以同一個 cursor 接續
+    pub fn relay(&self, ev: Event) {
+        let mut recent = self.recent.lock().unwrap();
+        if ev.seq <= self.last_seq() {
+            return; // 重新連線時補送的重複事件
+        }
+        self.last_seq.store(ev.seq, Ordering::SeqCst);
+        recent.events.push_back(ev.clone());
+        while recent.events.len() > self.capacity {
+            recent.events.pop_front();
+        }
+        let _ = self.tx.send(ev);
+    }
+
+    /// 主伺服器的 seq 倒退（高水位檔遺失）：捨棄轉播過的事件，從 seq 接續
+    pub fn rewind(&self, seq: u64) {
+        let mut recent = self.recent.lock().unwrap();
+        recent.events.clear();
+        self.last_seq.store(seq, Ordering::SeqCst);
+    }
+
+    fn touch(&self, recent: &mut Recent, ev: &Event) {
+        let Some(task_id) = ev.kind.task_id() else {
+            return;
+        };
+        if !matches!(ev.kind, EventKind::TaskRemoved { .. }) {
+            recent.touched.insert(task_id, ev.seq);
+            return;
+        }
+        recent.touched.remove(&task_id);
+        recent.removed.insert(ev.seq, task_id);
+        while recent.removed.len() > self.capacity {
+            if let Some((seq, _)) = recent.removed.pop_first() {
+                recent.floor = recent.floor.max(seq);
+            }
+        }
+    }
+
+    /// 目前最新 seq 與 version 之後有事件、被移除的任務（同一把鎖內）
+    pub fn changes_since(&self, version: u64) -> (u64, TaskChangeSet) {
+        let recent = self.recent.lock().unwrap();
+        let last = self.last_seq();
+        // 重啟後 seq 從高水位之後接續，重啟前的 version 都落在 floor 之下；
+        // 比目前還新的 version 只會來自 seq 倒退過的伺服器（高水位檔遺失）
+        if version == 0 || version < recent.floor || version > last {
+            return (last, TaskChangeSet::Full);
+        }
+        let touched = recent
+            .touched
+            .iter()
+            .filter(|(_, seq)| **seq > version)
+            .map(|(id, _)| *id)
+            .collect();
+        let removed = recent
+            .removed
+            .range(version + 1..)
+            .map(|(_, id)| *id)
+            .collect();
+        (last, TaskChangeSet::Since { touched, removed })
+    }
+
+    pub fn last_seq(&self) -> u64 {
+        self.last_seq.load(Ordering::SeqCst)
+    }
+
+    pub fn opened_at(&self) -> u64 {
+        self.opened_at
+    }
+
+    /// 取得目前最新 seq、since 之後仍保留的事件與後續的接收端（同一把鎖內，不漏不重）
+    pub fn subscribe(&self, since: Option<u64>) -> (u64, Vec<Event>, broadcast::Receiver<Event>) {
+        let recent = self.recent.lock().unwrap();
+        let rx = self.tx.subscribe();
+        let backlog = match since {
+            Some(since) => recent
+                .events
+                .iter()
+                .filter(|e| e.seq > since)
+                .cloned()
+                .collect(),
+            None => Vec::new(),
+        };
+        (self.last_seq(), backlog, rx)
+    }
+}
+
+/// 把事件與心跳送到 out（以 id 標示所屬的訂閱），直到連線關閉。
+/// 客戶端若跟不上（廣播佇列溢出）就回傳錯誤，讓它帶著 cursor 重新訂閱補送
+pub async fn stream(
+    state: &State,
+    since: Option<u64>,
+    id: Option<u64>,
+    out: &mpsc::Sender<Bytes>,
+) -> Result<()> {
+    let (last_seq, backlog, mut rx) = state.events.subscribe(since);
+    let interval_secs = state.config.events.heartbeat_secs.max(1);
+    let send = |resp: ServerResponse| async move {
+        out.send(crate::encode_reply(id, &resp)?)
+            .await
+            .map_err(|_| anyhow!("connection closed"))
+    };
+
+    // 先送一次心跳，讓沒有 cursor 的客戶端知道從哪裡接續
+    send(ServerResponse::Heartbeat {
+        last_seq,
+        interval_secs,
+    })
+    .await?;
+    for ev in backlog {
+        send(ServerResponse::Event(ev)).await?;
+    }
+
+    let period = Duration::from_secs(interval_secs);
+    let mut heartbeat = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+    loop {
+        let resp = tokio::select! {
+            ev = rx.recv() => match ev {
+                Ok(ev) => ServerResponse::Event(ev),
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    bail!("event subscriber lagged by {n} event(s)");
+                }
+                Err(broadcast::error::RecvError::Closed) => return Ok(()),
+            },
+            _ = heartbeat.tick() => ServerResponse::Heartbeat {
+                last_seq: state.events.last_seq(),
+                interval_secs,
+            },
+            _ = out.closed() => return Ok(()),
+        };
+        send(resp).await?;
+    }
+}
@@ -0,0 +1,703 @@
+use crate::{
+    config::{MockConfig, ServerConfig},
+    encoding,
+    live::{Tee, Utf8Carry},
+    outputs, policy, progress, sandbox,
+};
+use anyhow::{Context, Result};
+use chrono::{DateTime, FixedOffset};
+use scheduler_core::{
+    OutputEncoding, OutputStream, OutputsFrom, Progress, ResourceUsage, RunOutcome, SchedClass,
+    StopSignal, TaskSpec,
+};
+use std::{
+    ffi::OsString,
+    process::Stdio,
+    sync::{
+        atomic::{AtomicBool, AtomicU32, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt},
+    process::Command,
+    task::JoinHandle,
+    time::{sleep, timeout},
+};
+use tokio_util::sync::CancellationToken;
+
+/// 執行中的一次 run；登記在 State.running，供 watchdog 巡檢與終止
+#[derive(Debug)]
+pub struct RunHandle {
+    pub task_id: u64,
+    pub started_at: DateTime<FixedOffset>,
+    pid: AtomicU32,     // 0 = 尚未 spawn
+    exited: AtomicBool, // 子程序已結束並回收，正在讀完輸出
+    kill: CancellationToken,
+    reason: Mutex<Option<(RunOutcome, Instant)>>, // 第一個終止理由與要求的時間
+    stop_grace: Duration,                         // 停止訊號後等它自行結束的時間
+    progress: Mutex<Option<Progress>>,
+}
+
+impl RunHandle {
+    pub fn new(task_id: u64, started_at: DateTime<FixedOffset>, spec: &TaskSpec) -> Self {
+        Self {
+            task_id,
+            started_at,
+            pid: AtomicU32::new(0),
+            exited: AtomicBool::new(false),
+            kill: CancellationToken::new(),
+            reason: Mutex::new(None),
+            stop_grace: stop_grace(spec),
+            progress: Mutex::new(None),
+        }
+    }
+
+    pub fn pid(&self) -> Option<u32> {
+        match self.pid.load(Ordering::SeqCst) {
+            0 => None,
+            pid => Some(pid),
+        }
+    }
+
+    /// 子程序已結束並回收；之後只剩讀完輸出與寫檔，pid 可能已被重用
+    pub fn exited(&self) -> bool {
+        self.exited.load(Ordering::SeqCst)
+    }
+
+    /// 要求終止；只保留第一個理由
+    pub fn kill(&self, reason: RunOutcome) {
+        self.reason
+            .lock()
+            .unwrap()
+            .get_or_insert((reason, Instant::now()));
+        self.kill.cancel();
+    }
+
+    /// 是否已被要求終止
+    pub fn kill_requested(&self) -> Option<RunOutcome> {
+        self.reason.lock().unwrap().map(|(reason, _)| reason)
+    }
+
+    /// 要求終止後，停止訊號的寬限期加上讀完輸出的上限都過了仍未收尾
+    pub fn kill_overdue(&self) -> bool {
+        self.reason
+            .lock()
+            .unwrap()
+            .is_some_and(|(_, at)| at.elapsed() > self.stop_grace + DRAIN_AFTER_KILL)
+    }
+
+    /// 子程序最近一次回報的進度
+    pub fn progress(&self) -> Option<Progress> {
+        self.progress.lock().unwrap().clone()
+    }
+
+    pub fn set_progress(&self, p: Progress) {
+        *self.progress.lock().unwrap() = Some(p);
+    }
+}
+
+/// 子程序的執行結果
+pub struct ExecOutput {
+    pub status_code: i32,
+    pub outcome: RunOutcome,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+    /// 子程序結束時的資源用量；取不到時為 None
+    pub usage: Option<ResourceUsage>,
+}
+
+/// 被終止後等待輸出管線關閉的上限
+const DRAIN_AFTER_KILL: Duration = Duration::from_secs(5);
+/// 任務沒有設定 stop_grace_secs 時，送出停止訊號後等它自行結束的時間
+const DEFAULT_STOP_GRACE: Duration = Duration::from_secs(5);
+
+/// 執行外部程式並收集輸出；逾時或被要求終止時，連同整個行程樹一起結束。
+/// progress 為回報進度用的 FIFO；tee 為即時轉送輸出的 follower
+pub async fn run_command(
+    cfg: &ServerConfig,
+    spec: &TaskSpec,
+    run: &RunHandle,
+    progress: Option<&progress::Channel>,
+    tee: Option<&Tee>,
+) -> Result<ExecOutput> {
+    let program = policy::launch_program(&cfg.policy, &spec.cmd, child_path(spec).as_deref());
+    let mut cmd = match &spec.sandbox {
+        Some(profile) => sandbox::bwrap_command(&cfg.sandbox, profile, &program, spec),
+        None => {
+            let mut c = Command::new(&program);
+            c.args(&spec.args);
+            c
+        }
+    };
+    if spec.clean_env {
+        cmd.env_clear();
+        cmd.envs(clean_env_base());
+    }
+    cmd.envs(&spec.env);
+    if let Some(OutputsFrom::File(path)) = &spec.outputs {
+        cmd.env(outputs::OUTPUTS_ENV, path);
+    }
+    if let Some(ch) = progress {
+        cmd.env(progress::PROGRESS_ENV, ch.path());
+    }
+    cmd.stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    ProcessTree::configure(&mut cmd);
+    apply_sched(&mut cmd, &spec.sched);
+
+    let mut child = cmd
+        .spawn()
+        .with_context(|| format!("spawn {:?}", cmd.as_std().get_program()))?;
+    let tree = ProcessTree::attach(&child);
+    let mut watch = ExitWatch::start(&child);
+    run.pid.store(child.id().unwrap_or(0), Ordering::SeqCst);
+
+    let out_task = spawn_reader(
+        child.stdout.take(),
+        tee.map(|t| (t.clone(), OutputStream::Stdout)),
+        spec.output_encoding,
+    );
+    let err_task = spawn_reader(
+        child.stderr.take(),
+        tee.map(|t| (t.clone(), OutputStream::Stderr)),
+        spec.output_encoding,
+    );
+
+    let task_timeout = async {
+        match spec.timeout_secs {
+            Some(secs) => sleep(Duration::from_secs(secs)).await,
+            None => std::future::pending().await,
+        }
+    };
+
+    // 先等子程序結束（尚未回收）取得用量，再交給 tokio 回收
+    let exited = async {
+        watch.exited().await;
+        child.wait().await
+    };
+    let (status_code, outcome) = tokio::select! {
+        st = exited => (st?.code().unwrap_or(-1), RunOutcome::Exited),
+        _ = task_timeout => {
+            stop(spec, &tree, &mut child, &mut watch).await;
+            (-1, RunOutcome::TimedOut)
+        }
+        _ = run.kill.cancelled() => {
+            stop(spec, &tree, &mut child, &mut watch).await;
+            (-1, run.kill_requested().unwrap_or(RunOutcome::Lost))
+        }
+    };
+
+    run.exited.store(true, Ordering::SeqCst);
+
+    // 脫離群組的孫程序可能仍握著管線：正常結束時等到讀完，
+    // 但讀的期間被要求終止（例如超過伺服器上限）就不再無限等待
+    let killed = outcome != RunOutcome::Exited;
+    let (stdout, stderr) =
+        tokio::join!(drain(out_task, run, killed), drain(err_task, run, killed),);
+
+    Ok(ExecOutput {
+        status_code,
+        outcome,
+        stdout,
+        stderr,
+        usage: watch.usage,
+    })
+}
+
+/// 先送任務的停止訊號，寬限期內沒有結束再 SIGKILL；
+/// 最後一律清掉整個行程樹，不留下脫隊的子孫
+async fn stop(
+    spec: &TaskSpec,
+    tree: &ProcessTree,
+    child: &mut tokio::process::Child,
+    watch: &mut ExitWatch,
+) {
+    let signal = spec.stop_signal.unwrap_or_default();
+    if signal != StopSignal::Kill && tree.signal(signal) {
+        let grace = stop_grace(spec);
+        let exited = async {
+            watch.exited().await;
+            let _ = child.wait().await;
+        };
+        let _ = timeout(grace, exited).await;
+    }
+    tree.kill();
+    let _ = child.start_kill();
+    watch.exited().await;
+    let _ = child.wait().await;
+}
+
+fn stop_grace(spec: &TaskSpec) -> Duration {
+    spec.stop_grace_secs
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_STOP_GRACE)
+}
+
+/// clean_env 的任務仍從伺服器帶過去的環境變數
+#[cfg(not(windows))]
+const CLEAN_ENV_KEEP: &[&str] = &["PATH", "HOME", "TZ"];
+/// Windows 少了 SystemRoot 等變數，許多程式無法啟動
+#[cfg(windows)]
+const CLEAN_ENV_KEEP: &[&str] = &["PATH", "HOME", "TZ", "SystemRoot", "TEMP", "TMP"];
+
+/// 子程序實際使用的 PATH：任務 env 優先，其次 clean_env 的基本環境，最後沿用伺服器的
+pub fn child_path(spec: &TaskSpec) -> Option<OsString> {
+    if let Some(path) = spec.env.get("PATH") {
+        return Some(path.into());
+    }
+    if spec.clean_env {
+        return clean_env_base()
+            .into_iter()
+            .find(|(k, _)| k == "PATH")
+            .map(|(_, v)| v.into());
+    }
+    std::env::var_os("PATH")
+}
+
+/// clean_env 的基本環境；伺服器沒有 PATH 時給一個常見的預設值
+fn clean_env_base() -> Vec<(String, String)> {
+    let mut base: Vec<(String, String)> = CLEAN_ENV_KEEP
+        .iter()
+        .filter_map(|k| std::env::var(k).ok().map(|v| (k.to_string(), v)))
+        .collect();
+    if !base.iter().any(|(k, _)| k == "PATH") {
+        base.push((
+            "PATH".to_string(),
+            "/usr/local/bin:/usr/bin:/bin".to_string(),
+        ));
+    }
+    base
+}
+
+/// 檢查模擬規則的正規表示式（啟動時呼叫）
+pub fn check_mock(cfg: &MockConfig) -> Result<()> {
+    for rule in &cfg.rules {
+        regex::Regex::new(&rule.pattern)
+            .with_context(|| format!("invalid mock pattern {:?}", rule.pattern))?;
+    }
+    Ok(())
+}
+
+/// 模擬執行：不啟動子程序，等待規則指定的時間後回傳結果；逾時與終止照常生效
+pub async fn run_mock(cfg: &MockConfig, spec: &TaskSpec, run: &RunHandle) -> ExecOutput {
+    let mut line = spec.cmd.clone();
+    for a in &spec.args {
+        line.push(' ');
+        line.push_str(a);
+    }
+    let rule = cfg
+        .rules
+        .iter()
+        .find(|r| regex::Regex::new(&r.pattern).is_ok_and(|re| re.is_match(&line)));
+    let duration =
+        Duration::from_millis(rule.and_then(|r| r.duration_ms).unwrap_or(cfg.duration_ms));
+    let exit_code = rule.and_then(|r| r.exit_code).unwrap_or(cfg.exit_code);
+    let stdout = rule
+        .and_then(|r| r.stdout.clone())
+        .unwrap_or_default()
+        .into_bytes();
+
+    let (status_code, outcome) = match idle(spec, run, duration).await {
+        None => (exit_code, RunOutcome::Exited),
+        Some(outcome) => (-1, outcome),
+    };
+    ExecOutput {
+        status_code,
+        outcome,
+        stdout: if outcome == RunOutcome::Exited {
+            stdout
+        } else {
+            Vec::new()
+        },
+        stderr: Vec::new(),
+        usage: None,
+    }
+}
+
+/// 不執行任何東西，等待 duration；期間逾時或被要求終止時回傳該結果（模擬執行與故障注入用）
+pub async fn idle(spec: &TaskSpec, run: &RunHandle, duration: Duration) -> Option<RunOutcome> {
+    let task_timeout = async {
+        match spec.timeout_secs {
+            Some(secs) => sleep(Duration::from_secs(secs)).await,
+            None => std::future::pending().await,
+        }
+    };
+    tokio::select! {
+        _ = sleep(duration) => None,
+        _ = task_timeout => Some(RunOutcome::TimedOut),
+        _ = run.kill.cancelled() => Some(run.kill_requested().unwrap_or(RunOutcome::Lost)),
+    }
+}
+
+/// 讀完整條管線；有 tee 時每讀到一段就轉送
+fn spawn_reader<R>(
+    pipe: Option<R>,
+    tee: Option<(Tee, OutputStream)>,
+    encoding: OutputEncoding,
+) -> JoinHandle<Vec<u8>>
+where
+    R: AsyncRead + Unpin + Send + 'static,
+{
+    tokio::spawn(async move {
+        let mut buf = Vec::new();
+        let Some(mut p) = pipe else {
+            return buf;
+        };
+        let Some((tee, stream)) = tee else {
+            let _ = p.read_to_end(&mut buf).await;
+            return buf;
+        };
+        let mut carry = Utf8Carry::new(encoding);
+        let mut chunk = [0u8; 8192];
+        // stdout 判定為二進位後不再轉送，結束時只送一行摘要
+        let mut binary = false;
+        loop {
+            match p.read(&mut chunk).await {
+                Ok(0) | Err(_) => break,
+                Ok(n) => {
+                    buf.extend_from_slice(&chunk[..n]);
+                    binary = binary
+                        || (stream == OutputStream::Stdout && encoding::is_binary(&chunk[..n]));
+                    if !binary {
+                        tee.output(stream, carry.push(&chunk[..n]));
+                    }
+                }
+            }
+        }
+        if binary {
+            tee.output(stream, encoding::binary_notice(buf.len(), None));
+        } else {
+            tee.output(stream, carry.finish());
+        }
+        buf
+    })
+}
+
+/// 讀完一條管線；已被終止（或讀的期間被要求終止）時最多再等 DRAIN_AFTER_KILL
+async fn drain(mut task: JoinHandle<Vec<u8>>, run: &RunHandle, killed: bool) -> Vec<u8> {
+    if !killed {
+        tokio::select! {
+            out = &mut task => return out.unwrap_or_default(),
+            _ = run.kill.cancelled() => {}
+        }
+    }
+    match timeout(DRAIN_AFTER_KILL, &mut task).await {
+        Ok(out) => out.unwrap_or_default(),
+        Err(_) => {
+            task.abort();
+            Vec::new()
+        }
+    }
+}
+
+/// 在 exec 前於子程序內套用 nice / ionice / CPU 親和性
+/// 失敗時讓 spawn 失敗，而不是默默以預設優先權執行
+#[cfg(unix)]
+fn apply_sched(cmd: &mut Command, sched: &SchedClass) {
+    if sched.nice.is_none() && sched.ionice.is_none() && sched.cpu_affinity.is_empty() {
+        return;
+    }
+    let sched = sched.clone();
+    // SAFETY: 閉包只呼叫 async-signal-safe 的系統呼叫，不配置記憶體
+    unsafe {
+        cmd.pre_exec(move || {
+            if let Some(nice) = sched.nice {
+                if libc::setpriority(libc::PRIO_PROCESS, 0, nice) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+            }
+            #[cfg(target_os = "linux")]
+            {
+                if let Some(io) = sched.ionice {
+                    set_ionice(io)?;
+                }
+                if !sched.cpu_affinity.is_empty() {
+                    set_affinity(&sched.cpu_affinity)?;
+                }
+            }
+            Ok(())
+        });
+    }
+}
+
+#[cfg(not(unix))]
+fn apply_sched(_cmd: &mut Command, _sched: &SchedClass) {}
+
+#[cfg(target_os = "linux")]
+fn set_ionice(io: scheduler_core::IoNice) -> std::io::Result<()> {
+    use scheduler_core::IoNice;
+
+    const IOPRIO_CLASS_SHIFT: i32 = 13;
+    const IOPRIO_WHO_PROCESS: i32 = 1;
+    let (class, level) = match io {
+        IoNice::RealTime { level } => (1, level),
+        IoNice::BestEffort { level } => (2, level),
+        IoNice::Idle => (3, 0),
+    };
+    let prio = (class << IOPRIO_CLASS_SHIFT) | i32::from(level);
+    if unsafe { libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, 0, prio) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn set_affinity(cpus: &[usize]) -> std::io::Result<()> {
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        for &cpu in cpus {
+            libc::CPU_SET(cpu, &mut set);
+        }
+        if libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// 子程序與其衍生的子孫（shell wrapper 底下的程式等）
+/// Unix：子程序自成一個行程群組，終止時 killpg 整組
+#[cfg(unix)]
+struct ProcessTree {
+    pgid: Option<libc::pid_t>,
+}
+
+#[cfg(unix)]
+impl ProcessTree {
+    fn configure(cmd: &mut Command) {
+        cmd.process_group(0);
+    }
+
+    fn attach(child: &tokio::process::Child) -> Self {
+        Self {
+            pgid: child.id().map(|pid| pid as libc::pid_t),
+        }
+    }
+
+    fn kill(&self) {
+        if let Some(pgid) = self.pgid {
+            unsafe {
+                libc::killpg(pgid, libc::SIGKILL);
+            }
+        }
+    }
+
+    /// 送訊號給整個行程群組；送不出去時回傳 false
+    fn signal(&self, signal: StopSignal) -> bool {
+        let sig = match signal {
+            StopSignal::Term => libc::SIGTERM,
+            StopSignal::Int => libc::SIGINT,
+            StopSignal::Hup => libc::SIGHUP,
+            StopSignal::Quit => libc::SIGQUIT,
+            StopSignal::Usr1 => libc::SIGUSR1,
+            StopSignal::Usr2 => libc::SIGUSR2,
+            StopSignal::Kill => libc::SIGKILL,
+        };
+        match self.pgid {
+            Some(pgid) => unsafe { libc::killpg(pgid, sig) == 0 },
+            None => false,
+        }
+    }
+}
+
+/// Windows：子程序加入 Job Object，終止時 TerminateJobObject 整個 job
+#[cfg(windows)]
+struct ProcessTree {
+    job: Option<windows_sys::Win32::Foundation::HANDLE>,
+}
+
+// Job handle 只在擁有者手上使用，可安全跨執行緒移動
+#[cfg(windows)]
+unsafe impl Send for ProcessTree {}
+#[cfg(windows)]
+unsafe impl Sync for ProcessTree {}
+
+#[cfg(windows)]
+impl ProcessTree {
+    fn configure(_cmd: &mut Command) {}
+
+    fn attach(child: &tokio::process::Child) -> Self {
+        use windows_sys::Win32::{
+            Foundation::CloseHandle,
+            System::JobObjects::{AssignProcessToJobObject, CreateJobObjectW},
+        };
+
+        let Some(process) = child.raw_handle() else {
+            return Self { job: None };
+        };
+        unsafe {
+            let job = CreateJobObjectW(std::ptr::null(), std::ptr::null());
+            if job.is_null() {
+                return Self { job: None };
+            }
+            if AssignProcessToJobObject(job, process as _) == 0 {
+                CloseHandle(job);
+                return Self { job: None };
+            }
+            Self { job: Some(job) }
+        }
+    }
+
+    fn kill(&self) {
+        if let Some(job) = self.job {
+            unsafe {
+                windows_sys::Win32::System::JobObjects::TerminateJobObject(job, 1);
+            }
+        }
+    }
+
+    /// 沒有對應的訊號，直接終止
+    fn signal(&self, _signal: StopSignal) -> bool {
+        false
+    }
+}
+
+#[cfg(windows)]
+impl Drop for ProcessTree {
+    fn drop(&mut self) {
+        if let Some(job) = self.job.take() {
+            unsafe {
+                windows_sys::Win32::Foundation::CloseHandle(job);
+            }
+        }
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+struct ProcessTree;
+
+#[cfg(not(any(unix, windows)))]
+impl ProcessTree {
+    fn configure(_cmd: &mut Command) {}
+
+    fn attach(_child: &tokio::process::Child) -> Self {
+        Self
+    }
+
+    fn kill(&self) {}
+
+    fn signal(&self, _signal: StopSignal) -> bool {
+        false
+    }
+}
+
+/// 等子程序結束並讀出它的 rusage。
+/// Linux：以 pidfd 在 tokio 上等待結束（不佔用執行緒），再以 waitid(WNOWAIT) 讀出用量，
+/// 子程序停在 zombie 狀態不被回收，之後照常由 tokio 回收並取得結束碼；
+/// 核心不支援 pidfd 或其他平台不記錄用量
+struct ExitWatch {
+    #[cfg(target_os = "linux")]
+    pidfd: Option<(u32, tokio::io::unix::AsyncFd<std::os::fd::OwnedFd>)>,
+    usage: Option<ResourceUsage>,
+}
+
+impl ExitWatch {
+    #[cfg(target_os = "linux")]
+    fn start(child: &tokio::process::Child) -> Self {
+        Self {
+            pidfd: child
+                .id()
+                .and_then(|pid| open_pidfd(pid).map(|fd| (pid, fd))),
+            usage: None,
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn start(_child: &tokio::process::Child) -> Self {
+        Self { usage: None }
+    }
+
+    /// 子程序已結束（或無從等待）時返回；可重複呼叫，中途被取消也不會漏掉結果
+    async fn exited(&mut self) {
+        #[cfg(target_os = "linux")]
+        if let Some((pid, fd)) = &self.pidfd {
+            // pidfd 在子程序結束時變成可讀
+            if fd.readable().await.is_ok() {
+                self.usage = exit_usage(*pid);
+            }
+            self.pidfd = None;
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn open_pidfd(pid: u32) -> Option<tokio::io::unix::AsyncFd<std::os::fd::OwnedFd>> {
+    use std::os::fd::{FromRawFd, OwnedFd};
+    let fd = unsafe { libc::syscall(libc::SYS_pidfd_open, pid as libc::pid_t, 0) };
+    if fd < 0 {
+        return None;
+    }
+    // SAFETY: 剛開啟的 fd，只由這裡持有
+    let fd = unsafe { OwnedFd::from_raw_fd(fd as std::os::fd::RawFd) };
+    tokio::io::unix::AsyncFd::with_interest(fd, tokio::io::Interest::READABLE).ok()
+}
+
+/// 已結束（尚未回收）的子程序的用量；不等待
+#[cfg(target_os = "linux")]
+fn exit_usage(pid: u32) -> Option<ResourceUsage> {
+    // glibc 的 waitid 不回傳 rusage，直接呼叫系統呼叫
+    let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+    let mut ru: libc::rusage = unsafe { std::mem::zeroed() };
+    loop {
+        let rc = unsafe {
+            libc::syscall(
+                libc::SYS_waitid,
+                libc::P_PID,
+                pid as libc::id_t,
+                &mut info as *mut libc::siginfo_t,
+                libc::WEXITED | libc::WNOWAIT | libc::WNOHANG,
+                &mut ru as *mut libc::rusage,
+            )
+        };
+        if rc == 0 {
+            break;
+        }
+        if std::io::Error::last_os_error().raw_os_error() != Some(libc::EINTR) {
+            return None;
+        }
+    }
+    // WNOHANG 且還沒結束時 si_pid 為 0
+    if unsafe { info.si_pid() } == 0 {
+        return None;
+    }
+    let ms = |tv: libc::timeval| tv.tv_sec as u64 * 1000 + tv.tv_usec as u64 / 1000;
+    Some(ResourceUsage {
+        cpu_user_ms: ms(ru.ru_utime),
+        cpu_system_ms: ms(ru.ru_stime),
+        // Linux 的 ru_maxrss 單位即為 KiB
+        max_rss_kb: ru.ru_maxrss as u64,
+    })
+}
+
+/// 程序是否仍在執行；已結束但還沒被回收的 zombie 不算
+#[cfg(unix)]
+pub fn process_alive(pid: u32) -> bool {
+    let rc = unsafe { libc::kill(pid as libc::pid_t, 0) };
+    let exists = rc == 0 || std::io::Error::last_os_error().raw_os_error() != Some(libc::ESRCH);
+    exists && !is_zombie(pid)
+}
+
+/// /proc/<pid>/stat 的狀態欄：命令名稱可能含空白與括號，取最後一個 ')' 之後的欄位
+#[cfg(target_os = "linux")]
+fn is_zombie(pid: u32) -> bool {
+    std::fs::read_to_string(format!("/proc/{pid}/stat")).is_ok_and(|stat| {
+        stat.rsplit_once(')')
+            .and_then(|(_, rest)| rest.split_whitespace().next())
+            .is_some_and(|state| state == "Z" || state == "X")
+    })
+}
+
+/// 沒有 /proc 的平台看不出 zombie，視為仍在執行
+#[cfg(all(unix, not(target_os = "linux")))]
+fn is_zombie(_pid: u32) -> bool {
+    false
+}
+
+#[cfg(not(unix))]
+pub fn process_alive(_pid: u32) -> bool {
+    true
+}
@@ -0,0 +1,133 @@
+use crate::{duration_to, local_now_fixed, State};
+use anyhow::{bail, Context, Result};
+use scheduler_core::{FreezeStatus, SchedulerError};
+use std::{
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+use tokio::{sync::Notify, time::sleep};
+
+/// 全域凍結的狀態；存檔讓部署途中重啟伺服器也不會提早解凍。
+/// 時間到不必改檔，讀取時視為已解除
+pub struct Freeze {
+    path: PathBuf,
+    status: Mutex<Option<FreezeStatus>>,
+    /// 凍結被取代或解除時喚醒等待中的單次任務
+    changed: Notify,
+}
+
+impl Freeze {
+    pub fn load(path: &Path) -> Result<Self> {
+        let status = if path.exists() {
+            let text = std::fs::read_to_string(path)
+                .with_context(|| format!("read {}", path.display()))?;
+            serde_json::from_str(&text).with_context(|| format!("parse {}", path.display()))?
+        } else {
+            None
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            status: Mutex::new(status),
+            changed: Notify::new(),
+        })
+    }
+
+    /// 目前生效中的凍結
+    pub fn current(&self) -> Option<FreezeStatus> {
+        let now = local_now_fixed();
+        self.status
+            .lock()
+            .unwrap()
+            .clone()
+            .filter(|f| f.until > now)
+    }
+
+    fn replace(&self, status: Option<FreezeStatus>) -> Result<()> {
+        let mut current = self.status.lock().unwrap();
+        self.save(&status)?;
+        *current = status;
+        self.changed.notify_waiters();
+        Ok(())
+    }
+
+    /// 先寫暫存檔再改名，避免改寫到一半留下殘缺的檔案
+    fn save(&self, status: &Option<FreezeStatus>) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let tmp = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec_pretty(status)?)
+            .with_context(|| format!("write {}", tmp.display()))?;
+        std::fs::rename(&tmp, &self.path)
+            .with_context(|| format!("replace {}", self.path.display()))
+    }
+}
+
+/// 略過時記在 Skipped 紀錄中的原因
+pub fn reason(f: &FreezeStatus) -> String {
+    match &f.reason {
+        Some(why) => format!("scheduler frozen until {} ({why})", f.until),
+        None => format!("scheduler frozen until {}", f.until),
+    }
+}
+
+/// Freeze：從現在起凍結 duration_secs 秒，取代進行中的凍結
+pub fn freeze(
+    state: &State,
+    duration_secs: u64,
+    block_triggered: bool,
+    reason: Option<String>,
+    actor: &str,
+) -> Result<FreezeStatus> {
+    let since = local_now_fixed();
+    let until = i64::try_from(duration_secs)
+        .ok()
+        .filter(|secs| *secs > 0)
+        .and_then(chrono::Duration::try_seconds)
+        .and_then(|d| since.checked_add_signed(d));
+    let Some(until) = until else {
+        bail!(SchedulerError::InvalidRequest {
+            msg: format!("invalid freeze duration {duration_secs}s"),
+        });
+    };
+    let status = FreezeStatus {
+        since,
+        until,
+        block_triggered,
+        reason,
+        actor: actor.to_string(),
+    };
+    state.freeze.replace(Some(status.clone()))?;
+    let scope = if block_triggered {
+        "all runs"
+    } else {
+        "time-based triggers"
+    };
+    println!("🧊 scheduler frozen until {until} ({scope}) by {actor}");
+    Ok(status)
+}
+
+/// Unfreeze：提前解除；本來就沒有凍結時回傳 false
+pub fn unfreeze(state: &State, actor: &str) -> Result<bool> {
+    let was_frozen = state.freeze.current().is_some();
+    state.freeze.replace(None)?;
+    if was_frozen {
+        println!("🌤️ scheduler unfrozen by {actor}");
+    }
+    Ok(was_frozen)
+}
+
+/// 等到凍結結束（時間到、被解除或改短）
+pub async fn thawed(state: &State) {
+    loop {
+        // 先登記再讀狀態，中間被解除也不會漏掉通知
+        let changed = state.freeze.changed.notified();
+        let Some(f) = state.freeze.current() else {
+            return;
+        };
+        tokio::select! {
+            _ = sleep(duration_to(f.until)) => {}
+            _ = changed => {}
+        }
+    }
+}
@@ -0,0 +1,311 @@
+use crate::{add_task, config::GitSyncConfig, quota, remove_task, update_task, validate, State};
+use anyhow::{bail, Context, Result};
+use scheduler_core::{ChangeKind, FieldChange, ManifestChange, ManifestTask, TaskSpec};
+use serde::Deserialize;
+use serde_json::Value;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+    process::Command,
+    sync::Arc,
+    time::Duration,
+};
+
+/// git-sync 管理的任務在 TaskSpec.source 中的前綴
+const SOURCE_PREFIX: &str = "git-sync:";
+
+/// 任務清單檔：每個任務以 key 識別，其餘欄位同 TaskSpec
+#[derive(Debug, Deserialize)]
+struct Manifest {
+    #[serde(default)]
+    tasks: Vec<ManifestTask>,
+}
+
+/// 一次同步要做的變更
+#[derive(Debug, Default)]
+pub struct Plan {
+    pub add: Vec<(String, TaskSpec)>,
+    /// (key, 任務 id, 新規格)；原地更新，保留任務編號
+    pub update: Vec<(String, u64, TaskSpec)>,
+    pub remove: Vec<(String, u64)>,
+}
+
+impl Plan {
+    pub fn is_empty(&self) -> bool {
+        self.add.is_empty() && self.update.is_empty() && self.remove.is_empty()
+    }
+}
+
+pub fn spawn(state: Arc<State>) {
+    let Some(cfg) = state.config.git_sync.clone() else {
+        return;
+    };
+    tokio::spawn(async move {
+        let mut applied: Option<String> = None; // 上次成功套用的 commit
+        loop {
+            match sync_once(&state, &cfg, applied.as_deref()).await {
+                Ok(Some(commit)) => applied = Some(commit),
+                Ok(None) => {}
+                Err(e) => eprintln!("❌ git-sync failed: {e:#}"),
+            }
+            tokio::time::sleep(Duration::from_secs(cfg.interval_secs.max(10))).await;
+        }
+    });
+}
+
+/// 拉取並套用；回傳新套用的 commit（沒有變更或 dry run 時為 None）
+async fn sync_once(
+    state: &Arc<State>,
+    cfg: &GitSyncConfig,
+    applied: Option<&str>,
+) -> Result<Option<String>> {
+    let c = cfg.clone();
+    let commit = tokio::task::spawn_blocking(move || pull(&c)).await??;
+    if applied == Some(commit.as_str()) {
+        return Ok(None);
+    }
+
+    let path = cfg.dir.join(&cfg.manifest);
+    let text =
+        std::fs::read_to_string(&path).with_context(|| format!("read {}", path.display()))?;
+    let manifest: Manifest =
+        toml::from_str(&text).with_context(|| format!("parse {}", path.display()))?;
+
+    let plan = diff(state, SOURCE_PREFIX, manifest.tasks)?;
+    let short = &commit[..commit.len().min(12)];
+    if plan.is_empty() {
+        println!("🔄 git-sync {short}: tasks already up to date");
+        return Ok(Some(commit));
+    }
+    print_plan(short, &plan, cfg.dry_run);
+    if cfg.dry_run {
+        return Ok(None);
+    }
+    let removals = plan.remove.len();
+    if removals > cfg.max_removals {
+        bail!(
+            "plan removes {removals} task(s), more than max_removals = {}; not applied",
+            cfg.max_removals
+        );
+    }
+
+    let actor = format!("git-sync:{short}");
+    for (key, id) in plan.remove {
+        remove_task(state, id).await?;
+        println!("🔄 git-sync removed task {} ({key})", id);
+    }
+    // 原地更新以保留任務編號與版本紀錄
+    for (key, id, spec) in plan.update {
+        if update_task(state, id, spec, &actor, None).await?.is_some() {
+            println!("🔄 git-sync updated {key}: task {id}");
+        }
+    }
+    for (key, spec) in plan.add {
+        let id = add_task(state, spec, &actor).await?;
+        println!("🔄 git-sync added task {} ({key})", id);
+    }
+    Ok(Some(commit))
+}
+
+/// clone 或 fetch 到最新，回傳 HEAD commit
+fn pull(cfg: &GitSyncConfig) -> Result<String> {
+    if cfg.dir.join(".git").exists() {
+        git(&cfg.dir, &["fetch", "--depth", "1", "origin", &cfg.branch])?;
+        git(&cfg.dir, &["reset", "--hard", "FETCH_HEAD"])?;
+    } else {
+        let dir = cfg.dir.to_string_lossy();
+        git(
+            Path::new("."),
+            &[
+                "clone",
+                "--depth",
+                "1",
+                "--branch",
+                &cfg.branch,
+                &cfg.repo,
+                &dir,
+            ],
+        )?;
+    }
+    Ok(git(&cfg.dir, &["rev-parse", "HEAD"])?.trim().to_string())
+}
+
+fn git(dir: &Path, args: &[&str]) -> Result<String> {
+    let out = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .context("run git")?;
+    if !out.status.success() {
+        bail!(
+            "git {} failed: {}",
+            args.first().unwrap_or(&""),
+            String::from_utf8_lossy(&out.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&out.stdout).into_owned())
+}
+
+/// DiffManifest：以與 git-sync 相同的比對規則算出變更並逐欄列出，不套用
+pub fn preview(state: &State, tasks: Vec<ManifestTask>) -> Result<Vec<ManifestChange>> {
+    let plan = diff(state, SOURCE_PREFIX, tasks)?;
+    let current = |id: u64| state.tasks.get(&id).map(|ent| ent.spec.clone());
+    let mut changes = Vec::new();
+    for (key, id) in plan.remove {
+        changes.push(ManifestChange {
+            key,
+            change: ChangeKind::Remove,
+            task_id: Some(id),
+            fields: field_changes(current(id).as_ref(), None),
+        });
+    }
+    for (key, id, spec) in plan.update {
+        changes.push(ManifestChange {
+            key,
+            change: ChangeKind::Update,
+            task_id: Some(id),
+            fields: field_changes(current(id).as_ref(), Some(&spec)),
+        });
+    }
+    for (key, spec) in plan.add {
+        changes.push(ManifestChange {
+            key,
+            change: ChangeKind::Add,
+            task_id: None,
+            fields: field_changes(None, Some(&spec)),
+        });
+    }
+    Ok(changes)
+}
+
+/// 比對清單與目前 source 以 prefix 開頭的任務（git-sync 與設定檔宣告的任務共用）；
+/// 清單中任何一個任務無效就整次不套用
+pub fn diff(state: &State, prefix: &str, tasks: Vec<ManifestTask>) -> Result<Plan> {
+    let mut wanted: BTreeMap<String, TaskSpec> = BTreeMap::new();
+    for t in tasks {
+        if t.key.trim().is_empty() {
+            bail!("manifest task key must not be empty");
+        }
+        validate::validate_spec(&state.config, &t.spec)
+            .with_context(|| format!("invalid task {:?}", t.key))?;
+        let mut spec = t.spec;
+        spec.source = Some(format!("{prefix}{}", t.key));
+        if wanted.insert(t.key.clone(), spec).is_some() {
+            bail!("duplicate task key {:?}", t.key);
+        }
+    }
+
+    let current: BTreeMap<String, (u64, TaskSpec)> = state
+        .tasks
+        .iter()
+        .filter_map(|kv| {
+            let key = kv.value().spec.source.as_deref()?.strip_prefix(prefix)?;
+            Some((key.to_string(), (*kv.key(), kv.value().spec.clone())))
+        })
+        .collect();
+
+    let mut plan = Plan::default();
+    let mut new_namespaces = BTreeSet::new();
+    for (key, spec) in wanted.iter() {
+        match current.get(key) {
+            None => {
+                new_namespaces.insert(spec.namespace.clone());
+                plan.add.push((key.clone(), spec.clone()));
+            }
+            Some((id, old)) if !same_spec(old, spec) => {
+                plan.update.push((key.clone(), *id, spec.clone()))
+            }
+            Some(_) => {}
+        }
+    }
+    for (key, (id, _)) in &current {
+        if !wanted.contains_key(key) {
+            plan.remove.push((key.clone(), *id));
+        }
+    }
+    for ns in new_namespaces {
+        quota::check_add(state, &ns)?;
+    }
+    Ok(plan)
+}
+
+/// TaskSpec 沒有 PartialEq；以序列化結果比較
+fn same_spec(a: &TaskSpec, b: &TaskSpec) -> bool {
+    serde_json::to_value(a).ok() == serde_json::to_value(b).ok()
+}
+
+/// 兩份規格逐欄的差異：JSON 物件逐層比較，其餘值（含陣列）整體比較。
+/// 只有一邊時略過空值，新增與移除只列出有設定的欄位
+fn field_changes(old: Option<&TaskSpec>, new: Option<&TaskSpec>) -> Vec<FieldChange> {
+    let value = |spec: Option<&TaskSpec>| spec.and_then(|s| serde_json::to_value(s).ok());
+    let mut out = Vec::new();
+    compare("", value(old).as_ref(), value(new).as_ref(), &mut out);
+    out
+}
+
+fn compare(path: &str, old: Option<&Value>, new: Option<&Value>, out: &mut Vec<FieldChange>) {
+    let (a, b) = (
+        old.and_then(Value::as_object),
+        new.and_then(Value::as_object),
+    );
+    let nested = (a.is_some() || old.is_none()) && (b.is_some() || new.is_none());
+    if nested && (a.is_some() || b.is_some()) {
+        let keys: BTreeSet<&String> = a.into_iter().chain(b).flat_map(|m| m.keys()).collect();
+        for key in keys {
+            let field = match path {
+                "" => key.clone(),
+                _ => format!("{path}.{key}"),
+            };
+            compare(
+                &field,
+                a.and_then(|m| m.get(key)),
+                b.and_then(|m| m.get(key)),
+                out,
+            );
+        }
+        return;
+    }
+    let empty = |v: &Value| match v {
+        Value::Null => true,
+        Value::Bool(b) => !b,
+        Value::String(s) => s.is_empty(),
+        Value::Array(a) => a.is_empty(),
+        Value::Object(o) => o.is_empty(),
+        Value::Number(_) => false,
+    };
+    let skip = match (old, new) {
+        (Some(a), Some(b)) => a == b,
+        (Some(v), None) | (None, Some(v)) => empty(v),
+        (None, None) => true,
+    };
+    if !skip {
+        out.push(FieldChange {
+            field: path.to_string(),
+            old: old.map(Value::to_string),
+            new: new.map(Value::to_string),
+        });
+    }
+}
+
+fn print_plan(commit: &str, plan: &Plan, dry_run: bool) {
+    let mode = if dry_run {
+        " (dry run, not applied)"
+    } else {
+        ""
+    };
+    println!(
+        "🔄 git-sync {commit}: +{} ~{} -{}{mode}",
+        plan.add.len(),
+        plan.update.len(),
+        plan.remove.len()
+    );
+    for (key, _) in &plan.add {
+        println!("   + {key}");
+    }
+    for (key, id, _) in &plan.update {
+        println!("   ~ {key} (task {id})");
+    }
+    for (key, id) in &plan.remove {
+        println!("   - {key} (task {id})");
+    }
+}
@@ -0,0 +1,106 @@
+use crate::State;
+use anyhow::{bail, Result};
+use scheduler_core::{EventKind, Healthcheck, RunOutcome};
+use std::{collections::HashMap, sync::Arc, time::Duration};
+use tokio::{sync::broadcast, task::JoinHandle};
+
+const PING_TIMEOUT: Duration = Duration::from_secs(10);
+const PING_ATTEMPTS: u32 = 3;
+
+/// 依執行事件呼叫任務的 healthcheck 網址；ping 失敗只記錄，不影響執行
+pub fn spawn(state: Arc<State>) {
+    let (_, _, mut rx) = state.events.subscribe(None);
+    tokio::spawn(async move {
+        // run → 開始的 ping；結束的 ping 要等它送完，監控端才不會看到順序顛倒
+        let mut starts: HashMap<u64, JoinHandle<()>> = HashMap::new();
+        loop {
+            let ev = match rx.recv().await {
+                Ok(ev) => ev,
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    eprintln!("healthcheck pinger skipped {n} event(s)");
+                    continue;
+                }
+                Err(broadcast::error::RecvError::Closed) => return,
+            };
+            match ev.kind {
+                EventKind::RunStarted {
+                    task_id, run_id, ..
+                } => {
+                    let Some(url) = config(&state, task_id).and_then(|h| h.start_url()) else {
+                        continue;
+                    };
+                    starts.insert(run_id, tokio::spawn(ping(task_id, url)));
+                }
+                EventKind::RunFinished {
+                    task_id,
+                    run_id,
+                    status_code,
+                    outcome,
+                    ..
+                } => {
+                    let start = starts.remove(&run_id);
+                    let Some(hc) = config(&state, task_id) else {
+                        continue;
+                    };
+                    let url = if outcome == RunOutcome::Exited && status_code == 0 {
+                        hc.success_url()
+                    } else {
+                        hc.failure_url()
+                    };
+                    let Some(url) = url else { continue };
+                    tokio::spawn(async move {
+                        if let Some(start) = start {
+                            let _ = start.await;
+                        }
+                        ping(task_id, url).await;
+                    });
+                }
+                _ => {}
+            }
+        }
+    });
+}
+
+/// 新增任務時檢查網址
+pub fn check(hc: &Healthcheck) -> Result<()> {
+    let urls = [&hc.url, &hc.start, &hc.success, &hc.failure];
+    if urls.iter().all(|u| u.is_none()) {
+        bail!("healthcheck needs a url or at least one ping url");
+    }
+    for url in urls.into_iter().flatten() {
+        if !(url.starts_with("http://") || url.starts_with("https://")) {
+            bail!("healthcheck url must be http(s): {url}");
+        }
+    }
+    Ok(())
+}
+
+fn config(state: &State, task_id: u64) -> Option<Healthcheck> {
+    state.tasks.get(&task_id)?.spec.healthcheck.clone()
+}
+
+async fn ping(task_id: u64, url: String) {
+    for attempt in 1..=PING_ATTEMPTS {
+        let u = url.clone();
+        let res = tokio::task::spawn_blocking(move || {
+            let agent = ureq::AgentBuilder::new().timeout(PING_TIMEOUT).build();
+            match agent.get(&u).set("User-Agent", "scheduler-server").call() {
+                Ok(_) => Ok(()),
+                Err(ureq::Error::Status(code, _)) => bail!("HTTP {code}"),
+                Err(e) => Err(e.into()),
+            }
+        })
+        .await;
+        match res {
+            Ok(Ok(())) => return,
+            Ok(Err(e)) if attempt == PING_ATTEMPTS => {
+                eprintln!("⚠️ task {} healthcheck ping {} failed: {e:#}", task_id, url);
+            }
+            Err(e) => {
+                eprintln!("⚠️ task {} healthcheck ping {} failed: {e}", task_id, url);
+                return;
+            }
+            Ok(Err(_)) => tokio::time::sleep(Duration::from_secs(u64::from(attempt))).await,
+        }
+    }
+}
@@ -0,0 +1,519 @@
+use crate::{config::HistoryConfig, State};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, FixedOffset};
+use scheduler_core::{RunRecord, RunStatus};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{File, Metadata},
+    io::{Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+/// 寫入中的分段（即 path 本身）的編號
+const ACTIVE: u32 = 0;
+
+/// 執行歷史：分段的 JSON Lines 只附加檔。寫入中的分段是 path，超過 segment_bytes 時
+/// 封存成 `<stem>.<n>.jsonl` 並寫下索引 `<stem>.<n>.idx`。
+/// 記憶體只保留索引（任務、run、結束時間、狀態與位置），查詢時依索引讀出需要的紀錄
+pub struct History {
+    path: PathBuf,
+    segment_bytes: u64,
+    index: Mutex<Index>,
+}
+
+#[derive(Default)]
+struct Index {
+    /// 依寫入順序（舊到新）
+    entries: Vec<Entry>,
+    /// 任務 → entries 中的位置（舊到新）
+    by_task: HashMap<u64, Vec<usize>>,
+    by_run: HashMap<u64, usize>,
+    /// 已收錄的（任務、run、結束時間）；整理到一半停機留下的副本依此略過
+    seen: HashSet<(u64, u64, DateTime<FixedOffset>)>,
+    /// 封存分段的編號（遞增）
+    sealed: Vec<u32>,
+    /// 寫入中的分段讀到哪裡與檔案識別；唯讀副本由此接著讀主伺服器新附加的紀錄
+    active_len: u64,
+    active_id: Option<u64>,
+    /// 寫入中的分段最後一行不完整（寫到一半停機）；下一筆先補換行
+    torn: bool,
+}
+
+/// 一筆紀錄的索引
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Entry {
+    task_id: u64,
+    run_id: u64,
+    #[serde(default)]
+    upstream_run: Option<u64>,
+    finished_at: DateTime<FixedOffset>,
+    status: RunStatus,
+    /// 所在的分段；索引檔對應一個分段，不必記
+    #[serde(skip)]
+    segment: u32,
+    offset: u64,
+    /// 不含換行
+    len: u32,
+}
+
+impl Entry {
+    fn of(rec: &RunRecord, segment: u32, offset: u64, len: usize) -> Self {
+        Self {
+            task_id: rec.task_id,
+            run_id: rec.result.run_id,
+            upstream_run: rec.result.upstream_run,
+            finished_at: rec.result.finished_at,
+            status: RunStatus::of(&rec.result),
+            segment,
+            offset,
+            len: len as u32,
+        }
+    }
+}
+
+impl Index {
+    /// 加入一筆；任務、run 編號與結束時間都相同的（整理到一半停機留下的副本）略過。
+    /// 只比 run 編號會把 run 編號重新配發後的不同紀錄也當成副本
+    fn push(&mut self, e: Entry) {
+        if !self.seen.insert((e.task_id, e.run_id, e.finished_at)) {
+            return;
+        }
+        let i = self.entries.len();
+        if e.run_id != 0 {
+            self.by_run.insert(e.run_id, i);
+        }
+        self.by_task.entry(e.task_id).or_default().push(i);
+        self.entries.push(e);
+    }
+
+    /// 某任務的紀錄，新到舊
+    fn for_task(&self, task_id: u64) -> impl Iterator<Item = &Entry> {
+        self.by_task
+            .get(&task_id)
+            .into_iter()
+            .flat_map(|list| list.iter().rev())
+            .map(|&i| &self.entries[i])
+    }
+}
+
+impl History {
+    /// 載入各分段的索引；壞掉的行略過並提示，不讓伺服器無法啟動
+    pub fn load(cfg: &HistoryConfig) -> Result<Self> {
+        let history = Self {
+            path: cfg.path.clone(),
+            segment_bytes: cfg.segment_bytes.max(1),
+            index: Mutex::new(Index::default()),
+        };
+        *history.index.lock().unwrap() = history.scan()?;
+        Ok(history)
+    }
+
+    /// 封存分段讀 .idx（沒有或與分段對不上時解析分段），寫入中的分段逐行解析
+    fn scan(&self) -> Result<Index> {
+        let mut index = Index::default();
+        for n in self.list_sealed()? {
+            for e in self.sealed_entries(n)? {
+                index.push(e);
+            }
+            index.sealed.push(n);
+        }
+        match std::fs::read(&self.path) {
+            Ok(buf) => {
+                let mut entries = Vec::new();
+                let read = index_lines(&buf, ACTIVE, 0, &self.path, &mut entries);
+                for e in entries {
+                    index.push(e);
+                }
+                index.active_len = read;
+                index.active_id = std::fs::metadata(&self.path).ok().and_then(|m| file_id(&m));
+                index.torn = read < buf.len() as u64;
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e).with_context(|| format!("read {}", self.path.display())),
+        }
+        Ok(index)
+    }
+
+    fn sealed_entries(&self, n: u32) -> Result<Vec<Entry>> {
+        let seg = self.sibling(n, "jsonl");
+        let len = std::fs::metadata(&seg)
+            .with_context(|| format!("stat {}", seg.display()))?
+            .len();
+        let idx = std::fs::read(self.sibling(n, "idx")).ok();
+        if let Some(mut entries) = idx.and_then(|b| serde_json::from_slice::<Vec<Entry>>(&b).ok()) {
+            // 索引要剛好涵蓋整個分段，否則視為過期
+            let covered = entries
+                .last()
+                .map_or(0, |e| e.offset + u64::from(e.len) + 1);
+            if covered == len {
+                for e in &mut entries {
+                    e.segment = n;
+                }
+                return Ok(entries);
+            }
+        }
+        let buf = std::fs::read(&seg).with_context(|| format!("read {}", seg.display()))?;
+        let mut entries = Vec::new();
+        index_lines(&buf, n, 0, &seg, &mut entries);
+        Ok(entries)
+    }
+
+    /// 唯讀副本：讀入主伺服器之後附加的紀錄（只讀完整的行）；
+    /// 分段被封存或整理過就整份重新載入索引。回傳讀入的筆數
+    pub fn refresh(&self) -> Result<usize> {
+        let mut index = self.index.lock().unwrap();
+        let meta = std::fs::metadata(&self.path).ok();
+        let replaced = match &meta {
+            Some(m) => m.len() < index.active_len || file_id(m) != index.active_id,
+            None => index.active_len > 0,
+        };
+        if replaced || self.list_sealed()? != index.sealed {
+            *index = self.scan()?;
+            return Ok(index.entries.len());
+        }
+        if meta.is_none_or(|m| m.len() == index.active_len) {
+            return Ok(0);
+        }
+
+        let mut f =
+            File::open(&self.path).with_context(|| format!("open {}", self.path.display()))?;
+        f.seek(SeekFrom::Start(index.active_len))?;
+        let mut buf = Vec::new();
+        f.read_to_end(&mut buf)?;
+        let mut entries = Vec::new();
+        index.active_len += index_lines(&buf, ACTIVE, index.active_len, &self.path, &mut entries);
+        let count = entries.len();
+        for e in entries {
+            index.push(e);
+        }
+        Ok(count)
+    }
+
+    /// 目前最大的 run 編號（用來接續配發）
+    pub fn max_run_id(&self) -> u64 {
+        let index = self.index.lock().unwrap();
+        index.entries.iter().map(|e| e.run_id).max().unwrap_or(0)
+    }
+
+    /// 新增一筆紀錄並附加到寫入中的分段；分段夠大時封存。
+    /// 會做檔案 I/O 並持有索引鎖，async 路徑請經由 [`blocking`] 呼叫
+    pub fn append(&self, rec: RunRecord) -> Result<()> {
+        let mut line = serde_json::to_vec(&rec)?;
+        line.push(b'\n');
+
+        let mut index = self.index.lock().unwrap();
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut f = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .with_context(|| format!("open {}", self.path.display()))?;
+        if index.torn {
+            f.write_all(b"\n")?;
+            index.torn = false;
+        }
+        let offset = f.metadata()?.len();
+        f.write_all(&line)?;
+        index.active_len = offset + line.len() as u64;
+        index.push(Entry::of(&rec, ACTIVE, offset, line.len() - 1));
+        if index.active_len >= self.segment_bytes {
+            if let Err(e) = self.seal(&mut index) {
+                eprintln!("history seal error: {e:#}");
+            }
+        }
+        Ok(())
+    }
+
+    /// 寫入中的分段改名為下一個封存分段，並寫下它的索引
+    fn seal(&self, index: &mut Index) -> Result<()> {
+        let n = index.sealed.last().map_or(1, |n| n + 1);
+        let seg = self.sibling(n, "jsonl");
+        std::fs::rename(&self.path, &seg)
+            .with_context(|| format!("rename {} to {}", self.path.display(), seg.display()))?;
+        index.sealed.push(n);
+        index.active_len = 0;
+        index.active_id = None;
+        for e in index.entries.iter_mut().filter(|e| e.segment == ACTIVE) {
+            e.segment = n;
+        }
+        let entries: Vec<&Entry> = index.entries.iter().filter(|e| e.segment == n).collect();
+        write_index(&self.sibling(n, "idx"), &entries)
+    }
+
+    /// 某任務最近的 `limit` 筆紀錄，新到舊
+    pub fn for_task(&self, task_id: u64, limit: usize) -> Vec<RunRecord> {
+        let index = self.index.lock().unwrap();
+        self.read_all(index.for_task(task_id).take(limit))
+    }
+
+    /// 依 run 編號找紀錄
+    pub fn find_run(&self, run_id: u64) -> Option<RunRecord> {
+        let index = self.index.lock().unwrap();
+        let e = &index.entries[*index.by_run.get(&run_id)?];
+        self.read_all([e]).pop()
+    }
+
+    /// 任務接續前置 run upstream_run 的最近一筆紀錄
+    pub fn linked(&self, task_id: u64, upstream_run: u64) -> Option<RunRecord> {
+        let index = self.index.lock().unwrap();
+        let e = index
+            .for_task(task_id)
+            .find(|e| e.upstream_run == Some(upstream_run))?;
+        self.read_all([e]).pop()
+    }
+
+    /// [since, until) 內結束、狀態符合的紀錄，新到舊
+    pub fn query(
+        &self,
+        since: Option<DateTime<FixedOffset>>,
+        until: Option<DateTime<FixedOffset>>,
+        status: Option<RunStatus>,
+    ) -> Vec<RunRecord> {
+        let index = self.index.lock().unwrap();
+        let matched = index
+            .entries
+            .iter()
+            .rev()
+            .filter(|e| since.is_none_or(|t| e.finished_at >= t))
+            .filter(|e| until.is_none_or(|t| e.finished_at < t))
+            .filter(|e| status.is_none_or(|s| e.status == s));
+        self.read_all(matched)
+    }
+
+    /// 所有紀錄（或某任務的），舊到新
+    pub fn snapshot(&self, task_id: Option<u64>) -> Vec<RunRecord> {
+        let index = self.index.lock().unwrap();
+        match task_id {
+            Some(id) => {
+                let mut list = self.read_all(index.for_task(id));
+                list.reverse();
+                list
+            }
+            None => self.read_all(&index.entries),
+        }
+    }
+
+    /// 依保留政策刪除紀錄，並把留下的紀錄重寫成新的封存分段（整理）；回傳刪除筆數。
+    /// 新分段寫好才刪除舊分段，中途停機留下的重複紀錄在載入時略過
+    pub fn prune(&self, cfg: &HistoryConfig, now: DateTime<FixedOffset>) -> Result<usize> {
+        let mut index = self.index.lock().unwrap();
+        let before = index.entries.len();
+
+        // 只看索引由新到舊決定去留，再還原成舊到新
+        let cutoff = cfg
+            .max_age_days
+            .and_then(|d| Duration::try_days(i64::try_from(d).ok()?))
+            .and_then(|d| now.checked_sub_signed(d));
+        let mut per_task: HashMap<u64, usize> = HashMap::new();
+        let mut kept: Vec<&Entry> = Vec::with_capacity(before);
+        for e in index.entries.iter().rev() {
+            if cutoff.is_some_and(|c| e.finished_at < c) {
+                continue;
+            }
+            let n = per_task.entry(e.task_id).or_default();
+            if cfg.max_per_task.is_some_and(|max| *n >= max) {
+                continue;
+            }
+            if cfg.max_total.is_some_and(|max| kept.len() >= max) {
+                break;
+            }
+            *n += 1;
+            kept.push(e);
+        }
+        kept.reverse();
+
+        let removed = before - kept.len();
+        if removed == 0 {
+            return Ok(0);
+        }
+
+        // 原樣複製留下的行，不重新序列化
+        let mut fresh = Index::default();
+        let mut next = index.sealed.last().map_or(1, |n| n + 1);
+        let mut buf = Vec::new();
+        let mut pending = Vec::new();
+        let mut open = None;
+        for (i, e) in kept.iter().enumerate() {
+            let line = self.read_raw(&mut open, e)?;
+            pending.push(Entry {
+                segment: next,
+                offset: buf.len() as u64,
+                ..(*e).clone()
+            });
+            buf.extend_from_slice(&line);
+            buf.push(b'\n');
+            if buf.len() as u64 >= self.segment_bytes || i + 1 == kept.len() {
+                self.write_segment(next, &buf, &pending)?;
+                fresh.sealed.push(next);
+                for e in pending.drain(..) {
+                    fresh.push(e);
+                }
+                buf.clear();
+                next += 1;
+            }
+        }
+        drop(open);
+
+        for n in &index.sealed {
+            remove_file(&self.sibling(*n, "jsonl"))?;
+            remove_file(&self.sibling(*n, "idx"))?;
+        }
+        remove_file(&self.path)?;
+        *index = fresh;
+        Ok(removed)
+    }
+
+    /// 先寫暫存檔再改名，避免改寫到一半留下殘缺的分段
+    fn write_segment(&self, n: u32, buf: &[u8], entries: &[Entry]) -> Result<()> {
+        let seg = self.sibling(n, "jsonl");
+        let tmp = seg.with_extension("jsonl.tmp");
+        std::fs::write(&tmp, buf).with_context(|| format!("write {}", tmp.display()))?;
+        std::fs::rename(&tmp, &seg).with_context(|| format!("replace {}", seg.display()))?;
+        write_index(&self.sibling(n, "idx"), &entries.iter().collect::<Vec<_>>())
+    }
+
+    /// 依索引讀出紀錄；讀不到（檔案被主伺服器整理掉等）的略過並提示
+    fn read_all<'a>(&self, entries: impl IntoIterator<Item = &'a Entry>) -> Vec<RunRecord> {
+        let mut open = None;
+        let mut list = Vec::new();
+        for e in entries {
+            let rec = self
+                .read_raw(&mut open, e)
+                .and_then(|line| Ok(serde_json::from_slice::<RunRecord>(&line)?));
+            match rec {
+                Ok(rec) => list.push(rec),
+                Err(err) => eprintln!("history run {} read error: {err:#}", e.run_id),
+            }
+        }
+        list
+    }
+
+    /// 讀出一行；連續讀同一個分段時沿用已開啟的檔案
+    fn read_raw(&self, open: &mut Option<(u32, File)>, e: &Entry) -> Result<Vec<u8>> {
+        let (_, f) = match open.take().filter(|(seg, _)| *seg == e.segment) {
+            Some(cached) => open.insert(cached),
+            None => {
+                let path = self.segment_path(e.segment);
+                let f = File::open(&path).with_context(|| format!("open {}", path.display()))?;
+                open.insert((e.segment, f))
+            }
+        };
+        f.seek(SeekFrom::Start(e.offset))?;
+        let mut line = vec![0; e.len as usize];
+        f.read_exact(&mut line)?;
+        Ok(line)
+    }
+
+    fn segment_path(&self, segment: u32) -> PathBuf {
+        if segment == ACTIVE {
+            self.path.clone()
+        } else {
+            self.sibling(segment, "jsonl")
+        }
+    }
+
+    /// 與歷史檔同目錄的 `<stem>.<n>.<ext>`
+    fn sibling(&self, segment: u32, ext: &str) -> PathBuf {
+        let stem = self.path.file_stem().unwrap_or_default().to_string_lossy();
+        self.path
+            .with_file_name(format!("{stem}.{segment:06}.{ext}"))
+    }
+
+    /// 目錄中既有的封存分段，依編號排序
+    fn list_sealed(&self) -> Result<Vec<u32>> {
+        let dir = match self.path.parent() {
+            Some(p) if !p.as_os_str().is_empty() => p,
+            _ => Path::new("."),
+        };
+        let stem = self.path.file_stem().unwrap_or_default().to_string_lossy();
+        let prefix = format!("{stem}.");
+        let entries = match std::fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e).with_context(|| format!("list {}", dir.display())),
+        };
+        let mut list: Vec<u32> = entries
+            .filter_map(|entry| {
+                let name = entry.ok()?.file_name().into_string().ok()?;
+                let n = name.strip_prefix(&prefix)?.strip_suffix(".jsonl")?;
+                if n.is_empty() || !n.bytes().all(|b| b.is_ascii_digit()) {
+                    return None;
+                }
+                n.parse().ok().filter(|n| *n != ACTIVE)
+            })
+            .collect();
+        list.sort_unstable();
+        Ok(list)
+    }
+}
+
+/// 在 blocking 執行緒上讀寫歷史：分段的檔案 I/O 與等待索引鎖都不佔用 async 執行緒
+pub async fn blocking<T, F>(state: &Arc<State>, f: F) -> Result<T>
+where
+    F: FnOnce(&State) -> T + Send + 'static,
+    T: Send + 'static,
+{
+    let st = state.clone();
+    Ok(tokio::task::spawn_blocking(move || f(&st)).await?)
+}
+
+/// 新增一筆紀錄（在 blocking 執行緒上寫入）；失敗只提示
+pub async fn record(state: &Arc<State>, rec: RunRecord) {
+    let task_id = rec.task_id;
+    let res = blocking(state, move |st| st.history.append(rec)).await;
+    if let Err(e) = res.and_then(|r| r) {
+        eprintln!("task {} record history error: {e:?}", task_id);
+    }
+}
+
+/// 為 buf 中完整的行建立索引（offset 為 buf 在分段中的起點）；回傳涵蓋的位元組數
+fn index_lines(buf: &[u8], segment: u32, offset: u64, file: &Path, out: &mut Vec<Entry>) -> u64 {
+    let Some(end) = buf.iter().rposition(|&b| b == b'\n') else {
+        return 0;
+    };
+    let mut pos = 0;
+    for line in buf[..end].split(|&b| b == b'\n') {
+        let start = offset + pos as u64;
+        pos += line.len() + 1;
+        if line.trim_ascii().is_empty() {
+            continue;
+        }
+        match serde_json::from_slice::<RunRecord>(line) {
+            Ok(rec) => out.push(Entry::of(&rec, segment, start, line.len())),
+            Err(e) => eprintln!("history {} (byte {start}): skipped: {e}", file.display()),
+        }
+    }
+    end as u64 + 1
+}
+
+fn write_index(path: &Path, entries: &[&Entry]) -> Result<()> {
+    let tmp = path.with_extension("idx.tmp");
+    std::fs::write(&tmp, serde_json::to_vec(entries)?)
+        .with_context(|| format!("write {}", tmp.display()))?;
+    std::fs::rename(&tmp, path).with_context(|| format!("replace {}", path.display()))
+}
+
+fn remove_file(path: &Path) -> Result<()> {
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            Err(e).with_context(|| format!("remove {}", path.display()))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// 判斷檔案是否被換掉（封存是改名，inode 會不同）
+#[cfg(unix)]
+fn file_id(meta: &Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Some(meta.ino())
+}
+
+#[cfg(not(unix))]
+fn file_id(_meta: &Metadata) -> Option<u64> {
+    None
+}
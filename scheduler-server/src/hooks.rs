@@ -0,0 +1,460 @@
+use crate::{
+    access::Peer, config::TriggerHooksConfig, enter_queue, is_system_task, local_now_fixed,
+    metadata, run_with_metadata, template, throttle, trigger_rejected, verify::sha256_hex, State,
+};
+use anyhow::{bail, Context, Result};
+use scheduler_core::{SchedulerError, ServerResponse, TriggerHookInfo};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    time::timeout,
+};
+
+/// 讀完一個請求（標頭加本文）的時限，避免慢速連線佔住資源
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+/// 請求列加標頭的大小上限
+const MAX_HEAD_BYTES: usize = 16 * 1024;
+
+/// 存檔的一筆觸發網址；token 只存 SHA-256，檔案外流也無法拿來觸發
+#[derive(Clone, Serialize, Deserialize)]
+struct Stored {
+    #[serde(flatten)]
+    info: TriggerHookInfo,
+    token_sha256: String,
+}
+
+/// 存檔內容：編號只增不減，撤銷後不會把同一個編號配給新的網址
+#[derive(Default, Serialize, Deserialize)]
+struct Saved {
+    next_id: u64,
+    hooks: Vec<Stored>,
+}
+
+/// 已建立的觸發網址；項目不多，每次變更整份改寫
+pub struct TriggerHooks {
+    path: PathBuf,
+    items: Mutex<Saved>,
+}
+
+impl TriggerHooks {
+    pub fn load(path: &Path) -> Result<Self> {
+        let mut items: Saved = if path.exists() {
+            let text = std::fs::read_to_string(path)
+                .with_context(|| format!("read {}", path.display()))?;
+            serde_json::from_str(&text).with_context(|| format!("parse {}", path.display()))?
+        } else {
+            Saved::default()
+        };
+        let max_id = items.hooks.iter().map(|s| s.info.id).max().unwrap_or(0);
+        items.next_id = items.next_id.max(max_id + 1);
+        Ok(Self {
+            path: path.to_path_buf(),
+            items: Mutex::new(items),
+        })
+    }
+
+    pub fn list(&self) -> Vec<TriggerHookInfo> {
+        let items = self.items.lock().unwrap();
+        items.hooks.iter().map(|s| s.info.clone()).collect()
+    }
+
+    /// 驗證 token；通過時記下使用時間並回傳該網址
+    fn authorize(&self, id: u64, token: &str) -> Result<Option<TriggerHookInfo>> {
+        let mut items = self.items.lock().unwrap();
+        let Some(stored) = items.hooks.iter_mut().find(|s| s.info.id == id) else {
+            return Ok(None);
+        };
+        if stored.token_sha256 != sha256_hex(token.as_bytes()) {
+            return Ok(None);
+        }
+        stored.info.last_used = Some(local_now_fixed());
+        let info = stored.info.clone();
+        self.save(&items)?;
+        Ok(Some(info))
+    }
+
+    /// 先寫暫存檔再改名，避免改寫到一半留下殘缺的檔案
+    fn save(&self, items: &Saved) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let tmp = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec_pretty(items)?)
+            .with_context(|| format!("write {}", tmp.display()))?;
+        std::fs::rename(&tmp, &self.path)
+            .with_context(|| format!("replace {}", self.path.display()))
+    }
+}
+
+/// 不能當參數的環境變數：會改變命令的找法、動態連結或 shell 啟動行為，
+/// 等於讓持有 token 的外部系統決定執行什麼
+const RESERVED_PARAMS: &[&str] = &["PATH", "HOME", "SHELL", "IFS", "ENV", "BASH_ENV", "CDPATH"];
+const RESERVED_PREFIXES: &[&str] = &["LD_", "DYLD_"];
+
+/// CreateTriggerHook：檢查後建立，回傳網址與只出現這一次的 token。
+/// 這個環境沒有開啟觸發網址的端點（[trigger_hooks]，只屬於預設環境）時拒絕，
+/// 免得發出一個永遠無法使用的 token
+pub fn create(
+    state: &State,
+    task_id: u64,
+    label: Option<String>,
+    params: Vec<String>,
+    actor: &str,
+) -> Result<(TriggerHookInfo, String)> {
+    if state.config.trigger_hooks.is_none() {
+        bail!(SchedulerError::InvalidRequest {
+            msg: "trigger hooks are not enabled for this environment (configure [trigger_hooks])"
+                .to_string(),
+        });
+    }
+    let Some(task_env) = state.tasks.get(&task_id).map(|t| t.spec.env.clone()) else {
+        bail!(SchedulerError::NotFound {
+            msg: format!("task {task_id} not found"),
+        });
+    };
+    if is_system_task(state, task_id) {
+        bail!(SchedulerError::Unauthorized {
+            msg: format!("task {task_id} is a built-in task and cannot be triggered externally"),
+        });
+    }
+    if let Some(bad) = params.iter().find(|p| !is_param_name(p)) {
+        bail!(SchedulerError::InvalidRequest {
+            msg: format!("invalid parameter name {bad:?} (use A-Z, 0-9 and '_')"),
+        });
+    }
+    if let Some(bad) = params.iter().find(|p| is_reserved(p)) {
+        bail!(SchedulerError::Unauthorized {
+            msg: format!("{bad} cannot be a trigger hook parameter"),
+        });
+    }
+    if let Some(bad) = params.iter().find(|p| task_env.contains_key(*p)) {
+        bail!(SchedulerError::InvalidRequest {
+            msg: format!("{bad} is already set by the env of task {task_id}"),
+        });
+    }
+    let mut bytes = [0u8; 32];
+    getrandom::getrandom(&mut bytes).context("generate token")?;
+    let token = hex::encode(bytes);
+
+    let mut items = state.trigger_hooks.items.lock().unwrap();
+    let id = items.next_id;
+    let info = TriggerHookInfo {
+        id,
+        task_id,
+        label,
+        params,
+        actor: actor.to_string(),
+        created_at: local_now_fixed(),
+        last_used: None,
+    };
+    items.next_id += 1;
+    items.hooks.push(Stored {
+        info: info.clone(),
+        token_sha256: sha256_hex(token.as_bytes()),
+    });
+    state.trigger_hooks.save(&items)?;
+    println!("🪝 trigger hook {id} for task {task_id} created by {actor}");
+    Ok((info, token))
+}
+
+/// RevokeTriggerHook：不存在時回傳 false
+pub fn revoke(state: &State, id: u64, actor: &str) -> Result<bool> {
+    let mut items = state.trigger_hooks.items.lock().unwrap();
+    let Some(pos) = items.hooks.iter().position(|s| s.info.id == id) else {
+        return Ok(false);
+    };
+    items.hooks.remove(pos);
+    state.trigger_hooks.save(&items)?;
+    println!("🪝 trigger hook {id} revoked by {actor}");
+    Ok(true)
+}
+
+/// 參數會成為環境變數：大寫英文、數字與底線，不以數字開頭
+fn is_param_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_uppercase() || c == '_')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+}
+
+fn is_reserved(name: &str) -> bool {
+    RESERVED_PARAMS.contains(&name) || RESERVED_PREFIXES.iter().any(|p| name.starts_with(p))
+}
+
+/// 啟動觸發網址的 HTTP 端點並回傳實際位址；未設定 [trigger_hooks] 則不做事
+pub async fn spawn(state: Arc<State>) -> Result<Option<SocketAddr>> {
+    let Some(cfg) = state.config.trigger_hooks.clone() else {
+        return Ok(None);
+    };
+    let listener = TcpListener::bind(&cfg.bind).await?;
+    let addr = listener.local_addr()?;
+    println!("🪝 trigger hooks listening on {addr}");
+
+    tokio::spawn(async move {
+        loop {
+            let (stream, peer) = match listener.accept().await {
+                Ok(acc) => acc,
+                Err(e) => {
+                    eprintln!("trigger hook accept error: {e}");
+                    continue;
+                }
+            };
+            let (st, cfg) = (state.clone(), cfg.clone());
+            tokio::spawn(async move {
+                if let Err(e) = handle(st, &cfg, stream, peer).await {
+                    eprintln!("trigger hook {peer} error: {e:#}");
+                }
+            });
+        }
+    });
+    Ok(Some(addr))
+}
+
+/// 一條連線只處理一個請求，回覆後關閉
+async fn handle(
+    state: Arc<State>,
+    cfg: &TriggerHooksConfig,
+    mut stream: TcpStream,
+    peer: SocketAddr,
+) -> Result<()> {
+    let (status, body) = match timeout(READ_TIMEOUT, read_request(&mut stream, cfg)).await {
+        Ok(Ok(req)) => dispatch(&state, req, peer),
+        Ok(Err(reject)) => reject,
+        Err(_) => (408, error_body("request timed out")),
+    };
+    let reason = match status {
+        202 => "Accepted",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        408 => "Request Timeout",
+        413 => "Payload Too Large",
+        429 => "Too Many Requests",
+        _ => "Internal Server Error",
+    };
+    let head = format!(
+        "HTTP/1.1 {status} {reason}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        body.len()
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(body.as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+struct HookRequest {
+    method: String,
+    path: String,
+    token: Option<String>,
+    body: Vec<u8>,
+}
+
+type Reply = (u16, String);
+type Params = Vec<(String, String)>;
+type Metadata = BTreeMap<String, String>;
+
+fn error_body(msg: &str) -> String {
+    serde_json::json!({ "error": msg }).to_string()
+}
+
+/// 讀取請求列、標頭與 Content-Length 指定的本文
+async fn read_request(
+    stream: &mut TcpStream,
+    cfg: &TriggerHooksConfig,
+) -> Result<HookRequest, Reply> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 4096];
+    let head_end = loop {
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos;
+        }
+        if buf.len() > MAX_HEAD_BYTES {
+            return Err((413, error_body("request headers too large")));
+        }
+        match stream.read(&mut chunk).await {
+            Ok(0) | Err(_) => return Err((400, error_body("incomplete request"))),
+            Ok(n) => buf.extend_from_slice(&chunk[..n]),
+        }
+    };
+    let head = String::from_utf8_lossy(&buf[..head_end]).into_owned();
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next().unwrap_or_default().split(' ');
+    let (method, path) = match (request_line.next(), request_line.next()) {
+        (Some(m), Some(p)) => (m.to_string(), p.to_string()),
+        _ => return Err((400, error_body("malformed request line"))),
+    };
+    let mut token = None;
+    let mut length = 0;
+    for line in lines {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        if name.eq_ignore_ascii_case("authorization") {
+            token = value.strip_prefix("Bearer ").map(|t| t.trim().to_string());
+        } else if name.eq_ignore_ascii_case("content-length") {
+            length = value
+                .parse()
+                .map_err(|_| (400, error_body("invalid Content-Length")))?;
+        }
+    }
+    if length > cfg.max_body_bytes {
+        return Err((413, error_body("request body too large")));
+    }
+    let mut body = buf.split_off(head_end + 4);
+    while body.len() < length {
+        match stream.read(&mut chunk).await {
+            Ok(0) | Err(_) => return Err((400, error_body("incomplete request body"))),
+            Ok(n) => body.extend_from_slice(&chunk[..n]),
+        }
+    }
+    body.truncate(length);
+    Ok(HookRequest {
+        method,
+        path,
+        token,
+        body,
+    })
+}
+
+/// 驗證後觸發任務；存取紀錄中記為來源是 hook 的 RunNow
+fn dispatch(state: &Arc<State>, req: HookRequest, peer: SocketAddr) -> Reply {
+    let Some(id) = req
+        .path
+        .strip_prefix("/hooks/")
+        .and_then(|rest| rest.parse::<u64>().ok())
+    else {
+        return (404, error_body("not found"));
+    };
+    if req.method != "POST" {
+        return (405, error_body("use POST"));
+    }
+    let started = Instant::now();
+    let conn = state.access.connect(Peer {
+        transport: "hook",
+        addr: peer.to_string(),
+        identity: Some(format!("hook:{id}")),
+    });
+    let (status, resp) = trigger(state, id, req);
+    let (task, body) = match &resp {
+        ServerResponse::Triggered { id: task_id } => (
+            Some(*task_id),
+            serde_json::json!({ "task_id": task_id, "hook": id }).to_string(),
+        ),
+        ServerResponse::Error(e) => (None, error_body(&e.to_string())),
+        _ => (None, String::new()),
+    };
+    conn.request("RunNow", task, started, &resp);
+    (status, body)
+}
+
+fn trigger(state: &Arc<State>, id: u64, req: HookRequest) -> (u16, ServerResponse) {
+    let reject = |status: u16, e: SchedulerError| (status, ServerResponse::Error(e));
+    // 不存在與 token 錯誤回覆相同，不透露哪些編號有效
+    let hook = match req
+        .token
+        .as_deref()
+        .map(|token| state.trigger_hooks.authorize(id, token))
+    {
+        Some(Ok(Some(hook))) => hook,
+        Some(Err(e)) => {
+            return reject(
+                500,
+                SchedulerError::Internal {
+                    msg: format!("{e:#}"),
+                },
+            )
+        }
+        _ => {
+            return reject(
+                401,
+                SchedulerError::Unauthorized {
+                    msg: format!("invalid or missing token for hook {id}"),
+                },
+            )
+        }
+    };
+    let (params, metadata) = match parse_body(&req.body, &hook.params) {
+        Ok(parsed) => parsed,
+        Err(msg) => return reject(400, SchedulerError::InvalidRequest { msg }),
+    };
+    let task_id = hook.task_id;
+    let Some(mut spec) = state.tasks.get(&task_id).map(|t| t.spec.clone()) else {
+        return reject(
+            404,
+            SchedulerError::NotFound {
+                msg: format!("task {task_id} not found"),
+            },
+        );
+    };
+    // 參數只影響這一次執行，不改動任務本身；建立之後任務的 env 才加上的同名變數以任務為準
+    for (name, value) in params {
+        spec.env.entry(name).or_insert(value);
+    }
+    if let Err(e) = throttle::check_trigger(state, task_id, &spec) {
+        return reject(429, trigger_rejected(e));
+    }
+    let slot = match enter_queue(state, task_id) {
+        Ok(slot) => slot,
+        Err(e) => return reject(429, trigger_rejected(e)),
+    };
+    println!("▶️ task {task_id} run now by hook {id}");
+    let st = state.clone();
+    tokio::spawn(async move {
+        if let Err(e) = run_with_metadata(task_id, spec, st, metadata, Some(slot), None).await {
+            eprintln!("task {} run error: {e:?}", task_id);
+        }
+    });
+    (202, ServerResponse::Triggered { id: task_id })
+}
+
+/// 本文可為空，或是 JSON 物件：網址允許的參數（值為字串、數字、布林，不能含 `{{`），
+/// 以及記在 run 上的追查資訊 `"metadata": {...}`（參數名稱一律大寫，不會撞名）
+fn parse_body(body: &[u8], allowed: &[String]) -> Result<(Params, Metadata), String> {
+    if body.iter().all(u8::is_ascii_whitespace) {
+        return Ok(Default::default());
+    }
+    let value: serde_json::Value =
+        serde_json::from_slice(body).map_err(|e| format!("invalid JSON body: {e}"))?;
+    let serde_json::Value::Object(map) = value else {
+        return Err("body must be a JSON object".to_string());
+    };
+    let mut params = Vec::new();
+    let mut run_metadata = Metadata::new();
+    for (name, value) in map {
+        if name == "metadata" {
+            run_metadata = metadata::from_json(value).map_err(|e| e.to_string())?;
+            continue;
+        }
+        if !allowed.contains(&name) {
+            return Err(format!("parameter {name:?} is not allowed by this hook"));
+        }
+        let value = match value {
+            serde_json::Value::String(s) => s,
+            serde_json::Value::Number(n) => n.to_string(),
+            serde_json::Value::Bool(b) => b.to_string(),
+            _ => {
+                return Err(format!(
+                    "parameter {name:?} must be a string, number or bool"
+                ))
+            }
+        };
+        // 參數會放進任務 env，之後才代入模板：不能讓外部送來的值展開伺服器變數
+        if template::is_templated(&value) {
+            return Err(format!(
+                "parameter {name:?} must not contain template syntax \"{{{{\""
+            ));
+        }
+        params.push((name, value));
+    }
+    Ok((params, run_metadata))
+}
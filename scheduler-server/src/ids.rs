@@ -0,0 +1,115 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+/// 每次預留多少個 id 才寫一次檔；異常停機後最多跳過一個區塊，不會重用
+const BLOCK: u64 = 64;
+
+/// 任務 id 與 run 編號的配發器。
+/// 檔案只記錄已預留到哪裡（高水位），重啟後從高水位接續，
+/// 所以即使歷史被清掉、回收桶過期，舊的 id 也不會再配發出去
+pub struct IdAllocator {
+    path: PathBuf,
+    inner: Mutex<Counters>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+struct Counters {
+    /// 下一個要配發的任務 id
+    next_task: u64,
+    /// 下一個要配發的 run 編號
+    next_run: u64,
+    /// 已寫入檔案的預留上限（不含）
+    #[serde(default)]
+    reserved_task: u64,
+    #[serde(default)]
+    reserved_run: u64,
+}
+
+impl IdAllocator {
+    /// 載入高水位檔；不存在時從 1 開始
+    pub fn load(path: &Path) -> Result<Self> {
+        let mut inner = if path.exists() {
+            let text = std::fs::read_to_string(path)
+                .with_context(|| format!("read {}", path.display()))?;
+            let saved: Counters =
+                serde_json::from_str(&text).with_context(|| format!("parse {}", path.display()))?;
+            // 上次預留但沒用完的直接跳過
+            Counters {
+                next_task: saved.next_task.max(saved.reserved_task),
+                next_run: saved.next_run.max(saved.reserved_run),
+                ..saved
+            }
+        } else {
+            Counters::default()
+        };
+        inner.next_task = inner.next_task.max(1);
+        inner.next_run = inner.next_run.max(1);
+        Ok(Self {
+            path: path.to_path_buf(),
+            inner: Mutex::new(inner),
+        })
+    }
+
+    /// 配發新的任務 id；預留寫不進檔案時不配發
+    pub fn next_task(&self) -> Result<u64> {
+        let mut inner = self.inner.lock().unwrap();
+        let id = inner.next_task;
+        if id >= inner.reserved_task {
+            let saved = Counters {
+                reserved_task: id + BLOCK,
+                ..inner.clone()
+            };
+            self.save(&saved)?;
+            inner.reserved_task = saved.reserved_task;
+        }
+        inner.next_task += 1;
+        Ok(id)
+    }
+
+    /// 配發新的 run 編號。寫檔失敗不擋執行，只提示；下一次配發會再試著寫
+    pub fn next_run(&self) -> u64 {
+        let mut inner = self.inner.lock().unwrap();
+        let id = inner.next_run;
+        inner.next_run += 1;
+        if id >= inner.reserved_run {
+            let reserved = id + BLOCK;
+            let saved = Counters {
+                reserved_run: reserved,
+                ..inner.clone()
+            };
+            match self.save(&saved) {
+                Ok(()) => inner.reserved_run = reserved,
+                Err(e) => eprintln!("⚠️ run id reservation not saved: {e:#}"),
+            }
+        }
+        id
+    }
+
+    /// 已在使用中的任務 id（載入、還原、匯入時）；之後只配發比它大的
+    pub fn observe_task(&self, id: u64) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.next_task = inner.next_task.max(id.saturating_add(1));
+    }
+
+    /// 已在使用中的 run 編號；之後只配發比它大的
+    pub fn observe_run(&self, run_id: u64) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.next_run = inner.next_run.max(run_id.saturating_add(1));
+    }
+
+    fn save(&self, inner: &Counters) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let tmp = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec_pretty(inner)?)
+            .with_context(|| format!("write {}", tmp.display()))?;
+        std::fs::rename(&tmp, &self.path)
+            .with_context(|| format!("rename {}", self.path.display()))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,127 @@
+use crate::{access::Peer, handle_conn, State};
+use anyhow::{bail, Context, Result};
+use std::{fmt, net::SocketAddr, path::PathBuf, sync::Arc};
+use tokio::net::TcpListener;
+#[cfg(unix)]
+use tokio::net::UnixListener;
+
+/// 一個監聽中的位址；所有監聽位址共用同一份 State
+pub enum Listener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(UnixListener, PathBuf),
+}
+
+impl Listener {
+    /// "unix:<路徑>" 為 Unix socket，其餘視為 TCP（IPv6 寫成 "[::1]:7878"）
+    pub async fn bind(addr: &str) -> Result<Self> {
+        let Some(path) = addr.strip_prefix("unix:") else {
+            let listener = TcpListener::bind(addr)
+                .await
+                .with_context(|| format!("bind {addr}"))?;
+            return Ok(Self::Tcp(listener));
+        };
+        bind_unix(PathBuf::from(path))
+    }
+
+    /// TCP 的實際位址（bind 到 :0 時由系統配發埠號）
+    pub fn tcp_addr(&self) -> Option<SocketAddr> {
+        match self {
+            Self::Tcp(l) => l.local_addr().ok(),
+            #[cfg(unix)]
+            Self::Unix(..) => None,
+        }
+    }
+
+    /// 關機時要刪除的 socket 檔
+    pub fn unix_path(&self) -> Option<PathBuf> {
+        match self {
+            Self::Tcp(_) => None,
+            #[cfg(unix)]
+            Self::Unix(_, path) => Some(path.clone()),
+        }
+    }
+}
+
+impl fmt::Display for Listener {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Tcp(l) => match l.local_addr() {
+                Ok(addr) => write!(f, "{addr}"),
+                Err(_) => f.write_str("tcp"),
+            },
+            #[cfg(unix)]
+            Self::Unix(_, path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn bind_unix(path: PathBuf) -> Result<Listener> {
+    use std::os::unix::fs::FileTypeExt;
+    // 上次沒有正常關機留下的 socket 檔：沒人在聽才刪，避免搶走另一個執行中的伺服器
+    if let Ok(meta) = std::fs::symlink_metadata(&path) {
+        if !meta.file_type().is_socket() {
+            bail!("{} exists and is not a socket", path.display());
+        }
+        if std::os::unix::net::UnixStream::connect(&path).is_ok() {
+            bail!("{} is already in use", path.display());
+        }
+        std::fs::remove_file(&path)?;
+    }
+    let listener =
+        UnixListener::bind(&path).with_context(|| format!("bind unix:{}", path.display()))?;
+    Ok(Listener::Unix(listener, path))
+}
+
+#[cfg(not(unix))]
+fn bind_unix(path: PathBuf) -> Result<Listener> {
+    bail!("unix:{} is not supported on this platform", path.display())
+}
+
+/// 在背景接受連線；單次 accept 失敗（例如 fd 用盡）只記錄，不停止監聽
+pub fn spawn(state: Arc<State>, listener: Listener) {
+    tokio::spawn(async move {
+        loop {
+            match &listener {
+                Listener::Tcp(l) => match l.accept().await {
+                    Ok((stream, addr)) => {
+                        let peer = Peer {
+                            transport: "tcp",
+                            addr: addr.to_string(),
+                            identity: None,
+                        };
+                        serve(state.clone(), stream, peer)
+                    }
+                    Err(e) => eprintln!("accept error on {listener}: {e}"),
+                },
+                #[cfg(unix)]
+                Listener::Unix(l, _) => match l.accept().await {
+                    Ok((stream, _)) => {
+                        // 同一台機器上的呼叫者以 uid 辨識
+                        let identity = stream.peer_cred().ok().map(|c| format!("uid:{}", c.uid()));
+                        let peer = Peer {
+                            transport: "unix",
+                            addr: listener.to_string(),
+                            identity,
+                        };
+                        serve(state.clone(), stream, peer)
+                    }
+                    Err(e) => eprintln!("accept error on {listener}: {e}"),
+                },
+            }
+        }
+    });
+}
+
+fn serve<S>(state: Arc<State>, stream: S, peer: Peer)
+where
+    S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static,
+{
+    let label = peer.addr.clone();
+    tokio::spawn(async move {
+        if let Err(e) = handle_conn(state, stream, peer).await {
+            eprintln!("connection {label} error: {e:?}");
+        }
+    });
+}
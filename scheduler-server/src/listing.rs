@@ -0,0 +1,178 @@
+use crate::{chain, local_now_fixed, stagger, State, TaskEntry};
+use anyhow::{Context, Result};
+use chrono::{DateTime, FixedOffset, Local};
+use regex::{Regex, RegexBuilder};
+use scheduler_core::{
+    RunOutcome, RunReport, RunResult, RunStatus, Schedule, TaskInfo, TaskRunTally, TaskSort,
+    TaskSpec,
+};
+use std::{cmp::Ordering, collections::BTreeMap};
+
+/// 組出任務清單並依指定欄位排序
+pub fn list_tasks(state: &State, sort: TaskSort, descending: bool) -> Vec<TaskInfo> {
+    let mut list: Vec<TaskInfo> = state
+        .tasks
+        .iter()
+        .map(|kv| task_info(state, *kv.key(), kv.value()))
+        .collect();
+
+    list.sort_by(|a, b| {
+        let ord = match sort {
+            TaskSort::Id => Ordering::Equal,
+            TaskSort::Name => {
+                cmp_some_first(a.spec.name.as_ref(), b.spec.name.as_ref(), descending)
+            }
+            TaskSort::NextRun => cmp_some_first(a.next_run, b.next_run, descending),
+            TaskSort::LastRun => cmp_some_first(
+                a.last_result.as_ref().map(|r| r.finished_at),
+                b.last_result.as_ref().map(|r| r.finished_at),
+                descending,
+            ),
+            TaskSort::LastStatus => cmp_some_first(
+                a.last_result.as_ref().map(status_key),
+                b.last_result.as_ref().map(status_key),
+                descending,
+            ),
+        };
+        // 同值時以 id 決定順序，輸出才穩定
+        let by_id = if descending && sort == TaskSort::Id {
+            b.id.cmp(&a.id)
+        } else {
+            a.id.cmp(&b.id)
+        };
+        ord.then(by_id)
+    });
+    list
+}
+
+/// 只組出指定任務的資訊（已不存在的略過），依 id 排序
+pub fn task_infos(state: &State, ids: &[u64]) -> Vec<TaskInfo> {
+    let mut list: Vec<TaskInfo> = ids
+        .iter()
+        .filter_map(|id| state.tasks.get(id).map(|ent| task_info(state, *id, &ent)))
+        .collect();
+    list.sort_by_key(|t| t.id);
+    list
+}
+
+fn task_info(state: &State, id: u64, ent: &TaskEntry) -> TaskInfo {
+    let last = ent.last_result.lock().unwrap().clone(); // 同步鎖，無 await
+    let chained = chain::due_times(state, id);
+    TaskInfo {
+        id,
+        next_run: next_run(
+            &state.schedules.effective(&ent.spec.schedule),
+            last.as_ref(),
+        )
+        .map(|t| t + stagger::offset(state, id, &ent.spec))
+        .or(chained.first().copied()),
+        waiting_for: state.locks.waiting_for(id),
+        breaker_open_since: state.breakers.get(&id).and_then(|b| b.open_since),
+        chained,
+        progress: state
+            .running
+            .iter()
+            .find(|r| r.value().task_id == id)
+            .and_then(|r| r.value().progress()),
+        spec: ent.spec.clone(),
+        last_result: last,
+    }
+}
+
+/// 搜尋任務規格；結果依 id 排序
+pub fn search_tasks(state: &State, pattern: &str, regex: bool) -> Result<Vec<TaskInfo>> {
+    let re: Regex = if regex {
+        Regex::new(pattern).with_context(|| format!("invalid regex {pattern:?}"))?
+    } else {
+        RegexBuilder::new(&regex::escape(pattern))
+            .case_insensitive(true)
+            .build()?
+    };
+    let mut list = list_tasks(state, TaskSort::Id, false);
+    list.retain(|t| spec_matches(&t.spec, &re));
+    Ok(list)
+}
+
+fn spec_matches(spec: &TaskSpec, re: &Regex) -> bool {
+    spec.name.as_deref().is_some_and(|n| re.is_match(n))
+        || re.is_match(&spec.cmd)
+        || spec.args.iter().any(|a| re.is_match(a))
+        || spec.tags.iter().any(|t| re.is_match(t))
+        || spec
+            .owner
+            .iter()
+            .flat_map(|o| o.email.iter().chain(&o.team))
+            .any(|v| re.is_match(v))
+        || re.is_match(&spec.output_path.to_string_lossy())
+}
+
+/// 跨任務的執行查詢：逐任務彙總全部符合的紀錄，明細只回最新的 limit 筆
+pub fn runs_report(
+    state: &State,
+    since: Option<DateTime<FixedOffset>>,
+    until: Option<DateTime<FixedOffset>>,
+    status: Option<RunStatus>,
+    limit: usize,
+) -> RunReport {
+    let matched = state.history.query(since, until, status);
+    let mut tasks: BTreeMap<u64, TaskRunTally> = BTreeMap::new();
+    for rec in &matched {
+        let tally = tasks.entry(rec.task_id).or_insert_with(|| TaskRunTally {
+            task_id: rec.task_id,
+            label: task_label(state, rec.task_id),
+            succeeded: 0,
+            failed: 0,
+            skipped: 0,
+            last_finished_at: rec.result.finished_at,
+        });
+        match RunStatus::of(&rec.result) {
+            RunStatus::Succeeded => tally.succeeded += 1,
+            RunStatus::Failed => tally.failed += 1,
+            RunStatus::Skipped => tally.skipped += 1,
+        }
+        // 紀錄依寫入順序，不保證 finished_at 單調
+        tally.last_finished_at = tally.last_finished_at.max(rec.result.finished_at);
+    }
+    RunReport {
+        total: matched.len(),
+        runs: matched.into_iter().take(limit).collect(),
+        tasks: tasks.into_values().collect(),
+    }
+}
+
+/// 任務名稱（沒有則為命令）；已移除的任務以最後一個版本為準
+fn task_label(state: &State, id: u64) -> Option<String> {
+    let label = |spec: &TaskSpec| spec.name.clone().unwrap_or_else(|| spec.cmd.clone());
+    if let Some(ent) = state.tasks.get(&id) {
+        return Some(label(&ent.spec));
+    }
+    state.revisions.for_task(id).last().map(|r| label(&r.spec))
+}
+
+/// 下一次預定執行的時間；schedule 為具名排程換算後的實際排程
+pub fn next_run(schedule: &Schedule, last: Option<&RunResult>) -> Option<DateTime<FixedOffset>> {
+    match schedule {
+        Schedule::Once(t) if last.is_none() || *t > local_now_fixed() => Some(*t),
+        Schedule::Once(_) => None,
+        s => s.next_occurrence(local_now_fixed(), &Local),
+    }
+}
+
+/// 成功排在失敗之前，再依結束碼
+fn status_key(r: &RunResult) -> (bool, i32) {
+    (
+        r.outcome != RunOutcome::Exited || r.status_code != 0,
+        r.status_code,
+    )
+}
+
+/// 有值者依方向比較；None 不論方向都排在最後
+fn cmp_some_first<T: Ord>(a: Option<T>, b: Option<T>, descending: bool) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) if descending => b.cmp(&a),
+        (Some(a), Some(b)) => a.cmp(&b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
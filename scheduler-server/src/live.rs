@@ -0,0 +1,101 @@
+use crate::encoding;
+use scheduler_core::{OutputEncoding, OutputStream, RunOutcome, ServerResponse};
+use std::{collections::HashMap, sync::Mutex};
+use tokio::sync::mpsc;
+
+/// RunNow --follow 的即時輸出：follower 登記在 RunNow 預先配發的 run 編號上，
+/// 那一次 run 開始執行時取走，之後的輸出片段與結果都送給它。
+/// 同一任務其他的 run（定時觸發等）不會取走別人的 follower
+#[derive(Default)]
+pub struct Live {
+    waiting: Mutex<HashMap<u64, mpsc::UnboundedSender<ServerResponse>>>,
+}
+
+impl Live {
+    pub fn follow(&self, run_id: u64) -> mpsc::UnboundedReceiver<ServerResponse> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.waiting.lock().unwrap().insert(run_id, tx);
+        rx
+    }
+
+    /// run 開始時呼叫；沒有 follower 時回傳 None
+    pub fn take(&self, task_id: u64, run_id: u64) -> Option<Tee> {
+        let tx = self.waiting.lock().unwrap().remove(&run_id)?;
+        Some(Tee { task_id, tx })
+    }
+
+    /// run 沒有開始就結束（略過、被移除等）或 follower 離開時撤銷登記
+    pub fn forget(&self, run_id: u64) {
+        self.waiting.lock().unwrap().remove(&run_id);
+    }
+}
+
+/// 一次 run 的 follower；全部 drop 後接收端收到 None
+#[derive(Clone)]
+pub struct Tee {
+    task_id: u64,
+    tx: mpsc::UnboundedSender<ServerResponse>,
+}
+
+impl Tee {
+    pub fn output(&self, stream: OutputStream, data: String) {
+        if data.is_empty() {
+            return;
+        }
+        self.send(ServerResponse::RunOutput {
+            task_id: self.task_id,
+            stream,
+            data,
+        });
+    }
+
+    pub fn done(&self, run_id: u64, status_code: i32, outcome: RunOutcome) {
+        self.send(ServerResponse::RunDone {
+            task_id: self.task_id,
+            run_id,
+            status_code,
+            outcome,
+        });
+    }
+
+    fn send(&self, resp: ServerResponse) {
+        let _ = self.tx.send(resp); // follower 已斷線，忽略
+    }
+}
+
+/// 把讀到的位元組切成 UTF-8 字串；結尾不完整的多位元組字元留到下一次，
+/// 其餘無法解碼的位元組依任務的 output_encoding 處理
+pub struct Utf8Carry {
+    pending: Vec<u8>,
+    encoding: OutputEncoding,
+}
+
+impl Utf8Carry {
+    pub fn new(encoding: OutputEncoding) -> Self {
+        Self {
+            pending: Vec::new(),
+            encoding,
+        }
+    }
+
+    pub fn push(&mut self, bytes: &[u8]) -> String {
+        self.pending.extend_from_slice(bytes);
+        let keep = match std::str::from_utf8(&self.pending) {
+            Ok(_) => 0,
+            // 只有結尾被截斷（error_len 為 None）才保留
+            Err(e) if e.error_len().is_none() => self.pending.len() - e.valid_up_to(),
+            Err(_) => 0,
+        };
+        let rest = self.pending.split_off(self.pending.len() - keep);
+        let text = encoding::display(self.encoding, &self.pending).into_owned();
+        self.pending = rest;
+        text
+    }
+
+    /// 輸出結束：剩下的位元組一併送出
+    pub fn finish(&mut self) -> String {
+        let text = encoding::display(self.encoding, &self.pending).into_owned();
+        self.pending.clear();
+        text
+    }
+}
@@ -0,0 +1,32 @@
+use crate::{record_skip, State};
+use scheduler_core::{Schedule, TaskSpec};
+use std::sync::Arc;
+
+/// 負載過高時略過這一輪的低優先 Daily/Hourly 任務（含具名排程）；略過時記一筆 Skipped 並回傳 true
+///
+/// 只看定時觸發，外部觸發與依賴觸發照常排隊。
+pub async fn shed(state: &Arc<State>, id: u64, spec: &TaskSpec) -> bool {
+    let cfg = &state.config.load_shed;
+    let Some(max) = cfg.max_active else {
+        return false;
+    };
+    let recurring = matches!(
+        state.schedules.effective(&spec.schedule),
+        Schedule::Daily { .. } | Schedule::Hourly { .. }
+    );
+    if !recurring || spec.priority >= cfg.min_priority {
+        return false;
+    }
+    let active = state.running.len() + state.queue.len();
+    if active < max {
+        return false;
+    }
+
+    let reason = format!(
+        "server saturated ({active} active runs, limit {max}): {:?} priority run skipped",
+        spec.priority
+    );
+    println!("🪫 task {} {}", id, reason);
+    record_skip(state, id, spec, None, reason).await;
+    true
+}
@@ -0,0 +1,121 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+};
+use tokio::sync::Notify;
+
+/// 具名互斥資源：共用同一把鎖的任務不會同時執行
+///
+/// 每次取得時拿一張遞增的號碼牌，一次排進所有需要的鎖的佇列；
+/// 在每個佇列都排到最前面才算取得。號碼在各佇列中的順序一致，
+/// 因此先到先得且不會互相卡死。
+#[derive(Default)]
+pub struct LockManager {
+    inner: Mutex<Inner>,
+    changed: Notify,
+}
+
+#[derive(Default)]
+struct Inner {
+    next_ticket: u64,
+    queues: HashMap<String, VecDeque<u64>>,
+    /// 號碼牌 → (任務, 需要的鎖)
+    tickets: HashMap<u64, (u64, Vec<String>)>,
+}
+
+/// 持有（或仍在等待）的鎖；drop 時釋放並叫醒其他等待者
+pub struct LockGuard {
+    manager: Arc<LockManager>,
+    ticket: u64,
+}
+
+impl LockManager {
+    /// 依序排隊直到取得全部的鎖；future 被取消時自動退出佇列
+    pub async fn acquire(self: &Arc<Self>, task_id: u64, locks: &[String]) -> LockGuard {
+        let mut names = locks.to_vec();
+        names.sort();
+        names.dedup();
+
+        let ticket = {
+            let mut inner = self.inner.lock().unwrap();
+            inner.next_ticket += 1;
+            let ticket = inner.next_ticket;
+            for name in &names {
+                inner
+                    .queues
+                    .entry(name.clone())
+                    .or_default()
+                    .push_back(ticket);
+            }
+            inner.tickets.insert(ticket, (task_id, names));
+            ticket
+        };
+        let guard = LockGuard {
+            manager: self.clone(),
+            ticket,
+        };
+
+        loop {
+            // 先登記通知再檢查，避免錯過檢查與等待之間的釋放
+            let notified = self.changed.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+            if self.blocking(ticket).is_empty() {
+                return guard;
+            }
+            notified.await;
+        }
+    }
+
+    /// 某任務正在等待的鎖（已取得或不需要的不列出）
+    pub fn waiting_for(&self, task_id: u64) -> Vec<String> {
+        let inner = self.inner.lock().unwrap();
+        let mut out: Vec<String> = inner
+            .tickets
+            .iter()
+            .filter(|(_, (id, _))| *id == task_id)
+            .flat_map(|(ticket, _)| blocking_locked(&inner, *ticket))
+            .collect();
+        out.sort();
+        out.dedup();
+        out
+    }
+
+    fn blocking(&self, ticket: u64) -> Vec<String> {
+        blocking_locked(&self.inner.lock().unwrap(), ticket)
+    }
+
+    fn release(&self, ticket: u64) {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some((_, names)) = inner.tickets.remove(&ticket) {
+            for name in names {
+                if let Some(q) = inner.queues.get_mut(&name) {
+                    q.retain(|&t| t != ticket);
+                    if q.is_empty() {
+                        inner.queues.remove(&name);
+                    }
+                }
+            }
+        }
+        drop(inner);
+        self.changed.notify_waiters();
+    }
+}
+
+/// 號碼牌尚未排到最前面的鎖
+fn blocking_locked(inner: &Inner, ticket: u64) -> Vec<String> {
+    let Some((_, names)) = inner.tickets.get(&ticket) else {
+        return Vec::new();
+    };
+    names
+        .iter()
+        .filter(|n| inner.queues.get(*n).and_then(|q| q.front()) != Some(&ticket))
+        .cloned()
+        .collect()
+}
+
+impl Drop for LockGuard {
+    fn drop(&mut self) {
+        self.manager.release(self.ticket);
+    }
+}
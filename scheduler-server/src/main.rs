@@ -1,122 +1,1299 @@
-use anyhow::{Context, Result};
-use bytes::BytesMut;
-use chrono::{DateTime, FixedOffset, Local, Timelike};
+mod access;
+mod approval;
+mod artifacts;
+mod breaker;
+mod bridge;
+mod builtin;
+mod chain;
+mod chaos;
+mod cleanup;
+mod cmdcheck;
+mod config;
+mod configtasks;
+mod context;
+mod datalock;
+mod defaults;
+mod digest;
+mod disk;
+mod encoding;
+mod environments;
+mod events;
+mod exec;
+mod freeze;
+mod gitsync;
+mod healthcheck;
+mod history;
+mod hooks;
+mod ids;
+mod listen;
+mod listing;
+mod live;
+mod loadshed;
+mod locks;
+mod maintenance;
+mod mdns;
+mod metadata;
+mod metrics;
+mod mqtt;
+mod notify;
+mod outputs;
+mod perms;
+mod policy;
+mod progress;
+mod protocol;
+mod quarantine;
+mod queue;
+mod quota;
+mod replica;
+mod revisions;
+mod rotate;
+mod s3;
+mod sandbox;
+mod schedules;
+mod stagger;
+mod startup;
+mod success;
+mod template;
+mod throttle;
+mod trash;
+mod validate;
+mod verify;
+mod watchdog;
+mod window;
+mod ws;
+
+use access::{AccessLog, Peer};
+use anyhow::{bail, Context, Result};
+use bytes::{Bytes, BytesMut};
+use chrono::{DateTime, FixedOffset, Local};
+use clap::Parser;
+use config::{CmdCheck, DiskGuardAction, OrphanPolicy, ServerConfig};
 use dashmap::DashMap;
-use futures_util::{SinkExt, StreamExt};
-use scheduler_core::{ClientRequest, RunResult, Schedule, ServerResponse, TaskInfo, TaskSpec};
+use datalock::DataLock;
+use events::{EventBus, TaskChangeSet};
+use exec::RunHandle;
+use futures_util::{Sink, SinkExt, Stream, StreamExt};
+use history::History;
+use ids::IdAllocator;
+use listen::Listener;
+use live::Live;
+use locks::LockManager;
+use maintenance::Maintenance;
+use notify::Outbox;
+use quarantine::Quarantine;
+use queue::{RunQueue, Slot};
+use revisions::Revisions;
+use scheduler_core::{
+    BreakerState, ChaosEntry, CleanupReport, ClientRequest, EventKind, InvalidTask, OrphanedRun,
+    OutputStream, OutputsFrom, RemovalImpact, ResponseFrame, RunOutcome, RunRecord, RunResult,
+    Schedule, SchedulerError, ServerContext, ServerResponse, StartupReport, TaskSort, TaskSpec,
+    Trigger, WindowPolicy, SYSTEM_NAMESPACE,
+};
+use schedules::NamedSchedules;
 use std::{
-    collections::VecDeque,
+    collections::{BTreeMap, BTreeSet, HashSet, VecDeque},
     net::SocketAddr,
     path::{Path, PathBuf},
-    sync::{
-        atomic::{AtomicU64, Ordering},
-        Arc, Mutex,
-    },
-    time::Duration,
+    sync::{Arc, Mutex, OnceLock, RwLock},
+    time::{Duration, Instant},
 };
 use tokio::{
-    net::{TcpListener, TcpStream},
-    process::Command,
+    io::{AsyncRead, AsyncWrite},
+    sync::{mpsc, watch},
+    task::JoinSet,
     time::sleep,
 };
-use tokio_util::{
-    codec::{Framed, LengthDelimitedCodec},
-    sync::CancellationToken,
-};
+use tokio_util::{codec::Framed, sync::CancellationToken};
+use trash::Trash;
 
 /// 每個任務的狀態
 #[derive(Debug)]
 struct TaskEntry {
     spec: TaskSpec,
-    cancel: Option<CancellationToken>,          // 只給 Once/Daily 用；After 不需要
+    cancel: Option<CancellationToken>, // 只給 Once/Daily 用；After 不需要
     last_result: Arc<Mutex<Option<RunResult>>>, // 同步鎖，避免非 Send await
 }
 
 /// 伺服器全域狀態
 struct State {
-    tasks: DashMap<u64, TaskEntry>,       // 任務表
-    watchers: DashMap<u64, Vec<u64>>,     // 依賴：A -> [B..]（A 完成後觸發 B）
-    next_id: AtomicU64,                   // 遞增任務 ID
-    data_path: PathBuf,                   // 持久化檔案
+    tasks: DashMap<u64, TaskEntry>,                  // 任務表
+    watchers: DashMap<u64, Vec<u64>>,                // 依賴：A -> [B..]（A 完成後觸發 B）
+    ids: IdAllocator,                                // 任務 ID 與 run 編號（高水位持久化）
+    config: ServerConfig,                            // 伺服器設定（含持久化檔案路徑）
+    running: DashMap<u64, Arc<RunHandle>>,           // 執行中的 run（key 為 run 編號）
+    history: History,                                // 執行歷史
+    revisions: Revisions,                            // 任務規格的版本紀錄
+    trash: Trash,                                    // 移除後可還原的任務
+    quarantine: Quarantine,                          // 載入時無法解析的持久化紀錄
+    maintenance: Maintenance,                        // 預約的維護動作（暫停、移除）
+    freeze: freeze::Freeze,                          // 全域凍結（部署期間暫停定時觸發）
+    trigger_hooks: hooks::TriggerHooks,              // 外部系統觸發任務用的網址與 token 雜湊
+    schedules: NamedSchedules,                       // 多個任務共用的具名排程
+    events: EventBus,                                // 事件廣播（watch 訂閱）
+    outbox: Outbox,                                  // 待送的 webhook 通知
+    context: RwLock<ServerContext>,                  // 伺服器層級的模板變數（可執行期間修改）
+    locks: Arc<LockManager>,                         // 任務間的具名互斥鎖
+    queue: Arc<RunQueue>,                            // 已觸發、等待開始的 run
+    quota_usage: quota::Usage,                       // 配額的執行中 run 數與今日輸出量
+    trigger_starts: DashMap<u64, VecDeque<Instant>>, // 任務 → 最近一小時的啟動時間（節流用）
+    breakers: DashMap<u64, BreakerState>,            // 任務 → 斷路器狀態（隨任務持久化）
+    chaos: DashMap<u64, ChaosEntry>,                 // 任務 → 故障注入規則（只在記憶體）
+    approvals: DashMap<u64, approval::Waiting>,      // run 編號 → 等待人工核准
+    chained: DashMap<u64, chain::Pending>,           // 依賴鏈中排定延遲執行的 run
+    triggers_changed: watch::Sender<()>,             // 任務增刪時通知（MQTT 重新同步訂閱）
+    access: Arc<AccessLog>,                          // 連線與請求的存取紀錄
+    live: Live,                                      // RunNow --follow 等待中的 follower
+    started_at: DateTime<FixedOffset>,               // 啟動時間
+    startup: OnceLock<StartupReport>,                // 啟動時的自我檢查結果（載入完成後寫入）
+    environments: BTreeMap<String, Arc<State>>,      // 其他隔離的環境（只有預設環境有）
+    persisting: Mutex<()>,                           // 同一時間只有一個 persist 寫任務檔
+}
+
+/// 離開 execute_once 時（含錯誤/panic）一定清掉 running 登記；
+/// 「執行中」已寫入持久化檔（marked）卻沒走到記錄結果的 persist 就離開時（例如啟動命令失敗），
+/// 再存一次把標記清掉，否則重啟時會被誤判為孤兒
+struct RunningGuard<'a> {
+    state: &'a Arc<State>,
+    key: u64,
+    marked: bool,
+}
+
+impl RunningGuard<'_> {
+    /// 結果已記錄，最後的 persist 會一併清掉標記
+    fn finish(mut self) {
+        self.marked = false;
+    }
+}
+
+impl Drop for RunningGuard<'_> {
+    fn drop(&mut self) {
+        self.state.running.remove(&self.key);
+        if self.marked {
+            let state = self.state.clone();
+            let key = self.key;
+            tokio::spawn(async move {
+                if let Err(e) = persist(&state).await {
+                    eprintln!("run {} persist after abort error: {e:?}", key);
+                }
+            });
+        }
+    }
+}
+
+#[derive(Parser, Debug)]
+#[command(name = "scheduler-server")]
+struct Opts {
+    /// 設定檔（TOML）；未指定則全部使用預設值
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    /// 覆寫設定檔中的監聽位址（可重複；"unix:<路徑>" 為 Unix socket）
+    #[arg(long)]
+    bind: Vec<String>,
+
+    /// 覆寫設定檔中的持久化檔案
+    #[arg(long)]
+    data: Option<PathBuf>,
+
+    /// 以模擬執行器取代實際執行（規則見設定檔 [mock]）
+    #[arg(long)]
+    mock: bool,
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    // 可改 clap 參數；先用固定值方便跑起來
-    let bind = "127.0.0.1:7878".to_string();
-    let data = PathBuf::from("tasks.json");
+    let opts = Opts::parse();
+    let mut config = match &opts.config {
+        Some(p) => ServerConfig::load(p)?,
+        None => ServerConfig::default(),
+    };
+    if !opts.bind.is_empty() {
+        config.bind = opts.bind;
+    }
+    if config.bind.is_empty() {
+        bail!("bind: at least one listen address is required");
+    }
+    if let Some(data) = opts.data {
+        config.data_path = data;
+    }
+    if opts.mock {
+        config.mock.enabled = true;
+    }
+    exec::check_mock(&config.mock)?;
+    if let Some(s3) = &config.s3 {
+        s3::check(s3)?;
+    }
+    notify::check(&config.notify)?;
+    defaults::check(&config.defaults)?;
+    if let Some(digest) = &config.digest {
+        digest::check(digest)?;
+    }
+    environments::check(&config)?;
+    if config.mock.enabled {
+        println!("🎭 mock executor enabled: commands will not actually run");
+    }
+    if config.chaos.enabled {
+        println!("🐒 chaos mode enabled: failures and delays may be injected into runs");
+    }
+
+    // 其他檔案都還沒動之前先取得鎖，第二個伺服器在這裡就停下
+    // 唯讀副本讀主伺服器的檔案：不取得鎖，自己的事件只轉播不寫檔
+    let replica = config.replica.clone();
+    let _lock = match &replica {
+        Some(r) => {
+            config.events.persist = false;
+            println!(
+                "🪞 read-only replica of {}: not scheduling or running tasks",
+                r.primary
+            );
+            None
+        }
+        None => Some(DataLock::acquire(&config.data_path)?),
+    };
+    let access = Arc::new(AccessLog::open(config.access_log.as_ref())?);
+
+    // 其他環境各有自己的資料目錄與鎖，與預設環境一樣載入並開始排程
+    let mut environments = BTreeMap::new();
+    let mut _env_locks = Vec::new();
+    for (name, env) in &config.environments {
+        let cfg = config.for_environment(name, env);
+        _env_locks.push(DataLock::acquire(&cfg.data_path)?);
+        let st = open_state(cfg, access.clone(), BTreeMap::new())?;
+        println!(
+            "🌐 environment {name}: data in {}",
+            st.config.data_path.display()
+        );
+        start(&st).await?;
+        environments.insert(name.clone(), st);
+    }
+    let state = open_state(config, access, environments)?;
+
+    if let Some(cfg) = replica {
+        if let Err(e) = history::blocking(&state, replica::sync)
+            .await
+            .and_then(|r| r)
+        {
+            eprintln!("replica sync error: {e:#}");
+        }
+        let _ = state.startup.set(StartupReport {
+            tasks_loaded: state.tasks.len(),
+            ..StartupReport::default()
+        });
+        replica::spawn(state.clone(), cfg);
+        return serve_until_shutdown(state).await;
+    }
+
+    start(&state).await?;
+    serve_until_shutdown(state).await
+}
+
+/// 依設定開啟一個環境的持久化檔，建立它的 State（尚未載入任務）
+fn open_state(
+    config: ServerConfig,
+    access: Arc<AccessLog>,
+    environments: BTreeMap<String, Arc<State>>,
+) -> Result<Arc<State>> {
+    let history = History::load(&config.history)?;
+    let ids = IdAllocator::load(&config.ids_path)?;
+    // 高水位檔是後來才有的：從既有的歷史接續，舊資料升級後也不重用 run 編號
+    ids.observe_run(history.max_run_id());
 
-    let state = Arc::new(State {
+    Ok(Arc::new(State {
         tasks: DashMap::new(),
         watchers: DashMap::new(),
-        next_id: AtomicU64::new(1),
-        data_path: data.clone(),
-    });
+        ids,
+        running: DashMap::new(),
+        history,
+        revisions: Revisions::load(&config.revisions.path)?,
+        trash: Trash::load(&config.trash.path)?,
+        quarantine: Quarantine::load(&config.quarantine_path)?,
+        maintenance: Maintenance::load(&config.maintenance_path)?,
+        freeze: freeze::Freeze::load(&config.freeze_path)?,
+        quota_usage: quota::Usage::load(&config.quota_usage_path)?,
+        trigger_hooks: hooks::TriggerHooks::load(&config.trigger_hooks_path)?,
+        schedules: NamedSchedules::load(&config.schedules.path)?,
+        events: EventBus::open(&config.events, config.replica.is_some())?,
+        outbox: Outbox::load(&config.notify.outbox_path)?,
+        locks: Arc::new(LockManager::default()),
+        queue: Arc::new(RunQueue::default()),
+        context: RwLock::new(context::load(&config.context)?),
+        access,
+        live: Live::default(),
+        config,
+        trigger_starts: DashMap::new(),
+        breakers: DashMap::new(),
+        chaos: DashMap::new(),
+        approvals: DashMap::new(),
+        chained: DashMap::new(),
+        triggers_changed: watch::channel(()).0,
+        started_at: local_now_fixed(),
+        startup: OnceLock::new(),
+        environments,
+        persisting: Mutex::new(()),
+    }))
+}
 
-    // 啟動時載入持久化任務
+/// 載入持久化任務並開始排程，啟動背景工作
+async fn start(state: &Arc<State>) -> Result<()> {
+    let data = &state.config.data_path;
+    let mut report = StartupReport::default();
     if data.exists() {
-        if let Err(e) = load_persisted(&state, &data).await {
-            eprintln!("load persisted error: {e:?}");
+        match load_persisted(state, data).await {
+            Ok(loaded) => report = loaded,
+            Err(e) => eprintln!("load persisted error: {e:?}"),
         }
     }
+    // 已移除任務的 id 也不重用，版本紀錄才不會接錯任務，回收桶中的任務也才能原樣還原
+    state.ids.observe_task(state.revisions.max_task_id());
+    state.ids.observe_task(state.trash.max_task_id());
+    state.ids.observe_task(state.quarantine.max_task_id());
+    configtasks::reconcile(state).await?;
+    builtin::register(state)?;
+    startup::finish(state, report);
 
-    let listener = TcpListener::bind(&bind).await?;
-    println!("✅ scheduler-server listening on {bind}");
+    watchdog::spawn(state.clone());
+    notify::spawn(state.clone());
+    digest::spawn(state.clone())?;
+    mqtt::spawn(state.clone());
+    bridge::spawn(state.clone());
+    metrics::spawn(state.clone());
+    healthcheck::spawn(state.clone());
+    gitsync::spawn(state.clone());
+    trash::spawn(state.clone());
+    maintenance::spawn(state.clone());
+    Ok(())
+}
 
-    loop {
-        let (stream, peer) = listener.accept().await?;
-        let st = state.clone();
-        tokio::spawn(async move {
-            if let Err(e) = handle_conn(st, stream, peer).await {
-                eprintln!("connection {peer} error: {e:?}");
+/// 開始接受連線，直到收到結束訊號
+async fn serve_until_shutdown(state: Arc<State>) -> Result<()> {
+    let ws_addr = ws::spawn(state.clone()).await?;
+    hooks::spawn(state.clone()).await?;
+
+    // 全部 bind 成功才開始接受連線，任一位址失敗就不啟動
+    let mut listeners = Vec::new();
+    for addr in &state.config.bind {
+        listeners.push(Listener::bind(addr).await?);
+    }
+    let tcp_addrs: Vec<SocketAddr> = listeners.iter().filter_map(Listener::tcp_addr).collect();
+    let sockets: Vec<PathBuf> = listeners.iter().filter_map(Listener::unix_path).collect();
+    for listener in listeners {
+        // 印出實際位址（bind 到 :0 時由系統配發埠號）
+        println!("✅ scheduler-server listening on {listener}");
+        listen::spawn(state.clone(), listener);
+    }
+    // 副本不宣告：discover 找到的應該是主伺服器
+    let advertisement = match state.config.replica {
+        Some(_) => None,
+        None => mdns::advertise(&state, &tcp_addrs, ws_addr)?,
+    };
+
+    shutdown_signal().await;
+    let running: usize = state
+        .environments
+        .values()
+        .map(|env| env.running.len())
+        .sum();
+    println!(
+        "🛑 shutting down, cancelling {} running task(s)",
+        state.running.len() + running
+    );
+    for env in state.environments.values() {
+        cancel_all_runs(env).await;
+    }
+    cancel_all_runs(&state).await;
+    // 終止時發出的事件也要寫進事件檔，重連的訂閱者才補得到
+    for st in state.environments.values().chain([&state]) {
+        if let Err(e) = history::blocking(st, |s| s.events.close()).await {
+            eprintln!("event log close error: {e:#}");
+        }
+    }
+    if let Some(ad) = advertisement {
+        ad.stop();
+    }
+    for path in sockets {
+        let _ = std::fs::remove_file(path);
+    }
+    Ok(())
+}
+
+/// 等待 Ctrl-C（Unix 另含 SIGTERM）
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut term) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = term.recv() => {}
+                }
             }
-        });
+            Err(_) => {
+                let _ = tokio::signal::ctrl_c().await;
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+    }
+}
+
+/// 關閉前終止所有執行中的 run（連同行程群組），最多等 10 秒讓它們收尾
+async fn cancel_all_runs(state: &Arc<State>) {
+    for kv in state.running.iter() {
+        kv.value().kill(RunOutcome::Cancelled);
+    }
+    for _ in 0..100 {
+        if state.running.is_empty() {
+            break;
+        }
+        sleep(Duration::from_millis(100)).await;
     }
 }
 
-/// 單一連線：收 ClientRequest → 回 ServerResponse
-async fn handle_conn(state: Arc<State>, stream: TcpStream, _peer: SocketAddr) -> Result<()> {
-    let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+/// 單一連線：收 ClientRequest → 回 ServerResponse。
+/// 直接送的請求依序處理；RequestFrame 並行處理，回覆帶同一個 id
+async fn handle_conn<S>(state: Arc<State>, stream: S, peer: Peer) -> Result<()>
+where
+    S: AsyncRead + AsyncWrite + Send + 'static,
+{
+    let framed = Framed::new(stream, protocol::codec(state.config.max_frame_bytes));
+    let (sink, incoming) = framed.split();
+    serve(state, peer, incoming, sink).await
+}
 
-    while let Some(frame) = framed.next().await {
-        let bytes: BytesMut = frame?;
-        let req: ClientRequest = serde_json::from_slice(&bytes[..])?;
+/// 在一條已切成 frame 的連線上處理請求；TCP 與 WebSocket 共用
+async fn serve<I, E, K>(state: Arc<State>, peer: Peer, mut incoming: I, sink: K) -> Result<()>
+where
+    I: Stream<Item = Result<BytesMut, E>> + Unpin,
+    E: std::fmt::Display,
+    K: Sink<Bytes> + Send + Unpin + 'static,
+{
+    let conn = state.access.connect(peer);
+    // 所有回覆經由同一個 channel 寫出，並行的請求與訂閱才不會交錯寫入 frame
+    let (out, rx) = mpsc::channel::<Bytes>(64);
+    tokio::spawn(write_frames(sink, rx));
+    // 連線結束時一併中止；一般請求則讓它做完，避免新增、移除做到一半
+    let mut subscriptions = JoinSet::new();
 
-        let resp = match req {
-            ClientRequest::AddTask(spec) => {
-                let id = add_task(&state, spec).await?;
-                ServerResponse::Added { id }
+    while let Some(frame) = incoming.next().await {
+        let started = Instant::now();
+        let bytes: BytesMut = match frame {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                // frame 邊界已不可信，無法再切出下一個 frame：回覆原因後關閉連線
+                let resp = ServerResponse::Error(SchedulerError::InvalidRequest {
+                    msg: format!("invalid frame: {e}"),
+                });
+                conn.request("invalid", None, started, &resp);
+                let _ = out.send(encode_reply(None, &resp)?).await;
+                return Ok(());
             }
-            ClientRequest::RemoveTask { id } => {
-                let ok = remove_task(&state, id).await?;
-                ServerResponse::Removed { ok }
+        };
+        let req = match protocol::decode_request(&bytes, state.config.spec_mode) {
+            Ok(req) => req,
+            Err((id, msg)) => {
+                let resp = ServerResponse::Error(SchedulerError::InvalidRequest { msg });
+                conn.request("invalid", id, started, &resp);
+                out.send(encode_reply(id, &resp)?).await?;
+                continue;
+            }
+        };
+        // 指定了環境的請求交給該環境處理（這一個請求內 state 改指向它）
+        let (state, req) = match environments::select(&state, req.request) {
+            Ok((st, request)) => (
+                st,
+                protocol::Incoming {
+                    id: req.id,
+                    request,
+                },
+            ),
+            Err(e) => {
+                let resp = ServerResponse::Error(e);
+                conn.request("InEnvironment", req.id, started, &resp);
+                out.send(encode_reply(req.id, &resp)?).await?;
+                continue;
             }
-            ClientRequest::ListTasks => {
-                let mut list = Vec::new();
-                for kv in state.tasks.iter() {
-                    let id = *kv.key();
-                    let ent = kv.value();
-                    let last = ent.last_result.lock().unwrap().clone(); // 同步鎖，無 await
-                    list.push(TaskInfo {
+        };
+
+        match req {
+            // 舊客戶端：整條連線轉為事件串流，之後的請求不再處理
+            protocol::Incoming {
+                id: None,
+                request: ClientRequest::Subscribe { since },
+            } => {
+                let mut sub = conn.subscription(None);
+                tokio::select! {
+                    r = events::stream(&state, since, None, &out) => {
+                        if let Err(e) = r {
+                            eprintln!("{e:#}, disconnecting");
+                            sub.fail();
+                        }
+                    }
+                    _ = async { while let Some(Ok(_)) = incoming.next().await {} } => {}
+                }
+                return Ok(());
+            }
+            protocol::Incoming {
+                id: Some(id),
+                request: ClientRequest::Subscribe { since },
+            } => {
+                let (st, out) = (state.clone(), out.clone());
+                let mut sub = conn.subscription(Some(id));
+                subscriptions.spawn(async move {
+                    if let Err(e) = events::stream(&st, since, Some(id), &out).await {
+                        sub.fail();
+                        let resp = ServerResponse::Error(SchedulerError::Internal {
+                            msg: format!("{e:#}; subscribe again with since"),
+                        });
+                        if let Ok(frame) = encode_reply(Some(id), &resp) {
+                            let _ = out.send(frame).await;
+                        }
+                    }
+                });
+            }
+            // follow 的輸出片段陸續送出；舊客戶端在結束前不處理下一個請求
+            protocol::Incoming {
+                id: None,
+                request:
+                    ClientRequest::RunNow {
+                        id: task,
+                        follow: true,
+                        metadata,
+                    },
+            } => {
+                let resp = follow_run(&state, task, metadata, None, &out).await;
+                conn.request("RunNow", None, started, &resp);
+                out.send(encode_reply(None, &resp)?).await?;
+            }
+            protocol::Incoming {
+                id: Some(id),
+                request:
+                    ClientRequest::RunNow {
+                        id: task,
+                        follow: true,
+                        metadata,
+                    },
+            } => {
+                let (st, out, conn) = (state.clone(), out.clone(), conn.clone());
+                tokio::spawn(async move {
+                    let resp = follow_run(&st, task, metadata, Some(id), &out).await;
+                    conn.request("RunNow", Some(id), started, &resp);
+                    if let Ok(frame) = encode_reply(Some(id), &resp) {
+                        let _ = out.send(frame).await;
+                    }
+                });
+            }
+            protocol::Incoming { id: None, request } => {
+                let kind = request.kind();
+                let resp = handle_request(&state, request, &conn.actor()).await?;
+                conn.request(kind, None, started, &resp);
+                out.send(encode_reply(None, &resp)?).await?;
+            }
+            protocol::Incoming {
+                id: Some(id),
+                request,
+            } => {
+                let (st, out, conn) = (state.clone(), out.clone(), conn.clone());
+                tokio::spawn(async move {
+                    let kind = request.kind();
+                    let resp = handle_request(&st, request, &conn.actor())
+                        .await
+                        .unwrap_or_else(|e| {
+                            ServerResponse::Error(SchedulerError::Internal {
+                                msg: format!("{e:#}"),
+                            })
+                        });
+                    conn.request(kind, Some(id), started, &resp);
+                    if let Ok(frame) = encode_reply(Some(id), &resp) {
+                        let _ = out.send(frame).await;
+                    }
+                });
+            }
+        }
+        // 回收已結束的訂閱
+        while subscriptions.try_join_next().is_some() {}
+    }
+
+    Ok(())
+}
+
+/// 把 channel 中的 frame 依序寫到連線；寫入失敗（客戶端斷線）就停止
+async fn write_frames<K: Sink<Bytes> + Unpin>(mut sink: K, mut rx: mpsc::Receiver<Bytes>) {
+    while let Some(frame) = rx.recv().await {
+        if sink.send(frame).await.is_err() {
+            break;
+        }
+    }
+}
+
+/// 回覆編碼成 frame；有 id 時包成 ResponseFrame
+fn encode_reply(id: Option<u64>, resp: &ServerResponse) -> Result<Bytes> {
+    let bytes = match id {
+        Some(id) => serde_json::to_vec(&ResponseFrame {
+            id,
+            response: resp.clone(),
+        })?,
+        None => serde_json::to_vec(resp)?,
+    };
+    Ok(bytes.into())
+}
+
+/// RunNow --follow：先登記 follower 再立即執行，把輸出片段逐一送回；
+/// 回傳最後一個回應（RunDone，或 run 沒有開始時的錯誤）
+async fn follow_run(
+    state: &Arc<State>,
+    id: u64,
+    metadata: BTreeMap<String, String>,
+    reply: Option<u64>,
+    out: &mpsc::Sender<Bytes>,
+) -> ServerResponse {
+    if state.config.replica.is_some() {
+        return ServerResponse::Error(SchedulerError::InvalidRequest {
+            msg: "a read-only replica cannot follow runs; connect to the primary".to_string(),
+        });
+    }
+    if let Err(e) = metadata::check(&metadata) {
+        return ServerResponse::Error(e);
+    }
+    let Some(spec) = state.tasks.get(&id).map(|t| t.spec.clone()) else {
+        return ServerResponse::Error(SchedulerError::NotFound {
+            msg: format!("task {id} not found"),
+        });
+    };
+    let slot = match enter_queue(state, id) {
+        Ok(slot) => slot,
+        Err(e) => return ServerResponse::Error(trigger_rejected(e)),
+    };
+    // follower 登記在這次 run 的編號上，同任務其他的 run 不會取走它
+    let run_id = state.ids.next_run();
+    let mut rx = state.live.follow(run_id);
+    println!("▶️ task {} run {} now (follow)", id, run_id);
+    let st = state.clone();
+    let mut run = tokio::spawn(async move {
+        run_with_metadata(id, spec, st, metadata, Some(slot), Some(run_id)).await
+    });
+    // 轉送一個片段；客戶端已斷線時回傳 false（run 照常完成，只是不再轉送）
+    let forward = |chunk: &ServerResponse| {
+        let frame = encode_reply(reply, chunk);
+        async move {
+            match frame {
+                Ok(frame) => out.send(frame).await.is_ok(),
+                Err(_) => true,
+            }
+        }
+    };
+    let result = loop {
+        tokio::select! {
+            biased;
+            msg = rx.recv() => match msg {
+                Some(done @ ServerResponse::RunDone { .. }) => return done,
+                Some(chunk) => {
+                    if !forward(&chunk).await {
+                        return ServerResponse::Error(SchedulerError::Internal {
+                            msg: "follower disconnected".to_string(),
+                        });
+                    }
+                }
+                // follower 被取走卻沒有結果：執行失敗（例如命令無法啟動）
+                None => break run.await,
+            },
+            // 沒有開始執行就結束（略過、核准被駁回、任務被移除等）；
+            // 結束前送出的片段與結果可能還在 channel 裡
+            r = &mut run => {
+                while let Ok(msg) = rx.try_recv() {
+                    if matches!(msg, ServerResponse::RunDone { .. }) {
+                        return msg;
+                    }
+                    forward(&msg).await;
+                }
+                break r;
+            }
+        }
+    };
+    drop(rx);
+    state.live.forget(run_id);
+    let msg = match result {
+        Ok(Err(e)) => format!("run did not complete: {e:#}"),
+        _ => "run did not start; see the task's events for the reason".to_string(),
+    };
+    ServerResponse::Error(SchedulerError::Internal { msg })
+}
+
+/// 處理 Subscribe 以外的請求
+/// actor 為發出請求的連線（記入版本紀錄）
+async fn handle_request(
+    state: &Arc<State>,
+    req: ClientRequest,
+    actor: &str,
+) -> Result<ServerResponse> {
+    if let Some(cfg) = state.config.replica.as_ref() {
+        if !replica::serves_locally(&req) {
+            let req = environments::authorize(&state.config, req);
+            return Ok(replica::forward(cfg, state.config.max_frame_bytes, &req).await);
+        }
+    }
+    let resp = match req {
+        ClientRequest::AddTask(spec) => {
+            let missing = cmdcheck::check(&state.config, &spec);
+            if let Err(e) = validate::validate_spec(&state.config, &spec) {
+                ServerResponse::Error(client_error(e, |msg| SchedulerError::InvalidRequest {
+                    msg: format!("invalid task spec: {msg}"),
+                }))
+            } else if let Some(msg) = missing
+                .as_ref()
+                .filter(|_| state.config.cmd_check == CmdCheck::Reject)
+            {
+                ServerResponse::Error(SchedulerError::InvalidRequest {
+                    msg: format!("invalid task spec: {msg} (set skip_cmd_check to add it anyway)"),
+                })
+            } else if let Err(e) = quota::check_add(state, &spec.namespace) {
+                ServerResponse::Error(SchedulerError::Unauthorized {
+                    msg: format!("{e:#}"),
+                })
+            } else {
+                match add_task(state, spec, actor).await {
+                    Ok(id) => ServerResponse::Added {
                         id,
-                        spec: ent.spec.clone(),
-                        last_result: last,
+                        warnings: missing.into_iter().collect(),
+                    },
+                    Err(e) => ServerResponse::Error(client_error(e, |msg| {
+                        SchedulerError::Internal { msg }
+                    })),
+                }
+            }
+        }
+        ClientRequest::RemoveTask { id, .. } | ClientRequest::PurgeTask { id, .. }
+            if is_system_task(state, id) =>
+        {
+            ServerResponse::Error(SchedulerError::Unauthorized {
+                msg: format!("task {id} is a built-in task and cannot be removed"),
+            })
+        }
+        ClientRequest::RemoveTask { id, .. } | ClientRequest::PurgeTask { id, .. }
+            if configtasks::is_config_task(state, id) =>
+        {
+            ServerResponse::Error(SchedulerError::Unauthorized {
+                msg: format!(
+                    "task {id} is defined in the server config; remove it there (it can be paused with maintenance)"
+                ),
+            })
+        }
+        ClientRequest::RemoveTask { id, force: false }
+        | ClientRequest::PurgeTask { id, force: false }
+            if is_protected(state, id) =>
+        {
+            ServerResponse::Error(SchedulerError::Unauthorized {
+                msg: format!("task {id} is protected; use force to remove it"),
+            })
+        }
+        ClientRequest::RemoveTask { id, .. } => {
+            let ok = trash_task(state, id, actor).await?;
+            ServerResponse::Removed { ok }
+        }
+        ClientRequest::ListTrash => ServerResponse::Trash(state.trash.list()),
+        ClientRequest::PreviewRemoval { ids } => ServerResponse::RemovalPreview(
+            ids.iter().map(|&id| removal_impact(state, id)).collect(),
+        ),
+        ClientRequest::RestoreTask { id } => restore_task(state, id, actor).await,
+        ClientRequest::PurgeTask { id, .. } => {
+            let trashed = state.trash.take(id)?.is_some();
+            let removed = remove_task(state, id).await?;
+            if trashed || removed {
+                println!("🗑️ task {id} purged by {actor}");
+            }
+            ServerResponse::Removed {
+                ok: trashed || removed,
+            }
+        }
+        ClientRequest::ListTasks => {
+            ServerResponse::Tasks(listing::list_tasks(state, TaskSort::Id, false))
+        }
+        ClientRequest::ListTasksSorted { sort, descending } => {
+            ServerResponse::Tasks(listing::list_tasks(state, sort, descending))
+        }
+        ClientRequest::ListTasksSince { version } => match state.events.changes_since(version) {
+            (version, TaskChangeSet::Full) => ServerResponse::TaskChanges {
+                version,
+                full: true,
+                changed: listing::list_tasks(state, TaskSort::Id, false),
+                removed: Vec::new(),
+            },
+            (version, TaskChangeSet::Since { touched, mut removed }) => {
+                // 移除後又還原的任務算在 changed
+                removed.retain(|id| !state.tasks.contains_key(id));
+                removed.sort_unstable();
+                removed.dedup();
+                ServerResponse::TaskChanges {
+                    version,
+                    full: false,
+                    changed: listing::task_infos(state, &touched),
+                    removed,
+                }
+            }
+        },
+        ClientRequest::Search { pattern, regex } => {
+            match listing::search_tasks(state, &pattern, regex) {
+                Ok(list) => ServerResponse::Tasks(list),
+                Err(e) => ServerResponse::Error(SchedulerError::InvalidRequest {
+                    msg: format!("{e:#}"),
+                }),
+            }
+        }
+        ClientRequest::ListOutbox { dead_only } => {
+            ServerResponse::Outbox(state.outbox.list(dead_only))
+        }
+        ClientRequest::RequeueNotifications { ids } => match state.outbox.requeue(&ids) {
+            Ok(count) => ServerResponse::Requeued { count },
+            Err(e) => ServerResponse::Error(SchedulerError::Internal {
+                msg: format!("requeue notifications: {e:#}"),
+            }),
+        },
+        ClientRequest::GetQueueStats => ServerResponse::QueueStats(state.queue.stats()),
+        ClientRequest::GetContext => ServerResponse::Context(state.context.read().unwrap().clone()),
+        // 變數會代入命令、參數與沙箱路徑：只有持有權杖的客戶端可以修改
+        ClientRequest::UpdateContext { .. } if state.config.token.is_none() => {
+            ServerResponse::Error(SchedulerError::Unauthorized {
+                msg: "UpdateContext requires a token; set the server or environment token"
+                    .to_string(),
+            })
+        }
+        ClientRequest::UpdateContext {
+            environment,
+            set,
+            unset,
+        } => match set.keys().find(|k| !outputs::valid_key(k)) {
+            Some(key) => ServerResponse::Error(SchedulerError::InvalidRequest {
+                msg: format!("invalid variable name {key:?}"),
+            }),
+            None => {
+                let mut ctx = state.context.write().unwrap();
+                let mut next = ctx.clone();
+                if let Some(env) = environment {
+                    next.environment = env;
+                }
+                for key in &unset {
+                    next.vars.remove(key);
+                }
+                next.vars.extend(set);
+                match context::save(&state.config.context, &next) {
+                    Ok(()) => {
+                        *ctx = next;
+                        println!(
+                            "🔧 server context updated by {} (environment={})",
+                            actor, ctx.environment
+                        );
+                        ServerResponse::Context(ctx.clone())
+                    }
+                    Err(e) => ServerResponse::Error(SchedulerError::Internal {
+                        msg: format!("{e:#}"),
+                    }),
+                }
+            }
+        },
+        ClientRequest::GetOutput { id } => {
+            match artifacts::read_latest(&state.config.artifacts, id) {
+                Ok(Some(bytes)) if encoding::is_binary(&bytes) => ServerResponse::Output {
+                    id,
+                    content: encoding::binary_notice(
+                        bytes.len(),
+                        Some(&verify::sha256_hex(&bytes)),
+                    ),
+                },
+                Ok(Some(bytes)) => {
+                    let encoding = state
+                        .tasks
+                        .get(&id)
+                        .map(|t| t.spec.output_encoding)
+                        .unwrap_or_default();
+                    ServerResponse::Output {
+                        id,
+                        content: encoding::display(encoding, &bytes).into_owned(),
+                    }
+                }
+                Ok(None) => ServerResponse::Error(SchedulerError::NotFound {
+                    msg: format!("task {id} has no stored output"),
+                }),
+                Err(e) => ServerResponse::Error(SchedulerError::Internal {
+                    msg: format!("read output of task {id}: {e:#}"),
+                }),
+            }
+        }
+        ClientRequest::GetHistory { id, limit } => {
+            let list = history::blocking(state, move |st| st.history.for_task(id, limit)).await?;
+            ServerResponse::History(list)
+        }
+        ClientRequest::QueryRuns {
+            since,
+            until,
+            status,
+            limit,
+        } => {
+            let report = history::blocking(state, move |st| {
+                listing::runs_report(st, since, until, status, limit)
+            })
+            .await?;
+            ServerResponse::Runs(report)
+        }
+        ClientRequest::VerifyOutputs { id } => {
+            let st = state.clone();
+            let checks = tokio::task::spawn_blocking(move || verify::run(&st, id)).await?;
+            ServerResponse::Verified(checks)
+        }
+        ClientRequest::CancelChained { id } => {
+            let count = chain::cancel_task(state, id);
+            if count > 0 {
+                println!("⛓️ task {} cancelled {} chained run(s)", id, count);
+                if let Err(e) = persist(state).await {
+                    eprintln!("task {} persist chained run error: {e:?}", id);
+                }
+            }
+            ServerResponse::CancelledChained { id, count }
+        }
+        ClientRequest::ListApprovals => ServerResponse::Approvals(approval::pending(state)),
+        ClientRequest::Approve { run_id, approve } => {
+            if approval::decide(state, run_id, approve) {
+                ServerResponse::Decided {
+                    run_id,
+                    approved: approve,
+                }
+            } else {
+                ServerResponse::Error(SchedulerError::NotFound {
+                    msg: format!("run {run_id} is not waiting for approval"),
+                })
+            }
+        }
+        ClientRequest::ResetBreaker { id } => {
+            if state.tasks.contains_key(&id) {
+                let was_open = breaker::reset(state, id);
+                if let Err(e) = persist(state).await {
+                    eprintln!("task {} persist breaker error: {e:?}", id);
+                }
+                ServerResponse::BreakerReset { id, was_open }
+            } else {
+                ServerResponse::Error(SchedulerError::NotFound {
+                    msg: format!("task {id} not found"),
+                })
+            }
+        }
+        ClientRequest::PruneHistory => {
+            let pruned = history::blocking(state, |st| {
+                st.history.prune(&st.config.history, local_now_fixed())
+            })
+            .await?;
+            match pruned {
+                Ok(removed) => ServerResponse::Pruned { removed },
+                Err(e) => ServerResponse::Error(SchedulerError::Internal {
+                    msg: format!("prune history: {e:#}"),
+                }),
+            }
+        }
+        ClientRequest::ListRevisions { id } => {
+            ServerResponse::Revisions(state.revisions.for_task(id))
+        }
+        ClientRequest::RollbackTask { id, .. } if is_system_task(state, id) => {
+            ServerResponse::Error(SchedulerError::Unauthorized {
+                msg: format!("task {id} is a built-in task and cannot be changed"),
+            })
+        }
+        ClientRequest::RollbackTask { id, .. } if configtasks::is_config_task(state, id) => {
+            ServerResponse::Error(SchedulerError::Unauthorized {
+                msg: format!("task {id} is defined in the server config; change it there"),
+            })
+        }
+        ClientRequest::RollbackTask {
+            id, force: false, ..
+        } if is_protected(state, id) => ServerResponse::Error(SchedulerError::Unauthorized {
+            msg: format!("task {id} is protected; use force to roll it back"),
+        }),
+        ClientRequest::RollbackTask { id, revision, .. } => {
+            rollback_task(state, id, revision, actor).await
+        }
+        ClientRequest::ListSchedules => {
+            let mut users: BTreeMap<String, Vec<u64>> = BTreeMap::new();
+            for (id, name) in named_users(state) {
+                users.entry(name).or_default().push(id);
+            }
+            ServerResponse::Schedules(state.schedules.list(users))
+        }
+        ClientRequest::SetSchedule { name, schedule } => {
+            match state.schedules.set(&name, schedule) {
+                Ok(()) => {
+                    let tasks = reschedule_named(state, &name);
+                    println!("📅 named schedule {name} set by {actor} ({tasks} task(s) rescheduled)");
+                    ServerResponse::ScheduleSet { name, tasks }
+                }
+                Err(e) => ServerResponse::Error(client_error(e, |msg| {
+                    SchedulerError::InvalidRequest { msg }
+                })),
+            }
+        }
+        // follow 的 RunNow 在 serve 中處理
+        ClientRequest::RunNow { id, metadata, .. } => match state.tasks.get(&id).map(|t| t.spec.clone()) {
+            Some(spec) => {
+                if let Err(e) = metadata::check(&metadata) {
+                    return Ok(ServerResponse::Error(e));
+                }
+                let slot = match enter_queue(state, id) {
+                    Ok(slot) => slot,
+                    Err(e) => return Ok(ServerResponse::Error(trigger_rejected(e))),
+                };
+                println!("▶️ task {} run now by {}", id, actor);
+                let st = state.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = run_with_metadata(id, spec, st, metadata, Some(slot), None).await {
+                        eprintln!("task {} run error: {e:?}", id);
+                    }
+                });
+                ServerResponse::Triggered { id }
+            }
+            None => ServerResponse::Error(SchedulerError::NotFound {
+                msg: format!("task {id} not found"),
+            }),
+        },
+        ClientRequest::GetServerInfo => ServerResponse::ServerInfo(startup::info(state)),
+        ClientRequest::GetDependencyGraph => ServerResponse::DependencyGraph(chain::graph(state)),
+        ClientRequest::ListQuarantine => ServerResponse::Quarantine(state.quarantine.list()),
+        ClientRequest::DiscardQuarantined { seq } => match state.quarantine.discard(seq) {
+            Ok(true) => {
+                println!("🧹 quarantined record {seq} discarded by {actor}");
+                ServerResponse::QuarantineDiscarded { seq }
+            }
+            Ok(false) => ServerResponse::Error(SchedulerError::NotFound {
+                msg: format!("quarantined record {seq} not found"),
+            }),
+            Err(e) => ServerResponse::Error(client_error(e, |msg| SchedulerError::Internal { msg })),
+        },
+        ClientRequest::ScheduleMaintenance {
+            task_id,
+            action,
+            at,
+            force,
+        } => match maintenance::schedule(state, task_id, action, at, force, actor) {
+            Ok(seq) => ServerResponse::MaintenanceScheduled { seq },
+            Err(e) => ServerResponse::Error(client_error(e, |msg| SchedulerError::Internal { msg })),
+        },
+        ClientRequest::ListMaintenance => ServerResponse::Maintenance(state.maintenance.list()),
+        ClientRequest::CancelMaintenance { seq } => match state.maintenance.cancel(seq) {
+            Ok(Some(a)) => {
+                println!("🛠️ maintenance #{seq} for task {} cancelled by {actor}", a.task_id);
+                ServerResponse::MaintenanceCancelled { seq }
+            }
+            Ok(None) => ServerResponse::Error(SchedulerError::NotFound {
+                msg: format!("maintenance #{seq} not found"),
+            }),
+            Err(e) => ServerResponse::Error(client_error(e, |msg| SchedulerError::Internal { msg })),
+        },
+        ClientRequest::SetChaos { task_id, rule } => match chaos::set(state, task_id, rule) {
+            Ok(()) => ServerResponse::ChaosSet { task_id },
+            Err(e) => ServerResponse::Error(client_error(e, |msg| SchedulerError::Internal { msg })),
+        },
+        ClientRequest::ListChaos => ServerResponse::Chaos(chaos::list(state)),
+        ClientRequest::Freeze {
+            duration_secs,
+            block_triggered,
+            reason,
+        } => match freeze::freeze(state, duration_secs, block_triggered, reason, actor) {
+            Ok(status) => ServerResponse::Frozen(status),
+            Err(e) => ServerResponse::Error(client_error(e, |msg| SchedulerError::Internal { msg })),
+        },
+        ClientRequest::Unfreeze => match freeze::unfreeze(state, actor) {
+            Ok(was_frozen) => ServerResponse::Unfrozen { was_frozen },
+            Err(e) => ServerResponse::Error(SchedulerError::Internal {
+                msg: format!("{e:#}"),
+            }),
+        },
+        ClientRequest::CreateTriggerHook {
+            task_id,
+            label,
+            params,
+        } => match hooks::create(state, task_id, label, params, actor) {
+            Ok((hook, token)) => ServerResponse::TriggerHookCreated { hook, token },
+            Err(e) => ServerResponse::Error(client_error(e, |msg| SchedulerError::Internal { msg })),
+        },
+        ClientRequest::ListTriggerHooks => ServerResponse::TriggerHooks(state.trigger_hooks.list()),
+        ClientRequest::RevokeTriggerHook { id } => match hooks::revoke(state, id, actor) {
+            Ok(true) => ServerResponse::TriggerHookRevoked { id },
+            Ok(false) => ServerResponse::Error(SchedulerError::NotFound {
+                msg: format!("trigger hook {id} not found"),
+            }),
+            Err(e) => ServerResponse::Error(SchedulerError::Internal {
+                msg: format!("{e:#}"),
+            }),
+        },
+        ClientRequest::DiffManifest { tasks } => match gitsync::preview(state, tasks) {
+            Ok(changes) => ServerResponse::ManifestDiff(changes),
+            Err(e) => ServerResponse::Error(client_error(e, |msg| SchedulerError::InvalidRequest { msg })),
+        },
+        ClientRequest::RetryChain { run_id } => match history::blocking(state, move |st| {
+            chain::retry_plan(st, run_id)
+        })
+        .await?
+        {
+            Ok(plan) => {
+                let tasks: Vec<u64> = plan.iter().map(|r| r.task_id).collect();
+                println!("🔁 chain of run {} retried by {}: tasks {:?}", run_id, actor, tasks);
+                for retry in plan {
+                    let (id, st) = (retry.task_id, state.clone());
+                    tokio::spawn(async move {
+                        if let Err(e) = retry_chained(id, retry, st).await {
+                            eprintln!("task {} retry error: {e:?}", id);
+                        }
                     });
                 }
-                ServerResponse::Tasks(list)
+                ServerResponse::ChainRetried { run_id, tasks }
+            }
+            Err(e) => ServerResponse::Error(client_error(e, |msg| {
+                SchedulerError::InvalidRequest { msg }
+            })),
+        },
+        ClientRequest::RemoveSchedule { name } => {
+            let users: Vec<u64> = named_users(state)
+                .into_iter()
+                .filter(|(_, n)| *n == name)
+                .map(|(id, _)| id)
+                .collect();
+            if !users.is_empty() {
+                ServerResponse::Error(SchedulerError::InvalidRequest {
+                    msg: format!("named schedule {name:?} is used by task(s) {users:?}"),
+                })
+            } else {
+                match state.schedules.remove(&name) {
+                    Ok(true) => {
+                        println!("📅 named schedule {name} removed by {actor}");
+                        ServerResponse::ScheduleRemoved { name }
+                    }
+                    Ok(false) => ServerResponse::Error(SchedulerError::NotFound {
+                        msg: format!("named schedule {name:?} not found"),
+                    }),
+                    Err(e) => ServerResponse::Error(SchedulerError::Internal {
+                        msg: format!("{e:#}"),
+                    }),
+                }
             }
+        }
+        req => ServerResponse::Error(SchedulerError::InvalidRequest {
+            msg: format!("unsupported request: {req:?}"),
+        }),
+    };
+    Ok(resp)
+}
+
+/// 改回某一版的規格；設定可能在那之後收緊，所以與新增做相同的檢查
+async fn rollback_task(state: &Arc<State>, id: u64, revision: u32, actor: &str) -> ServerResponse {
+    let Some(namespace) = state.tasks.get(&id).map(|t| t.spec.namespace.clone()) else {
+        return ServerResponse::Error(SchedulerError::NotFound {
+            msg: format!("task {id} not found"),
+        });
+    };
+    let Some(rev) = state.revisions.get(id, revision) else {
+        return ServerResponse::Error(SchedulerError::NotFound {
+            msg: format!("task {id} has no revision {revision}"),
+        });
+    };
+    if let Err(e) = validate::validate_spec(&state.config, &rev.spec) {
+        return ServerResponse::Error(client_error(e, |msg| SchedulerError::InvalidRequest {
+            msg: format!("revision {revision} is no longer valid: {msg}"),
+        }));
+    }
+    if rev.spec.namespace != namespace {
+        if let Err(e) = quota::check_add(state, &rev.spec.namespace) {
+            return ServerResponse::Error(SchedulerError::Unauthorized {
+                msg: format!("{e:#}"),
+            });
+        }
+    }
+    match update_task(state, id, rev.spec, actor, Some(revision)).await {
+        Ok(Some(new)) => {
+            println!("⏪ task {id} rolled back to revision {revision} by {actor}");
+            ServerResponse::RolledBack { id, revision: new }
+        }
+        Ok(None) => ServerResponse::Error(SchedulerError::NotFound {
+            msg: format!("task {id} not found"),
+        }),
+        Err(e) => ServerResponse::Error(client_error(e, |msg| SchedulerError::Internal { msg })),
+    }
+}
+
+/// 以具名排程排定的任務：(任務 id, 排程名稱)
+fn named_users(state: &State) -> Vec<(u64, String)> {
+    let mut users: Vec<(u64, String)> = state
+        .tasks
+        .iter()
+        .filter_map(|kv| match &kv.value().spec.schedule {
+            Schedule::Named { name } => Some((*kv.key(), name.clone())),
+            _ => None,
+        })
+        .collect();
+    users.sort_unstable();
+    users
+}
+
+/// 具名排程改變後，重新啟動引用它的任務的排程迴圈；回傳任務數
+fn reschedule_named(state: &Arc<State>, name: &str) -> usize {
+    let ids: Vec<u64> = named_users(state)
+        .into_iter()
+        .filter(|(_, n)| n == name)
+        .map(|(id, _)| id)
+        .collect();
+    for &id in &ids {
+        let tok = CancellationToken::new();
+        let spec = {
+            let Some(mut ent) = state.tasks.get_mut(&id) else {
+                continue;
+            };
+            if let Some(old) = ent.cancel.replace(tok.clone()) {
+                old.cancel();
+            }
+            ent.spec.clone()
         };
+        spawn_scheduler_loop(id, spec, tok, state.clone());
+    }
+    ids.len()
+}
 
-        let out = serde_json::to_vec(&resp)?;
-        framed.send(out.into()).await?;
+/// 已分類的錯誤原樣回給客戶端，其餘的以 other 包裝完整訊息
+fn client_error(e: anyhow::Error, other: impl FnOnce(String) -> SchedulerError) -> SchedulerError {
+    match e.downcast::<SchedulerError>() {
+        Ok(e) => e,
+        Err(e) => other(format!("{e:#}")),
     }
+}
 
-    Ok(())
+fn is_system_task(state: &State, id: u64) -> bool {
+    state
+        .tasks
+        .get(&id)
+        .is_some_and(|ent| ent.spec.namespace == SYSTEM_NAMESPACE)
 }
 
-/// 新增任務：為 Once/Daily 啟動排程；After 只登記依賴
-async fn add_task(state: &Arc<State>, spec: TaskSpec) -> Result<u64> {
-    let id = state.next_id.fetch_add(1, Ordering::SeqCst);
+/// 受保護的任務；回收桶中的也算（永久刪除同樣要 force）
+fn is_protected(state: &State, id: u64) -> bool {
+    match state.tasks.get(&id) {
+        Some(ent) => ent.spec.protected,
+        None => state
+            .trash
+            .list()
+            .iter()
+            .any(|t| t.id == id && t.spec.protected),
+    }
+}
+
+/// 新增任務：為 Once/Daily 啟動排程；After 只登記依賴；Manual 只等 triggers。
+/// actor 記入版本紀錄（第 1 版）
+async fn add_task(state: &Arc<State>, spec: TaskSpec, actor: &str) -> Result<u64> {
+    let id = state.ids.next_task()?;
+    insert_task(state, id, spec, actor).await?;
+    Ok(id)
+}
+
+/// 以指定 id 放入任務表（新增與從回收桶還原共用）
+async fn insert_task(state: &Arc<State>, id: u64, spec: TaskSpec, actor: &str) -> Result<()> {
+    if let Some(cycle) = dependency_cycle(state, id, &spec.schedule) {
+        bail!(SchedulerError::CycleDetected { cycle });
+    }
+    state.schedules.check_ref(&spec.schedule)?;
 
     let base = TaskEntry {
         spec: spec.clone(),
@@ -129,7 +1306,12 @@ async fn add_task(state: &Arc<State>, spec: TaskSpec) -> Result<u64> {
             state.watchers.entry(*task_id).or_default().push(id);
             base
         }
-        Schedule::Once(_) | Schedule::Daily { .. } => {
+        Schedule::Manual => base, // 只由 triggers 啟動
+        Schedule::Reboot => base, // 下次伺服器啟動時才執行
+        Schedule::Once(_)
+        | Schedule::Daily { .. }
+        | Schedule::Hourly { .. }
+        | Schedule::Named { .. } => {
             let tok = CancellationToken::new();
             spawn_scheduler_loop(id, spec.clone(), tok.clone(), state.clone());
             TaskEntry {
@@ -137,11 +1319,193 @@ async fn add_task(state: &Arc<State>, spec: TaskSpec) -> Result<u64> {
                 ..base
             }
         }
+        _ => base, // 看不懂的排程在驗證時已擋下
     };
 
     state.tasks.insert(id, entry);
+    state.triggers_changed.send_replace(());
     persist(state).await?;
-    Ok(id)
+    record_revision(state, id, &spec, actor, None);
+    state.events.emit(EventKind::TaskAdded { task_id: id });
+    Ok(())
+}
+
+/// 使用者的移除：有保留期時先放進回收桶再移除，之後可還原
+async fn trash_task(state: &Arc<State>, id: u64, actor: &str) -> Result<bool> {
+    let Some(spec) = state.tasks.get(&id).map(|t| t.spec.clone()) else {
+        return Ok(false);
+    };
+    let days = state.config.trash.retention_days;
+    if days > 0 {
+        state.trash.put(id, spec, actor, days)?;
+    }
+    remove_task(state, id).await
+}
+
+/// 移除任務前的試算，不改變任何狀態
+fn removal_impact(state: &State, id: u64) -> RemovalImpact {
+    let task = state.tasks.get(&id);
+    let trashed = state.trash.list().into_iter().find(|t| t.id == id);
+    let spec = task
+        .as_ref()
+        .map(|t| &t.spec)
+        .or(trashed.as_ref().map(|t| &t.spec));
+    let mut dependents = state
+        .watchers
+        .get(&id)
+        .map(|w| w.value().clone())
+        .unwrap_or_default();
+    dependents.sort_unstable();
+    RemovalImpact {
+        id,
+        label: spec.map(|s| s.name.clone().unwrap_or_else(|| s.cmd.clone())),
+        found: task.is_some(),
+        in_trash: trashed.is_some(),
+        system: spec.is_some_and(|s| s.namespace == SYSTEM_NAMESPACE),
+        protected: spec.is_some_and(|s| s.protected),
+        recurring: task.as_ref().is_some_and(|t| {
+            matches!(
+                state.schedules.effective(&t.spec.schedule),
+                Schedule::Daily { .. } | Schedule::Hourly { .. }
+            )
+        }),
+        dependents,
+        running: state
+            .running
+            .iter()
+            .filter(|r| r.value().task_id == id)
+            .count(),
+    }
+}
+
+/// 從回收桶還原；設定可能在移除後收緊，所以與新增做相同的檢查
+async fn restore_task(state: &Arc<State>, id: u64, actor: &str) -> ServerResponse {
+    let Some(item) = state.trash.list().into_iter().find(|t| t.id == id) else {
+        return ServerResponse::Error(SchedulerError::NotFound {
+            msg: format!("task {id} is not in the trash"),
+        });
+    };
+    if let Err(e) = validate::validate_spec(&state.config, &item.spec) {
+        return ServerResponse::Error(client_error(e, |msg| SchedulerError::InvalidRequest {
+            msg: format!("task {id} is no longer valid: {msg}"),
+        }));
+    }
+    if let Err(e) = quota::check_add(state, &item.spec.namespace) {
+        return ServerResponse::Error(SchedulerError::Unauthorized {
+            msg: format!("{e:#}"),
+        });
+    }
+    // 先檢查循環，取出後的新增才不會失敗而弄丟項目
+    if let Some(cycle) = dependency_cycle(state, id, &item.spec.schedule) {
+        return ServerResponse::Error(SchedulerError::CycleDetected { cycle });
+    }
+    let result = match state.trash.take(id) {
+        Ok(_) => insert_task(state, id, item.spec, actor).await,
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(()) => {
+            println!("♻️ task {id} restored from trash by {actor}");
+            ServerResponse::Restored { id }
+        }
+        Err(e) => ServerResponse::Error(client_error(e, |msg| SchedulerError::Internal { msg })),
+    }
+}
+
+/// 就地換掉任務規格：id、上次結果與斷路器狀態保留，排程依新規格重新啟動。
+/// 執行中的 run 照舊做完。回傳新版本號；任務不存在時為 None
+async fn update_task(
+    state: &Arc<State>,
+    id: u64,
+    spec: TaskSpec,
+    actor: &str,
+    rollback_of: Option<u32>,
+) -> Result<Option<u32>> {
+    if let Some(cycle) = dependency_cycle(state, id, &spec.schedule) {
+        bail!(SchedulerError::CycleDetected { cycle });
+    }
+    state.schedules.check_ref(&spec.schedule)?;
+    let old = {
+        let Some(mut ent) = state.tasks.get_mut(&id) else {
+            return Ok(None);
+        };
+        if let Some(tok) = ent.cancel.take() {
+            tok.cancel();
+        }
+        std::mem::replace(&mut ent.spec, spec.clone())
+    };
+
+    if let Schedule::After { task_id, .. } = old.schedule {
+        if let Some(mut w) = state.watchers.get_mut(&task_id) {
+            w.retain(|&x| x != id);
+        }
+    }
+    match &spec.schedule {
+        Schedule::After { task_id, .. } => {
+            state.watchers.entry(*task_id).or_default().push(id);
+        }
+        Schedule::Once(_)
+        | Schedule::Daily { .. }
+        | Schedule::Hourly { .. }
+        | Schedule::Named { .. } => {
+            let tok = CancellationToken::new();
+            if let Some(mut ent) = state.tasks.get_mut(&id) {
+                ent.cancel = Some(tok.clone());
+            }
+            spawn_scheduler_loop(id, spec.clone(), tok, state.clone());
+        }
+        _ => {}
+    }
+
+    state.triggers_changed.send_replace(());
+    persist(state).await?;
+    let revision = record_revision(state, id, &spec, actor, rollback_of);
+    state.events.emit(EventKind::TaskUpdated {
+        task_id: id,
+        revision: revision.unwrap_or_default(),
+    });
+    Ok(Some(revision.unwrap_or_default()))
+}
+
+/// 記下任務的新版本；內建任務每次啟動重建，不記。寫檔失敗只提示，不影響變更本身
+fn record_revision(
+    state: &State,
+    id: u64,
+    spec: &TaskSpec,
+    actor: &str,
+    rollback_of: Option<u32>,
+) -> Option<u32> {
+    if spec.namespace == SYSTEM_NAMESPACE {
+        return None;
+    }
+    match state.revisions.record(id, spec, actor, rollback_of) {
+        Ok(revision) => Some(revision),
+        Err(e) => {
+            eprintln!("task {id} revision record error: {e:?}");
+            None
+        }
+    }
+}
+
+/// 以 id 新增此排程後，沿上游走回 id 的話就是循環；回傳循環上的任務 id
+fn dependency_cycle(state: &State, id: u64, schedule: &Schedule) -> Option<Vec<u64>> {
+    let mut cycle = vec![id];
+    let mut up = match schedule {
+        Schedule::After { task_id, .. } => *task_id,
+        _ => return None,
+    };
+    while up != id {
+        // 既有的任務不會成環，走過所有任務仍未回到 id 就不是循環
+        if cycle.len() > state.tasks.len() {
+            return None;
+        }
+        cycle.push(up);
+        up = match state.tasks.get(&up)?.spec.schedule {
+            Schedule::After { task_id, .. } => task_id,
+            _ => return None,
+        };
+    }
+    Some(cycle)
 }
 
 /// 移除任務：取消（若有）並維護依賴
@@ -150,44 +1514,93 @@ async fn remove_task(state: &Arc<State>, id: u64) -> Result<bool> {
         if let Some(tok) = ent.cancel.take() {
             tok.cancel();
         }
+        for kv in state.running.iter() {
+            if kv.value().task_id == id {
+                kv.value().kill(RunOutcome::Cancelled);
+            }
+        }
         for mut kv in state.watchers.iter_mut() {
             kv.value_mut().retain(|&x| x != id);
         }
+        state.trigger_starts.remove(&id);
+        state.breakers.remove(&id);
+        state.chaos.remove(&id);
+        approval::cancel_task(state, id);
+        chain::cancel_task(state, id);
+        state.triggers_changed.send_replace(());
         persist(state).await?;
+        state.events.emit(EventKind::TaskRemoved { task_id: id });
         return Ok(true);
     }
     Ok(false)
 }
 
-/// 為 Once/Daily 啟動一個 scheduler 迴圈（依賴任務不走這裡）
-fn spawn_scheduler_loop(
-    id: u64,
-    spec: TaskSpec,
-    cancel: CancellationToken,
-    state: Arc<State>,
-) {
+/// 為 Once/Daily/Hourly（含具名排程）啟動一個 scheduler 迴圈（依賴任務不走這裡）
+fn spawn_scheduler_loop(id: u64, spec: TaskSpec, cancel: CancellationToken, state: Arc<State>) {
     tokio::spawn(async move {
         loop {
-            let next_time: DateTime<FixedOffset> = match &spec.schedule {
-                Schedule::Once(t) => *t, // 已是 FixedOffset
-                Schedule::Daily { hour, minute } => next_daily_at(*hour, *minute),
-                Schedule::After { .. } => unreachable!("After doesn't use loop"),
+            // 具名排程每輪重新查詢；修改時另由 reschedule_named 重啟迴圈
+            let schedule = state.schedules.effective(&spec.schedule);
+            let next_time: DateTime<FixedOffset> = match &schedule {
+                Schedule::Once(t) => *t, // 已是 FixedOffset；過時的也照跑（補跑）
+                s @ (Schedule::Daily { .. } | Schedule::Hourly { .. }) => s
+                    .next_occurrence(local_now_fixed(), &Local)
+                    .expect("daily/hourly always have a next occurrence"),
+                // 引用的具名排程不存在（例如排程檔被刪除）：等到重新設定或任務移除
+                _ => {
+                    eprintln!(
+                        "⚠️ task {} references a missing named schedule: {}",
+                        id, spec.schedule
+                    );
+                    cancel.cancelled().await;
+                    break;
+                }
             };
+            // 同一分鐘的 Daily 任務錯開啟動；每輪重算，任務增減後自動重新分配
+            let offset = stagger::offset(&state, id, &spec);
 
-            let wait = duration_to(next_time);
-            println!(
-                "⏰ task {} scheduled at {} ({}s later)",
-                id,
-                next_time,
-                wait.as_secs()
-            );
+            let wait = duration_to(next_time) + offset;
+            if offset.is_zero() {
+                println!(
+                    "⏰ task {} scheduled at {} ({}s later)",
+                    id,
+                    next_time,
+                    wait.as_secs()
+                );
+            } else {
+                println!(
+                    "⏰ task {} scheduled at {} +{:.1}s staggered ({}s later)",
+                    id,
+                    next_time,
+                    offset.as_secs_f64(),
+                    wait.as_secs()
+                );
+            }
 
             tokio::select! {
                 _ = sleep(wait) => {
+                    // 全域凍結：單次任務等解凍後補跑，週期任務略過這一輪
+                    if let Some(f) = state.freeze.current() {
+                        if matches!(schedule, Schedule::Once(_)) {
+                            println!("🧊 task {} deferred until the scheduler is unfrozen", id);
+                            tokio::select! {
+                                _ = freeze::thawed(&state) => continue,
+                                _ = cancel.cancelled() => {
+                                    println!("task {} cancelled", id);
+                                    break;
+                                }
+                            }
+                        }
+                        record_skip(&state, id, &spec, None, freeze::reason(&f)).await;
+                        continue;
+                    }
+                    if loadshed::shed(&state, id, &spec).await {
+                        continue;
+                    }
                     if let Err(e) = run_once_and_record(id, spec.clone(), state.clone()).await {
                         eprintln!("task {} run error: {e:?}", id);
                     }
-                    if matches!(spec.schedule, Schedule::Once(_)) { break; }
+                    if matches!(schedule, Schedule::Once(_)) { break; }
                 }
                 _ = cancel.cancelled() => {
                     println!("task {} cancelled", id);
@@ -198,103 +1611,726 @@ fn spawn_scheduler_loop(
     });
 }
 
-/// 只負責「執行一次 + 記錄結果」（不處理依賴、不遞迴）
-async fn execute_once(id: u64, spec: &TaskSpec, state: &Arc<State>) -> Result<()> {
-    // 1) 執行外部程式
-    let output = Command::new(&spec.cmd)
-        .args(&spec.args)
-        .output()
-        .await
-        .with_context(|| format!("spawn {:?}", spec.cmd))?;
-    let status = output.status.code().unwrap_or(-1);
+/// 只負責「執行一次 + 記錄結果」（不處理依賴、不遞迴）；
+/// assigned_run 為觸發端預先配發的 run 編號，沒有時開始執行才配發
+async fn execute_once(
+    id: u64,
+    spec: &TaskSpec,
+    state: &Arc<State>,
+    upstream: Option<RunResult>,
+    metadata: BTreeMap<String, String>,
+    slot: Option<Slot>,
+    assigned_run: Option<u64>,
+) -> Result<()> {
+    // 0) 代入模板變數；設定可能在任務新增後才收緊，執行前再檢查一次命令政策。
+    // 前置 run 未指定（RetryChain 才會指定）時用前置任務最近一次的結果
+    let builtin = builtin::Builtin::from_spec(spec);
+    let upstream = match &spec.schedule {
+        Schedule::After { task_id, .. } => upstream.or_else(|| {
+            state
+                .tasks
+                .get(task_id)
+                .and_then(|ent| ent.value().last_result.lock().unwrap().clone())
+        }),
+        _ => None,
+    };
+    let rendered;
+    let spec = match builtin {
+        Some(_) => spec,
+        None => {
+            let ctx = state.context.read().unwrap().clone();
+            let inherited = defaults::apply(&state.config.defaults, spec);
+            match template::render(&inherited, &ctx, upstream.as_ref()) {
+                Ok(s) => {
+                    rendered = s;
+                    &rendered
+                }
+                Err(e) => {
+                    state.events.emit(EventKind::RunSkipped {
+                        task_id: id,
+                        reason: format!("{e:#}"),
+                    });
+                    return Err(e);
+                }
+            }
+        }
+    };
+    if builtin.is_none() {
+        let search = exec::child_path(spec);
+        if let Err(e) = policy::check_command(&state.config.policy, &spec.cmd, search.as_deref()) {
+            state.events.emit(EventKind::RunSkipped {
+                task_id: id,
+                reason: format!("{e:#}"),
+            });
+            return Err(e);
+        }
+    }
+
+    // 全域凍結且連觸發的執行也擋下：不執行，記一筆 Skipped（定時觸發已在排程迴圈略過）
+    if let Some(f) = state.freeze.current().filter(|f| f.block_triggered) {
+        let reason = freeze::reason(&f);
+        record_skip(state, id, spec, assigned_run, reason.clone()).await;
+        bail!("task {id} {reason}");
+    }
+
+    // 預約的維護暫停期間：不執行，記一筆 Skipped
+    if let Some(until) = state.maintenance.paused_until(id, local_now_fixed()) {
+        let reason = format!("paused for maintenance until {until}");
+        record_skip(state, id, spec, assigned_run, reason.clone()).await;
+        bail!("task {id} {reason}");
+    }
+
+    // 從這裡到開始執行都算排隊：等允許時段、等核准、等鎖；觸發端已排入時沿用它的位置
+    let slot = match slot {
+        Some(slot) => slot,
+        None => enter_queue(state, id)?,
+    };
+
+    // 允許時段：窗外的觸發延到下一次時段開始（只存在記憶體），或依設定略過
+    if let Some(w) = &spec.allowed_window {
+        if let Some(wait) = window::wait_for(w, local_now_fixed())? {
+            if w.outside == WindowPolicy::Skip {
+                let reason = format!("outside allowed window {}-{}", w.start, w.end);
+                state.events.emit(EventKind::RunSkipped {
+                    task_id: id,
+                    reason: reason.clone(),
+                });
+                bail!("task {id} {reason}");
+            }
+            println!(
+                "🕙 task {} outside window {}-{}, deferred {}s",
+                id,
+                w.start,
+                w.end,
+                wait.as_secs()
+            );
+            if slot.wait(sleep(wait)).await.is_none() {
+                return Err(evicted(state, id));
+            }
+            if !state.tasks.contains_key(&id) {
+                bail!("task {id} was removed while deferred");
+            }
+        }
+    }
+
+    // 磁碟空間保護：寧可不跑，也不要跑到一半 ENOSPC
+    let guard = &state.config.disk_guard;
+    let mut skip_output = false;
+    match disk::check_low_space(guard, &spec.output_path) {
+        Ok(Some(free)) => {
+            eprintln!(
+                "🚨 CRITICAL: low disk space for task {} output {} ({} MiB free, min {} MiB)",
+                id,
+                spec.output_path.display(),
+                free / (1024 * 1024),
+                guard.min_free_mb.unwrap_or_default()
+            );
+            match guard.action {
+                DiskGuardAction::SkipRun => {
+                    state.events.emit(EventKind::RunSkipped {
+                        task_id: id,
+                        reason: "low disk space".to_string(),
+                    });
+                    bail!("disk space guard: run of task {id} skipped");
+                }
+                DiskGuardAction::SkipOutput => skip_output = true,
+            }
+        }
+        Ok(None) => {}
+        Err(e) => eprintln!("task {} disk space check error: {e:?}", id),
+    }
+
+    // 斷路器跳脫中：不執行，等冷卻後試跑或人工重設
+    if let Some(cb) = &spec.breaker {
+        if let Err(e) = breaker::check(state, id, cb) {
+            state.events.emit(EventKind::RunSkipped {
+                task_id: id,
+                reason: format!("{e:#}"),
+            });
+            return Err(e);
+        }
+    }
+
+    // 核准關卡：等人工核准後才往下走；駁回或逾時則本輪略過
+    let approved_run = match &spec.approval {
+        Some(gate) => {
+            let run_id = assigned_run.unwrap_or_else(|| state.ids.next_run());
+            match slot
+                .wait(approval::wait(state, id, run_id, spec, gate))
+                .await
+            {
+                Some(approved) => Some(approved?),
+                None => return Err(evicted(state, id)),
+            }
+        }
+        None => None,
+    };
+    if approved_run.is_some() && !state.tasks.contains_key(&id) {
+        bail!("task {id} was removed while waiting for approval");
+    }
+
+    // 具名鎖：排隊直到與共用鎖的任務錯開；等待期間不算執行中
+    let _locks = if spec.locks.is_empty() {
+        None
+    } else {
+        let waiting_since = Instant::now();
+        let Some(held) = slot.wait(state.locks.acquire(id, &spec.locks)).await else {
+            return Err(evicted(state, id));
+        };
+        let waited = waiting_since.elapsed();
+        if waited >= Duration::from_secs(1) {
+            println!(
+                "🔒 task {} waited {:.1}s for locks {:?}",
+                id,
+                waited.as_secs_f64(),
+                spec.locks
+            );
+        }
+        if !state.tasks.contains_key(&id) {
+            bail!("task {id} was removed while waiting for locks");
+        }
+        Some(held)
+    };
+    drop(slot);
+
+    // 1) 執行外部程式（登記到 running，供 watchdog 巡檢）
+    let started_at = local_now_fixed();
+    let run = Arc::new(RunHandle::new(id, started_at, spec));
+    let run_id = approved_run
+        .or(assigned_run)
+        .unwrap_or_else(|| state.ids.next_run());
+    let _permit = match builtin {
+        Some(_) => None,
+        None => match quota::check_run_start(state, &spec.namespace) {
+            Ok(permit) => permit,
+            Err(e) => {
+                state.events.emit(EventKind::RunSkipped {
+                    task_id: id,
+                    reason: format!("{e:#}"),
+                });
+                return Err(e);
+            }
+        },
+    };
+    state.running.insert(run_id, run.clone());
+    let mut guard = RunningGuard {
+        state,
+        key: run_id,
+        marked: false,
+    };
+    state.events.emit(EventKind::RunStarted {
+        task_id: id,
+        run_id,
+        metadata: metadata.clone(),
+    });
+    // 記下「執行中」，若伺服器中途停止，重啟時可對帳
+    match persist(state).await {
+        Ok(()) => guard.marked = true,
+        Err(e) => eprintln!("task {} persist running mark error: {e:?}", id),
+    }
+
+    if let Some(from) = &spec.outputs {
+        outputs::prepare(from);
+    }
+    // RunNow --follow 登記的 follower 跟著這次 run；實際執行的命令邊讀邊送
+    let tee = state.live.take(id, run_id);
+    // 故障注入：延遲後照常執行，或直接以指定的結束碼失敗
+    let injected = match builtin.is_none().then(|| chaos::roll(state, id)).flatten() {
+        Some(rule) => chaos::inject(&rule, id, spec, &run).await,
+        None => None,
+    };
+    let streamed = builtin.is_none() && !state.config.mock.enabled && injected.is_none();
+    let output = match (builtin, injected) {
+        (Some(b), _) => b.run(state).await,
+        (None, Some(output)) => output,
+        (None, None) if state.config.mock.enabled => {
+            exec::run_mock(&state.config.mock, spec, &run).await
+        }
+        (None, None) => {
+            let channel = match spec.sandbox {
+                None => progress::open(state, id, run_id, &run).unwrap_or_else(|e| {
+                    eprintln!("task {} progress channel error: {e:?}", id);
+                    None
+                }),
+                Some(_) => None,
+            };
+            exec::run_command(&state.config, spec, &run, channel.as_ref(), tee.as_ref()).await?
+        }
+    };
+    state.running.remove(&run_id);
+    // 二進位的 stdout 不寫進文字輸出檔、不轉送，也不解析輸出變數
+    let binary = encoding::is_binary(&output.stdout);
+    if let Some(tee) = tee.as_ref().filter(|_| !streamed) {
+        let text = |bytes| encoding::display(spec.output_encoding, bytes).into_owned();
+        let stdout = if binary {
+            encoding::binary_notice(output.stdout.len(), None)
+        } else {
+            text(&output.stdout)
+        };
+        tee.output(OutputStream::Stdout, stdout);
+        tee.output(OutputStream::Stderr, text(&output.stderr));
+    }
+    // 檔案標頭記程式實際的結束碼；紀錄、事件與斷路器用依 success 條件判定後的
+    let (status, exit_code) = success::judge(
+        spec.success.as_ref(),
+        spec.output_encoding,
+        output.outcome,
+        output.status_code,
+        &output.stdout,
+    );
     let now = local_now_fixed(); // FixedOffset
+    quota::record_output(
+        state,
+        &spec.namespace,
+        (output.stdout.len() + output.stderr.len()) as u64,
+    );
 
-    // 2) 寫檔（同步 I/O，無 await）
-    {
-        ensure_parent_dir(&spec.output_path)?;
-        let mut f = std::fs::OpenOptions::new()
-            .create(true)
-            .write(true)
-            .append(spec.append)
-            .open(&spec.output_path)?;
+    // 2) 組出輸出內容，寫檔並另存 artifact（同步 I/O，無 await）
+    let mut written: Option<(u64, String)> = None; // 大小與 SHA-256
+    let mut artifact = None;
+    if !skip_output {
         use std::io::Write;
-        writeln!(f, "=== [{}] task {} exit {} ===", now, id, status)?;
-        if !output.stdout.is_empty() {
-            f.write_all(&output.stdout)?;
-            if !spec.append { writeln!(f)?; }
+        let mut buf: Vec<u8> = Vec::new();
+        if binary {
+            // 輸出檔只放原始位元組，stderr 連同標頭另存一份
+            buf.extend_from_slice(&output.stdout);
+            if !output.stderr.is_empty() {
+                let mut side = Vec::new();
+                writeln!(
+                    side,
+                    "=== [{}] task {} exit {} ===",
+                    now, id, output.status_code
+                )?;
+                side.extend_from_slice(&output.stderr);
+                let path = binary_stderr_path(&spec.output_path);
+                if let Err(e) = std::fs::write(&path, side) {
+                    eprintln!("task {} write {} error: {e:?}", id, path.display());
+                }
+            }
+        } else if output.outcome == RunOutcome::Exited {
+            writeln!(
+                buf,
+                "=== [{}] task {} exit {} ===",
+                now, id, output.status_code
+            )?;
+        } else {
+            writeln!(
+                buf,
+                "=== [{}] task {} exit {} ({:?}) ===",
+                now, id, output.status_code, output.outcome
+            )?;
         }
-        if !output.stderr.is_empty() {
-            writeln!(f, "\n--- stderr ---")?;
-            f.write_all(&output.stderr)?;
-            writeln!(f)?;
+        if !binary && !output.stdout.is_empty() {
+            buf.write_all(&output.stdout)?;
+            if !spec.append {
+                writeln!(buf)?;
+            }
+        }
+        if !binary && !output.stderr.is_empty() {
+            writeln!(buf, "\n--- stderr ---")?;
+            buf.write_all(&output.stderr)?;
+            writeln!(buf)?;
+        }
+
+        let (file_mode, dir_mode) = perms::modes(&state.config.defaults, spec)?;
+        let created = perms::create_parent(&spec.output_path, dir_mode)?;
+        let mut f = perms::open(&spec.output_path, spec.append, file_mode)?;
+        f.write_all(&buf)?;
+        if let Err(e) = perms::apply(&state.config.defaults, spec, &created, &spec.output_path) {
+            eprintln!("task {} output permissions error: {e:#}", id);
         }
+        written = Some((buf.len() as u64, verify::sha256_hex(&buf)));
+
+        match artifacts::store(&state.config.artifacts, id, now, &buf) {
+            Ok(path) => artifact = path,
+            Err(e) => eprintln!("task {} store artifact error: {e:?}", id),
+        }
+        s3::upload(state, id, run_id, spec, now, buf);
     }
 
-    // 3) 更新 last_result（同步鎖）
+    // 3) 收集輸出變數，更新 last_result（同步鎖）並寫入歷史
+    let published = match &spec.outputs {
+        Some(OutputsFrom::LastLine) if binary => {
+            eprintln!("task {} outputs skipped: stdout is binary", id);
+            Default::default()
+        }
+        Some(from) => {
+            outputs::collect(from, &output.stdout, spec.output_encoding).unwrap_or_else(|e| {
+                eprintln!("task {} outputs error: {e:#}", id);
+                Default::default()
+            })
+        }
+        None => Default::default(),
+    };
+    // 4) 清理：輸出與輸出變數都收完之後才做；模擬執行不動真的檔案
+    let cleanup = if spec.cleanup.is_empty() || state.config.mock.enabled {
+        None
+    } else {
+        let actions = spec.cleanup.clone();
+        let roots = cleanup::roots(&state.config.cleanup, spec);
+        let report = tokio::task::spawn_blocking(move || cleanup::run(&actions, &roots))
+            .await
+            .unwrap_or_else(|e| CleanupReport {
+                errors: vec![format!("cleanup aborted: {e}")],
+                ..Default::default()
+            });
+        for e in &report.errors {
+            eprintln!("task {} cleanup error: {e}", id);
+        }
+        Some(report)
+    };
+    let result = RunResult {
+        run_id,
+        started_at: Some(started_at),
+        finished_at: now,
+        status_code: status,
+        exit_code,
+        upstream_run: upstream.as_ref().map(|r| r.run_id),
+        outcome: output.outcome,
+        stdout_len: output.stdout.len(),
+        stderr_len: output.stderr.len(),
+        wrote_to: spec.output_path.clone(),
+        outputs: published,
+        output_size: written.as_ref().map(|(size, _)| *size),
+        output_sha256: written.map(|(_, sha)| sha),
+        binary,
+        artifact,
+        usage: output.usage,
+        cleanup,
+        metadata: metadata.clone(),
+    };
     if let Some(ent) = state.tasks.get(&id) {
         let last = ent.value().last_result.clone();
         drop(ent);
-        let mut g = last.lock().unwrap();
-        *g = Some(RunResult {
-            finished_at: now,
-            status_code: status,
-            stdout_len: output.stdout.len(),
-            stderr_len: output.stderr.len(),
-            wrote_to: spec.output_path.clone(),
-        });
+        *last.lock().unwrap() = Some(result.clone());
+    }
+    history::record(
+        state,
+        RunRecord {
+            task_id: id,
+            result,
+        },
+    )
+    .await;
+    state.events.emit(EventKind::RunFinished {
+        task_id: id,
+        run_id,
+        status_code: status,
+        outcome: output.outcome,
+        metadata,
+    });
+    if let Some(tee) = &tee {
+        tee.done(run_id, status, output.outcome);
+    }
+    if let Some(cb) = &spec.breaker {
+        breaker::record(state, id, cb, output.outcome, status);
     }
 
+    // 5) 清掉「執行中」並保存結果（含斷路器狀態）
+    guard.finish();
+    persist(state).await?;
+
     Ok(())
 }
 
-/// 執行當前任務，並「迭代」展開整條依賴鏈（不遞迴、不 spawn）
+/// 外部訊息到達：啟動所有 trigger 符合的任務（source 只用於日誌），
+/// 訊息帶的追查資訊記在每個 run 上
+fn fire_triggers(
+    state: &Arc<State>,
+    source: &str,
+    metadata: BTreeMap<String, String>,
+    hit: impl Fn(&Trigger) -> bool,
+) {
+    let bound: Vec<_> = state
+        .tasks
+        .iter()
+        .filter(|kv| kv.value().spec.triggers.iter().any(&hit))
+        .map(|kv| (*kv.key(), kv.value().spec.clone()))
+        .collect();
+    for (id, spec) in bound {
+        // 太頻繁的觸發直接略過；一陣觸發湧入時由佇列決定收不收
+        if throttle::check_trigger(state, id, &spec).is_err() {
+            continue;
+        }
+        let Ok(slot) = enter_queue(state, id) else {
+            continue;
+        };
+        println!("📡 task {} triggered by {}", id, source);
+        let (st, metadata) = (state.clone(), metadata.clone());
+        tokio::spawn(async move {
+            if let Err(e) = run_with_metadata(id, spec, st, metadata, Some(slot), None).await {
+                eprintln!("task {} run error: {e:?}", id);
+            }
+        });
+    }
+}
+
+/// 所有任務某一種 trigger 的 topic/subject（去重）
+fn trigger_subjects(state: &State, pick: impl Fn(&Trigger) -> Option<&String>) -> BTreeSet<String> {
+    state
+        .tasks
+        .iter()
+        .flat_map(|kv| {
+            kv.value()
+                .spec
+                .triggers
+                .iter()
+                .filter_map(&pick)
+                .cloned()
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// 執行當前任務，並「迭代」展開整條依賴鏈（不遞迴）；有延遲的依賴另行排定
 async fn run_once_and_record(id: u64, spec: TaskSpec, state: Arc<State>) -> Result<()> {
+    run_with_metadata(id, spec, state, BTreeMap::new(), None, None).await
+}
+
+/// 同 run_once_and_record，當前任務的 run 記下觸發者附上的追查資訊；
+/// slot 為觸發端已排入佇列的位置（佇列滿時觸發端直接回覆拒絕），
+/// run_id 為觸發端預先配發的 run 編號（RunNow --follow 依此登記 follower）
+async fn run_with_metadata(
+    id: u64,
+    spec: TaskSpec,
+    state: Arc<State>,
+    metadata: BTreeMap<String, String>,
+    slot: Option<Slot>,
+    run_id: Option<u64>,
+) -> Result<()> {
     // 先跑當前任務
-    execute_once(id, &spec, &state).await?;
-
-    // 準備 queue：待執行的依賴 (dep_id, spec, delay_secs)
-    let mut q: VecDeque<(u64, TaskSpec, u64)> = VecDeque::new();
-
-    // 第一層依賴（複製資料，避免持有 guard 跨 await）
-    if let Some(dependents) = state.watchers.get(&id) {
-        for dep_id in dependents.value().clone() {
-            if let Some(ent) = state.tasks.get(&dep_id) {
-                if let Schedule::After { task_id, delay_secs } = ent.value().spec.schedule.clone() {
-                    if task_id == id {
-                        q.push_back((dep_id, ent.value().spec.clone(), delay_secs));
-                    }
+    let expired =
+        execute_watching_dependents(id, &spec, &state, None, metadata, slot, run_id).await?;
+    expand_chain(id, expired, &state).await;
+    Ok(())
+}
+
+/// RetryChain 的一個節點：以當時前置 run 的結果執行，之後照常展開依賴
+async fn retry_chained(id: u64, retry: chain::Retry, state: Arc<State>) -> Result<()> {
+    let expired = execute_watching_dependents(
+        id,
+        &retry.spec,
+        &state,
+        retry.upstream,
+        BTreeMap::new(),
+        None,
+        None,
+    )
+    .await?;
+    expand_chain(id, expired, &state).await;
+    Ok(())
+}
+
+/// 延遲時間到的依賴（由 chain 排定，等待期間佔著佇列位置）：執行後繼續展開後續依賴
+async fn run_chained(id: u64, spec: TaskSpec, state: Arc<State>, slot: Slot) {
+    if let Some(expired) = execute_dependent(id, &spec, &state, Some(slot)).await {
+        expand_chain(id, expired, &state).await;
+    }
+}
+
+/// 以 BFS 展開 id 完成後的依賴；無延遲的直接執行，有延遲的排定為待執行的 run
+async fn expand_chain(id: u64, expired: HashSet<u64>, state: &Arc<State>) {
+    // queue：(已完成的任務, 其逾時略過的依賴)
+    let mut q: VecDeque<(u64, HashSet<u64>)> = VecDeque::from([(id, expired)]);
+    while let Some((done_id, expired)) = q.pop_front() {
+        for (dep_id, dep_spec) in dependents_of(state, done_id) {
+            if expired.contains(&dep_id) {
+                continue;
+            }
+            let delay_secs = after_delay(&dep_spec);
+            if delay_secs > 0 {
+                let due = local_now_fixed() + chrono::Duration::seconds(delay_secs as i64);
+                chain::schedule(state, dep_id, due);
+                if let Err(e) = persist(state).await {
+                    eprintln!("task {} persist chained run error: {e:?}", dep_id);
                 }
+                continue;
+            }
+            if let Some(expired) = execute_dependent(dep_id, &dep_spec, state, None).await {
+                q.push_back((dep_id, expired));
             }
         }
     }
+}
 
-    // 逐一處理 queue（BFS/迭代）
-    while let Some((cur_id, cur_spec, delay_secs)) = q.pop_front() {
-        if delay_secs > 0 {
-            sleep(Duration::from_secs(delay_secs)).await;
-        }
-        if let Err(e) = execute_once(cur_id, &cur_spec, &state).await {
-            eprintln!("dependent task {} run error: {:?}", cur_id, e);
-            // 不中斷鏈，繼續處理後續依賴
+/// 執行依賴任務；失敗或被節流不中斷鏈，只有核准關卡未通過時回傳 None（後續依賴不執行）
+async fn execute_dependent(
+    id: u64,
+    spec: &TaskSpec,
+    state: &Arc<State>,
+    slot: Option<Slot>,
+) -> Option<HashSet<u64>> {
+    if throttle::check_trigger(state, id, spec).is_err() {
+        return Some(HashSet::new());
+    }
+    match execute_watching_dependents(id, spec, state, None, BTreeMap::new(), slot, None).await {
+        Ok(expired) => Some(expired),
+        Err(e) if e.is::<approval::NotApproved>() => None,
+        Err(e) => {
+            eprintln!("dependent task {} run error: {:?}", id, e);
+            Some(HashSet::new())
         }
+    }
+}
 
-        // 推展「以 cur_id 為前置」的後續依賴
-        if let Some(dependents) = state.watchers.get(&cur_id) {
-            for dep_id in dependents.value().clone() {
-                if let Some(ent) = state.tasks.get(&dep_id) {
-                    if let Schedule::After { task_id, delay_secs } =
-                        ent.value().spec.schedule.clone()
-                    {
-                        if task_id == cur_id {
-                            q.push_back((dep_id, ent.value().spec.clone(), delay_secs));
-                        }
-                    }
+/// 以 id 為前置的依賴任務（複製資料，避免持有 guard 跨 await）
+fn dependents_of(state: &State, id: u64) -> Vec<(u64, TaskSpec)> {
+    let Some(dependents) = state.watchers.get(&id).map(|d| d.value().clone()) else {
+        return Vec::new();
+    };
+    dependents
+        .into_iter()
+        .filter_map(|dep_id| {
+            let ent = state.tasks.get(&dep_id)?;
+            match ent.value().spec.schedule {
+                Schedule::After { task_id, .. } if task_id == id => {
+                    Some((dep_id, ent.value().spec.clone()))
                 }
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+fn after_delay(spec: &TaskSpec) -> u64 {
+    match spec.schedule {
+        Schedule::After { delay_secs, .. } => delay_secs,
+        _ => 0,
+    }
+}
+
+/// 執行任務；執行（含排隊等鎖）超過依賴任務的 expires_after_secs 時，
+/// 該依賴本輪記為略過，回傳這些依賴的 id
+async fn execute_watching_dependents(
+    id: u64,
+    spec: &TaskSpec,
+    state: &Arc<State>,
+    upstream: Option<RunResult>,
+    metadata: BTreeMap<String, String>,
+    slot: Option<Slot>,
+    run_id: Option<u64>,
+) -> Result<HashSet<u64>> {
+    let started = tokio::time::Instant::now();
+    let mut deadlines: Vec<(tokio::time::Instant, u64, TaskSpec)> = dependents_of(state, id)
+        .into_iter()
+        .filter_map(|(dep_id, dep_spec)| match dep_spec.schedule {
+            Schedule::After {
+                expires_after_secs: Some(secs),
+                ..
+            } => Some((started + Duration::from_secs(secs), dep_id, dep_spec)),
+            _ => None,
+        })
+        .collect();
+    deadlines.sort_by_key(|(at, dep_id, _)| (*at, *dep_id));
+    let mut deadlines = VecDeque::from(deadlines);
+
+    let mut expired = HashSet::new();
+    let run = execute_once(id, spec, state, upstream, metadata, slot, run_id);
+    tokio::pin!(run);
+    loop {
+        let next = deadlines.front().map(|(at, ..)| *at);
+        tokio::select! {
+            res = &mut run => return res.map(|()| expired),
+            _ = tokio::time::sleep_until(next.unwrap_or(started)), if next.is_some() => {
+                let Some((_, dep_id, dep_spec)) = deadlines.pop_front() else { continue };
+                let secs = started.elapsed().as_secs();
+                println!(
+                    "⌛ dependent task {} expired: task {} still unfinished after {}s",
+                    dep_id, id, secs
+                );
+                record_skip(
+                    state,
+                    dep_id,
+                    &dep_spec,
+                    None,
+                    format!("upstream task {id} did not finish within {secs}s"),
+                ).await;
+                expired.insert(dep_id);
             }
         }
     }
+}
 
-    Ok(())
+/// 二進位輸出時另存 stderr 的檔案：`<輸出檔>.stderr`
+fn binary_stderr_path(output_path: &Path) -> PathBuf {
+    let mut name = output_path.as_os_str().to_owned();
+    name.push(".stderr");
+    PathBuf::from(name)
+}
+
+/// 排入等待開始的 run 佇列；佇列滿而不收時發出 RunSkipped
+fn enter_queue(state: &Arc<State>, id: u64) -> Result<Slot> {
+    state.queue.enter(&state.config.queue, id).inspect_err(|e| {
+        eprintln!("⚠️ task {} {e:#}", id);
+        state.events.emit(EventKind::RunSkipped {
+            task_id: id,
+            reason: format!("{e:#}"),
+        });
+    })
+}
+
+/// 觸發被拒（佇列已滿、節流）時回覆客戶端的錯誤
+fn trigger_rejected(e: anyhow::Error) -> SchedulerError {
+    SchedulerError::InvalidRequest {
+        msg: format!("{e:#}"),
+    }
+}
+
+/// 排隊中被 drop-oldest 擠出佇列：發出 RunSkipped，回傳給呼叫端的錯誤
+fn evicted(state: &State, id: u64) -> anyhow::Error {
+    state.events.emit(EventKind::RunSkipped {
+        task_id: id,
+        reason: "run queue full: dropped oldest pending run".to_string(),
+    });
+    anyhow::anyhow!("task {id} dropped from the run queue")
+}
+
+/// 未執行就略過的一輪：記一筆 Skipped 到 last_result 與歷史，並發出 RunSkipped；
+/// run_id 未指定時另行配發
+async fn record_skip(
+    state: &Arc<State>,
+    id: u64,
+    spec: &TaskSpec,
+    run_id: Option<u64>,
+    reason: String,
+) {
+    let result = RunResult {
+        run_id: run_id.unwrap_or_else(|| state.ids.next_run()),
+        started_at: None,
+        finished_at: local_now_fixed(),
+        status_code: -1,
+        exit_code: None,
+        upstream_run: None,
+        outcome: RunOutcome::Skipped,
+        stdout_len: 0,
+        stderr_len: 0,
+        wrote_to: defaults::output_path(&state.config.defaults, &spec.output_path),
+        outputs: Default::default(),
+        output_size: None,
+        output_sha256: None,
+        binary: false,
+        artifact: None,
+        usage: None,
+        cleanup: None,
+        metadata: Default::default(),
+    };
+    if let Some(ent) = state.tasks.get(&id) {
+        let last = ent.value().last_result.clone();
+        drop(ent);
+        *last.lock().unwrap() = Some(result.clone());
+    }
+    history::record(
+        state,
+        RunRecord {
+            task_id: id,
+            result,
+        },
+    )
+    .await;
+    state.events.emit(EventKind::RunSkipped {
+        task_id: id,
+        reason,
+    });
 }
 
 // ===== 持久化：最小實作 =====
@@ -303,47 +2339,186 @@ async fn persist(state: &Arc<State>) -> Result<()> {
     struct Rec {
         id: u64,
         spec: TaskSpec,
+        last_result: Option<RunResult>,
+        running_since: Option<DateTime<FixedOffset>>,
+        breaker: Option<BreakerState>,
+        chained: Vec<DateTime<FixedOffset>>,
     }
 
+    // 快照與寫檔都在鎖內：較晚取得的快照一定較晚落地，不會被舊的覆蓋
+    let _guard = state.persisting.lock().unwrap();
     let mut arr = Vec::new();
     for kv in state.tasks.iter() {
+        let id = *kv.key();
+        if kv.value().spec.namespace == SYSTEM_NAMESPACE {
+            continue; // 內建任務每次啟動依設定重建
+        }
+        let running_since = state
+            .running
+            .iter()
+            .filter(|r| r.value().task_id == id)
+            .map(|r| r.value().started_at)
+            .min();
         arr.push(Rec {
-            id: *kv.key(),
+            id,
             spec: kv.value().spec.clone(),
+            last_result: kv.value().last_result.lock().unwrap().clone(),
+            running_since,
+            breaker: state.breakers.get(&id).map(|b| b.value().clone()),
+            chained: chain::due_times(state, id),
         });
     }
 
+    // 先寫暫存檔再改名：中途當掉也不會留下殘缺的任務檔
+    let path = &state.config.data_path;
+    let tmp = path.with_extension("json.tmp");
     let s = serde_json::to_string_pretty(&arr)?;
-    std::fs::write(&state.data_path, s)?;
+    std::fs::write(&tmp, s).with_context(|| format!("write {}", tmp.display()))?;
+    std::fs::rename(&tmp, path).with_context(|| format!("replace {}", path.display()))?;
     Ok(())
 }
 
-async fn load_persisted(state: &Arc<State>, path: &Path) -> Result<()> {
+/// 載入持久化任務；回傳的報告只填了載入數與無效的任務
+async fn load_persisted(state: &Arc<State>, path: &Path) -> Result<StartupReport> {
     #[derive(serde::Deserialize)]
     struct Rec {
         id: u64,
         spec: TaskSpec,
+        #[serde(default)]
+        last_result: Option<RunResult>,
+        #[serde(default)]
+        running_since: Option<DateTime<FixedOffset>>,
+        #[serde(default)]
+        breaker: Option<BreakerState>,
+        #[serde(default)]
+        chained: Vec<DateTime<FixedOffset>>,
     }
 
     let bytes = std::fs::read(path)?;
-    let list: Vec<Rec> = serde_json::from_slice(&bytes[..])?;
-    let mut max_id = 0u64;
+    let mut orphans: Vec<(u64, TaskSpec, DateTime<FixedOffset>)> = Vec::new();
+    let mut chained: Vec<(u64, DateTime<FixedOffset>)> = Vec::new();
+    let mut reboot: Vec<(u64, TaskSpec)> = Vec::new();
+    let mut report = StartupReport::default();
 
-    for r in list {
-        max_id = max_id.max(r.id);
+    // 逐筆解析：壞掉的紀錄移到隔離檔，其餘照常載入
+    let list: Vec<serde_json::Value> = match serde_json::from_slice(&bytes[..]) {
+        Ok(list) => list,
+        Err(e) => {
+            let text = String::from_utf8_lossy(&bytes).into_owned();
+            state
+                .quarantine
+                .put(None, format!("unreadable data file: {e}"), text)?;
+            eprintln!(
+                "⚠️ {} is unreadable and was quarantined: {e}",
+                path.display()
+            );
+            report.quarantined += 1;
+            persist(state).await?;
+            return Ok(report);
+        }
+    };
+    let mut seen = HashSet::new();
+    let mut records = Vec::new();
+    for v in list {
+        let id = v.get("id").and_then(|id| id.as_u64());
+        let reason = match serde_json::from_value::<Rec>(v.clone()) {
+            Ok(r) if seen.insert(r.id) => {
+                records.push(r);
+                continue;
+            }
+            Ok(r) => format!("duplicate task id {}", r.id),
+            Err(e) => e.to_string(),
+        };
+        // 先寫進隔離檔：寫不進去就整個載入失敗，不能讓之後的 persist 蓋掉它
+        state.quarantine.put(id, reason.clone(), v.to_string())?;
+        match id {
+            Some(id) => eprintln!("⚠️ task {id} is malformed and was quarantined: {reason}"),
+            None => eprintln!("⚠️ a malformed task record was quarantined: {reason}"),
+        }
+        report.quarantined += 1;
+    }
+
+    for r in records {
+        state.ids.observe_task(r.id);
+
+        // 上次停機時仍在執行：結果不明，標記為 Orphaned
+        let last_result = match r.running_since {
+            Some(since) => {
+                orphans.push((r.id, r.spec.clone(), since));
+                let result = RunResult {
+                    run_id: state.ids.next_run(),
+                    started_at: Some(since),
+                    finished_at: local_now_fixed(),
+                    status_code: -1,
+                    exit_code: None,
+                    upstream_run: None,
+                    outcome: RunOutcome::Orphaned,
+                    stdout_len: 0,
+                    stderr_len: 0,
+                    wrote_to: defaults::output_path(&state.config.defaults, &r.spec.output_path),
+                    outputs: Default::default(),
+                    output_size: None,
+                    output_sha256: None,
+                    binary: false,
+                    artifact: None,
+                    usage: None,
+                    cleanup: None,
+                    metadata: Default::default(),
+                };
+                state.events.emit(EventKind::RunFinished {
+                    task_id: r.id,
+                    run_id: result.run_id,
+                    status_code: result.status_code,
+                    outcome: result.outcome,
+                    metadata: Default::default(),
+                });
+                let rec = RunRecord {
+                    task_id: r.id,
+                    result: result.clone(),
+                };
+                history::record(state, rec).await;
+                Some(result)
+            }
+            None => r.last_result,
+        };
+
+        if let Some(b) = r.breaker {
+            state.breakers.insert(r.id, b);
+        }
+        report.tasks_loaded += 1;
 
         let base = TaskEntry {
             spec: r.spec.clone(),
             cancel: None,
-            last_result: Arc::new(Mutex::new(None)),
+            last_result: Arc::new(Mutex::new(last_result)),
         };
 
+        // 規格已不再有效（例如命令政策收緊、具名排程被刪除）：保留但不排程，修正後再 update
+        if let Err(e) = startup::check_loaded(state, &r.spec) {
+            eprintln!("⚠️ task {} is invalid and will not run: {e:#}", r.id);
+            report.invalid.push(InvalidTask {
+                id: r.id,
+                reason: format!("{e:#}"),
+            });
+            state.tasks.insert(r.id, base);
+            continue;
+        }
+        chained.extend(r.chained.iter().map(|due| (r.id, *due)));
+
         let entry = match &r.spec.schedule {
             Schedule::After { task_id, .. } => {
                 state.watchers.entry(*task_id).or_default().push(r.id);
                 base
             }
-            Schedule::Once(_) | Schedule::Daily { .. } => {
+            Schedule::Manual => base,
+            Schedule::Reboot => {
+                reboot.push((r.id, r.spec.clone()));
+                base
+            }
+            Schedule::Once(_)
+            | Schedule::Daily { .. }
+            | Schedule::Hourly { .. }
+            | Schedule::Named { .. } => {
                 let tok = CancellationToken::new();
                 spawn_scheduler_loop(r.id, r.spec.clone(), tok.clone(), state.clone());
                 TaskEntry {
@@ -351,44 +2526,78 @@ async fn load_persisted(state: &Arc<State>, path: &Path) -> Result<()> {
                     ..base
                 }
             }
+            // 較新版本寫下的排程：保留任務但不執行
+            _ => {
+                eprintln!(
+                    "⚠️ task {} has an unrecognized schedule and will not run",
+                    r.id
+                );
+                report.invalid.push(InvalidTask {
+                    id: r.id,
+                    reason: "unrecognized schedule".to_string(),
+                });
+                base
+            }
         };
 
         state.tasks.insert(r.id, entry);
     }
 
-    state
-        .next_id
-        .store(max_id.saturating_add(1), Ordering::SeqCst);
-    Ok(())
-}
-
-// ===== 時間/工具（統一 FixedOffset） =====
-fn ensure_parent_dir(p: &Path) -> Result<()> {
-    if let Some(parent) = p.parent() {
-        std::fs::create_dir_all(parent)?;
+    // 停機前排定的延遲依賴：任務都載入後再排定，已過時的立即執行
+    for (id, due) in chained {
+        chain::schedule(state, id, due);
+    }
+    for (id, spec) in reboot {
+        println!("🔁 task {} runs at startup (@reboot)", id);
+        let st = state.clone();
+        tokio::spawn(async move {
+            if let Err(e) = run_once_and_record(id, spec, st).await {
+                eprintln!("task {} run error: {e:?}", id);
+            }
+        });
     }
-    Ok(())
-}
 
-fn next_daily_at(hour: u32, minute: u32) -> DateTime<FixedOffset> {
-    let now = Local::now();
-    let today = now
-        .with_hour(hour)
-        .and_then(|t| t.with_minute(minute))
-        .and_then(|t| t.with_second(0))
-        .unwrap();
-    let next_local = if today > now {
-        today
-    } else {
-        today + chrono::Duration::days(1)
-    };
-    next_local.fixed_offset()
+    if !orphans.is_empty() {
+        let policy = state.config.recovery.orphan_policy;
+        println!(
+            "♻️ startup reconciliation: {} run(s) were in progress when the server stopped (policy {:?})",
+            orphans.len(),
+            policy
+        );
+        for (id, spec, since) in orphans {
+            println!("   - task {} running since {}", id, since);
+            // Once 任務在載入時已由排程迴圈補跑，@reboot 任務啟動時本來就會跑，不重複觸發
+            let rerun = policy == OrphanPolicy::Rerun
+                && !matches!(spec.schedule, Schedule::Once(_) | Schedule::Reboot)
+                && !report.invalid.iter().any(|t| t.id == id);
+            report.orphans.push(OrphanedRun {
+                task_id: id,
+                running_since: since,
+                rerun,
+            });
+            if rerun {
+                let st = state.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = run_once_and_record(id, spec, st).await {
+                        eprintln!("task {} rerun error: {e:?}", id);
+                    }
+                });
+            }
+        }
+        persist(state).await?;
+    }
+    // 隔離的紀錄已另存，從持久化檔拿掉
+    if report.quarantined > 0 {
+        persist(state).await?;
+    }
+    Ok(report)
 }
 
+// ===== 時間/工具（統一 FixedOffset） =====
 fn duration_to(when: DateTime<FixedOffset>) -> Duration {
+    // 不可截成整秒：提早醒來會讓 Daily 在同一分鐘內重複觸發
     let now = Local::now().fixed_offset();
-    let secs = (when - now).num_seconds().max(0) as u64;
-    Duration::from_secs(secs)
+    (when - now).to_std().unwrap_or(Duration::ZERO)
 }
 
 fn local_now_fixed() -> DateTime<FixedOffset> {
@@ -0,0 +1,264 @@
+use crate::{
+    configtasks, duration_to, is_protected, is_system_task, local_now_fixed, trash_task, State,
+};
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, FixedOffset};
+use scheduler_core::{MaintenanceAction, MaintenanceKind, SchedulerError};
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::{sync::Notify, time::sleep};
+
+/// 沒有更早到期的預約時，最久隔多久再檢查一次（系統時間被調整時也能跟上）
+const IDLE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// 預約的維護動作：移除在 at 執行，暫停在 until 結束，之後從檔案刪除。
+/// 項目不多，每次變更整份改寫
+pub struct Maintenance {
+    path: PathBuf,
+    items: Mutex<Vec<MaintenanceAction>>,
+    /// 預約增減時喚醒執行迴圈
+    changed: Notify,
+}
+
+impl Maintenance {
+    pub fn load(path: &Path) -> Result<Self> {
+        let items = if path.exists() {
+            let text = std::fs::read_to_string(path)
+                .with_context(|| format!("read {}", path.display()))?;
+            serde_json::from_str(&text).with_context(|| format!("parse {}", path.display()))?
+        } else {
+            Vec::new()
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            items: Mutex::new(items),
+            changed: Notify::new(),
+        })
+    }
+
+    /// 新增一筆預約，回傳編號
+    fn put(
+        &self,
+        task_id: u64,
+        action: MaintenanceKind,
+        at: DateTime<FixedOffset>,
+        force: bool,
+        actor: &str,
+    ) -> Result<u64> {
+        let mut items = self.items.lock().unwrap();
+        let seq = items.iter().map(|a| a.seq).max().unwrap_or(0) + 1;
+        items.push(MaintenanceAction {
+            seq,
+            task_id,
+            action,
+            at,
+            force,
+            actor: actor.to_string(),
+            created_at: local_now_fixed(),
+        });
+        self.save(&items)?;
+        self.changed.notify_one();
+        Ok(seq)
+    }
+
+    /// 依生效時間排序
+    pub fn list(&self) -> Vec<MaintenanceAction> {
+        let mut items = self.items.lock().unwrap().clone();
+        items.sort_by_key(|a| (a.at, a.seq));
+        items
+    }
+
+    /// 取消一筆；不存在時為 None
+    pub fn cancel(&self, seq: u64) -> Result<Option<MaintenanceAction>> {
+        let mut items = self.items.lock().unwrap();
+        let Some(pos) = items.iter().position(|a| a.seq == seq) else {
+            return Ok(None);
+        };
+        let item = items.remove(pos);
+        self.save(&items)?;
+        self.changed.notify_one();
+        Ok(Some(item))
+    }
+
+    /// 任務目前在暫停中時，回傳最晚的恢復時間
+    pub fn paused_until(
+        &self,
+        task_id: u64,
+        now: DateTime<FixedOffset>,
+    ) -> Option<DateTime<FixedOffset>> {
+        let items = self.items.lock().unwrap();
+        items
+            .iter()
+            .filter(|a| a.task_id == task_id && a.at <= now)
+            .filter_map(|a| match &a.action {
+                MaintenanceKind::Pause { until } if *until > now => Some(*until),
+                _ => None,
+            })
+            .max()
+    }
+
+    /// 取出到期（移除已到 at、暫停已到 until）或任務已不存在的預約
+    fn take_due(
+        &self,
+        now: DateTime<FixedOffset>,
+        exists: impl Fn(u64) -> bool,
+    ) -> Result<Vec<MaintenanceAction>> {
+        let mut items = self.items.lock().unwrap();
+        let (due, keep): (Vec<_>, Vec<_>) = items
+            .drain(..)
+            .partition(|a| !exists(a.task_id) || end_of(a) <= now);
+        *items = keep;
+        if !due.is_empty() {
+            self.save(&items)?;
+        }
+        Ok(due)
+    }
+
+    /// 下一筆預約到期的時間
+    fn next_due(&self) -> Option<DateTime<FixedOffset>> {
+        self.items.lock().unwrap().iter().map(end_of).min()
+    }
+
+    /// 先寫暫存檔再改名，避免改寫到一半留下殘缺的檔案
+    fn save(&self, items: &[MaintenanceAction]) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let tmp = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec_pretty(items)?)
+            .with_context(|| format!("write {}", tmp.display()))?;
+        std::fs::rename(&tmp, &self.path)
+            .with_context(|| format!("replace {}", self.path.display()))
+    }
+}
+
+/// 預約完成的時間：暫停到 until 結束，其他動作在 at 執行
+fn end_of(a: &MaintenanceAction) -> DateTime<FixedOffset> {
+    match &a.action {
+        MaintenanceKind::Pause { until } => *until,
+        _ => a.at,
+    }
+}
+
+/// ScheduleMaintenance：檢查後寫入預約
+pub fn schedule(
+    state: &State,
+    task_id: u64,
+    action: MaintenanceKind,
+    at: DateTime<FixedOffset>,
+    force: bool,
+    actor: &str,
+) -> Result<u64> {
+    if !state.tasks.contains_key(&task_id) {
+        bail!(SchedulerError::NotFound {
+            msg: format!("task {task_id} not found"),
+        });
+    }
+    if is_system_task(state, task_id) {
+        bail!(SchedulerError::Unauthorized {
+            msg: format!("task {task_id} is a built-in task and cannot be maintained"),
+        });
+    }
+    let now = local_now_fixed();
+    match &action {
+        MaintenanceKind::Pause { until } if *until <= at => bail!(SchedulerError::InvalidRequest {
+            msg: format!("pause must end after it starts ({until} <= {at})"),
+        }),
+        MaintenanceKind::Pause { until } if *until <= now => {
+            bail!(SchedulerError::InvalidRequest {
+                msg: format!("pause already ended at {until}"),
+            })
+        }
+        MaintenanceKind::Pause { .. } => {}
+        MaintenanceKind::Remove if configtasks::is_config_task(state, task_id) => {
+            bail!(SchedulerError::Unauthorized {
+                msg: format!(
+                    "task {task_id} is defined in the server config; only pause is allowed"
+                ),
+            })
+        }
+        MaintenanceKind::Remove if is_protected(state, task_id) && !force => {
+            bail!(SchedulerError::Unauthorized {
+                msg: format!("task {task_id} is protected; use force to remove it"),
+            })
+        }
+        MaintenanceKind::Remove => {}
+        _ => bail!(SchedulerError::InvalidRequest {
+            msg: "unsupported maintenance action".to_string(),
+        }),
+    }
+    let seq = state
+        .maintenance
+        .put(task_id, action.clone(), at, force, actor)?;
+    match action {
+        MaintenanceKind::Pause { until } => {
+            println!(
+                "🛠️ task {task_id} pause scheduled from {at} until {until} by {actor} (#{seq})"
+            )
+        }
+        _ => println!("🛠️ task {task_id} removal scheduled at {at} by {actor} (#{seq})"),
+    }
+    Ok(seq)
+}
+
+/// 執行到期的預約；啟動時先跑一次，之後在下一筆到期或預約增減時醒來
+pub fn spawn(state: Arc<State>) {
+    tokio::spawn(async move {
+        loop {
+            run_due(&state).await;
+            let wait = state
+                .maintenance
+                .next_due()
+                .map_or(IDLE_INTERVAL, |at| duration_to(at).min(IDLE_INTERVAL));
+            tokio::select! {
+                _ = sleep(wait) => {}
+                _ = state.maintenance.changed.notified() => {}
+            }
+        }
+    });
+}
+
+async fn run_due(state: &Arc<State>) {
+    let due = match state
+        .maintenance
+        .take_due(local_now_fixed(), |id| state.tasks.contains_key(&id))
+    {
+        Ok(due) => due,
+        Err(e) => {
+            eprintln!("maintenance error: {e:#}");
+            return;
+        }
+    };
+    for a in due {
+        let id = a.task_id;
+        if !state.tasks.contains_key(&id) {
+            println!(
+                "🛠️ maintenance #{} dropped: task {id} no longer exists",
+                a.seq
+            );
+            continue;
+        }
+        match a.action {
+            MaintenanceKind::Pause { .. } => {
+                println!("🛠️ task {id} resumed after maintenance (#{})", a.seq)
+            }
+            MaintenanceKind::Remove if is_protected(state, id) && !a.force => {
+                eprintln!(
+                    "🛠️ maintenance #{} not run: task {id} became protected",
+                    a.seq
+                )
+            }
+            MaintenanceKind::Remove => {
+                let actor = format!("{} (maintenance #{})", a.actor, a.seq);
+                match trash_task(state, id, &actor).await {
+                    Ok(_) => println!("🛠️ task {id} removed by maintenance #{}", a.seq),
+                    Err(e) => eprintln!("task {id} maintenance removal error: {e:#}"),
+                }
+            }
+            _ => {}
+        }
+    }
+}
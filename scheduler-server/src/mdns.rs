@@ -0,0 +1,78 @@
+use crate::State;
+use anyhow::{Context, Result};
+use mdns_sd::{ServiceDaemon, ServiceInfo};
+use scheduler_core::MDNS_SERVICE_TYPE;
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
+
+/// 進行中的 mDNS 宣告；關機時呼叫 `stop` 送出 goodbye，讓客戶端立即移除
+pub struct Advertisement {
+    daemon: ServiceDaemon,
+    fullname: String,
+}
+
+/// 在區網宣告第一個非 loopback 的 TCP 端點；未設定 [mdns] 或沒有這種端點時不宣告
+pub fn advertise(
+    state: &Arc<State>,
+    tcp: &[SocketAddr],
+    ws: Option<SocketAddr>,
+) -> Result<Option<Advertisement>> {
+    let Some(cfg) = &state.config.mdns else {
+        return Ok(None);
+    };
+    let Some(addr) = tcp.iter().find(|a| !a.ip().is_loopback()).copied() else {
+        println!("📡 mdns skipped: no TCP listener is reachable from the LAN");
+        return Ok(None);
+    };
+
+    let (hostname, environment) = {
+        let ctx = state.context.read().unwrap();
+        (ctx.hostname.clone(), ctx.environment.clone())
+    };
+    // mDNS 主機名稱只取第一段，例如 "edge-01.example.com" → "edge-01.local."
+    let host = hostname.split('.').next().unwrap_or("scheduler");
+    let instance = cfg.instance.clone().unwrap_or_else(|| host.to_string());
+    let mut props = vec![
+        ("version", env!("CARGO_PKG_VERSION").to_string()),
+        ("environment", environment),
+    ];
+    if let Some(ws) = ws {
+        props.push(("ws_port", ws.port().to_string()));
+    }
+
+    // 綁在 0.0.0.0 / :: 時由 daemon 自動帶入各網卡位址
+    let auto_addr = addr.ip().is_unspecified();
+    let ips: Vec<IpAddr> = if auto_addr { vec![] } else { vec![addr.ip()] };
+    let mut info = ServiceInfo::new(
+        MDNS_SERVICE_TYPE,
+        &instance,
+        &format!("{host}.local."),
+        ips.as_slice(),
+        addr.port(),
+        props.as_slice(),
+    )
+    .context("invalid mdns service info")?;
+    if auto_addr {
+        info = info.enable_addr_auto();
+    }
+    let fullname = info.get_fullname().to_string();
+
+    let daemon = ServiceDaemon::new().context("failed to start mdns daemon")?;
+    daemon
+        .register(info)
+        .context("failed to register mdns service")?;
+    println!("📡 mdns advertising {fullname} on port {}", addr.port());
+    Ok(Some(Advertisement { daemon, fullname }))
+}
+
+impl Advertisement {
+    pub fn stop(self) {
+        // goodbye 封包送出後才關閉 daemon；失敗也不影響關機
+        if let Ok(done) = self.daemon.unregister(&self.fullname) {
+            let _ = done.recv_timeout(std::time::Duration::from_secs(1));
+        }
+        let _ = self.daemon.shutdown();
+    }
+}
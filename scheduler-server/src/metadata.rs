@@ -0,0 +1,72 @@
+use scheduler_core::SchedulerError;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// 一次 run 最多附幾個項目；追查資訊只需要幾個編號，不是拿來傳資料的
+const MAX_ENTRIES: usize = 32;
+const MAX_KEY_LEN: usize = 64;
+const MAX_VALUE_LEN: usize = 1024;
+
+/// 觸發時附上的追查資訊（RunResult.metadata）檢查：數量與長度有上限，名稱不可為空或含控制字元
+pub fn check(metadata: &BTreeMap<String, String>) -> Result<(), SchedulerError> {
+    let invalid = |msg: String| Err(SchedulerError::InvalidRequest { msg });
+    if metadata.len() > MAX_ENTRIES {
+        return invalid(format!(
+            "at most {MAX_ENTRIES} metadata entries are allowed"
+        ));
+    }
+    for (key, value) in metadata {
+        if key.is_empty() || key.len() > MAX_KEY_LEN || key.chars().any(char::is_control) {
+            return invalid(format!(
+                "invalid metadata key {key:?} (1-{MAX_KEY_LEN} characters, no control characters)"
+            ));
+        }
+        if value.len() > MAX_VALUE_LEN {
+            return invalid(format!(
+                "metadata {key:?} is longer than {MAX_VALUE_LEN} bytes"
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// JSON 物件轉成追查資訊；值只接受字串、數字與布林
+pub fn from_json(value: Value) -> Result<BTreeMap<String, String>, SchedulerError> {
+    let Value::Object(map) = value else {
+        return Err(SchedulerError::InvalidRequest {
+            msg: "metadata must be a JSON object".to_string(),
+        });
+    };
+    let mut metadata = BTreeMap::new();
+    for (key, value) in map {
+        let value = match value {
+            Value::String(s) => s,
+            Value::Number(n) => n.to_string(),
+            Value::Bool(b) => b.to_string(),
+            _ => {
+                return Err(SchedulerError::InvalidRequest {
+                    msg: format!("metadata {key:?} must be a string, number or bool"),
+                })
+            }
+        };
+        metadata.insert(key, value);
+    }
+    check(&metadata)?;
+    Ok(metadata)
+}
+
+/// 外部觸發（MQTT、bridge）的訊息內容：JSON 物件中的 "metadata" 物件即追查資訊；
+/// 其他格式的訊息照常觸發，只是不帶追查資訊
+pub fn from_payload(payload: &[u8]) -> BTreeMap<String, String> {
+    let Ok(Value::Object(mut msg)) = serde_json::from_slice(payload) else {
+        return BTreeMap::new();
+    };
+    match msg.remove("metadata").map(from_json) {
+        Some(Ok(metadata)) => metadata,
+        Some(Err(e)) => {
+            eprintln!("trigger message metadata ignored: {e}");
+            BTreeMap::new()
+        }
+        None => BTreeMap::new(),
+    }
+}
@@ -0,0 +1,280 @@
+use crate::{config::MetricsExportConfig, history, State};
+use anyhow::{bail, Context, Result};
+use scheduler_core::{EventKind, QueueStats, RunOutcome, RunRecord, TaskSpec};
+use std::{fmt::Write, path::Path, sync::Arc, time::Duration};
+use tokio::sync::broadcast;
+
+/// 伺服器自己加上的標籤，任務不可覆寫
+const RESERVED_LABELS: [&str; 4] = ["task_id", "name", "namespace", "job"];
+
+/// 檢查任務的指標標籤：名稱須符合 Prometheus 規則，且不可與內建標籤重複
+pub fn check_labels(spec: &TaskSpec) -> Result<()> {
+    for key in spec.metric_labels.keys() {
+        let mut chars = key.chars();
+        let valid = chars
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid || key.starts_with("__") {
+            bail!("metric label {key:?} is not a valid Prometheus label name");
+        }
+        if RESERVED_LABELS.contains(&key.as_str()) {
+            bail!("metric label {key:?} is reserved");
+        }
+    }
+    Ok(())
+}
+
+/// 佇列指標的匯出間隔；排隊深度沒有對應的事件，定期匯出
+const QUEUE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// 每次執行結束後匯出該任務的指標；任務移除時清掉。
+/// run 佇列的深度與丟棄、拒絕次數定期匯出，有 run 結束或被略過時也更新
+pub fn spawn(state: Arc<State>) {
+    let cfg = state.config.metrics_export.clone();
+    if cfg.pushgateway_url.is_none() && cfg.textfile_dir.is_none() {
+        return;
+    }
+    let (_, _, mut rx) = state.events.subscribe(None);
+    tokio::spawn(async move {
+        let mut tick = tokio::time::interval(QUEUE_INTERVAL);
+        loop {
+            let ev = tokio::select! {
+                ev = rx.recv() => ev,
+                _ = tick.tick() => {
+                    if let Err(e) = export_queue(&state, &cfg).await {
+                        eprintln!("metrics export error: {e:#}");
+                    }
+                    continue;
+                }
+            };
+            let ev = match ev {
+                Ok(ev) => ev,
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    eprintln!("metrics export skipped {n} event(s)");
+                    continue;
+                }
+                Err(broadcast::error::RecvError::Closed) => return,
+            };
+            let res = match ev.kind {
+                EventKind::RunFinished {
+                    task_id, run_id, ..
+                } => match export(&state, &cfg, task_id, run_id).await {
+                    Ok(()) => export_queue(&state, &cfg).await,
+                    Err(e) => Err(e),
+                },
+                EventKind::RunSkipped { .. } => export_queue(&state, &cfg).await,
+                EventKind::TaskRemoved { task_id } => remove(&cfg, task_id).await,
+                _ => Ok(()),
+            };
+            if let Err(e) = res {
+                eprintln!("metrics export error: {e:#}");
+            }
+        }
+    });
+}
+
+async fn export(
+    state: &Arc<State>,
+    cfg: &MetricsExportConfig,
+    task_id: u64,
+    run_id: u64,
+) -> Result<()> {
+    let latest = history::blocking(state, move |st| st.history.for_task(task_id, 1)).await?;
+    let Some(rec) = latest.into_iter().find(|r| r.result.run_id == run_id) else {
+        return Ok(());
+    };
+    let spec = state.tasks.get(&task_id).map(|e| e.spec.clone());
+
+    if let Some(dir) = &cfg.textfile_dir {
+        let name = format!("scheduler_task_{task_id}");
+        write_textfile(dir, &name, &render(&rec, spec.as_ref(), true))?;
+    }
+    if let Some(url) = &cfg.pushgateway_url {
+        // task_id 已在 grouping key 中，指標上不重複
+        let body = render(&rec, spec.as_ref(), false);
+        push(cfg, group_url(url, cfg, task_id), body).await?;
+    }
+    Ok(())
+}
+
+/// run 佇列指標；不分任務，pushgateway 上放在只有 job 的 group
+async fn export_queue(state: &State, cfg: &MetricsExportConfig) -> Result<()> {
+    let body = render_queue(&state.queue.stats());
+    if let Some(dir) = &cfg.textfile_dir {
+        write_textfile(dir, "scheduler_queue", &body)?;
+    }
+    if let Some(url) = &cfg.pushgateway_url {
+        let url = format!("{}/metrics/job/{}", url.trim_end_matches('/'), cfg.job);
+        push(cfg, url, body).await?;
+    }
+    Ok(())
+}
+
+async fn push(cfg: &MetricsExportConfig, url: String, body: String) -> Result<()> {
+    let timeout = Duration::from_secs(cfg.timeout_secs.max(1));
+    tokio::task::spawn_blocking(move || {
+        let agent = ureq::AgentBuilder::new().timeout(timeout).build();
+        match agent
+            .put(&url)
+            .set("Content-Type", "text/plain; version=0.0.4")
+            .send_string(&body)
+        {
+            Ok(_) => Ok(()),
+            Err(ureq::Error::Status(code, _)) => bail!("pushgateway HTTP {code}"),
+            Err(e) => Err(e.into()),
+        }
+    })
+    .await?
+}
+
+async fn remove(cfg: &MetricsExportConfig, task_id: u64) -> Result<()> {
+    if let Some(dir) = &cfg.textfile_dir {
+        let path = dir.join(format!("scheduler_task_{task_id}.prom"));
+        if path.exists() {
+            std::fs::remove_file(&path).with_context(|| format!("remove {}", path.display()))?;
+        }
+    }
+    if let Some(url) = &cfg.pushgateway_url {
+        let url = group_url(url, cfg, task_id);
+        let timeout = Duration::from_secs(cfg.timeout_secs.max(1));
+        tokio::task::spawn_blocking(move || {
+            let agent = ureq::AgentBuilder::new().timeout(timeout).build();
+            match agent.delete(&url).call() {
+                Ok(_) | Err(ureq::Error::Status(404, _)) => Ok(()),
+                Err(ureq::Error::Status(code, _)) => bail!("pushgateway HTTP {code}"),
+                Err(e) => Err(e.into()),
+            }
+        })
+        .await??;
+    }
+    Ok(())
+}
+
+fn group_url(base: &str, cfg: &MetricsExportConfig, task_id: u64) -> String {
+    format!(
+        "{}/metrics/job/{}/task_id/{task_id}",
+        base.trim_end_matches('/'),
+        cfg.job
+    )
+}
+
+/// 先寫暫存檔再改名；node_exporter 只讀 .prom，不會讀到寫一半的檔
+fn write_textfile(dir: &Path, name: &str, body: &str) -> Result<()> {
+    std::fs::create_dir_all(dir).with_context(|| format!("create {}", dir.display()))?;
+    let path = dir.join(format!("{name}.prom"));
+    let tmp = dir.join(format!("{name}.prom.tmp"));
+    std::fs::write(&tmp, body).with_context(|| format!("write {}", tmp.display()))?;
+    std::fs::rename(&tmp, &path).with_context(|| format!("replace {}", path.display()))?;
+    Ok(())
+}
+
+/// Prometheus 文字格式
+fn render(rec: &RunRecord, spec: Option<&TaskSpec>, with_task_id: bool) -> String {
+    let r = &rec.result;
+    let mut labels = Vec::new();
+    if with_task_id {
+        labels.push(("task_id", rec.task_id.to_string()));
+    }
+    if let Some(spec) = spec {
+        labels.push(("name", spec.name.clone().unwrap_or_default()));
+        labels.push(("namespace", spec.namespace.clone()));
+        labels.extend(
+            spec.metric_labels
+                .iter()
+                .map(|(k, v)| (k.as_str(), v.clone())),
+        );
+    }
+    let labels = labels
+        .iter()
+        .map(|(k, v)| format!("{k}=\"{}\"", escape(v)))
+        .collect::<Vec<_>>()
+        .join(",");
+
+    let duration = r
+        .started_at
+        .map(|s| (r.finished_at - s).num_milliseconds().max(0) as f64 / 1000.0);
+    let success = r.outcome == RunOutcome::Exited && r.status_code == 0;
+    let finished = r.finished_at.timestamp_millis() as f64 / 1000.0;
+
+    let mut out = String::new();
+    let mut gauge = |name: &str, help: &str, value: f64| {
+        let _ = writeln!(out, "# HELP {name} {help}");
+        let _ = writeln!(out, "# TYPE {name} gauge");
+        let _ = writeln!(out, "{name}{{{labels}}} {value}");
+    };
+    gauge(
+        "scheduler_run_success",
+        "Whether the last run exited with code 0.",
+        f64::from(u8::from(success)),
+    );
+    gauge(
+        "scheduler_run_exit_code",
+        "Exit code of the last run.",
+        f64::from(r.status_code),
+    );
+    if let Some(d) = duration {
+        gauge(
+            "scheduler_run_duration_seconds",
+            "Duration of the last run.",
+            d,
+        );
+    }
+    gauge(
+        "scheduler_run_finished_timestamp_seconds",
+        "Unix time the last run finished.",
+        finished,
+    );
+    gauge(
+        "scheduler_run_output_bytes",
+        "Bytes of stdout and stderr written by the last run.",
+        (r.stdout_len + r.stderr_len) as f64,
+    );
+    if let Some(u) = r.usage {
+        gauge(
+            "scheduler_run_cpu_seconds",
+            "User plus system CPU time of the last run.",
+            (u.cpu_user_ms + u.cpu_system_ms) as f64 / 1000.0,
+        );
+        gauge(
+            "scheduler_run_max_rss_bytes",
+            "Peak resident memory of the last run.",
+            (u.max_rss_kb * 1024) as f64,
+        );
+    }
+    out
+}
+
+fn render_queue(stats: &QueueStats) -> String {
+    let mut out = String::new();
+    let mut metric = |name: &str, kind: &str, help: &str, value: u64| {
+        let _ = writeln!(out, "# HELP {name} {help}");
+        let _ = writeln!(out, "# TYPE {name} {kind}");
+        let _ = writeln!(out, "{name} {value}");
+    };
+    metric(
+        "scheduler_queue_pending",
+        "gauge",
+        "Runs triggered but waiting to start.",
+        stats.pending as u64,
+    );
+    metric(
+        "scheduler_queue_dropped_total",
+        "counter",
+        "Pending runs dropped because the run queue was full.",
+        stats.dropped,
+    );
+    metric(
+        "scheduler_queue_rejected_total",
+        "counter",
+        "Triggers rejected because the run queue was full.",
+        stats.rejected,
+    );
+    out
+}
+
+fn escape(v: &str) -> String {
+    v.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
@@ -0,0 +1,210 @@
+use crate::{config::MqttConfig, fire_triggers, history, metadata, trigger_subjects, State};
+use anyhow::{bail, Result};
+use rumqttc::{AsyncClient, Event as MqttEvent, MqttOptions, Packet, QoS};
+use scheduler_core::{EventKind, Trigger};
+use std::{collections::BTreeSet, sync::Arc, time::Duration};
+use tokio::sync::{broadcast, watch};
+
+/// 連線失敗後重試前的等待
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// 啟動 MQTT 整合：事件迴圈、依任務 triggers 維護訂閱、發布執行結果
+pub fn spawn(state: Arc<State>) {
+    let Some(cfg) = state.config.mqtt.clone() else {
+        return;
+    };
+    let mut opts = MqttOptions::new(cfg.client_id.clone(), cfg.host.clone(), cfg.port);
+    opts.set_keep_alive(Duration::from_secs(cfg.keep_alive_secs.max(5)));
+    if let Some(user) = &cfg.username {
+        opts.set_credentials(user.clone(), cfg.password.clone().unwrap_or_default());
+    }
+    let qos = qos(cfg.qos);
+    let (client, mut eventloop) = AsyncClient::new(opts, 64);
+
+    // 每次（重新）連上都要重新訂閱
+    let (connected_tx, connected_rx) = watch::channel(0u64);
+
+    let st = state.clone();
+    tokio::spawn(async move {
+        let mut up = false;
+        loop {
+            match eventloop.poll().await {
+                Ok(MqttEvent::Incoming(Packet::ConnAck(_))) => {
+                    up = true;
+                    println!("📡 mqtt connected to {}:{}", cfg.host, cfg.port);
+                    connected_tx.send_modify(|n| *n += 1);
+                }
+                Ok(MqttEvent::Incoming(Packet::Publish(p))) => fire_triggers(
+                    &st,
+                    &format!("mqtt topic {}", p.topic),
+                    metadata::from_payload(&p.payload),
+                    |t| matches!(t, Trigger::Mqtt { topic } if topic_matches(topic, &p.topic)),
+                ),
+                Ok(_) => {}
+                Err(e) => {
+                    if up {
+                        eprintln!("📡 mqtt connection lost: {e}");
+                    }
+                    up = false;
+                    tokio::time::sleep(RECONNECT_DELAY).await;
+                }
+            }
+        }
+    });
+
+    let sub_client = client.clone();
+    let st = state.clone();
+    tokio::spawn(async move {
+        sync_subscriptions(st, sub_client, qos, connected_rx).await;
+    });
+
+    if let Some(topic) = state
+        .config
+        .mqtt
+        .as_ref()
+        .and_then(|c| c.result_topic.clone())
+    {
+        tokio::spawn(async move {
+            publish_results(state, client, qos, topic).await;
+        });
+    }
+}
+
+/// 新增任務時檢查 trigger 設定
+pub fn check_triggers(cfg: Option<&MqttConfig>, triggers: &[Trigger]) -> Result<()> {
+    for t in triggers {
+        if let Trigger::Mqtt { topic } = t {
+            if cfg.is_none() {
+                bail!("mqtt trigger requires the [mqtt] server configuration");
+            }
+            check_filter(topic)?;
+        }
+    }
+    Ok(())
+}
+
+fn check_filter(filter: &str) -> Result<()> {
+    if filter.is_empty() {
+        bail!("mqtt topic must not be empty");
+    }
+    let levels: Vec<&str> = filter.split('/').collect();
+    for (i, level) in levels.iter().enumerate() {
+        let bad_hash = level.contains('#') && (*level != "#" || i + 1 != levels.len());
+        let bad_plus = level.contains('+') && *level != "+";
+        if bad_hash || bad_plus {
+            bail!("invalid mqtt topic filter {filter:?}");
+        }
+    }
+    Ok(())
+}
+
+/// topic 是否符合 filter（+ 比對一層，# 比對其後所有層）
+fn topic_matches(filter: &str, topic: &str) -> bool {
+    let mut f = filter.split('/');
+    let mut t = topic.split('/');
+    loop {
+        match (f.next(), t.next()) {
+            (Some("#"), _) => return true,
+            (Some("+"), Some(_)) => {}
+            (Some(a), Some(b)) if a == b => {}
+            (None, None) => return true,
+            _ => return false,
+        }
+    }
+}
+
+/// 讓 broker 上的訂閱與任務 triggers 一致
+async fn sync_subscriptions(
+    state: Arc<State>,
+    client: AsyncClient,
+    qos: QoS,
+    mut connected: watch::Receiver<u64>,
+) {
+    let mut changed = state.triggers_changed.subscribe();
+    let mut subscribed: BTreeSet<String> = BTreeSet::new();
+    loop {
+        tokio::select! {
+            res = connected.changed() => {
+                if res.is_err() {
+                    return;
+                }
+                subscribed.clear(); // 新的連線沒有任何訂閱
+            }
+            res = changed.changed() => {
+                if res.is_err() {
+                    return;
+                }
+            }
+        }
+        if *connected.borrow() == 0 {
+            continue; // 還沒連上，連上時會再同步
+        }
+
+        let wanted = trigger_subjects(&state, |t| match t {
+            Trigger::Mqtt { topic } => Some(topic),
+            _ => None,
+        });
+        for topic in wanted.difference(&subscribed) {
+            if let Err(e) = client.subscribe(topic.clone(), qos).await {
+                eprintln!("mqtt subscribe {topic} error: {e}");
+            }
+        }
+        for topic in subscribed.difference(&wanted) {
+            if let Err(e) = client.unsubscribe(topic.clone()).await {
+                eprintln!("mqtt unsubscribe {topic} error: {e}");
+            }
+        }
+        subscribed = wanted;
+    }
+}
+
+/// 每次執行結束後把 RunRecord 發布到 result_topic
+async fn publish_results(state: Arc<State>, client: AsyncClient, qos: QoS, topic: String) {
+    let (_, _, mut rx) = state.events.subscribe(None);
+    loop {
+        let ev = match rx.recv().await {
+            Ok(ev) => ev,
+            Err(broadcast::error::RecvError::Lagged(n)) => {
+                eprintln!("mqtt result publisher skipped {n} event(s)");
+                continue;
+            }
+            Err(broadcast::error::RecvError::Closed) => return,
+        };
+        let EventKind::RunFinished {
+            task_id, run_id, ..
+        } = ev.kind
+        else {
+            continue;
+        };
+        let latest =
+            match history::blocking(&state, move |st| st.history.for_task(task_id, 1)).await {
+                Ok(list) => list,
+                Err(e) => {
+                    eprintln!("mqtt read history error: {e:#}");
+                    continue;
+                }
+            };
+        let Some(rec) = latest.into_iter().find(|r| r.result.run_id == run_id) else {
+            continue;
+        };
+        let payload = match serde_json::to_vec(&rec) {
+            Ok(p) => p,
+            Err(e) => {
+                eprintln!("mqtt encode result error: {e}");
+                continue;
+            }
+        };
+        let topic = topic.replace("{task_id}", &task_id.to_string());
+        if let Err(e) = client.publish(topic, qos, false, payload).await {
+            eprintln!("mqtt publish error: {e}");
+        }
+    }
+}
+
+fn qos(level: u8) -> QoS {
+    match level {
+        0 => QoS::AtMostOnce,
+        2 => QoS::ExactlyOnce,
+        _ => QoS::AtLeastOnce,
+    }
+}
@@ -0,0 +1,509 @@
+use crate::{
+    config::{NotifyConfig, NotifyOn, Webhook},
+    local_now_fixed, State,
+};
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, FixedOffset};
+use lettre::{message::Mailbox, Message, SmtpTransport, Transport};
+use scheduler_core::{Event, EventKind, Notification, Owner, Priority, RunOutcome};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::sync::{broadcast, Notify};
+
+/// 持久化的通知 outbox：事件先寫進來才送，確保「至少送達一次」。
+/// 檔案是只附加的日誌（JSON Lines），每次變動附加一行；行數夠多時改寫成一份快照
+pub struct Outbox {
+    path: PathBuf,
+    inner: Mutex<Inner>,
+    wake: Notify,
+}
+
+/// 日誌至少累積這麼多行才改寫
+const COMPACT_MIN_LINES: usize = 256;
+
+struct Inner {
+    state: OutboxFile,
+    /// 日誌目前的行數；超過通知數兩倍（且至少 COMPACT_MIN_LINES）時改寫
+    lines: usize,
+    file: Option<File>,
+}
+
+#[derive(Default, Clone, Serialize, Deserialize)]
+struct OutboxFile {
+    /// 已轉成通知的最後一個事件；重啟後從這裡補
+    cursor: u64,
+    next_id: u64,
+    entries: Vec<Notification>,
+}
+
+/// 日誌的一行
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum Op {
+    /// 改寫後的第一行：當時的完整內容
+    Snapshot(OutboxFile),
+    /// 新增或更新一筆通知
+    Put(Box<Notification>),
+    Remove {
+        id: u64,
+    },
+    Cursor {
+        seq: u64,
+    },
+}
+
+impl OutboxFile {
+    fn apply(&mut self, op: Op) {
+        match op {
+            Op::Snapshot(file) => *self = file,
+            Op::Put(n) => {
+                self.next_id = self.next_id.max(n.id);
+                match self.entries.iter_mut().find(|e| e.id == n.id) {
+                    Some(e) => *e = *n,
+                    None => self.entries.push(*n),
+                }
+            }
+            Op::Remove { id } => self.entries.retain(|n| n.id != id),
+            Op::Cursor { seq } => self.cursor = self.cursor.max(seq),
+        }
+    }
+}
+
+impl Outbox {
+    /// 重播日誌（壞掉或寫到一半的行略過並提示）後改寫成快照；
+    /// 舊版整份 JSON 的 outbox 檔也能讀入
+    pub fn load(path: &Path) -> Result<Self> {
+        let mut state = OutboxFile::default();
+        if path.exists() {
+            let bytes = std::fs::read(path).with_context(|| format!("read {}", path.display()))?;
+            match serde_json::from_slice::<OutboxFile>(&bytes) {
+                Ok(file) => state = file,
+                Err(_) => {
+                    for (i, line) in bytes.split(|&b| b == b'\n').enumerate() {
+                        if line.trim_ascii().is_empty() {
+                            continue;
+                        }
+                        match serde_json::from_slice::<Op>(line) {
+                            Ok(op) => state.apply(op),
+                            Err(e) => {
+                                eprintln!("outbox {}:{}: skipped: {e}", path.display(), i + 1)
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        let outbox = Self {
+            path: path.to_path_buf(),
+            inner: Mutex::new(Inner {
+                state,
+                lines: 0,
+                file: None,
+            }),
+            wake: Notify::new(),
+        };
+        if path.exists() {
+            outbox.compact(&mut outbox.inner.lock().unwrap())?;
+        }
+        Ok(outbox)
+    }
+
+    fn cursor(&self) -> u64 {
+        self.inner.lock().unwrap().state.cursor
+    }
+
+    /// 把 cursor 拉回 seq（日誌的 Cursor 只會往前，所以改寫成快照）
+    fn rewind(&self, seq: u64) -> Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.state.cursor = seq;
+        self.compact(&mut inner)
+    }
+
+    pub fn list(&self, dead_only: bool) -> Vec<Notification> {
+        let inner = self.inner.lock().unwrap();
+        inner
+            .state
+            .entries
+            .iter()
+            .filter(|n| !dead_only || n.dead)
+            .cloned()
+            .collect()
+    }
+
+    /// 重設重試次數並立即排入；ids 為空時重排所有 dead letter
+    pub fn requeue(&self, ids: &[u64]) -> Result<usize> {
+        let mut inner = self.inner.lock().unwrap();
+        let now = local_now_fixed();
+        let mut ops = Vec::new();
+        for n in inner.state.entries.iter_mut() {
+            if (ids.is_empty() && n.dead) || ids.contains(&n.id) {
+                n.dead = false;
+                n.attempts = 0;
+                n.next_attempt = now;
+                ops.push(Op::Put(Box::new(n.clone())));
+            }
+        }
+        let count = ops.len();
+        if count > 0 {
+            self.log(&mut inner, &ops)?;
+            self.wake.notify_one();
+        }
+        Ok(count)
+    }
+
+    /// 把事件轉成各 webhook 的通知並推進 cursor（同一次寫入，cursor 在最後一行）；
+    /// task 為事件所屬任務目前的負責人與優先順序
+    fn enqueue(&self, cfg: &NotifyConfig, ev: &Event, task: TaskMeta) -> Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        if ev.seq <= inner.state.cursor {
+            return Ok(());
+        }
+        let now = local_now_fixed();
+        let (owner, priority) = task;
+        let mut ops = Vec::new();
+        for hook in cfg.webhooks.iter().filter(|h| wants(h, &ev.kind, priority)) {
+            inner.state.next_id += 1;
+            let n = Notification {
+                id: inner.state.next_id,
+                url: hook.url.clone(),
+                event: ev.clone(),
+                attempts: 0,
+                next_attempt: now,
+                last_error: None,
+                dead: false,
+                owner: owner.clone(),
+            };
+            inner.state.entries.push(n.clone());
+            ops.push(Op::Put(Box::new(n)));
+        }
+        inner.state.cursor = ev.seq;
+        ops.push(Op::Cursor { seq: ev.seq });
+        self.log(&mut inner, &ops)?;
+        self.wake.notify_one();
+        Ok(())
+    }
+
+    /// 目前到期的通知
+    fn due(&self, now: DateTime<FixedOffset>) -> Vec<Notification> {
+        let inner = self.inner.lock().unwrap();
+        inner
+            .state
+            .entries
+            .iter()
+            .filter(|n| !n.dead && n.next_attempt <= now)
+            .cloned()
+            .collect()
+    }
+
+    /// 最近一筆要重試的時間
+    fn next_due(&self) -> Option<DateTime<FixedOffset>> {
+        let inner = self.inner.lock().unwrap();
+        inner
+            .state
+            .entries
+            .iter()
+            .filter(|n| !n.dead)
+            .map(|n| n.next_attempt)
+            .min()
+    }
+
+    fn record(&self, cfg: &NotifyConfig, id: u64, result: Result<()>) -> Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        let entries = &mut inner.state.entries;
+        let Some(pos) = entries.iter().position(|n| n.id == id) else {
+            return Ok(());
+        };
+        let op = match result {
+            Ok(()) => {
+                entries.remove(pos);
+                Op::Remove { id }
+            }
+            Err(e) => {
+                let n = &mut entries[pos];
+                n.attempts += 1;
+                n.last_error = Some(format!("{e:#}"));
+                if n.attempts >= cfg.max_attempts {
+                    n.dead = true;
+                    eprintln!(
+                        "📪 notification {} to {} dead-lettered after {} attempt(s): {e:#}",
+                        n.id, n.url, n.attempts
+                    );
+                } else {
+                    let exp = 1u64 << (n.attempts - 1).min(32);
+                    let secs = cfg
+                        .retry_base_secs
+                        .saturating_mul(exp)
+                        .min(cfg.retry_max_secs);
+                    n.next_attempt = local_now_fixed() + chrono::Duration::seconds(secs as i64);
+                }
+                Op::Put(Box::new(n.clone()))
+            }
+        };
+        self.log(&mut inner, &[op])
+    }
+
+    /// 附加變動到日誌（記憶體中的內容已更新）；日誌太長時改寫成快照
+    fn log(&self, inner: &mut Inner, ops: &[Op]) -> Result<()> {
+        if inner.lines + ops.len() > COMPACT_MIN_LINES.max(inner.state.entries.len() * 2) {
+            return self.compact(inner);
+        }
+        let mut buf = Vec::new();
+        for op in ops {
+            serde_json::to_writer(&mut buf, op)?;
+            buf.push(b'\n');
+        }
+        let file = match &mut inner.file {
+            Some(f) => f,
+            None => inner.file.insert(
+                std::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&self.path)
+                    .with_context(|| format!("open {}", self.path.display()))?,
+            ),
+        };
+        if let Err(e) = file.write_all(&buf) {
+            inner.file = None; // 下次重新開啟
+            return Err(e).with_context(|| format!("append {}", self.path.display()));
+        }
+        inner.lines += ops.len();
+        Ok(())
+    }
+
+    /// 先寫暫存檔再改名；之後的變動附加到新檔
+    fn compact(&self, inner: &mut Inner) -> Result<()> {
+        let mut buf = serde_json::to_vec(&Op::Snapshot(inner.state.clone()))?;
+        buf.push(b'\n');
+        let tmp = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp, &buf).with_context(|| format!("write {}", tmp.display()))?;
+        std::fs::rename(&tmp, &self.path)
+            .with_context(|| format!("replace {}", self.path.display()))?;
+        inner.file = None;
+        inner.lines = 1;
+        Ok(())
+    }
+}
+
+/// 任務已移除時不知道優先順序，一律通知
+fn wants(hook: &Webhook, kind: &EventKind, priority: Option<Priority>) -> bool {
+    if let (Some(min), Some(p)) = (hook.min_priority, priority) {
+        if p < min {
+            return false;
+        }
+    }
+    let on = match kind {
+        EventKind::RunFinished {
+            outcome: RunOutcome::Exited,
+            status_code: 0,
+            ..
+        } => NotifyOn::Success,
+        EventKind::RunFinished {
+            outcome: RunOutcome::Cancelled,
+            ..
+        } => return false,
+        EventKind::RunFinished { .. } => NotifyOn::Failure,
+        EventKind::RunSkipped { .. } => NotifyOn::Skipped,
+        EventKind::RunKilled { .. } => NotifyOn::Killed,
+        EventKind::BreakerOpened { .. } => NotifyOn::Breaker,
+        EventKind::ApprovalRequested { .. } => NotifyOn::Approval,
+        EventKind::DigestReady { .. } => NotifyOn::Digest,
+        _ => return false,
+    };
+    hook.on.contains(&on)
+}
+
+/// 啟動通知：一個迴圈把事件寫入 outbox，另一個迴圈負責送出與重試
+pub fn spawn(state: Arc<State>) {
+    if state.config.notify.webhooks.is_empty() && state.outbox.list(false).is_empty() {
+        return;
+    }
+
+    let st = state.clone();
+    tokio::spawn(async move {
+        // 從 outbox 記錄的 cursor 接續，停機期間的事件由事件檔補回
+        let mut cursor = st.outbox.cursor();
+        // cursor 比事件的 seq 還新：seq 倒退過（高水位檔遺失），
+        // 不拉回的話這次啟動的事件到 cursor 為止都會被當成已處理而漏送
+        let opened_at = st.events.opened_at();
+        if cursor > opened_at {
+            eprintln!("⚠️ event seq went back from {cursor} to {opened_at}; outbox cursor reset");
+            if let Err(e) = st.outbox.rewind(opened_at) {
+                eprintln!("outbox save error: {e:?}");
+            }
+            cursor = opened_at;
+        }
+        loop {
+            let (_, backlog, mut rx) = st.events.subscribe(Some(cursor));
+            for ev in backlog {
+                cursor = ev.seq;
+                let task = task_of(&st, &ev);
+                if let Err(e) = st.outbox.enqueue(&st.config.notify, &ev, task) {
+                    eprintln!("outbox enqueue error: {e:?}");
+                }
+            }
+            loop {
+                match rx.recv().await {
+                    Ok(ev) => {
+                        cursor = ev.seq;
+                        let task = task_of(&st, &ev);
+                        if let Err(e) = st.outbox.enqueue(&st.config.notify, &ev, task) {
+                            eprintln!("outbox enqueue error: {e:?}");
+                        }
+                    }
+                    // 跟不上就用 cursor 重新訂閱補回
+                    Err(broadcast::error::RecvError::Lagged(_)) => break,
+                    Err(broadcast::error::RecvError::Closed) => return,
+                }
+            }
+        }
+    });
+
+    tokio::spawn(async move {
+        loop {
+            for n in state.outbox.due(local_now_fixed()) {
+                let cfg = state.config.notify.clone();
+                let res = deliver(&cfg, &n).await;
+                if let Err(e) = state.outbox.record(&cfg, n.id, res) {
+                    eprintln!("outbox save error: {e:?}");
+                }
+            }
+            let wait = match state.outbox.next_due() {
+                Some(at) => (at - local_now_fixed())
+                    .to_std()
+                    .unwrap_or(Duration::ZERO)
+                    .max(Duration::from_millis(100)),
+                None => Duration::from_secs(3600),
+            };
+            tokio::select! {
+                _ = tokio::time::sleep(wait) => {}
+                _ = state.outbox.wake.notified() => {}
+            }
+        }
+    });
+}
+
+type TaskMeta = (Option<Owner>, Option<Priority>);
+
+/// 任務目前的負責人（未指定時用 [defaults]）與優先順序（任務已移除則都沒有）
+fn task_of(state: &State, ev: &Event) -> TaskMeta {
+    let Some(task) = ev.kind.task_id().and_then(|id| state.tasks.get(&id)) else {
+        return (None, None);
+    };
+    let owner = task.spec.owner.clone();
+    (
+        owner.or_else(|| state.config.defaults.owner.clone()),
+        Some(task.spec.priority),
+    )
+}
+
+/// 啟動前檢查：`mailto:` 通知需要 [notify.smtp]
+pub fn check(cfg: &NotifyConfig) -> Result<()> {
+    let mailto = cfg.webhooks.iter().any(|h| h.url.starts_with("mailto:"));
+    match &cfg.smtp {
+        None if mailto => bail!("mailto: notifications require [notify.smtp]"),
+        Some(smtp) => {
+            smtp.from
+                .parse::<Mailbox>()
+                .with_context(|| format!("notify.smtp.from {:?}", smtp.from))?;
+        }
+        None => {}
+    }
+    Ok(())
+}
+
+/// POST 事件 JSON（任務有負責人時另帶 owner 欄位）；2xx 才算送達。
+/// `mailto:` 則寄一封純文字信
+async fn deliver(cfg: &NotifyConfig, n: &Notification) -> Result<()> {
+    if let Some(to) = n.url.strip_prefix("mailto:") {
+        return mail(cfg, to, n).await;
+    }
+    let url = n.url.clone();
+    let mut body = serde_json::to_value(&n.event)?;
+    if let Some(owner) = &n.owner {
+        body["owner"] = serde_json::to_value(owner)?;
+    }
+    let timeout = Duration::from_secs(cfg.timeout_secs.max(1));
+    tokio::task::spawn_blocking(move || {
+        let agent = ureq::AgentBuilder::new().timeout(timeout).build();
+        match agent
+            .post(&url)
+            .set("User-Agent", "scheduler-server")
+            .send_json(body)
+        {
+            Ok(_) => Ok(()),
+            Err(ureq::Error::Status(code, _)) => bail!("HTTP {code}"),
+            Err(e) => Err(e.into()),
+        }
+    })
+    .await?
+}
+
+/// 經 [notify.smtp] 寄信；to 可用逗號分隔多個地址
+async fn mail(cfg: &NotifyConfig, to: &str, n: &Notification) -> Result<()> {
+    let Some(smtp) = cfg.smtp.clone() else {
+        bail!("mailto: notifications require [notify.smtp]");
+    };
+    let (subject, mut body) = match &n.event.kind {
+        EventKind::DigestReady { digest } => (
+            "[scheduler] digest".to_string(),
+            crate::digest::render(digest),
+        ),
+        kind => (
+            format!("[scheduler] {}", subject_of(kind)),
+            serde_json::to_string_pretty(&n.event)?,
+        ),
+    };
+    if let Some(owner) = &n.owner {
+        body.push_str(&format!("\nowner: {owner}\n"));
+    }
+    let mut msg = Message::builder().from(smtp.from.parse()?).subject(subject);
+    for addr in to.split(',').map(str::trim).filter(|a| !a.is_empty()) {
+        msg = msg.to(addr
+            .parse()
+            .with_context(|| format!("invalid address {addr:?}"))?);
+    }
+    let msg = msg.body(body)?;
+    let timeout = Duration::from_secs(cfg.timeout_secs.max(1));
+    tokio::task::spawn_blocking(move || {
+        SmtpTransport::builder_dangerous(&smtp.host)
+            .port(smtp.port)
+            .timeout(Some(timeout))
+            .build()
+            .send(&msg)?;
+        Ok(())
+    })
+    .await?
+}
+
+fn subject_of(kind: &EventKind) -> String {
+    match kind {
+        EventKind::RunFinished {
+            task_id,
+            outcome: RunOutcome::Exited,
+            status_code: 0,
+            ..
+        } => format!("task {task_id} succeeded"),
+        EventKind::RunFinished {
+            task_id,
+            outcome: RunOutcome::Exited,
+            status_code,
+            ..
+        } => format!("task {task_id} failed (exit {status_code})"),
+        EventKind::RunFinished {
+            task_id, outcome, ..
+        } => format!("task {task_id} failed ({outcome:?})"),
+        EventKind::RunSkipped { task_id, .. } => format!("task {task_id} skipped"),
+        EventKind::RunKilled { task_id, .. } => format!("task {task_id} killed"),
+        EventKind::BreakerOpened { task_id, .. } => format!("task {task_id} breaker opened"),
+        EventKind::ApprovalRequested { task_id, .. } => {
+            format!("task {task_id} awaits approval")
+        }
+        _ => "event".to_string(),
+    }
+}
@@ -0,0 +1,73 @@
+use crate::encoding;
+use anyhow::{bail, Context, Result};
+use scheduler_core::{OutputEncoding, OutputsFrom};
+use std::collections::BTreeMap;
+
+/// 交給子程序的環境變數：OutputsFrom::File 時指向要寫入的檔案
+pub const OUTPUTS_ENV: &str = "SCHEDULER_OUTPUTS";
+
+/// 執行前清掉上一次留下的輸出檔，避免沿用舊值
+pub fn prepare(from: &OutputsFrom) {
+    if let OutputsFrom::File(path) = from {
+        match std::fs::remove_file(path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => eprintln!("remove stale outputs file {}: {e}", path.display()),
+        }
+    }
+}
+
+/// 執行結束後讀取任務發布的輸出變數；依 encoding 解碼（utf8 時無法解碼即不採用）
+pub fn collect(
+    from: &OutputsFrom,
+    stdout: &[u8],
+    encoding: OutputEncoding,
+) -> Result<BTreeMap<String, String>> {
+    match from {
+        OutputsFrom::LastLine => {
+            let text = encoding::text(encoding, stdout).context("stdout")?;
+            let Some(line) = text.lines().rev().find(|l| !l.trim().is_empty()) else {
+                return Ok(BTreeMap::new());
+            };
+            parse_pairs(line.split_whitespace())
+        }
+        OutputsFrom::File(path) => {
+            if !path.exists() {
+                return Ok(BTreeMap::new());
+            }
+            let bytes = std::fs::read(path)
+                .with_context(|| format!("read outputs file {}", path.display()))?;
+            let text = encoding::text(encoding, &bytes)
+                .with_context(|| format!("outputs file {}", path.display()))?;
+            parse_pairs(
+                text.lines()
+                    .map(str::trim)
+                    .filter(|l| !l.is_empty() && !l.starts_with('#')),
+            )
+        }
+    }
+}
+
+fn parse_pairs<'a>(items: impl Iterator<Item = &'a str>) -> Result<BTreeMap<String, String>> {
+    let mut out = BTreeMap::new();
+    for item in items {
+        let Some((key, value)) = item.split_once('=') else {
+            bail!("expected key=value, got {item:?}");
+        };
+        let key = key.trim();
+        if !valid_key(key) {
+            bail!("invalid output key {key:?}");
+        }
+        out.insert(key.to_string(), value.trim().to_string());
+    }
+    Ok(out)
+}
+
+/// 輸出變數名稱：英文字母或底線開頭，其後為英數字或底線
+pub fn valid_key(key: &str) -> bool {
+    let mut chars = key.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
@@ -0,0 +1,188 @@
+use crate::config::TaskDefaults;
+use anyhow::{bail, Context, Result};
+use scheduler_core::{OutputPerms, TaskSpec};
+use std::path::{Path, PathBuf};
+
+/// 解析八進位權限（"0640"、"640"、"0o640"）
+pub fn parse_mode(text: &str) -> Result<u32> {
+    let digits = text.trim();
+    let digits = digits.strip_prefix("0o").unwrap_or(digits);
+    match u32::from_str_radix(digits, 8) {
+        Ok(mode) if !digits.is_empty() && mode <= 0o7777 => Ok(mode),
+        _ => bail!("invalid octal mode {text:?}"),
+    }
+}
+
+/// AddTask 時的檢查：權限格式正確，使用者與群組存在
+pub fn check(perms: &OutputPerms) -> Result<()> {
+    if !cfg!(unix) {
+        bail!("output_perms is only supported on Unix");
+    }
+    if let Some(mode) = &perms.mode {
+        parse_mode(mode).context("output_perms.mode")?;
+    }
+    if let Some(user) = &perms.user {
+        lookup_user(user)?;
+    }
+    if let Some(group) = &perms.group {
+        lookup_group(group)?;
+    }
+    Ok(())
+}
+
+/// 建立輸出檔所在的目錄，回傳這次新建立的目錄（由外而內）。
+/// 目錄建立時就帶著 dir_mode，不會有一段時間沿用伺服器的 umask
+pub fn create_parent(path: &Path, dir_mode: Option<u32>) -> Result<Vec<PathBuf>> {
+    let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) else {
+        return Ok(Vec::new());
+    };
+    let mut created: Vec<PathBuf> = parent
+        .ancestors()
+        .take_while(|p| !p.as_os_str().is_empty() && !p.exists())
+        .map(Path::to_path_buf)
+        .collect();
+    created.reverse();
+    let mut builder = std::fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    if let Some(mode) = dir_mode {
+        use std::os::unix::fs::DirBuilderExt;
+        // 擁有者先保有 rwx 才能往下建子目錄，apply 再設定成確切的權限
+        builder.mode(mode | 0o700);
+    }
+    #[cfg(not(unix))]
+    let _ = dir_mode;
+    builder.create(parent)?;
+    Ok(created)
+}
+
+/// 開啟輸出檔；新建立的檔案一開始就是 file_mode
+pub fn open(path: &Path, append: bool, file_mode: Option<u32>) -> Result<std::fs::File> {
+    let mut options = std::fs::OpenOptions::new();
+    options
+        .create(true)
+        .write(true)
+        .append(append)
+        .truncate(!append);
+    #[cfg(unix)]
+    if let Some(mode) = file_mode {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(mode);
+    }
+    #[cfg(not(unix))]
+    let _ = file_mode;
+    Ok(options.open(path)?)
+}
+
+/// 輸出檔與新目錄的權限：任務的 output_perms 優先，否則依 [defaults] umask；
+/// 兩者都沒有設定時為 None（沿用伺服器程序的 umask）
+pub fn modes(cfg: &TaskDefaults, spec: &TaskSpec) -> Result<(Option<u32>, Option<u32>)> {
+    let mode = spec.output_perms.as_ref().and_then(|p| p.mode.as_deref());
+    let file_mode = match (mode, &cfg.umask) {
+        (Some(mode), _) => Some(parse_mode(mode)?),
+        (None, Some(umask)) => Some(0o666 & !parse_mode(umask)?),
+        (None, None) => None,
+    };
+    let dir_mode = match (mode, &cfg.umask) {
+        // 可讀的目錄才有意義地可進入：r 位元對應加上 x
+        (Some(_), _) => file_mode.map(|m| m | ((m & 0o444) >> 2)),
+        (None, Some(umask)) => Some(0o777 & !parse_mode(umask)?),
+        (None, None) => None,
+    };
+    Ok((file_mode, dir_mode))
+}
+
+/// 寫完輸出後再確定一次權限並設定擁有者：建立時的權限會被程序的 umask 遮掉，
+/// 既有的檔案也不會套用建立時的權限。
+/// 既有的上層目錄不動，只處理這次新建立的目錄
+pub fn apply(cfg: &TaskDefaults, spec: &TaskSpec, dirs: &[PathBuf], file: &Path) -> Result<()> {
+    let perms = spec.output_perms.clone().unwrap_or_default();
+    let (file_mode, dir_mode) = modes(cfg, spec)?;
+    let uid = perms.user.as_deref().map(lookup_user).transpose()?;
+    let gid = perms.group.as_deref().map(lookup_group).transpose()?;
+
+    for dir in dirs {
+        set(dir, dir_mode, uid, gid)?;
+    }
+    set(file, file_mode, uid, gid)
+}
+
+#[cfg(unix)]
+fn set(path: &Path, mode: Option<u32>, uid: Option<u32>, gid: Option<u32>) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    if let Some(mode) = mode {
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+            .with_context(|| format!("chmod {:o} {}", mode, path.display()))?;
+    }
+    if uid.is_some() || gid.is_some() {
+        std::os::unix::fs::chown(path, uid, gid)
+            .with_context(|| format!("chown {}", path.display()))?;
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn set(_path: &Path, _mode: Option<u32>, _uid: Option<u32>, _gid: Option<u32>) -> Result<()> {
+    Ok(())
+}
+
+/// 使用者名稱或 uid
+#[cfg(unix)]
+fn lookup_user(name: &str) -> Result<u32> {
+    if let Ok(uid) = name.parse() {
+        return Ok(uid);
+    }
+    let c_name = std::ffi::CString::new(name)?;
+    let mut pwd: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut buf = vec![0 as libc::c_char; 16 * 1024];
+    let mut found = std::ptr::null_mut();
+    let rc = unsafe {
+        libc::getpwnam_r(
+            c_name.as_ptr(),
+            &mut pwd,
+            buf.as_mut_ptr(),
+            buf.len(),
+            &mut found,
+        )
+    };
+    if rc != 0 || found.is_null() {
+        bail!("unknown user {name:?}");
+    }
+    Ok(pwd.pw_uid)
+}
+
+/// 群組名稱或 gid
+#[cfg(unix)]
+fn lookup_group(name: &str) -> Result<u32> {
+    if let Ok(gid) = name.parse() {
+        return Ok(gid);
+    }
+    let c_name = std::ffi::CString::new(name)?;
+    let mut grp: libc::group = unsafe { std::mem::zeroed() };
+    let mut buf = vec![0 as libc::c_char; 16 * 1024];
+    let mut found = std::ptr::null_mut();
+    let rc = unsafe {
+        libc::getgrnam_r(
+            c_name.as_ptr(),
+            &mut grp,
+            buf.as_mut_ptr(),
+            buf.len(),
+            &mut found,
+        )
+    };
+    if rc != 0 || found.is_null() {
+        bail!("unknown group {name:?}");
+    }
+    Ok(grp.gr_gid)
+}
+
+#[cfg(not(unix))]
+fn lookup_user(name: &str) -> Result<u32> {
+    bail!("cannot look up user {name:?} on this platform")
+}
+
+#[cfg(not(unix))]
+fn lookup_group(name: &str) -> Result<u32> {
+    bail!("cannot look up group {name:?} on this platform")
+}
@@ -0,0 +1,99 @@
+use crate::config::CommandPolicy;
+use anyhow::{bail, Result};
+use std::{
+    ffi::{OsStr, OsString},
+    path::{Path, PathBuf},
+};
+
+/// 是否設定了任何規則
+pub fn is_active(policy: &CommandPolicy) -> bool {
+    !policy.allow.is_empty() || !policy.deny.is_empty()
+}
+
+/// 檢查任務命令是否允許執行；`search` 為子程序實際使用的 PATH
+/// 規則項目：以 `/` 結尾為路徑前綴；含 `/` 為完整路徑；其餘比對執行檔名稱。
+/// allow 的名稱規則只放行由伺服器 PATH 找到的那個執行檔
+/// （`ls` 不會放行 `/tmp/x/ls`，任務自己把 PATH 指到別處也一樣）；
+/// deny 的名稱規則也擋下任何路徑下同名的執行檔
+/// deny 優先；allow 非空時，命令必須符合其中一項
+pub fn check_command(policy: &CommandPolicy, cmd: &str, search: Option<&OsStr>) -> Result<()> {
+    if !is_active(policy) {
+        return Ok(());
+    }
+
+    let resolved = resolve_command(cmd, search);
+    if let Some(rule) = policy
+        .deny
+        .iter()
+        .find(|r| rule_matches(r, cmd, resolved.as_deref(), true))
+    {
+        bail!("command {cmd:?} is denied by server policy ({rule})");
+    }
+    if !policy.allow.is_empty()
+        && !policy
+            .allow
+            .iter()
+            .any(|r| rule_matches(r, cmd, resolved.as_deref(), false))
+    {
+        bail!("command {cmd:?} is not in the server allowlist");
+    }
+    Ok(())
+}
+
+fn rule_matches(rule: &str, cmd: &str, resolved: Option<&Path>, deny: bool) -> bool {
+    if rule.ends_with('/') {
+        resolved.is_some_and(|p| p.starts_with(rule))
+    } else if rule.contains('/') {
+        let rule_path = std::fs::canonicalize(rule).unwrap_or_else(|_| PathBuf::from(rule));
+        resolved.is_some_and(|p| p == rule_path)
+    } else if deny {
+        let names = [
+            Path::new(cmd).file_name(),
+            resolved.and_then(|p| p.file_name()),
+        ];
+        names.into_iter().flatten().any(|n| n == rule)
+    } else {
+        !cmd.contains('/')
+            && cmd == rule
+            && resolved.map(Path::to_path_buf)
+                == resolve_command(cmd, std::env::var_os("PATH").as_deref())
+    }
+}
+
+/// 解析命令的實際路徑：含 `/` 者取正規化路徑，否則在 `search` 中尋找
+pub fn resolve_command(cmd: &str, search: Option<&OsStr>) -> Option<PathBuf> {
+    if cmd.contains('/') {
+        return std::fs::canonicalize(cmd).ok();
+    }
+    find_program(cmd, search).and_then(|p| std::fs::canonicalize(p).ok())
+}
+
+/// 在 `search` 中找名稱為 `cmd` 的執行檔（不解開符號連結，
+/// 以免多合一程式看到不同的 argv[0]）
+pub fn find_program(cmd: &str, search: Option<&OsStr>) -> Option<PathBuf> {
+    std::env::split_paths(search?)
+        .map(|dir| dir.join(cmd))
+        .find(|p| p.is_file())
+}
+
+/// 以伺服器自己的 PATH 尋找（給 bwrap 等伺服器端工具用）
+pub fn server_program(cmd: &Path) -> PathBuf {
+    match cmd.to_str() {
+        Some(name) if !name.contains('/') => {
+            find_program(name, std::env::var_os("PATH").as_deref())
+                .unwrap_or_else(|| cmd.to_path_buf())
+        }
+        _ => cmd.to_path_buf(),
+    }
+}
+
+/// 任務實際要啟動的程式：有政策時，名稱命令改以子程序 PATH 解析出的絕對路徑啟動，
+/// 與檢查時看到的是同一個執行檔
+pub fn launch_program(policy: &CommandPolicy, cmd: &str, search: Option<&OsStr>) -> OsString {
+    if is_active(policy) && !cmd.contains('/') {
+        if let Some(p) = find_program(cmd, search) {
+            return p.into_os_string();
+        }
+    }
+    OsString::from(cmd)
+}
@@ -0,0 +1,200 @@
+use crate::{config::ProgressConfig, exec::RunHandle, local_now_fixed, State};
+use anyhow::{Context, Result};
+use scheduler_core::{EventKind, Progress};
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::{
+    io::{AsyncBufRead, AsyncBufReadExt},
+    task::JoinHandle,
+};
+
+/// 交給子程序的環境變數：指向本次 run 專用的 named pipe，
+/// 每行一筆 `<百分比>[%] [階段]` 或只有階段，例如 `echo "40 uploading" > "$SCHEDULER_PROGRESS"`
+pub const PROGRESS_ENV: &str = "SCHEDULER_PROGRESS";
+/// 一行回報最多讀這麼多位元組，超過的部分捨棄到換行為止
+const MAX_LINE_BYTES: usize = 1024;
+/// 階段最多保留的字元數
+const MAX_PHASE_CHARS: usize = 120;
+
+/// 一次 run 的進度管道；drop 時停止讀取並刪除 FIFO
+pub struct Channel {
+    path: PathBuf,
+    reader: JoinHandle<()>,
+}
+
+impl Channel {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for Channel {
+    fn drop(&mut self) {
+        self.reader.abort();
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// 建立 FIFO 並開始讀取；不支援的平台回傳 None（任務照常執行，只是沒有進度）
+pub fn open(
+    state: &Arc<State>,
+    task_id: u64,
+    run_id: u64,
+    run: &Arc<RunHandle>,
+) -> Result<Option<Channel>> {
+    let cfg = &state.config.progress;
+    if !cfg.enabled {
+        return Ok(None);
+    }
+    std::fs::create_dir_all(&cfg.dir).with_context(|| format!("create {}", cfg.dir.display()))?;
+    // 子程序的工作目錄可能不同，交給它絕對路徑
+    let path = std::env::current_dir()?
+        .join(&cfg.dir)
+        .join(format!("run-{run_id}.fifo"));
+    let Some(rx) = fifo::create(&path)? else {
+        return Ok(None);
+    };
+    let reader = tokio::spawn(read(
+        rx,
+        cfg.clone(),
+        state.clone(),
+        task_id,
+        run_id,
+        run.clone(),
+    ));
+    Ok(Some(Channel { path, reader }))
+}
+
+/// 逐行讀取回報；進度一律記在 RunHandle，事件則依 min_interval_ms 節流
+async fn read(
+    rx: fifo::Receiver,
+    cfg: ProgressConfig,
+    state: Arc<State>,
+    task_id: u64,
+    run_id: u64,
+    run: Arc<RunHandle>,
+) {
+    let min_interval = Duration::from_millis(cfg.min_interval_ms);
+    let mut last_emit: Option<Instant> = None;
+    let mut rx = tokio::io::BufReader::new(rx);
+    let mut buf = Vec::new();
+    while let Ok(true) = next_line(&mut rx, &mut buf).await {
+        let Some((percent, phase)) = parse(&String::from_utf8_lossy(&buf)) else {
+            continue;
+        };
+        let prev = run.progress();
+        // 只回報百分比時沿用先前的階段
+        let phase = phase.or_else(|| prev.as_ref().and_then(|p| p.phase.clone()));
+        let progress = Progress {
+            percent: percent.or_else(|| prev.as_ref().and_then(|p| p.percent)),
+            phase,
+            at: local_now_fixed(),
+        };
+        let phase_changed = prev.as_ref().map(|p| &p.phase) != Some(&progress.phase);
+        let unchanged = prev
+            .as_ref()
+            .is_some_and(|p| p.percent == progress.percent && p.phase == progress.phase);
+        run.set_progress(progress.clone());
+        if unchanged {
+            continue;
+        }
+        let due = last_emit.is_none_or(|t| t.elapsed() >= min_interval);
+        if due || phase_changed || progress.percent == Some(100) {
+            last_emit = Some(Instant::now());
+            state.events.emit(EventKind::RunProgress {
+                task_id,
+                run_id,
+                progress,
+            });
+        }
+    }
+}
+
+/// 讀一行到 buf（不含換行），最多 MAX_LINE_BYTES，其餘捨棄；讀到結尾時回傳 false
+async fn next_line<R: AsyncBufRead + Unpin>(r: &mut R, buf: &mut Vec<u8>) -> std::io::Result<bool> {
+    buf.clear();
+    loop {
+        let chunk = r.fill_buf().await?;
+        if chunk.is_empty() {
+            return Ok(!buf.is_empty());
+        }
+        let end = chunk.iter().position(|&b| b == b'\n');
+        let part = &chunk[..end.unwrap_or(chunk.len())];
+        let room = MAX_LINE_BYTES.saturating_sub(buf.len());
+        buf.extend_from_slice(&part[..part.len().min(room)]);
+        match end {
+            Some(i) => {
+                r.consume(i + 1);
+                return Ok(true);
+            }
+            None => {
+                let n = chunk.len();
+                r.consume(n);
+            }
+        }
+    }
+}
+
+/// 階段截到 MAX_PHASE_CHARS 個字元
+fn phase_of(s: &str) -> String {
+    s.chars().take(MAX_PHASE_CHARS).collect()
+}
+
+/// `42`、`42%`、`42 uploading`、`uploading`；百分比超過 100 視為 100，空行忽略
+fn parse(line: &str) -> Option<(Option<u8>, Option<String>)> {
+    let line = line.trim();
+    if line.is_empty() {
+        return None;
+    }
+    let (first, rest) = match line.split_once(char::is_whitespace) {
+        Some((first, rest)) => (first, rest.trim()),
+        None => (line, ""),
+    };
+    match first.trim_end_matches('%').parse::<f64>() {
+        Ok(pct) if pct.is_finite() => {
+            let phase = (!rest.is_empty()).then(|| phase_of(rest));
+            Some((Some(pct.clamp(0.0, 100.0) as u8), phase))
+        }
+        _ => Some((None, Some(phase_of(line)))),
+    }
+}
+
+#[cfg(unix)]
+mod fifo {
+    use anyhow::{Context, Result};
+    use std::{ffi::CString, os::unix::ffi::OsStrExt, path::Path};
+
+    pub type Receiver = tokio::net::unix::pipe::Receiver;
+
+    pub fn create(path: &Path) -> Result<Option<Receiver>> {
+        let _ = std::fs::remove_file(path);
+        let c = CString::new(path.as_os_str().as_bytes())?;
+        if unsafe { libc::mkfifo(c.as_ptr(), 0o600) } != 0 {
+            return Err(std::io::Error::last_os_error())
+                .with_context(|| format!("mkfifo {}", path.display()));
+        }
+        // 自己也以寫入端開啟，子程序每次寫完關閉時讀取端不會收到 EOF
+        let mut opts = tokio::net::unix::pipe::OpenOptions::new();
+        #[cfg(target_os = "linux")]
+        opts.read_write(true);
+        let rx = opts
+            .open_receiver(path)
+            .with_context(|| format!("open {}", path.display()))?;
+        Ok(Some(rx))
+    }
+}
+
+#[cfg(not(unix))]
+mod fifo {
+    use anyhow::Result;
+    use std::path::Path;
+
+    pub type Receiver = tokio::io::Empty;
+
+    pub fn create(_path: &Path) -> Result<Option<Receiver>> {
+        Ok(None)
+    }
+}
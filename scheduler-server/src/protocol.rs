@@ -0,0 +1,126 @@
+use crate::config::SpecMode;
+use scheduler_core::{ClientRequest, RequestFrame, TaskSpec};
+use serde::Deserialize;
+use tokio_util::codec::LengthDelimitedCodec;
+
+/// GetHistory 單次最多回傳幾筆
+const MAX_HISTORY_LIMIT: usize = 10_000;
+/// 搜尋字串長度上限（位元組）
+const MAX_PATTERN_LEN: usize = 1024;
+/// 單一請求中清單類欄位（ids、變數等）的項目上限
+const MAX_ITEMS: usize = 10_000;
+
+/// 長度前綴的 frame 編解碼器；超過上限的 frame 直接視為錯誤，不配置緩衝區
+pub fn codec(max_frame_bytes: usize) -> LengthDelimitedCodec {
+    LengthDelimitedCodec::builder()
+        .max_frame_length(max_frame_bytes)
+        .new_codec()
+}
+
+/// 一個解析過的請求；id 為 RequestFrame 的關聯編號，直接送 ClientRequest 時為 None
+pub struct Incoming {
+    pub id: Option<u64>,
+    pub request: ClientRequest,
+}
+
+/// 只看有沒有頂層的 id 欄位，用來分辨 RequestFrame 與直接送的 ClientRequest
+#[derive(Deserialize)]
+struct Probe {
+    id: Option<u64>,
+}
+
+/// 解析並檢查一個請求；錯誤訊息直接回給客戶端（帶上讀得到的 id），連線保持可用。
+/// strict 時 AddTask 的規格不可有不認得的欄位
+pub fn decode_request(frame: &[u8], mode: SpecMode) -> Result<Incoming, (Option<u64>, String)> {
+    let id = serde_json::from_slice::<Probe>(frame)
+        .ok()
+        .and_then(|p| p.id);
+    let request = match id {
+        Some(_) => serde_json::from_slice::<RequestFrame>(frame).map(|f| f.request),
+        None => serde_json::from_slice::<ClientRequest>(frame),
+    }
+    .map_err(|e| (id, format!("invalid request: {e}")))?;
+    let inner = match &request {
+        ClientRequest::InEnvironment { request, .. } => request.as_ref(),
+        req => req,
+    };
+    if mode == SpecMode::Strict && matches!(inner, ClientRequest::AddTask(_)) {
+        let unknown = unknown_spec_fields(frame, id.is_some());
+        if !unknown.is_empty() {
+            let msg = format!(
+                "invalid request: unknown field(s) in task spec: {}",
+                unknown.join(", ")
+            );
+            return Err((id, msg));
+        }
+    }
+    check_request(&request).map_err(|e| (id, format!("invalid request: {e}")))?;
+    Ok(Incoming { id, request })
+}
+
+/// 已確定是合法的 AddTask：再解析一次規格，列出被忽略的欄位（例如 `healthcheck.sucess`）
+fn unknown_spec_fields(frame: &[u8], tagged: bool) -> Vec<String> {
+    let Ok(v) = serde_json::from_slice::<serde_json::Value>(frame) else {
+        return Vec::new();
+    };
+    let mut raw = match tagged {
+        true => &v["request"],
+        false => &v,
+    };
+    if let Some(inner) = raw.get("InEnvironment") {
+        raw = &inner["request"];
+    }
+    let raw = &raw["AddTask"];
+    let mut unknown = Vec::new();
+    let _: Result<TaskSpec, _> =
+        serde_ignored::deserialize(raw, |path| unknown.push(field_path(&path)));
+    unknown
+}
+
+/// 只保留欄位名稱與陣列索引，略過 Option、列舉等沒有名稱的層級
+fn field_path(path: &serde_ignored::Path) -> String {
+    use serde_ignored::Path;
+    let (parent, seg) = match path {
+        Path::Root => return String::new(),
+        Path::Map { parent, key } => (*parent, key.clone()),
+        Path::Seq { parent, index } => (*parent, index.to_string()),
+        Path::Some { parent }
+        | Path::NewtypeStruct { parent }
+        | Path::NewtypeVariant { parent } => return field_path(parent),
+    };
+    match field_path(parent) {
+        p if p.is_empty() => seg,
+        p => format!("{p}.{seg}"),
+    }
+}
+
+/// 結構之外的範圍檢查，避免單一請求讓伺服器做過量的工作
+fn check_request(req: &ClientRequest) -> Result<(), String> {
+    match req {
+        ClientRequest::GetHistory { limit, .. } if *limit > MAX_HISTORY_LIMIT => {
+            Err(format!("history limit {limit} exceeds {MAX_HISTORY_LIMIT}"))
+        }
+        ClientRequest::Search { pattern, .. } if pattern.len() > MAX_PATTERN_LEN => Err(format!(
+            "search pattern is longer than {MAX_PATTERN_LEN} bytes"
+        )),
+        ClientRequest::RequeueNotifications { ids } if ids.len() > MAX_ITEMS => {
+            Err(format!("too many ids (max {MAX_ITEMS})"))
+        }
+        ClientRequest::UpdateContext { set, unset, .. } if set.len() + unset.len() > MAX_ITEMS => {
+            Err(format!("too many variables (max {MAX_ITEMS})"))
+        }
+        ClientRequest::AddTask(spec)
+            if spec.args.len() + spec.env.len() + spec.tags.len() + spec.locks.len()
+                > MAX_ITEMS =>
+        {
+            Err(format!("task spec has too many items (max {MAX_ITEMS})"))
+        }
+        ClientRequest::InEnvironment { request, .. } => match request.as_ref() {
+            ClientRequest::InEnvironment { .. } => {
+                Err("InEnvironment requests cannot be nested".to_string())
+            }
+            inner => check_request(inner),
+        },
+        _ => Ok(()),
+    }
+}
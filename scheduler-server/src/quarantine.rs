@@ -0,0 +1,78 @@
+use crate::local_now_fixed;
+use anyhow::{Context, Result};
+use scheduler_core::QuarantinedTask;
+use std::{
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+/// 隔離檔：載入持久化檔時無法解析的紀錄原樣保存在這裡，
+/// 之後改寫持久化檔也不會弄丟。項目不多，每次變更整份改寫
+pub struct Quarantine {
+    path: PathBuf,
+    items: Mutex<Vec<QuarantinedTask>>,
+}
+
+impl Quarantine {
+    pub fn load(path: &Path) -> Result<Self> {
+        let items = if path.exists() {
+            let text = std::fs::read_to_string(path)
+                .with_context(|| format!("read {}", path.display()))?;
+            serde_json::from_str(&text).with_context(|| format!("parse {}", path.display()))?
+        } else {
+            Vec::new()
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            items: Mutex::new(items),
+        })
+    }
+
+    /// 隔離一筆紀錄；先寫入隔離檔，成功後才可以從持久化檔拿掉
+    pub fn put(&self, id: Option<u64>, reason: String, record: String) -> Result<()> {
+        let mut items = self.items.lock().unwrap();
+        let seq = items.iter().map(|t| t.seq).max().unwrap_or(0) + 1;
+        items.push(QuarantinedTask {
+            seq,
+            id,
+            reason,
+            quarantined_at: local_now_fixed(),
+            record,
+        });
+        self.save(&items)
+    }
+
+    /// 隔離檔中最大的任務 id；修復後可能以原 id 還原，不可配發給新任務
+    pub fn max_task_id(&self) -> u64 {
+        let items = self.items.lock().unwrap();
+        items.iter().filter_map(|t| t.id).max().unwrap_or(0)
+    }
+
+    /// 先隔離的在前
+    pub fn list(&self) -> Vec<QuarantinedTask> {
+        self.items.lock().unwrap().clone()
+    }
+
+    /// 刪除一筆；不存在時回傳 false
+    pub fn discard(&self, seq: u64) -> Result<bool> {
+        let mut items = self.items.lock().unwrap();
+        let Some(pos) = items.iter().position(|t| t.seq == seq) else {
+            return Ok(false);
+        };
+        items.remove(pos);
+        self.save(&items)?;
+        Ok(true)
+    }
+
+    /// 先寫暫存檔再改名，避免改寫到一半留下殘缺的檔案
+    fn save(&self, items: &[QuarantinedTask]) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let tmp = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec_pretty(items)?)
+            .with_context(|| format!("write {}", tmp.display()))?;
+        std::fs::rename(&tmp, &self.path)
+            .with_context(|| format!("replace {}", self.path.display()))
+    }
+}
@@ -0,0 +1,134 @@
+use crate::config::{QueueConfig, QueuePolicy};
+use anyhow::{bail, Result};
+use scheduler_core::QueueStats;
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+use tokio_util::sync::CancellationToken;
+
+/// 已觸發、但還在等待開始的 run：等允許時段、等核准、等鎖，以及依賴鏈中等待延遲的 run。
+/// 超過上限時依 QueuePolicy 丟棄或拒絕，避免下游卡住時佇列無限成長
+#[derive(Default)]
+pub struct RunQueue {
+    inner: Mutex<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    next_slot: u64,
+    /// 依進入順序，舊到新
+    pending: VecDeque<Pending>,
+    dropped: u64,
+    rejected: u64,
+}
+
+struct Pending {
+    slot: u64,
+    task_id: u64,
+    evict: CancellationToken,
+}
+
+/// 佇列中的位置；drop 時離開佇列（開始執行或放棄等待）
+pub struct Slot {
+    queue: Arc<RunQueue>,
+    slot: u64,
+    evict: CancellationToken,
+}
+
+impl Slot {
+    /// 被 drop-oldest 擠出佇列時完成
+    pub async fn evicted(&self) {
+        self.evict.cancelled().await
+    }
+
+    /// 在佇列中等待 fut 完成；先被擠出佇列時放棄 fut，回傳 None
+    pub async fn wait<F: std::future::Future>(&self, fut: F) -> Option<F::Output> {
+        tokio::select! {
+            out = fut => Some(out),
+            _ = self.evicted() => None,
+        }
+    }
+}
+
+impl RunQueue {
+    /// 進入佇列；超過上限時依政策擠掉最舊的，或拒絕這次觸發
+    pub fn enter(self: &Arc<Self>, cfg: &QueueConfig, task_id: u64) -> Result<Slot> {
+        let mut inner = self.inner.lock().unwrap();
+
+        let task_full = cfg.max_pending_per_task.is_some_and(|max| {
+            inner
+                .pending
+                .iter()
+                .filter(|p| p.task_id == task_id)
+                .count()
+                >= max
+        });
+        let total_full = cfg
+            .max_pending_total
+            .is_some_and(|max| inner.pending.len() >= max);
+        if task_full || total_full {
+            let scope = if task_full { "task" } else { "global" };
+            match cfg.policy {
+                QueuePolicy::DropNewest => {
+                    inner.dropped += 1;
+                    bail!("run queue full ({scope} limit): dropped newest trigger");
+                }
+                QueuePolicy::Reject => {
+                    inner.rejected += 1;
+                    bail!("run queue full ({scope} limit): trigger rejected");
+                }
+                QueuePolicy::DropOldest => {
+                    let victim = inner
+                        .pending
+                        .iter()
+                        .position(|p| !task_full || p.task_id == task_id);
+                    if let Some(p) = victim.and_then(|i| inner.pending.remove(i)) {
+                        p.evict.cancel();
+                        inner.dropped += 1;
+                    }
+                }
+            }
+        }
+
+        inner.next_slot += 1;
+        let slot = inner.next_slot;
+        let evict = CancellationToken::new();
+        inner.pending.push_back(Pending {
+            slot,
+            task_id,
+            evict: evict.clone(),
+        });
+        Ok(Slot {
+            queue: self.clone(),
+            slot,
+            evict,
+        })
+    }
+
+    /// 目前排隊中的 run 數
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().pending.len()
+    }
+
+    pub fn stats(&self) -> QueueStats {
+        let inner = self.inner.lock().unwrap();
+        let mut per_task = std::collections::BTreeMap::new();
+        for p in &inner.pending {
+            *per_task.entry(p.task_id).or_insert(0) += 1;
+        }
+        QueueStats {
+            pending: inner.pending.len(),
+            per_task,
+            dropped: inner.dropped,
+            rejected: inner.rejected,
+        }
+    }
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        let mut inner = self.queue.inner.lock().unwrap();
+        inner.pending.retain(|p| p.slot != self.slot);
+    }
+}
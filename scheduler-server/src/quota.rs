@@ -0,0 +1,152 @@
+use crate::{
+    config::{Quota, ServerConfig},
+    State,
+};
+use anyhow::{bail, Context, Result};
+use chrono::{Local, NaiveDate};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+/// 配額的使用量，以配額的鍵計算：個別設定的命名空間各自一份，
+/// 其餘命名空間合用 "*" 那一份（換個命名空間名稱不會多出額度）
+pub struct Usage {
+    path: PathBuf,
+    /// 執行中的 run 數（只在記憶體）
+    runs: Mutex<HashMap<String, usize>>,
+    /// 今日輸出量；存檔，重啟後不歸零
+    output: Mutex<BTreeMap<String, (NaiveDate, u64)>>,
+}
+
+/// 佔用一個同時執行的名額；drop 時歸還
+pub struct RunPermit<'a> {
+    usage: &'a Usage,
+    key: String,
+}
+
+impl Drop for RunPermit<'_> {
+    fn drop(&mut self) {
+        let mut runs = self.usage.runs.lock().unwrap();
+        if let Some(count) = runs.get_mut(&self.key) {
+            *count = count.saturating_sub(1);
+        }
+    }
+}
+
+impl Usage {
+    pub fn load(path: &Path) -> Result<Self> {
+        let output = if path.exists() {
+            let text = std::fs::read_to_string(path)
+                .with_context(|| format!("read {}", path.display()))?;
+            serde_json::from_str(&text).with_context(|| format!("parse {}", path.display()))?
+        } else {
+            BTreeMap::new()
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            runs: Mutex::new(HashMap::new()),
+            output: Mutex::new(output),
+        })
+    }
+
+    /// 先寫暫存檔再改名，避免改寫到一半留下殘缺的檔案
+    fn save(&self, output: &BTreeMap<String, (NaiveDate, u64)>) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let tmp = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec_pretty(output)?)
+            .with_context(|| format!("write {}", tmp.display()))?;
+        std::fs::rename(&tmp, &self.path)
+            .with_context(|| format!("replace {}", self.path.display()))
+    }
+}
+
+/// 命名空間適用的配額與其鍵
+fn quota_of<'a>(config: &'a ServerConfig, namespace: &str) -> Option<(&'a str, &'a Quota)> {
+    config
+        .quotas
+        .get_key_value(namespace)
+        .or_else(|| config.quotas.get_key_value("*"))
+        .map(|(k, q)| (k.as_str(), q))
+}
+
+/// AddTask 時檢查任務數上限
+pub fn check_add(state: &State, namespace: &str) -> Result<()> {
+    let Some((key, quota)) = quota_of(&state.config, namespace) else {
+        return Ok(());
+    };
+    let Some(max) = quota.max_tasks else {
+        return Ok(());
+    };
+    let count = state
+        .tasks
+        .iter()
+        .filter(|kv| {
+            quota_of(&state.config, &kv.value().spec.namespace).is_some_and(|(k, _)| k == key)
+        })
+        .count();
+    if count >= max {
+        bail!("quota exceeded: quota {key:?} already has {count} task(s) (max {max})");
+    }
+    Ok(())
+}
+
+/// 開始執行前檢查並佔用名額；計數與檢查在同一把鎖內，同時開始的 run 不會一起超額。
+/// 沒有適用的配額時回傳 None
+pub fn check_run_start<'a>(state: &'a State, namespace: &str) -> Result<Option<RunPermit<'a>>> {
+    let Some((key, quota)) = quota_of(&state.config, namespace) else {
+        return Ok(None);
+    };
+
+    if let Some(max) = quota.max_output_bytes_per_day {
+        let used = output_today(state, key);
+        if used >= max {
+            bail!("quota exceeded: quota {key:?} wrote {used} output bytes today (max {max})");
+        }
+    }
+
+    let mut runs = state.quota_usage.runs.lock().unwrap();
+    let running = runs.entry(key.to_string()).or_default();
+    if let Some(max) = quota.max_concurrent_runs {
+        if *running >= max {
+            bail!("quota exceeded: quota {key:?} already has {max} concurrent run(s)");
+        }
+    }
+    *running += 1;
+    Ok(Some(RunPermit {
+        usage: &state.quota_usage,
+        key: key.to_string(),
+    }))
+}
+
+/// 累計今日輸出量並存檔
+pub fn record_output(state: &State, namespace: &str, bytes: u64) {
+    let Some((key, _)) = quota_of(&state.config, namespace) else {
+        return;
+    };
+    let today = today();
+    let mut output = state.quota_usage.output.lock().unwrap();
+    let ent = output.entry(key.to_string()).or_insert((today, 0));
+    if ent.0 != today {
+        *ent = (today, 0);
+    }
+    ent.1 = ent.1.saturating_add(bytes);
+    output.retain(|_, (day, _)| *day == today);
+    if let Err(e) = state.quota_usage.save(&output) {
+        eprintln!("quota usage save error: {e:#}");
+    }
+}
+
+fn output_today(state: &State, key: &str) -> u64 {
+    match state.quota_usage.output.lock().unwrap().get(key) {
+        Some(&(day, used)) if day == today() => used,
+        _ => 0,
+    }
+}
+
+fn today() -> NaiveDate {
+    Local::now().date_naive()
+}
@@ -0,0 +1,218 @@
+use crate::{config::ReplicaConfig, environments, history, protocol, State, TaskEntry};
+use anyhow::{bail, Context, Result};
+use bytes::Bytes;
+use futures_util::{SinkExt, StreamExt};
+use scheduler_core::{
+    BreakerState, ClientRequest, RunResult, SchedulerError, ServerResponse, TaskSpec,
+};
+use serde::Deserialize;
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::{
+    net::TcpStream,
+    sync::Notify,
+    time::{sleep, timeout},
+};
+use tokio_util::codec::{Framed, LengthDelimitedCodec};
+
+/// 轉送給主伺服器的請求最久等多久
+const FORWARD_TIMEOUT: Duration = Duration::from_secs(30);
+/// 事件串流中斷後多久重新連線
+const RECONNECT_DELAY: Duration = Duration::from_secs(2);
+/// 主伺服器先發事件才寫持久化檔：收到事件後稍等再重新讀取
+const SETTLE: Duration = Duration::from_millis(200);
+
+/// 副本在本機回覆的請求：只讀任務表、歷史與輸出檔（Subscribe 由轉播的事件回覆）
+pub fn serves_locally(req: &ClientRequest) -> bool {
+    matches!(
+        req,
+        ClientRequest::ListTasks
+            | ClientRequest::ListTasksSorted { .. }
+            | ClientRequest::Search { .. }
+            | ClientRequest::GetOutput { .. }
+            | ClientRequest::GetHistory { .. }
+            | ClientRequest::QueryRuns { .. }
+            | ClientRequest::GetServerInfo
+    )
+}
+
+/// 主伺服器會連續回覆多個片段的請求（訂閱事件、跟隨輸出）
+fn streams(req: &ClientRequest) -> bool {
+    match req {
+        ClientRequest::InEnvironment { request, .. } => streams(request),
+        ClientRequest::Subscribe { .. } | ClientRequest::RunNow { follow: true, .. } => true,
+        _ => false,
+    }
+}
+
+/// 其餘請求原樣轉送給主伺服器；每個請求一條新連線，送出後不重試，避免重複執行變更。
+/// 只轉回一個回覆，串流的請求直接拒絕。主伺服器記下的 actor 是副本的連線
+pub async fn forward(
+    cfg: &ReplicaConfig,
+    max_frame_bytes: usize,
+    req: &ClientRequest,
+) -> ServerResponse {
+    if streams(req) {
+        return ServerResponse::Error(SchedulerError::InvalidRequest {
+            msg: "a read-only replica cannot forward streaming requests; connect to the primary"
+                .to_string(),
+        });
+    }
+    let exchange = async {
+        let mut conn = connect(cfg, max_frame_bytes).await?;
+        conn.send(Bytes::from(serde_json::to_vec(req)?)).await?;
+        let frame = conn
+            .next()
+            .await
+            .context("primary closed the connection")??;
+        Ok::<_, anyhow::Error>(serde_json::from_slice(&frame)?)
+    };
+    match timeout(FORWARD_TIMEOUT, exchange).await {
+        Ok(Ok(resp)) => resp,
+        Ok(Err(e)) => ServerResponse::Error(SchedulerError::Internal {
+            msg: format!(
+                "read-only replica could not forward to primary {}: {e:#}",
+                cfg.primary
+            ),
+        }),
+        Err(_) => ServerResponse::Error(SchedulerError::Internal {
+            msg: format!("primary {} did not reply in time", cfg.primary),
+        }),
+    }
+}
+
+async fn connect(
+    cfg: &ReplicaConfig,
+    max_frame_bytes: usize,
+) -> Result<Framed<TcpStream, LengthDelimitedCodec>> {
+    let stream = TcpStream::connect(&cfg.primary)
+        .await
+        .with_context(|| format!("connect {}", cfg.primary))?;
+    Ok(Framed::new(stream, protocol::codec(max_frame_bytes)))
+}
+
+/// 重新讀取持久化檔：任務表（含最近結果與斷路器）、新附加的歷史與具名排程
+pub fn sync(state: &State) -> Result<()> {
+    reload_tasks(state)?;
+    state.history.refresh()?;
+    state.schedules.reload()?;
+    Ok(())
+}
+
+/// 以持久化檔取代任務表，不排程；檔案不完整（主伺服器正在改寫）時保留原狀，下次再讀
+fn reload_tasks(state: &State) -> Result<()> {
+    #[derive(Deserialize)]
+    struct Rec {
+        id: u64,
+        spec: TaskSpec,
+        #[serde(default)]
+        last_result: Option<RunResult>,
+        #[serde(default)]
+        breaker: Option<BreakerState>,
+    }
+
+    let path = &state.config.data_path;
+    let list: Vec<serde_json::Value> = match std::fs::read(path) {
+        Ok(bytes) => {
+            serde_json::from_slice(&bytes).with_context(|| format!("parse {}", path.display()))?
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(e).with_context(|| format!("read {}", path.display())),
+    };
+    let mut seen = HashSet::new();
+    // 解析不了的紀錄由主伺服器隔離，這裡略過
+    for r in list
+        .into_iter()
+        .filter_map(|v| serde_json::from_value::<Rec>(v).ok())
+    {
+        seen.insert(r.id);
+        match state.tasks.get_mut(&r.id) {
+            Some(mut ent) => {
+                ent.spec = r.spec;
+                *ent.last_result.lock().unwrap() = r.last_result;
+            }
+            None => {
+                state.tasks.insert(
+                    r.id,
+                    TaskEntry {
+                        spec: r.spec,
+                        cancel: None,
+                        last_result: Arc::new(Mutex::new(r.last_result)),
+                    },
+                );
+            }
+        }
+        match r.breaker {
+            Some(b) => {
+                state.breakers.insert(r.id, b);
+            }
+            None => {
+                state.breakers.remove(&r.id);
+            }
+        }
+    }
+    state.tasks.retain(|id, _| seen.contains(id));
+    state.breakers.retain(|id, _| seen.contains(id));
+    Ok(())
+}
+
+/// 轉播主伺服器的事件，並在事件到達或每隔 sync_interval_secs 重新讀取持久化檔
+pub fn spawn(state: Arc<State>, cfg: ReplicaConfig) {
+    let changed = Arc::new(Notify::new());
+
+    let (st, notify, relay_cfg) = (state.clone(), changed.clone(), cfg.clone());
+    tokio::spawn(async move {
+        loop {
+            if let Err(e) = relay(&st, &relay_cfg, &notify).await {
+                eprintln!("replica event stream from {}: {e:#}", relay_cfg.primary);
+            }
+            sleep(RECONNECT_DELAY).await;
+        }
+    });
+
+    tokio::spawn(async move {
+        let interval = Duration::from_secs(cfg.sync_interval_secs.max(1));
+        loop {
+            tokio::select! {
+                _ = sleep(interval) => {}
+                _ = changed.notified() => sleep(SETTLE).await,
+            }
+            if let Err(e) = history::blocking(&state, sync).await.and_then(|r| r) {
+                eprintln!("replica sync error: {e:#}");
+            }
+        }
+    });
+}
+
+/// 從目前的 seq 訂閱主伺服器的事件，收到就轉播給本機的訂閱者
+async fn relay(state: &State, cfg: &ReplicaConfig, changed: &Notify) -> Result<()> {
+    let mut conn = connect(cfg, state.config.max_frame_bytes).await?;
+    let since = state.events.last_seq();
+    let req = environments::authorize(
+        &state.config,
+        ClientRequest::Subscribe { since: Some(since) },
+    );
+    conn.send(Bytes::from(serde_json::to_vec(&req)?)).await?;
+
+    let mut first = true;
+    while let Some(frame) = conn.next().await {
+        match serde_json::from_slice::<ServerResponse>(&frame?)? {
+            ServerResponse::Event(ev) => {
+                state.events.relay(ev);
+                changed.notify_one();
+            }
+            // 主伺服器的 seq 倒退（高水位檔遺失）：從頭接續
+            ServerResponse::Heartbeat { last_seq, .. } if first && last_seq < since => {
+                state.events.rewind(last_seq);
+                bail!("primary restarted (event seq {last_seq} < {since}), resubscribing");
+            }
+            ServerResponse::Error(e) => bail!("{e}"),
+            _ => {}
+        }
+        first = false;
+    }
+    bail!("primary closed the event stream")
+}
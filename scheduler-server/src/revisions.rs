@@ -0,0 +1,121 @@
+use crate::local_now_fixed;
+use anyhow::{Context, Result};
+use scheduler_core::{Revision, SpecChange, TaskSpec};
+use std::{
+    collections::BTreeSet,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+/// 任務規格的版本紀錄：JSON Lines 只附加檔 + 記憶體副本（舊到新）。
+/// 任務移除後仍保留，之後可查到它最後的樣子
+pub struct Revisions {
+    path: PathBuf,
+    records: Mutex<Vec<Revision>>,
+}
+
+impl Revisions {
+    /// 載入版本檔；壞掉的行略過並提示
+    pub fn load(path: &Path) -> Result<Self> {
+        let mut records = Vec::new();
+        if path.exists() {
+            let text = std::fs::read_to_string(path)
+                .with_context(|| format!("read {}", path.display()))?;
+            for (i, line) in text.lines().enumerate() {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                match serde_json::from_str::<Revision>(line) {
+                    Ok(rev) => records.push(rev),
+                    Err(e) => eprintln!("revisions {}:{}: skipped: {e}", path.display(), i + 1),
+                }
+            }
+        }
+        Ok(Self {
+            path: path.to_path_buf(),
+            records: Mutex::new(records),
+        })
+    }
+
+    /// 出現過的最大任務 id；任務 id 不可重用，否則會接到舊任務的版本
+    pub fn max_task_id(&self) -> u64 {
+        let records = self.records.lock().unwrap();
+        records.iter().map(|r| r.task_id).max().unwrap_or(0)
+    }
+
+    /// 記錄任務的新版本並回傳版本號
+    pub fn record(
+        &self,
+        task_id: u64,
+        spec: &TaskSpec,
+        actor: &str,
+        rollback_of: Option<u32>,
+    ) -> Result<u32> {
+        let mut records = self.records.lock().unwrap();
+        let prev = records.iter().rev().find(|r| r.task_id == task_id);
+        let rev = Revision {
+            task_id,
+            revision: prev.map_or(1, |p| p.revision + 1),
+            at: local_now_fixed(),
+            actor: actor.to_string(),
+            rollback_of,
+            changes: match prev {
+                Some(p) => diff(&p.spec, spec)?,
+                None => Vec::new(),
+            },
+            spec: spec.clone(),
+        };
+
+        let mut line = serde_json::to_vec(&rev)?;
+        line.push(b'\n');
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .with_context(|| format!("open {}", self.path.display()))?
+            .write_all(&line)?;
+        let revision = rev.revision;
+        records.push(rev);
+        Ok(revision)
+    }
+
+    /// 某任務的所有版本，舊到新
+    pub fn for_task(&self, task_id: u64) -> Vec<Revision> {
+        let records = self.records.lock().unwrap();
+        records
+            .iter()
+            .filter(|r| r.task_id == task_id)
+            .cloned()
+            .collect()
+    }
+
+    pub fn get(&self, task_id: u64, revision: u32) -> Option<Revision> {
+        let records = self.records.lock().unwrap();
+        records
+            .iter()
+            .find(|r| r.task_id == task_id && r.revision == revision)
+            .cloned()
+    }
+}
+
+/// 逐一比較 TaskSpec 的頂層欄位（以 JSON 表示）
+fn diff(old: &TaskSpec, new: &TaskSpec) -> Result<Vec<SpecChange>> {
+    let (old, new) = (serde_json::to_value(old)?, serde_json::to_value(new)?);
+    let (Some(old), Some(new)) = (old.as_object(), new.as_object()) else {
+        return Ok(Vec::new());
+    };
+    let fields: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+    Ok(fields
+        .into_iter()
+        .filter(|f| old.get(*f) != new.get(*f))
+        .map(|f| SpecChange {
+            field: f.clone(),
+            old: old.get(f).map(|v| v.to_string()),
+            new: new.get(f).map(|v| v.to_string()),
+        })
+        .collect())
+}
@@ -0,0 +1,183 @@
+use crate::config::{Compression, RotateConfig, RotateEvery};
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Local};
+use std::{
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+/// 只附加的日誌檔；設定了輪替時，超過大小或跨過時段就把目前的檔案改名為
+/// `<檔名>.<時間>` 並開新檔，在背景壓縮並刪除超過保留份數的舊檔。
+/// 寫入與輪替在同一把鎖內（由呼叫端持有），每一行都完整落在某一個檔案
+pub struct RotatingFile {
+    path: PathBuf,
+    rotate: Option<RotateConfig>,
+    file: File,
+    len: u64,
+    /// 目前的檔案屬於哪個時段（every 未設定時為 None）
+    period: Option<String>,
+}
+
+impl RotatingFile {
+    pub fn open(path: &Path, rotate: Option<RotateConfig>) -> Result<Self> {
+        if let Some(cfg) = &rotate {
+            if cfg.max_bytes.is_none() && cfg.every.is_none() {
+                bail!("rotate needs max_bytes or every");
+            }
+            if cfg.max_bytes == Some(0) || cfg.keep == 0 {
+                bail!("rotate.max_bytes and rotate.keep must be at least 1");
+            }
+        }
+        let file = append(path)?;
+        let meta = file.metadata()?;
+        // 沿用既有的檔案時，以最後修改時間判斷它屬於哪個時段
+        let modified = meta
+            .modified()
+            .map(DateTime::<Local>::from)
+            .unwrap_or_else(|_| Local::now());
+        let period = rotate
+            .as_ref()
+            .and_then(|cfg| cfg.every)
+            .map(|every| period_of(every, modified));
+        Ok(Self {
+            path: path.to_path_buf(),
+            rotate,
+            file,
+            len: meta.len(),
+            period,
+        })
+    }
+
+    /// 寫入一整行；需要時先輪替（輪替失敗只提示，繼續寫目前的檔案）
+    pub fn write_line(&mut self, line: &[u8]) -> std::io::Result<()> {
+        if self.due(line.len() as u64) {
+            if let Err(e) = self.rotate() {
+                eprintln!("log {} rotate error: {e:#}", self.path.display());
+            }
+        }
+        self.file.write_all(line)?;
+        self.len += line.len() as u64;
+        Ok(())
+    }
+
+    fn due(&self, incoming: u64) -> bool {
+        let Some(cfg) = &self.rotate else {
+            return false;
+        };
+        // 空檔案不輪替，單行超過上限時照樣寫入
+        let too_big = cfg
+            .max_bytes
+            .is_some_and(|max| self.len > 0 && self.len + incoming > max);
+        let new_period = cfg
+            .every
+            .is_some_and(|every| self.period.as_deref() != Some(&period_of(every, Local::now())));
+        too_big || new_period
+    }
+
+    fn rotate(&mut self) -> Result<()> {
+        let Some(cfg) = self.rotate.clone() else {
+            return Ok(());
+        };
+        let stamp = Local::now().format("%Y%m%dT%H%M%S%.3f").to_string();
+        let mut rotated = sibling(&self.path, &stamp);
+        let mut n = 1;
+        while rotated.exists() {
+            rotated = sibling(&self.path, &format!("{stamp}-{n}"));
+            n += 1;
+        }
+        std::fs::rename(&self.path, &rotated)
+            .with_context(|| format!("rename {} to {}", self.path.display(), rotated.display()))?;
+        self.file = append(&self.path)?;
+        self.len = 0;
+        self.period = cfg.every.map(|every| period_of(every, Local::now()));
+
+        // 壓縮與清理不佔住寫入的鎖
+        let path = self.path.clone();
+        std::thread::spawn(move || {
+            if let Err(e) = compress(&rotated, cfg.compression) {
+                eprintln!("log {} compress error: {e:#}", rotated.display());
+            }
+            if let Err(e) = prune(&path, cfg.keep) {
+                eprintln!("log {} prune error: {e:#}", path.display());
+            }
+        });
+        Ok(())
+    }
+}
+
+fn append(path: &Path) -> Result<File> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("open {}", path.display()))
+}
+
+fn period_of(every: RotateEvery, at: DateTime<Local>) -> String {
+    match every {
+        RotateEvery::Hourly => at.format("%Y%m%d%H").to_string(),
+        RotateEvery::Daily => at.format("%Y%m%d").to_string(),
+    }
+}
+
+/// 與日誌同目錄的 `<檔名>.<suffix>`
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{name}.{suffix}"))
+}
+
+/// 壓縮成 .gz / .zst 後刪除原檔；寫到暫存檔再改名，中途失敗時保留原檔
+fn compress(path: &Path, compression: Compression) -> Result<()> {
+    let ext = match compression {
+        Compression::None => return Ok(()),
+        Compression::Gzip => "gz",
+        Compression::Zstd => "zst",
+    };
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let target = path.with_file_name(format!("{name}.{ext}"));
+    let tmp = path.with_file_name(format!("{name}.{ext}.tmp"));
+    let mut input = File::open(path).with_context(|| format!("open {}", path.display()))?;
+    let output = File::create(&tmp).with_context(|| format!("create {}", tmp.display()))?;
+    match compression {
+        Compression::Gzip => {
+            let mut enc = flate2::write::GzEncoder::new(output, flate2::Compression::default());
+            std::io::copy(&mut input, &mut enc)?;
+            enc.finish()?.sync_all()?;
+        }
+        _ => zstd::stream::copy_encode(&mut input, output, 0)?,
+    }
+    std::fs::rename(&tmp, &target).with_context(|| format!("replace {}", target.display()))?;
+    std::fs::remove_file(path).with_context(|| format!("remove {}", path.display()))
+}
+
+/// 只保留最新的 keep 份輪替檔（檔名中的時間依字典序即時間順序）
+fn prune(path: &Path, keep: usize) -> Result<()> {
+    let dir = match path.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p,
+        _ => Path::new("."),
+    };
+    let prefix = format!(
+        "{}.",
+        path.file_name().unwrap_or_default().to_string_lossy()
+    );
+    let mut rotated: Vec<PathBuf> = std::fs::read_dir(dir)
+        .with_context(|| format!("list {}", dir.display()))?
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let name = entry.file_name().into_string().ok()?;
+            let rest = name.strip_prefix(&prefix)?;
+            let rotated = rest.starts_with(|c: char| c.is_ascii_digit()) && !rest.ends_with(".tmp");
+            rotated.then(|| entry.path())
+        })
+        .collect();
+    rotated.sort();
+    let excess = rotated.len().saturating_sub(keep);
+    for old in &rotated[..excess] {
+        std::fs::remove_file(old).with_context(|| format!("remove {}", old.display()))?;
+    }
+    Ok(())
+}
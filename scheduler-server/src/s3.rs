@@ -0,0 +1,225 @@
+use crate::{artifacts, config::S3Config, State};
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, FixedOffset, Utc};
+use hmac::{Hmac, Mac};
+use scheduler_core::TaskSpec;
+use sha2::{Digest, Sha256};
+use std::{sync::Arc, time::Duration};
+
+/// 第一次重試前的等待；之後每次加倍
+const RETRY_BASE: Duration = Duration::from_secs(2);
+
+struct Credentials {
+    access_key: String,
+    secret_key: String,
+    session_token: Option<String>,
+}
+
+/// 啟動時檢查設定與憑證，避免每次上傳才失敗
+pub fn check(cfg: &S3Config) -> Result<()> {
+    if cfg.bucket.is_empty() {
+        bail!("s3 bucket must not be empty");
+    }
+    credentials(cfg)?;
+    Ok(())
+}
+
+/// 在背景上傳一次執行的輸出；失敗依指數退避重試，最後放棄時只記錄
+pub fn upload(
+    state: &Arc<State>,
+    task_id: u64,
+    run_id: u64,
+    spec: &TaskSpec,
+    at: DateTime<FixedOffset>,
+    content: Vec<u8>,
+) {
+    let Some(cfg) = state.config.s3.clone() else {
+        return;
+    };
+    let key = object_key(&cfg.key, task_id, run_id, spec, at);
+    tokio::spawn(async move {
+        let (ext, body) = match artifacts::encode(cfg.compression, &content) {
+            Ok(v) => v,
+            Err(e) => {
+                eprintln!("task {} s3 encode error: {e:#}", task_id);
+                return;
+            }
+        };
+        let key = format!("{key}.{ext}");
+        let body = Arc::new(body);
+        let mut delay = RETRY_BASE;
+        for attempt in 1..=cfg.max_attempts.max(1) {
+            let (c, k, b) = (cfg.clone(), key.clone(), body.clone());
+            let res = tokio::task::spawn_blocking(move || put_object(&c, &k, &b)).await;
+            match res {
+                Ok(Ok(())) => return,
+                Ok(Err(e)) if attempt < cfg.max_attempts => {
+                    eprintln!(
+                        "task {} s3 upload {} failed (attempt {attempt}): {e:#}",
+                        task_id, key
+                    );
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                }
+                Ok(Err(e)) => {
+                    eprintln!("❌ task {} s3 upload {} gave up: {e:#}", task_id, key);
+                }
+                Err(e) => {
+                    eprintln!("❌ task {} s3 upload {} failed: {e}", task_id, key);
+                    return;
+                }
+            }
+        }
+    });
+}
+
+fn object_key(
+    template: &str,
+    task_id: u64,
+    run_id: u64,
+    spec: &TaskSpec,
+    at: DateTime<FixedOffset>,
+) -> String {
+    let name: String = spec
+        .name
+        .as_deref()
+        .unwrap_or("unnamed")
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || "._-".contains(c) {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    template
+        .replace("{task_id}", &task_id.to_string())
+        .replace("{run_id}", &run_id.to_string())
+        .replace("{namespace}", &spec.namespace)
+        .replace("{name}", &name)
+        .replace("{date}", &at.format("%Y-%m-%d").to_string())
+        .replace("{time}", &at.format("%H%M%S").to_string())
+        .trim_start_matches('/')
+        .to_string()
+}
+
+fn credentials(cfg: &S3Config) -> Result<Credentials> {
+    let env = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+    let access_key = cfg.access_key.clone().or_else(|| env("AWS_ACCESS_KEY_ID"));
+    let secret_key = cfg
+        .secret_key
+        .clone()
+        .or_else(|| env("AWS_SECRET_ACCESS_KEY"));
+    match (access_key, secret_key) {
+        (Some(access_key), Some(secret_key)) => Ok(Credentials {
+            access_key,
+            secret_key,
+            session_token: env("AWS_SESSION_TOKEN"),
+        }),
+        _ => bail!("s3 credentials missing: set access_key/secret_key or AWS_ACCESS_KEY_ID/AWS_SECRET_ACCESS_KEY"),
+    }
+}
+
+/// 以 AWS Signature V4 簽署的 PutObject
+fn put_object(cfg: &S3Config, key: &str, body: &[u8]) -> Result<()> {
+    let creds = credentials(cfg)?;
+    let endpoint = cfg
+        .endpoint
+        .clone()
+        .unwrap_or_else(|| format!("https://s3.{}.amazonaws.com", cfg.region));
+    let endpoint = endpoint.trim_end_matches('/');
+    let (scheme, host) = endpoint
+        .split_once("://")
+        .context("s3 endpoint must start with http:// or https://")?;
+    let key_path = uri_encode(key);
+    let (host, path) = if cfg.path_style {
+        (
+            host.to_string(),
+            format!("/{}/{key_path}", uri_encode(&cfg.bucket)),
+        )
+    } else {
+        (format!("{}.{host}", cfg.bucket), format!("/{key_path}"))
+    };
+    let url = format!("{scheme}://{host}{path}");
+
+    let now = Utc::now();
+    let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+    let date = now.format("%Y%m%d").to_string();
+    let payload_hash = hex::encode(Sha256::digest(body));
+
+    let mut headers = vec![
+        ("host", host.clone()),
+        ("x-amz-content-sha256", payload_hash.clone()),
+        ("x-amz-date", amz_date.clone()),
+    ];
+    if let Some(token) = &creds.session_token {
+        headers.push(("x-amz-security-token", token.clone()));
+    }
+    let canonical_headers: String = headers
+        .iter()
+        .map(|(k, v)| format!("{k}:{}\n", v.trim()))
+        .collect();
+    let signed_headers = headers
+        .iter()
+        .map(|(k, _)| *k)
+        .collect::<Vec<_>>()
+        .join(";");
+    let canonical_request =
+        format!("PUT\n{path}\n\n{canonical_headers}\n{signed_headers}\n{payload_hash}");
+
+    let scope = format!("{date}/{}/s3/aws4_request", cfg.region);
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{amz_date}\n{scope}\n{}",
+        hex::encode(Sha256::digest(canonical_request.as_bytes()))
+    );
+    let mut signing_key = hmac(format!("AWS4{}", creds.secret_key).as_bytes(), &date);
+    for part in [cfg.region.as_str(), "s3", "aws4_request"] {
+        signing_key = hmac(&signing_key, part);
+    }
+    let signature = hex::encode(hmac(&signing_key, &string_to_sign));
+    let authorization = format!(
+        "AWS4-HMAC-SHA256 Credential={}/{scope}, SignedHeaders={signed_headers}, Signature={signature}",
+        creds.access_key
+    );
+
+    let agent = ureq::AgentBuilder::new()
+        .timeout(Duration::from_secs(cfg.timeout_secs.max(1)))
+        .build();
+    let mut req = agent
+        .put(&url)
+        .set("Authorization", &authorization)
+        .set("Content-Type", "text/plain; charset=utf-8");
+    for (k, v) in &headers {
+        if *k != "host" {
+            req = req.set(k, v);
+        }
+    }
+    match req.send_bytes(body) {
+        Ok(_) => Ok(()),
+        Err(ureq::Error::Status(code, resp)) => {
+            let text = resp.into_string().unwrap_or_default();
+            bail!("HTTP {code}: {}", text.trim())
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn hmac(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("hmac accepts any key length");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// SigV4 的 URI 編碼：保留 unreserved 字元與路徑分隔的 /
+fn uri_encode(s: &str) -> String {
+    let mut out = String::new();
+    for b in s.bytes() {
+        if b.is_ascii_alphanumeric() || b"-_.~/".contains(&b) {
+            out.push(b as char);
+        } else {
+            out.push_str(&format!("%{b:02X}"));
+        }
+    }
+    out
+}
@@ -0,0 +1,29 @@
+use crate::{config::SandboxConfig, policy};
+use scheduler_core::{SandboxProfile, TaskSpec};
+use std::ffi::OsStr;
+use tokio::process::Command;
+
+/// 以 bubblewrap 包住任務命令：
+/// 根目錄唯讀掛載、/dev /proc 與私有 /tmp，宣告的路徑可寫，預設切斷網路
+/// bwrap 本身以伺服器的 PATH 尋找，不受任務 env 影響
+pub fn bwrap_command(
+    cfg: &SandboxConfig,
+    profile: &SandboxProfile,
+    program: &OsStr,
+    spec: &TaskSpec,
+) -> Command {
+    let mut cmd = Command::new(policy::server_program(&cfg.bwrap_path));
+    cmd.args(["--die-with-parent", "--new-session"])
+        .args(["--ro-bind", "/", "/"])
+        .args(["--dev", "/dev"])
+        .args(["--proc", "/proc"])
+        .args(["--tmpfs", "/tmp"]);
+    for p in &profile.writable_paths {
+        cmd.arg("--bind").arg(p).arg(p);
+    }
+    if !profile.network {
+        cmd.arg("--unshare-net");
+    }
+    cmd.arg("--").arg(program).args(&spec.args);
+    cmd
+}
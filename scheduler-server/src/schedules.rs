@@ -0,0 +1,123 @@
+use anyhow::{bail, Context, Result};
+use scheduler_core::{NamedSchedule, Schedule, SchedulerError};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+/// 具名排程：多個任務以 `named <名稱>` 引用同一個排程，改一次全部套用。
+/// 項目不多，每次變更整份改寫
+pub struct NamedSchedules {
+    path: PathBuf,
+    items: Mutex<BTreeMap<String, Schedule>>,
+}
+
+impl NamedSchedules {
+    pub fn load(path: &Path) -> Result<Self> {
+        let items = if path.exists() {
+            let text = std::fs::read_to_string(path)
+                .with_context(|| format!("read {}", path.display()))?;
+            serde_json::from_str(&text).with_context(|| format!("parse {}", path.display()))?
+        } else {
+            BTreeMap::new()
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            items: Mutex::new(items),
+        })
+    }
+
+    /// 重新讀取檔案（唯讀副本跟上主伺服器的變更）
+    pub fn reload(&self) -> Result<()> {
+        let fresh = Self::load(&self.path)?;
+        *self.items.lock().unwrap() = fresh.items.into_inner().unwrap();
+        Ok(())
+    }
+
+    /// 任務實際依循的排程；引用的具名排程不存在時視同 Manual（不自動執行）
+    pub fn effective(&self, schedule: &Schedule) -> Schedule {
+        match schedule {
+            Schedule::Named { name } => self
+                .items
+                .lock()
+                .unwrap()
+                .get(name)
+                .cloned()
+                .unwrap_or(Schedule::Manual),
+            s => s.clone(),
+        }
+    }
+
+    /// 新增任務前確認引用的具名排程存在
+    pub fn check_ref(&self, schedule: &Schedule) -> Result<()> {
+        if let Schedule::Named { name } = schedule {
+            if !self.items.lock().unwrap().contains_key(name) {
+                bail!(SchedulerError::InvalidSchedule {
+                    msg: format!("named schedule {name:?} does not exist"),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// tasks 為引用各排程的任務（名稱 → id，已排序）
+    pub fn list(&self, mut tasks: BTreeMap<String, Vec<u64>>) -> Vec<NamedSchedule> {
+        let items = self.items.lock().unwrap();
+        items
+            .iter()
+            .map(|(name, schedule)| NamedSchedule {
+                name: name.clone(),
+                schedule: schedule.clone(),
+                tasks: tasks.remove(name).unwrap_or_default(),
+            })
+            .collect()
+    }
+
+    pub fn set(&self, name: &str, schedule: Schedule) -> Result<()> {
+        check(name, &schedule)?;
+        let mut items = self.items.lock().unwrap();
+        items.insert(name.to_string(), schedule);
+        self.save(&items)
+    }
+
+    /// 回傳是否存在
+    pub fn remove(&self, name: &str) -> Result<bool> {
+        let mut items = self.items.lock().unwrap();
+        if items.remove(name).is_none() {
+            return Ok(false);
+        }
+        self.save(&items)?;
+        Ok(true)
+    }
+
+    /// 先寫暫存檔再改名，避免改寫到一半留下殘缺的檔案
+    fn save(&self, items: &BTreeMap<String, Schedule>) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let tmp = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec_pretty(items)?)
+            .with_context(|| format!("write {}", tmp.display()))?;
+        std::fs::rename(&tmp, &self.path)
+            .with_context(|| format!("replace {}", self.path.display()))
+    }
+}
+
+/// 具名排程只能是有固定時間的排程（Once、Daily、Hourly）
+fn check(name: &str, schedule: &Schedule) -> Result<()> {
+    if name.is_empty() || name.contains(char::is_whitespace) {
+        bail!("invalid schedule name {name:?}");
+    }
+    let msg = match *schedule {
+        Schedule::Daily { hour, minute } if hour > 23 || minute > 59 => {
+            format!("daily time out of range: {hour:02}:{minute:02}")
+        }
+        Schedule::Hourly { minute } if minute > 59 => {
+            format!("hourly minute must be within 0..=59, got {minute}")
+        }
+        Schedule::Once(_) | Schedule::Daily { .. } | Schedule::Hourly { .. } => return Ok(()),
+        _ => format!("named schedule must be once, daily or hourly, got {schedule}"),
+    };
+    bail!(SchedulerError::InvalidSchedule { msg })
+}
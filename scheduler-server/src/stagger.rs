@@ -0,0 +1,36 @@
+use crate::State;
+use scheduler_core::{Schedule, TaskSpec};
+use std::time::Duration;
+
+/// Daily 任務實際啟動時間相對排定分鐘的延後量
+///
+/// 同一分鐘的任務依 id 排序後平均分配到 window 內；每次排程時重新計算，
+/// 任務增減後下一輪即套用新的分配。
+pub fn offset(state: &State, id: u64, spec: &TaskSpec) -> Duration {
+    let cfg = &state.config.stagger;
+    let Schedule::Daily { hour, minute } = state.schedules.effective(&spec.schedule) else {
+        return Duration::ZERO;
+    };
+    if cfg.window_secs == 0 || spec.exact_start {
+        return Duration::ZERO;
+    }
+
+    let mut group: Vec<u64> = state
+        .tasks
+        .iter()
+        .filter(|kv| {
+            let s = &kv.value().spec;
+            !s.exact_start
+                && matches!(state.schedules.effective(&s.schedule), Schedule::Daily { hour: h, minute: m } if h == hour && m == minute)
+        })
+        .map(|kv| *kv.key())
+        .collect();
+    if group.len() < cfg.min_tasks.max(2) {
+        return Duration::ZERO;
+    }
+    group.sort_unstable();
+    let Some(rank) = group.iter().position(|&x| x == id) else {
+        return Duration::ZERO;
+    };
+    Duration::from_millis(cfg.window_secs * 1000 * rank as u64 / group.len() as u64)
+}
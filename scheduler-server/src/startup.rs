@@ -0,0 +1,96 @@
+use crate::{builtin, listing, validate, State};
+use anyhow::Result;
+use scheduler_core::{
+    EventKind, ServerInfo, StartupReport, TaskSpec, UpcomingFire, SYSTEM_NAMESPACE,
+};
+use std::sync::Arc;
+
+/// 啟動摘要列出幾次接下來的執行
+const NEXT_FIRES: usize = 5;
+
+/// 持久化的任務在這一版、這份設定下是否仍然有效
+pub fn check_loaded(state: &State, spec: &TaskSpec) -> Result<()> {
+    // source 由 git 同步寫入，新增時才不允許客戶端指定
+    let spec = TaskSpec {
+        source: None,
+        ..spec.clone()
+    };
+    validate::validate_spec(&state.config, &spec)?;
+    state.schedules.check_ref(&spec.schedule)?;
+    Ok(())
+}
+
+/// 任務都載入、內建任務登記後：補上接下來的執行與儲存檢查，寫到日誌、發出 ServerStarted 事件
+/// 並保留給 GetServerInfo
+pub fn finish(state: &Arc<State>, mut report: StartupReport) {
+    report.next_fires = next_fires(state, &report);
+    (report.storage_ok, report.storage_report) = builtin::check_storage(state);
+
+    println!(
+        "🩺 startup: {} task(s) loaded, {} invalid, {} quarantined, {} orphaned run(s), storage {}",
+        report.tasks_loaded,
+        report.invalid.len(),
+        report.quarantined,
+        report.orphans.len(),
+        if report.storage_ok {
+            "ok"
+        } else {
+            "has problems"
+        }
+    );
+    for t in &report.invalid {
+        println!("   - task {} not scheduled: {}", t.id, t.reason);
+    }
+    if !report.storage_ok {
+        for line in report.storage_report.lines() {
+            println!("   {line}");
+        }
+    }
+    for f in &report.next_fires {
+        let name = f
+            .name
+            .as_deref()
+            .map(|n| format!(" ({n})"))
+            .unwrap_or_default();
+        println!("   next: task {}{} at {}", f.task_id, name, f.at);
+    }
+    state.events.emit(EventKind::ServerStarted {
+        report: report.clone(),
+    });
+    let _ = state.startup.set(report);
+}
+
+pub fn info(state: &State) -> ServerInfo {
+    ServerInfo {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        started_at: state.started_at,
+        startup: state.startup.get().cloned().unwrap_or_default(),
+        freeze: state.freeze.current(),
+    }
+}
+
+/// 最早的幾次預定執行；內建任務與未排程的無效任務不列
+fn next_fires(state: &State, report: &StartupReport) -> Vec<UpcomingFire> {
+    let mut fires: Vec<UpcomingFire> = state
+        .tasks
+        .iter()
+        .filter(|kv| kv.value().spec.namespace != SYSTEM_NAMESPACE)
+        .filter(|kv| !report.invalid.iter().any(|t| t.id == *kv.key()))
+        .filter_map(|kv| {
+            let ent = kv.value();
+            let last = ent.last_result.lock().unwrap().clone();
+            let at = listing::next_run(
+                &state.schedules.effective(&ent.spec.schedule),
+                last.as_ref(),
+            )?;
+            Some(UpcomingFire {
+                task_id: *kv.key(),
+                name: ent.spec.name.clone(),
+                at,
+            })
+        })
+        .collect();
+    fires.sort_by_key(|f| (f.at, f.task_id));
+    fires.truncate(NEXT_FIRES);
+    fires
+}
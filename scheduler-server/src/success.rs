@@ -0,0 +1,59 @@
+use crate::encoding;
+use anyhow::{bail, Context, Result};
+use regex::Regex;
+use scheduler_core::{OutputEncoding, RunOutcome, SuccessCriteria};
+
+/// 輸出條件不成立、但程式以 0 結束時記錄的結束碼
+pub const CRITERIA_FAILED: i32 = 1;
+
+/// AddTask 時的檢查；raw 時樣式以位元組的正規表示式編譯
+pub fn check(c: &SuccessCriteria, encoding: OutputEncoding) -> Result<()> {
+    if c.exit_codes.iter().any(|&code| !(0..=255).contains(&code)) {
+        bail!("success exit_codes must be within 0..=255");
+    }
+    for pattern in [&c.output_matches, &c.output_rejects].into_iter().flatten() {
+        let compiled = match encoding {
+            OutputEncoding::Raw => regex::bytes::Regex::new(pattern).map(drop),
+            _ => Regex::new(pattern).map(drop),
+        };
+        compiled.with_context(|| format!("invalid success regex {pattern:?}"))?;
+    }
+    Ok(())
+}
+
+/// 依條件判定一次執行：回傳 (status_code, exit_code)，
+/// 改判時 exit_code 帶程式實際的結束碼；逾時、被終止等結局不改判。
+/// utf8 而輸出無法解碼時，輸出條件一律不成立
+pub fn judge(
+    criteria: Option<&SuccessCriteria>,
+    encoding: OutputEncoding,
+    outcome: RunOutcome,
+    status_code: i32,
+    stdout: &[u8],
+) -> (i32, Option<i32>) {
+    let Some(c) = criteria.filter(|_| outcome == RunOutcome::Exited) else {
+        return (status_code, None);
+    };
+    let text = encoding::text(encoding, stdout).ok();
+    // 規格已在新增時檢查過；仍無法編譯的樣式一律視為條件不成立
+    let found = |pattern: &String| match encoding {
+        OutputEncoding::Raw => regex::bytes::Regex::new(pattern)
+            .ok()
+            .map(|re| re.is_match(stdout)),
+        _ => Some(Regex::new(pattern).ok()?.is_match(text.as_deref()?)),
+    };
+    let code_ok = status_code == 0 || c.exit_codes.contains(&status_code);
+    let output_ok = c
+        .output_matches
+        .as_ref()
+        .is_none_or(|p| found(p) == Some(true))
+        && c.output_rejects
+            .as_ref()
+            .is_none_or(|p| found(p) == Some(false));
+    match (code_ok && output_ok, status_code) {
+        (true, 0) => (0, None),
+        (true, code) => (0, Some(code)),
+        (false, 0) => (CRITERIA_FAILED, Some(0)),
+        (false, code) => (code, None),
+    }
+}
@@ -0,0 +1,297 @@
+use crate::outputs;
+use anyhow::{bail, Context, Result};
+use chrono::{
+    format::StrftimeItems, DateTime, Days, Local, Months, NaiveTime, TimeDelta, TimeZone,
+};
+use scheduler_core::{OutputsFrom, RunResult, Schedule, ServerContext, TaskSpec};
+use std::path::{Component, Path, PathBuf};
+
+/// AddTask 時檢查模板語法與變數名稱；vars 的值在執行時才查，可之後再設定
+pub fn check(spec: &TaskSpec) -> Result<()> {
+    let mut uses_upstream = false;
+    for text in texts(spec) {
+        for var in variables(&text)? {
+            if let Some(expr) = date_expr(var) {
+                expr.with_context(|| format!("{{{{{var}}}}}"))?;
+                continue;
+            }
+            match var.split_once('.') {
+                Some(("upstream", key)) if outputs::valid_key(key) => uses_upstream = true,
+                Some(("server", "hostname" | "environment")) => {}
+                Some(("vars", key)) if outputs::valid_key(key) => {}
+                _ => bail!("unknown template variable {{{{{var}}}}}"),
+            }
+        }
+    }
+    if uses_upstream && !matches!(spec.schedule, Schedule::After { .. }) {
+        bail!("{{{{upstream.*}}}} can only be used by tasks scheduled after another task");
+    }
+    if let Some(key) = spec.env.keys().find(|k| k.is_empty() || k.contains('=')) {
+        bail!("invalid env name {key:?}");
+    }
+    Ok(())
+}
+
+/// 字串是否含有模板
+pub fn is_templated(text: &str) -> bool {
+    text.contains("{{")
+}
+
+/// 代入模板變數，得到這次實際執行的規格
+/// upstream 可用前置任務發布的輸出，以及內建的 run_id、status_code、output_path；
+/// 日期運算式以這次執行開始時的本地時間計算
+pub fn render(
+    spec: &TaskSpec,
+    ctx: &ServerContext,
+    upstream: Option<&RunResult>,
+) -> Result<TaskSpec> {
+    let now = Local::now();
+    let lookup = |var: &str| -> Result<String> {
+        if let Some(expr) = date_expr(var) {
+            return expr
+                .and_then(|expr| expr.eval(now))
+                .with_context(|| format!("{{{{{var}}}}}"));
+        }
+        match var.split_once('.') {
+            Some(("server", "hostname")) => Ok(ctx.hostname.clone()),
+            Some(("server", "environment")) => Ok(ctx.environment.clone()),
+            Some(("vars", key)) => match ctx.vars.get(key) {
+                Some(v) => Ok(v.clone()),
+                None => bail!("{{{{{var}}}}}: server variable {key:?} is not set"),
+            },
+            Some(("upstream", key)) => {
+                let Some(r) = upstream else {
+                    bail!("{{{{{var}}}}}: upstream task has no result yet");
+                };
+                if let Some(v) = r.outputs.get(key) {
+                    return Ok(v.clone());
+                }
+                match key {
+                    "run_id" => Ok(r.run_id.to_string()),
+                    "status_code" => Ok(r.status_code.to_string()),
+                    "output_path" => Ok(r.wrote_to.display().to_string()),
+                    _ => bail!(
+                        "{{{{{var}}}}}: upstream run {} did not publish {key:?}",
+                        r.run_id
+                    ),
+                }
+            }
+            _ => bail!("unknown template variable {{{{{var}}}}}"),
+        }
+    };
+
+    let mut out = spec.clone();
+    out.cmd = substitute(&out.cmd, &lookup)?;
+    for arg in out.args.iter_mut() {
+        *arg = substitute(arg, &lookup)?;
+    }
+    for value in out.env.values_mut() {
+        *value = substitute(value, &lookup)?;
+    }
+    out.output_path = substitute_path(&out.output_path, &lookup)?;
+    if let Some(OutputsFrom::File(path)) = &mut out.outputs {
+        *path = substitute_path(path, &lookup)?;
+    }
+    if let Some(sb) = &mut out.sandbox {
+        for p in sb.writable_paths.iter_mut() {
+            let templated = p.to_str().is_some_and(is_templated);
+            *p = substitute_path(p, &lookup)?;
+            // 新增任務時無法檢查含變數的路徑，代入後再檢查：變數可在執行期間修改
+            if templated && (!p.is_absolute() || p.components().any(|c| c == Component::ParentDir))
+            {
+                bail!(
+                    "sandbox writable path {} must be absolute without '..' after rendering",
+                    p.display()
+                );
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// 本機主機名稱；取不到時為 "localhost"
+pub fn system_hostname() -> String {
+    #[cfg(unix)]
+    {
+        let mut buf = [0u8; 256];
+        if unsafe { libc::gethostname(buf.as_mut_ptr().cast(), buf.len()) } == 0 {
+            let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+            if let Ok(name) = std::str::from_utf8(&buf[..len]) {
+                if !name.is_empty() {
+                    return name.to_string();
+                }
+            }
+        }
+    }
+    std::env::var("COMPUTERNAME")
+        .or_else(|_| std::env::var("HOSTNAME"))
+        .unwrap_or_else(|_| "localhost".to_string())
+}
+
+/// 日期運算式：`today`（當天 00:00）或 `now`，之後可接多段 `+<n><單位>` / `-<n><單位>`
+/// （s、m、h、d、w，M 為月），`:` 之後為 strftime 格式，
+/// 預設 today 為 `%Y-%m-%d`、now 為 `%Y-%m-%dT%H:%M:%S`。例如 `{{today-1d:%Y%m%d}}`
+struct DateExpr<'a> {
+    today: bool,
+    offsets: Vec<(i64, char)>,
+    format: &'a str,
+}
+
+/// 不是 today / now 開頭的變數回傳 None
+fn date_expr(var: &str) -> Option<Result<DateExpr<'_>>> {
+    let (expr, format) = match var.split_once(':') {
+        Some((expr, format)) => (expr.trim_end(), Some(format)),
+        None => (var, None),
+    };
+    let (today, rest) = match (expr.strip_prefix("today"), expr.strip_prefix("now")) {
+        (Some(rest), _) => (true, rest),
+        (_, Some(rest)) => (false, rest),
+        _ => return None,
+    };
+    if !rest.is_empty() && !rest.starts_with(['+', '-']) {
+        return None;
+    }
+    let default = if today {
+        "%Y-%m-%d"
+    } else {
+        "%Y-%m-%dT%H:%M:%S"
+    };
+    let format = format.unwrap_or(default);
+    Some(parse_offsets(rest).and_then(|offsets| {
+        if format.is_empty() {
+            bail!("empty date format");
+        }
+        if StrftimeItems::new(format).parse().is_err() {
+            bail!("invalid date format {format:?}");
+        }
+        Ok(DateExpr {
+            today,
+            offsets,
+            format,
+        })
+    }))
+}
+
+fn parse_offsets(mut rest: &str) -> Result<Vec<(i64, char)>> {
+    let mut offsets = Vec::new();
+    while !rest.is_empty() {
+        let Some(body) = rest.strip_prefix(['+', '-']) else {
+            bail!("invalid date offset {rest:?} (expected e.g. -1d)");
+        };
+        let digits = body
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(body.len());
+        let n: i64 = match body[..digits].parse::<i64>() {
+            Ok(n) if rest.starts_with('-') => -n,
+            Ok(n) => n,
+            Err(_) => bail!("invalid date offset {rest:?} (expected e.g. -1d)"),
+        };
+        let unit = body[digits..].chars().next();
+        match unit {
+            Some(unit @ ('s' | 'm' | 'h' | 'd' | 'w' | 'M')) => offsets.push((n, unit)),
+            _ => bail!("date offset {rest:?} needs a unit: s, m, h, d, w or M"),
+        }
+        rest = &body[digits + 1..];
+    }
+    Ok(offsets)
+}
+
+impl DateExpr<'_> {
+    fn eval(&self, now: DateTime<Local>) -> Result<String> {
+        let mut at = now;
+        if self.today {
+            // 當天 00:00 不存在（夏令時間切換）時取當下
+            let midnight = now.date_naive().and_time(NaiveTime::MIN);
+            at = Local
+                .from_local_datetime(&midnight)
+                .earliest()
+                .unwrap_or(now);
+        }
+        for &(n, unit) in &self.offsets {
+            let step = n.unsigned_abs();
+            let shifted = match unit {
+                // 日、週、月依日曆計算，跨夏令時間時保留鐘面時間
+                'd' | 'w' => {
+                    let days = Days::new(if unit == 'w' { step * 7 } else { step });
+                    if n < 0 {
+                        at.checked_sub_days(days)
+                    } else {
+                        at.checked_add_days(days)
+                    }
+                }
+                'M' => {
+                    let months = Months::new(u32::try_from(step).unwrap_or(u32::MAX));
+                    if n < 0 {
+                        at.checked_sub_months(months)
+                    } else {
+                        at.checked_add_months(months)
+                    }
+                }
+                _ => {
+                    let secs = match unit {
+                        'h' => 3600,
+                        'm' => 60,
+                        _ => 1,
+                    };
+                    n.checked_mul(secs)
+                        .and_then(TimeDelta::try_seconds)
+                        .and_then(|delta| at.checked_add_signed(delta))
+                }
+            };
+            at = shifted.context("date out of range")?;
+        }
+        Ok(at.format(self.format).to_string())
+    }
+}
+
+/// 規格中可使用模板的字串（非 UTF-8 的路徑不處理）
+fn texts(spec: &TaskSpec) -> Vec<String> {
+    let mut out = vec![spec.cmd.clone()];
+    out.extend(spec.args.iter().cloned());
+    out.extend(spec.env.values().cloned());
+    let mut paths: Vec<&Path> = vec![&spec.output_path];
+    if let Some(OutputsFrom::File(p)) = &spec.outputs {
+        paths.push(p);
+    }
+    if let Some(sb) = &spec.sandbox {
+        paths.extend(sb.writable_paths.iter().map(PathBuf::as_path));
+    }
+    out.extend(paths.iter().filter_map(|p| p.to_str()).map(str::to_string));
+    out
+}
+
+/// 取出字串中所有 `{{...}}` 的變數名稱
+fn variables(text: &str) -> Result<Vec<&str>> {
+    let mut vars = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start + 2..].find("}}") else {
+            bail!("unclosed '{{{{' in {text:?}");
+        };
+        vars.push(rest[start + 2..start + 2 + len].trim());
+        rest = &rest[start + 2 + len + 2..];
+    }
+    Ok(vars)
+}
+
+fn substitute(text: &str, lookup: &impl Fn(&str) -> Result<String>) -> Result<String> {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start + 2..].find("}}") else {
+            bail!("unclosed '{{{{' in {text:?}");
+        };
+        out.push_str(&rest[..start]);
+        out.push_str(&lookup(rest[start + 2..start + 2 + len].trim())?);
+        rest = &rest[start + 2 + len + 2..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+fn substitute_path(path: &Path, lookup: &impl Fn(&str) -> Result<String>) -> Result<PathBuf> {
+    match path.to_str() {
+        Some(text) if is_templated(text) => Ok(PathBuf::from(substitute(text, lookup)?)),
+        _ => Ok(path.to_path_buf()),
+    }
+}
@@ -0,0 +1,53 @@
+use crate::State;
+use anyhow::{bail, Result};
+use scheduler_core::{EventKind, TaskSpec, Throttle};
+use std::time::{Duration, Instant};
+
+const HOUR: Duration = Duration::from_secs(3600);
+
+/// 外部觸發（事件、trigger hook）與依賴觸發的 run 在排入前檢查；
+/// 定時與手動（RunNow、RetryChain）的 run 不受節流。擋下時發出 RunSkipped
+pub fn check_trigger(state: &State, task_id: u64, spec: &TaskSpec) -> Result<()> {
+    let Some(throttle) = &spec.throttle else {
+        return Ok(());
+    };
+    check_and_record(state, task_id, throttle).inspect_err(|e| {
+        state.events.emit(EventKind::RunSkipped {
+            task_id,
+            reason: format!("{e:#}"),
+        });
+    })
+}
+
+/// 依任務的節流設定決定這次觸發能否執行；可以執行時記下啟動時間
+/// （僅存於記憶體，重啟後重新計算）
+fn check_and_record(state: &State, task_id: u64, throttle: &Throttle) -> Result<()> {
+    let now = Instant::now();
+    let mut starts = state.trigger_starts.entry(task_id).or_default();
+    while starts
+        .front()
+        .is_some_and(|t| now.duration_since(*t) >= HOUR)
+    {
+        starts.pop_front();
+    }
+
+    if let (Some(min), Some(last)) = (throttle.min_interval_secs, starts.back()) {
+        let since = now.duration_since(*last);
+        if since < Duration::from_secs(min) {
+            bail!(
+                "throttled: last run started {}s ago (min interval {min}s)",
+                since.as_secs()
+            );
+        }
+    }
+    if let Some(max) = throttle.max_per_hour {
+        if starts.len() >= max as usize {
+            bail!(
+                "throttled: {} run(s) in the last hour (max {max})",
+                starts.len()
+            );
+        }
+    }
+    starts.push_back(now);
+    Ok(())
+}
@@ -0,0 +1,121 @@
+use crate::{local_now_fixed, State};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration as ChronoDuration, FixedOffset};
+use scheduler_core::{TaskSpec, TrashedTask};
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::time::sleep;
+
+/// 多久清一次過期的項目
+const SWEEP_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// 回收桶：移除的任務保留到期限為止，期間可以還原。
+/// 項目不多，每次變更整份改寫
+pub struct Trash {
+    path: PathBuf,
+    items: Mutex<Vec<TrashedTask>>,
+}
+
+impl Trash {
+    pub fn load(path: &Path) -> Result<Self> {
+        let items = if path.exists() {
+            let text = std::fs::read_to_string(path)
+                .with_context(|| format!("read {}", path.display()))?;
+            serde_json::from_str(&text).with_context(|| format!("parse {}", path.display()))?
+        } else {
+            Vec::new()
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            items: Mutex::new(items),
+        })
+    }
+
+    /// 回收桶中最大的任務 id；還原時沿用原 id，所以不可配發給新任務
+    pub fn max_task_id(&self) -> u64 {
+        let items = self.items.lock().unwrap();
+        items.iter().map(|t| t.id).max().unwrap_or(0)
+    }
+
+    /// 放進回收桶，保留 retention_days 天
+    pub fn put(&self, id: u64, spec: TaskSpec, actor: &str, retention_days: u64) -> Result<()> {
+        let now = local_now_fixed();
+        let days = i64::try_from(retention_days).unwrap_or(i64::MAX);
+        let expires_at = ChronoDuration::try_days(days)
+            .and_then(|d| now.checked_add_signed(d))
+            .unwrap_or(now);
+        let mut items = self.items.lock().unwrap();
+        items.retain(|t| t.id != id);
+        items.push(TrashedTask {
+            id,
+            spec,
+            removed_at: now,
+            removed_by: actor.to_string(),
+            expires_at,
+        });
+        self.save(&items)
+    }
+
+    /// 取出（還原或永久刪除時）；不在回收桶中為 None
+    pub fn take(&self, id: u64) -> Result<Option<TrashedTask>> {
+        let mut items = self.items.lock().unwrap();
+        let Some(pos) = items.iter().position(|t| t.id == id) else {
+            return Ok(None);
+        };
+        let item = items.remove(pos);
+        self.save(&items)?;
+        Ok(Some(item))
+    }
+
+    /// 先移除的在前
+    pub fn list(&self) -> Vec<TrashedTask> {
+        self.items.lock().unwrap().clone()
+    }
+
+    /// 刪除過期的項目；回傳被刪除的任務 id
+    pub fn expire(&self, now: DateTime<FixedOffset>) -> Result<Vec<u64>> {
+        let mut items = self.items.lock().unwrap();
+        let expired: Vec<u64> = items
+            .iter()
+            .filter(|t| t.expires_at <= now)
+            .map(|t| t.id)
+            .collect();
+        if !expired.is_empty() {
+            items.retain(|t| t.expires_at > now);
+            self.save(&items)?;
+        }
+        Ok(expired)
+    }
+
+    /// 先寫暫存檔再改名，避免改寫到一半留下殘缺的檔案
+    fn save(&self, items: &[TrashedTask]) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let tmp = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec_pretty(items)?)
+            .with_context(|| format!("write {}", tmp.display()))?;
+        std::fs::rename(&tmp, &self.path)
+            .with_context(|| format!("replace {}", self.path.display()))
+    }
+}
+
+/// 定期永久刪除過期的項目（啟動時先清一次）
+pub fn spawn(state: Arc<State>) {
+    tokio::spawn(async move {
+        loop {
+            match state.trash.expire(local_now_fixed()) {
+                Ok(ids) => {
+                    for id in ids {
+                        println!("🗑️ task {id} purged from trash (retention expired)");
+                    }
+                }
+                Err(e) => eprintln!("trash expire error: {e:#}"),
+            }
+            sleep(SWEEP_INTERVAL).await;
+        }
+    });
+}
@@ -0,0 +1,129 @@
+use crate::{
+    bridge, builtin, cleanup, config::ServerConfig, defaults, exec, healthcheck, metrics, mqtt,
+    perms, policy, success, template, window,
+};
+use anyhow::{bail, Result};
+use scheduler_core::{IoNice, Schedule, SchedulerError, TaskSpec, Trigger, SYSTEM_NAMESPACE};
+
+/// 與 cpu_set_t 的容量一致
+const MAX_CPUS: usize = 1024;
+
+/// AddTask 時的規格檢查；錯誤訊息直接回給客戶端，排程與權限類的錯誤帶 SchedulerError
+pub fn validate_spec(cfg: &ServerConfig, spec: &TaskSpec) -> Result<()> {
+    if spec.cmd.trim().is_empty() {
+        bail!("cmd must not be empty");
+    }
+    if spec.source.is_some() {
+        bail!("source is set by the server and cannot be given");
+    }
+    if spec.namespace == SYSTEM_NAMESPACE || spec.cmd.starts_with(builtin::CMD_PREFIX) {
+        bail!(SchedulerError::Unauthorized {
+            msg: format!(
+                "namespace {SYSTEM_NAMESPACE:?} and {:?} commands are reserved",
+                builtin::CMD_PREFIX
+            ),
+        });
+    }
+    // 含模板的命令或 PATH 要代入後才知道，留到執行前檢查
+    let effective = defaults::apply(&cfg.defaults, spec);
+    let search = exec::child_path(&effective);
+    let templated_path = effective
+        .env
+        .get("PATH")
+        .is_some_and(|p| template::is_templated(p));
+    if !template::is_templated(&spec.cmd) && !templated_path {
+        policy::check_command(&cfg.policy, &spec.cmd, search.as_deref()).map_err(|e| {
+            SchedulerError::Unauthorized {
+                msg: format!("{e:#}"),
+            }
+        })?;
+    }
+    template::check(spec)?;
+
+    let sched = &spec.sched;
+    if let Some(nice) = sched.nice {
+        if !(-20..=19).contains(&nice) {
+            bail!("nice must be within -20..=19, got {nice}");
+        }
+    }
+    if let Some(IoNice::RealTime { level } | IoNice::BestEffort { level }) = sched.ionice {
+        if level > 7 {
+            bail!("ionice level must be within 0..=7, got {level}");
+        }
+    }
+    if let Some(cpu) = sched.cpu_affinity.iter().find(|&&c| c >= MAX_CPUS) {
+        bail!("cpu {cpu} is out of range for cpu_affinity");
+    }
+
+    if spec
+        .throttle
+        .as_ref()
+        .is_some_and(|t| t.max_per_hour == Some(0))
+    {
+        bail!("throttle max_per_hour must be at least 1");
+    }
+    if spec.breaker.as_ref().is_some_and(|b| b.failures == 0) {
+        bail!("breaker failures must be at least 1");
+    }
+    if spec.locks.iter().any(|l| l.trim().is_empty()) {
+        bail!("lock names must not be empty");
+    }
+    check_schedule(spec).map_err(|msg| SchedulerError::InvalidSchedule { msg })?;
+    if spec
+        .triggers
+        .iter()
+        .any(|t| matches!(t, Trigger::Unknown(_)))
+    {
+        bail!("unrecognized trigger");
+    }
+    mqtt::check_triggers(cfg.mqtt.as_ref(), &spec.triggers)?;
+    bridge::check_triggers(cfg.bridge.as_ref(), &spec.triggers)?;
+    if let Some(hc) = &spec.healthcheck {
+        healthcheck::check(hc)?;
+    }
+    if let Some(w) = &spec.allowed_window {
+        window::check(w)?;
+    }
+    if let Some(c) = &spec.success {
+        success::check(c, spec.output_encoding)?;
+    }
+    metrics::check_labels(spec)?;
+    cleanup::check(&cfg.cleanup, spec)?;
+    if let Some(p) = &spec.output_perms {
+        perms::check(p)?;
+    }
+
+    if let Some(sb) = &spec.sandbox {
+        if !cfg!(target_os = "linux") {
+            bail!("sandbox is only supported on Linux");
+        }
+        if let Some(p) = sb
+            .writable_paths
+            .iter()
+            .find(|p| !p.is_absolute() && !p.to_str().is_some_and(template::is_templated))
+        {
+            bail!("sandbox writable path must be absolute: {}", p.display());
+        }
+    }
+    Ok(())
+}
+
+fn check_schedule(spec: &TaskSpec) -> Result<(), String> {
+    match spec.schedule {
+        Schedule::Daily { hour, minute } if hour > 23 || minute > 59 => {
+            Err(format!("daily time out of range: {hour:02}:{minute:02}"))
+        }
+        Schedule::Hourly { minute } if minute > 59 => {
+            Err(format!("hourly minute must be within 0..=59, got {minute}"))
+        }
+        Schedule::After {
+            expires_after_secs: Some(0),
+            ..
+        } => Err("expires_after_secs must be at least 1".to_string()),
+        Schedule::Manual if spec.triggers.is_empty() => {
+            Err("manual schedule requires at least one trigger".to_string())
+        }
+        Schedule::Unknown(_) => Err("unrecognized schedule".to_string()),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,109 @@
+use crate::{artifacts, defaults, State};
+use scheduler_core::{CheckStatus, OutputCheck, RunRecord, SYSTEM_NAMESPACE};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    io::{Read, Seek, SeekFrom},
+    path::Path,
+};
+
+pub fn sha256_hex(data: &[u8]) -> String {
+    hex::encode(Sha256::digest(data))
+}
+
+/// 以歷史中的 checksum 驗證：每筆紀錄的 artifact，以及每個任務輸出檔中最近一次寫入的部分
+pub fn run(state: &State, task_id: Option<u64>) -> Vec<OutputCheck> {
+    let records: Vec<RunRecord> = state
+        .history
+        .snapshot(task_id)
+        .into_iter()
+        .filter(|r| r.result.output_sha256.is_some())
+        .collect();
+
+    let mut checks = Vec::new();
+    for rec in &records {
+        let Some(path) = &rec.result.artifact else {
+            continue;
+        };
+        let status = match artifacts::read_artifact(path) {
+            Ok(content) => compare(rec, &content),
+            Err(e) => CheckStatus::Missing(format!("{e:#}")),
+        };
+        checks.push(OutputCheck {
+            task_id: rec.task_id,
+            run_id: rec.result.run_id,
+            path: path.clone(),
+            status,
+        });
+    }
+
+    // 輸出檔會被下一次執行覆寫或附加，只能驗證最近一次寫入的內容
+    let mut latest: HashMap<u64, &RunRecord> = HashMap::new();
+    for rec in &records {
+        latest.insert(rec.task_id, rec);
+    }
+    let mut latest: Vec<_> = latest.into_values().collect();
+    latest.sort_by_key(|r| r.task_id);
+    for rec in latest {
+        let Some(append) = state
+            .tasks
+            .get(&rec.task_id)
+            .filter(|e| e.spec.namespace != SYSTEM_NAMESPACE)
+            .filter(|e| {
+                defaults::output_path(&state.config.defaults, &e.spec.output_path)
+                    == rec.result.wrote_to
+            })
+            .map(|e| e.spec.append)
+        else {
+            continue;
+        };
+        let path = &rec.result.wrote_to;
+        checks.push(OutputCheck {
+            task_id: rec.task_id,
+            run_id: rec.result.run_id,
+            path: path.clone(),
+            status: check_output_file(rec, path, append),
+        });
+    }
+    checks
+}
+
+/// append 的輸出檔只比對結尾的 output_size 個位元組
+fn check_output_file(rec: &RunRecord, path: &Path, append: bool) -> CheckStatus {
+    let expected = rec.result.output_size.unwrap_or(0);
+    let read = || -> std::io::Result<(u64, Vec<u8>)> {
+        let mut f = std::fs::File::open(path)?;
+        let len = f.metadata()?.len();
+        if append && len > expected {
+            f.seek(SeekFrom::Start(len - expected))?;
+        }
+        let mut buf = Vec::new();
+        f.read_to_end(&mut buf)?;
+        Ok((len, buf))
+    };
+    match read() {
+        Ok((len, _)) if (append && len < expected) || (!append && len != expected) => {
+            CheckStatus::SizeMismatch {
+                expected,
+                actual: len,
+            }
+        }
+        Ok((_, content)) => compare(rec, &content),
+        Err(e) => CheckStatus::Missing(e.to_string()),
+    }
+}
+
+fn compare(rec: &RunRecord, content: &[u8]) -> CheckStatus {
+    let expected = rec.result.output_size.unwrap_or(0);
+    if content.len() as u64 != expected {
+        return CheckStatus::SizeMismatch {
+            expected,
+            actual: content.len() as u64,
+        };
+    }
+    if rec.result.output_sha256.as_deref() == Some(sha256_hex(content).as_str()) {
+        CheckStatus::Ok
+    } else {
+        CheckStatus::HashMismatch
+    }
+}
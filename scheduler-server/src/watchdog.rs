@@ -0,0 +1,82 @@
+use crate::{exec, local_now_fixed, State};
+use scheduler_core::{EventKind, RunOutcome};
+use std::{sync::Arc, time::Duration};
+use tokio::time::sleep;
+
+/// 全域 watchdog：與任務自身的 timeout_secs 無關，定期巡檢所有執行中的 run
+/// - 超過伺服器上限 max_runtime_secs → 終止並標記 TimedOut
+/// - 子程序已消失（含已結束卻沒被回收的 zombie） → 標記 Lost
+/// - 已要求終止，停止訊號的寬限期加上讀完輸出的上限都過了仍未收尾 → 直接清除登記，
+///   避免名額永遠被佔住；子程序已結束、還在讀完輸出的 run 不動（讀取的等待有上限）
+pub fn spawn(state: Arc<State>) {
+    let cfg = state.config.watchdog.clone();
+    if cfg.max_runtime_secs.is_none() && !cfg.detect_lost {
+        return;
+    }
+    let interval = Duration::from_secs(cfg.interval_secs.max(1));
+
+    tokio::spawn(async move {
+        loop {
+            sleep(interval).await;
+            let now = local_now_fixed();
+            let mut stale = Vec::new();
+
+            for kv in state.running.iter() {
+                let run = kv.value();
+
+                if let Some(reason) = run.kill_requested() {
+                    // 子程序已結束、還在讀完輸出：等待有上限，由 run 自己收尾；
+                    // 還在寬限期內的子程序仍在執行，照樣佔著名額
+                    if run.exited() || !run.kill_overdue() {
+                        continue;
+                    }
+                    eprintln!(
+                        "watchdog: run of task {} still registered after {:?}, dropping bookkeeping",
+                        run.task_id, reason
+                    );
+                    stale.push(*kv.key());
+                    continue;
+                }
+
+                if let Some(max) = cfg.max_runtime_secs {
+                    let elapsed = (now - run.started_at).num_seconds().max(0) as u64;
+                    if elapsed > max {
+                        eprintln!(
+                            "watchdog: task {} running {}s exceeds server ceiling {}s, killing",
+                            run.task_id, elapsed, max
+                        );
+                        run.kill(RunOutcome::TimedOut);
+                        state.events.emit(EventKind::RunKilled {
+                            task_id: run.task_id,
+                            run_id: *kv.key(),
+                            reason: RunOutcome::TimedOut,
+                        });
+                        continue;
+                    }
+                }
+
+                // 子程序已結束的 run，pid 可能已被別的程序重用
+                if cfg.detect_lost && !run.exited() {
+                    if let Some(pid) = run.pid() {
+                        if !exec::process_alive(pid) {
+                            eprintln!(
+                                "watchdog: task {} child pid {} disappeared, marking lost",
+                                run.task_id, pid
+                            );
+                            run.kill(RunOutcome::Lost);
+                            state.events.emit(EventKind::RunKilled {
+                                task_id: run.task_id,
+                                run_id: *kv.key(),
+                                reason: RunOutcome::Lost,
+                            });
+                        }
+                    }
+                }
+            }
+
+            for key in stale {
+                state.running.remove(&key);
+            }
+        }
+    });
+}
@@ -0,0 +1,51 @@
+use crate::duration_to;
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, FixedOffset, Local, Timelike};
+use scheduler_core::{Schedule, TimeWindow};
+use std::time::Duration;
+
+/// 解析 HH:MM
+pub fn parse_hhmm(s: &str) -> Result<(u32, u32)> {
+    let (h, m) = s
+        .split_once(':')
+        .with_context(|| format!("invalid time {s:?} (expected HH:MM)"))?;
+    let hour: u32 = h
+        .parse()
+        .with_context(|| format!("invalid hour in {s:?}"))?;
+    let minute: u32 = m
+        .parse()
+        .with_context(|| format!("invalid minute in {s:?}"))?;
+    if hour > 23 || minute > 59 {
+        bail!("time out of range: {s:?}");
+    }
+    Ok((hour, minute))
+}
+
+/// AddTask 時檢查時段設定
+pub fn check(w: &TimeWindow) -> Result<()> {
+    let start = parse_hhmm(&w.start).context("allowed_window.start")?;
+    let end = parse_hhmm(&w.end).context("allowed_window.end")?;
+    if start == end {
+        bail!("allowed_window start and end must differ");
+    }
+    Ok(())
+}
+
+/// 現在不在時段內時，回傳距離下一次時段開始的時間
+pub fn wait_for(w: &TimeWindow, now: DateTime<FixedOffset>) -> Result<Option<Duration>> {
+    let (sh, sm) = parse_hhmm(&w.start)?;
+    let (eh, em) = parse_hhmm(&w.end)?;
+    let (start, end) = (sh * 60 + sm, eh * 60 + em);
+    let t = now.hour() * 60 + now.minute();
+    let inside = if start < end {
+        start <= t && t < end
+    } else {
+        t >= start || t < end // 跨午夜
+    };
+    let start_at = Schedule::Daily {
+        hour: sh,
+        minute: sm,
+    }
+    .next_occurrence(now, &Local);
+    Ok((!inside).then(|| start_at.map(duration_to).unwrap_or_default()))
+}
@@ -0,0 +1,103 @@
+use crate::{access::Peer, config::WebSocketConfig, serve, State};
+use anyhow::Result;
+use bytes::{Bytes, BytesMut};
+use futures_util::{future, SinkExt, StreamExt};
+use std::{net::SocketAddr, sync::Arc};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_tungstenite::tungstenite::{
+    handshake::server::{ErrorResponse, Request, Response},
+    http::{header::ORIGIN, StatusCode},
+    protocol::WebSocketConfig as WsLimits,
+    Error as WsError, Message,
+};
+
+/// 啟動 WebSocket 端點並回傳實際位址；未設定 [websocket] 則不做事
+pub async fn spawn(state: Arc<State>) -> Result<Option<SocketAddr>> {
+    let Some(cfg) = state.config.websocket.clone() else {
+        return Ok(None);
+    };
+    let listener = TcpListener::bind(&cfg.bind).await?;
+    let addr = listener.local_addr()?;
+    println!("🌐 websocket listening on {addr}");
+    if cfg.allowed_origins.is_empty() {
+        println!("🌐 websocket allowed_origins is empty: browsers will be refused");
+    }
+
+    tokio::spawn(async move {
+        loop {
+            let (stream, peer) = match listener.accept().await {
+                Ok(acc) => acc,
+                Err(e) => {
+                    eprintln!("websocket accept error: {e}");
+                    continue;
+                }
+            };
+            let (st, cfg) = (state.clone(), cfg.clone());
+            tokio::spawn(async move {
+                if let Err(e) = handle(st, &cfg, stream, peer).await {
+                    eprintln!("websocket {peer} error: {e:#}");
+                }
+            });
+        }
+    });
+    Ok(Some(addr))
+}
+
+async fn handle(
+    state: Arc<State>,
+    cfg: &WebSocketConfig,
+    stream: TcpStream,
+    peer: SocketAddr,
+) -> Result<()> {
+    let limits = WsLimits::default()
+        .max_message_size(Some(state.config.max_frame_bytes))
+        .max_frame_size(Some(state.config.max_frame_bytes));
+    // 瀏覽器的 Origin 即存取紀錄中的身分
+    let mut identity = None;
+    #[allow(clippy::result_large_err)] // 回呼的型別由 tungstenite 決定
+    let check_origin = |req: &Request, resp: Response| match req.headers().get(ORIGIN) {
+        Some(origin) if !origin_allowed(cfg, origin.to_str().unwrap_or_default()) => {
+            let mut err = ErrorResponse::new(Some("origin not allowed".to_string()));
+            *err.status_mut() = StatusCode::FORBIDDEN;
+            Err(err)
+        }
+        origin => {
+            identity = origin
+                .and_then(|o| o.to_str().ok())
+                .map(|o| format!("origin:{o}"));
+            Ok(resp)
+        }
+    };
+    let ws =
+        tokio_tungstenite::accept_hdr_async_with_config(stream, check_origin, Some(limits)).await?;
+    let (sink, stream) = ws.split();
+
+    // 只取資料訊息；Ping/Pong 由 tungstenite 自行回應，收到 Close 即結束
+    let incoming = stream
+        .take_while(|msg| future::ready(!matches!(msg, Ok(Message::Close(_)))))
+        .filter_map(|msg| {
+            future::ready(match msg {
+                Ok(Message::Text(text)) => Some(Ok(BytesMut::from(text.as_bytes()))),
+                Ok(Message::Binary(bin)) => Some(Ok(BytesMut::from(&bin[..]))),
+                Ok(_) => None,
+                Err(e) => Some(Err(e)),
+            })
+        });
+    // 回覆都是 JSON，以文字訊息送出，瀏覽器端可直接 JSON.parse
+    let sink = sink.with(|frame: Bytes| {
+        let text = String::from_utf8_lossy(&frame).into_owned();
+        future::ready(Ok::<_, WsError>(Message::text(text)))
+    });
+    let peer = Peer {
+        transport: "ws",
+        addr: peer.to_string(),
+        identity,
+    };
+    serve(state, peer, Box::pin(incoming), Box::pin(sink)).await
+}
+
+fn origin_allowed(cfg: &WebSocketConfig, origin: &str) -> bool {
+    cfg.allowed_origins
+        .iter()
+        .any(|o| o == "*" || o.trim_end_matches('/') == origin)
+}
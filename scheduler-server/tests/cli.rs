@@ -0,0 +1,366 @@
+//! scheduler-cli 的端對端測試：對測試伺服器執行 CLI 執行檔，檢查輸出與結束碼
+
+mod support;
+
+use scheduler_core::{ClientRequest, Schedule, ServerResponse, TaskInfo};
+use std::{
+    path::{Path, PathBuf},
+    process::{Output, Stdio},
+    sync::OnceLock,
+};
+use support::{now, once_in, spec, TestServer, WAIT};
+use tokio::time::timeout;
+
+/// scheduler-cli 執行檔；不在這個套件裡，先建置一次確保不是舊版
+fn cli_bin() -> &'static Path {
+    static BIN: OnceLock<PathBuf> = OnceLock::new();
+    BIN.get_or_init(|| {
+        let status = std::process::Command::new(env!("CARGO"))
+            .args(["build", "-p", "scheduler-cli", "--bin", "scheduler-cli"])
+            .current_dir(env!("CARGO_MANIFEST_DIR"))
+            .status()
+            .expect("run cargo build");
+        assert!(status.success(), "building scheduler-cli failed");
+        Path::new(env!("CARGO_BIN_EXE_scheduler-server")).with_file_name("scheduler-cli")
+    })
+}
+
+/// 在 dir 執行 CLI；設定檔與暫存目錄都指到 dir，不受本機 profile 與環境變數影響
+async fn cli(dir: &Path, args: &[&str]) -> Output {
+    let run = tokio::process::Command::new(cli_bin())
+        .args(args)
+        .current_dir(dir)
+        .env("XDG_CONFIG_HOME", dir.join("config"))
+        .env("XDG_STATE_HOME", dir.join("state"))
+        .env_remove("SCHEDULER_PROFILE")
+        .env_remove("SCHEDULER_ENV")
+        .env_remove("SCHEDULER_ENV_TOKEN")
+        .env_remove("SCHEDULER_TZ")
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .output();
+    timeout(WAIT, run)
+        .await
+        .expect("scheduler-cli did not finish in time")
+        .expect("run scheduler-cli")
+}
+
+/// 對測試伺服器執行 CLI
+async fn cli_on(server: &TestServer, args: &[&str]) -> Output {
+    let connect = server.addr.to_string();
+    let args: Vec<&str> = ["--connect", connect.as_str()]
+        .into_iter()
+        .chain(args.iter().copied())
+        .collect();
+    cli(&server.dir, &args).await
+}
+
+fn stderr(out: &Output) -> String {
+    String::from_utf8_lossy(&out.stderr).into_owned()
+}
+
+/// 不需要伺服器的子命令用的暫存目錄
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("scheduler-it-cli-{}-{name}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn stdout(out: &Output) -> String {
+    String::from_utf8_lossy(&out.stdout).into_owned()
+}
+
+async fn tasks(server: &TestServer) -> Vec<TaskInfo> {
+    match server
+        .client()
+        .await
+        .request(ClientRequest::ListTasks)
+        .await
+    {
+        ServerResponse::Tasks(list) => list,
+        other => panic!("unexpected {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn add_from_a_spec_file_submits_every_field() {
+    let server = TestServer::start().await;
+    std::fs::write(
+        server.path("spec.yaml"),
+        "name: nightly\n\
+         cmd: echo\n\
+         args: [hi]\n\
+         output_path: nightly.log\n\
+         append: true\n\
+         schedule: {Daily: {hour: 3, minute: 0}}\n\
+         tags: [etl]\n\
+         locks: [db]\n\
+         timeout_secs: 30\n\
+         env:\n  MODE: full\n",
+    )
+    .unwrap();
+    let out = cli_on(&server, &["add", "-f", "spec.yaml"]).await;
+    assert!(out.status.success(), "{}", stderr(&out));
+
+    let list = tasks(&server).await;
+    assert_eq!(list.len(), 1);
+    let added = &list[0].spec;
+    assert_eq!(added.name.as_deref(), Some("nightly"));
+    assert_eq!(added.tags, ["etl"]);
+    assert_eq!(added.locks, ["db"]);
+    assert_eq!(added.timeout_secs, Some(30));
+    assert_eq!(added.env["MODE"], "full");
+
+    // 拼錯的欄位不會被默默略過，也不會送出
+    std::fs::write(
+        server.path("typo.json"),
+        r#"{"cmd": "true", "args": [], "output_path": "t.log", "append": false,
+            "schedule": {"Daily": {"hour": 3, "minute": 0}}, "timeout": 30}"#,
+    )
+    .unwrap();
+    let out = cli_on(&server, &["add", "-f", "typo.json"]).await;
+    assert_eq!(out.status.code(), Some(64));
+    assert!(stderr(&out).contains("timeout"), "{}", stderr(&out));
+    assert_eq!(tasks(&server).await.len(), 1);
+}
+
+#[tokio::test]
+async fn lint_reports_manifest_problems_for_ci() {
+    let dir = scratch("lint");
+    let task = |key: &str, output: &str, append: bool, schedule: &str, extra: &str| {
+        format!(
+            "  - key: {key}\n    cmd: \"true\"\n    args: []\n    output_path: {output}\n    \
+             append: {append}\n    schedule: {schedule}\n{extra}"
+        )
+    };
+    let daily = "{Daily: {hour: 2, minute: 0}}";
+    let warnings = [
+        task(
+            "load",
+            "load.log",
+            false,
+            daily,
+            "    id: 1\n    locks: [db]\n",
+        ),
+        task("report", "report.log", false, daily, "    locks: [db]\n"),
+        task(
+            "cleanup",
+            "cleanup.log",
+            false,
+            "{After: {task_id: 1, delay_secs: 172800}}",
+            "",
+        ),
+    ]
+    .concat();
+    std::fs::write(dir.join("jobs.yaml"), format!("tasks:\n{warnings}")).unwrap();
+
+    // 只有警告：預設通過，--deny-warnings 時失敗
+    let out = cli(&dir, &["lint", "-f", "jobs.yaml"]).await;
+    assert!(out.status.success(), "{}", stderr(&out));
+    assert!(stdout(&out).contains("lock-overlap"), "{}", stdout(&out));
+    let out = cli(
+        &dir,
+        &["--json", "lint", "-f", "jobs.yaml", "--deny-warnings"],
+    )
+    .await;
+    assert_eq!(out.status.code(), Some(8));
+    let report: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    assert_eq!(report["tasks"], 3);
+    assert_eq!(report["errors"], 0);
+    let codes: Vec<&str> = report["diagnostics"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|d| d["code"].as_str().unwrap())
+        .collect();
+    assert_eq!(codes, ["lock-overlap", "long-delay"]);
+
+    // 兩個任務覆寫同一個輸出檔是錯誤
+    let collision = task("audit", "./load.log", false, daily, "");
+    std::fs::write(
+        dir.join("jobs.yaml"),
+        format!("tasks:\n{warnings}{collision}"),
+    )
+    .unwrap();
+    let out = cli(&dir, &["--json", "lint", "-f", "jobs.yaml"]).await;
+    assert_eq!(out.status.code(), Some(8));
+    let report: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    assert_eq!(report["errors"], 1);
+    assert_eq!(report["diagnostics"][0]["code"], "output-collision");
+    assert_eq!(
+        report["diagnostics"][0]["tasks"],
+        serde_json::json!(["load", "audit"])
+    );
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn diff_lists_what_applying_a_manifest_would_change() {
+    let server = TestServer::start().await;
+    let nightly =
+        "  - key: nightly\n    cmd: \"true\"\n    args: []\n    output_path: nightly.log\n    \
+                   append: true\n    schedule: {Daily: {hour: 3, minute: 0}}\n";
+    std::fs::write(server.path("jobs.yaml"), format!("tasks:\n{nightly}")).unwrap();
+    let out = cli_on(&server, &["diff", "-f", "jobs.yaml"]).await;
+    assert!(out.status.success(), "{}", stderr(&out));
+    let text = stdout(&out);
+    assert!(text.contains("+ nightly"), "{text}");
+    assert!(text.contains("schedule.Daily.hour: 3"), "{text}");
+    assert!(text.contains("新增 1、更新 0、移除 0"), "{text}");
+    // 只是試算，伺服器上沒有多出任務
+    assert!(tasks(&server).await.is_empty());
+
+    let out = cli_on(&server, &["diff", "-f", "jobs.yaml", "--exit-code"]).await;
+    assert_eq!(out.status.code(), Some(9));
+    std::fs::write(server.path("empty.yaml"), "tasks: []\n").unwrap();
+    let out = cli_on(&server, &["diff", "-f", "empty.yaml", "--exit-code"]).await;
+    assert!(out.status.success(), "{}", stderr(&out));
+
+    // 同一個 key 出現兩次，伺服器無法套用
+    std::fs::write(
+        server.path("dup.yaml"),
+        format!("tasks:\n{nightly}{nightly}"),
+    )
+    .unwrap();
+    let out = cli_on(&server, &["diff", "-f", "dup.yaml"]).await;
+    assert_eq!(out.status.code(), Some(64));
+    assert!(stderr(&out).contains("清單無法套用"), "{}", stderr(&out));
+}
+
+#[tokio::test]
+async fn tz_converts_times_on_the_client() {
+    let server = TestServer::start().await;
+    let at = chrono::DateTime::parse_from_rfc3339("2030-01-01T00:00:00+00:00").unwrap();
+    server
+        .add(spec("true", &[], server.path("a.log"), Schedule::Once(at)))
+        .await;
+
+    for (tz, shown) in [("utc", "00:00"), ("Asia/Taipei", "08:00")] {
+        let out = cli_on(&server, &["--tz", tz, "list"]).await;
+        assert!(out.status.success(), "{}", stderr(&out));
+        let text = stdout(&out);
+        assert!(text.contains(&format!("once 2030-01-01 {shown}")), "{text}");
+    }
+
+    // 未指定 --tz 時用 profile 的時區
+    let profile = server.path("config/scheduler/config.toml");
+    std::fs::create_dir_all(profile.parent().unwrap()).unwrap();
+    std::fs::write(
+        &profile,
+        "default_profile = \"ops\"\n[profiles.ops]\ntz = \"Asia/Tokyo\"\n",
+    )
+    .unwrap();
+    let out = cli_on(&server, &["list"]).await;
+    assert!(
+        stdout(&out).contains("once 2030-01-01 09:00"),
+        "{}",
+        stdout(&out)
+    );
+
+    let out = cli_on(&server, &["--tz", "Mars/Olympus", "list"]).await;
+    assert_eq!(out.status.code(), Some(64));
+    assert!(stderr(&out).contains("未知的時區"), "{}", stderr(&out));
+    std::fs::write(
+        &profile,
+        "default_profile = \"ops\"\n[profiles.ops]\ntz = \"Mars/Olympus\"\n",
+    )
+    .unwrap();
+    let out = cli_on(&server, &["list"]).await;
+    assert_eq!(out.status.code(), Some(64));
+    assert!(stderr(&out).contains("profile 的 tz"), "{}", stderr(&out));
+}
+
+#[tokio::test]
+async fn list_and_history_show_relative_times_unless_raw() {
+    let server = TestServer::start().await;
+    let mut events = server.subscribe().await;
+    let later = now() + chrono::Duration::seconds(2 * 3600 + 13 * 60 + 30);
+    server
+        .add(spec(
+            "true",
+            &[],
+            server.path("a.log"),
+            Schedule::Once(later),
+        ))
+        .await;
+    let done = server
+        .add(spec("sleep", &["0.2"], server.path("b.log"), once_in(100)))
+        .await;
+    events.run_finished(done).await;
+
+    let out = cli_on(&server, &["list"]).await;
+    assert!(out.status.success(), "{}", stderr(&out));
+    assert!(stdout(&out).contains("in 2h 13m"), "{}", stdout(&out));
+
+    // 結束超過幾秒後才不再顯示 now
+    tokio::time::sleep(std::time::Duration::from_secs(6)).await;
+    let id = done.to_string();
+    let out = cli_on(&server, &["history", "--id", &id]).await;
+    let text = stdout(&out);
+    let line = text
+        .lines()
+        .find(|l| l.starts_with("- run="))
+        .unwrap_or_else(|| panic!("{text}"));
+    assert!(line.contains(" seconds ago"), "{line}");
+    let took = line
+        .split("took=")
+        .nth(1)
+        .unwrap()
+        .split_whitespace()
+        .next()
+        .unwrap();
+    assert!(
+        took.ends_with('s') && took.trim_end_matches('s').parse::<f64>().unwrap() >= 0.2,
+        "{line}"
+    );
+
+    // --raw-times 印出換算時區後的完整時間
+    let out = cli_on(
+        &server,
+        &["--raw-times", "--tz", "utc", "history", "--id", &id],
+    )
+    .await;
+    let text = stdout(&out);
+    assert!(!text.contains(" ago"), "{text}");
+    assert!(text.contains(" +00:00"), "{text}");
+}
+
+#[tokio::test]
+async fn discover_finds_servers_advertised_over_mdns() {
+    let instance = format!("it-{}", std::process::id());
+    let server = TestServer::with_binds(
+        &format!("[mdns]\ninstance = {instance:?}\n"),
+        &["0.0.0.0:0"],
+    )
+    .await;
+    // 只綁 loopback 的伺服器區網上連不到，不宣告
+    let hidden = format!("{instance}-lo");
+    let _loopback = TestServer::with_config(&format!("[mdns]\ninstance = {hidden:?}\n")).await;
+
+    let dir = scratch("discover");
+    let out = cli(&dir, &["--json", "discover", "--timeout", "3"]).await;
+    assert!(out.status.success(), "{}", stderr(&out));
+    let found: Vec<serde_json::Value> = serde_json::from_slice(&out.stdout).unwrap();
+    let ours = found
+        .iter()
+        .find(|f| f["instance"] == instance.as_str())
+        .unwrap_or_else(|| panic!("{found:?}"));
+    assert!(
+        ours["addr"]
+            .as_str()
+            .unwrap()
+            .ends_with(&format!(":{}", server.addr.port())),
+        "{ours}"
+    );
+    assert!(
+        !found.iter().any(|f| f["instance"] == hidden.as_str()),
+        "{found:?}"
+    );
+
+    // --discover 找到唯一一台時直接連線
+    let out = cli(&dir, &["--discover", "info"]).await;
+    assert!(out.status.success(), "{}", stderr(&out));
+    assert!(stderr(&out).contains(&instance), "{}", stderr(&out));
+    let _ = std::fs::remove_dir_all(&dir);
+}
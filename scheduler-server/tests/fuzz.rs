@@ -0,0 +1,152 @@
+//! 以固定種子產生的亂數輸入測試請求解碼：錯誤的 frame 只得到 Error 回應，連線與伺服器不受影響
+
+mod support;
+
+use scheduler_core::{ClientRequest, Schedule, SchedulerError, ServerResponse};
+use support::{spec, Client, TestServer};
+
+/// xorshift64*：不需額外依賴、結果可重現
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn bytes(&mut self, len: usize) -> Vec<u8> {
+        (0..len).map(|_| self.next() as u8).collect()
+    }
+}
+
+/// 連線仍能正常處理請求
+async fn assert_alive(client: &mut Client) {
+    let req = ClientRequest::ListTasks;
+    assert!(matches!(
+        client.request(req).await,
+        ServerResponse::Tasks(_)
+    ));
+}
+
+#[tokio::test]
+async fn random_frames_get_error_responses() {
+    let server = TestServer::start().await;
+    let mut client = server.client().await;
+    let mut rng = Rng(0x5eed_0001);
+
+    for _ in 0..500 {
+        let len = rng.below(2048);
+        let frame = rng.bytes(len);
+        client.send_raw(&frame).await;
+        match client.recv().await {
+            Some(ServerResponse::Error(_)) => {}
+            other => panic!("unexpected response to garbage: {other:?}"),
+        }
+    }
+    assert_alive(&mut client).await;
+}
+
+#[tokio::test]
+async fn mutated_requests_never_drop_the_connection() {
+    let server = TestServer::start().await;
+    let mut client = server.client().await;
+    let mut rng = Rng(0x5eed_0002);
+
+    let daily = Schedule::Daily { hour: 3, minute: 0 };
+    let seeds: Vec<Vec<u8>> = vec![
+        serde_json::to_vec(&ClientRequest::AddTask(spec(
+            "true",
+            &["a", "b"],
+            server.path("x.log"),
+            daily,
+        )))
+        .unwrap(),
+        serde_json::to_vec(&ClientRequest::GetHistory { id: 1, limit: 5 }).unwrap(),
+        serde_json::to_vec(&ClientRequest::Search {
+            pattern: "ab".to_string(),
+            regex: true,
+        })
+        .unwrap(),
+    ];
+
+    for _ in 0..1000 {
+        let mut frame = seeds[rng.below(seeds.len())].clone();
+        for _ in 0..=rng.below(4) {
+            let pos = rng.below(frame.len());
+            match rng.below(3) {
+                0 => frame[pos] = rng.next() as u8,
+                1 => {
+                    frame.remove(pos);
+                }
+                _ => frame.insert(pos, rng.next() as u8),
+            }
+        }
+        client.send_raw(&frame).await;
+        // 變造後仍合法的請求照常處理；其餘回 Error
+        assert!(client.recv().await.is_some(), "connection closed");
+    }
+    assert_alive(&mut client).await;
+}
+
+#[tokio::test]
+async fn deeply_nested_json_is_rejected() {
+    let server = TestServer::start().await;
+    let mut client = server.client().await;
+
+    let depth = 100_000;
+    let mut frame = "[".repeat(depth);
+    frame.push_str(&"]".repeat(depth));
+    client.send_raw(frame.as_bytes()).await;
+    assert!(matches!(
+        client.recv().await,
+        Some(ServerResponse::Error(_))
+    ));
+    assert_alive(&mut client).await;
+}
+
+#[tokio::test]
+async fn out_of_range_fields_are_rejected() {
+    let server = TestServer::start().await;
+    let mut client = server.client().await;
+
+    let req = ClientRequest::GetHistory {
+        id: 1,
+        limit: usize::MAX,
+    };
+    assert!(matches!(
+        client.request(req).await,
+        ServerResponse::Error(_)
+    ));
+    let req = ClientRequest::Search {
+        pattern: "a".repeat(4096),
+        regex: false,
+    };
+    assert!(matches!(
+        client.request(req).await,
+        ServerResponse::Error(_)
+    ));
+    assert_alive(&mut client).await;
+}
+
+#[tokio::test]
+async fn oversized_frame_is_refused() {
+    let server = TestServer::with_config("max_frame_bytes = 1024\n").await;
+    let mut client = server.client().await;
+
+    client.send_raw(&vec![b' '; 4096]).await;
+    match client.recv().await {
+        Some(ServerResponse::Error(SchedulerError::InvalidRequest { msg })) => {
+            assert!(msg.contains("frame"))
+        }
+        other => panic!("unexpected {other:?}"),
+    }
+    // 無法重新對齊 frame，伺服器關閉這條連線，但仍接受新連線
+    assert!(client.recv().await.is_none());
+    assert_alive(&mut server.client().await).await;
+}
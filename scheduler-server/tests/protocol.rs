@@ -0,0 +1,1756 @@
+mod support;
+
+use futures_util::{SinkExt, StreamExt};
+use scheduler_core::{
+    ClientRequest, DependencyIssueKind, EventKind, Healthcheck, RequestFrame, ResponseFrame,
+    SandboxProfile, Schedule, SchedulerError, ServerResponse, TaskSort, Throttle, Trigger,
+    DEFAULT_ENVIRONMENT,
+};
+use std::collections::BTreeMap;
+use support::{once_in, spec, TestServer, WAIT};
+use tokio::time::timeout;
+use tokio_tungstenite::tungstenite::{client::IntoClientRequest, Message};
+
+#[tokio::test]
+async fn add_list_remove() {
+    let server = TestServer::start().await;
+    let mut events = server.subscribe().await;
+    let daily = Schedule::Daily { hour: 3, minute: 0 };
+
+    let mut s = spec("true", &[], server.path("a.log"), daily);
+    s.name = Some("nightly".to_string());
+    let id = server.add(s).await;
+    events
+        .wait_for(|k| matches!(k, EventKind::TaskAdded { task_id } if *task_id == id))
+        .await;
+
+    let mut client = server.client().await;
+    let req = ClientRequest::ListTasks;
+    match client.request(req.clone()).await {
+        ServerResponse::Tasks(list) => {
+            let t = list.iter().find(|t| t.id == id).expect("task listed");
+            assert_eq!(t.spec.name.as_deref(), Some("nightly"));
+            assert!(t.next_run.is_some());
+        }
+        other => panic!("unexpected {other:?}"),
+    }
+
+    match client
+        .request(ClientRequest::RemoveTask { id, force: false })
+        .await
+    {
+        ServerResponse::Removed { ok } => assert!(ok),
+        other => panic!("unexpected {other:?}"),
+    }
+    match client.request(req).await {
+        ServerResponse::Tasks(list) => assert!(list.iter().all(|t| t.id != id)),
+        other => panic!("unexpected {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn invalid_spec_is_rejected() {
+    let server = TestServer::start().await;
+    let daily = Schedule::Daily { hour: 3, minute: 0 };
+    let mut client = server.client().await;
+
+    let empty = spec(" ", &[], server.path("a.log"), daily.clone());
+    assert!(matches!(
+        client.request(ClientRequest::AddTask(empty)).await,
+        ServerResponse::Error(_)
+    ));
+
+    // 只有依賴任務能引用 upstream
+    let templated = spec(
+        "echo",
+        &["{{upstream.x}}"],
+        server.path("a.log"),
+        daily.clone(),
+    );
+    assert!(matches!(
+        client.request(ClientRequest::AddTask(templated)).await,
+        ServerResponse::Error(_)
+    ));
+
+    let mut pinged = spec("true", &[], server.path("a.log"), daily);
+    pinged.healthcheck = Some(Healthcheck {
+        url: Some("hc-ping.com/abc".to_string()),
+        ..Default::default()
+    });
+    assert!(matches!(
+        client.request(ClientRequest::AddTask(pinged)).await,
+        ServerResponse::Error(_)
+    ));
+}
+
+#[tokio::test]
+async fn missing_commands_are_flagged_at_add() {
+    let daily = Schedule::Daily { hour: 3, minute: 0 };
+
+    // 預設只警告
+    let server = TestServer::start().await;
+    let mut client = server.client().await;
+    let typo = spec("no-such-cmd-xyz", &[], server.path("a.log"), daily.clone());
+    match client.request(ClientRequest::AddTask(typo.clone())).await {
+        ServerResponse::Added { warnings, .. } => {
+            assert!(warnings[0].contains("not found in PATH"), "{warnings:?}");
+        }
+        other => panic!("unexpected {other:?}"),
+    }
+    let found = spec("true", &[], server.path("b.log"), daily.clone());
+    match client.request(ClientRequest::AddTask(found)).await {
+        ServerResponse::Added { warnings, .. } => assert!(warnings.is_empty(), "{warnings:?}"),
+        other => panic!("unexpected {other:?}"),
+    }
+
+    let server = TestServer::with_config("cmd_check = \"reject\"\n").await;
+    let mut client = server.client().await;
+    match client.request(ClientRequest::AddTask(typo.clone())).await {
+        ServerResponse::Error(SchedulerError::InvalidRequest { msg }) => {
+            assert!(msg.contains("no-such-cmd-xyz"), "{msg}");
+        }
+        other => panic!("unexpected {other:?}"),
+    }
+    let mut skipped = typo;
+    skipped.skip_cmd_check = true;
+    assert!(matches!(
+        client.request(ClientRequest::AddTask(skipped)).await,
+        ServerResponse::Added { .. }
+    ));
+}
+
+#[tokio::test]
+async fn unknown_task_is_not_found() {
+    let server = TestServer::start().await;
+    let mut client = server.client().await;
+    assert!(matches!(
+        client
+            .request(ClientRequest::RemoveTask {
+                id: 999,
+                force: false
+            })
+            .await,
+        ServerResponse::Removed { ok: false }
+    ));
+    assert!(matches!(
+        client.request(ClientRequest::GetOutput { id: 999 }).await,
+        ServerResponse::Error(SchedulerError::NotFound { .. })
+    ));
+}
+
+#[tokio::test]
+async fn tagged_requests_share_one_connection() {
+    let server = TestServer::start().await;
+    let mut client = server.client().await;
+
+    // 訂閱之後同一條連線仍可送其他請求，回覆以 id 對應
+    client
+        .send_tagged(1, ClientRequest::Subscribe { since: None })
+        .await;
+    let hello = client.recv_tagged().await;
+    assert!(matches!(
+        hello,
+        ResponseFrame {
+            id: 1,
+            response: ServerResponse::Heartbeat { .. }
+        }
+    ));
+
+    let daily = Schedule::Daily { hour: 3, minute: 0 };
+    client
+        .send_tagged(
+            2,
+            ClientRequest::AddTask(spec("true", &[], server.path("a.log"), daily)),
+        )
+        .await;
+    client
+        .send_tagged(3, ClientRequest::GetHistory { id: 1, limit: 1 })
+        .await;
+    client.send_raw(br#"{"id":4,"request":"Nope"}"#).await;
+
+    let mut added = None;
+    let (mut history, mut rejected, mut event) = (false, false, false);
+    while added.is_none() || !history || !rejected || !event {
+        match client.recv_tagged().await {
+            ResponseFrame {
+                id: 2,
+                response: ServerResponse::Added { id, .. },
+            } => added = Some(id),
+            ResponseFrame {
+                id: 3,
+                response: ServerResponse::History(_),
+            } => history = true,
+            ResponseFrame {
+                id: 4,
+                response: ServerResponse::Error(SchedulerError::InvalidRequest { .. }),
+            } => rejected = true,
+            ResponseFrame {
+                id: 1,
+                response: ServerResponse::Event(ev),
+            } => {
+                assert!(matches!(ev.kind, EventKind::TaskAdded { .. }));
+                event = true;
+            }
+            ResponseFrame {
+                id: 1,
+                response: ServerResponse::Heartbeat { .. },
+            } => {}
+            other => panic!("unexpected {other:?}"),
+        }
+    }
+
+    // 直接送的請求照舊依序回覆
+    assert!(matches!(
+        client
+            .request(ClientRequest::RemoveTask {
+                id: added.unwrap(),
+                force: false,
+            })
+            .await,
+        ServerResponse::Removed { ok: true }
+    ));
+}
+
+async fn ws_connect(
+    addr: std::net::SocketAddr,
+    origin: &str,
+) -> Result<
+    tokio_tungstenite::WebSocketStream<tokio::net::TcpStream>,
+    tokio_tungstenite::tungstenite::Error,
+> {
+    let mut req = format!("ws://{addr}/").into_client_request().unwrap();
+    req.headers_mut().insert("Origin", origin.parse().unwrap());
+    let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+    tokio_tungstenite::client_async(req, stream)
+        .await
+        .map(|(ws, _)| ws)
+}
+
+#[tokio::test]
+async fn websocket_speaks_the_same_protocol() {
+    let server = TestServer::with_config(
+        "[websocket]\nbind = \"127.0.0.1:0\"\nallowed_origins = [\"http://dash.local\"]\n",
+    )
+    .await;
+    let ws_addr = server.ws_addr.expect("websocket address");
+
+    assert!(ws_connect(ws_addr, "http://evil.example").await.is_err());
+
+    let mut ws = ws_connect(ws_addr, "http://dash.local").await.unwrap();
+    let frame = RequestFrame {
+        id: 7,
+        request: ClientRequest::Subscribe { since: None },
+    };
+    ws.send(Message::text(serde_json::to_string(&frame).unwrap()))
+        .await
+        .unwrap();
+    let list = ClientRequest::ListTasks;
+    ws.send(Message::text(serde_json::to_string(&list).unwrap()))
+        .await
+        .unwrap();
+
+    let (mut heartbeat, mut tasks) = (false, false);
+    while !heartbeat || !tasks {
+        let msg = timeout(WAIT, ws.next())
+            .await
+            .expect("timed out")
+            .expect("closed")
+            .unwrap();
+        let Message::Text(text) = msg else {
+            continue;
+        };
+        if let Ok(tagged) = serde_json::from_str::<ResponseFrame>(&text) {
+            assert_eq!(tagged.id, 7);
+            heartbeat |= matches!(tagged.response, ServerResponse::Heartbeat { .. });
+        } else {
+            let resp: ServerResponse = serde_json::from_str(&text).unwrap();
+            assert!(matches!(resp, ServerResponse::Tasks(_)));
+            tasks = true;
+        }
+    }
+}
+
+#[tokio::test]
+async fn dependency_cycle_is_rejected() {
+    let server = TestServer::start().await;
+    let after = |task_id| Schedule::After {
+        task_id,
+        delay_secs: 0,
+        expires_after_secs: None,
+    };
+    let daily = Schedule::Daily { hour: 3, minute: 0 };
+    let root = server
+        .add(spec("true", &[], server.path("a.log"), daily))
+        .await;
+
+    // 上游可以是之後才新增的任務，所以兩個依賴任務能互相指向
+    let first = server
+        .add(spec("true", &[], server.path("b.log"), after(root + 2)))
+        .await;
+    let mut client = server.client().await;
+    let looped = spec("true", &[], server.path("c.log"), after(first));
+    match client.request(ClientRequest::AddTask(looped)).await {
+        ServerResponse::Error(SchedulerError::CycleDetected { cycle }) => {
+            assert_eq!(cycle, vec![root + 2, first]);
+        }
+        other => panic!("unexpected {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn mqtt_triggers_are_validated() {
+    let plain = TestServer::start().await;
+    let mut s = spec("true", &[], plain.path("a.log"), Schedule::Manual);
+    s.triggers = vec![Trigger::Mqtt {
+        topic: "jobs/run".to_string(),
+    }];
+    // 伺服器沒有 [mqtt] 設定
+    let mut client = plain.client().await;
+    assert!(matches!(
+        client.request(ClientRequest::AddTask(s.clone())).await,
+        ServerResponse::Error(_)
+    ));
+
+    let server = TestServer::with_config("[mqtt]\nhost = \"127.0.0.1\"\nport = 1\n").await;
+    let mut client = server.client().await;
+    assert!(matches!(
+        client.request(ClientRequest::AddTask(s.clone())).await,
+        ServerResponse::Added { .. }
+    ));
+
+    let mut bad = s.clone();
+    bad.triggers = vec![Trigger::Mqtt {
+        topic: "jobs/#/run".to_string(),
+    }];
+    assert!(matches!(
+        client.request(ClientRequest::AddTask(bad)).await,
+        ServerResponse::Error(_)
+    ));
+
+    // manual 沒有 trigger 就永遠不會執行
+    s.triggers.clear();
+    assert!(matches!(
+        client.request(ClientRequest::AddTask(s)).await,
+        ServerResponse::Error(SchedulerError::InvalidSchedule { .. })
+    ));
+}
+
+#[tokio::test]
+async fn bridge_triggers_require_config() {
+    let plain = TestServer::start().await;
+    let mut s = spec("true", &[], plain.path("a.log"), Schedule::Manual);
+    s.triggers = vec![Trigger::Bridge {
+        subject: "deploy.done".to_string(),
+    }];
+
+    let mut client = plain.client().await;
+    assert!(matches!(
+        client.request(ClientRequest::AddTask(s.clone())).await,
+        ServerResponse::Error(_)
+    ));
+
+    // 連不上 NATS 不影響新增任務，連上後才訂閱
+    let server =
+        TestServer::with_config("[bridge]\nkind = \"nats\"\nurl = \"nats://127.0.0.1:1\"\n").await;
+    let mut client = server.client().await;
+    assert!(matches!(
+        client.request(ClientRequest::AddTask(s)).await,
+        ServerResponse::Added { .. }
+    ));
+}
+
+#[tokio::test]
+async fn daily_tasks_in_same_minute_are_staggered() {
+    let server = TestServer::with_config("[stagger]\nwindow_secs = 60\n").await;
+    let daily = Schedule::Daily { hour: 3, minute: 0 };
+    let a = server
+        .add(spec("true", &[], server.path("a.log"), daily.clone()))
+        .await;
+    let b = server
+        .add(spec("true", &[], server.path("b.log"), daily.clone()))
+        .await;
+    let mut s = spec("true", &[], server.path("c.log"), daily);
+    s.exact_start = true;
+    let c = server.add(s).await;
+
+    let mut client = server.client().await;
+    let req = ClientRequest::ListTasks;
+    let ServerResponse::Tasks(list) = client.request(req).await else {
+        panic!("expected task list");
+    };
+    let next = |id: u64| list.iter().find(|t| t.id == id).unwrap().next_run.unwrap();
+    // 兩個參與錯開的任務平分 60 秒；exact_start 的任務準時
+    assert_eq!((next(b) - next(a)).num_seconds(), 30);
+    assert_eq!(next(c), next(a));
+}
+
+#[tokio::test]
+async fn listeners_share_state() {
+    let server = TestServer::with_binds("", &["127.0.0.1:0", "[::1]:0", "unix:server.sock"]).await;
+    let [v4, v6] = server.addrs[..] else {
+        panic!("expected two TCP listeners, got {:?}", server.addrs);
+    };
+    assert!(v6.is_ipv6());
+
+    let mut unix = support::Client::connect_unix(server.path("server.sock")).await;
+    let s = spec(
+        "true",
+        &[],
+        server.path("a.log"),
+        Schedule::Daily { hour: 3, minute: 0 },
+    );
+    let id = match unix.request(ClientRequest::AddTask(s)).await {
+        ServerResponse::Added { id, .. } => id,
+        other => panic!("unexpected: {other:?}"),
+    };
+
+    for addr in [v4, v6] {
+        let mut client = support::Client::connect(addr).await;
+        let req = ClientRequest::ListTasks;
+        match client.request(req).await {
+            ServerResponse::Tasks(tasks) => assert!(tasks.iter().any(|t| t.id == id)),
+            other => panic!("unexpected: {other:?}"),
+        }
+    }
+}
+
+#[tokio::test]
+async fn access_log_samples_successes_and_keeps_errors() {
+    let server =
+        TestServer::with_config("[access_log]\npath = \"access.jsonl\"\nsample_every = 2\n").await;
+    let mut client = server.client().await;
+    for _ in 0..3 {
+        let req = ClientRequest::ListTasks;
+        assert!(matches!(
+            client.request(req).await,
+            ServerResponse::Tasks(_)
+        ));
+    }
+    client.request(ClientRequest::GetOutput { id: 999 }).await;
+    drop(client);
+
+    // close 在伺服器察覺斷線後才寫入
+    let path = server.path("access.jsonl");
+    let records: Vec<serde_json::Value> = timeout(WAIT, async {
+        loop {
+            let text = std::fs::read_to_string(&path).unwrap_or_default();
+            if text.contains("\"close\"") {
+                break text
+                    .lines()
+                    .map(|l| serde_json::from_str(l).unwrap())
+                    .collect();
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        }
+    })
+    .await
+    .expect("close record was not written");
+
+    let events: Vec<&str> = records
+        .iter()
+        .map(|r| r["event"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        events,
+        ["connect", "request", "request", "request", "close"]
+    );
+    // 成功的 3 筆抽樣留下第 1、3 筆；錯誤不抽樣
+    let outcomes: Vec<&str> = records[1..4]
+        .iter()
+        .map(|r| r["outcome"].as_str().unwrap())
+        .collect();
+    assert_eq!(outcomes, ["ok", "ok", "NotFound"]);
+    assert_eq!(records[3]["request"], "GetOutput");
+    assert_eq!(records[0]["transport"], "tcp");
+    assert_eq!(records[4]["requests"], 4);
+    assert!(records.iter().all(|r| r["conn"] == records[0]["conn"]));
+}
+
+#[tokio::test]
+async fn access_log_rotates_and_keeps_compressed_copies() {
+    let server = TestServer::with_config(
+        "[access_log]\npath = \"access.jsonl\"\nconnections = false\n\
+         [access_log.rotate]\nmax_bytes = 400\nkeep = 2\ncompression = \"gzip\"\n",
+    )
+    .await;
+    let mut client = server.client().await;
+    for _ in 0..20 {
+        client.request(ClientRequest::GetServerInfo).await;
+    }
+
+    // 壓縮與清理在背景進行
+    let raw = timeout(WAIT, async {
+        loop {
+            let mut names: Vec<String> = std::fs::read_dir(&server.dir)
+                .unwrap()
+                .filter_map(|e| e.unwrap().file_name().into_string().ok())
+                .filter(|n| n.starts_with("access.jsonl."))
+                .collect();
+            names.sort();
+            if names.len() == 2 && names.iter().all(|n| n.ends_with(".gz")) {
+                // 最後幾個請求的紀錄可能又觸發一次輪替，讀不到就再等
+                if let Ok(raw) = std::fs::read(server.path(&names[1])) {
+                    break raw;
+                }
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        }
+    })
+    .await
+    .expect("rotated logs were not compressed and pruned");
+
+    let mut text = String::new();
+    std::io::Read::read_to_string(&mut flate2::read::GzDecoder::new(&raw[..]), &mut text).unwrap();
+    assert!(!text.is_empty());
+    for line in text.lines() {
+        let record: serde_json::Value = serde_json::from_str(line).unwrap();
+        assert_eq!(record["request"], "GetServerInfo");
+    }
+    let current = std::fs::read_to_string(server.path("access.jsonl")).unwrap();
+    assert!(current.len() <= 400);
+}
+
+#[tokio::test]
+async fn revisions_record_and_roll_back() {
+    let server = TestServer::start().await;
+    let daily = Schedule::Daily { hour: 4, minute: 0 };
+    let id = server
+        .add(spec("true", &[], server.path("r.log"), daily))
+        .await;
+    let mut client = server.client().await;
+
+    let list = match client.request(ClientRequest::ListRevisions { id }).await {
+        ServerResponse::Revisions(list) => list,
+        other => panic!("unexpected {other:?}"),
+    };
+    assert_eq!(list.len(), 1);
+    assert_eq!(list[0].revision, 1);
+    assert!(list[0].actor.starts_with("tcp:"), "{}", list[0].actor);
+
+    match client
+        .request(ClientRequest::RollbackTask {
+            id,
+            revision: 1,
+            force: false,
+        })
+        .await
+    {
+        ServerResponse::RolledBack { id: got, revision } => {
+            assert_eq!((got, revision), (id, 2));
+        }
+        other => panic!("unexpected {other:?}"),
+    }
+    let list = match client.request(ClientRequest::ListRevisions { id }).await {
+        ServerResponse::Revisions(list) => list,
+        other => panic!("unexpected {other:?}"),
+    };
+    assert_eq!(list.len(), 2);
+    assert_eq!(list[1].rollback_of, Some(1));
+    assert!(list[1].changes.is_empty());
+
+    assert!(matches!(
+        client
+            .request(ClientRequest::RollbackTask {
+                id,
+                revision: 9,
+                force: false
+            })
+            .await,
+        ServerResponse::Error(SchedulerError::NotFound { .. })
+    ));
+}
+
+#[tokio::test]
+async fn removed_tasks_go_to_trash_and_can_be_restored() {
+    let server = TestServer::start().await;
+    let daily = Schedule::Daily { hour: 5, minute: 0 };
+    let id = server
+        .add(spec("true", &[], server.path("t.log"), daily.clone()))
+        .await;
+    let second = server
+        .add(spec("true", &[], server.path("u.log"), daily))
+        .await;
+    let mut client = server.client().await;
+    let listed = |resp: ServerResponse| match resp {
+        ServerResponse::Tasks(list) => list.iter().map(|t| t.id).collect::<Vec<_>>(),
+        other => panic!("unexpected {other:?}"),
+    };
+    let list = ClientRequest::ListTasks;
+
+    for task in [id, second] {
+        assert!(matches!(
+            client
+                .request(ClientRequest::RemoveTask {
+                    id: task,
+                    force: false
+                })
+                .await,
+            ServerResponse::Removed { ok: true }
+        ));
+    }
+    match client.request(ClientRequest::ListTrash).await {
+        ServerResponse::Trash(items) => {
+            assert_eq!(items.iter().map(|t| t.id).collect::<Vec<_>>(), [id, second]);
+            assert!(items[0].expires_at > items[0].removed_at);
+        }
+        other => panic!("unexpected {other:?}"),
+    }
+
+    match client.request(ClientRequest::RestoreTask { id }).await {
+        ServerResponse::Restored { id: got } => assert_eq!(got, id),
+        other => panic!("unexpected {other:?}"),
+    }
+    assert_eq!(listed(client.request(list.clone()).await), [id]);
+
+    // 永久刪除後就無法還原
+    assert!(matches!(
+        client
+            .request(ClientRequest::PurgeTask {
+                id: second,
+                force: false
+            })
+            .await,
+        ServerResponse::Removed { ok: true }
+    ));
+    assert!(matches!(
+        client
+            .request(ClientRequest::RestoreTask { id: second })
+            .await,
+        ServerResponse::Error(SchedulerError::NotFound { .. })
+    ));
+    assert!(matches!(
+        client.request(ClientRequest::ListTrash).await,
+        ServerResponse::Trash(items) if items.is_empty()
+    ));
+}
+
+#[tokio::test]
+async fn removal_preview_reports_impact_without_removing() {
+    let server = TestServer::start().await;
+    let daily = Schedule::Daily { hour: 6, minute: 0 };
+    let root = server
+        .add(spec("true", &[], server.path("p.log"), daily))
+        .await;
+    let after = Schedule::After {
+        task_id: root,
+        delay_secs: 0,
+        expires_after_secs: None,
+    };
+    let child = server
+        .add(spec("true", &[], server.path("q.log"), after))
+        .await;
+    let mut client = server.client().await;
+
+    let req = ClientRequest::PreviewRemoval {
+        ids: vec![root, child, 999],
+    };
+    match client.request(req).await {
+        ServerResponse::RemovalPreview(list) => {
+            assert_eq!(list.len(), 3);
+            assert!(list[0].found && list[0].recurring);
+            assert_eq!(list[0].dependents, [child]);
+            assert!(list[1].found && !list[1].recurring && list[1].dependents.is_empty());
+            assert!(!list[2].found && !list[2].in_trash);
+        }
+        other => panic!("unexpected {other:?}"),
+    }
+    let list = ClientRequest::ListTasks;
+    match client.request(list).await {
+        ServerResponse::Tasks(tasks) => assert_eq!(tasks.len(), 2),
+        other => panic!("unexpected {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn protected_tasks_need_force() {
+    let server = TestServer::start().await;
+    let mut s = spec(
+        "true",
+        &[],
+        server.path("p.log"),
+        Schedule::Daily { hour: 3, minute: 0 },
+    );
+    s.protected = true;
+    let id = server.add(s).await;
+    let mut client = server.client().await;
+
+    let refused = |resp: ServerResponse| match resp {
+        ServerResponse::Error(SchedulerError::Unauthorized { msg }) => {
+            assert!(msg.contains("protected"), "{msg}")
+        }
+        other => panic!("unexpected {other:?}"),
+    };
+    refused(
+        client
+            .request(ClientRequest::RemoveTask { id, force: false })
+            .await,
+    );
+    refused(
+        client
+            .request(ClientRequest::RollbackTask {
+                id,
+                revision: 1,
+                force: false,
+            })
+            .await,
+    );
+    match client
+        .request(ClientRequest::PreviewRemoval { ids: vec![id] })
+        .await
+    {
+        ServerResponse::RemovalPreview(list) => assert!(list[0].protected),
+        other => panic!("unexpected {other:?}"),
+    }
+
+    assert!(matches!(
+        client
+            .request(ClientRequest::RemoveTask { id, force: true })
+            .await,
+        ServerResponse::Removed { ok: true }
+    ));
+    // 回收桶中的仍受保護
+    refused(
+        client
+            .request(ClientRequest::PurgeTask { id, force: false })
+            .await,
+    );
+    assert!(matches!(
+        client
+            .request(ClientRequest::PurgeTask { id, force: true })
+            .await,
+        ServerResponse::Removed { ok: true }
+    ));
+}
+
+#[tokio::test]
+async fn unknown_spec_fields_rejected_unless_lenient() {
+    let daily = Schedule::Daily { hour: 3, minute: 0 };
+    for (config, strict) in [("", true), ("spec_mode = \"lenient\"\n", false)] {
+        let server = TestServer::with_config(config).await;
+        let add = ClientRequest::AddTask(spec("true", &[], server.path("a.log"), daily.clone()));
+        let mut frame = serde_json::to_value(add).unwrap();
+        frame["AddTask"]["apend"] = true.into();
+        frame["AddTask"]["healthcheck"] = serde_json::json!({"url": "http://x", "sucess": "y"});
+
+        let mut client = server.client().await;
+        client.send_raw(&serde_json::to_vec(&frame).unwrap()).await;
+        match client.recv().await.expect("connection closed") {
+            ServerResponse::Error(SchedulerError::InvalidRequest { msg }) if strict => {
+                assert!(
+                    msg.contains("apend") && msg.contains("healthcheck.sucess"),
+                    "{msg}"
+                );
+            }
+            ServerResponse::Added { .. } if !strict => {}
+            other => panic!("unexpected {other:?}"),
+        }
+    }
+}
+
+#[tokio::test]
+async fn list_tasks_sorted_and_legacy_frames() {
+    let server = TestServer::start().await;
+    let daily = Schedule::Daily { hour: 3, minute: 0 };
+    for name in ["b", "a", "c"] {
+        let mut s = spec("true", &[], server.path("a.log"), daily.clone());
+        s.name = Some(name.to_string());
+        server.add(s).await;
+    }
+    let names = |resp| match resp {
+        ServerResponse::Tasks(list) => list
+            .into_iter()
+            .map(|t| t.spec.name.unwrap_or_default())
+            .collect::<Vec<_>>(),
+        other => panic!("unexpected {other:?}"),
+    };
+
+    let mut client = server.client().await;
+    let sorted = client
+        .request(ClientRequest::ListTasksSorted {
+            sort: TaskSort::Name,
+            descending: true,
+        })
+        .await;
+    assert_eq!(names(sorted), ["c", "b", "a"]);
+    // 舊客戶端送的是沒有參數的 "ListTasks"，依 id 排序
+    client.send_raw(br#""ListTasks""#).await;
+    let legacy = client.recv().await.expect("connection closed");
+    assert_eq!(names(legacy), ["b", "a", "c"]);
+}
+
+#[tokio::test]
+async fn startup_report_lists_invalid_tasks_and_next_fires() {
+    // 事先寫好的持久化檔：一個正常的 Daily 任務、一個已被政策禁止的命令
+    let data =
+        std::env::temp_dir().join(format!("scheduler-it-startup-{}.json", std::process::id()));
+    let daily = Schedule::Daily { hour: 3, minute: 0 };
+    let out = std::env::temp_dir().join("scheduler-it-startup.log");
+    let mut ok = spec("true", &[], out.clone(), daily.clone());
+    ok.name = Some("nightly".to_string());
+    let denied = spec("rm", &["-rf", "x"], out, daily);
+    let records = serde_json::json!([
+        { "id": 4, "spec": ok },
+        { "id": 7, "spec": denied },
+    ]);
+    std::fs::write(&data, serde_json::to_vec(&records).unwrap()).unwrap();
+
+    let server = TestServer::with_config(&format!(
+        "data_path = {:?}\n[policy]\ndeny = [\"rm\"]\n",
+        data.display().to_string()
+    ))
+    .await;
+    let info = match server
+        .client()
+        .await
+        .request(ClientRequest::GetServerInfo)
+        .await
+    {
+        ServerResponse::ServerInfo(info) => info,
+        other => panic!("unexpected {other:?}"),
+    };
+    let _ = std::fs::remove_file(&data);
+
+    let report = info.startup;
+    assert_eq!(report.tasks_loaded, 2);
+    assert_eq!(report.invalid.len(), 1);
+    assert_eq!(report.invalid[0].id, 7);
+    assert!(report.invalid[0].reason.contains("denied"), "{report:?}");
+    assert!(report.storage_ok, "{}", report.storage_report);
+    assert_eq!(report.next_fires.len(), 1);
+    assert_eq!(report.next_fires[0].task_id, 4);
+    assert_eq!(report.next_fires[0].name.as_deref(), Some("nightly"));
+}
+
+#[tokio::test]
+async fn bare_name_policy_rules_only_allow_path_commands() {
+    let config = "[policy]\nallow = [\"true\", \"rm\"]\ndeny = [\"rm\"]\n";
+    let server = TestServer::with_config(config).await;
+    let daily = Schedule::Daily { hour: 3, minute: 0 };
+    let add =
+        |cmd: &str| ClientRequest::AddTask(spec(cmd, &[], server.path("p.log"), daily.clone()));
+    let mut client = server.client().await;
+    assert!(matches!(
+        client.request(add("true")).await,
+        ServerResponse::Added { .. }
+    ));
+
+    // 同名但放在別處的執行檔不算在 allow 的 "true" 之內
+    let installed = std::env::split_paths(&std::env::var_os("PATH").unwrap())
+        .map(|dir| dir.join("true"))
+        .find(|p| p.is_file())
+        .expect("true in PATH");
+    let copy = server.path("bin/true");
+    std::fs::create_dir_all(copy.parent().unwrap()).unwrap();
+    std::fs::copy(installed, &copy).unwrap();
+    assert!(matches!(
+        client.request(add(&copy.display().to_string())).await,
+        ServerResponse::Error(SchedulerError::Unauthorized { .. })
+    ));
+
+    // deny 的名稱規則擋下以路徑指定的同名命令
+    assert!(matches!(
+        client.request(add("/bin/rm")).await,
+        ServerResponse::Error(SchedulerError::Unauthorized { .. })
+    ));
+
+    // 任務把 PATH 指到放了同名執行檔的目錄，也不算在 allow 的 "true" 之內
+    let mut shadowed = spec("true", &[], server.path("p.log"), daily.clone());
+    shadowed.env.insert(
+        "PATH".to_string(),
+        copy.parent().unwrap().display().to_string(),
+    );
+    assert!(matches!(
+        client.request(ClientRequest::AddTask(shadowed)).await,
+        ServerResponse::Error(SchedulerError::Unauthorized { .. })
+    ));
+}
+
+#[tokio::test]
+async fn orphaned_runs_are_reported_and_failed_spawns_leave_no_mark() {
+    // 上次停機時任務 4 仍在執行
+    let data =
+        std::env::temp_dir().join(format!("scheduler-it-orphans-{}.json", std::process::id()));
+    let daily = Schedule::Daily { hour: 3, minute: 0 };
+    let out = std::env::temp_dir().join("scheduler-it-orphans.log");
+    let since = chrono::Local::now().fixed_offset() - chrono::Duration::hours(1);
+    let records = serde_json::json!([
+        { "id": 4, "spec": spec("true", &[], out.clone(), daily.clone()), "running_since": since },
+    ]);
+    std::fs::write(&data, serde_json::to_vec(&records).unwrap()).unwrap();
+
+    let server =
+        TestServer::with_config(&format!("data_path = {:?}\n", data.display().to_string())).await;
+    let mut client = server.client().await;
+    match client.request(ClientRequest::GetServerInfo).await {
+        ServerResponse::ServerInfo(info) => {
+            let orphans = &info.startup.orphans;
+            assert_eq!(orphans.len(), 1, "{orphans:?}");
+            assert_eq!(orphans[0].task_id, 4);
+            assert!(!orphans[0].rerun);
+        }
+        other => panic!("unexpected {other:?}"),
+    }
+    // 啟動摘要也以事件發出，重播得到
+    let mut sub = server.client().await;
+    sub.send(&ClientRequest::Subscribe { since: Some(0) }).await;
+    timeout(WAIT, async {
+        loop {
+            match sub.recv().await.expect("event stream closed") {
+                ServerResponse::Event(ev) => {
+                    if let EventKind::ServerStarted { report } = ev.kind {
+                        assert_eq!(report.orphans.len(), 1);
+                        return;
+                    }
+                }
+                _ => continue,
+            }
+        }
+    })
+    .await
+    .expect("no ServerStarted event");
+
+    // 命令啟動失敗：「執行中」標記要從持久化檔清掉，否則下次啟動會被當成孤兒
+    let mut broken = spec("/nonexistent/scheduler-it-cmd", &[], out, daily);
+    broken.skip_cmd_check = true;
+    let id = server.add(broken).await;
+    client
+        .request(ClientRequest::RunNow {
+            id,
+            follow: false,
+            metadata: Default::default(),
+        })
+        .await;
+    let marked = || {
+        let saved: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(&data).unwrap()).unwrap();
+        saved
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|r| !r["running_since"].is_null())
+    };
+    tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+    timeout(WAIT, async {
+        while marked() {
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        }
+    })
+    .await
+    .expect("running mark left behind");
+    let _ = std::fs::remove_file(&data);
+}
+
+#[tokio::test]
+async fn malformed_persisted_tasks_are_quarantined() {
+    let data = std::env::temp_dir().join(format!(
+        "scheduler-it-quarantine-{}.json",
+        std::process::id()
+    ));
+    let good = spec(
+        "true",
+        &[],
+        std::env::temp_dir().join("scheduler-it-quarantine.log"),
+        Schedule::Daily { hour: 3, minute: 0 },
+    );
+    let records = serde_json::json!([
+        { "id": 3, "spec": { "cmd": "echo", "schedule": "Manual" } },
+        { "id": 5, "spec": good },
+        { "id": 5, "spec": good },
+    ]);
+    std::fs::write(&data, serde_json::to_vec(&records).unwrap()).unwrap();
+
+    let server =
+        TestServer::with_config(&format!("data_path = {:?}\n", data.display().to_string())).await;
+    let mut client = server.client().await;
+    let list = match client.request(ClientRequest::ListQuarantine).await {
+        ServerResponse::Quarantine(list) => list,
+        other => panic!("unexpected {other:?}"),
+    };
+    assert_eq!(list.len(), 2, "{list:?}");
+    assert_eq!(list[0].id, Some(3));
+    assert!(list[0].reason.contains("missing field"), "{list:?}");
+    assert!(list[1].reason.contains("duplicate"), "{list:?}");
+
+    // 有效的照常載入，持久化檔只剩它
+    match client.request(ClientRequest::ListTasks).await {
+        ServerResponse::Tasks(tasks) => {
+            assert_eq!(tasks.iter().map(|t| t.id).collect::<Vec<_>>(), [5]);
+        }
+        other => panic!("unexpected {other:?}"),
+    }
+    let persisted: Vec<serde_json::Value> =
+        serde_json::from_slice(&std::fs::read(&data).unwrap()).unwrap();
+    let _ = std::fs::remove_file(&data);
+    assert_eq!(persisted.len(), 1);
+    assert!(server.path("quarantine.json").exists());
+
+    let seq = list[0].seq;
+    match client
+        .request(ClientRequest::DiscardQuarantined { seq })
+        .await
+    {
+        ServerResponse::QuarantineDiscarded { seq: s } => assert_eq!(s, seq),
+        other => panic!("unexpected {other:?}"),
+    }
+    match client
+        .request(ClientRequest::DiscardQuarantined { seq })
+        .await
+    {
+        ServerResponse::Error(SchedulerError::NotFound { .. }) => {}
+        other => panic!("unexpected {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn second_server_on_the_same_data_file_exits() {
+    let dir = std::env::temp_dir().join(format!("scheduler-it-lock-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let data = dir.join("tasks.json");
+    // 上次異常結束留下的鎖檔：直接接手
+    std::fs::write(dir.join("tasks.json.lock"), "4194303\n").unwrap();
+    let first =
+        TestServer::with_config(&format!("data_path = {:?}\n", data.display().to_string())).await;
+
+    let second = tokio::process::Command::new(env!("CARGO_BIN_EXE_scheduler-server"))
+        .arg("--data")
+        .arg(&data)
+        .args(["--bind", "127.0.0.1:0"])
+        .current_dir(&dir)
+        .kill_on_drop(true)
+        .output();
+    let second = timeout(WAIT, second)
+        .await
+        .expect("second server kept running")
+        .unwrap();
+    assert!(!second.status.success());
+    let stderr = String::from_utf8_lossy(&second.stderr);
+    assert!(
+        stderr.contains("in use by another scheduler-server"),
+        "{stderr}"
+    );
+
+    // 第一個伺服器不受影響
+    first
+        .add(spec(
+            "true",
+            &[],
+            first.path("a.log"),
+            Schedule::Daily { hour: 3, minute: 0 },
+        ))
+        .await;
+    drop(first);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn dependency_graph_reports_dangling_edges() {
+    let after = |task_id| Schedule::After {
+        task_id,
+        delay_secs: 0,
+        expires_after_secs: None,
+    };
+    // 持久化檔中有一個前置任務已不存在的依賴
+    let data = std::env::temp_dir().join(format!("scheduler-it-deps-{}.json", std::process::id()));
+    let out = std::env::temp_dir().join("scheduler-it-deps.log");
+    let records = serde_json::json!([{ "id": 1, "spec": spec("true", &[], out, after(99)) }]);
+    std::fs::write(&data, serde_json::to_vec(&records).unwrap()).unwrap();
+    let server =
+        TestServer::with_config(&format!("data_path = {:?}\n", data.display().to_string())).await;
+
+    let daily = Schedule::Daily { hour: 3, minute: 0 };
+    let up = server
+        .add(spec("true", &[], server.path("up.log"), daily))
+        .await;
+    let a = server
+        .add(spec("true", &[], server.path("a.log"), after(up)))
+        .await;
+    let b = server
+        .add(spec("true", &[], server.path("b.log"), after(up)))
+        .await;
+
+    let graph = match server
+        .client()
+        .await
+        .request(ClientRequest::GetDependencyGraph)
+        .await
+    {
+        ServerResponse::DependencyGraph(graph) => graph,
+        other => panic!("unexpected {other:?}"),
+    };
+    let _ = std::fs::remove_file(&data);
+    let watch = graph.dependents.iter().find(|w| w.upstream == up).unwrap();
+    assert_eq!(watch.dependents, [a, b]);
+    let upstream: Vec<(u64, u64)> = graph
+        .upstream
+        .iter()
+        .map(|e| (e.task_id, e.upstream))
+        .collect();
+    assert_eq!(upstream, [(1, 99), (a, up), (b, up)]);
+    assert_eq!(graph.issues.len(), 1, "{graph:?}");
+    assert_eq!(graph.issues[0].task_id, 1);
+    assert_eq!(graph.issues[0].upstream, 99);
+    assert_eq!(graph.issues[0].kind, DependencyIssueKind::MissingUpstream);
+}
+
+#[tokio::test]
+async fn replica_serves_reads_and_forwards_changes() {
+    let primary = TestServer::start().await;
+    let daily = Schedule::Daily { hour: 3, minute: 0 };
+    let existing = primary
+        .add(spec("true", &[], primary.path("a.log"), daily.clone()))
+        .await;
+
+    let replica = TestServer::with_config(&format!(
+        "data_path = \"{}\"\n[history]\npath = \"{}\"\n[replica]\nprimary = \"{}\"\n",
+        primary.path("tasks.json").display(),
+        primary.path("history.jsonl").display(),
+        primary.addr
+    ))
+    .await;
+    let mut client = replica.client().await;
+    let list = ClientRequest::ListTasks;
+    match client.request(list.clone()).await {
+        ServerResponse::Tasks(tasks) => assert!(tasks.iter().any(|t| t.id == existing)),
+        other => panic!("unexpected {other:?}"),
+    }
+
+    // 變更轉送給主伺服器
+    let added = match client
+        .request(ClientRequest::AddTask(spec(
+            "true",
+            &[],
+            primary.path("b.log"),
+            daily,
+        )))
+        .await
+    {
+        ServerResponse::Added { id, .. } => id,
+        other => panic!("unexpected {other:?}"),
+    };
+    match primary.client().await.request(list.clone()).await {
+        ServerResponse::Tasks(tasks) => assert!(tasks.iter().any(|t| t.id == added)),
+        other => panic!("unexpected {other:?}"),
+    }
+
+    // 事件由副本轉播，歷史隨後可在副本查到
+    let mut events = replica.subscribe().await;
+    client
+        .request(ClientRequest::RunNow {
+            id: existing,
+            follow: false,
+            metadata: Default::default(),
+        })
+        .await;
+    events.run_finished(existing).await;
+    timeout(WAIT, async {
+        while client.history(existing).await.is_empty() {
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        }
+    })
+    .await
+    .expect("replica never saw the run");
+    match client.request(list).await {
+        ServerResponse::Tasks(tasks) => assert!(tasks.iter().any(|t| t.id == added)),
+        other => panic!("unexpected {other:?}"),
+    }
+
+    // 副本不執行任務，無法跟隨輸出
+    match client
+        .request(ClientRequest::RunNow {
+            id: existing,
+            follow: true,
+            metadata: Default::default(),
+        })
+        .await
+    {
+        ServerResponse::Error(SchedulerError::InvalidRequest { msg }) => {
+            assert!(msg.contains("replica"), "{msg}")
+        }
+        other => panic!("unexpected {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn list_tasks_since_returns_only_changes() {
+    let server = TestServer::start().await;
+    let daily = Schedule::Daily { hour: 3, minute: 0 };
+    let a = server
+        .add(spec("true", &[], server.path("a.log"), daily.clone()))
+        .await;
+    let b = server
+        .add(spec("true", &[], server.path("b.log"), daily.clone()))
+        .await;
+    let mut client = server.client().await;
+    let since = |version| ClientRequest::ListTasksSince { version };
+    let changes = |resp: ServerResponse| match resp {
+        ServerResponse::TaskChanges {
+            version,
+            full,
+            changed,
+            removed,
+        } => (
+            version,
+            full,
+            changed.iter().map(|t| t.id).collect::<Vec<_>>(),
+            removed,
+        ),
+        other => panic!("unexpected {other:?}"),
+    };
+
+    let (v1, full, changed, removed) = changes(client.request(since(0)).await);
+    assert!(full);
+    assert_eq!(changed, [a, b]);
+    assert!(removed.is_empty());
+
+    assert!(matches!(
+        client
+            .request(ClientRequest::RemoveTask {
+                id: b,
+                force: false
+            })
+            .await,
+        ServerResponse::Removed { ok: true }
+    ));
+    let c = server
+        .add(spec("true", &[], server.path("c.log"), daily))
+        .await;
+    let (v2, full, changed, removed) = changes(client.request(since(v1)).await);
+    assert!(!full);
+    assert!(v2 > v1);
+    assert_eq!(changed, [c]);
+    assert_eq!(removed, [b]);
+
+    let (v3, full, changed, removed) = changes(client.request(since(v2)).await);
+    assert_eq!((v3, full), (v2, false));
+    assert!(changed.is_empty() && removed.is_empty());
+
+    // 比伺服器目前還新的版本（例如伺服器重啟過）回傳完整清單
+    let (_, full, changed, _) = changes(client.request(since(v2 + 100)).await);
+    assert!(full);
+    assert_eq!(changed, [a, c]);
+}
+
+#[tokio::test]
+async fn list_tasks_since_is_full_after_restart_without_persisted_events() {
+    // 事件不寫檔時，重啟後 seq 也要從高水位接續，舊的 version 一律回完整清單
+    let dir = std::env::temp_dir().join(format!("scheduler-it-since-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let config = format!(
+        "data_path = {:?}\n[events]\npersist = false\nseq_path = {:?}\n",
+        dir.join("tasks.json").display().to_string(),
+        dir.join("events.seq.json").display().to_string(),
+    );
+    let daily = Schedule::Daily { hour: 3, minute: 0 };
+    let since = |version| ClientRequest::ListTasksSince { version };
+
+    let first = TestServer::with_config(&config).await;
+    let a = first
+        .add(spec("true", &[], first.path("a.log"), daily.clone()))
+        .await;
+    let version = match first.client().await.request(since(0)).await {
+        ServerResponse::TaskChanges { version, .. } => version,
+        other => panic!("unexpected {other:?}"),
+    };
+    drop(first);
+
+    let second = TestServer::with_config(&config).await;
+    let mut client = second.client().await;
+    let mut added = Vec::new();
+    for i in 0..3 {
+        let out = second.path(format!("{i}.log"));
+        added.push(second.add(spec("true", &[], out, daily.clone())).await);
+    }
+    match client.request(since(version)).await {
+        ServerResponse::TaskChanges {
+            version: now,
+            full,
+            changed,
+            ..
+        } => {
+            assert!(now > version);
+            assert!(full);
+            let ids: Vec<u64> = changed.iter().map(|t| t.id).collect();
+            assert!(ids.contains(&a), "{ids:?}");
+            assert!(added.iter().all(|id| ids.contains(id)), "{ids:?}");
+        }
+        other => panic!("unexpected {other:?}"),
+    }
+    drop(second);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn environment_data_lives_next_to_the_data_path() {
+    // 預設環境的資料放在啟動目錄以外：其他環境的資料跟著它，不落在啟動目錄
+    let data = std::env::temp_dir().join(format!("scheduler-it-envdir-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&data);
+    std::fs::create_dir_all(&data).unwrap();
+    let server = TestServer::with_config(&format!(
+        "data_path = {:?}\n[environments.dev]\n",
+        data.join("tasks.json").display().to_string()
+    ))
+    .await;
+    let add = ClientRequest::AddTask(spec(
+        "true",
+        &[],
+        server.path("dev.log"),
+        Schedule::Daily { hour: 3, minute: 0 },
+    ));
+    let req = ClientRequest::InEnvironment {
+        environment: "dev".to_string(),
+        token: None,
+        request: Box::new(add),
+    };
+    assert!(matches!(
+        server.client().await.request(req).await,
+        ServerResponse::Added { .. }
+    ));
+    assert!(data.join("environments/dev/tasks.json").exists());
+    assert!(!server.path("environments").exists());
+    drop(server);
+    let _ = std::fs::remove_dir_all(&data);
+}
+
+#[tokio::test]
+async fn environments_keep_tasks_and_data_apart() {
+    let server = TestServer::with_config(
+        "[environments.dev]\ntoken = \"s3cret\"\n\n[environments.dev.vars]\nregion = \"eu\"\n",
+    )
+    .await;
+    let mut client = server.client().await;
+    let in_dev = |token: Option<&str>, request| ClientRequest::InEnvironment {
+        environment: "dev".to_string(),
+        token: token.map(str::to_string),
+        request: Box::new(request),
+    };
+    let list = || ClientRequest::ListTasks;
+    let ids = |resp: ServerResponse| match resp {
+        ServerResponse::Tasks(list) => list.iter().map(|t| t.id).collect::<Vec<_>>(),
+        other => panic!("unexpected {other:?}"),
+    };
+    let before = ids(client.request(list()).await);
+
+    let daily = Schedule::Daily { hour: 3, minute: 0 };
+    let add = ClientRequest::AddTask(spec("true", &[], server.path("dev.log"), daily));
+    let id = match client.request(in_dev(Some("s3cret"), add)).await {
+        ServerResponse::Added { id, .. } => id,
+        other => panic!("unexpected {other:?}"),
+    };
+    assert!(ids(client.request(in_dev(Some("s3cret"), list())).await).contains(&id));
+    assert_eq!(ids(client.request(list()).await), before);
+    assert!(server.path("environments/dev/tasks.json").exists());
+
+    match client
+        .request(in_dev(Some("s3cret"), ClientRequest::GetContext))
+        .await
+    {
+        ServerResponse::Context(ctx) => {
+            assert_eq!(ctx.environment, "dev");
+            assert_eq!(ctx.vars.get("region").map(String::as_str), Some("eu"));
+        }
+        other => panic!("unexpected {other:?}"),
+    }
+
+    assert!(matches!(
+        client.request(in_dev(Some("wrong"), list())).await,
+        ServerResponse::Error(SchedulerError::Unauthorized { .. })
+    ));
+    assert!(matches!(
+        client.request(in_dev(None, list())).await,
+        ServerResponse::Error(SchedulerError::Unauthorized { .. })
+    ));
+    let unknown = ClientRequest::InEnvironment {
+        environment: "qa".to_string(),
+        token: None,
+        request: Box::new(list()),
+    };
+    assert!(matches!(
+        client.request(unknown).await,
+        ServerResponse::Error(SchedulerError::NotFound { .. })
+    ));
+    let nested = in_dev(Some("s3cret"), in_dev(Some("s3cret"), list()));
+    assert!(matches!(
+        client.request(nested).await,
+        ServerResponse::Error(SchedulerError::InvalidRequest { .. })
+    ));
+}
+
+#[tokio::test]
+async fn default_environment_requires_its_own_token() {
+    let server =
+        TestServer::with_config("token = \"top\"\n\n[environments.dev]\ntoken = \"s3cret\"\n")
+            .await;
+    let mut client = server.client().await;
+    let wrap = |environment: &str, token: &str| ClientRequest::InEnvironment {
+        environment: environment.to_string(),
+        token: Some(token.to_string()),
+        request: Box::new(ClientRequest::ListTasks),
+    };
+    // 沒包裝、權杖錯誤或拿別的環境的權杖都拒絕
+    for req in [
+        ClientRequest::ListTasks,
+        wrap(DEFAULT_ENVIRONMENT, "wrong"),
+        wrap(DEFAULT_ENVIRONMENT, "s3cret"),
+        wrap("dev", "top"),
+    ] {
+        match client.request(req).await {
+            ServerResponse::Error(SchedulerError::Unauthorized { .. }) => {}
+            other => panic!("unexpected {other:?}"),
+        }
+    }
+    for req in [wrap(DEFAULT_ENVIRONMENT, "top"), wrap("dev", "s3cret")] {
+        match client.request(req).await {
+            ServerResponse::Tasks(_) => {}
+            other => panic!("unexpected {other:?}"),
+        }
+    }
+}
+
+#[tokio::test]
+async fn update_context_requires_a_token_and_is_saved() {
+    let vars = ClientRequest::UpdateContext {
+        environment: None,
+        set: [("region".to_string(), "eu".to_string())].into(),
+        unset: Vec::new(),
+    };
+    let open = TestServer::start().await;
+    assert!(matches!(
+        open.client().await.request(vars.clone()).await,
+        ServerResponse::Error(SchedulerError::Unauthorized { .. })
+    ));
+
+    let server = TestServer::with_config("token = \"top\"\n").await;
+    let wrapped = ClientRequest::InEnvironment {
+        environment: DEFAULT_ENVIRONMENT.to_string(),
+        token: Some("top".to_string()),
+        request: Box::new(vars),
+    };
+    match server.client().await.request(wrapped).await {
+        ServerResponse::Context(ctx) => assert_eq!(ctx.vars["region"], "eu"),
+        other => panic!("unexpected {other:?}"),
+    }
+    // 重啟後仍套用
+    let saved = std::fs::read_to_string(server.path("context.json")).unwrap();
+    assert!(saved.contains("\"region\": \"eu\""), "{saved}");
+}
+
+#[tokio::test]
+async fn templated_sandbox_paths_are_checked_after_rendering() {
+    let server = TestServer::with_config("[context.vars]\ndir = \"../../etc\"\n").await;
+    let mut events = server.subscribe().await;
+    let mut task = spec("true", &[], server.path("s.log"), once_in(100));
+    task.sandbox = Some(SandboxProfile {
+        writable_paths: vec!["/tmp/{{vars.dir}}".into()],
+        network: false,
+    });
+    let id = server.add(task).await;
+    let ev = events
+        .wait_for(|k| matches!(k, EventKind::RunSkipped { task_id, .. } if *task_id == id))
+        .await;
+    let EventKind::RunSkipped { reason, .. } = ev.kind else {
+        unreachable!()
+    };
+    assert!(reason.contains("after rendering"), "{reason}");
+}
+
+/// 以原始 HTTP 呼叫觸發網址，回傳狀態碼
+async fn post_hook(addr: std::net::SocketAddr, id: u64, token: &str, body: &str) -> u16 {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+    let req = format!(
+        "POST /hooks/{id} HTTP/1.1\r\nHost: ci\r\nAuthorization: Bearer {token}\r\nContent-Length: {}\r\n\r\n{body}",
+        body.len()
+    );
+    stream.write_all(req.as_bytes()).await.unwrap();
+    let mut resp = String::new();
+    stream.read_to_string(&mut resp).await.unwrap();
+    resp.split(' ').nth(1).unwrap().parse().unwrap()
+}
+
+#[tokio::test]
+async fn trigger_hooks_run_a_task_with_parameters() {
+    let server = TestServer::with_config("[trigger_hooks]\nbind = \"127.0.0.1:0\"\n").await;
+    let hook_addr = server.hook_addr.expect("trigger hook address");
+    let mut events = server.subscribe().await;
+    let daily = Schedule::Daily { hour: 3, minute: 0 };
+    let out = server.path("hook.log");
+    let task = server
+        .add(spec("sh", &["-c", "echo sha=$GIT_SHA"], out.clone(), daily))
+        .await;
+
+    let mut client = server.client().await;
+    let bad = ClientRequest::CreateTriggerHook {
+        task_id: task,
+        label: None,
+        params: vec!["git-sha".to_string()],
+    };
+    assert!(matches!(
+        client.request(bad).await,
+        ServerResponse::Error(SchedulerError::InvalidRequest { .. })
+    ));
+    // 會改變執行內容的環境變數不能當參數
+    let reserved = ClientRequest::CreateTriggerHook {
+        task_id: task,
+        label: None,
+        params: vec!["LD_PRELOAD".to_string()],
+    };
+    assert!(matches!(
+        client.request(reserved).await,
+        ServerResponse::Error(SchedulerError::Unauthorized { .. })
+    ));
+    let create = ClientRequest::CreateTriggerHook {
+        task_id: task,
+        label: Some("ci".to_string()),
+        params: vec!["GIT_SHA".to_string()],
+    };
+    let (hook, token) = match client.request(create).await {
+        ServerResponse::TriggerHookCreated { hook, token } => (hook, token),
+        other => panic!("unexpected {other:?}"),
+    };
+    assert_eq!(hook.task_id, task);
+    // 伺服器只存 token 的雜湊
+    let stored = std::fs::read_to_string(server.path("trigger_hooks.json")).unwrap();
+    assert!(!stored.contains(&token));
+
+    assert_eq!(post_hook(hook_addr, hook.id, "wrong", "").await, 401);
+    assert_eq!(
+        post_hook(hook_addr, hook.id, &token, r#"{"OTHER": "x"}"#).await,
+        400
+    );
+    // 參數值不能帶模板，否則會展開成伺服器變數
+    assert_eq!(
+        post_hook(
+            hook_addr,
+            hook.id,
+            &token,
+            r#"{"GIT_SHA": "{{vars.SECRET}}"}"#
+        )
+        .await,
+        400
+    );
+    assert_eq!(
+        post_hook(
+            hook_addr,
+            hook.id,
+            &token,
+            r#"{"GIT_SHA": "abc123", "metadata": {"pipeline": 42}}"#
+        )
+        .await,
+        202
+    );
+    events.run_finished(task).await;
+    let output = std::fs::read_to_string(&out).unwrap();
+    assert!(output.contains("sha=abc123"), "{output}");
+    let history = client.history(task).await;
+    assert_eq!(
+        history[0]
+            .result
+            .metadata
+            .get("pipeline")
+            .map(String::as_str),
+        Some("42")
+    );
+
+    match client.request(ClientRequest::ListTriggerHooks).await {
+        ServerResponse::TriggerHooks(list) => {
+            assert_eq!(list.len(), 1);
+            assert!(list[0].last_used.is_some());
+        }
+        other => panic!("unexpected {other:?}"),
+    }
+    assert!(matches!(
+        client
+            .request(ClientRequest::RevokeTriggerHook { id: hook.id })
+            .await,
+        ServerResponse::TriggerHookRevoked { .. }
+    ));
+    assert_eq!(post_hook(hook_addr, hook.id, &token, "").await, 401);
+    // 撤銷後不重用編號
+    let again = ClientRequest::CreateTriggerHook {
+        task_id: task,
+        label: None,
+        params: Vec::new(),
+    };
+    match client.request(again).await {
+        ServerResponse::TriggerHookCreated { hook: next, .. } => assert!(next.id > hook.id),
+        other => panic!("unexpected {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn trigger_hooks_require_an_endpoint() {
+    // 沒有設定 [trigger_hooks]，或在其他環境中：沒有端點收得到，不發 token
+    let server = TestServer::with_config("[environments.dev]\n").await;
+    let daily = Schedule::Daily { hour: 3, minute: 0 };
+    let task = server
+        .add(spec("true", &[], server.path("a.log"), daily))
+        .await;
+    let create = ClientRequest::CreateTriggerHook {
+        task_id: task,
+        label: None,
+        params: Vec::new(),
+    };
+    let mut client = server.client().await;
+    assert!(matches!(
+        client.request(create.clone()).await,
+        ServerResponse::Error(SchedulerError::InvalidRequest { .. })
+    ));
+    let in_dev = ClientRequest::InEnvironment {
+        environment: "dev".to_string(),
+        token: None,
+        request: Box::new(create),
+    };
+    assert!(matches!(
+        client.request(in_dev).await,
+        ServerResponse::Error(SchedulerError::InvalidRequest { .. })
+    ));
+}
+
+#[tokio::test]
+async fn throttle_applies_only_to_triggered_runs() {
+    let server = TestServer::with_config("[trigger_hooks]\nbind = \"127.0.0.1:0\"\n").await;
+    let hook_addr = server.hook_addr.expect("trigger hook address");
+    let mut events = server.subscribe().await;
+    let mut throttled = spec("true", &[], server.path("t.log"), once_in(100));
+    throttled.throttle = Some(Throttle {
+        min_interval_secs: Some(3600),
+        max_per_hour: None,
+    });
+    let id = server.add(throttled).await;
+    events.run_finished(id).await;
+
+    // 定時與手動的 run 不受節流，也不計入
+    let mut client = server.client().await;
+    let run_now = ClientRequest::RunNow {
+        id,
+        follow: false,
+        metadata: Default::default(),
+    };
+    assert!(matches!(
+        client.request(run_now).await,
+        ServerResponse::Triggered { .. }
+    ));
+    events.run_finished(id).await;
+
+    let create = ClientRequest::CreateTriggerHook {
+        task_id: id,
+        label: None,
+        params: Vec::new(),
+    };
+    let (hook, token) = match client.request(create).await {
+        ServerResponse::TriggerHookCreated { hook, token } => (hook, token),
+        other => panic!("unexpected {other:?}"),
+    };
+    assert_eq!(post_hook(hook_addr, hook.id, &token, "").await, 202);
+    events.run_finished(id).await;
+    assert_eq!(post_hook(hook_addr, hook.id, &token, "").await, 429);
+}
+
+#[tokio::test]
+async fn run_metadata_is_kept_with_the_run() {
+    let server = TestServer::start().await;
+    let mut events = server.subscribe().await;
+    let daily = Schedule::Daily { hour: 3, minute: 0 };
+    let id = server
+        .add(spec("true", &[], server.path("meta.log"), daily))
+        .await;
+    let mut client = server.client().await;
+
+    let bad = ClientRequest::RunNow {
+        id,
+        follow: false,
+        metadata: [(String::new(), "x".to_string())].into(),
+    };
+    assert!(matches!(
+        client.request(bad).await,
+        ServerResponse::Error(SchedulerError::InvalidRequest { .. })
+    ));
+
+    let metadata: BTreeMap<String, String> = [
+        ("ticket".to_string(), "OPS-12".to_string()),
+        ("commit".to_string(), "3f2c1d0".to_string()),
+    ]
+    .into();
+    client
+        .request(ClientRequest::RunNow {
+            id,
+            follow: false,
+            metadata: metadata.clone(),
+        })
+        .await;
+    let started = events
+        .wait_for(|k| matches!(k, EventKind::RunStarted { task_id, .. } if *task_id == id))
+        .await;
+    match started.kind {
+        EventKind::RunStarted { metadata: got, .. } => assert_eq!(got, metadata),
+        other => panic!("unexpected {other:?}"),
+    }
+    match events.run_finished(id).await.kind {
+        EventKind::RunFinished { metadata: got, .. } => assert_eq!(got, metadata),
+        other => panic!("unexpected {other:?}"),
+    }
+    let history = client.history(id).await;
+    assert_eq!(history[0].result.metadata, metadata);
+}
+
+#[tokio::test]
+async fn persisted_events_are_compacted_and_continue_after_restart() {
+    // 事件檔由背景執行緒附加，超過 buffer 兩倍時改寫；重啟後 seq 接續
+    let dir = std::env::temp_dir().join(format!("scheduler-it-events-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let log = dir.join("events.jsonl");
+    let config = format!(
+        "data_path = {:?}\n[events]\nbuffer = 3\npath = {:?}\n",
+        dir.join("tasks.json").display().to_string(),
+        log.display().to_string(),
+    );
+    let seqs = || -> Vec<u64> {
+        std::fs::read_to_string(&log)
+            .unwrap_or_default()
+            .lines()
+            .map(|l| {
+                serde_json::from_str::<scheduler_core::Event>(l)
+                    .unwrap()
+                    .seq
+            })
+            .collect()
+    };
+
+    let first = TestServer::with_config(&config).await;
+    let mut events = first.subscribe().await;
+    let daily = Schedule::Daily { hour: 3, minute: 0 };
+    let mut last = 0;
+    for i in 0..8 {
+        let out = first.path(format!("{i}.log"));
+        let id = first.add(spec("true", &[], out, daily.clone())).await;
+        last = events
+            .wait_for(|k| matches!(k, EventKind::TaskAdded { task_id } if *task_id == id))
+            .await
+            .seq;
+    }
+    timeout(WAIT, async {
+        while seqs().last() != Some(&last) {
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+    })
+    .await
+    .expect("events not written");
+    let written = seqs();
+    assert!(written.len() <= 6, "{written:?}");
+    assert!(written.windows(2).all(|w| w[1] == w[0] + 1), "{written:?}");
+    drop(events);
+    drop(first);
+
+    let second = TestServer::with_config(&config).await;
+    let mut events = second.subscribe().await;
+    let id = second
+        .add(spec("true", &[], second.path("next.log"), daily))
+        .await;
+    let ev = events
+        .wait_for(|k| matches!(k, EventKind::TaskAdded { task_id } if *task_id == id))
+        .await;
+    assert!(ev.seq > last, "{} <= {last}", ev.seq);
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,2659 @@
+#![cfg(unix)]
+
+mod support;
+
+use scheduler_core::{
+    Approval, ChangeKind, ChaosRule, CheckStatus, CircuitBreaker, CleanupAction, ClientRequest,
+    Event, EventKind, FieldChange, Healthcheck, MaintenanceKind, ManifestTask, OutputEncoding,
+    OutputPerms, OutputStream, OutputsFrom, Owner, Priority, RunOutcome, RunStatus, Schedule,
+    SchedulerError, ServerResponse, StopSignal, SuccessCriteria, TaskSpec, TimeWindow,
+    WindowPolicy,
+};
+use support::{now, once_in, spec, FakeHttp, TestServer, WAIT};
+
+#[tokio::test]
+async fn once_task_runs_and_records_history() {
+    let server = TestServer::start().await;
+    let mut events = server.subscribe().await;
+    let out = server.path("hello.log");
+    let id = server
+        .add(spec("echo", &["hello"], out.clone(), once_in(200)))
+        .await;
+
+    let ev = events.run_finished(id).await;
+    match ev.kind {
+        EventKind::RunFinished {
+            status_code,
+            outcome,
+            ..
+        } => {
+            assert_eq!(status_code, 0);
+            assert_eq!(outcome, RunOutcome::Exited);
+        }
+        other => panic!("unexpected {other:?}"),
+    }
+    assert!(std::fs::read_to_string(&out).unwrap().contains("hello"));
+
+    let mut client = server.client().await;
+    match client
+        .request(ClientRequest::GetHistory { id, limit: 10 })
+        .await
+    {
+        ServerResponse::History(list) => {
+            assert_eq!(list.len(), 1);
+            assert_eq!(list[0].result.status_code, 0);
+        }
+        other => panic!("unexpected {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn dependent_receives_upstream_outputs() {
+    let server = TestServer::start().await;
+    let mut events = server.subscribe().await;
+
+    let mut up = spec(
+        "sh",
+        &["-c", "echo artifact=/data/x.tar"],
+        server.path("up.log"),
+        once_in(200),
+    );
+    up.outputs = Some(OutputsFrom::LastLine);
+    let up_id = server.add(up).await;
+
+    let down_out = server.path("down.log");
+    let down = spec(
+        "echo",
+        &["got", "{{upstream.artifact}}"],
+        down_out.clone(),
+        Schedule::After {
+            task_id: up_id,
+            delay_secs: 0,
+            expires_after_secs: None,
+        },
+    );
+    let down_id = server.add(down).await;
+
+    events.run_finished(down_id).await;
+    assert!(std::fs::read_to_string(&down_out)
+        .unwrap()
+        .contains("got /data/x.tar"));
+}
+
+#[tokio::test]
+async fn date_templates_expand_at_run_time() {
+    let server = TestServer::start().await;
+    let mut events = server.subscribe().await;
+    let out = server.path("dates.log");
+    let before = now();
+    let id = server
+        .add(spec(
+            "echo",
+            &["{{today-1d:%Y%m%d}}", "{{now+1M:%Y-%m}}", "{{today}}"],
+            out.clone(),
+            once_in(200),
+        ))
+        .await;
+    events.run_finished(id).await;
+    let after = now();
+
+    // 執行時剛好跨過午夜時，接受前後兩天的任一結果
+    let expect = |t: chrono::DateTime<chrono::FixedOffset>| {
+        let yesterday = t.checked_sub_days(chrono::Days::new(1)).unwrap();
+        let next_month = t.checked_add_months(chrono::Months::new(1)).unwrap();
+        format!(
+            "{} {} {}",
+            yesterday.format("%Y%m%d"),
+            next_month.format("%Y-%m"),
+            t.format("%Y-%m-%d")
+        )
+    };
+    let text = std::fs::read_to_string(&out).unwrap();
+    assert!(
+        text.contains(&expect(before)) || text.contains(&expect(after)),
+        "{text}"
+    );
+
+    let mut client = server.client().await;
+    for bad in ["{{today-1}}", "{{now+1y}}", "{{today:}}", "{{today:%Q}}"] {
+        let s = spec("echo", &[bad], server.path("bad.log"), once_in(60_000));
+        match client.request(ClientRequest::AddTask(s)).await {
+            ServerResponse::Error(_) => {}
+            other => panic!("{bad} accepted: {other:?}"),
+        }
+    }
+}
+
+#[tokio::test]
+async fn shared_lock_serializes_runs() {
+    let server = TestServer::start().await;
+    let mut events = server.subscribe().await;
+
+    let mut ids = Vec::new();
+    for i in 0..2 {
+        let mut s = spec(
+            "sh",
+            &["-c", "date +%s%N; sleep 0.3; date +%s%N"],
+            server.path(format!("{i}.log")),
+            once_in(200),
+        );
+        s.locks = vec!["db".to_string()];
+        ids.push(server.add(s).await);
+    }
+
+    // 哪個先拿到鎖不一定，結束事件可能以任意順序到達
+    events.runs_finished(&ids).await;
+
+    // 兩次執行的時間區間不重疊
+    let spans: Vec<(u128, u128)> = (0..2)
+        .map(|i| {
+            let text = std::fs::read_to_string(server.path(format!("{i}.log"))).unwrap();
+            let nums: Vec<u128> = text.lines().filter_map(|l| l.trim().parse().ok()).collect();
+            (nums[0], nums[1])
+        })
+        .collect();
+    assert!(spans[0].1 <= spans[1].0 || spans[1].1 <= spans[0].0);
+}
+
+#[tokio::test]
+async fn mock_executor_applies_matching_rule() {
+    let server = TestServer::with_config(
+        r#"
+[mock]
+enabled = true
+[[mock.rules]]
+pattern = "^deploy "
+duration_ms = 50
+exit_code = 3
+stdout = "version=1.2.3"
+"#,
+    )
+    .await;
+    let mut events = server.subscribe().await;
+    let mut s = spec(
+        "deploy",
+        &["--prod"],
+        server.path("deploy.log"),
+        once_in(200),
+    );
+    s.outputs = Some(OutputsFrom::LastLine);
+    let id = server.add(s).await;
+
+    let ev = events.run_finished(id).await;
+    assert!(matches!(
+        ev.kind,
+        EventKind::RunFinished { status_code: 3, .. }
+    ));
+    let mut client = server.client().await;
+    match client
+        .request(ClientRequest::GetHistory { id, limit: 1 })
+        .await
+    {
+        ServerResponse::History(list) => {
+            assert_eq!(list[0].result.outputs.get("version").unwrap(), "1.2.3");
+        }
+        other => panic!("unexpected {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn run_metrics_are_written_to_textfile() {
+    let server = TestServer::with_config("[metrics_export]\ntextfile_dir = \"prom\"\n").await;
+    let mut events = server.subscribe().await;
+    let mut labeled = spec("true", &[], server.path("a.log"), once_in(100));
+    labeled
+        .metric_labels
+        .insert("team".to_string(), "payments".to_string());
+    let id = server.add(labeled).await;
+    events.run_finished(id).await;
+
+    // 匯出在事件之後非同步進行
+    let prom = server.path(format!("prom/scheduler_task_{id}.prom"));
+    let mut text = String::new();
+    for _ in 0..50 {
+        if let Ok(t) = std::fs::read_to_string(&prom) {
+            text = t;
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    assert!(text.contains(&format!("scheduler_run_success{{task_id=\"{id}\"")));
+    assert!(text.contains("team=\"payments\""), "{text}");
+    assert!(text.contains("scheduler_run_exit_code"));
+
+    // 內建標籤不可覆寫
+    let mut reserved = spec("true", &[], server.path("b.log"), once_in(100));
+    reserved
+        .metric_labels
+        .insert("namespace".to_string(), "x".to_string());
+    match server
+        .client()
+        .await
+        .request(ClientRequest::AddTask(reserved))
+        .await
+    {
+        ServerResponse::Error(SchedulerError::InvalidRequest { msg }) => {
+            assert!(msg.contains("reserved"), "{msg}");
+        }
+        other => panic!("unexpected {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn metric_labels_reach_the_pushgateway() {
+    let mut gateway = FakeHttp::start(&[]).await;
+    let url = gateway.url.clone();
+
+    let server = TestServer::with_config(&format!(
+        "[metrics_export]\npushgateway_url = {url:?}\njob = \"it\"\n"
+    ))
+    .await;
+    let mut events = server.subscribe().await;
+    let mut labeled = spec("true", &[], server.path("a.log"), once_in(100));
+    labeled.metric_labels = [("service", "billing"), ("team", "payments")]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    let id = server.add(labeled).await;
+    events.run_finished(id).await;
+
+    let group = format!("PUT /metrics/job/it/task_id/{id} ");
+    let body = loop {
+        let (path, body) = gateway.next().await;
+        if path.starts_with(&group) {
+            break body;
+        }
+    };
+    let success = body
+        .lines()
+        .find(|l| l.starts_with("scheduler_run_success{"))
+        .unwrap_or_else(|| panic!("{body}"));
+    assert!(success.contains("service=\"billing\""), "{success}");
+    assert!(success.contains("team=\"payments\""), "{success}");
+}
+
+#[tokio::test]
+async fn healthcheck_pings_follow_each_run() {
+    // 第一個 ping 回 500，要重試
+    let mut pings = FakeHttp::start(&[500]).await;
+    let url = pings.url.clone();
+    let server = TestServer::start().await;
+    let mut events = server.subscribe().await;
+
+    let mut ok = spec("true", &[], server.path("ok.log"), once_in(100));
+    ok.healthcheck = Some(Healthcheck {
+        url: Some(format!("{url}/ping/ok")),
+        ..Default::default()
+    });
+    let id = server.add(ok).await;
+    events.run_finished(id).await;
+    assert!(pings.next().await.0.starts_with("GET /ping/ok/start "));
+    assert!(pings.next().await.0.starts_with("GET /ping/ok/start "));
+    // 結束的 ping 在開始的之後
+    assert!(pings.next().await.0.starts_with("GET /ping/ok "));
+
+    let mut failing = spec("false", &[], server.path("bad.log"), once_in(100));
+    failing.healthcheck = Some(Healthcheck {
+        failure: Some(format!("{url}/ping/bad/fail")),
+        ..Default::default()
+    });
+    let id = server.add(failing).await;
+    events.run_finished(id).await;
+    assert!(pings.next().await.0.starts_with("GET /ping/bad/fail "));
+
+    let mut invalid = spec("true", &[], server.path("c.log"), once_in(100));
+    invalid.healthcheck = Some(Healthcheck {
+        url: Some("ftp://example.com/ping".to_string()),
+        ..Default::default()
+    });
+    match server
+        .client()
+        .await
+        .request(ClientRequest::AddTask(invalid))
+        .await
+    {
+        ServerResponse::Error(SchedulerError::InvalidRequest { msg }) => {
+            assert!(msg.contains("http(s)"), "{msg}");
+        }
+        other => panic!("unexpected {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn run_output_is_uploaded_to_s3_with_retry() {
+    // 第一次上傳回 503，要退避後重試
+    let mut s3 = FakeHttp::start(&[503]).await;
+    let server = TestServer::with_config(&format!(
+        "[s3]\nbucket = \"it\"\nendpoint = {:?}\nkey = \"{{namespace}}/{{task_id}}/{{run_id}}\"\n\
+         access_key = \"AKID\"\nsecret_key = \"secret\"\nmax_attempts = 3\n",
+        s3.url
+    ))
+    .await;
+    let mut events = server.subscribe().await;
+    let id = server
+        .add(spec(
+            "echo",
+            &["uploaded"],
+            server.path("a.log"),
+            once_in(100),
+        ))
+        .await;
+    let run_id = match events.run_finished(id).await.kind {
+        EventKind::RunFinished { run_id, .. } => run_id,
+        other => panic!("unexpected {other:?}"),
+    };
+
+    let put = format!("PUT /it/default/{id}/{run_id}.log ");
+    for _ in 0..2 {
+        let (line, body) = s3.next().await;
+        assert!(line.starts_with(&put), "{line}");
+        assert!(body.contains(&format!("task {id} exit 0")), "{body}");
+        assert!(body.contains("uploaded\n"), "{body}");
+    }
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn long_runs_do_not_hold_a_thread_each() {
+    let server = TestServer::start().await;
+    let threads = || {
+        std::fs::read_dir(format!("/proc/{}/task", server.pid()))
+            .unwrap()
+            .count()
+    };
+    let mut events = server.subscribe().await;
+    let before = threads();
+    let mut ids = Vec::new();
+    for i in 0..32 {
+        let out = server.path(format!("{i}.log"));
+        ids.push(server.add(spec("sleep", &["5"], out, once_in(100))).await);
+    }
+    for _ in &ids {
+        events
+            .wait_for(|k| matches!(k, EventKind::RunStarted { .. }))
+            .await;
+    }
+    let during = threads();
+    assert!(
+        during < before + ids.len() / 2,
+        "{before} threads before, {during} with {} runs",
+        ids.len()
+    );
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn runs_record_cpu_time_and_peak_rss() {
+    let server = TestServer::start().await;
+    let mut events = server.subscribe().await;
+    let busy = spec(
+        "sh",
+        &["-c", "i=0; while [ $i -lt 200000 ]; do i=$((i+1)); done"],
+        server.path("busy.log"),
+        once_in(100),
+    );
+    let busy = server.add(busy).await;
+    let mut slow = spec("sleep", &["30"], server.path("slow.log"), once_in(100));
+    slow.timeout_secs = Some(1);
+    let slow = server.add(slow).await;
+    events.runs_finished(&[busy, slow]).await;
+
+    let mut client = server.client().await;
+    let usage = client.history(busy).await[0].result.usage.expect("usage");
+    assert!(usage.max_rss_kb > 0, "{usage:?}");
+    assert!(usage.cpu_user_ms + usage.cpu_system_ms > 0, "{usage:?}");
+
+    // 逾時被終止的也記錄
+    let r = client.history(slow).await[0].result.clone();
+    assert_eq!(r.outcome, RunOutcome::TimedOut);
+    assert!(r.usage.is_some_and(|u| u.max_rss_kb > 0), "{r:?}");
+}
+
+#[tokio::test]
+async fn clean_env_tasks_do_not_inherit_server_environment() {
+    let server = TestServer::start().await;
+    let mut events = server.subscribe().await;
+    // 伺服器由 cargo test 啟動，繼承了 CARGO_* 等變數
+    let inherit = server
+        .add(spec("env", &[], server.path("inherit.log"), once_in(100)))
+        .await;
+    let mut clean = spec("env", &[], server.path("clean.log"), once_in(100));
+    clean.clean_env = true;
+    clean.env.insert("GREETING".to_string(), "hi".to_string());
+    let clean = server.add(clean).await;
+    events.runs_finished(&[inherit, clean]).await;
+
+    let inherited = std::fs::read_to_string(server.path("inherit.log")).unwrap();
+    assert!(inherited.contains("CARGO_"), "{inherited}");
+    let env = std::fs::read_to_string(server.path("clean.log")).unwrap();
+    assert!(!env.contains("CARGO_"), "{env}");
+    assert!(env.contains("PATH="), "{env}");
+    assert!(env.contains("GREETING=hi"), "{env}");
+}
+
+#[tokio::test]
+async fn output_files_get_configured_permissions() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let server = TestServer::with_config("[defaults]\numask = \"027\"\n").await;
+    let mut events = server.subscribe().await;
+    let plain = server
+        .add(spec(
+            "true",
+            &[],
+            server.path("a/b/plain.log"),
+            once_in(100),
+        ))
+        .await;
+    let mut private = spec("true", &[], server.path("c/private.log"), once_in(100));
+    private.output_perms = Some(OutputPerms {
+        mode: Some("0600".to_string()),
+        ..Default::default()
+    });
+    let private = server.add(private).await;
+    events.runs_finished(&[plain, private]).await;
+
+    let mode = |p: &str| {
+        std::fs::metadata(server.path(p))
+            .unwrap()
+            .permissions()
+            .mode()
+            & 0o7777
+    };
+    assert_eq!(mode("a/b/plain.log"), 0o640);
+    assert_eq!(mode("a/b"), 0o750);
+    assert_eq!(mode("a"), 0o750);
+    assert_eq!(mode("c/private.log"), 0o600);
+    assert_eq!(mode("c"), 0o700);
+
+    let mut client = server.client().await;
+    for perms in [
+        OutputPerms {
+            mode: Some("0999".to_string()),
+            ..Default::default()
+        },
+        OutputPerms {
+            user: Some("no-such-user-xyz".to_string()),
+            ..Default::default()
+        },
+    ] {
+        let mut bad = spec("true", &[], server.path("bad.log"), once_in(60_000));
+        bad.output_perms = Some(perms);
+        assert!(matches!(
+            client.request(ClientRequest::AddTask(bad)).await,
+            ServerResponse::Error(SchedulerError::InvalidRequest { .. })
+        ));
+    }
+}
+
+#[tokio::test]
+async fn git_sync_adds_manifest_tasks() {
+    let repo = std::env::temp_dir().join(format!("scheduler-it-git-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&repo);
+    std::fs::create_dir_all(&repo).unwrap();
+    std::fs::write(
+        repo.join("tasks.toml"),
+        r#"
+[[tasks]]
+key = "nightly"
+cmd = "true"
+args = []
+output_path = "nightly.log"
+append = true
+schedule = { Daily = { hour = 3, minute = 0 } }
+"#,
+    )
+    .unwrap();
+    let git = |args: &[&str]| {
+        let ok = std::process::Command::new("git")
+            .args(["-c", "user.name=t", "-c", "user.email=t@example.com"])
+            .args(args)
+            .current_dir(&repo)
+            .output()
+            .expect("run git")
+            .status
+            .success();
+        assert!(ok, "git {args:?}");
+    };
+    git(&["init", "-q", "-b", "main"]);
+    git(&["add", "tasks.toml"]);
+    git(&["commit", "-q", "-m", "tasks"]);
+
+    let server =
+        TestServer::with_config(&format!("[git_sync]\nrepo = {:?}\n", repo.display())).await;
+    let mut client = server.client().await;
+    let req = ClientRequest::ListTasks;
+    let mut synced = None;
+    for _ in 0..50 {
+        if let ServerResponse::Tasks(list) = client.request(req.clone()).await {
+            synced = list.into_iter().find(|t| t.spec.namespace != "system");
+        }
+        if synced.is_some() {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    let task = synced.expect("manifest task added");
+    assert_eq!(task.spec.source.as_deref(), Some("git-sync:nightly"));
+
+    // DiffManifest 以同一套比對規則試算，不套用
+    let mut nightly = task.spec.clone();
+    nightly.source = None;
+    let manifest = |key: &str, spec: &TaskSpec| ManifestTask {
+        key: key.to_string(),
+        spec: spec.clone(),
+    };
+    let diff = |tasks| ClientRequest::DiffManifest { tasks };
+    match client
+        .request(diff(vec![manifest("nightly", &nightly)]))
+        .await
+    {
+        ServerResponse::ManifestDiff(changes) => assert!(changes.is_empty(), "{changes:?}"),
+        other => panic!("unexpected {other:?}"),
+    }
+    let mut moved = nightly.clone();
+    moved.schedule = Schedule::Daily { hour: 4, minute: 0 };
+    let tasks = vec![manifest("nightly", &moved), manifest("weekly", &nightly)];
+    match client.request(diff(tasks)).await {
+        ServerResponse::ManifestDiff(changes) => {
+            assert_eq!(changes.len(), 2);
+            assert_eq!(changes[0].change, ChangeKind::Update);
+            assert_eq!(changes[0].task_id, Some(task.id));
+            assert_eq!(
+                changes[0].fields,
+                [FieldChange {
+                    field: "schedule.Daily.hour".to_string(),
+                    old: Some("3".to_string()),
+                    new: Some("4".to_string()),
+                }]
+            );
+            assert_eq!(changes[1].change, ChangeKind::Add);
+            assert_eq!(changes[1].key, "weekly");
+            assert!(changes[1].fields.iter().any(|f| f.field == "cmd"));
+        }
+        other => panic!("unexpected {other:?}"),
+    }
+    match client.request(diff(Vec::new())).await {
+        ServerResponse::ManifestDiff(changes) => {
+            assert_eq!(changes.len(), 1);
+            assert_eq!(changes[0].change, ChangeKind::Remove);
+        }
+        other => panic!("unexpected {other:?}"),
+    }
+    let tasks = vec![manifest("nightly", &nightly), manifest("nightly", &moved)];
+    assert!(matches!(
+        client.request(diff(tasks)).await,
+        ServerResponse::Error(SchedulerError::InvalidRequest { .. })
+    ));
+    // 試算後伺服器上的任務不變
+    match client.request(req).await {
+        ServerResponse::Tasks(list) => {
+            let user: Vec<_> = list
+                .iter()
+                .filter(|t| t.spec.namespace != "system")
+                .collect();
+            assert_eq!(user.len(), 1);
+            assert!(matches!(
+                user[0].spec.schedule,
+                Schedule::Daily { hour: 3, .. }
+            ));
+        }
+        other => panic!("unexpected {other:?}"),
+    }
+    let _ = std::fs::remove_dir_all(&repo);
+}
+
+#[tokio::test]
+async fn verify_detects_tampered_output() {
+    let server = TestServer::start().await;
+    let mut events = server.subscribe().await;
+    let out = server.path("v.log");
+    let id = server
+        .add(spec("echo", &["data"], out.clone(), once_in(100)))
+        .await;
+    events.run_finished(id).await;
+
+    let mut client = server.client().await;
+    let verify = ClientRequest::VerifyOutputs { id: Some(id) };
+    match client.request(verify.clone()).await {
+        ServerResponse::Verified(checks) => {
+            assert!(!checks.is_empty());
+            assert!(checks.iter().all(|c| c.status == CheckStatus::Ok));
+        }
+        other => panic!("unexpected {other:?}"),
+    }
+
+    let mut text = std::fs::read(&out).unwrap();
+    let last = text.len() - 2;
+    text[last] ^= 1;
+    std::fs::write(&out, text).unwrap();
+    match client.request(verify).await {
+        ServerResponse::Verified(checks) => {
+            assert!(checks.iter().any(|c| c.status == CheckStatus::HashMismatch));
+        }
+        other => panic!("unexpected {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn verify_accepts_a_shorter_rewrite() {
+    let server = TestServer::start().await;
+    let mut events = server.subscribe().await;
+    let input = server.path("input.txt");
+    std::fs::write(&input, "a much longer first line of output\n").unwrap();
+    let cat = format!("cat {}", input.display());
+    let id = server
+        .add(spec(
+            "sh",
+            &["-c", &cat],
+            server.path("short.log"),
+            Schedule::Daily { hour: 3, minute: 0 },
+        ))
+        .await;
+
+    let mut client = server.client().await;
+    for text in ["a much longer first line of output\n", "short\n"] {
+        std::fs::write(&input, text).unwrap();
+        client
+            .request(ClientRequest::RunNow {
+                id,
+                follow: false,
+                metadata: Default::default(),
+            })
+            .await;
+        events.run_finished(id).await;
+    }
+    // 沒有 append 的輸出檔每次重寫，不留前一次較長輸出的尾巴
+    let log = std::fs::read_to_string(server.path("short.log")).unwrap();
+    assert!(!log.contains("longer"), "{log}");
+    match client
+        .request(ClientRequest::VerifyOutputs { id: Some(id) })
+        .await
+    {
+        ServerResponse::Verified(checks) => {
+            assert!(!checks.is_empty());
+            assert!(
+                checks.iter().all(|c| c.status == CheckStatus::Ok),
+                "{checks:?}"
+            );
+        }
+        other => panic!("unexpected {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn breaker_opens_after_failures_and_resets() {
+    let server = TestServer::start().await;
+    let mut events = server.subscribe().await;
+    let mut s = spec("false", &[], server.path("a.log"), once_in(100));
+    s.breaker = Some(CircuitBreaker {
+        failures: 1,
+        cooldown_secs: None,
+    });
+    let id = server.add(s).await;
+    events
+        .wait_for(
+            |k| matches!(k, EventKind::BreakerOpened { task_id, failures: 1 } if *task_id == id),
+        )
+        .await;
+
+    let mut client = server.client().await;
+    let req = ClientRequest::ListTasks;
+    let ServerResponse::Tasks(list) = client.request(req).await else {
+        panic!("expected task list");
+    };
+    let t = list.iter().find(|t| t.id == id).expect("task listed");
+    assert!(t.breaker_open_since.is_some());
+
+    match client.request(ClientRequest::ResetBreaker { id }).await {
+        ServerResponse::BreakerReset { was_open, .. } => assert!(was_open),
+        other => panic!("unexpected {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn dependent_expires_when_upstream_is_slow() {
+    let server = TestServer::start().await;
+    let mut events = server.subscribe().await;
+    let up_id = server
+        .add(spec("sleep", &["2"], server.path("up.log"), once_in(100)))
+        .await;
+    let down_out = server.path("down.log");
+    let down_id = server
+        .add(spec(
+            "true",
+            &[],
+            down_out.clone(),
+            Schedule::After {
+                task_id: up_id,
+                delay_secs: 0,
+                expires_after_secs: Some(1),
+            },
+        ))
+        .await;
+
+    events
+        .wait_for(|k| matches!(k, EventKind::RunSkipped { task_id, .. } if *task_id == down_id))
+        .await;
+    events.run_finished(up_id).await;
+    tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+    assert!(!down_out.exists(), "expired dependent must not run");
+}
+
+#[tokio::test]
+async fn approval_gate_pauses_chain_until_approved() {
+    let server = TestServer::start().await;
+    let mut events = server.subscribe().await;
+    let up_id = server
+        .add(spec("true", &[], server.path("up.log"), once_in(100)))
+        .await;
+    let mut gate = spec(
+        "echo",
+        &["deploy"],
+        server.path("deploy.log"),
+        Schedule::After {
+            task_id: up_id,
+            delay_secs: 0,
+            expires_after_secs: None,
+        },
+    );
+    gate.approval = Some(Approval { timeout_secs: None });
+    let gate_id = server.add(gate).await;
+
+    let ev = events
+        .wait_for(
+            |k| matches!(k, EventKind::ApprovalRequested { task_id, .. } if *task_id == gate_id),
+        )
+        .await;
+    let EventKind::ApprovalRequested { run_id, .. } = ev.kind else {
+        unreachable!()
+    };
+    assert!(!server.path("deploy.log").exists());
+
+    let mut client = server.client().await;
+    match client.request(ClientRequest::ListApprovals).await {
+        ServerResponse::Approvals(list) => assert_eq!(list[0].run_id, run_id),
+        other => panic!("unexpected {other:?}"),
+    }
+    let req = ClientRequest::Approve {
+        run_id,
+        approve: true,
+    };
+    assert!(matches!(
+        client.request(req).await,
+        ServerResponse::Decided { approved: true, .. }
+    ));
+
+    let ev = events.run_finished(gate_id).await;
+    assert!(matches!(ev.kind, EventKind::RunFinished { run_id: r, .. } if r == run_id));
+}
+
+#[tokio::test]
+async fn follow_receives_only_its_own_run() {
+    // 同任務先觸發的 run 先核准、先開始，不能把 follower 取走
+    let server = TestServer::start().await;
+    let mut events = server.subscribe().await;
+    let daily = Schedule::Daily { hour: 3, minute: 0 };
+    let mut gated = spec("true", &[], server.path("a.log"), daily);
+    gated.approval = Some(Approval { timeout_secs: None });
+    let id = server.add(gated).await;
+    async fn requested(events: &mut support::Events, id: u64) -> u64 {
+        let ev = events
+            .wait_for(
+                |k| matches!(k, EventKind::ApprovalRequested { task_id, .. } if *task_id == id),
+            )
+            .await;
+        let EventKind::ApprovalRequested { run_id, .. } = ev.kind else {
+            unreachable!()
+        };
+        run_id
+    }
+
+    let mut client = server.client().await;
+    client
+        .request(ClientRequest::RunNow {
+            id,
+            follow: false,
+            metadata: Default::default(),
+        })
+        .await;
+    let first = requested(&mut events, id).await;
+    let mut follower = server.client().await;
+    follower
+        .send(&ClientRequest::RunNow {
+            id,
+            follow: true,
+            metadata: Default::default(),
+        })
+        .await;
+    let second = requested(&mut events, id).await;
+    assert_ne!(first, second);
+
+    for run_id in [first, second] {
+        let req = ClientRequest::Approve {
+            run_id,
+            approve: true,
+        };
+        assert!(matches!(
+            client.request(req).await,
+            ServerResponse::Decided { approved: true, .. }
+        ));
+        let ev = events.run_finished(id).await;
+        assert!(matches!(ev.kind, EventKind::RunFinished { run_id: r, .. } if r == run_id));
+    }
+    match follower.recv().await.expect("connection closed") {
+        ServerResponse::RunDone { run_id, .. } => assert_eq!(run_id, second),
+        other => panic!("unexpected: {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn run_outside_allowed_window_is_skipped() {
+    let server = TestServer::start().await;
+    let mut events = server.subscribe().await;
+    // 兩小時後才開始的一小時時段，現在一定在窗外
+    let at = |h: i64| {
+        (now() + chrono::Duration::hours(h))
+            .format("%H:%M")
+            .to_string()
+    };
+    let mut s = spec("true", &[], server.path("a.log"), once_in(100));
+    s.allowed_window = Some(TimeWindow {
+        start: at(2),
+        end: at(3),
+        outside: WindowPolicy::Skip,
+    });
+    let id = server.add(s).await;
+
+    let ev = events
+        .wait_for(|k| matches!(k, EventKind::RunSkipped { task_id, .. } if *task_id == id))
+        .await;
+    let EventKind::RunSkipped { reason, .. } = ev.kind else {
+        unreachable!()
+    };
+    assert!(reason.contains("outside allowed window"), "{reason}");
+    assert!(!server.path("a.log").exists());
+}
+
+#[tokio::test]
+async fn delayed_dependent_is_listed_and_cancellable() {
+    let server = TestServer::start().await;
+    let mut events = server.subscribe().await;
+    let up_id = server
+        .add(spec("true", &[], server.path("up.log"), once_in(100)))
+        .await;
+    let down_out = server.path("down.log");
+    let down_id = server
+        .add(spec(
+            "true",
+            &[],
+            down_out.clone(),
+            Schedule::After {
+                task_id: up_id,
+                delay_secs: 60,
+                expires_after_secs: None,
+            },
+        ))
+        .await;
+    events.run_finished(up_id).await;
+
+    let mut client = server.client().await;
+    let req = ClientRequest::ListTasks;
+    let mut chained = Vec::new();
+    for _ in 0..50 {
+        let ServerResponse::Tasks(list) = client.request(req.clone()).await else {
+            panic!("expected task list");
+        };
+        let t = list.iter().find(|t| t.id == down_id).expect("task listed");
+        if !t.chained.is_empty() {
+            assert_eq!(t.next_run, t.chained.first().copied());
+            chained = t.chained.clone();
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    assert_eq!(chained.len(), 1);
+
+    match client
+        .request(ClientRequest::CancelChained { id: down_id })
+        .await
+    {
+        ServerResponse::CancelledChained { count, .. } => assert_eq!(count, 1),
+        other => panic!("unexpected {other:?}"),
+    }
+    assert!(!down_out.exists());
+}
+
+#[tokio::test]
+async fn failure_notifications_carry_the_owner() {
+    // 送不出去的 webhook：通知會留在 outbox 供檢查
+    let server =
+        TestServer::with_config("[[notify.webhooks]]\nurl = \"http://127.0.0.1:1/\"\n").await;
+    let mut events = server.subscribe().await;
+    let mut s = spec("false", &[], server.path("f.log"), once_in(200));
+    s.owner = Some(Owner {
+        email: Some("oncall@example.com".to_string()),
+        team: Some("data".to_string()),
+    });
+    let id = server.add(s).await;
+    events.run_finished(id).await;
+
+    let mut client = server.client().await;
+    let list = tokio::time::timeout(WAIT, async {
+        loop {
+            match client
+                .request(ClientRequest::ListOutbox { dead_only: false })
+                .await
+            {
+                ServerResponse::Outbox(list) if !list.is_empty() => break list,
+                ServerResponse::Outbox(_) => {
+                    tokio::time::sleep(std::time::Duration::from_millis(50)).await
+                }
+                other => panic!("unexpected: {other:?}"),
+            }
+        }
+    })
+    .await
+    .expect("no notification was queued");
+    let owner = list[0].owner.as_ref().expect("owner missing");
+    assert_eq!(owner.to_string(), "data <oncall@example.com>");
+}
+
+#[tokio::test]
+async fn outbox_journal_survives_restart() {
+    // 送不出去的通知進 dead letter；outbox 是附加的日誌，重啟後重播回來
+    let dir = std::env::temp_dir().join(format!("scheduler-it-outbox-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let outbox = dir.join("outbox.json");
+    let config = format!(
+        "data_path = {:?}\n[notify]\noutbox_path = {:?}\nmax_attempts = 1\n\
+         [[notify.webhooks]]\nurl = \"http://127.0.0.1:1/\"\n",
+        dir.join("tasks.json").display().to_string(),
+        outbox.display().to_string(),
+    );
+    async fn dead_letters(client: &mut support::Client) -> Vec<u64> {
+        match client
+            .request(ClientRequest::ListOutbox { dead_only: true })
+            .await
+        {
+            ServerResponse::Outbox(list) => list.iter().map(|n| n.id).collect(),
+            other => panic!("unexpected: {other:?}"),
+        }
+    }
+
+    let first = TestServer::with_config(&config).await;
+    let mut events = first.subscribe().await;
+    let id = first
+        .add(spec("false", &[], first.path("f.log"), once_in(100)))
+        .await;
+    events.run_finished(id).await;
+    let mut client = first.client().await;
+    let dead = tokio::time::timeout(WAIT, async {
+        loop {
+            let dead = dead_letters(&mut client).await;
+            if !dead.is_empty() {
+                break dead;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        }
+    })
+    .await
+    .expect("notification was not dead-lettered");
+    // 每次變動只附加一行，不改寫整份檔案
+    let journal = std::fs::read_to_string(&outbox).unwrap();
+    assert!(journal.lines().count() > 1, "{journal}");
+    drop(client);
+    drop(events);
+    drop(first);
+
+    let second = TestServer::with_config(&config).await;
+    let mut client = second.client().await;
+    assert_eq!(dead_letters(&mut client).await, dead);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn outbox_keeps_notifying_after_event_seq_goes_back() {
+    // 事件不寫檔、高水位檔也遺失：重啟後 seq 從頭起算，outbox 的 cursor 要跟著拉回
+    let dir = std::env::temp_dir().join(format!("scheduler-it-rewind-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let seq = dir.join("events.seq.json");
+    let config = format!(
+        "data_path = {:?}\n[events]\npersist = false\nseq_path = {:?}\n\
+         [notify]\noutbox_path = {:?}\nmax_attempts = 1\n\
+         [[notify.webhooks]]\nurl = \"http://127.0.0.1:1/\"\n",
+        dir.join("tasks.json").display().to_string(),
+        seq.display().to_string(),
+        dir.join("outbox.json").display().to_string(),
+    );
+    async fn queued_for(client: &mut support::Client, task: u64) {
+        tokio::time::timeout(WAIT, async {
+            loop {
+                match client
+                    .request(ClientRequest::ListOutbox { dead_only: false })
+                    .await
+                {
+                    ServerResponse::Outbox(list)
+                        if list.iter().any(|n| n.event.kind.task_id() == Some(task)) =>
+                    {
+                        return
+                    }
+                    ServerResponse::Outbox(_) => {
+                        tokio::time::sleep(std::time::Duration::from_millis(50)).await
+                    }
+                    other => panic!("unexpected: {other:?}"),
+                }
+            }
+        })
+        .await
+        .unwrap_or_else(|_| panic!("no notification for task {task}"));
+    }
+
+    let first = TestServer::with_config(&config).await;
+    let mut events = first.subscribe().await;
+    // 讓 cursor 走得比第二次啟動的事件遠
+    for i in 0..4 {
+        let out = first.path(format!("{i}.log"));
+        let id = first.add(spec("false", &[], out, once_in(100))).await;
+        events.run_finished(id).await;
+        queued_for(&mut first.client().await, id).await;
+    }
+    drop(events);
+    drop(first);
+
+    std::fs::remove_file(&seq).unwrap();
+    let second = TestServer::with_config(&config).await;
+    let mut events = second.subscribe().await;
+    let id = second
+        .add(spec("false", &[], second.path("f.log"), once_in(100)))
+        .await;
+    events.run_finished(id).await;
+    queued_for(&mut second.client().await, id).await;
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn low_priority_failures_wait_for_the_digest() {
+    let server = TestServer::with_config(
+        "[[notify.webhooks]]\nurl = \"http://127.0.0.1:1/\"\non = [\"failure\", \"digest\"]\n\
+         min_priority = \"high\"\n[digest]\nevery = \"weekly\"\n",
+    )
+    .await;
+    let mut events = server.subscribe().await;
+    let mut low = spec("false", &[], server.path("low.log"), once_in(100));
+    low.priority = Priority::Low;
+    let low = server.add(low).await;
+    events.run_finished(low).await;
+    let mut high = spec("false", &[], server.path("high.log"), once_in(100));
+    high.priority = Priority::High;
+    let high = server.add(high).await;
+    events.run_finished(high).await;
+
+    // 事件依序轉成通知：高優先的到了，低優先的就不會再來
+    let mut client = server.client().await;
+    let list = tokio::time::timeout(WAIT, async {
+        loop {
+            match client
+                .request(ClientRequest::ListOutbox { dead_only: false })
+                .await
+            {
+                ServerResponse::Outbox(list) if !list.is_empty() => break list,
+                ServerResponse::Outbox(_) => {
+                    tokio::time::sleep(std::time::Duration::from_millis(50)).await
+                }
+                other => panic!("unexpected: {other:?}"),
+            }
+        }
+    })
+    .await
+    .expect("no notification was queued");
+    assert_eq!(list.len(), 1);
+    assert_eq!(list[0].event.kind.task_id(), Some(high));
+
+    // 兩者都計入累積中的彙總
+    let failures = tokio::time::timeout(WAIT, async {
+        loop {
+            let text = std::fs::read_to_string(server.path("digest.json")).unwrap_or_default();
+            let book: serde_json::Value = serde_json::from_str(&text).unwrap_or_default();
+            let ids: Vec<u64> = book["failures"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|e| e["task_id"].as_u64())
+                .collect();
+            if ids.len() == 2 {
+                break ids;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        }
+    })
+    .await
+    .expect("failures were not collected for the digest");
+    assert_eq!(failures, [low, high]);
+}
+
+#[tokio::test]
+async fn run_queries_filter_by_time_and_status() {
+    let server = TestServer::start().await;
+    let mut events = server.subscribe().await;
+    let before = now();
+    let ok = server
+        .add(spec("true", &[], server.path("ok.log"), once_in(100)))
+        .await;
+    let bad = server
+        .add(spec("false", &[], server.path("bad.log"), once_in(100)))
+        .await;
+    events.runs_finished(&[ok, bad]).await;
+
+    let mut client = server.client().await;
+    let query = |since, status| ClientRequest::QueryRuns {
+        since,
+        until: None,
+        status,
+        limit: 10,
+    };
+    match client
+        .request(query(Some(before), Some(RunStatus::Failed)))
+        .await
+    {
+        ServerResponse::Runs(report) => {
+            assert_eq!(report.total, 1);
+            assert_eq!(report.runs[0].task_id, bad);
+            assert_eq!(report.tasks.len(), 1);
+            assert_eq!((report.tasks[0].task_id, report.tasks[0].failed), (bad, 1));
+        }
+        other => panic!("unexpected: {other:?}"),
+    }
+    match client.request(query(Some(before), None)).await {
+        ServerResponse::Runs(report) => {
+            assert_eq!(report.total, 2);
+            let ids: Vec<u64> = report.tasks.iter().map(|t| t.task_id).collect();
+            assert_eq!(ids, [ok, bad]);
+        }
+        other => panic!("unexpected: {other:?}"),
+    }
+    let later = now() + chrono::Duration::hours(1);
+    match client.request(query(Some(later), None)).await {
+        ServerResponse::Runs(report) => assert_eq!(report.total, 0),
+        other => panic!("unexpected: {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn named_schedule_changes_apply_to_every_task() {
+    let server = TestServer::start().await;
+    let mut client = server.client().await;
+    let named = Schedule::Named {
+        name: "nightly".to_string(),
+    };
+    let missing = spec("true", &[], server.path("m.log"), named.clone());
+    match client.request(ClientRequest::AddTask(missing)).await {
+        ServerResponse::Error(SchedulerError::InvalidSchedule { .. }) => {}
+        other => panic!("unexpected: {other:?}"),
+    }
+
+    let set = |schedule| ClientRequest::SetSchedule {
+        name: "nightly".to_string(),
+        schedule,
+    };
+    match client
+        .request(set(Schedule::Daily { hour: 3, minute: 0 }))
+        .await
+    {
+        ServerResponse::ScheduleSet { tasks: 0, .. } => {}
+        other => panic!("unexpected: {other:?}"),
+    }
+    let mut events = server.subscribe().await;
+    let a = server
+        .add(spec("true", &[], server.path("a.log"), named.clone()))
+        .await;
+    let b = server
+        .add(spec("true", &[], server.path("b.log"), named))
+        .await;
+    match client.request(ClientRequest::ListSchedules).await {
+        ServerResponse::Schedules(list) => {
+            assert_eq!(list.len(), 1);
+            assert_eq!(list[0].tasks, [a, b]);
+        }
+        other => panic!("unexpected: {other:?}"),
+    }
+    let remove = ClientRequest::RemoveSchedule {
+        name: "nightly".to_string(),
+    };
+    match client.request(remove.clone()).await {
+        ServerResponse::Error(SchedulerError::InvalidRequest { .. }) => {}
+        other => panic!("unexpected: {other:?}"),
+    }
+
+    // 改成馬上執行一次：兩個任務都依新排程執行
+    match client.request(set(once_in(200))).await {
+        ServerResponse::ScheduleSet { tasks: 2, .. } => {}
+        other => panic!("unexpected: {other:?}"),
+    }
+    events.runs_finished(&[a, b]).await;
+}
+
+#[tokio::test]
+async fn running_task_reports_progress() {
+    let server = TestServer::start().await;
+    let mut events = server.subscribe().await;
+    let script = r#"echo "40 downloading" > "$SCHEDULER_PROGRESS"; sleep 1; echo 100 > "$SCHEDULER_PROGRESS""#;
+    let id = server
+        .add(spec(
+            "sh",
+            &["-c", script],
+            server.path("p.log"),
+            once_in(100),
+        ))
+        .await;
+
+    let ev = events
+        .wait_for(|k| matches!(k, EventKind::RunProgress { task_id, .. } if *task_id == id))
+        .await;
+    let EventKind::RunProgress { progress, .. } = ev.kind else {
+        unreachable!()
+    };
+    assert_eq!(progress.percent, Some(40));
+    assert_eq!(progress.phase.as_deref(), Some("downloading"));
+
+    let mut client = server.client().await;
+    let list = ClientRequest::ListTasks;
+    match client.request(list.clone()).await {
+        ServerResponse::Tasks(tasks) => {
+            let p = tasks[0].progress.as_ref().expect("progress while running");
+            assert_eq!(p.percent, Some(40));
+        }
+        other => panic!("unexpected: {other:?}"),
+    }
+
+    // 只回報百分比時沿用先前的階段
+    let ev = events
+        .wait_for(|k| matches!(k, EventKind::RunProgress { task_id, .. } if *task_id == id))
+        .await;
+    let EventKind::RunProgress { progress, .. } = ev.kind else {
+        unreachable!()
+    };
+    assert_eq!(progress.percent, Some(100));
+    assert_eq!(progress.phase.as_deref(), Some("downloading"));
+    events.run_finished(id).await;
+    match client.request(list).await {
+        ServerResponse::Tasks(tasks) => assert!(tasks[0].progress.is_none()),
+        other => panic!("unexpected: {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn oversized_progress_lines_are_truncated() {
+    let server = TestServer::start().await;
+    let mut events = server.subscribe().await;
+    // 一行 1 MiB：讀取端只留開頭，階段截短，之後的回報照常解析
+    let script = r#"{ printf '30 '; head -c 1048576 /dev/zero | tr '\0' x; echo; echo "60 next"; } > "$SCHEDULER_PROGRESS""#;
+    let id = server
+        .add(spec(
+            "sh",
+            &["-c", script],
+            server.path("p.log"),
+            once_in(100),
+        ))
+        .await;
+
+    let mut seen = Vec::new();
+    while seen.len() < 2 {
+        let ev = events
+            .wait_for(|k| matches!(k, EventKind::RunProgress { task_id, .. } if *task_id == id))
+            .await;
+        let EventKind::RunProgress { progress, .. } = ev.kind else {
+            unreachable!()
+        };
+        seen.push(progress);
+    }
+    assert_eq!(seen[0].percent, Some(30));
+    let phase = seen[0].phase.as_deref().unwrap();
+    assert!(
+        phase.len() <= 120 && phase.starts_with("xxx"),
+        "{}",
+        phase.len()
+    );
+    assert_eq!(seen[1].percent, Some(60));
+    assert_eq!(seen[1].phase.as_deref(), Some("next"));
+    events.run_finished(id).await;
+}
+
+#[tokio::test]
+async fn run_now_follow_streams_output() {
+    let server = TestServer::start().await;
+    let script = "echo one; echo two >&2; sleep 0.2; echo three; exit 3";
+    let id = server
+        .add(spec(
+            "sh",
+            &["-c", script],
+            server.path("f.log"),
+            Schedule::Daily { hour: 3, minute: 0 },
+        ))
+        .await;
+
+    let mut client = server.client().await;
+    client
+        .send(&ClientRequest::RunNow {
+            id,
+            follow: true,
+            metadata: Default::default(),
+        })
+        .await;
+    let (mut stdout, mut stderr) = (String::new(), String::new());
+    loop {
+        match client.recv().await.expect("connection closed") {
+            ServerResponse::RunOutput { stream, data, .. } => match stream {
+                OutputStream::Stdout => stdout.push_str(&data),
+                _ => stderr.push_str(&data),
+            },
+            ServerResponse::RunDone {
+                task_id,
+                status_code,
+                outcome,
+                ..
+            } => {
+                assert_eq!((task_id, status_code), (id, 3));
+                assert_eq!(outcome, RunOutcome::Exited);
+                break;
+            }
+            other => panic!("unexpected: {other:?}"),
+        }
+    }
+    assert_eq!(stdout, "one\nthree\n");
+    assert_eq!(stderr, "two\n");
+    // 輸出檔照常寫入
+    let log = std::fs::read_to_string(server.path("f.log")).unwrap();
+    assert!(log.contains("three"));
+
+    // 同一條連線之後仍可送出一般請求；未 follow 時立即回覆
+    let mut events = server.subscribe().await;
+    match client
+        .request(ClientRequest::RunNow {
+            id,
+            follow: false,
+            metadata: Default::default(),
+        })
+        .await
+    {
+        ServerResponse::Triggered { id: got } => assert_eq!(got, id),
+        other => panic!("unexpected: {other:?}"),
+    }
+    events.run_finished(id).await;
+    match client
+        .request(ClientRequest::RunNow {
+            id: id + 100,
+            follow: true,
+            metadata: Default::default(),
+        })
+        .await
+    {
+        ServerResponse::Error(SchedulerError::NotFound { .. }) => {}
+        other => panic!("unexpected: {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn success_criteria_reclassify_runs() {
+    let server = TestServer::start().await;
+    let mut events = server.subscribe().await;
+    let mut benign = spec("sh", &["-c", "exit 24"], server.path("a.log"), once_in(200));
+    benign.success = Some(SuccessCriteria {
+        exit_codes: vec![24],
+        ..Default::default()
+    });
+    let benign = server.add(benign).await;
+    let mut noisy = spec(
+        "sh",
+        &["-c", "echo 'ERROR: disk full'"],
+        server.path("b.log"),
+        once_in(200),
+    );
+    noisy.success = Some(SuccessCriteria {
+        output_rejects: Some("^ERROR".to_string()),
+        ..Default::default()
+    });
+    let noisy = server.add(noisy).await;
+    events.runs_finished(&[benign, noisy]).await;
+
+    let mut client = server.client().await;
+    for (id, status, exit) in [(benign, 0, 24), (noisy, 1, 0)] {
+        match client
+            .request(ClientRequest::GetHistory { id, limit: 10 })
+            .await
+        {
+            ServerResponse::History(list) => {
+                assert_eq!(list[0].result.status_code, status);
+                assert_eq!(list[0].result.exit_code, Some(exit));
+            }
+            other => panic!("unexpected {other:?}"),
+        }
+    }
+    // 輸出檔標頭仍是程式實際的結束碼
+    let log = std::fs::read_to_string(server.path("a.log")).unwrap();
+    assert!(log.contains("exit 24"));
+
+    let mut bad = spec("true", &[], server.path("c.log"), once_in(60_000));
+    bad.success = Some(SuccessCriteria {
+        output_matches: Some("(".to_string()),
+        ..Default::default()
+    });
+    match client.request(ClientRequest::AddTask(bad)).await {
+        ServerResponse::Error(_) => {}
+        other => panic!("unexpected {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn output_encoding_controls_output_conditions() {
+    let server = TestServer::start().await;
+    let mut events = server.subscribe().await;
+    let binary = |name: &str, encoding, criteria| {
+        let mut s = spec(
+            "sh",
+            &["-c", "printf '\\377ok\\n'"],
+            server.path(name),
+            once_in(200),
+        );
+        s.output_encoding = encoding;
+        s.success = Some(criteria);
+        s
+    };
+    // raw：以位元組比對，不經解碼
+    let raw = binary(
+        "raw.log",
+        OutputEncoding::Raw,
+        SuccessCriteria {
+            output_matches: Some("(?-u)^\\xffok".to_string()),
+            ..Default::default()
+        },
+    );
+    let raw = server.add(raw).await;
+    // utf8：無法解碼時條件不成立，即使 rejects 的樣式沒有出現
+    let strict = binary(
+        "strict.log",
+        OutputEncoding::Utf8,
+        SuccessCriteria {
+            output_rejects: Some("^ERROR".to_string()),
+            ..Default::default()
+        },
+    );
+    let strict = server.add(strict).await;
+    // lossy（預設）：無法解碼的位元組換成替代字元後照常比對
+    let lossy = binary(
+        "lossy.log",
+        OutputEncoding::Lossy,
+        SuccessCriteria {
+            output_matches: Some("^\u{FFFD}ok".to_string()),
+            ..Default::default()
+        },
+    );
+    let lossy = server.add(lossy).await;
+    events.runs_finished(&[raw, strict, lossy]).await;
+
+    let mut client = server.client().await;
+    for (id, status) in [(raw, 0), (strict, 1), (lossy, 0)] {
+        match client
+            .request(ClientRequest::GetHistory { id, limit: 10 })
+            .await
+        {
+            ServerResponse::History(list) => assert_eq!(list[0].result.status_code, status),
+            other => panic!("unexpected {other:?}"),
+        }
+    }
+
+    // raw 的樣式以位元組編譯：utf8 任務不接受
+    let mut bad = spec("true", &[], server.path("bad.log"), once_in(60_000));
+    bad.output_encoding = OutputEncoding::Utf8;
+    bad.success = Some(SuccessCriteria {
+        output_matches: Some("(?-u)\\xff".to_string()),
+        ..Default::default()
+    });
+    match client.request(ClientRequest::AddTask(bad)).await {
+        ServerResponse::Error(_) => {}
+        other => panic!("unexpected {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn binary_stdout_is_written_raw() {
+    let server = TestServer::with_config("[artifacts]\ndir = \"artifacts\"\n").await;
+    let mut events = server.subscribe().await;
+    let out = server.path("blob.bin");
+    let mut blob = spec(
+        "sh",
+        &["-c", "printf 'PK\\000\\001\\002'; echo oops >&2"],
+        out.clone(),
+        once_in(200),
+    );
+    blob.outputs = Some(OutputsFrom::LastLine);
+    let blob = server.add(blob).await;
+    events.run_finished(blob).await;
+
+    // 輸出檔只有原始位元組，stderr 另存
+    assert_eq!(std::fs::read(&out).unwrap(), b"PK\0\x01\x02");
+    let stderr = std::fs::read_to_string(server.path("blob.bin.stderr")).unwrap();
+    assert!(stderr.contains("oops"));
+
+    let mut client = server.client().await;
+    match client
+        .request(ClientRequest::GetHistory { id: blob, limit: 1 })
+        .await
+    {
+        ServerResponse::History(list) => {
+            let r = &list[0].result;
+            assert!(r.binary);
+            assert_eq!(r.output_size, Some(5));
+            assert!(r.outputs.is_empty());
+        }
+        other => panic!("unexpected {other:?}"),
+    }
+    match client.request(ClientRequest::GetOutput { id: blob }).await {
+        ServerResponse::Output { content, .. } => {
+            assert!(content.starts_with("[binary output: 5 bytes, sha256 "));
+        }
+        other => panic!("unexpected {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn retry_chain_reruns_only_failed_nodes() {
+    let server = TestServer::start().await;
+    let mut events = server.subscribe().await;
+    let after = |task_id| Schedule::After {
+        task_id,
+        delay_secs: 0,
+        expires_after_secs: None,
+    };
+
+    let mut up = spec(
+        "sh",
+        &["-c", "echo key=v1"],
+        server.path("up.log"),
+        once_in(200),
+    );
+    up.outputs = Some(OutputsFrom::LastLine);
+    let up = server.add(up).await;
+    let flag = server.path("flag");
+    let script = format!("test -e {} && echo got $1", flag.display());
+    let flaky = server
+        .add(spec(
+            "sh",
+            &["-c", &script, "_", "{{upstream.key}}"],
+            server.path("flaky.log"),
+            after(up),
+        ))
+        .await;
+    let tail = server
+        .add(spec(
+            "echo",
+            &["tail"],
+            server.path("tail.log"),
+            after(flaky),
+        ))
+        .await;
+    let sibling = server
+        .add(spec("echo", &["ok"], server.path("sibling.log"), after(up)))
+        .await;
+    events.runs_finished(&[up, flaky, tail, sibling]).await;
+
+    let mut client = server.client().await;
+    let up_run = client.history(up).await[0].result.run_id;
+    assert_ne!(client.history(flaky).await[0].result.status_code, 0);
+
+    std::fs::write(&flag, "").unwrap();
+    match client
+        .request(ClientRequest::RetryChain { run_id: up_run })
+        .await
+    {
+        ServerResponse::ChainRetried { tasks, .. } => assert_eq!(tasks, vec![flaky]),
+        other => panic!("unexpected {other:?}"),
+    }
+    events.runs_finished(&[flaky, tail]).await;
+    let runs = client.history(flaky).await;
+    assert_eq!(runs.len(), 2);
+    assert_eq!(runs[0].result.status_code, 0);
+    assert_eq!(runs[0].result.upstream_run, Some(up_run));
+    assert!(std::fs::read_to_string(server.path("flaky.log"))
+        .unwrap()
+        .contains("got v1"));
+    // 成功的分支與鏈頭不重跑
+    assert_eq!(client.history(sibling).await.len(), 1);
+    assert_eq!(client.history(up).await.len(), 1);
+
+    match client
+        .request(ClientRequest::RetryChain { run_id: up_run })
+        .await
+    {
+        ServerResponse::Error(SchedulerError::InvalidRequest { .. }) => {}
+        other => panic!("unexpected {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn tasks_inherit_server_defaults() {
+    let server = TestServer::with_config(
+        "[defaults]\noutput_dir = \"out\"\ntimeout_secs = 1\n\
+         env = { GREETING = \"hello\", NAME = \"default\" }\n",
+    )
+    .await;
+    let mut events = server.subscribe().await;
+
+    let mut greet = spec(
+        "sh",
+        &["-c", "echo $GREETING $NAME"],
+        "greet.log".into(),
+        once_in(200),
+    );
+    greet.env.insert("NAME".to_string(), "task".to_string());
+    let greet = server.add(greet).await;
+    // 任務自己的逾時優先於預設
+    let mut slow = spec("sleep", &["2"], server.path("slow.log"), once_in(200));
+    slow.timeout_secs = Some(5);
+    let slow = server.add(slow).await;
+    let stuck = server
+        .add(spec(
+            "sleep",
+            &["5"],
+            server.path("stuck.log"),
+            once_in(200),
+        ))
+        .await;
+    events.runs_finished(&[greet, slow, stuck]).await;
+
+    let log = std::fs::read_to_string(server.path("out/greet.log")).unwrap();
+    assert!(log.contains("hello task"));
+    let mut client = server.client().await;
+    assert_eq!(
+        client.history(slow).await[0].result.outcome,
+        RunOutcome::Exited
+    );
+    assert_eq!(
+        client.history(stuck).await[0].result.outcome,
+        RunOutcome::TimedOut
+    );
+}
+
+#[tokio::test]
+async fn concurrent_runs_leave_a_complete_task_file() {
+    // 每次 run 開始與結束都會存檔；同時結束的 run 不能把檔案寫壞
+    let server = TestServer::start().await;
+    let mut events = server.subscribe().await;
+    let mut ids = Vec::new();
+    for i in 0..16 {
+        ids.push(
+            server
+                .add(spec(
+                    "true",
+                    &[],
+                    server.path(format!("{i}.log")),
+                    once_in(200),
+                ))
+                .await,
+        );
+    }
+    events.runs_finished(&ids).await;
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+
+    let text = std::fs::read_to_string(server.path("tasks.json")).unwrap();
+    let saved: Vec<serde_json::Value> = serde_json::from_str(&text).unwrap();
+    assert_eq!(saved.len(), ids.len());
+    assert!(!server.path("tasks.json.tmp").exists());
+}
+
+#[tokio::test]
+async fn shutdown_events_are_persisted_and_seq_is_never_reused() {
+    let dir = std::env::temp_dir().join(format!("scheduler-it-seq-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let log = dir.join("events.jsonl");
+    let config = format!(
+        "data_path = {:?}\n[events]\npath = {:?}\nseq_path = {:?}\n",
+        dir.join("tasks.json").display().to_string(),
+        log.display().to_string(),
+        dir.join("events.seq.json").display().to_string(),
+    );
+
+    // 關閉時終止執行中的 run：這些事件也要寫進事件檔
+    let mut first = TestServer::with_config(&config).await;
+    let mut events = first.subscribe().await;
+    let id = first
+        .add(spec("sleep", &["30"], first.path("s.log"), once_in(100)))
+        .await;
+    events
+        .wait_for(|k| matches!(k, EventKind::RunStarted { task_id, .. } if *task_id == id))
+        .await;
+    first.terminate().await;
+    let persisted: Vec<Event> = std::fs::read_to_string(&log)
+        .unwrap()
+        .lines()
+        .map(|l| serde_json::from_str(l).unwrap())
+        .collect();
+    assert!(
+        persisted.iter().any(|e| matches!(
+            e.kind,
+            EventKind::RunFinished { task_id, outcome: RunOutcome::Cancelled, .. } if task_id == id
+        )),
+        "{persisted:?}"
+    );
+    let last = persisted.last().unwrap().seq;
+
+    // 事件檔的尾端遺失（例如異常停機）：已經發出去的 seq 也不能再配給別的事件
+    std::fs::remove_file(&log).unwrap();
+    let second = TestServer::with_config(&config).await;
+    let mut events = second.subscribe().await;
+    let id = second
+        .add(spec(
+            "true",
+            &[],
+            second.path("t.log"),
+            Schedule::Daily { hour: 3, minute: 0 },
+        ))
+        .await;
+    let ev = events
+        .wait_for(|k| matches!(k, EventKind::TaskAdded { task_id } if *task_id == id))
+        .await;
+    assert!(
+        ev.seq > last,
+        "seq {} reused (first server reached {last})",
+        ev.seq
+    );
+    drop(second);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn ids_are_not_reused_after_restart() {
+    // 兩次啟動只共用高水位檔，任務與歷史都是新的
+    let ids = std::env::temp_dir().join(format!("scheduler-it-ids-{}.json", std::process::id()));
+    let _ = std::fs::remove_file(&ids);
+    let config = format!("ids_path = {:?}\n", ids.display().to_string());
+
+    let first = TestServer::with_config(&config).await;
+    let mut events = first.subscribe().await;
+    let old = first
+        .add(spec("true", &[], first.path("a.log"), once_in(100)))
+        .await;
+    events.run_finished(old).await;
+    let old_run = first.client().await.history(old).await[0].result.run_id;
+    drop(first);
+
+    let second = TestServer::with_config(&config).await;
+    let mut events = second.subscribe().await;
+    let new = second
+        .add(spec("true", &[], second.path("b.log"), once_in(100)))
+        .await;
+    events.run_finished(new).await;
+    let new_run = second.client().await.history(new).await[0].result.run_id;
+    assert!(new > old, "task id {new} reused (first server gave {old})");
+    assert!(
+        new_run > old_run,
+        "run id {new_run} reused (first server gave {old_run})"
+    );
+    let _ = std::fs::remove_file(&ids);
+}
+
+#[tokio::test]
+async fn maintenance_pauses_and_removes_tasks() {
+    let server = TestServer::start().await;
+    let mut events = server.subscribe().await;
+    let daily = Schedule::Daily { hour: 3, minute: 0 };
+    let paused = server
+        .add(spec("true", &[], server.path("p.log"), daily.clone()))
+        .await;
+    let doomed = server
+        .add(spec("true", &[], server.path("d.log"), daily))
+        .await;
+    let mut client = server.client().await;
+    let hour = chrono::Duration::hours(1);
+
+    // 不合理的預約
+    match client
+        .request(ClientRequest::ScheduleMaintenance {
+            task_id: paused,
+            action: MaintenanceKind::Pause { until: now() },
+            at: now() + hour,
+            force: false,
+        })
+        .await
+    {
+        ServerResponse::Error(SchedulerError::InvalidRequest { .. }) => {}
+        other => panic!("unexpected {other:?}"),
+    }
+    match client
+        .request(ClientRequest::ScheduleMaintenance {
+            task_id: doomed + 100,
+            action: MaintenanceKind::Remove,
+            at: now(),
+            force: false,
+        })
+        .await
+    {
+        ServerResponse::Error(SchedulerError::NotFound { .. }) => {}
+        other => panic!("unexpected {other:?}"),
+    }
+
+    // 暫停中：執行被略過並記一筆 Skipped
+    let seq = match client
+        .request(ClientRequest::ScheduleMaintenance {
+            task_id: paused,
+            action: MaintenanceKind::Pause {
+                until: now() + hour,
+            },
+            at: now() - hour,
+            force: false,
+        })
+        .await
+    {
+        ServerResponse::MaintenanceScheduled { seq } => seq,
+        other => panic!("unexpected {other:?}"),
+    };
+    client
+        .request(ClientRequest::RunNow {
+            id: paused,
+            follow: false,
+            metadata: Default::default(),
+        })
+        .await;
+    events
+        .wait_for(|k| {
+            matches!(k, EventKind::RunSkipped { task_id, reason }
+                if *task_id == paused && reason.contains("maintenance"))
+        })
+        .await;
+    let history = client.history(paused).await;
+    assert_eq!(history.last().unwrap().result.outcome, RunOutcome::Skipped);
+
+    match client.request(ClientRequest::ListMaintenance).await {
+        ServerResponse::Maintenance(list) => {
+            assert_eq!(list.len(), 1);
+            assert_eq!((list[0].seq, list[0].task_id), (seq, paused));
+        }
+        other => panic!("unexpected {other:?}"),
+    }
+
+    // 取消暫停即恢復
+    assert!(matches!(
+        client
+            .request(ClientRequest::CancelMaintenance { seq })
+            .await,
+        ServerResponse::MaintenanceCancelled { .. }
+    ));
+    client
+        .request(ClientRequest::RunNow {
+            id: paused,
+            follow: false,
+            metadata: Default::default(),
+        })
+        .await;
+    events.run_finished(paused).await;
+
+    // 預約移除：到時由伺服器移除
+    assert!(matches!(
+        client
+            .request(ClientRequest::ScheduleMaintenance {
+                task_id: doomed,
+                action: MaintenanceKind::Remove,
+                at: now() + chrono::Duration::milliseconds(300),
+                force: false,
+            })
+            .await,
+        ServerResponse::MaintenanceScheduled { .. }
+    ));
+    events
+        .wait_for(|k| matches!(k, EventKind::TaskRemoved { task_id } if *task_id == doomed))
+        .await;
+    match client.request(ClientRequest::ListMaintenance).await {
+        ServerResponse::Maintenance(list) => assert!(list.is_empty(), "{list:?}"),
+        other => panic!("unexpected {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn chaos_rules_inject_failures() {
+    let rule = ChaosRule {
+        probability: 1.0,
+        count: Some(2),
+        delay_ms: 0,
+        exit_code: Some(3),
+    };
+    let daily = Schedule::Daily { hour: 3, minute: 0 };
+
+    // 沒有開啟 [chaos] 時拒絕
+    let server = TestServer::start().await;
+    let id = server
+        .add(spec("true", &[], server.path("c.log"), daily.clone()))
+        .await;
+    match server
+        .client()
+        .await
+        .request(ClientRequest::SetChaos {
+            task_id: id,
+            rule: Some(rule.clone()),
+        })
+        .await
+    {
+        ServerResponse::Error(SchedulerError::Unauthorized { .. }) => {}
+        other => panic!("unexpected {other:?}"),
+    }
+
+    let server = TestServer::with_config("[chaos]\nenabled = true\n").await;
+    let mut events = server.subscribe().await;
+    let id = server
+        .add(spec("true", &[], server.path("c.log"), daily))
+        .await;
+    let mut client = server.client().await;
+    assert!(matches!(
+        client
+            .request(ClientRequest::SetChaos {
+                task_id: id,
+                rule: Some(rule),
+            })
+            .await,
+        ServerResponse::ChaosSet { .. }
+    ));
+
+    // 前兩次注入失敗，用完後照常執行
+    for _ in 0..3 {
+        client
+            .request(ClientRequest::RunNow {
+                id,
+                follow: false,
+                metadata: Default::default(),
+            })
+            .await;
+        events.run_finished(id).await;
+    }
+    let codes: Vec<i32> = client
+        .history(id)
+        .await
+        .iter()
+        .map(|r| r.result.status_code)
+        .collect();
+    // 新的在前
+    assert_eq!(codes, [0, 3, 3]);
+    match client.request(ClientRequest::ListChaos).await {
+        ServerResponse::Chaos(list) => assert!(list.is_empty(), "{list:?}"),
+        other => panic!("unexpected {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn history_segments_are_indexed_and_compacted() {
+    // 兩次啟動共用任務與歷史；每筆紀錄都超過分段大小，各自封存成一個分段
+    let dir = std::env::temp_dir().join(format!("scheduler-it-history-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let config = format!(
+        "data_path = {:?}\n[history]\npath = {:?}\nsegment_bytes = 1\n",
+        dir.join("tasks.json").display().to_string(),
+        dir.join("history.jsonl").display().to_string(),
+    );
+    let segments = || {
+        let mut names: Vec<String> = std::fs::read_dir(&dir)
+            .unwrap()
+            .filter_map(|e| e.unwrap().file_name().into_string().ok())
+            .filter(|n| n.starts_with("history."))
+            .collect();
+        names.sort();
+        names
+    };
+
+    let first = TestServer::with_config(&config).await;
+    let mut events = first.subscribe().await;
+    let mut client = first.client().await;
+    let daily = Schedule::Daily { hour: 3, minute: 0 };
+    let a = first
+        .add(spec("true", &[], first.path("a.log"), daily.clone()))
+        .await;
+    let b = first
+        .add(spec("true", &[], first.path("b.log"), daily))
+        .await;
+    for id in [a, b, a, a] {
+        client
+            .request(ClientRequest::RunNow {
+                id,
+                follow: false,
+                metadata: Default::default(),
+            })
+            .await;
+        events.run_finished(id).await;
+    }
+    let runs: Vec<u64> = client
+        .history(a)
+        .await
+        .iter()
+        .map(|r| r.result.run_id)
+        .collect();
+    assert_eq!(runs.len(), 3);
+    assert!(runs.windows(2).all(|w| w[0] > w[1]), "{runs:?}");
+    assert_eq!(
+        segments(),
+        [
+            "history.000001.idx",
+            "history.000001.jsonl",
+            "history.000002.idx",
+            "history.000002.jsonl",
+            "history.000003.idx",
+            "history.000003.jsonl",
+            "history.000004.idx",
+            "history.000004.jsonl",
+        ]
+    );
+    drop(client);
+    drop(first);
+
+    // 少了索引的分段在載入時重新解析
+    std::fs::remove_file(dir.join("history.000002.idx")).unwrap();
+    let second = TestServer::with_config(&format!("{config}max_per_task = 2\n")).await;
+    let mut events = second.subscribe().await;
+    let mut client = second.client().await;
+    let again: Vec<u64> = client
+        .history(a)
+        .await
+        .iter()
+        .map(|r| r.result.run_id)
+        .collect();
+    assert_eq!(again, runs);
+    assert_eq!(client.history(b).await.len(), 1);
+
+    assert!(matches!(
+        client.request(ClientRequest::PruneHistory).await,
+        ServerResponse::Pruned { removed: 1 }
+    ));
+    let kept: Vec<u64> = client
+        .history(a)
+        .await
+        .iter()
+        .map(|r| r.result.run_id)
+        .collect();
+    assert_eq!(kept, runs[..2]);
+    assert_eq!(client.history(b).await.len(), 1);
+    // 整理後只剩新寫的分段
+    assert_eq!(
+        segments(),
+        [
+            "history.000005.idx",
+            "history.000005.jsonl",
+            "history.000006.idx",
+            "history.000006.jsonl",
+            "history.000007.idx",
+            "history.000007.jsonl",
+        ]
+    );
+
+    client
+        .request(ClientRequest::RunNow {
+            id: a,
+            follow: false,
+            metadata: Default::default(),
+        })
+        .await;
+    events.run_finished(a).await;
+    let latest = client.history(a).await;
+    assert_eq!(latest.len(), 3);
+    assert!(latest[0].result.run_id > runs[0]);
+    drop(client);
+    drop(second);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn history_recovery_skips_only_exact_duplicates() {
+    // 整理到一半停機會留下一模一樣的副本；run 編號相同但任務不同的是另一筆紀錄
+    let dir = std::env::temp_dir().join(format!("scheduler-it-history-dup-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let history = dir.join("history.jsonl");
+    let config = format!(
+        "data_path = {:?}\n[history]\npath = {:?}\n",
+        dir.join("tasks.json").display().to_string(),
+        history.display().to_string(),
+    );
+
+    let first = TestServer::with_config(&config).await;
+    let mut events = first.subscribe().await;
+    let mut client = first.client().await;
+    let daily = Schedule::Daily { hour: 3, minute: 0 };
+    let a = first
+        .add(spec("true", &[], first.path("a.log"), daily.clone()))
+        .await;
+    let b = first
+        .add(spec("true", &[], first.path("b.log"), daily))
+        .await;
+    client
+        .request(ClientRequest::RunNow {
+            id: a,
+            follow: false,
+            metadata: Default::default(),
+        })
+        .await;
+    events.run_finished(a).await;
+    drop(client);
+    drop(first);
+
+    let line = std::fs::read_to_string(&history).unwrap();
+    let line = line.trim_end();
+    let mut other: serde_json::Value = serde_json::from_str(line).unwrap();
+    other["task_id"] = b.into();
+    std::fs::write(&history, format!("{line}\n{line}\n{other}\n")).unwrap();
+
+    let second = TestServer::with_config(&config).await;
+    let mut client = second.client().await;
+    let runs = client.history(a).await;
+    assert_eq!(runs.len(), 1, "{runs:?}");
+    let copied = client.history(b).await;
+    assert_eq!(copied.len(), 1, "{copied:?}");
+    assert_eq!(copied[0].result.run_id, runs[0].result.run_id);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn freeze_defers_time_based_runs_until_unfrozen() {
+    let server = TestServer::start().await;
+    let mut events = server.subscribe().await;
+    let mut client = server.client().await;
+    let freeze = |block_triggered| ClientRequest::Freeze {
+        duration_secs: 60,
+        block_triggered,
+        reason: Some("deploy".to_string()),
+    };
+    match client.request(freeze(false)).await {
+        ServerResponse::Frozen(f) => {
+            assert!(!f.block_triggered);
+            assert!(f.until > now() + chrono::Duration::seconds(50));
+        }
+        other => panic!("unexpected {other:?}"),
+    }
+    match client.request(ClientRequest::GetServerInfo).await {
+        ServerResponse::ServerInfo(info) => {
+            let f = info.freeze.expect("freeze reported");
+            assert_eq!(f.reason.as_deref(), Some("deploy"));
+        }
+        other => panic!("unexpected {other:?}"),
+    }
+
+    let timed = server
+        .add(spec("true", &[], server.path("timed.log"), once_in(200)))
+        .await;
+    let manual = server
+        .add(spec(
+            "true",
+            &[],
+            server.path("manual.log"),
+            Schedule::Daily { hour: 3, minute: 0 },
+        ))
+        .await;
+
+    // 只凍結定時觸發：手動執行照跑，單次任務等著
+    client
+        .request(ClientRequest::RunNow {
+            id: manual,
+            follow: false,
+            metadata: Default::default(),
+        })
+        .await;
+    events.run_finished(manual).await;
+    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+    assert!(client.history(timed).await.is_empty());
+
+    // 連觸發的執行一起凍結
+    client.request(freeze(true)).await;
+    client
+        .request(ClientRequest::RunNow {
+            id: manual,
+            follow: false,
+            metadata: Default::default(),
+        })
+        .await;
+    events
+        .wait_for(|k| matches!(k, EventKind::RunSkipped { task_id, reason } if *task_id == manual && reason.contains("frozen")))
+        .await;
+
+    // 解凍後補跑延後的單次任務
+    assert!(matches!(
+        client.request(ClientRequest::Unfreeze).await,
+        ServerResponse::Unfrozen { was_frozen: true }
+    ));
+    events.run_finished(timed).await;
+    assert_eq!(client.history(timed).await.len(), 1);
+    match client.request(ClientRequest::GetServerInfo).await {
+        ServerResponse::ServerInfo(info) => assert!(info.freeze.is_none()),
+        other => panic!("unexpected {other:?}"),
+    }
+    assert!(matches!(
+        client.request(ClientRequest::Unfreeze).await,
+        ServerResponse::Unfrozen { was_frozen: false }
+    ));
+}
+
+#[tokio::test]
+async fn stop_signal_gives_tasks_a_grace_period() {
+    let server = TestServer::start().await;
+    let mut events = server.subscribe().await;
+    let cleanup =
+        |marker: &str| format!("trap 'echo cleaned > {marker}; exit 1' TERM INT; sleep 30 & wait");
+
+    // 預設先送 SIGTERM，任務可以清理後自行結束
+    let marker = server.path("term.marker");
+    let mut term = spec(
+        "sh",
+        &["-c", &cleanup(&marker.display().to_string())],
+        server.path("term.log"),
+        once_in(100),
+    );
+    term.timeout_secs = Some(1);
+    let term = server.add(term).await;
+
+    // 指定的訊號；kill 不給清理的機會
+    let int_marker = server.path("int.marker");
+    let mut int = spec(
+        "sh",
+        &["-c", &cleanup(&int_marker.display().to_string())],
+        server.path("int.log"),
+        once_in(100),
+    );
+    int.timeout_secs = Some(1);
+    int.stop_signal = Some(StopSignal::Int);
+    let int = server.add(int).await;
+    let kill_marker = server.path("kill.marker");
+    let mut kill = spec(
+        "sh",
+        &["-c", &cleanup(&kill_marker.display().to_string())],
+        server.path("kill.log"),
+        once_in(100),
+    );
+    kill.timeout_secs = Some(1);
+    kill.stop_signal = Some(StopSignal::Kill);
+    let kill = server.add(kill).await;
+
+    // 不理會訊號的任務在寬限期後被 SIGKILL
+    let mut stubborn = spec(
+        "sh",
+        &["-c", "trap '' TERM; sleep 30"],
+        server.path("stubborn.log"),
+        once_in(100),
+    );
+    stubborn.timeout_secs = Some(1);
+    stubborn.stop_grace_secs = Some(1);
+    let stubborn = server.add(stubborn).await;
+
+    let started = std::time::Instant::now();
+    events.runs_finished(&[term, int, kill, stubborn]).await;
+    assert!(started.elapsed() < std::time::Duration::from_secs(10));
+
+    assert!(std::fs::read_to_string(&marker)
+        .unwrap()
+        .contains("cleaned"));
+    assert!(int_marker.exists());
+    assert!(!kill_marker.exists());
+    let mut client = server.client().await;
+    for id in [term, int, kill, stubborn] {
+        assert_eq!(
+            client.history(id).await[0].result.outcome,
+            RunOutcome::TimedOut
+        );
+    }
+}
+
+#[tokio::test]
+async fn cleanup_actions_run_after_the_command() {
+    let server = TestServer::with_config("[cleanup]\nroots = [\"scratch\"]\n").await;
+    let mut events = server.subscribe().await;
+    let work = server.path("scratch/work");
+    let parts = server.path("scratch/parts");
+    std::fs::create_dir_all(&parts).unwrap();
+    let script = format!(
+        "mkdir -p {w}/sub && echo tmp > {w}/sub/a && \
+         echo old > {p}/old.part && touch -d '10 days ago' {p}/old.part && \
+         echo new > {p}/new.part && echo keep > {p}/keep.csv",
+        w = work.display(),
+        p = parts.display()
+    );
+    let mut s = spec("sh", &["-c", &script], server.path("job.log"), once_in(100));
+    s.cleanup = vec![
+        CleanupAction::RemoveDir { path: work.clone() },
+        CleanupAction::RemoveFiles {
+            glob: format!("{}/*.part", parts.display()),
+            older_than_days: Some(7),
+        },
+    ];
+    let id = server.add(s).await;
+    events.run_finished(id).await;
+
+    assert!(!work.exists());
+    assert!(!parts.join("old.part").exists());
+    assert!(parts.join("new.part").exists());
+    assert!(parts.join("keep.csv").exists());
+    let mut client = server.client().await;
+    let r = client.history(id).await[0].result.clone();
+    assert_eq!(r.outcome, RunOutcome::Exited);
+    let report = r.cleanup.expect("cleanup recorded");
+    // work、work/sub、work/sub/a 與 old.part
+    assert_eq!(report.removed, 4);
+    assert!(report.freed_bytes > 0);
+    assert!(report.errors.is_empty(), "{:?}", report.errors);
+
+    // 相對路徑與目錄中的萬用字元都拒絕
+    for action in [
+        CleanupAction::RemoveDir {
+            path: "tmp/work".into(),
+        },
+        CleanupAction::RemoveFiles {
+            glob: "/tmp/*/x.part".to_string(),
+            older_than_days: None,
+        },
+    ] {
+        let mut bad = spec("true", &[], server.path("bad.log"), once_in(60_000));
+        bad.cleanup = vec![action];
+        assert!(matches!(
+            client.request(ClientRequest::AddTask(bad)).await,
+            ServerResponse::Error(SchedulerError::InvalidRequest { .. })
+        ));
+    }
+    // 允許的目錄以外、或允許的目錄本身，都不能清
+    for action in [
+        CleanupAction::RemoveDir {
+            path: "/etc/cron.d".into(),
+        },
+        CleanupAction::RemoveDir {
+            path: server.path("scratch"),
+        },
+        CleanupAction::RemoveFiles {
+            glob: format!("{}/*.json", server.dir.display()),
+            older_than_days: None,
+        },
+    ] {
+        let mut bad = spec("true", &[], server.path("bad.log"), once_in(60_000));
+        bad.cleanup = vec![action];
+        assert!(matches!(
+            client.request(ClientRequest::AddTask(bad)).await,
+            ServerResponse::Error(SchedulerError::Unauthorized { .. })
+        ));
+    }
+}
+
+#[tokio::test]
+async fn config_tasks_are_loaded_and_cannot_be_removed() {
+    let server = TestServer::with_config(
+        "[[tasks]]\nkey = \"vacuum\"\ncmd = \"echo\"\nargs = [\"vacuumed\"]\n\
+         output_path = \"vacuum.log\"\nappend = false\nschedule = { Daily = { hour = 3, minute = 0 } }\n",
+    )
+    .await;
+    let mut events = server.subscribe().await;
+    let mut client = server.client().await;
+    let id = match client.request(ClientRequest::ListTasks).await {
+        ServerResponse::Tasks(tasks) => tasks
+            .iter()
+            .find(|t| t.spec.source.as_deref() == Some("config:vacuum"))
+            .map(|t| t.id)
+            .expect("config task not loaded"),
+        other => panic!("unexpected {other:?}"),
+    };
+
+    for req in [
+        ClientRequest::RemoveTask { id, force: true },
+        ClientRequest::RollbackTask {
+            id,
+            revision: 1,
+            force: true,
+        },
+        ClientRequest::ScheduleMaintenance {
+            task_id: id,
+            action: MaintenanceKind::Remove,
+            at: now(),
+            force: true,
+        },
+    ] {
+        match client.request(req).await {
+            ServerResponse::Error(SchedulerError::Unauthorized { .. }) => {}
+            other => panic!("unexpected {other:?}"),
+        }
+    }
+    let hour = chrono::Duration::hours(1);
+    match client
+        .request(ClientRequest::ScheduleMaintenance {
+            task_id: id,
+            action: MaintenanceKind::Pause {
+                until: now() + hour,
+            },
+            at: now() + hour / 2,
+            force: false,
+        })
+        .await
+    {
+        ServerResponse::MaintenanceScheduled { .. } => {}
+        other => panic!("unexpected {other:?}"),
+    }
+
+    // 照常可以手動執行
+    client
+        .request(ClientRequest::RunNow {
+            id,
+            follow: false,
+            metadata: Default::default(),
+        })
+        .await;
+    events.run_finished(id).await;
+    let log = std::fs::read_to_string(server.path("vacuum.log")).unwrap();
+    assert!(log.contains("vacuumed"), "{log}");
+}
+
+#[tokio::test]
+async fn approval_waits_count_against_the_run_queue() {
+    let config = "[queue]\nmax_pending_total = 1\npolicy = \"reject\"\n\
+                  [metrics_export]\ntextfile_dir = \"prom\"\n";
+    let server = TestServer::with_config(config).await;
+    let mut events = server.subscribe().await;
+    let mut gated = spec(
+        "true",
+        &[],
+        server.path("a.log"),
+        Schedule::Daily { hour: 3, minute: 0 },
+    );
+    gated.approval = Some(Approval { timeout_secs: None });
+    let id = server.add(gated).await;
+    let run_now = ClientRequest::RunNow {
+        id,
+        follow: false,
+        metadata: Default::default(),
+    };
+
+    // 等核准的 run 佔著佇列，第二次觸發直接被拒
+    let mut client = server.client().await;
+    assert!(matches!(
+        client.request(run_now.clone()).await,
+        ServerResponse::Triggered { .. }
+    ));
+    events
+        .wait_for(|k| matches!(k, EventKind::ApprovalRequested { task_id, .. } if *task_id == id))
+        .await;
+    assert!(matches!(
+        client.request(run_now).await,
+        ServerResponse::Error(SchedulerError::InvalidRequest { .. })
+    ));
+    match client.request(ClientRequest::GetQueueStats).await {
+        ServerResponse::QueueStats(stats) => {
+            assert_eq!(stats.pending, 1);
+            assert_eq!(stats.rejected, 1);
+        }
+        other => panic!("unexpected {other:?}"),
+    }
+
+    // 深度與拒絕次數匯出成指標
+    let prom = server.path("prom/scheduler_queue.prom");
+    let mut text = String::new();
+    for _ in 0..50 {
+        text = std::fs::read_to_string(&prom).unwrap_or_default();
+        if text.contains("scheduler_queue_rejected_total 1") {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    assert!(text.contains("scheduler_queue_rejected_total 1"), "{text}");
+    assert!(text.contains("scheduler_queue_pending 1"), "{text}");
+}
+
+#[tokio::test]
+async fn unlisted_namespaces_share_the_wildcard_quota() {
+    let config = "[quotas.\"*\"]\nmax_tasks = 2\nmax_concurrent_runs = 1\n";
+    let server = TestServer::with_config(config).await;
+    let mut events = server.subscribe().await;
+    let at = once_in(300);
+    let mut ids = Vec::new();
+    for ns in ["team-a", "team-b"] {
+        let mut s = spec(
+            "sleep",
+            &["0.5"],
+            server.path(format!("{ns}.log")),
+            at.clone(),
+        );
+        s.namespace = ns.to_string();
+        ids.push(server.add(s).await);
+    }
+
+    // 換個命名空間名稱不會多出額度
+    let mut extra = spec("true", &[], server.path("c.log"), at);
+    extra.namespace = "team-c".to_string();
+    match server
+        .client()
+        .await
+        .request(ClientRequest::AddTask(extra))
+        .await
+    {
+        ServerResponse::Error(SchedulerError::Unauthorized { msg }) => {
+            assert!(msg.contains("quota"), "{msg}")
+        }
+        other => panic!("unexpected {other:?}"),
+    }
+
+    // 同時開始的兩個 run 只有一個拿到名額
+    let ev = events
+        .wait_for(|k| matches!(k, EventKind::RunSkipped { task_id, .. } if ids.contains(task_id)))
+        .await;
+    let EventKind::RunSkipped { task_id, reason } = ev.kind else {
+        unreachable!()
+    };
+    assert!(reason.contains("concurrent"), "{reason}");
+    let other = ids.into_iter().find(|id| *id != task_id).unwrap();
+    events.run_finished(other).await;
+
+    // 今日輸出量存檔，重啟後不歸零
+    let usage = std::fs::read_to_string(server.path("quota_usage.json")).unwrap();
+    assert!(usage.contains("\"*\""), "{usage}");
+}
+
+#[tokio::test]
+async fn saturated_server_skips_low_priority_recurring_runs() {
+    use chrono::{TimeDelta, Timelike};
+    let server =
+        TestServer::with_config("[load_shed]\nmax_active = 1\nmin_priority = \"normal\"\n").await;
+    let mut events = server.subscribe().await;
+    // 佔住唯一的名額
+    let busy = server
+        .add(spec(
+            "sleep",
+            &["120"],
+            server.path("busy.log"),
+            once_in(100),
+        ))
+        .await;
+    events
+        .wait_for(|k| matches!(k, EventKind::RunStarted { task_id, .. } if *task_id == busy))
+        .await;
+
+    // 只有定時觸發會被略過：兩個任務在下一個整分鐘同時到期
+    let mut at = (now() + TimeDelta::minutes(1)).with_second(0).unwrap();
+    if at - now() < TimeDelta::seconds(3) {
+        at += TimeDelta::minutes(1);
+    }
+    let hourly = Schedule::Hourly {
+        minute: at.minute(),
+    };
+    let mut low = spec("true", &[], server.path("low.log"), hourly.clone());
+    low.priority = Priority::Low;
+    let low = server.add(low).await;
+    let mut high = spec("true", &[], server.path("high.log"), hourly);
+    high.priority = Priority::High;
+    let high = server.add(high).await;
+    tokio::time::sleep((at - now()).to_std().unwrap_or_default()).await;
+
+    let ev = events
+        .wait_for(|k| matches!(k, EventKind::RunSkipped { task_id, .. } if *task_id == low))
+        .await;
+    let EventKind::RunSkipped { reason, .. } = ev.kind else {
+        unreachable!()
+    };
+    assert!(reason.contains("saturated"), "{reason}");
+    // 高優先的照常執行
+    events.run_finished(high).await;
+
+    let mut client = server.client().await;
+    let history = client.history(low).await;
+    assert_eq!(history[0].result.outcome, RunOutcome::Skipped);
+}
+
+#[tokio::test]
+async fn watchdog_keeps_killed_runs_registered_during_the_stop_grace() {
+    let server =
+        TestServer::with_config("[watchdog]\ninterval_secs = 1\nmax_runtime_secs = 1\n").await;
+    let mut events = server.subscribe().await;
+    // 忽略 SIGTERM：要等寬限期過後的 SIGKILL 才會結束
+    let mut s = spec(
+        "sh",
+        &["-c", "trap '' TERM; sleep 30"],
+        server.path("stubborn.log"),
+        once_in(100),
+    );
+    s.stop_grace_secs = Some(4);
+    let id = server.add(s).await;
+    events
+        .wait_for(|k| matches!(k, EventKind::RunKilled { task_id, .. } if *task_id == id))
+        .await;
+
+    // 子程序還在寬限期內執行，watchdog 巡檢過幾次後仍算在執行中
+    tokio::time::sleep(std::time::Duration::from_millis(2500)).await;
+    match server
+        .client()
+        .await
+        .request(ClientRequest::PreviewRemoval { ids: vec![id] })
+        .await
+    {
+        ServerResponse::RemovalPreview(list) => assert_eq!(list[0].running, 1),
+        other => panic!("unexpected {other:?}"),
+    }
+    let finished = events.run_finished(id).await;
+    match finished.kind {
+        EventKind::RunFinished { outcome, .. } => assert_eq!(outcome, RunOutcome::TimedOut),
+        other => panic!("unexpected {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn watchdog_leaves_runs_that_are_draining_output() {
+    let server = TestServer::with_config("[watchdog]\ninterval_secs = 1\n").await;
+    let mut events = server.subscribe().await;
+    // 子程序馬上結束，留在背景的孫程序還握著 stdout 兩秒
+    let out = server.path("drain.log");
+    let id = server
+        .add(spec(
+            "sh",
+            &["-c", "echo early; (sleep 2; echo late) &"],
+            out.clone(),
+            once_in(100),
+        ))
+        .await;
+
+    let mut killed = false;
+    events
+        .wait_for(|k| {
+            killed |= matches!(k, EventKind::RunKilled { task_id, .. } if *task_id == id);
+            matches!(k, EventKind::RunFinished { task_id, .. } if *task_id == id)
+        })
+        .await;
+    assert!(!killed, "draining run was reported lost");
+    let text = std::fs::read_to_string(&out).unwrap();
+    assert!(text.contains("early") && text.contains("late"), "{text}");
+}
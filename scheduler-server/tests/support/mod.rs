@@ -0,0 +1,415 @@
+//! 端對端測試共用：在暫存目錄以隨機埠啟動 scheduler-server，並提供送請求、等事件的輔助函式
+#![allow(dead_code)] // 各測試檔只用到其中一部分
+
+use bytes::BytesMut;
+use chrono::{DateTime, FixedOffset, Local};
+use futures_util::SinkExt;
+use scheduler_core::{
+    ClientRequest, Event, EventKind, OutputEncoding, Priority, RequestFrame, ResponseFrame,
+    RunRecord, Schedule, ServerResponse, TaskSpec, DEFAULT_NAMESPACE,
+};
+use std::{
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    process::Stdio,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncWrite, BufReader},
+    net::{TcpStream, UnixStream},
+    process::{Child, Command},
+    sync::mpsc,
+    time::timeout,
+};
+use tokio_stream::StreamExt;
+use tokio_util::codec::{Framed, LengthDelimitedCodec};
+
+/// 等待伺服器啟動、回應與事件的預設上限
+pub const WAIT: Duration = Duration::from_secs(10);
+
+/// 以暫存目錄與隨機埠執行的伺服器；drop 時結束程序並刪除目錄
+pub struct TestServer {
+    child: Child,
+    pub addr: SocketAddr,
+    /// 所有 TCP 監聽位址（依 --bind 順序；第一個即 addr）
+    pub addrs: Vec<SocketAddr>,
+    /// 設定了 [websocket] 時的 WebSocket 位址
+    pub ws_addr: Option<SocketAddr>,
+    /// 設定了 [trigger_hooks] 時的觸發網址位址
+    pub hook_addr: Option<SocketAddr>,
+    pub dir: PathBuf,
+}
+
+impl TestServer {
+    /// 預設設定啟動
+    pub async fn start() -> Self {
+        Self::with_config("").await
+    }
+
+    /// 附加額外的 TOML 設定（所有相對路徑都在暫存目錄內）
+    pub async fn with_config(extra: &str) -> Self {
+        Self::with_binds(extra, &["127.0.0.1:0"]).await
+    }
+
+    /// 指定監聽位址（各自一個 --bind）；至少要有一個 TCP 位址
+    pub async fn with_binds(extra: &str, binds: &[&str]) -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        let dir = std::env::temp_dir().join(format!(
+            "scheduler-it-{}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::SeqCst)
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("config.toml"), extra).unwrap();
+
+        let mut child = Command::new(env!("CARGO_BIN_EXE_scheduler-server"))
+            .args(["--config", "config.toml"])
+            .args(binds.iter().flat_map(|b| ["--bind", b]))
+            .current_dir(&dir)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(std::fs::File::create(dir.join("server.log")).unwrap())
+            .kill_on_drop(true)
+            .spawn()
+            .expect("spawn scheduler-server");
+
+        // 從啟動訊息取得實際位址，其餘輸出持續讀掉以免管線塞滿
+        // WebSocket 與觸發網址端點（若有）比主要埠先開
+        let mut lines = BufReader::new(child.stdout.take().unwrap()).lines();
+        let mut ws_addr = None;
+        let mut hook_addr = None;
+        let mut addrs = Vec::new();
+        timeout(WAIT, async {
+            let mut pending = binds.len();
+            while let Some(line) = lines.next_line().await.unwrap() {
+                let Some(addr) = line.split("listening on ").nth(1) else {
+                    continue;
+                };
+                if line.contains("websocket") {
+                    ws_addr = Some(addr.trim().parse().unwrap());
+                    continue;
+                }
+                if line.contains("trigger hooks") {
+                    hook_addr = Some(addr.trim().parse().unwrap());
+                    continue;
+                }
+                // Unix socket 不是 SocketAddr，只計數
+                if let Ok(addr) = addr.trim().parse::<SocketAddr>() {
+                    addrs.push(addr);
+                }
+                pending -= 1;
+                if pending == 0 {
+                    return;
+                }
+            }
+            panic!("server exited before listening");
+        })
+        .await
+        .expect("server did not start in time");
+        tokio::spawn(async move { while let Ok(Some(_)) = lines.next_line().await {} });
+
+        Self {
+            child,
+            addr: addrs[0],
+            addrs,
+            ws_addr,
+            hook_addr,
+            dir,
+        }
+    }
+
+    /// 伺服器的程序編號
+    pub fn pid(&self) -> u32 {
+        self.child.id().expect("server still running")
+    }
+
+    /// 暫存目錄中的路徑
+    pub fn path(&self, rel: impl AsRef<Path>) -> PathBuf {
+        self.dir.join(rel)
+    }
+
+    pub async fn client(&self) -> Client {
+        Client::connect(self.addr).await
+    }
+
+    /// 訂閱事件（從目前之後開始）；收到第一個心跳才回傳，之後的事件不會漏掉
+    pub async fn subscribe(&self) -> Events {
+        let mut client = self.client().await;
+        client.send(&ClientRequest::Subscribe { since: None }).await;
+        match client.recv().await {
+            Some(ServerResponse::Heartbeat { .. }) => {}
+            other => panic!("expected initial heartbeat, got {other:?}"),
+        }
+        Events { client }
+    }
+
+    /// 新增任務並回傳 id；伺服器拒絕時 panic
+    pub async fn add(&self, spec: TaskSpec) -> u64 {
+        match self
+            .client()
+            .await
+            .request(ClientRequest::AddTask(spec))
+            .await
+        {
+            ServerResponse::Added { id, .. } => id,
+            other => panic!("AddTask failed: {other:?}"),
+        }
+    }
+}
+
+impl TestServer {
+    /// 送 SIGTERM 讓伺服器正常關閉並等它結束
+    #[cfg(unix)]
+    pub async fn terminate(&mut self) {
+        if let Some(pid) = self.child.id() {
+            // SAFETY: 只對自己啟動、尚未回收的子程序送訊號
+            unsafe { libc::kill(pid as libc::pid_t, libc::SIGTERM) };
+        }
+        timeout(WAIT, self.child.wait())
+            .await
+            .expect("server did not shut down in time")
+            .expect("wait for server");
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        let _ = self.child.start_kill();
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// 假的 HTTP 端點：記下收到的請求（第一行如 "PUT /a/b HTTP/1.1" 與內容）
+pub struct FakeHttp {
+    pub url: String,
+    requests: mpsc::UnboundedReceiver<(String, String)>,
+}
+
+impl FakeHttp {
+    /// statuses 依序當作前幾個請求的狀態碼，用完後一律回 200
+    pub async fn start(statuses: &[u16]) -> Self {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let mut statuses = Vec::from(statuses).into_iter();
+        let (tx, requests) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            while let Ok((mut conn, _)) = listener.accept().await {
+                let mut buf = Vec::new();
+                let mut chunk = [0u8; 4096];
+                let (head, body) = loop {
+                    let n = conn.read(&mut chunk).await.unwrap_or(0);
+                    if n == 0 {
+                        break (String::new(), String::new());
+                    }
+                    buf.extend_from_slice(&chunk[..n]);
+                    let text = String::from_utf8_lossy(&buf).into_owned();
+                    let Some((head, body)) = text.split_once("\r\n\r\n") else {
+                        continue;
+                    };
+                    let len = head
+                        .lines()
+                        .find_map(|l| {
+                            l.to_ascii_lowercase()
+                                .strip_prefix("content-length:")
+                                .map(|v| v.trim().parse::<usize>().unwrap())
+                        })
+                        .unwrap_or(0);
+                    if body.len() >= len {
+                        break (head.to_string(), body.to_string());
+                    }
+                };
+                let status = statuses.next().unwrap_or(200);
+                let _ = conn
+                    .write_all(
+                        format!(
+                            "HTTP/1.1 {status} X\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                        )
+                        .as_bytes(),
+                    )
+                    .await;
+                let line = head.lines().next().unwrap_or_default().to_string();
+                let _ = tx.send((line, body));
+            }
+        });
+        Self { url, requests }
+    }
+
+    /// 下一個請求
+    pub async fn next(&mut self) -> (String, String) {
+        timeout(WAIT, self.requests.recv())
+            .await
+            .expect("timed out waiting for an HTTP request")
+            .expect("fake endpoint stopped")
+    }
+}
+
+/// 與 scheduler-cli 相同的協定：長度前綴 + JSON
+pub struct Client {
+    framed: Framed<Box<dyn Io>, LengthDelimitedCodec>,
+}
+
+/// TCP 與 Unix socket 共用同一個 Client
+pub trait Io: AsyncRead + AsyncWrite + Send + Unpin {}
+impl<T: AsyncRead + AsyncWrite + Send + Unpin> Io for T {}
+
+impl Client {
+    pub async fn connect(addr: SocketAddr) -> Self {
+        let stream = TcpStream::connect(addr).await.expect("connect");
+        Self::over(Box::new(stream))
+    }
+
+    pub async fn connect_unix(path: impl AsRef<Path>) -> Self {
+        let stream = UnixStream::connect(path).await.expect("connect unix");
+        Self::over(Box::new(stream))
+    }
+
+    fn over(stream: Box<dyn Io>) -> Self {
+        Self {
+            framed: Framed::new(stream, LengthDelimitedCodec::new()),
+        }
+    }
+
+    pub async fn send(&mut self, req: &ClientRequest) {
+        self.send_raw(&serde_json::to_vec(req).unwrap()).await;
+    }
+
+    /// 送出任意內容的 frame（測試錯誤輸入用）
+    pub async fn send_raw(&mut self, frame: &[u8]) {
+        self.framed
+            .send(bytes::Bytes::copy_from_slice(frame))
+            .await
+            .expect("send");
+    }
+
+    /// 下一個回應；連線關閉時回傳 None
+    pub async fn recv(&mut self) -> Option<ServerResponse> {
+        let frame: BytesMut = timeout(WAIT, self.framed.next())
+            .await
+            .expect("timed out waiting for a response")?
+            .ok()?;
+        Some(serde_json::from_slice(&frame).expect("decode response"))
+    }
+
+    pub async fn request(&mut self, req: ClientRequest) -> ServerResponse {
+        self.send(&req).await;
+        self.recv().await.expect("connection closed")
+    }
+
+    /// 任務的執行歷史，新到舊
+    pub async fn history(&mut self, id: u64) -> Vec<RunRecord> {
+        match self
+            .request(ClientRequest::GetHistory { id, limit: 100 })
+            .await
+        {
+            ServerResponse::History(list) => list,
+            other => panic!("unexpected {other:?}"),
+        }
+    }
+
+    /// 以 RequestFrame 送出，不等回覆
+    pub async fn send_tagged(&mut self, id: u64, request: ClientRequest) {
+        let frame = RequestFrame { id, request };
+        self.send_raw(&serde_json::to_vec(&frame).unwrap()).await;
+    }
+
+    /// 下一個帶 id 的回覆
+    pub async fn recv_tagged(&mut self) -> ResponseFrame {
+        let frame: BytesMut = timeout(WAIT, self.framed.next())
+            .await
+            .expect("timed out waiting for a response")
+            .expect("connection closed")
+            .expect("read frame");
+        serde_json::from_slice(&frame).expect("decode response frame")
+    }
+}
+
+/// 事件訂閱
+pub struct Events {
+    client: Client,
+}
+
+impl Events {
+    /// 等到符合條件的事件；略過心跳與其他事件
+    pub async fn wait_for(&mut self, mut pred: impl FnMut(&EventKind) -> bool) -> Event {
+        timeout(WAIT, async {
+            loop {
+                match self.client.recv().await {
+                    Some(ServerResponse::Event(ev)) if pred(&ev.kind) => return ev,
+                    Some(_) => continue,
+                    None => panic!("event stream closed"),
+                }
+            }
+        })
+        .await
+        .expect("timed out waiting for event")
+    }
+
+    /// 等到某任務的一次執行結束
+    pub async fn run_finished(&mut self, task: u64) -> Event {
+        self.wait_for(|k| matches!(k, EventKind::RunFinished { task_id, .. } if *task_id == task))
+            .await
+    }
+
+    /// 等這些任務都執行完畢；同時到期的任務完成順序不一定
+    pub async fn runs_finished(&mut self, tasks: &[u64]) {
+        let mut pending = tasks.to_vec();
+        while !pending.is_empty() {
+            let ev = self
+                .wait_for(|k| matches!(k, EventKind::RunFinished { task_id, .. } if pending.contains(task_id)))
+                .await;
+            pending.retain(|id| Some(*id) != ev.kind.task_id());
+        }
+    }
+}
+
+/// 現在起 ms 毫秒後執行一次
+pub fn once_in(ms: i64) -> Schedule {
+    Schedule::Once(now() + chrono::Duration::milliseconds(ms))
+}
+
+pub fn now() -> DateTime<FixedOffset> {
+    Local::now().fixed_offset()
+}
+
+/// 最小的任務規格；其他欄位由測試自行覆寫
+pub fn spec(cmd: &str, args: &[&str], output: PathBuf, schedule: Schedule) -> TaskSpec {
+    TaskSpec {
+        name: None,
+        tags: Vec::new(),
+        namespace: DEFAULT_NAMESPACE.to_string(),
+        owner: None,
+        metric_labels: Default::default(),
+        cmd: cmd.to_string(),
+        args: args.iter().map(|a| a.to_string()).collect(),
+        env: Default::default(),
+        clean_env: false,
+        skip_cmd_check: false,
+        outputs: None,
+        output_path: output,
+        append: false,
+        output_perms: None,
+        schedule,
+        timeout_secs: None,
+        stop_signal: None,
+        stop_grace_secs: None,
+        sched: Default::default(),
+        sandbox: None,
+        locks: Vec::new(),
+        throttle: None,
+        triggers: Vec::new(),
+        healthcheck: None,
+        allowed_window: None,
+        approval: None,
+        breaker: None,
+        priority: Priority::Normal,
+        success: None,
+        output_encoding: OutputEncoding::Lossy,
+        cleanup: Vec::new(),
+        exact_start: false,
+        protected: false,
+        source: None,
+    }
+}